@@ -0,0 +1,17 @@
+-- Migration 014: client fingerprint columns on conn_track.
+--
+-- The client's identification string and the algorithms it offers in its
+-- SSH_MSG_KEXINIT are captured before the key exchange completes. HASSH is the
+-- MD5 of "kex;ciphers;macs;compression" (client to server lists) and clusters
+-- tooling even when the version string is spoofed. All three columns are NULL
+-- for connections that never got as far as sending a KEXINIT (port scans).
+
+ALTER TABLE conn_track ADD COLUMN client_version TEXT;
+ALTER TABLE conn_track ADD COLUMN hassh TEXT;
+ALTER TABLE conn_track ADD COLUMN hassh_algorithms TEXT;
+
+CREATE INDEX idx_conn_track_hassh ON conn_track(hassh);
+
+COMMENT ON COLUMN conn_track.client_version IS 'SSH identification string sent by the client';
+COMMENT ON COLUMN conn_track.hassh IS 'HASSH fingerprint (MD5 of hassh_algorithms)';
+COMMENT ON COLUMN conn_track.hassh_algorithms IS 'kex;ciphers;macs;compression offered by the client';
@@ -0,0 +1,23 @@
+-- Migration 015: terminal size of interactive sessions.
+--
+-- Clients announce their terminal with a pty-req when the channel opens and
+-- send a window-change request whenever it is resized. Bots usually request a
+-- fixed 80x24 pty (or none at all) and never resize, so resize events are a
+-- good hint that a human is at the keyboard.
+
+CREATE TABLE terminal_events (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    auth_id UUID NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    event_type VARCHAR(20) NOT NULL,
+    term TEXT,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL
+);
+
+CREATE INDEX idx_terminal_events_auth_id ON terminal_events(auth_id);
+
+COMMENT ON COLUMN terminal_events.event_type IS 'pty-req when the terminal is allocated, window-change on resize';
+COMMENT ON COLUMN terminal_events.term IS 'TERM value from the pty-req, NULL for window-change';
+COMMENT ON COLUMN terminal_events.width IS 'Terminal width in characters';
+COMMENT ON COLUMN terminal_events.height IS 'Terminal height in rows';
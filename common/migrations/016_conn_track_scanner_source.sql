@@ -0,0 +1,12 @@
+-- Migration 016: known research scanners on conn_track.
+--
+-- Shodan, Censys and similar projects connect to every SSH server on the
+-- internet. Connections from their published ranges or with a forward-confirmed
+-- reverse DNS name in one of their domains get the scanner's name here, so
+-- reports can separate research scanning from attackers. NULL for everyone else.
+
+ALTER TABLE conn_track ADD COLUMN scanner_source TEXT;
+
+CREATE INDEX idx_conn_track_scanner_source ON conn_track(scanner_source) WHERE scanner_source IS NOT NULL;
+
+COMMENT ON COLUMN conn_track.scanner_source IS 'Name of the known scanner the connection came from, e.g. censys';
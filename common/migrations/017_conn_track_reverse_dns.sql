@@ -0,0 +1,11 @@
+-- Migration 017: reverse DNS names on conn_track.
+--
+-- PTR names of the client address at connection time, e.g. a hosting
+-- provider's "static.1.2.3.4.clients.example.net" or a university scanner.
+-- They are whatever the address owner published and are not forward-confirmed.
+-- NULL when the address has no PTR record, the lookup timed out or reverse DNS
+-- lookups are disabled.
+
+ALTER TABLE conn_track ADD COLUMN reverse_dns TEXT[];
+
+COMMENT ON COLUMN conn_track.reverse_dns IS 'PTR names of the client address at connection time';
@@ -0,0 +1,10 @@
+-- Migration 018: raw byte stream recordings of sessions.
+--
+-- With --record-raw-streams every interactive session writes the exact bytes
+-- the client sent and received, with timestamps, to a file on the honeypot
+-- host. The file's path is kept here so a session can be matched to its
+-- recording. NULL when recording is disabled or the file couldn't be created.
+
+ALTER TABLE sessions ADD COLUMN raw_stream_path TEXT;
+
+COMMENT ON COLUMN sessions.raw_stream_path IS 'Path of the raw byte stream recording of this session on the honeypot host';
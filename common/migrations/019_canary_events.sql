@@ -0,0 +1,20 @@
+-- Migration 019: reads of canary files.
+--
+-- Canary files (fake cloud credentials, keys, wallets) are planted in the
+-- virtual filesystem and have no purpose other than being found. Reading one
+-- with cat, head, tail and friends or fetching it over SFTP is a strong sign
+-- of an attacker looking for loot rather than a bot running its script.
+
+CREATE TABLE canary_events (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    auth_id UUID NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    path TEXT NOT NULL,
+    command TEXT NOT NULL
+);
+
+CREATE INDEX idx_canary_events_auth_id ON canary_events(auth_id);
+CREATE INDEX idx_canary_events_timestamp ON canary_events(timestamp DESC);
+
+COMMENT ON COLUMN canary_events.path IS 'Canary file that was read, symlinks resolved';
+COMMENT ON COLUMN canary_events.command IS 'Command line that read it, "sftp open" for SFTP';
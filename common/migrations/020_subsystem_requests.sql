@@ -0,0 +1,19 @@
+-- Migration 020: subsystem requests.
+--
+-- Besides sftp, clients ask for netconf, custom vendor subsystems and the like.
+-- Which subsystems a client probes for says a lot about the tool behind it, so
+-- every request is kept, together with whether the honeypot accepted it.
+
+CREATE TABLE subsystem_requests (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    auth_id UUID NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    name TEXT NOT NULL,
+    accepted BOOLEAN NOT NULL
+);
+
+CREATE INDEX idx_subsystem_requests_auth_id ON subsystem_requests(auth_id);
+CREATE INDEX idx_subsystem_requests_name ON subsystem_requests(name);
+
+COMMENT ON COLUMN subsystem_requests.name IS 'Subsystem name as sent by the client, e.g. sftp or netconf';
+COMMENT ON COLUMN subsystem_requests.accepted IS 'Whether the request got channel_success';
@@ -0,0 +1,26 @@
+-- Migration 021: executions.
+--
+-- Running a dropped file (./x, /tmp/x, or a name found on PATH) is the step after
+-- the download. Every attempt is kept with what bash made of it and the file as
+-- it was at that moment, since it is often deleted right after.
+
+CREATE TABLE executions (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    auth_id UUID NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    path TEXT NOT NULL,
+    command TEXT NOT NULL,
+    outcome TEXT NOT NULL,
+    file_size BIGINT,
+    file_hash TEXT,
+    content BYTEA
+);
+
+CREATE INDEX idx_executions_auth_id ON executions(auth_id);
+CREATE INDEX idx_executions_file_hash ON executions(file_hash);
+
+COMMENT ON COLUMN executions.path IS 'Absolute path of the file that was run, symlinks not followed';
+COMMENT ON COLUMN executions.command IS 'Command line that ran it, as typed';
+COMMENT ON COLUMN executions.outcome IS 'executed, permission denied, exec format error, bad interpreter, is a directory or not found';
+COMMENT ON COLUMN executions.file_hash IS 'SHA256 of content, NULL when there was no file';
+COMMENT ON COLUMN executions.content IS 'The file as it was when run';
@@ -0,0 +1,9 @@
+-- Migration 022: truncated uploads.
+--
+-- Only the first --max-upload-size bytes of an upload are kept. The hash and size
+-- still describe the whole file as the client sent it, binary_data then only holds
+-- its beginning.
+
+ALTER TABLE uploaded_files ADD COLUMN truncated BOOLEAN NOT NULL DEFAULT FALSE;
+
+COMMENT ON COLUMN uploaded_files.truncated IS 'File was bigger than --max-upload-size, binary_data only holds the first bytes while file_size and file_hash cover all of it';
@@ -0,0 +1,12 @@
+-- Migration 023: uploads on disk.
+--
+-- With --upload-store-dir uploads are written to <dir>/<sha256> for malware
+-- sandboxes and YARA, which work on files. The blob in the database becomes
+-- optional (--store-uploads-in-db=false leaves it out) and the row points at the
+-- file instead.
+
+ALTER TABLE uploaded_files ALTER COLUMN binary_data DROP NOT NULL;
+ALTER TABLE uploaded_files ADD COLUMN stored_path TEXT;
+
+COMMENT ON COLUMN uploaded_files.stored_path IS 'File in the upload store directory, named by file_hash (with .partial appended when truncated), NULL when not written to disk';
+COMMENT ON COLUMN uploaded_files.binary_data IS 'The uploaded bytes, NULL when only kept in the upload store';
@@ -0,0 +1,8 @@
+-- Migration 024: YARA matches of uploads.
+--
+-- With --yara-rules (and the yara cargo feature) every upload is scanned and the
+-- names of the rules it matches are stored with it.
+
+ALTER TABLE uploaded_files ADD COLUMN yara_matches TEXT[] NOT NULL DEFAULT '{}';
+
+COMMENT ON COLUMN uploaded_files.yara_matches IS 'Names of the YARA rules the kept bytes match, empty without --yara-rules';
@@ -0,0 +1,24 @@
+-- Migration 025: dns_queries.
+--
+-- Attackers resolve their C2 and download hosts with dig, host or nslookup
+-- before using them. The names are IOCs whatever the honeypot answered, so
+-- every lookup is kept with the answer that was shown.
+
+CREATE TABLE dns_queries (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    auth_id UUID NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    name TEXT NOT NULL,
+    record_type TEXT NOT NULL,
+    command TEXT NOT NULL,
+    status TEXT NOT NULL,
+    answers TEXT[] NOT NULL DEFAULT '{}'
+);
+
+CREATE INDEX idx_dns_queries_auth_id ON dns_queries(auth_id);
+CREATE INDEX idx_dns_queries_name ON dns_queries(name);
+
+COMMENT ON COLUMN dns_queries.name IS 'Name looked up, lowercase without the trailing dot. in-addr.arpa/ip6.arpa for reverse lookups';
+COMMENT ON COLUMN dns_queries.command IS 'dig, host or nslookup';
+COMMENT ON COLUMN dns_queries.status IS 'NOERROR, NXDOMAIN or SERVFAIL, as shown to the client';
+COMMENT ON COLUMN dns_queries.answers IS 'Record data shown to the client, empty unless --dns-lookups resolve';
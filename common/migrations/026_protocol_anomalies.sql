@@ -0,0 +1,21 @@
+-- Migration 026: protocol_anomalies.
+--
+-- Malformed packets, out of range lengths and impossible key exchanges are
+-- rarely honest clients. Fuzzers and exploit scanners send them before (or
+-- instead of) authenticating, so they are kept per source IP even though no
+-- auth row exists for them.
+
+CREATE TABLE protocol_anomalies (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    ip INET NOT NULL,
+    port INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    detail TEXT NOT NULL
+);
+
+CREATE INDEX idx_protocol_anomalies_ip ON protocol_anomalies(ip);
+CREATE INDEX idx_protocol_anomalies_kind ON protocol_anomalies(kind);
+
+COMMENT ON COLUMN protocol_anomalies.kind IS 'invalid_length, oversized_packet, malformed_packet, bad_kexinit, no_common_algorithm, strict_kex_violation or protocol_violation';
+COMMENT ON COLUMN protocol_anomalies.detail IS 'Error reported by the SSH library';
@@ -0,0 +1,16 @@
+-- Migration 027: commands.parsed_command.
+--
+-- The fake shell's parse of every command line, next to the raw text, so
+-- sessions can be searched by what they ran instead of by regex, e.g.
+--   anything piped into a shell:
+--     jsonb_path_exists(parsed_command,
+--       '$.pipelines[*].stages[1 to last] ? (@.command == "bash" || @.command == "sh")')
+--   writes to authorized_keys:
+--     jsonb_path_exists(parsed_command,
+--       '$.pipelines[*].stages[*].redirects[*] ? (@.target like_regex "authorized_keys$")')
+
+ALTER TABLE commands ADD COLUMN parsed_command JSONB;
+
+CREATE INDEX idx_commands_parsed_command ON commands USING GIN (parsed_command jsonb_path_ops);
+
+COMMENT ON COLUMN commands.parsed_command IS 'Pipelines, stages (command, args, redirects) and compound keywords of the line as the fake shell parsed it. NULL for blank lines and rows recorded before migration 027';
@@ -0,0 +1,21 @@
+-- Migration 028: shells that were shown the legal banner.
+--
+-- With --legal-banner-file every shell is shown a legal or warning banner after
+-- the welcome message. For deterrence research each showing is recorded with
+-- the first command typed after it, so sessions that carried on can be told
+-- apart from those that left without running anything.
+
+CREATE TABLE legal_banner_views (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    auth_id UUID NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    shown_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    banner_sha256 TEXT NOT NULL,
+    first_command TEXT,
+    first_command_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_legal_banner_views_auth_id ON legal_banner_views(auth_id);
+CREATE INDEX idx_legal_banner_views_banner_sha256 ON legal_banner_views(banner_sha256);
+
+COMMENT ON COLUMN legal_banner_views.banner_sha256 IS 'SHA-256 of the banner text, tells experiments with different banners apart';
+COMMENT ON COLUMN legal_banner_views.first_command IS 'First non-empty command line typed after the banner, NULL if the session ended without one';
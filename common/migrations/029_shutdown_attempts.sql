@@ -0,0 +1,23 @@
+-- Migration 029: shutdown_attempts.
+--
+-- reboot, poweroff, halt and shutdown are run to cover tracks, to make a
+-- dropped payload start at boot or simply to break the machine. Root shells
+-- see the system go down and lose their session, anyone else is refused by
+-- logind; both are kept as a sign of destructive intent.
+
+CREATE TABLE shutdown_attempts (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    auth_id UUID NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    command TEXT NOT NULL,
+    action TEXT NOT NULL,
+    permitted BOOLEAN NOT NULL,
+    immediate BOOLEAN NOT NULL
+);
+
+CREATE INDEX idx_shutdown_attempts_auth_id ON shutdown_attempts(auth_id);
+
+COMMENT ON COLUMN shutdown_attempts.command IS 'The command with its arguments, e.g. shutdown -r now';
+COMMENT ON COLUMN shutdown_attempts.action IS 'reboot, poweroff or halt';
+COMMENT ON COLUMN shutdown_attempts.permitted IS 'Run as root; the session was closed if it was also immediate';
+COMMENT ON COLUMN shutdown_attempts.immediate IS 'The system was to go down now, false for shutdown with a later time or -k';
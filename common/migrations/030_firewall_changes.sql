@@ -0,0 +1,21 @@
+-- Migration 030: firewall_changes.
+--
+-- iptables and ufw are run to lock other attackers (or the owner) out, to
+-- open a port for a dropped backdoor or to redirect traffic. Every command
+-- that would change the rules is kept, whether it was refused for lack of
+-- root or applied to the session's rule table.
+
+CREATE TABLE firewall_changes (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    auth_id UUID NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    tool TEXT NOT NULL,
+    command TEXT NOT NULL,
+    permitted BOOLEAN NOT NULL
+);
+
+CREATE INDEX idx_firewall_changes_auth_id ON firewall_changes(auth_id);
+
+COMMENT ON COLUMN firewall_changes.tool IS 'iptables or ufw';
+COMMENT ON COLUMN firewall_changes.command IS 'The command with its arguments, e.g. iptables -A INPUT -p tcp --dport 22 -j DROP';
+COMMENT ON COLUMN firewall_changes.permitted IS 'Run as root, the change was applied to the session''s rules';
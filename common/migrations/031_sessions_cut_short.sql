@@ -0,0 +1,10 @@
+-- Migration 031: sessions closed by --disconnect-after-commands.
+--
+-- Operators who accept credentials but only want the first few commands of a
+-- session can have it closed once that many have run. Those sessions didn't
+-- end on the attacker's terms, so what they did next is unknown rather than
+-- absent; the number of commands run is kept to tell them apart.
+
+ALTER TABLE sessions ADD COLUMN cut_short_after_commands INTEGER;
+
+COMMENT ON COLUMN sessions.cut_short_after_commands IS 'Commands run before --disconnect-after-commands closed the session, NULL if it ended on its own';
@@ -0,0 +1,11 @@
+-- Migration 032: commands.input_bytes.
+--
+-- Bots sometimes paste tens of KB on one line. The fake shell keeps only the
+-- first --max-input-line bytes of a line and runs that, so the stored command
+-- is a prefix; how much was typed is kept to tell cut lines apart. Each such
+-- line is also an oversized_input protocol anomaly of its source.
+
+ALTER TABLE commands ADD COLUMN input_bytes BIGINT;
+
+COMMENT ON COLUMN commands.input_bytes IS 'Bytes typed on a line cut at --max-input-line, NULL for lines kept whole';
+COMMENT ON COLUMN protocol_anomalies.kind IS 'invalid_length, oversized_packet, malformed_packet, bad_kexinit, no_common_algorithm, strict_kex_violation, protocol_violation, vulnerable_profile_probe or oversized_input';
@@ -0,0 +1,16 @@
+-- Migration 033: what public key attempts offered, not just its fingerprint.
+--
+-- The key algorithm and size tell client tooling apart, and attackers reuse
+-- the same key across many targets: the full key, as it would appear in an
+-- authorized_keys line, correlates attempts from different addresses. A hash
+-- index serves those lookups, RSA keys are too long for a btree to be useful.
+
+ALTER TABLE auth ADD COLUMN public_key_algorithm TEXT;
+ALTER TABLE auth ADD COLUMN public_key_bits INTEGER;
+ALTER TABLE auth ADD COLUMN public_key_blob TEXT;
+
+CREATE INDEX idx_auth_public_key_blob ON auth USING hash (public_key_blob);
+
+COMMENT ON COLUMN auth.public_key_algorithm IS 'Algorithm of the offered key, e.g. ssh-ed25519 or ssh-rsa';
+COMMENT ON COLUMN auth.public_key_bits IS 'Size of the offered key in bits, NULL when not known for its algorithm';
+COMMENT ON COLUMN auth.public_key_blob IS 'Base64 of the offered key as in an authorized_keys line';
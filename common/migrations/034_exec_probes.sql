@@ -0,0 +1,20 @@
+-- Migration 034: exec requests from file transfer and version control tools.
+--
+-- rsync, git and sftp clients exec their server side instead of asking for a
+-- shell. The honeypot answers them the way a real host would, built in or
+-- from exec_responses in the config file, and keeps which tool each one was
+-- for: these probes come from non-interactive tooling that a taunt would
+-- scare away. The command itself is in commands like any other exec.
+
+CREATE TABLE exec_probes (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    auth_id UUID NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    tool TEXT NOT NULL,
+    command TEXT NOT NULL
+);
+
+CREATE INDEX idx_exec_probes_auth_id ON exec_probes(auth_id);
+CREATE INDEX idx_exec_probes_tool ON exec_probes(tool);
+
+COMMENT ON COLUMN exec_probes.tool IS 'rsync, git-upload-pack, git-receive-pack, internal-sftp or the exec_responses prefix that matched';
@@ -0,0 +1,17 @@
+-- Migration 035: signal requests on session channels.
+--
+-- An SSH client can ask the server to deliver a signal (INT, TERM, KILL, ...)
+-- to what runs on a channel. OpenSSH's client never does, so a signal request
+-- points at a library client or a hand-written tool, and which signal it sent
+-- tells what it meant to stop.
+
+CREATE TABLE channel_signals (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    auth_id UUID NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    signal TEXT NOT NULL
+);
+
+CREATE INDEX idx_channel_signals_auth_id ON channel_signals(auth_id);
+
+COMMENT ON COLUMN channel_signals.signal IS 'Signal name without the SIG prefix as sent by the client, e.g. INT or TERM';
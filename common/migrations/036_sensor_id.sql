@@ -0,0 +1,21 @@
+-- Migration 036: the sensor each record came from.
+--
+-- Operators running several sensors (per region, per cloud, per experiment)
+-- into one database need to tell which one saw what. Each sensor stores its
+-- --sensor-id with the connections, logins, commands, sessions and uploads it
+-- records; rows of sensors without one, and rows from before, keep NULL.
+
+ALTER TABLE conn_track ADD COLUMN sensor_id TEXT;
+ALTER TABLE auth ADD COLUMN sensor_id TEXT;
+ALTER TABLE commands ADD COLUMN sensor_id TEXT;
+ALTER TABLE sessions ADD COLUMN sensor_id TEXT;
+ALTER TABLE uploaded_files ADD COLUMN sensor_id TEXT;
+
+CREATE INDEX idx_conn_track_sensor_id ON conn_track(sensor_id);
+CREATE INDEX idx_auth_sensor_id ON auth(sensor_id);
+
+COMMENT ON COLUMN conn_track.sensor_id IS '--sensor-id of the honeypot that recorded the row, NULL if it had none';
+COMMENT ON COLUMN auth.sensor_id IS '--sensor-id of the honeypot that recorded the row, NULL if it had none';
+COMMENT ON COLUMN commands.sensor_id IS '--sensor-id of the honeypot that recorded the row, NULL if it had none';
+COMMENT ON COLUMN sessions.sensor_id IS '--sensor-id of the honeypot that recorded the row, NULL if it had none';
+COMMENT ON COLUMN uploaded_files.sensor_id IS '--sensor-id of the honeypot that recorded the row, NULL if it had none';
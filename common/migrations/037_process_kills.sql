@@ -0,0 +1,27 @@
+-- Migration 037: process_kills.
+--
+-- Miners and bots kill what competes with them (other miners, watchdogs,
+-- monitoring agents) with kill and pkill. Every attempt is kept with the
+-- signal and the processes it was aimed at, whether or not the session's
+-- user was allowed to signal them.
+
+CREATE TABLE process_kills (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    auth_id UUID NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    tool TEXT NOT NULL,
+    command TEXT NOT NULL,
+    signal TEXT NOT NULL,
+    pids INTEGER[] NOT NULL DEFAULT '{}',
+    names TEXT[] NOT NULL DEFAULT '{}',
+    permitted BOOLEAN NOT NULL
+);
+
+CREATE INDEX idx_process_kills_auth_id ON process_kills(auth_id);
+
+COMMENT ON COLUMN process_kills.tool IS 'kill or pkill';
+COMMENT ON COLUMN process_kills.command IS 'The command with its arguments, e.g. pkill -9 xmrig';
+COMMENT ON COLUMN process_kills.signal IS 'Signal name without the SIG prefix, e.g. TERM or KILL, 0 for kill -0';
+COMMENT ON COLUMN process_kills.pids IS 'Processes of the session''s process table the signal was aimed at, empty when none matched';
+COMMENT ON COLUMN process_kills.names IS 'Command names of those processes, in the order of pids';
+COMMENT ON COLUMN process_kills.permitted IS 'The user owned every one of them or was root';
@@ -0,0 +1,235 @@
+-- SSH Honeypot SQLite schema
+-- Migration 001: the tables of the PostgreSQL migrations up to 036
+--
+-- For a single sensor recording into a local file instead of a PostgreSQL
+-- server. Addresses and UUIDs are TEXT, JSON documents TEXT, and the lists
+-- PostgreSQL keeps in arrays (reverse_dns, answers, yara_matches) JSON arrays in
+-- TEXT. Timestamps are RFC 3339 TEXT, which sorts in time order.
+--
+-- The IP intelligence caches, reported_ips, the enriched views and the NOTIFY
+-- triggers only serve enrichment and the dashboard, which need PostgreSQL, and
+-- are left out.
+
+CREATE TABLE auth (
+    id TEXT PRIMARY KEY NOT NULL,
+    timestamp TEXT NOT NULL,
+    ip TEXT NOT NULL,
+    username TEXT NOT NULL,
+    auth_type TEXT NOT NULL,
+    password TEXT,
+    public_key TEXT,
+    public_key_algorithm TEXT,
+    public_key_bits INTEGER,
+    public_key_blob TEXT,
+    successful BOOLEAN NOT NULL DEFAULT FALSE,
+    abuseipdb_data TEXT,
+    ipapi_data TEXT,
+    sensor_id TEXT
+);
+
+CREATE INDEX idx_auth_timestamp ON auth(timestamp);
+CREATE INDEX idx_auth_ip_timestamp ON auth(ip, timestamp DESC);
+CREATE INDEX idx_auth_username ON auth(username);
+CREATE INDEX idx_auth_password ON auth(password);
+CREATE INDEX idx_auth_public_key_blob ON auth(public_key_blob);
+
+CREATE TABLE conn_track (
+    id INTEGER PRIMARY KEY,
+    timestamp TEXT NOT NULL,
+    ip TEXT NOT NULL,
+    port INTEGER,
+    local_port INTEGER,
+    client_version TEXT,
+    hassh TEXT,
+    hassh_algorithms TEXT,
+    scanner_source TEXT,
+    reverse_dns TEXT,
+    sensor_id TEXT
+);
+
+CREATE INDEX idx_conn_track_ip ON conn_track(ip);
+CREATE INDEX idx_conn_track_timestamp ON conn_track(timestamp);
+CREATE INDEX idx_conn_track_hassh ON conn_track(hassh);
+
+CREATE TABLE commands (
+    id INTEGER PRIMARY KEY,
+    auth_id TEXT NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    timestamp TEXT NOT NULL,
+    command TEXT NOT NULL,
+    parsed_command TEXT,
+    input_bytes INTEGER,
+    sensor_id TEXT
+);
+
+CREATE INDEX idx_commands_auth_id ON commands(auth_id);
+CREATE INDEX idx_commands_timestamp ON commands(timestamp);
+
+-- Session ids are handed back to the server, a random UUID like PostgreSQL's
+-- gen_random_uuid()
+CREATE TABLE sessions (
+    id TEXT PRIMARY KEY NOT NULL DEFAULT (lower(
+        hex(randomblob(4)) || '-' || hex(randomblob(2)) || '-4' ||
+        substr(hex(randomblob(2)), 2) || '-' ||
+        substr('89ab', 1 + (abs(random()) % 4), 1) ||
+        substr(hex(randomblob(2)), 2) || '-' || hex(randomblob(6))
+    )),
+    auth_id TEXT NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    start_time TEXT NOT NULL,
+    end_time TEXT,
+    duration_seconds INTEGER,
+    raw_stream_path TEXT,
+    cut_short_after_commands INTEGER,
+    sensor_id TEXT
+);
+
+CREATE INDEX idx_sessions_auth_id ON sessions(auth_id);
+CREATE INDEX idx_sessions_start_time ON sessions(start_time);
+
+CREATE TABLE uploaded_files (
+    id INTEGER PRIMARY KEY,
+    auth_id TEXT NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    timestamp TEXT NOT NULL,
+    filename TEXT NOT NULL,
+    filepath TEXT NOT NULL,
+    file_size INTEGER NOT NULL,
+    file_hash TEXT NOT NULL,
+    claimed_mime_type TEXT,
+    detected_mime_type TEXT,
+    format_mismatch BOOLEAN NOT NULL DEFAULT FALSE,
+    file_entropy REAL,
+    truncated BOOLEAN NOT NULL DEFAULT FALSE,
+    yara_matches TEXT NOT NULL DEFAULT '[]',
+    stored_path TEXT,
+    binary_data BLOB,
+    sensor_id TEXT
+);
+
+CREATE INDEX idx_uploaded_files_auth_id ON uploaded_files(auth_id);
+CREATE INDEX idx_uploaded_files_hash ON uploaded_files(file_hash);
+
+CREATE TABLE terminal_events (
+    id INTEGER PRIMARY KEY,
+    auth_id TEXT NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    timestamp TEXT NOT NULL,
+    event_type TEXT NOT NULL,
+    term TEXT,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL
+);
+
+CREATE INDEX idx_terminal_events_auth_id ON terminal_events(auth_id);
+
+CREATE TABLE canary_events (
+    id INTEGER PRIMARY KEY,
+    auth_id TEXT NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    timestamp TEXT NOT NULL,
+    path TEXT NOT NULL,
+    command TEXT NOT NULL
+);
+
+CREATE INDEX idx_canary_events_auth_id ON canary_events(auth_id);
+
+CREATE TABLE subsystem_requests (
+    id INTEGER PRIMARY KEY,
+    auth_id TEXT NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    timestamp TEXT NOT NULL,
+    name TEXT NOT NULL,
+    accepted BOOLEAN NOT NULL
+);
+
+CREATE INDEX idx_subsystem_requests_auth_id ON subsystem_requests(auth_id);
+
+CREATE TABLE executions (
+    id INTEGER PRIMARY KEY,
+    auth_id TEXT NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    timestamp TEXT NOT NULL,
+    path TEXT NOT NULL,
+    command TEXT NOT NULL,
+    outcome TEXT NOT NULL,
+    file_size INTEGER,
+    file_hash TEXT,
+    content BLOB
+);
+
+CREATE INDEX idx_executions_auth_id ON executions(auth_id);
+CREATE INDEX idx_executions_file_hash ON executions(file_hash);
+
+CREATE TABLE dns_queries (
+    id INTEGER PRIMARY KEY,
+    auth_id TEXT NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    timestamp TEXT NOT NULL,
+    name TEXT NOT NULL,
+    record_type TEXT NOT NULL,
+    command TEXT NOT NULL,
+    status TEXT NOT NULL,
+    answers TEXT NOT NULL DEFAULT '[]'
+);
+
+CREATE INDEX idx_dns_queries_auth_id ON dns_queries(auth_id);
+CREATE INDEX idx_dns_queries_name ON dns_queries(name);
+
+-- No auth row exists for most of these, they are kept per source address
+CREATE TABLE protocol_anomalies (
+    id INTEGER PRIMARY KEY,
+    timestamp TEXT NOT NULL,
+    ip TEXT NOT NULL,
+    port INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    detail TEXT NOT NULL
+);
+
+CREATE INDEX idx_protocol_anomalies_ip ON protocol_anomalies(ip);
+CREATE INDEX idx_protocol_anomalies_kind ON protocol_anomalies(kind);
+
+CREATE TABLE legal_banner_views (
+    id INTEGER PRIMARY KEY,
+    auth_id TEXT NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    shown_at TEXT NOT NULL,
+    banner_sha256 TEXT NOT NULL,
+    first_command TEXT,
+    first_command_at TEXT
+);
+
+CREATE INDEX idx_legal_banner_views_auth_id ON legal_banner_views(auth_id);
+
+CREATE TABLE shutdown_attempts (
+    id INTEGER PRIMARY KEY,
+    auth_id TEXT NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    timestamp TEXT NOT NULL,
+    command TEXT NOT NULL,
+    action TEXT NOT NULL,
+    permitted BOOLEAN NOT NULL,
+    immediate BOOLEAN NOT NULL
+);
+
+CREATE INDEX idx_shutdown_attempts_auth_id ON shutdown_attempts(auth_id);
+
+CREATE TABLE firewall_changes (
+    id INTEGER PRIMARY KEY,
+    auth_id TEXT NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    timestamp TEXT NOT NULL,
+    tool TEXT NOT NULL,
+    command TEXT NOT NULL,
+    permitted BOOLEAN NOT NULL
+);
+
+CREATE INDEX idx_firewall_changes_auth_id ON firewall_changes(auth_id);
+
+CREATE TABLE exec_probes (
+    id INTEGER PRIMARY KEY,
+    auth_id TEXT NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    timestamp TEXT NOT NULL,
+    tool TEXT NOT NULL,
+    command TEXT NOT NULL
+);
+
+CREATE INDEX idx_exec_probes_auth_id ON exec_probes(auth_id);
+
+CREATE TABLE channel_signals (
+    id INTEGER PRIMARY KEY,
+    auth_id TEXT NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    timestamp TEXT NOT NULL,
+    signal TEXT NOT NULL
+);
+
+CREATE INDEX idx_channel_signals_auth_id ON channel_signals(auth_id);
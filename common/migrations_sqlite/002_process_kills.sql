@@ -0,0 +1,17 @@
+-- Migration 002: process_kills, PostgreSQL migration 037.
+--
+-- pids and names are JSON arrays in TEXT.
+
+CREATE TABLE process_kills (
+    id INTEGER PRIMARY KEY,
+    auth_id TEXT NOT NULL REFERENCES auth(id) ON DELETE CASCADE,
+    timestamp TEXT NOT NULL,
+    tool TEXT NOT NULL,
+    command TEXT NOT NULL,
+    signal TEXT NOT NULL,
+    pids TEXT NOT NULL DEFAULT '[]',
+    names TEXT NOT NULL DEFAULT '[]',
+    permitted BOOLEAN NOT NULL
+);
+
+CREATE INDEX idx_process_kills_auth_id ON process_kills(auth_id);
@@ -1,6 +1,8 @@
 use chrono::{DateTime, Utc};
 use sqlx::types::uuid::Uuid;
 use sqlx::{Error, PgPool, Row, query};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
 use tokio::sync::mpsc;
 
 // Database message types
@@ -11,27 +13,172 @@ pub enum DbMessage {
         ip: String,
         port: u16,
         local_port: u16,
+        /// Client identification string, if the client got that far
+        client_version: Option<String>,
+        /// HASSH fingerprint of the client's KEXINIT
+        hassh: Option<String>,
+        /// The `kex;ciphers;macs;compression` string the HASSH was computed from
+        hassh_algorithms: Option<String>,
+        /// Known research scanner the connection came from (e.g. "censys")
+        scanner_source: Option<String>,
+        /// PTR names of the client address, None if it has none or the lookup failed
+        reverse_dns: Option<Vec<String>>,
     },
+    /// `auth_id` is made by the sender, so authentication never waits for the insert
     RecordAuth {
+        auth_id: String,
         timestamp: DateTime<Utc>,
         ip: String,
         username: String,
         auth_type: String,
         password: Option<String>,
+        /// SHA512 fingerprint of the offered key
         public_key: Option<String>,
+        /// Algorithm of the offered key, e.g. ssh-ed25519
+        public_key_algorithm: Option<String>,
+        /// Size of the offered key in bits, None when not known for its algorithm
+        public_key_bits: Option<u32>,
+        /// Base64 of the offered key as in an authorized_keys line
+        public_key_blob: Option<String>,
         successful: bool,
         abuseipdb_data: Option<serde_json::Value>,
         ipapi_data: Option<serde_json::Value>,
-        response_tx: tokio::sync::oneshot::Sender<Result<String, String>>,
     },
     RecordCommand {
         auth_id: String,
         timestamp: DateTime<Utc>,
         command: String,
+        /// What the fake shell's parser made of the line, None for blank lines
+        parsed_command: Option<serde_json::Value>,
+        /// Bytes typed when the line was cut at --max-input-line
+        input_bytes: Option<usize>,
+    },
+    /// pty-req or window-change from an authenticated client
+    RecordTerminalEvent {
+        auth_id: String,
+        timestamp: DateTime<Utc>,
+        /// "pty-req" or "window-change"
+        event_type: &'static str,
+        term: Option<String>,
+        columns: u32,
+        rows: u32,
+    },
+    /// Subsystem requested on a session channel
+    RecordSubsystemRequest {
+        auth_id: String,
+        timestamp: DateTime<Utc>,
+        name: String,
+        accepted: bool,
+    },
+    /// Signal request on a session channel
+    RecordChannelSignal {
+        auth_id: String,
+        timestamp: DateTime<Utc>,
+        /// Name without the SIG prefix, e.g. "INT"
+        signal: String,
+    },
+    /// An exec request answered like the tool it came from expects
+    RecordExecProbe {
+        auth_id: String,
+        timestamp: DateTime<Utc>,
+        /// e.g. rsync or git-upload-pack, the matching prefix for configured answers
+        tool: String,
+        command: String,
+    },
+    /// A canary file was read
+    RecordCanaryHit {
+        auth_id: String,
+        timestamp: DateTime<Utc>,
+        path: String,
+        /// Command line that read it, or the SFTP operation
+        command: String,
+    },
+    /// A shell was shown the --legal-banner-file banner
+    RecordLegalBannerShown {
+        auth_id: String,
+        timestamp: DateTime<Utc>,
+        banner_sha256: String,
+    },
+    /// First command typed in a shell after the legal banner
+    RecordLegalBannerResponse {
+        auth_id: String,
+        timestamp: DateTime<Utc>,
+        command: String,
+    },
+    /// A file from the virtual filesystem was run
+    RecordExecution {
+        auth_id: String,
+        timestamp: DateTime<Utc>,
+        path: String,
+        /// Command line as typed
+        command: String,
+        /// What bash made of it, e.g. executed or permission denied
+        outcome: String,
+        file_hash: Option<String>,
+        /// The file as it was when run, None if there was no file
+        content: Option<Vec<u8>>,
+    },
+    /// A connection broke the SSH protocol in a way honest clients don't,
+    /// usually before authenticating
+    RecordProtocolAnomaly {
+        timestamp: DateTime<Utc>,
+        ip: String,
+        port: u16,
+        /// Short classification, e.g. invalid_length or bad_kexinit
+        kind: String,
+        detail: String,
+    },
+    /// dig, host or nslookup looked up a name
+    RecordDnsQuery {
+        auth_id: String,
+        timestamp: DateTime<Utc>,
+        name: String,
+        record_type: String,
+        /// Command that asked, dig, host or nslookup
+        command: String,
+        /// NOERROR, NXDOMAIN or SERVFAIL as shown to the client
+        status: String,
+        answers: Vec<String>,
+    },
+    /// reboot, poweroff, halt or shutdown was run
+    RecordShutdownAttempt {
+        auth_id: String,
+        timestamp: DateTime<Utc>,
+        command: String,
+        /// reboot, poweroff or halt
+        action: String,
+        permitted: bool,
+        immediate: bool,
+    },
+    /// iptables or ufw was asked to change the firewall
+    RecordFirewallChange {
+        auth_id: String,
+        timestamp: DateTime<Utc>,
+        /// iptables or ufw
+        tool: String,
+        command: String,
+        permitted: bool,
+    },
+    /// kill or pkill signalled processes of the fake process table
+    RecordProcessKill {
+        auth_id: String,
+        timestamp: DateTime<Utc>,
+        /// kill or pkill
+        tool: String,
+        command: String,
+        /// Name without the SIG prefix, e.g. "TERM", "0" for `kill -0`
+        signal: String,
+        /// Processes the signal was aimed at
+        pids: Vec<u32>,
+        /// Their command names, in the order of `pids`
+        names: Vec<String>,
+        permitted: bool,
     },
     RecordSessionStart {
         auth_id: String,
         start_time: DateTime<Utc>,
+        /// Raw byte stream recording of the session, if enabled
+        raw_stream_path: Option<String>,
         response_tx: tokio::sync::oneshot::Sender<Result<String, String>>,
     },
     RecordSessionEnd {
@@ -39,6 +186,11 @@ pub enum DbMessage {
         end_time: DateTime<Utc>,
         duration_seconds: i64,
     },
+    /// The session was closed by --disconnect-after-commands
+    RecordSessionCutShort {
+        session_id: String,
+        commands: u32,
+    },
     RecordFileUpload {
         auth_id: String,
         timestamp: DateTime<Utc>,
@@ -50,18 +202,38 @@ pub enum DbMessage {
         detected_mime_type: Option<String>,
         format_mismatch: bool,
         file_entropy: Option<f64>,
-        binary_data: Vec<u8>,
+        /// Bigger than --max-upload-size, `binary_data` only holds the beginning
+        /// while `file_size` and `file_hash` cover the whole file
+        truncated: bool,
+        /// Names of the YARA rules the file matches
+        yara_matches: Vec<String>,
+        /// Where the upload store wrote the file, None when it isn't written to disk
+        stored_path: Option<String>,
+        /// None when the file only went to the upload store
+        binary_data: Option<Vec<u8>>,
     },
     Shutdown,
 }
 
+/// Where [`run_db_handler`] stores what the honeypot records: Postgres when
+/// serving, [`MemorySink`] when testing without a database
+pub trait DbSink: Send + Sync + 'static {
+    /// Checked once before the first message, Err stops the handler
+    fn ready(&self) -> impl Future<Output = Result<(), String>> + Send {
+        async { Ok(()) }
+    }
+
+    /// Store one message. [`DbMessage::Shutdown`] is handled by [`run_db_handler`]
+    fn record(&self, message: DbMessage) -> impl Future<Output = ()> + Send;
+}
+
 // Database handler function that runs in its own task
-pub async fn run_db_handler(mut rx: mpsc::Receiver<DbMessage>, pool: PgPool) {
-    log::trace!("Starting PostgreSQL database handler");
+pub async fn run_db_handler<S: DbSink>(mut rx: mpsc::Receiver<DbMessage>, sink: S) {
+    log::trace!("Starting database handler");
 
     // Verify database connection
-    match pool.acquire().await {
-        Ok(_) => {
+    match sink.ready().await {
+        Ok(()) => {
             log::trace!("Database connection pool initialized successfully");
         }
         Err(e) => {
@@ -81,16 +253,65 @@ pub async fn run_db_handler(mut rx: mpsc::Receiver<DbMessage>, pool: PgPool) {
             "Processing database message: {:?}",
             std::mem::discriminant(&msg)
         );
-        match msg {
+        if let DbMessage::Shutdown = msg {
+            log::info!("Database handler shutting down");
+            break;
+        }
+        sink.record(msg).await;
+    }
+    log::trace!("Database handler stopped");
+}
+
+/// Postgres behind [`run_db_handler`]
+#[derive(Debug, Clone)]
+pub struct PgSink {
+    pub pool: PgPool,
+    /// --sensor-id, stored with connections, logins, commands, sessions and
+    /// uploads so a fleet of sensors can share one database
+    pub sensor_id: Option<String>,
+}
+
+impl DbSink for PgSink {
+    async fn ready(&self) -> Result<(), String> {
+        self.pool
+            .acquire()
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    async fn record(&self, message: DbMessage) {
+        let pool = &self.pool;
+        let sensor_id = self.sensor_id.as_deref();
+        match message {
             DbMessage::RecordConnect {
                 timestamp,
                 ip,
                 port,
                 local_port,
+                client_version,
+                hassh,
+                hassh_algorithms,
+                scanner_source,
+                reverse_dns,
             } => {
                 log::trace!("Recording connection from {} at {}", ip, timestamp);
 
-                match record_connect(&pool, timestamp, ip, port, local_port).await {
+                match record_connect(
+                    pool,
+                    sensor_id,
+                    timestamp,
+                    ip,
+                    port,
+                    local_port,
+                    client_version,
+                    hassh,
+                    hassh_algorithms,
+                    scanner_source,
+                    reverse_dns,
+                )
+                .await
+                {
                     Ok(_) => {
                         log::trace!("Connection recorded");
                     }
@@ -100,16 +321,19 @@ pub async fn run_db_handler(mut rx: mpsc::Receiver<DbMessage>, pool: PgPool) {
                 };
             }
             DbMessage::RecordAuth {
+                auth_id,
                 timestamp,
                 ip,
                 username,
                 auth_type,
                 password,
                 public_key,
+                public_key_algorithm,
+                public_key_bits,
+                public_key_blob,
                 successful,
                 abuseipdb_data,
                 ipapi_data,
-                response_tx,
             } => {
                 log::trace!(
                     "Recording {} auth attempt: user='{}' from {} (success={})",
@@ -119,44 +343,237 @@ pub async fn run_db_handler(mut rx: mpsc::Receiver<DbMessage>, pool: PgPool) {
                     successful
                 );
 
-                let result = record_auth(
-                    &pool,
+                if let Err(e) = record_auth(
+                    pool,
+                    sensor_id,
+                    auth_id,
                     timestamp,
                     ip,
                     username,
                     auth_type,
                     password,
                     public_key,
+                    public_key_algorithm,
+                    public_key_bits,
+                    public_key_blob,
                     successful,
                     abuseipdb_data,
                     ipapi_data,
                 )
-                .await;
-
-                let response = match result {
-                    Ok(auth_id) => Ok(auth_id),
-                    Err(e) => {
-                        log::error!("Database error recording auth: {}", e);
-                        Err(e.to_string())
-                    }
-                };
-                let _ = response_tx.send(response);
+                .await
+                {
+                    log::error!("Database error recording auth: {}", e);
+                }
             }
             DbMessage::RecordCommand {
                 auth_id,
                 timestamp,
                 command,
+                parsed_command,
+                input_bytes,
             } => {
-                if let Err(e) = record_command(&pool, auth_id, timestamp, command).await {
+                if let Err(e) = record_command(
+                    pool,
+                    sensor_id,
+                    auth_id,
+                    timestamp,
+                    command,
+                    parsed_command,
+                    input_bytes,
+                )
+                .await
+                {
                     log::error!("Database error recording command: {}", e);
                 }
             }
+            DbMessage::RecordTerminalEvent {
+                auth_id,
+                timestamp,
+                event_type,
+                term,
+                columns,
+                rows,
+            } => {
+                if let Err(e) =
+                    record_terminal_event(pool, auth_id, timestamp, event_type, term, columns, rows)
+                        .await
+                {
+                    log::error!("Database error recording terminal event: {}", e);
+                }
+            }
+            DbMessage::RecordSubsystemRequest {
+                auth_id,
+                timestamp,
+                name,
+                accepted,
+            } => {
+                if let Err(e) =
+                    record_subsystem_request(pool, auth_id, timestamp, name, accepted).await
+                {
+                    log::error!("Database error recording subsystem request: {}", e);
+                }
+            }
+            DbMessage::RecordChannelSignal {
+                auth_id,
+                timestamp,
+                signal,
+            } => {
+                if let Err(e) = record_channel_signal(pool, auth_id, timestamp, signal).await {
+                    log::error!("Database error recording channel signal: {}", e);
+                }
+            }
+            DbMessage::RecordExecProbe {
+                auth_id,
+                timestamp,
+                tool,
+                command,
+            } => {
+                if let Err(e) = record_exec_probe(pool, auth_id, timestamp, tool, command).await {
+                    log::error!("Database error recording exec probe: {}", e);
+                }
+            }
+            DbMessage::RecordCanaryHit {
+                auth_id,
+                timestamp,
+                path,
+                command,
+            } => {
+                if let Err(e) = record_canary_hit(pool, auth_id, timestamp, path, command).await {
+                    log::error!("Database error recording canary hit: {}", e);
+                }
+            }
+            DbMessage::RecordLegalBannerShown {
+                auth_id,
+                timestamp,
+                banner_sha256,
+            } => {
+                if let Err(e) =
+                    record_legal_banner_shown(pool, auth_id, timestamp, banner_sha256).await
+                {
+                    log::error!("Database error recording legal banner: {}", e);
+                }
+            }
+            DbMessage::RecordLegalBannerResponse {
+                auth_id,
+                timestamp,
+                command,
+            } => {
+                if let Err(e) =
+                    record_legal_banner_response(pool, auth_id, timestamp, command).await
+                {
+                    log::error!("Database error recording legal banner response: {}", e);
+                }
+            }
+            DbMessage::RecordExecution {
+                auth_id,
+                timestamp,
+                path,
+                command,
+                outcome,
+                file_hash,
+                content,
+            } => {
+                if let Err(e) = record_execution(
+                    pool, auth_id, timestamp, path, command, outcome, file_hash, content,
+                )
+                .await
+                {
+                    log::error!("Database error recording execution: {}", e);
+                }
+            }
+            DbMessage::RecordProtocolAnomaly {
+                timestamp,
+                ip,
+                port,
+                kind,
+                detail,
+            } => {
+                if let Err(e) =
+                    record_protocol_anomaly(pool, timestamp, ip, port, kind, detail).await
+                {
+                    log::error!("Database error recording protocol anomaly: {}", e);
+                }
+            }
+            DbMessage::RecordDnsQuery {
+                auth_id,
+                timestamp,
+                name,
+                record_type,
+                command,
+                status,
+                answers,
+            } => {
+                if let Err(e) = record_dns_query(
+                    pool,
+                    auth_id,
+                    timestamp,
+                    name,
+                    record_type,
+                    command,
+                    status,
+                    answers,
+                )
+                .await
+                {
+                    log::error!("Database error recording DNS query: {}", e);
+                }
+            }
+            DbMessage::RecordShutdownAttempt {
+                auth_id,
+                timestamp,
+                command,
+                action,
+                permitted,
+                immediate,
+            } => {
+                if let Err(e) = record_shutdown_attempt(
+                    pool, auth_id, timestamp, command, action, permitted, immediate,
+                )
+                .await
+                {
+                    log::error!("Database error recording shutdown attempt: {}", e);
+                }
+            }
+            DbMessage::RecordFirewallChange {
+                auth_id,
+                timestamp,
+                tool,
+                command,
+                permitted,
+            } => {
+                if let Err(e) =
+                    record_firewall_change(pool, auth_id, timestamp, tool, command, permitted).await
+                {
+                    log::error!("Database error recording firewall change: {}", e);
+                }
+            }
+            DbMessage::RecordProcessKill {
+                auth_id,
+                timestamp,
+                tool,
+                command,
+                signal,
+                pids,
+                names,
+                permitted,
+            } => {
+                if let Err(e) = record_process_kill(
+                    pool, auth_id, timestamp, tool, command, signal, pids, names, permitted,
+                )
+                .await
+                {
+                    log::error!("Database error recording process kill: {}", e);
+                }
+            }
             DbMessage::RecordSessionStart {
                 auth_id,
                 start_time,
+                raw_stream_path,
                 response_tx,
             } => {
-                let result = record_session_start(&pool, auth_id, start_time).await;
+                let result =
+                    record_session_start(pool, sensor_id, auth_id, start_time, raw_stream_path)
+                        .await;
 
                 let response = match result {
                     Ok(session_id) => Ok(session_id),
@@ -173,11 +590,19 @@ pub async fn run_db_handler(mut rx: mpsc::Receiver<DbMessage>, pool: PgPool) {
                 duration_seconds,
             } => {
                 if let Err(e) =
-                    record_session_end(&pool, session_id, end_time, duration_seconds).await
+                    record_session_end(pool, session_id, end_time, duration_seconds).await
                 {
                     log::error!("Database error recording session end: {}", e);
                 }
             }
+            DbMessage::RecordSessionCutShort {
+                session_id,
+                commands,
+            } => {
+                if let Err(e) = record_session_cut_short(pool, session_id, commands).await {
+                    log::error!("Database error recording cut short session: {}", e);
+                }
+            }
             DbMessage::RecordFileUpload {
                 auth_id,
                 timestamp,
@@ -189,10 +614,14 @@ pub async fn run_db_handler(mut rx: mpsc::Receiver<DbMessage>, pool: PgPool) {
                 detected_mime_type,
                 format_mismatch,
                 file_entropy,
+                truncated,
+                yara_matches,
+                stored_path,
                 binary_data,
             } => {
                 if let Err(e) = record_file_upload(
-                    &pool,
+                    pool,
+                    sensor_id,
                     auth_id,
                     timestamp,
                     filename,
@@ -203,6 +632,9 @@ pub async fn run_db_handler(mut rx: mpsc::Receiver<DbMessage>, pool: PgPool) {
                     detected_mime_type,
                     format_mismatch,
                     file_entropy,
+                    truncated,
+                    yara_matches,
+                    stored_path,
                     binary_data,
                 )
                 .await
@@ -210,13 +642,53 @@ pub async fn run_db_handler(mut rx: mpsc::Receiver<DbMessage>, pool: PgPool) {
                     log::error!("Database error recording file upload: {}", e);
                 }
             }
-            DbMessage::Shutdown => {
-                log::info!("Database handler shutting down");
-                break;
-            }
+            DbMessage::Shutdown => {}
         }
     }
-    log::trace!("Database handler stopped");
+}
+
+/// Keeps what is recorded in memory, for tests of the code sending
+/// [`DbMessage`]s. Session starts are answered with ids counting up from 1
+#[derive(Debug, Clone, Default)]
+pub struct MemorySink {
+    messages: Arc<StdMutex<Vec<DbMessage>>>,
+    sessions: Arc<AtomicU64>,
+}
+
+impl MemorySink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The messages recorded since the last call, oldest first
+    pub fn take(&self) -> Vec<DbMessage> {
+        std::mem::take(&mut *self.messages.lock().unwrap())
+    }
+}
+
+impl DbSink for MemorySink {
+    async fn record(&self, message: DbMessage) {
+        let message = match message {
+            DbMessage::RecordSessionStart {
+                auth_id,
+                start_time,
+                raw_stream_path,
+                response_tx,
+            } => {
+                let id = self.sessions.fetch_add(1, Ordering::Relaxed) + 1;
+                let _ = response_tx.send(Ok(Uuid::from_u128(id as u128).to_string()));
+                // The sender is used up, a closed one stands in for it
+                DbMessage::RecordSessionStart {
+                    auth_id,
+                    start_time,
+                    raw_stream_path,
+                    response_tx: tokio::sync::oneshot::channel().0,
+                }
+            }
+            message => message,
+        };
+        self.messages.lock().unwrap().push(message);
+    }
 }
 
 pub async fn connect_to_db_with_retry(
@@ -271,59 +743,109 @@ pub async fn initialize_database_pool(
     Ok(pool)
 }
 
-// Record authentication attempt in database and return the generated UUID
+// Record authentication attempt in database under the id the server made for it
+#[allow(clippy::too_many_arguments)]
 async fn record_auth(
     pool: &PgPool,
+    sensor_id: Option<&str>,
+    auth_id: String,
     timestamp: DateTime<Utc>,
     ip: String,
     username: String,
     auth_type: String,
     password: Option<String>,
     public_key: Option<String>,
+    public_key_algorithm: Option<String>,
+    public_key_bits: Option<u32>,
+    public_key_blob: Option<String>,
     successful: bool,
     abuseipdb_data: Option<serde_json::Value>,
     ipapi_data: Option<serde_json::Value>,
-) -> Result<String, sqlx::Error> {
+) -> Result<(), sqlx::Error> {
     log::trace!("Recording auth attempt: {} from {}", username, ip);
 
-    let row = query(
-        "INSERT INTO auth (timestamp, ip, username, auth_type, password, public_key, successful, abuseipdb_data, ipapi_data)
-         VALUES ($1, $2::inet, $3, $4, $5, $6, $7, $8, $9)
-         RETURNING id"
+    query(
+        "INSERT INTO auth (id, timestamp, ip, username, auth_type, password, public_key, public_key_algorithm, public_key_bits, public_key_blob, successful, abuseipdb_data, ipapi_data, sensor_id)
+         VALUES ($1::uuid, $2, $3::inet, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)"
     )
+    .bind(&auth_id)
     .bind(timestamp)
     .bind(&ip.to_string())
     .bind(username)
     .bind(auth_type)
     .bind(password)
     .bind(public_key)
+    .bind(public_key_algorithm)
+    .bind(public_key_bits.map(|bits| bits as i32))
+    .bind(public_key_blob)
     .bind(successful)
     .bind(abuseipdb_data)
     .bind(ipapi_data)
-    .fetch_one(pool)
+    .bind(sensor_id)
+    .execute(pool)
     .await?;
 
-    let auth_id: Uuid = row.get("id");
-    Ok(auth_id.to_string())
+    Ok(())
 }
 
 // Record connection attempt in database
+#[allow(clippy::too_many_arguments)]
 async fn record_connect(
     pool: &PgPool,
+    sensor_id: Option<&str>,
     timestamp: DateTime<Utc>,
     ip: String,
     port: u16,
     local_port: u16,
+    client_version: Option<String>,
+    hassh: Option<String>,
+    hassh_algorithms: Option<String>,
+    scanner_source: Option<String>,
+    reverse_dns: Option<Vec<String>>,
 ) -> Result<(), Error> {
     log::trace!("Recording connection attempt from {} on port {}", ip, port);
 
-    query("INSERT INTO conn_track (timestamp, ip, port, local_port) VALUES ($1, $2::inet, $3, $4)")
-        .bind(timestamp)
-        .bind(&ip.to_string())
-        .bind(port as i32)
-        .bind(local_port as i32)
-        .execute(pool)
-        .await?;
+    query(
+        "INSERT INTO conn_track (timestamp, ip, port, local_port, client_version, hassh, hassh_algorithms, scanner_source, reverse_dns, sensor_id)
+         VALUES ($1, $2::inet, $3, $4, $5, $6, $7, $8, $9, $10)",
+    )
+    .bind(timestamp)
+    .bind(&ip.to_string())
+    .bind(port as i32)
+    .bind(local_port as i32)
+    .bind(client_version)
+    .bind(hassh)
+    .bind(hassh_algorithms)
+    .bind(scanner_source)
+    .bind(reverse_dns)
+    .bind(sensor_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+async fn record_protocol_anomaly(
+    pool: &PgPool,
+    timestamp: DateTime<Utc>,
+    ip: String,
+    port: u16,
+    kind: String,
+    detail: String,
+) -> Result<(), Error> {
+    log::trace!("Recording protocol anomaly {} from {}", kind, ip);
+
+    query(
+        "INSERT INTO protocol_anomalies (timestamp, ip, port, kind, detail)
+         VALUES ($1, $2::inet, $3, $4, $5)",
+    )
+    .bind(timestamp)
+    .bind(&ip)
+    .bind(port as i32)
+    .bind(kind)
+    .bind(detail)
+    .execute(pool)
+    .await?;
 
     Ok(())
 }
@@ -331,19 +853,360 @@ async fn record_connect(
 // Record command in database
 async fn record_command(
     pool: &PgPool,
+    sensor_id: Option<&str>,
     auth_id: String,
     timestamp: DateTime<Utc>,
     command: String,
+    parsed_command: Option<serde_json::Value>,
+    input_bytes: Option<usize>,
 ) -> Result<(), Error> {
     log::trace!("Recording command: {}", command);
 
     query(
-        "INSERT INTO commands (auth_id, timestamp, command)
+        "INSERT INTO commands (auth_id, timestamp, command, parsed_command, input_bytes, sensor_id)
+         VALUES ($1::uuid, $2, $3, $4, $5, $6)",
+    )
+    .bind(&auth_id)
+    .bind(timestamp)
+    .bind(command)
+    .bind(parsed_command)
+    .bind(input_bytes.map(|bytes| bytes as i64))
+    .bind(sensor_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Record a terminal allocation or resize
+async fn record_terminal_event(
+    pool: &PgPool,
+    auth_id: String,
+    timestamp: DateTime<Utc>,
+    event_type: &str,
+    term: Option<String>,
+    columns: u32,
+    rows: u32,
+) -> Result<(), Error> {
+    log::trace!(
+        "Recording terminal event: {} {}x{} for {}",
+        event_type,
+        columns,
+        rows,
+        auth_id
+    );
+
+    query(
+        "INSERT INTO terminal_events (auth_id, timestamp, event_type, term, width, height)
+         VALUES ($1::uuid, $2, $3, $4, $5, $6)",
+    )
+    .bind(&auth_id)
+    .bind(timestamp)
+    .bind(event_type)
+    .bind(term)
+    .bind(columns as i32)
+    .bind(rows as i32)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Record a subsystem request and whether it was accepted
+async fn record_subsystem_request(
+    pool: &PgPool,
+    auth_id: String,
+    timestamp: DateTime<Utc>,
+    name: String,
+    accepted: bool,
+) -> Result<(), Error> {
+    log::trace!("Recording subsystem request: {} for {}", name, auth_id);
+
+    query(
+        "INSERT INTO subsystem_requests (auth_id, timestamp, name, accepted)
+         VALUES ($1::uuid, $2, $3, $4)",
+    )
+    .bind(&auth_id)
+    .bind(timestamp)
+    .bind(name)
+    .bind(accepted)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Record a signal a client asked to be delivered on a session channel
+async fn record_channel_signal(
+    pool: &PgPool,
+    auth_id: String,
+    timestamp: DateTime<Utc>,
+    signal: String,
+) -> Result<(), Error> {
+    log::trace!("Recording signal {} for {}", signal, auth_id);
+
+    query(
+        "INSERT INTO channel_signals (auth_id, timestamp, signal)
+         VALUES ($1::uuid, $2, $3)",
+    )
+    .bind(&auth_id)
+    .bind(timestamp)
+    .bind(signal)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+async fn record_exec_probe(
+    pool: &PgPool,
+    auth_id: String,
+    timestamp: DateTime<Utc>,
+    tool: String,
+    command: String,
+) -> Result<(), Error> {
+    log::trace!("Recording {} exec probe for {}", tool, auth_id);
+
+    query(
+        "INSERT INTO exec_probes (auth_id, timestamp, tool, command)
+         VALUES ($1::uuid, $2, $3, $4)",
+    )
+    .bind(&auth_id)
+    .bind(timestamp)
+    .bind(tool)
+    .bind(command)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Record a read of a canary file
+async fn record_legal_banner_shown(
+    pool: &PgPool,
+    auth_id: String,
+    timestamp: DateTime<Utc>,
+    banner_sha256: String,
+) -> Result<(), Error> {
+    log::trace!("Recording legal banner shown to {}", auth_id);
+
+    query(
+        "INSERT INTO legal_banner_views (auth_id, shown_at, banner_sha256)
          VALUES ($1::uuid, $2, $3)",
     )
     .bind(&auth_id)
     .bind(timestamp)
+    .bind(banner_sha256)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Fill in the first command of the session's latest showing still waiting for one
+async fn record_legal_banner_response(
+    pool: &PgPool,
+    auth_id: String,
+    timestamp: DateTime<Utc>,
+    command: String,
+) -> Result<(), Error> {
+    log::trace!(
+        "Recording first command after the legal banner for {}",
+        auth_id
+    );
+
+    query(
+        "UPDATE legal_banner_views SET first_command = $3, first_command_at = $2
+         WHERE id = (
+             SELECT id FROM legal_banner_views
+             WHERE auth_id = $1::uuid AND first_command IS NULL
+             ORDER BY shown_at DESC
+             LIMIT 1
+         )",
+    )
+    .bind(&auth_id)
+    .bind(timestamp)
+    .bind(command)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+async fn record_canary_hit(
+    pool: &PgPool,
+    auth_id: String,
+    timestamp: DateTime<Utc>,
+    path: String,
+    command: String,
+) -> Result<(), Error> {
+    log::trace!("Recording canary hit: {} for {}", path, auth_id);
+
+    query(
+        "INSERT INTO canary_events (auth_id, timestamp, path, command)
+         VALUES ($1::uuid, $2, $3, $4)",
+    )
+    .bind(&auth_id)
+    .bind(timestamp)
+    .bind(path)
+    .bind(command)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Record a file being run, with a copy of the file
+#[allow(clippy::too_many_arguments)]
+async fn record_execution(
+    pool: &PgPool,
+    auth_id: String,
+    timestamp: DateTime<Utc>,
+    path: String,
+    command: String,
+    outcome: String,
+    file_hash: Option<String>,
+    content: Option<Vec<u8>>,
+) -> Result<(), Error> {
+    log::trace!(
+        "Recording execution: {} ({}) for {}",
+        path,
+        outcome,
+        auth_id
+    );
+
+    query(
+        "INSERT INTO executions (auth_id, timestamp, path, command, outcome, file_size, file_hash, content)
+         VALUES ($1::uuid, $2, $3, $4, $5, $6, $7, $8)",
+    )
+    .bind(&auth_id)
+    .bind(timestamp)
+    .bind(path)
+    .bind(command)
+    .bind(outcome)
+    .bind(content.as_ref().map(|content| content.len() as i64))
+    .bind(file_hash)
+    .bind(content)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn record_dns_query(
+    pool: &PgPool,
+    auth_id: String,
+    timestamp: DateTime<Utc>,
+    name: String,
+    record_type: String,
+    command: String,
+    status: String,
+    answers: Vec<String>,
+) -> Result<(), Error> {
+    log::trace!(
+        "Recording DNS query: {} {} ({}) for {}",
+        name,
+        record_type,
+        status,
+        auth_id
+    );
+
+    query(
+        "INSERT INTO dns_queries (auth_id, timestamp, name, record_type, command, status, answers)
+         VALUES ($1::uuid, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(&auth_id)
+    .bind(timestamp)
+    .bind(name)
+    .bind(record_type)
+    .bind(command)
+    .bind(status)
+    .bind(answers)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+async fn record_shutdown_attempt(
+    pool: &PgPool,
+    auth_id: String,
+    timestamp: DateTime<Utc>,
+    command: String,
+    action: String,
+    permitted: bool,
+    immediate: bool,
+) -> Result<(), Error> {
+    log::trace!("Recording shutdown attempt: {} for {}", command, auth_id);
+
+    query(
+        "INSERT INTO shutdown_attempts (auth_id, timestamp, command, action, permitted, immediate)
+         VALUES ($1::uuid, $2, $3, $4, $5, $6)",
+    )
+    .bind(&auth_id)
+    .bind(timestamp)
+    .bind(command)
+    .bind(action)
+    .bind(permitted)
+    .bind(immediate)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+async fn record_firewall_change(
+    pool: &PgPool,
+    auth_id: String,
+    timestamp: DateTime<Utc>,
+    tool: String,
+    command: String,
+    permitted: bool,
+) -> Result<(), Error> {
+    log::trace!("Recording firewall change: {} for {}", command, auth_id);
+
+    query(
+        "INSERT INTO firewall_changes (auth_id, timestamp, tool, command, permitted)
+         VALUES ($1::uuid, $2, $3, $4, $5)",
+    )
+    .bind(&auth_id)
+    .bind(timestamp)
+    .bind(tool)
     .bind(command)
+    .bind(permitted)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn record_process_kill(
+    pool: &PgPool,
+    auth_id: String,
+    timestamp: DateTime<Utc>,
+    tool: String,
+    command: String,
+    signal: String,
+    pids: Vec<u32>,
+    names: Vec<String>,
+    permitted: bool,
+) -> Result<(), Error> {
+    log::trace!("Recording process kill: {} for {}", command, auth_id);
+
+    query(
+        "INSERT INTO process_kills (auth_id, timestamp, tool, command, signal, pids, names, permitted)
+         VALUES ($1::uuid, $2, $3, $4, $5, $6, $7, $8)",
+    )
+    .bind(&auth_id)
+    .bind(timestamp)
+    .bind(tool)
+    .bind(command)
+    .bind(signal)
+    .bind(pids.into_iter().map(|pid| pid as i32).collect::<Vec<_>>())
+    .bind(names)
+    .bind(permitted)
     .execute(pool)
     .await?;
 
@@ -354,8 +1217,10 @@ async fn record_command(
 // duration_seconds are left NULL until the session closes. Returns the new id.
 async fn record_session_start(
     pool: &PgPool,
+    sensor_id: Option<&str>,
     auth_id: String,
     start_time: DateTime<Utc>,
+    raw_stream_path: Option<String>,
 ) -> Result<String, Error> {
     log::trace!(
         "Recording session start: auth={} at {}",
@@ -364,12 +1229,14 @@ async fn record_session_start(
     );
 
     let row = query(
-        "INSERT INTO sessions (auth_id, start_time)
-         VALUES ($1::uuid, $2)
+        "INSERT INTO sessions (auth_id, start_time, raw_stream_path, sensor_id)
+         VALUES ($1::uuid, $2, $3, $4)
          RETURNING id",
     )
     .bind(&auth_id)
     .bind(start_time)
+    .bind(raw_stream_path)
+    .bind(sensor_id)
     .fetch_one(pool)
     .await?;
 
@@ -405,9 +1272,35 @@ async fn record_session_end(
     Ok(())
 }
 
+// Mark a session as closed by --disconnect-after-commands
+async fn record_session_cut_short(
+    pool: &PgPool,
+    session_id: String,
+    commands: u32,
+) -> Result<(), Error> {
+    log::trace!(
+        "Recording session {} cut short after {} commands",
+        session_id,
+        commands
+    );
+
+    query(
+        "UPDATE sessions
+         SET cut_short_after_commands = $2
+         WHERE id = $1::uuid",
+    )
+    .bind(&session_id)
+    .bind(commands as i32)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
 // Record file upload in database
 async fn record_file_upload(
     pool: &PgPool,
+    sensor_id: Option<&str>,
     auth_id: String,
     timestamp: DateTime<Utc>,
     filename: String,
@@ -418,18 +1311,17 @@ async fn record_file_upload(
     detected_mime_type: Option<String>,
     format_mismatch: bool,
     file_entropy: Option<f64>,
-    binary_data: Vec<u8>,
+    truncated: bool,
+    yara_matches: Vec<String>,
+    stored_path: Option<String>,
+    binary_data: Option<Vec<u8>>,
 ) -> Result<(), Error> {
-    log::trace!(
-        "Recording file upload: {} ({} bytes)",
-        filename,
-        binary_data.len()
-    );
+    log::trace!("Recording file upload: {} ({} bytes)", filename, file_size);
 
     query(
         "INSERT INTO uploaded_files (auth_id, timestamp, filename, filepath, file_size, file_hash, 
-                                   claimed_mime_type, detected_mime_type, format_mismatch, file_entropy, binary_data)
-         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)"
+                                   claimed_mime_type, detected_mime_type, format_mismatch, file_entropy, truncated, yara_matches, stored_path, binary_data, sensor_id)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)"
     )
     .bind(&auth_id)
     .bind(timestamp)
@@ -441,7 +1333,11 @@ async fn record_file_upload(
     .bind(detected_mime_type)
     .bind(format_mismatch)
     .bind(file_entropy)
+    .bind(truncated)
+    .bind(yara_matches)
+    .bind(stored_path)
     .bind(binary_data)
+    .bind(sensor_id)
     .execute(pool)
     .await?;
 
@@ -638,3 +1534,117 @@ pub async fn get_ipapi_check(
         }
     }
 }
+
+/// A login as the fake shell's `last` and `lastb` show it
+#[derive(Debug, Clone)]
+pub struct RecentLogin {
+    pub username: String,
+    pub ip: String,
+    pub start: DateTime<Utc>,
+    /// None while the session is live
+    pub end: Option<DateTime<Utc>>,
+}
+
+// Get the most recent sessions with who opened them, newest first
+pub async fn get_recent_sessions(pool: &PgPool, limit: i64) -> Result<Vec<RecentLogin>, Error> {
+    let rows = query(
+        "SELECT a.username, host(a.ip) AS ip_text, s.start_time, s.end_time
+         FROM sessions s JOIN auth a ON s.auth_id = a.id
+         ORDER BY s.start_time DESC
+         LIMIT $1",
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| RecentLogin {
+            username: row.get("username"),
+            ip: row.get("ip_text"),
+            start: row.get("start_time"),
+            end: row.get("end_time"),
+        })
+        .collect())
+}
+
+// Get the most recent auth attempts that were rejected or never opened a
+// session, newest first. On a real server those guesses would have failed
+pub async fn get_recent_failed_logins(
+    pool: &PgPool,
+    limit: i64,
+) -> Result<Vec<RecentLogin>, Error> {
+    let rows = query(
+        "SELECT a.username, host(a.ip) AS ip_text, a.timestamp
+         FROM auth a
+         WHERE NOT a.successful
+            OR NOT EXISTS (SELECT 1 FROM sessions s WHERE s.auth_id = a.id)
+         ORDER BY a.timestamp DESC
+         LIMIT $1",
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let timestamp: DateTime<Utc> = row.get("timestamp");
+            RecentLogin {
+                username: row.get("username"),
+                ip: row.get("ip_text"),
+                start: timestamp,
+                end: Some(timestamp),
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn memory_sink_records_without_a_database() {
+        let sink = MemorySink::new();
+        let (tx, rx) = mpsc::channel(8);
+        let handler = tokio::spawn(run_db_handler(rx, sink.clone()));
+
+        tx.send(DbMessage::RecordCommand {
+            auth_id: "a1".to_string(),
+            timestamp: Utc::now(),
+            command: "uname -a".to_string(),
+            parsed_command: None,
+            input_bytes: None,
+        })
+        .await
+        .unwrap();
+        let (response_tx, response_rx) = tokio::sync::oneshot::channel();
+        tx.send(DbMessage::RecordSessionStart {
+            auth_id: "a1".to_string(),
+            start_time: Utc::now(),
+            raw_stream_path: None,
+            response_tx,
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            response_rx.await.unwrap(),
+            Ok("00000000-0000-0000-0000-000000000001".to_string())
+        );
+        tx.send(DbMessage::Shutdown).await.unwrap();
+        handler.await.unwrap();
+
+        let messages = sink.take();
+        assert_eq!(messages.len(), 2);
+        assert!(matches!(
+            &messages[0],
+            DbMessage::RecordCommand { command, .. } if command == "uname -a"
+        ));
+        assert!(matches!(
+            &messages[1],
+            DbMessage::RecordSessionStart { auth_id, .. } if auth_id == "a1"
+        ));
+        assert!(sink.take().is_empty());
+    }
+}
@@ -0,0 +1,33 @@
+//! Canonical form of client addresses.
+//!
+//! A listener on `[::]` sees IPv4 clients as IPv4-mapped IPv6 addresses
+//! (`::ffff:192.0.2.1`). Everything is stored, looked up and sent to the
+//! enrichment APIs in the canonical form, so the same client is always the
+//! same `inet` value and AbuseIPDB/ip-api get the address they expect.
+
+use std::net::IpAddr;
+
+/// Unmap IPv4-mapped IPv6 addresses, everything else is returned as is
+pub fn canonical_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        IpAddr::V4(_) => ip,
+    }
+}
+
+/// Parse an address given by a user, e.g. on the report generator command line.
+/// Accepts the bracketed `[2001:db8::1]` form and the host prefix length
+/// (`/32`, `/128`) that PostgreSQL's `inet::text` appends
+pub fn parse_ip(input: &str) -> Option<IpAddr> {
+    let input = input.trim();
+    let input = input
+        .strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .unwrap_or(input);
+    let input = match input.split_once('/') {
+        Some((address, "32" | "128")) => address,
+        Some(_) => return None,
+        None => input,
+    };
+    input.parse().ok().map(canonical_ip)
+}
@@ -1,4 +1,7 @@
 pub mod dashboard;
 pub mod dashboard_config;
 pub mod db;
+pub mod ip;
+pub mod recording;
 pub mod report;
+pub mod sqlite;
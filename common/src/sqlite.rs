@@ -0,0 +1,641 @@
+//! SQLite behind [`run_db_handler`](crate::db::run_db_handler), for a single
+//! sensor without a PostgreSQL server. A `database_url` like
+//! `sqlite://honeypot.db` selects it, the file is created on first start.
+//!
+//! Everything the honeypot records goes to the same tables as with PostgreSQL,
+//! with addresses, UUIDs, JSON and lists stored as TEXT. The dashboard, reports,
+//! `replay`, the AbuseIPDB and ip-api caches and `last` from recorded logins
+//! query PostgreSQL and are not available on SQLite.
+
+use crate::db::{DbMessage, DbSink};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool};
+use sqlx::{Error, Row, query};
+use std::str::FromStr;
+
+/// Whether `database_url` points at an SQLite file rather than PostgreSQL
+pub fn is_sqlite_url(database_url: &str) -> bool {
+    database_url.starts_with("sqlite:")
+}
+
+/// Open (creating it if missing) the database file and run its migrations
+pub async fn initialize_sqlite_pool(
+    database_url: &str,
+    skip_migrations: bool,
+) -> Result<SqlitePool, Error> {
+    log::trace!("Opening SQLite database");
+
+    let options = SqliteConnectOptions::from_str(database_url)?
+        .create_if_missing(true)
+        // sqlite3 reading the file doesn't block the honeypot's writes
+        .journal_mode(SqliteJournalMode::Wal);
+    let pool = SqlitePool::connect_with(options).await?;
+
+    log::trace!("Running database migrations");
+    if !skip_migrations {
+        sqlx::migrate!("./migrations_sqlite").run(&pool).await?;
+    }
+
+    log::info!("Database initialized successfully");
+    Ok(pool)
+}
+
+/// Lists PostgreSQL keeps in arrays, as a JSON array
+fn json_list<T: Into<serde_json::Value>>(items: Vec<T>) -> String {
+    serde_json::Value::from(items).to_string()
+}
+
+/// SQLite behind [`run_db_handler`](crate::db::run_db_handler)
+#[derive(Debug, Clone)]
+pub struct SqliteSink {
+    pub pool: SqlitePool,
+    /// --sensor-id, stored with connections, logins, commands, sessions and uploads
+    pub sensor_id: Option<String>,
+}
+
+impl DbSink for SqliteSink {
+    async fn ready(&self) -> Result<(), String> {
+        self.pool
+            .acquire()
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    async fn record(&self, message: DbMessage) {
+        let pool = &self.pool;
+        let sensor_id = self.sensor_id.as_deref();
+        let (what, result) = match message {
+            DbMessage::RecordConnect {
+                timestamp,
+                ip,
+                port,
+                local_port,
+                client_version,
+                hassh,
+                hassh_algorithms,
+                scanner_source,
+                reverse_dns,
+            } => (
+                "connect event",
+                query(
+                    "INSERT INTO conn_track (timestamp, ip, port, local_port, client_version, hassh, hassh_algorithms, scanner_source, reverse_dns, sensor_id)
+                     VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                )
+                .bind(timestamp)
+                .bind(ip)
+                .bind(port as i32)
+                .bind(local_port as i32)
+                .bind(client_version)
+                .bind(hassh)
+                .bind(hassh_algorithms)
+                .bind(scanner_source)
+                .bind(reverse_dns.map(json_list))
+                .bind(sensor_id)
+                .execute(pool)
+                .await,
+            ),
+            DbMessage::RecordAuth {
+                auth_id,
+                timestamp,
+                ip,
+                username,
+                auth_type,
+                password,
+                public_key,
+                public_key_algorithm,
+                public_key_bits,
+                public_key_blob,
+                successful,
+                abuseipdb_data,
+                ipapi_data,
+            } => (
+                "auth",
+                query(
+                    "INSERT INTO auth (id, timestamp, ip, username, auth_type, password, public_key, public_key_algorithm, public_key_bits, public_key_blob, successful, abuseipdb_data, ipapi_data, sensor_id)
+                     VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                )
+                .bind(auth_id)
+                .bind(timestamp)
+                .bind(ip)
+                .bind(username)
+                .bind(auth_type)
+                .bind(password)
+                .bind(public_key)
+                .bind(public_key_algorithm)
+                .bind(public_key_bits.map(|bits| bits as i32))
+                .bind(public_key_blob)
+                .bind(successful)
+                .bind(abuseipdb_data.map(|data| data.to_string()))
+                .bind(ipapi_data.map(|data| data.to_string()))
+                .bind(sensor_id)
+                .execute(pool)
+                .await,
+            ),
+            DbMessage::RecordCommand {
+                auth_id,
+                timestamp,
+                command,
+                parsed_command,
+                input_bytes,
+            } => (
+                "command",
+                query(
+                    "INSERT INTO commands (auth_id, timestamp, command, parsed_command, input_bytes, sensor_id)
+                     VALUES (?, ?, ?, ?, ?, ?)",
+                )
+                .bind(auth_id)
+                .bind(timestamp)
+                .bind(command)
+                .bind(parsed_command.map(|parsed| parsed.to_string()))
+                .bind(input_bytes.map(|bytes| bytes as i64))
+                .bind(sensor_id)
+                .execute(pool)
+                .await,
+            ),
+            DbMessage::RecordTerminalEvent {
+                auth_id,
+                timestamp,
+                event_type,
+                term,
+                columns,
+                rows,
+            } => (
+                "terminal event",
+                query(
+                    "INSERT INTO terminal_events (auth_id, timestamp, event_type, term, width, height)
+                     VALUES (?, ?, ?, ?, ?, ?)",
+                )
+                .bind(auth_id)
+                .bind(timestamp)
+                .bind(event_type)
+                .bind(term)
+                .bind(columns as i32)
+                .bind(rows as i32)
+                .execute(pool)
+                .await,
+            ),
+            DbMessage::RecordSubsystemRequest {
+                auth_id,
+                timestamp,
+                name,
+                accepted,
+            } => (
+                "subsystem request",
+                query(
+                    "INSERT INTO subsystem_requests (auth_id, timestamp, name, accepted)
+                     VALUES (?, ?, ?, ?)",
+                )
+                .bind(auth_id)
+                .bind(timestamp)
+                .bind(name)
+                .bind(accepted)
+                .execute(pool)
+                .await,
+            ),
+            DbMessage::RecordChannelSignal {
+                auth_id,
+                timestamp,
+                signal,
+            } => (
+                "channel signal",
+                query(
+                    "INSERT INTO channel_signals (auth_id, timestamp, signal)
+                     VALUES (?, ?, ?)",
+                )
+                .bind(auth_id)
+                .bind(timestamp)
+                .bind(signal)
+                .execute(pool)
+                .await,
+            ),
+            DbMessage::RecordExecProbe {
+                auth_id,
+                timestamp,
+                tool,
+                command,
+            } => (
+                "exec probe",
+                query(
+                    "INSERT INTO exec_probes (auth_id, timestamp, tool, command)
+                     VALUES (?, ?, ?, ?)",
+                )
+                .bind(auth_id)
+                .bind(timestamp)
+                .bind(tool)
+                .bind(command)
+                .execute(pool)
+                .await,
+            ),
+            DbMessage::RecordCanaryHit {
+                auth_id,
+                timestamp,
+                path,
+                command,
+            } => (
+                "canary hit",
+                query(
+                    "INSERT INTO canary_events (auth_id, timestamp, path, command)
+                     VALUES (?, ?, ?, ?)",
+                )
+                .bind(auth_id)
+                .bind(timestamp)
+                .bind(path)
+                .bind(command)
+                .execute(pool)
+                .await,
+            ),
+            DbMessage::RecordLegalBannerShown {
+                auth_id,
+                timestamp,
+                banner_sha256,
+            } => (
+                "legal banner",
+                query(
+                    "INSERT INTO legal_banner_views (auth_id, shown_at, banner_sha256)
+                     VALUES (?, ?, ?)",
+                )
+                .bind(auth_id)
+                .bind(timestamp)
+                .bind(banner_sha256)
+                .execute(pool)
+                .await,
+            ),
+            DbMessage::RecordLegalBannerResponse {
+                auth_id,
+                timestamp,
+                command,
+            } => (
+                "legal banner response",
+                query(
+                    "UPDATE legal_banner_views SET first_command = ?3, first_command_at = ?2
+                     WHERE id = (
+                         SELECT id FROM legal_banner_views
+                         WHERE auth_id = ?1 AND first_command IS NULL
+                         ORDER BY shown_at DESC
+                         LIMIT 1
+                     )",
+                )
+                .bind(auth_id)
+                .bind(timestamp)
+                .bind(command)
+                .execute(pool)
+                .await,
+            ),
+            DbMessage::RecordExecution {
+                auth_id,
+                timestamp,
+                path,
+                command,
+                outcome,
+                file_hash,
+                content,
+            } => (
+                "execution",
+                query(
+                    "INSERT INTO executions (auth_id, timestamp, path, command, outcome, file_size, file_hash, content)
+                     VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+                )
+                .bind(auth_id)
+                .bind(timestamp)
+                .bind(path)
+                .bind(command)
+                .bind(outcome)
+                .bind(content.as_ref().map(|content| content.len() as i64))
+                .bind(file_hash)
+                .bind(content)
+                .execute(pool)
+                .await,
+            ),
+            DbMessage::RecordProtocolAnomaly {
+                timestamp,
+                ip,
+                port,
+                kind,
+                detail,
+            } => (
+                "protocol anomaly",
+                query(
+                    "INSERT INTO protocol_anomalies (timestamp, ip, port, kind, detail)
+                     VALUES (?, ?, ?, ?, ?)",
+                )
+                .bind(timestamp)
+                .bind(ip)
+                .bind(port as i32)
+                .bind(kind)
+                .bind(detail)
+                .execute(pool)
+                .await,
+            ),
+            DbMessage::RecordDnsQuery {
+                auth_id,
+                timestamp,
+                name,
+                record_type,
+                command,
+                status,
+                answers,
+            } => (
+                "DNS query",
+                query(
+                    "INSERT INTO dns_queries (auth_id, timestamp, name, record_type, command, status, answers)
+                     VALUES (?, ?, ?, ?, ?, ?, ?)",
+                )
+                .bind(auth_id)
+                .bind(timestamp)
+                .bind(name)
+                .bind(record_type)
+                .bind(command)
+                .bind(status)
+                .bind(json_list(answers))
+                .execute(pool)
+                .await,
+            ),
+            DbMessage::RecordShutdownAttempt {
+                auth_id,
+                timestamp,
+                command,
+                action,
+                permitted,
+                immediate,
+            } => (
+                "shutdown attempt",
+                query(
+                    "INSERT INTO shutdown_attempts (auth_id, timestamp, command, action, permitted, immediate)
+                     VALUES (?, ?, ?, ?, ?, ?)",
+                )
+                .bind(auth_id)
+                .bind(timestamp)
+                .bind(command)
+                .bind(action)
+                .bind(permitted)
+                .bind(immediate)
+                .execute(pool)
+                .await,
+            ),
+            DbMessage::RecordFirewallChange {
+                auth_id,
+                timestamp,
+                tool,
+                command,
+                permitted,
+            } => (
+                "firewall change",
+                query(
+                    "INSERT INTO firewall_changes (auth_id, timestamp, tool, command, permitted)
+                     VALUES (?, ?, ?, ?, ?)",
+                )
+                .bind(auth_id)
+                .bind(timestamp)
+                .bind(tool)
+                .bind(command)
+                .bind(permitted)
+                .execute(pool)
+                .await,
+            ),
+            DbMessage::RecordProcessKill {
+                auth_id,
+                timestamp,
+                tool,
+                command,
+                signal,
+                pids,
+                names,
+                permitted,
+            } => (
+                "process kill",
+                query(
+                    "INSERT INTO process_kills (auth_id, timestamp, tool, command, signal, pids, names, permitted)
+                     VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+                )
+                .bind(auth_id)
+                .bind(timestamp)
+                .bind(tool)
+                .bind(command)
+                .bind(signal)
+                .bind(json_list(pids))
+                .bind(json_list(names))
+                .bind(permitted)
+                .execute(pool)
+                .await,
+            ),
+            DbMessage::RecordSessionStart {
+                auth_id,
+                start_time,
+                raw_stream_path,
+                response_tx,
+            } => {
+                let result = query(
+                    "INSERT INTO sessions (auth_id, start_time, raw_stream_path, sensor_id)
+                     VALUES (?, ?, ?, ?)
+                     RETURNING id",
+                )
+                .bind(auth_id)
+                .bind(start_time)
+                .bind(raw_stream_path)
+                .bind(sensor_id)
+                .fetch_one(pool)
+                .await
+                .map(|row| row.get::<String, _>("id"));
+
+                let response = result.map_err(|e| {
+                    log::error!("Database error recording session start: {}", e);
+                    e.to_string()
+                });
+                let _ = response_tx.send(response);
+                return;
+            }
+            DbMessage::RecordSessionEnd {
+                session_id,
+                end_time,
+                duration_seconds,
+            } => (
+                "session end",
+                query(
+                    "UPDATE sessions
+                     SET end_time = ?2, duration_seconds = ?3
+                     WHERE id = ?1",
+                )
+                .bind(session_id)
+                .bind(end_time)
+                .bind(duration_seconds)
+                .execute(pool)
+                .await,
+            ),
+            DbMessage::RecordSessionCutShort {
+                session_id,
+                commands,
+            } => (
+                "cut short session",
+                query(
+                    "UPDATE sessions
+                     SET cut_short_after_commands = ?2
+                     WHERE id = ?1",
+                )
+                .bind(session_id)
+                .bind(commands as i32)
+                .execute(pool)
+                .await,
+            ),
+            DbMessage::RecordFileUpload {
+                auth_id,
+                timestamp,
+                filename,
+                filepath,
+                file_size,
+                file_hash,
+                claimed_mime_type,
+                detected_mime_type,
+                format_mismatch,
+                file_entropy,
+                truncated,
+                yara_matches,
+                stored_path,
+                binary_data,
+            } => (
+                "file upload",
+                query(
+                    "INSERT INTO uploaded_files (auth_id, timestamp, filename, filepath, file_size, file_hash,
+                                                 claimed_mime_type, detected_mime_type, format_mismatch, file_entropy, truncated, yara_matches, stored_path, binary_data, sensor_id)
+                     VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                )
+                .bind(auth_id)
+                .bind(timestamp)
+                .bind(filename)
+                .bind(filepath)
+                .bind(file_size as i64)
+                .bind(file_hash)
+                .bind(claimed_mime_type)
+                .bind(detected_mime_type)
+                .bind(format_mismatch)
+                .bind(file_entropy)
+                .bind(truncated)
+                .bind(json_list(yara_matches))
+                .bind(stored_path)
+                .bind(binary_data)
+                .bind(sensor_id)
+                .execute(pool)
+                .await,
+            ),
+            DbMessage::Shutdown => return,
+        };
+        if let Err(e) = result {
+            log::error!("Database error recording {}: {}", what, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::run_db_handler;
+    use chrono::Utc;
+    use sqlx::sqlite::SqlitePoolOptions;
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn records_a_session() {
+        // Every connection to :memory: is a database of its own
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations_sqlite")
+            .run(&pool)
+            .await
+            .unwrap();
+        let sink = SqliteSink {
+            pool: pool.clone(),
+            sensor_id: Some("lab".to_string()),
+        };
+        let (tx, rx) = mpsc::channel(8);
+        let handler = tokio::spawn(run_db_handler(rx, sink));
+
+        let auth_id = "6f1c2a52-8f0e-4a51-9d0c-3d1f4c7a9b10".to_string();
+        tx.send(DbMessage::RecordAuth {
+            auth_id: auth_id.clone(),
+            timestamp: Utc::now(),
+            ip: "192.0.2.7".to_string(),
+            username: "root".to_string(),
+            auth_type: "password".to_string(),
+            password: Some("123456".to_string()),
+            public_key: None,
+            public_key_algorithm: None,
+            public_key_bits: None,
+            public_key_blob: None,
+            successful: true,
+            abuseipdb_data: None,
+            ipapi_data: None,
+        })
+        .await
+        .unwrap();
+        let (response_tx, response_rx) = tokio::sync::oneshot::channel();
+        tx.send(DbMessage::RecordSessionStart {
+            auth_id: auth_id.clone(),
+            start_time: Utc::now(),
+            raw_stream_path: None,
+            response_tx,
+        })
+        .await
+        .unwrap();
+        let session_id = response_rx.await.unwrap().unwrap();
+        assert_eq!(session_id.len(), 36);
+        tx.send(DbMessage::RecordDnsQuery {
+            auth_id: auth_id.clone(),
+            timestamp: Utc::now(),
+            name: "c2.example".to_string(),
+            record_type: "A".to_string(),
+            command: "dig".to_string(),
+            status: "NOERROR".to_string(),
+            answers: vec!["192.0.2.1".to_string()],
+        })
+        .await
+        .unwrap();
+        tx.send(DbMessage::RecordProcessKill {
+            auth_id: auth_id.clone(),
+            timestamp: Utc::now(),
+            tool: "pkill".to_string(),
+            command: "pkill -9 xmrig".to_string(),
+            signal: "KILL".to_string(),
+            pids: vec![4242],
+            names: vec!["xmrig".to_string()],
+            permitted: true,
+        })
+        .await
+        .unwrap();
+        tx.send(DbMessage::RecordSessionEnd {
+            session_id: session_id.clone(),
+            end_time: Utc::now(),
+            duration_seconds: 42,
+        })
+        .await
+        .unwrap();
+        tx.send(DbMessage::Shutdown).await.unwrap();
+        handler.await.unwrap();
+
+        let row = query(
+            "SELECT a.username, a.sensor_id, s.duration_seconds, d.answers
+             FROM auth a
+             JOIN sessions s ON s.auth_id = a.id
+             JOIN dns_queries d ON d.auth_id = a.id
+             WHERE s.id = ?",
+        )
+        .bind(&session_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(row.get::<String, _>("username"), "root");
+        assert_eq!(row.get::<String, _>("sensor_id"), "lab");
+        assert_eq!(row.get::<i64, _>("duration_seconds"), 42);
+        assert_eq!(row.get::<String, _>("answers"), r#"["192.0.2.1"]"#);
+
+        let row = query("SELECT signal, pids, names FROM process_kills WHERE auth_id = ?")
+            .bind(&auth_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(row.get::<String, _>("signal"), "KILL");
+        assert_eq!(row.get::<String, _>("pids"), "[4242]");
+        assert_eq!(row.get::<String, _>("names"), r#"["xmrig"]"#);
+    }
+}
@@ -0,0 +1,360 @@
+use super::busybox::write_file;
+use crate::filesystem::fs2::{FileContent, FileSystem};
+
+/// System accounts of a stock Ubuntu 20.04 server
+const SYSTEM_PASSWD: &str = "root:x:0:0:root:/root:/bin/bash\n\
+    daemon:x:1:1:daemon:/usr/sbin:/usr/sbin/nologin\n\
+    bin:x:2:2:bin:/bin:/usr/sbin/nologin\n\
+    sys:x:3:3:sys:/dev:/usr/sbin/nologin\n\
+    sync:x:4:65534:sync:/bin:/bin/sync\n\
+    games:x:5:60:games:/usr/games:/usr/sbin/nologin\n\
+    man:x:6:12:man:/var/cache/man:/usr/sbin/nologin\n\
+    lp:x:7:7:lp:/var/spool/lpd:/usr/sbin/nologin\n\
+    mail:x:8:8:mail:/var/mail:/usr/sbin/nologin\n\
+    news:x:9:9:news:/var/spool/news:/usr/sbin/nologin\n\
+    uucp:x:10:10:uucp:/var/spool/uucp:/usr/sbin/nologin\n\
+    proxy:x:13:13:proxy:/bin:/usr/sbin/nologin\n\
+    www-data:x:33:33:www-data:/var/www:/usr/sbin/nologin\n\
+    backup:x:34:34:backup:/var/backups:/usr/sbin/nologin\n\
+    list:x:38:38:Mailing List Manager:/var/list:/usr/sbin/nologin\n\
+    irc:x:39:39:ircd:/var/run/ircd:/usr/sbin/nologin\n\
+    gnats:x:41:41:Gnats Bug-Reporting System (admin):/var/lib/gnats:/usr/sbin/nologin\n\
+    nobody:x:65534:65534:nobody:/nonexistent:/usr/sbin/nologin\n\
+    systemd-network:x:100:102:systemd Network Management,,,:/run/systemd:/usr/sbin/nologin\n\
+    systemd-resolve:x:101:103:systemd Resolver,,,:/run/systemd:/usr/sbin/nologin\n\
+    systemd-timesync:x:102:104:systemd Time Synchronization,,,:/run/systemd:/usr/sbin/nologin\n\
+    messagebus:x:103:106::/nonexistent:/usr/sbin/nologin\n\
+    syslog:x:104:110::/home/syslog:/usr/sbin/nologin\n\
+    _apt:x:105:65534::/nonexistent:/usr/sbin/nologin\n\
+    tss:x:106:111:TPM software stack,,,:/var/lib/tpm:/bin/false\n\
+    uuidd:x:107:112::/run/uuidd:/usr/sbin/nologin\n\
+    tcpdump:x:108:113::/nonexistent:/usr/sbin/nologin\n\
+    landscape:x:109:115::/var/lib/landscape:/usr/sbin/nologin\n\
+    pollinate:x:110:1::/var/cache/pollinate:/bin/false\n\
+    sshd:x:111:65534::/run/sshd:/usr/sbin/nologin\n\
+    systemd-coredump:x:999:999:systemd Core Dumper:/:/usr/sbin/nologin\n\
+    lxd:x:998:100::/var/snap/lxd/common/lxd:/bin/false\n";
+
+/// System groups, `(name, gid, members get added)`. The ones `id` lists for a
+/// login user get every configured user as member
+const SYSTEM_GROUPS: &[(&str, u32, bool)] = &[
+    ("root", 0, false),
+    ("daemon", 1, false),
+    ("bin", 2, false),
+    ("sys", 3, false),
+    ("adm", 4, true),
+    ("tty", 5, false),
+    ("disk", 6, false),
+    ("lp", 7, false),
+    ("mail", 8, false),
+    ("news", 9, false),
+    ("uucp", 10, false),
+    ("man", 12, false),
+    ("proxy", 13, false),
+    ("kmem", 15, false),
+    ("dialout", 20, false),
+    ("fax", 21, false),
+    ("voice", 22, false),
+    ("cdrom", 24, true),
+    ("floppy", 25, false),
+    ("tape", 26, false),
+    ("sudo", 27, true),
+    ("audio", 29, false),
+    ("dip", 30, true),
+    ("www-data", 33, false),
+    ("backup", 34, false),
+    ("operator", 37, false),
+    ("list", 38, false),
+    ("irc", 39, false),
+    ("src", 40, false),
+    ("gnats", 41, false),
+    ("shadow", 42, false),
+    ("utmp", 43, false),
+    ("video", 44, false),
+    ("sasl", 45, false),
+    ("plugdev", 46, true),
+    ("staff", 50, false),
+    ("games", 60, false),
+    ("users", 100, false),
+    ("nogroup", 65534, false),
+    ("systemd-journal", 101, false),
+    ("systemd-network", 102, false),
+    ("systemd-resolve", 103, false),
+    ("systemd-timesync", 104, false),
+    ("crontab", 105, false),
+    ("messagebus", 106, false),
+    ("input", 107, false),
+    ("kvm", 108, false),
+    ("render", 109, false),
+    ("syslog", 110, false),
+    ("tss", 111, false),
+    ("uuidd", 112, false),
+    ("tcpdump", 113, false),
+    ("ssh", 114, false),
+    ("landscape", 115, false),
+    ("lpadmin", 120, true),
+    ("lxd", 131, true),
+    ("sambashare", 132, true),
+    ("systemd-coredump", 999, false),
+];
+
+/// First uid/gid handed to login users, like adduser does
+const FIRST_USER_ID: u32 = 1000;
+
+/// Write /etc/hostname, /etc/hosts, /etc/passwd and /etc/group for `hostname`
+/// and the login `users`, so `cat /etc/hostname` agrees with `uname -n` and
+/// /etc/passwd with `id`. Replaces whatever the base image shipped.
+pub fn write_account_files(
+    fs: &mut FileSystem,
+    hostname: &str,
+    users: &[String],
+) -> std::io::Result<()> {
+    if fs.get_file("/etc").is_err() {
+        fs.create_directory("/etc")?;
+    }
+
+    // System accounts already have their entry, and every user gets only one
+    let mut login_users: Vec<&String> = Vec::new();
+    for user in users {
+        let is_system = SYSTEM_PASSWD
+            .lines()
+            .any(|line| line.split(':').next() == Some(user.as_str()));
+        if !is_system && !login_users.contains(&user) {
+            login_users.push(user);
+        }
+    }
+    let users = login_users;
+
+    let mut passwd = SYSTEM_PASSWD.to_string();
+    for (id, user) in (FIRST_USER_ID..).zip(&users) {
+        passwd.push_str(&format!(
+            "{0}:x:{1}:{1}:{0},,,:/home/{0}:/bin/bash\n",
+            user, id
+        ));
+    }
+
+    let members = users
+        .iter()
+        .map(|user| user.as_str())
+        .collect::<Vec<_>>()
+        .join(",");
+    let mut group = String::new();
+    for (name, gid, login_group) in SYSTEM_GROUPS {
+        let members = match *name {
+            // syslog is in adm on every Ubuntu install
+            "adm" if members.is_empty() => "syslog".to_string(),
+            "adm" => format!("syslog,{}", members),
+            _ if *login_group => members.clone(),
+            _ => String::new(),
+        };
+        group.push_str(&format!("{}:x:{}:{}\n", name, gid, members));
+    }
+    for (id, user) in (FIRST_USER_ID..).zip(&users) {
+        group.push_str(&format!("{}:x:{}:\n", user, id));
+    }
+
+    let hosts = format!(
+        "127.0.0.1 localhost\n\
+        127.0.1.1 {}\n\
+        \n\
+        # The following lines are desirable for IPv6 capable hosts\n\
+        ::1     ip6-localhost ip6-loopback\n\
+        fe00::0 ip6-localnet\n\
+        ff00::0 ip6-mcastprefix\n\
+        ff02::1 ip6-allnodes\n\
+        ff02::2 ip6-allrouters\n",
+        hostname
+    );
+
+    write_file(
+        fs,
+        "/etc/hostname",
+        format!("{}\n", hostname).into_bytes(),
+        0o644,
+    )?;
+    write_file(fs, "/etc/hosts", hosts.into_bytes(), 0o644)?;
+    write_file(fs, "/etc/passwd", passwd.into_bytes(), 0o644)?;
+    write_file(fs, "/etc/group", group.into_bytes(), 0o644)
+}
+
+/// An account as /etc/passwd lists it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Account {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub gecos: String,
+    pub home: String,
+    pub shell: String,
+}
+
+impl std::fmt::Display for Account {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:x:{}:{}:{}:{}:{}",
+            self.name, self.uid, self.gid, self.gecos, self.home, self.shell
+        )
+    }
+}
+
+/// A group as /etc/group lists it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    pub name: String,
+    pub gid: u32,
+    pub members: Vec<String>,
+}
+
+impl std::fmt::Display for Group {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:x:{}:{}", self.name, self.gid, self.members.join(","))
+    }
+}
+
+/// The accounts and groups the system knows: /etc/passwd and /etc/group as they
+/// are now, edits made in the session included, so getent, id and /proc agree
+/// with them. A session user missing from /etc/passwd (any name logs in) is added
+/// with the next free uid and the groups of the first login user, or those of a
+/// fresh install when there is none, as if it came from a directory service
+#[derive(Debug, Clone, Default)]
+pub struct AccountTable {
+    pub accounts: Vec<Account>,
+    pub groups: Vec<Group>,
+}
+
+impl AccountTable {
+    pub fn read(fs: &FileSystem, session_user: &str) -> Self {
+        let accounts: Vec<Account> = read_text(fs, "/etc/passwd")
+            .lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split(':').collect();
+                let [name, _, uid, gid, gecos, home, shell] = fields[..] else {
+                    return None;
+                };
+                Some(Account {
+                    name: name.to_string(),
+                    uid: uid.parse().ok()?,
+                    gid: gid.parse().ok()?,
+                    gecos: gecos.to_string(),
+                    home: home.to_string(),
+                    shell: shell.to_string(),
+                })
+            })
+            .collect();
+        let groups = read_text(fs, "/etc/group")
+            .lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split(':').collect();
+                let [name, _, gid, members] = fields[..] else {
+                    return None;
+                };
+                Some(Group {
+                    name: name.to_string(),
+                    gid: gid.parse().ok()?,
+                    members: members
+                        .split(',')
+                        .filter(|member| !member.is_empty())
+                        .map(str::to_string)
+                        .collect(),
+                })
+            })
+            .collect();
+
+        let mut table = Self { accounts, groups };
+        // Root is never a login user, only missing when /etc/passwd is
+        if !matches!(session_user, "" | "root") && table.user(session_user).is_none() {
+            table.add_login_user(session_user);
+        }
+        table
+    }
+
+    /// Account by name or uid
+    pub fn user(&self, key: &str) -> Option<&Account> {
+        self.accounts
+            .iter()
+            .find(|account| account.name == key)
+            .or_else(|| {
+                let uid: u32 = key.parse().ok()?;
+                self.accounts.iter().find(|account| account.uid == uid)
+            })
+    }
+
+    /// Group by name or gid
+    pub fn group(&self, key: &str) -> Option<&Group> {
+        self.groups
+            .iter()
+            .find(|group| group.name == key)
+            .or_else(|| {
+                let gid: u32 = key.parse().ok()?;
+                self.groups.iter().find(|group| group.gid == gid)
+            })
+    }
+
+    /// Groups of `account` the way id lists them: the primary group first, then
+    /// the groups naming it as member in /etc/group order
+    pub fn groups_of(&self, account: &Account) -> Vec<(u32, Option<&str>)> {
+        let primary = self.groups.iter().find(|group| group.gid == account.gid);
+        let mut groups = vec![(account.gid, primary.map(|group| group.name.as_str()))];
+        for group in &self.groups {
+            if group.members.contains(&account.name) && group.gid != account.gid {
+                groups.push((group.gid, Some(group.name.as_str())));
+            }
+        }
+        groups
+    }
+
+    fn add_login_user(&mut self, name: &str) {
+        let is_login = |account: &&Account| (FIRST_USER_ID..65534).contains(&account.uid);
+        let template = self.accounts.iter().find(is_login).map(|a| a.name.clone());
+        let uid = self
+            .accounts
+            .iter()
+            .filter(is_login)
+            .map(|account| account.uid + 1)
+            .max()
+            .unwrap_or(FIRST_USER_ID);
+
+        self.accounts.push(Account {
+            name: name.to_string(),
+            uid,
+            gid: uid,
+            gecos: format!("{},,,", name),
+            home: format!("/home/{}", name),
+            shell: "/bin/bash".to_string(),
+        });
+        for group in &mut self.groups {
+            let joins = match &template {
+                Some(template) => group.members.contains(template),
+                None => SYSTEM_GROUPS
+                    .iter()
+                    .any(|(system, _, login_group)| *login_group && *system == group.name),
+            };
+            if joins {
+                group.members.push(name.to_string());
+            }
+        }
+        self.groups.push(Group {
+            name: name.to_string(),
+            gid: uid,
+            members: Vec::new(),
+        });
+    }
+}
+
+/// Home directory of `user` as /etc/passwd has it. Users it doesn't list get
+/// /home/NAME like [`AccountTable::read`] adds them, root /root
+pub fn home_dir(fs: &FileSystem, user: &str) -> String {
+    match AccountTable::read(fs, user).user(user) {
+        Some(account) => account.home.clone(),
+        None => "/root".to_string(),
+    }
+}
+
+/// Contents of a text file in `fs`, empty when it is missing
+fn read_text(fs: &FileSystem, path: &str) -> String {
+    match fs.follow_symlink(path).map(|entry| entry.file_content) {
+        Ok(Some(FileContent::RegularFile(bytes))) => String::from_utf8_lossy(&bytes).into(),
+        _ => String::new(),
+    }
+}
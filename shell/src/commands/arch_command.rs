@@ -0,0 +1,42 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use async_trait::async_trait;
+
+/// Arch command: prints the machine hardware name, same as `uname -m`
+pub struct ArchCommand;
+
+#[async_trait]
+impl Command for ArchCommand {
+    fn name(&self) -> &'static str {
+        "arch"
+    }
+
+    fn help(&self) -> String {
+        "Usage: arch [OPTION]...\r\n\
+        Print machine architecture.\r\n\
+        \r\n\
+        \x20     --help     display this help and exit\r\n\
+        \x20     --version  output version information and exit\r\n"
+            .to_string()
+    }
+
+    fn version(&self) -> String {
+        "arch (GNU coreutils) 8.32\r\n".to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        match args.first().map(String::as_str) {
+            None => Ok(format!("{}\r\n", context.os.machine)),
+            Some("--help") => Ok(self.help()),
+            Some("--version") => Ok(self.version()),
+            Some(arg) if arg.starts_with('-') => Err(CommandError::InvalidArguments(format!(
+                "arch: unrecognized option '{}'\r\nTry 'arch --help' for more information.",
+                arg
+            ))),
+            Some(arg) => Err(CommandError::InvalidArguments(format!(
+                "arch: extra operand '{}'\r\nTry 'arch --help' for more information.",
+                arg
+            ))),
+        }
+    }
+}
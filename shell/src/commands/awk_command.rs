@@ -0,0 +1,2109 @@
+//! awk as Ubuntu ships it, mawk 1.3.4, for the part of the language recon
+//! one-liners use.
+//!
+//! Supported: `-F`, `-v`, `-f` and `var=value` operands; `BEGIN` and `END`;
+//! patterns that are a regex, an expression or a `pattern, pattern` range, with
+//! or without an action; `$0`, `$1..$N` (also assigned to), `NF`, `NR`, `FNR`,
+//! `FS`, `OFS` and `ORS`; `print`, `printf`, `if`/`else`, `while`, `for (;;)`,
+//! `break`, `continue`, `next` and `exit`; the arithmetic, comparison, match,
+//! logical, ternary, concatenation, assignment and increment operators; and
+//! `length`, `substr`, `index`, `match`, `sub`, `gsub`, `sprintf`, `tolower`,
+//! `toupper`, `int`, `sqrt`, `exp`, `log`, `sin`, `cos`, `atan2`, `close` and
+//! `fflush`.
+//!
+//! Not supported, and a syntax error like in an awk that lacks them: arrays
+//! (with `in`, `delete`, `split` and `for (k in a)`), user-defined functions,
+//! `getline`, `do`/`while`, `system`, `rand`/`srand` and `RS`. Output sent to
+//! a file or command with `>`, `>>` or `|` is dropped.
+//!
+//! A script can't hold the session or the honeypot's memory: loops stop after
+//! MAX_LOOP_ITERATIONS, a program fails with `out of memory` once it builds a
+//! string longer than MAX_STRING_LENGTH or has handled MAX_WORK bytes of
+//! strings, and widths, precisions and field numbers are limited like mawk's.
+//! The interpreter runs on a blocking thread, not on the session's.
+
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use super::dispatcher::DEFAULT_MAX_OUTPUT;
+use super::sed_command::translate;
+use crate::filesystem::fs2::FileContent;
+use async_trait::async_trait;
+use regex::Regex;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Loop iterations a program may run in total before it is stopped
+const MAX_LOOP_ITERATIONS: usize = 1_000_000;
+
+/// Longest string a program may build, more than any command may print
+const MAX_STRING_LENGTH: usize = DEFAULT_MAX_OUTPUT;
+
+/// Bytes of string values a program may produce in total, copies included.
+/// Bounds the time a loop spends copying strings, which the loop limit doesn't
+const MAX_WORK: usize = 256 * 1024 * 1024;
+
+/// Widest field and longest precision of a printf conversion, mawk's sprintf buffer
+const MAX_FORMAT_WIDTH: usize = 8192;
+
+/// Fields a record may have, mawk's limit
+const MAX_FIELDS: usize = 32767;
+
+const USAGE: &str = "usage: awk [-F value] [-v var=value] [--] 'program text' [file ...]\r\n\
+    usage: awk [-F value] [-v var=value] [-f program-file] [--] [file ...]";
+
+const VERSION: &str = "mawk 1.3.4 20200120\r\n\
+    Copyright 2008-2019,2020, Thomas E. Dickey\r\n\
+    Copyright 1991-1996,2014, Michael D. Brennan\r\n\
+    \r\n\
+    random-funcs:       srandom/random\r\n\
+    regex-funcs:        internal\r\n\
+    compiled limits:\r\n\
+    sprintf buffer      8192\r\n\
+    maximum-integer     2147483647\r\n";
+
+/// Built-in functions, with the number of arguments they take
+const BUILTINS: &[(&str, usize, usize)] = &[
+    ("length", 0, 1),
+    ("substr", 2, 3),
+    ("index", 2, 2),
+    ("match", 2, 2),
+    ("sub", 2, 3),
+    ("gsub", 2, 3),
+    ("sprintf", 1, usize::MAX),
+    ("tolower", 1, 1),
+    ("toupper", 1, 1),
+    ("int", 1, 1),
+    ("sqrt", 1, 1),
+    ("exp", 1, 1),
+    ("log", 1, 1),
+    ("sin", 1, 1),
+    ("cos", 1, 1),
+    ("atan2", 2, 2),
+    ("close", 1, 1),
+    ("fflush", 0, 1),
+];
+
+/// Words awk reserves that this one doesn't implement
+const UNSUPPORTED: &[&str] = &[
+    "function", "func", "getline", "delete", "in", "do", "return", "split", "system", "rand",
+    "srand",
+];
+
+fn syntax_error(line: usize, near: &str) -> CommandError {
+    CommandError::InvalidArguments(format!(
+        "awk: line {}: syntax error at or near {}",
+        line, near
+    ))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Str(String),
+    Regex(String),
+    Name(String),
+    /// A name directly followed by `(`
+    Call(String),
+    Dollar,
+    LBrace,
+    RBrace,
+    LParen,
+    RParen,
+    Semicolon,
+    Newline,
+    Comma,
+    Op(&'static str),
+    Other(char),
+    Eof,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Number(n) => format_number(*n),
+            Token::Str(s) => format!("\"{}\"", s),
+            Token::Regex(r) => format!("/{}/", r),
+            Token::Name(name) | Token::Call(name) => name.clone(),
+            Token::Dollar => "$".to_string(),
+            Token::LBrace => "{".to_string(),
+            Token::RBrace => "}".to_string(),
+            Token::LParen => "(".to_string(),
+            Token::RParen => ")".to_string(),
+            Token::Semicolon => ";".to_string(),
+            Token::Newline => "end of line".to_string(),
+            Token::Comma => ",".to_string(),
+            Token::Op(op) => op.to_string(),
+            Token::Other(c) => c.to_string(),
+            Token::Eof => "end of file".to_string(),
+        }
+    }
+
+    /// Whether a `/` after this token divides rather than starts a regex
+    fn ends_operand(&self) -> bool {
+        matches!(
+            self,
+            Token::Number(_)
+                | Token::Str(_)
+                | Token::Regex(_)
+                | Token::Name(_)
+                | Token::RParen
+                | Token::Op("++" | "--")
+        )
+    }
+}
+
+/// Longest first, so `>=` isn't read as `>` and `=`
+const OPERATORS: &[&str] = &[
+    "&&", "||", "==", "!=", "<=", ">=", "!~", "++", "--", "+=", "-=", "*=", "/=", "%=", "^=", ">>",
+    "+", "-", "*", "/", "%", "^", "!", "<", ">", "~", "?", ":", "=", "|",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, CommandError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens: Vec<(Token, usize)> = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let regex_allowed = !tokens.last().is_some_and(|(token, _)| token.ends_operand());
+        let token = match c {
+            ' ' | '\t' | '\r' => {
+                i += 1;
+                continue;
+            }
+            '\\' if chars.get(i + 1) == Some(&'\n') => {
+                line += 1;
+                i += 2;
+                continue;
+            }
+            '#' => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            '\n' => {
+                tokens.push((Token::Newline, line));
+                line += 1;
+                i += 1;
+                continue;
+            }
+            '"' => {
+                let mut s = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None | Some('\n') => {
+                            return Err(CommandError::InvalidArguments(format!(
+                                "awk: line {}: runaway string constant \"{} ...",
+                                line, s
+                            )));
+                        }
+                        Some('"') => break,
+                        Some('\\') => {
+                            i += 1;
+                            match chars.get(i) {
+                                Some('n') => s.push('\n'),
+                                Some('t') => s.push('\t'),
+                                Some('r') => s.push('\r'),
+                                Some('/') => s.push('/'),
+                                Some('"') => s.push('"'),
+                                Some('\\') => s.push('\\'),
+                                Some('\n') => line += 1,
+                                Some(other) => {
+                                    s.push('\\');
+                                    s.push(*other);
+                                }
+                                None => continue,
+                            }
+                        }
+                        Some(other) => s.push(*other),
+                    }
+                    i += 1;
+                }
+                i += 1;
+                tokens.push((Token::Str(s), line));
+                continue;
+            }
+            '/' if regex_allowed => {
+                let mut re = String::new();
+                let mut in_bracket = false;
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None | Some('\n') => {
+                            return Err(CommandError::InvalidArguments(format!(
+                                "awk: line {}: runaway regular expression /{} ...",
+                                line, re
+                            )));
+                        }
+                        Some('/') if !in_bracket => break,
+                        Some('\\') if chars.get(i + 1) == Some(&'/') => {
+                            re.push('/');
+                            i += 1;
+                        }
+                        Some('\\') => {
+                            re.push('\\');
+                            if let Some(next) = chars.get(i + 1) {
+                                re.push(*next);
+                                i += 1;
+                            }
+                        }
+                        Some('[') => {
+                            in_bracket = true;
+                            re.push('[');
+                        }
+                        Some(']') => {
+                            in_bracket = false;
+                            re.push(']');
+                        }
+                        Some(other) => re.push(*other),
+                    }
+                    i += 1;
+                }
+                i += 1;
+                tokens.push((Token::Regex(re), line));
+                continue;
+            }
+            c if c.is_ascii_digit()
+                || (c == '.' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit())) =>
+            {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                if i < chars.len() && matches!(chars[i], 'e' | 'E') {
+                    let mut j = i + 1;
+                    if j < chars.len() && matches!(chars[j], '+' | '-') {
+                        j += 1;
+                    }
+                    if j < chars.len() && chars[j].is_ascii_digit() {
+                        i = j;
+                        while i < chars.len() && chars[i].is_ascii_digit() {
+                            i += 1;
+                        }
+                    }
+                }
+                let text: String = chars[start..i].iter().collect();
+                tokens.push((Token::Number(number_prefix(&text)), line));
+                continue;
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let name: String = chars[start..i].iter().collect();
+                let keyword = matches!(name.as_str(), "if" | "while" | "for" | "print" | "printf");
+                let token = match chars.get(i) {
+                    Some('(') if !keyword => Token::Call(name),
+                    _ => Token::Name(name),
+                };
+                tokens.push((token, line));
+                continue;
+            }
+            '$' => Token::Dollar,
+            '{' => Token::LBrace,
+            '}' => Token::RBrace,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ';' => Token::Semicolon,
+            ',' => Token::Comma,
+            _ => {
+                let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+                match OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+                    Some(op) => {
+                        i += op.len();
+                        tokens.push((Token::Op(op), line));
+                        continue;
+                    }
+                    None => Token::Other(c),
+                }
+            }
+        };
+        tokens.push((token, line));
+        i += 1;
+    }
+    tokens.push((Token::Eof, line));
+    Ok(tokens)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Concat,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+#[derive(Debug)]
+enum Expr {
+    Num(f64),
+    Str(String),
+    /// A regex on its own matches `$0`
+    Regex(String),
+    Var(String),
+    Field(Box<Expr>),
+    /// `(a, b)`, only meaningful as the argument list of print
+    Group(Vec<Expr>),
+    Assign(Box<Expr>, Option<BinOp>, Box<Expr>),
+    Incr {
+        target: Box<Expr>,
+        delta: f64,
+        prefix: bool,
+    },
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Plus(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    Match(bool, Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Cond(Box<Expr>, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+impl Expr {
+    fn is_lvalue(&self) -> bool {
+        matches!(self, Expr::Var(_) | Expr::Field(_))
+    }
+}
+
+#[derive(Debug)]
+enum Stmt {
+    Expr(Expr),
+    /// Arguments and whether the output was redirected
+    Print(Vec<Expr>, bool),
+    Printf(Vec<Expr>, bool),
+    If(Expr, Box<Stmt>, Option<Box<Stmt>>),
+    While(Expr, Box<Stmt>),
+    For(Option<Expr>, Option<Expr>, Option<Expr>, Box<Stmt>),
+    Block(Vec<Stmt>),
+    Next,
+    Exit(Option<Expr>),
+    Break,
+    Continue,
+}
+
+#[derive(Debug)]
+enum Pattern {
+    All,
+    Expr(Expr),
+    Range(Expr, Expr),
+}
+
+#[derive(Debug)]
+struct Item {
+    pattern: Pattern,
+    /// None prints the record
+    action: Option<Vec<Stmt>>,
+}
+
+#[derive(Debug, Default)]
+struct Program {
+    begin: Vec<Vec<Stmt>>,
+    items: Vec<Item>,
+    end: Vec<Vec<Stmt>>,
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    /// In the argument list of print, where `>` redirects instead of comparing
+    no_gt: bool,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos.min(self.tokens.len() - 1)].0
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.pos.min(self.tokens.len() - 1)].1
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.peek().clone();
+        if self.pos < self.tokens.len() - 1 {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error(&self) -> CommandError {
+        let token = self.peek();
+        match token {
+            Token::Eof => CommandError::InvalidArguments(format!(
+                "awk: line {}: syntax error at or near end of file",
+                self.line()
+            )),
+            _ => syntax_error(self.line(), &token.describe()),
+        }
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), CommandError> {
+        match *self.peek() == token {
+            true => {
+                self.advance();
+                Ok(())
+            }
+            false => Err(self.error()),
+        }
+    }
+
+    fn eat_op(&mut self, op: &str) -> bool {
+        match self.peek() {
+            Token::Op(found) if *found == op => {
+                self.advance();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn skip_newlines(&mut self) {
+        while *self.peek() == Token::Newline {
+            self.advance();
+        }
+    }
+
+    fn skip_terminators(&mut self) {
+        while matches!(self.peek(), Token::Newline | Token::Semicolon) {
+            self.advance();
+        }
+    }
+
+    fn program(mut self) -> Result<Program, CommandError> {
+        let mut program = Program::default();
+        loop {
+            self.skip_terminators();
+            match self.peek().clone() {
+                Token::Eof => return Ok(program),
+                Token::Name(name) if name == "BEGIN" || name == "END" => {
+                    self.advance();
+                    let block = self.block()?;
+                    match name.as_str() {
+                        "BEGIN" => program.begin.push(block),
+                        _ => program.end.push(block),
+                    }
+                }
+                Token::LBrace => {
+                    let action = self.block()?;
+                    program.items.push(Item {
+                        pattern: Pattern::All,
+                        action: Some(action),
+                    });
+                    continue;
+                }
+                _ => {
+                    let first = self.expr()?;
+                    let pattern = match *self.peek() == Token::Comma {
+                        true => {
+                            self.advance();
+                            self.skip_newlines();
+                            Pattern::Range(first, self.expr()?)
+                        }
+                        false => Pattern::Expr(first),
+                    };
+                    if *self.peek() == Token::LBrace {
+                        let action = self.block()?;
+                        program.items.push(Item {
+                            pattern,
+                            action: Some(action),
+                        });
+                        continue;
+                    }
+                    // A pattern without an action ends with its line
+                    if !matches!(self.peek(), Token::Newline | Token::Semicolon | Token::Eof) {
+                        return Err(self.error());
+                    }
+                    program.items.push(Item {
+                        pattern,
+                        action: None,
+                    });
+                }
+            }
+        }
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, CommandError> {
+        self.expect(Token::LBrace)?;
+        let mut statements = Vec::new();
+        loop {
+            self.skip_terminators();
+            if *self.peek() == Token::RBrace {
+                self.advance();
+                return Ok(statements);
+            }
+            statements.push(self.statement()?);
+        }
+    }
+
+    /// The end of a simple statement: `;` or a newline, or the `}` closing its block
+    fn end_simple(&mut self) -> Result<(), CommandError> {
+        match self.peek() {
+            Token::Semicolon | Token::Newline => {
+                self.advance();
+                Ok(())
+            }
+            Token::RBrace | Token::Eof => Ok(()),
+            _ => Err(self.error()),
+        }
+    }
+
+    fn at_end_of_simple(&self) -> bool {
+        matches!(
+            self.peek(),
+            Token::Semicolon | Token::Newline | Token::RBrace | Token::Eof
+        )
+    }
+
+    fn statement(&mut self) -> Result<Stmt, CommandError> {
+        let keyword = match self.peek() {
+            Token::Name(name) => name.clone(),
+            Token::LBrace => return Ok(Stmt::Block(self.block()?)),
+            Token::Semicolon => {
+                self.advance();
+                return Ok(Stmt::Block(Vec::new()));
+            }
+            _ => String::new(),
+        };
+        let statement = match keyword.as_str() {
+            "if" => {
+                self.advance();
+                self.expect(Token::LParen)?;
+                let condition = self.expr()?;
+                self.expect(Token::RParen)?;
+                self.skip_newlines();
+                let then = self.statement()?;
+                let before_else = self.pos;
+                self.skip_terminators();
+                let otherwise = match self.peek() {
+                    Token::Name(name) if name == "else" => {
+                        self.advance();
+                        self.skip_newlines();
+                        Some(Box::new(self.statement()?))
+                    }
+                    _ => {
+                        self.pos = before_else;
+                        None
+                    }
+                };
+                return Ok(Stmt::If(condition, Box::new(then), otherwise));
+            }
+            "while" => {
+                self.advance();
+                self.expect(Token::LParen)?;
+                let condition = self.expr()?;
+                self.expect(Token::RParen)?;
+                self.skip_newlines();
+                return Ok(Stmt::While(condition, Box::new(self.statement()?)));
+            }
+            "for" => {
+                self.advance();
+                self.expect(Token::LParen)?;
+                let init = match self.peek() {
+                    Token::Semicolon => None,
+                    _ => Some(self.expr()?),
+                };
+                self.expect(Token::Semicolon)?;
+                self.skip_newlines();
+                let condition = match self.peek() {
+                    Token::Semicolon => None,
+                    _ => Some(self.expr()?),
+                };
+                self.expect(Token::Semicolon)?;
+                self.skip_newlines();
+                let step = match self.peek() {
+                    Token::RParen => None,
+                    _ => Some(self.expr()?),
+                };
+                self.expect(Token::RParen)?;
+                self.skip_newlines();
+                let body = self.statement()?;
+                return Ok(Stmt::For(init, condition, step, Box::new(body)));
+            }
+            "next" => {
+                self.advance();
+                Stmt::Next
+            }
+            "break" => {
+                self.advance();
+                Stmt::Break
+            }
+            "continue" => {
+                self.advance();
+                Stmt::Continue
+            }
+            "exit" => {
+                self.advance();
+                match self.at_end_of_simple() {
+                    true => Stmt::Exit(None),
+                    false => Stmt::Exit(Some(self.expr()?)),
+                }
+            }
+            "print" | "printf" => {
+                self.advance();
+                let mut args = Vec::new();
+                if !self.at_end_of_simple() && !matches!(self.peek(), Token::Op(">" | ">>" | "|")) {
+                    self.no_gt = true;
+                    let list = self.expr_list();
+                    self.no_gt = false;
+                    args = list?;
+                }
+                if matches!(args.as_slice(), [Expr::Group(_)])
+                    && let Some(Expr::Group(list)) = args.pop()
+                {
+                    args = list;
+                }
+                let redirected = matches!(self.peek(), Token::Op(">" | ">>" | "|"));
+                if redirected {
+                    self.advance();
+                    self.no_gt = true;
+                    let target = self.concat();
+                    self.no_gt = false;
+                    target?;
+                }
+                match keyword.as_str() {
+                    "print" => Stmt::Print(args, redirected),
+                    _ if args.is_empty() => return Err(self.error()),
+                    _ => Stmt::Printf(args, redirected),
+                }
+            }
+            _ => Stmt::Expr(self.expr()?),
+        };
+        self.end_simple()?;
+        Ok(statement)
+    }
+
+    fn expr_list(&mut self) -> Result<Vec<Expr>, CommandError> {
+        let mut list = vec![self.expr()?];
+        while *self.peek() == Token::Comma {
+            self.advance();
+            self.skip_newlines();
+            list.push(self.expr()?);
+        }
+        Ok(list)
+    }
+
+    fn expr(&mut self) -> Result<Expr, CommandError> {
+        let condition = self.or()?;
+        if self.eat_op("?") {
+            self.skip_newlines();
+            let then = self.expr()?;
+            self.skip_newlines();
+            if !self.eat_op(":") {
+                return Err(self.error());
+            }
+            self.skip_newlines();
+            let otherwise = self.expr()?;
+            return Ok(Expr::Cond(
+                Box::new(condition),
+                Box::new(then),
+                Box::new(otherwise),
+            ));
+        }
+        let op = match self.peek() {
+            Token::Op("=") => None,
+            Token::Op("+=") => Some(BinOp::Add),
+            Token::Op("-=") => Some(BinOp::Sub),
+            Token::Op("*=") => Some(BinOp::Mul),
+            Token::Op("/=") => Some(BinOp::Div),
+            Token::Op("%=") => Some(BinOp::Mod),
+            Token::Op("^=") => Some(BinOp::Pow),
+            _ => return Ok(condition),
+        };
+        if !condition.is_lvalue() {
+            return Err(self.error());
+        }
+        self.advance();
+        self.skip_newlines();
+        let value = self.expr()?;
+        Ok(Expr::Assign(Box::new(condition), op, Box::new(value)))
+    }
+
+    fn or(&mut self) -> Result<Expr, CommandError> {
+        let mut left = self.and()?;
+        while self.eat_op("||") {
+            self.skip_newlines();
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, CommandError> {
+        let mut left = self.matching()?;
+        while self.eat_op("&&") {
+            self.skip_newlines();
+            left = Expr::And(Box::new(left), Box::new(self.matching()?));
+        }
+        Ok(left)
+    }
+
+    fn matching(&mut self) -> Result<Expr, CommandError> {
+        let mut left = self.comparison()?;
+        loop {
+            let negate = match self.peek() {
+                Token::Op("~") => false,
+                Token::Op("!~") => true,
+                _ => return Ok(left),
+            };
+            self.advance();
+            left = Expr::Match(negate, Box::new(left), Box::new(self.comparison()?));
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Expr, CommandError> {
+        let left = self.concat()?;
+        let op = match self.peek() {
+            Token::Op("<") => BinOp::Lt,
+            Token::Op("<=") => BinOp::Le,
+            Token::Op(">") if !self.no_gt => BinOp::Gt,
+            Token::Op(">=") => BinOp::Ge,
+            Token::Op("==") => BinOp::Eq,
+            Token::Op("!=") => BinOp::Ne,
+            _ => return Ok(left),
+        };
+        self.advance();
+        Ok(Expr::Binary(op, Box::new(left), Box::new(self.concat()?)))
+    }
+
+    fn concat(&mut self) -> Result<Expr, CommandError> {
+        let mut left = self.additive()?;
+        loop {
+            let starts_operand = match self.peek() {
+                Token::Name(name) => !matches!(name.as_str(), "in" | "getline" | "else"),
+                Token::Number(_)
+                | Token::Str(_)
+                | Token::Call(_)
+                | Token::Dollar
+                | Token::LParen => true,
+                _ => false,
+            };
+            if !starts_operand {
+                return Ok(left);
+            }
+            left = Expr::Binary(BinOp::Concat, Box::new(left), Box::new(self.additive()?));
+        }
+    }
+
+    fn additive(&mut self) -> Result<Expr, CommandError> {
+        let mut left = self.multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Token::Op("+") => BinOp::Add,
+                Token::Op("-") => BinOp::Sub,
+                _ => return Ok(left),
+            };
+            self.advance();
+            left = Expr::Binary(op, Box::new(left), Box::new(self.multiplicative()?));
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, CommandError> {
+        let mut left = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Token::Op("*") => BinOp::Mul,
+                Token::Op("/") => BinOp::Div,
+                Token::Op("%") => BinOp::Mod,
+                _ => return Ok(left),
+            };
+            self.advance();
+            left = Expr::Binary(op, Box::new(left), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, CommandError> {
+        if self.eat_op("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat_op("-") {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        if self.eat_op("+") {
+            return Ok(Expr::Plus(Box::new(self.unary()?)));
+        }
+        let base = self.postfix()?;
+        if self.eat_op("^") {
+            // Right associative, and the exponent may be negated
+            return Ok(Expr::Binary(
+                BinOp::Pow,
+                Box::new(base),
+                Box::new(self.unary()?),
+            ));
+        }
+        Ok(base)
+    }
+
+    fn postfix(&mut self) -> Result<Expr, CommandError> {
+        let operand = self.primary()?;
+        if operand.is_lvalue() {
+            for (op, delta) in [("++", 1.0), ("--", -1.0)] {
+                if self.eat_op(op) {
+                    return Ok(Expr::Incr {
+                        target: Box::new(operand),
+                        delta,
+                        prefix: false,
+                    });
+                }
+            }
+        }
+        Ok(operand)
+    }
+
+    fn primary(&mut self) -> Result<Expr, CommandError> {
+        match self.peek().clone() {
+            Token::Number(n) => {
+                self.advance();
+                Ok(Expr::Num(n))
+            }
+            Token::Str(s) => {
+                self.advance();
+                Ok(Expr::Str(s))
+            }
+            Token::Regex(re) => {
+                self.advance();
+                Ok(Expr::Regex(re))
+            }
+            Token::Dollar => {
+                self.advance();
+                Ok(Expr::Field(Box::new(self.primary()?)))
+            }
+            Token::Op(op @ ("++" | "--")) => {
+                self.advance();
+                let target = self.primary()?;
+                if !target.is_lvalue() {
+                    return Err(self.error());
+                }
+                Ok(Expr::Incr {
+                    target: Box::new(target),
+                    delta: if op == "++" { 1.0 } else { -1.0 },
+                    prefix: true,
+                })
+            }
+            Token::Op("-") => {
+                self.advance();
+                Ok(Expr::Neg(Box::new(self.primary()?)))
+            }
+            Token::LParen => {
+                self.advance();
+                let no_gt = std::mem::replace(&mut self.no_gt, false);
+                let list = self.expr_list();
+                self.no_gt = no_gt;
+                let mut list = list?;
+                self.expect(Token::RParen)?;
+                Ok(match list.len() {
+                    1 => list.pop().unwrap(),
+                    _ => Expr::Group(list),
+                })
+            }
+            Token::Call(name) => self.call(name),
+            Token::Name(name) if UNSUPPORTED.contains(&name.as_str()) => Err(self.error()),
+            Token::Name(name) if BUILTINS.iter().any(|(builtin, ..)| *builtin == name) => {
+                match self.tokens.get(self.pos + 1).map(|(token, _)| token) {
+                    Some(Token::LParen) => self.call(name),
+                    // length without parentheses is the length of $0
+                    _ if name == "length" => {
+                        self.advance();
+                        Ok(Expr::Call(name, Vec::new()))
+                    }
+                    _ => Err(self.error()),
+                }
+            }
+            Token::Name(name)
+                if !matches!(
+                    name.as_str(),
+                    "BEGIN"
+                        | "END"
+                        | "print"
+                        | "printf"
+                        | "if"
+                        | "else"
+                        | "while"
+                        | "for"
+                        | "next"
+                        | "exit"
+                        | "break"
+                        | "continue"
+                ) =>
+            {
+                self.advance();
+                Ok(Expr::Var(name))
+            }
+            _ => Err(self.error()),
+        }
+    }
+
+    fn call(&mut self, name: String) -> Result<Expr, CommandError> {
+        let line = self.line();
+        let Some(&(_, min, max)) = BUILTINS.iter().find(|(builtin, ..)| *builtin == name) else {
+            if UNSUPPORTED.contains(&name.as_str()) {
+                return Err(self.error());
+            }
+            return Err(CommandError::InvalidArguments(format!(
+                "awk: line {}: function {} never defined",
+                line, name
+            )));
+        };
+        self.advance();
+        self.expect(Token::LParen)?;
+        let mut args = Vec::new();
+        if *self.peek() != Token::RParen {
+            let no_gt = std::mem::replace(&mut self.no_gt, false);
+            let list = self.expr_list();
+            self.no_gt = no_gt;
+            args = list?;
+        }
+        self.expect(Token::RParen)?;
+        if args.len() < min || args.len() > max {
+            return Err(syntax_error(line, &name));
+        }
+        if matches!(name.as_str(), "sub" | "gsub") && args.get(2).is_some_and(|t| !t.is_lvalue()) {
+            return Err(syntax_error(line, &name));
+        }
+        Ok(Expr::Call(name, args))
+    }
+}
+
+/// The leading number in `s` the way awk converts strings, 0 if there is none
+fn number_prefix(s: &str) -> f64 {
+    let s = s.trim_start();
+    let bytes = s.as_bytes();
+    let mut end = 0;
+    if matches!(bytes.first(), Some(b'+' | b'-')) {
+        end += 1;
+    }
+    let digits_start = end;
+    while end < bytes.len() && bytes[end].is_ascii_digit() {
+        end += 1;
+    }
+    if end < bytes.len() && bytes[end] == b'.' {
+        end += 1;
+        while end < bytes.len() && bytes[end].is_ascii_digit() {
+            end += 1;
+        }
+    }
+    if end == digits_start || (end == digits_start + 1 && bytes[digits_start] == b'.') {
+        return 0.0;
+    }
+    if end < bytes.len() && matches!(bytes[end], b'e' | b'E') {
+        let mut exp = end + 1;
+        if exp < bytes.len() && matches!(bytes[exp], b'+' | b'-') {
+            exp += 1;
+        }
+        if exp < bytes.len() && bytes[exp].is_ascii_digit() {
+            end = exp;
+            while end < bytes.len() && bytes[end].is_ascii_digit() {
+                end += 1;
+            }
+        }
+    }
+    s[..end].parse().unwrap_or(0.0)
+}
+
+/// Whether input text like a field looks like a number, and so compares as one
+fn looks_numeric(s: &str) -> bool {
+    let trimmed = s.trim();
+    if trimmed.is_empty() {
+        return false;
+    }
+    let unsigned = trimmed.trim_start_matches(['+', '-']);
+    if unsigned.len() + 1 < trimmed.len()
+        || !unsigned.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+    {
+        return false;
+    }
+    trimmed.parse::<f64>().is_ok()
+}
+
+/// A number as awk prints it: integers whole, anything else with `%.6g`
+fn format_number(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < i64::MAX as f64 {
+        return (n as i64).to_string();
+    }
+    if n.is_nan() {
+        return if n.is_sign_negative() { "-nan" } else { "nan" }.to_string();
+    }
+    if n.is_infinite() {
+        return if n < 0.0 { "-inf" } else { "inf" }.to_string();
+    }
+    format_g(n, 6, false)
+}
+
+/// C's `%e`, with at least two exponent digits
+fn format_e(n: f64, precision: usize, upper: bool) -> String {
+    let formatted = format!("{:.*e}", precision, n);
+    let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let sign = if exponent < 0 { '-' } else { '+' };
+    let e = if upper { 'E' } else { 'e' };
+    format!("{}{}{}{:02}", mantissa, e, sign, exponent.abs())
+}
+
+/// C's `%g`: the shorter of `%e` and `%f` at `precision` significant digits,
+/// without trailing zeros
+fn format_g(n: f64, precision: usize, upper: bool) -> String {
+    let precision = precision.max(1);
+    if n == 0.0 {
+        return "0".to_string();
+    }
+    let scientific = format!("{:.*e}", precision - 1, n);
+    let exponent: i32 = scientific
+        .split_once('e')
+        .and_then(|(_, e)| e.parse().ok())
+        .unwrap_or(0);
+    let trim = |s: String| -> String {
+        match s.contains('.') {
+            true => s.trim_end_matches('0').trim_end_matches('.').to_string(),
+            false => s,
+        }
+    };
+    if exponent < -4 || exponent >= precision as i32 {
+        let formatted = format_e(n, precision - 1, upper);
+        let marker = if upper { 'E' } else { 'e' };
+        let (mantissa, exp) = formatted.split_once(marker).unwrap();
+        format!("{}{}{}", trim(mantissa.to_string()), marker, exp)
+    } else {
+        let decimals = (precision as i32 - 1 - exponent).max(0) as usize;
+        trim(format!("{:.*}", decimals, n))
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    Uninit,
+    Num(f64),
+    Str(String),
+    /// Text from the input, a number when it looks like one
+    StrNum(String),
+}
+
+impl Value {
+    fn num(&self) -> f64 {
+        match self {
+            Value::Uninit => 0.0,
+            Value::Num(n) => *n,
+            Value::Str(s) | Value::StrNum(s) => number_prefix(s),
+        }
+    }
+
+    fn str(&self) -> String {
+        match self {
+            Value::Uninit => String::new(),
+            Value::Num(n) => format_number(*n),
+            Value::Str(s) | Value::StrNum(s) => s.clone(),
+        }
+    }
+
+    fn is_numeric(&self) -> bool {
+        match self {
+            Value::Uninit | Value::Num(_) => true,
+            Value::Str(_) => false,
+            Value::StrNum(s) => looks_numeric(s),
+        }
+    }
+
+    fn truthy(&self) -> bool {
+        match self {
+            Value::Uninit => false,
+            Value::Num(n) => *n != 0.0,
+            Value::Str(s) => !s.is_empty(),
+            Value::StrNum(s) if looks_numeric(s) => number_prefix(s) != 0.0,
+            Value::StrNum(s) => !s.is_empty(),
+        }
+    }
+}
+
+fn bool_value(b: bool) -> Value {
+    Value::Num(if b { 1.0 } else { 0.0 })
+}
+
+/// `printf` and `sprintf`. Stops once the result is longer than
+/// MAX_STRING_LENGTH, which the caller fails on
+fn sprintf(format: &str, args: &[Value]) -> String {
+    let mut out = String::new();
+    let mut args = args.iter();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if out.len() > MAX_STRING_LENGTH {
+            break;
+        }
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        if chars.peek() == Some(&'%') {
+            chars.next();
+            out.push('%');
+            continue;
+        }
+        let mut flags = String::new();
+        while let Some(&flag) = chars.peek().filter(|c| "-+ #0".contains(**c)) {
+            flags.push(flag);
+            chars.next();
+        }
+        let mut number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+            if chars.peek() == Some(&'*') {
+                chars.next();
+                return Some(args.next().map_or(0.0, Value::num) as i64);
+            }
+            let mut digits = String::new();
+            while let Some(&d) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                digits.push(d);
+                chars.next();
+            }
+            digits.parse().ok()
+        };
+        let width = number(&mut chars).unwrap_or(0);
+        let precision = match chars.peek() {
+            Some('.') => {
+                chars.next();
+                let precision = number(&mut chars).unwrap_or(0).max(0) as usize;
+                Some(precision.min(MAX_FORMAT_WIDTH))
+            }
+            _ => None,
+        };
+        if width < 0 {
+            flags.push('-');
+        }
+        let width = (width.unsigned_abs() as usize).min(MAX_FORMAT_WIDTH);
+        let Some(conversion) = chars.next() else {
+            out.push('%');
+            out.push_str(&flags);
+            break;
+        };
+        let arg = args.next().cloned().unwrap_or(Value::Uninit);
+        let numeric = "diouxXeEfFgG".contains(conversion);
+        let mut body = match conversion {
+            'd' | 'i' => {
+                let n = arg.num().trunc();
+                let digits = format!("{:.0}", n.abs());
+                let digits = match precision {
+                    Some(p) if digits.len() < p => format!("{:0>p$}", digits, p = p),
+                    _ => digits,
+                };
+                if n < 0.0 {
+                    format!("-{}", digits)
+                } else {
+                    digits
+                }
+            }
+            'o' | 'u' | 'x' | 'X' => {
+                let n = arg.num().trunc() as i64 as u64;
+                match conversion {
+                    'o' => format!("{:o}", n),
+                    'u' => n.to_string(),
+                    'x' => format!("{:x}", n),
+                    _ => format!("{:X}", n),
+                }
+            }
+            'c' => match &arg {
+                Value::Num(n) => char::from_u32(*n as u32).map_or(String::new(), String::from),
+                other => other
+                    .str()
+                    .chars()
+                    .next()
+                    .map_or(String::new(), String::from),
+            },
+            's' => {
+                let s = arg.str();
+                match precision {
+                    Some(p) => s.chars().take(p).collect(),
+                    None => s,
+                }
+            }
+            'f' | 'F' => format!("{:.*}", precision.unwrap_or(6), arg.num()),
+            'e' | 'E' => format_e(arg.num(), precision.unwrap_or(6), conversion == 'E'),
+            'g' | 'G' => format_g(arg.num(), precision.unwrap_or(6), conversion == 'G'),
+            other => {
+                out.push('%');
+                out.push_str(&flags);
+                out.push(other);
+                continue;
+            }
+        };
+        if numeric && !body.starts_with('-') {
+            if flags.contains('+') {
+                body.insert(0, '+');
+            } else if flags.contains(' ') {
+                body.insert(0, ' ');
+            }
+        }
+        let len = body.chars().count();
+        if len < width {
+            let pad = width - len;
+            if flags.contains('-') {
+                body.push_str(&" ".repeat(pad));
+            } else if flags.contains('0') && numeric && precision.is_none() {
+                let sign = if body.starts_with(['-', '+', ' ']) {
+                    1
+                } else {
+                    0
+                };
+                body.insert_str(sign, &"0".repeat(pad));
+            } else {
+                body.insert_str(0, &" ".repeat(pad));
+            }
+        }
+        out.push_str(&body);
+    }
+    out
+}
+
+/// What a statement leaves the program to do next
+#[derive(Debug, PartialEq)]
+enum Flow {
+    Normal,
+    Next,
+    Exit,
+    Break,
+    Continue,
+}
+
+struct Interpreter {
+    vars: HashMap<String, Value>,
+    record: String,
+    fields: Vec<String>,
+    nr: usize,
+    out: String,
+    regexes: HashMap<String, Regex>,
+    iterations: usize,
+    /// Bytes of string values produced so far, see MAX_WORK
+    work: usize,
+}
+
+/// What mawk says when it can't allocate a string
+fn out_of_memory() -> CommandError {
+    CommandError::ExecutionError("awk: out of memory".to_string())
+}
+
+fn too_many_fields() -> CommandError {
+    CommandError::ExecutionError(format!(
+        "awk: program limit exceeded: maximum number of fields size={}",
+        MAX_FIELDS
+    ))
+}
+
+impl Interpreter {
+    fn new() -> Self {
+        let vars = [("FS", " "), ("OFS", " "), ("ORS", "\n"), ("SUBSEP", "\x1c")]
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), Value::Str(value.to_string())))
+            .collect();
+        Self {
+            vars,
+            record: String::new(),
+            fields: Vec::new(),
+            nr: 0,
+            out: String::new(),
+            regexes: HashMap::new(),
+            iterations: 0,
+            work: 0,
+        }
+    }
+
+    fn regex(&mut self, source: &str) -> Result<Regex, CommandError> {
+        if let Some(regex) = self.regexes.get(source) {
+            return Ok(regex.clone());
+        }
+        let regex = Regex::new(&translate(source, true)).map_err(|err| {
+            log::debug!("awk regex {:?} did not compile: {}", source, err);
+            CommandError::ExecutionError(format!(
+                "awk: line 1: regular expression compile failed (missing operand)\r\n{}",
+                source
+            ))
+        })?;
+        self.regexes.insert(source.to_string(), regex.clone());
+        Ok(regex)
+    }
+
+    /// The regex an operand of `~`, `match` or `sub` stands for: a regex literal
+    /// as written, anything else by its string value
+    fn regex_of(&mut self, expr: &Expr) -> Result<Regex, CommandError> {
+        let source = match expr {
+            Expr::Regex(source) => source.clone(),
+            other => self.eval(other)?.str(),
+        };
+        self.regex(&source)
+    }
+
+    fn set_record(&mut self, record: String) {
+        self.record = record;
+        let separator = self.vars.get("FS").map_or(" ".to_string(), Value::str);
+        self.fields = if separator == " " {
+            self.record.split_whitespace().map(String::from).collect()
+        } else if self.record.is_empty() {
+            Vec::new()
+        } else if separator.chars().count() == 1 && separator != "\\" {
+            self.record
+                .split(separator.as_str())
+                .map(String::from)
+                .collect()
+        } else {
+            match self.regex(&separator) {
+                Ok(regex) => regex.split(&self.record).map(String::from).collect(),
+                Err(_) => self
+                    .record
+                    .split(separator.as_str())
+                    .map(String::from)
+                    .collect(),
+            }
+        };
+    }
+
+    fn rebuild_record(&mut self) -> Result<(), CommandError> {
+        let separator = self.vars.get("OFS").map_or(" ".to_string(), Value::str);
+        let length = self.fields.iter().map(String::len).sum::<usize>()
+            + separator.len() * self.fields.len().saturating_sub(1);
+        if length > MAX_STRING_LENGTH {
+            return Err(out_of_memory());
+        }
+        self.charge(length)?;
+        self.record = self.fields.join(&separator);
+        Ok(())
+    }
+
+    fn get_var(&self, name: &str) -> Value {
+        match name {
+            "NF" => Value::Num(self.fields.len() as f64),
+            "NR" | "FNR" => Value::Num(self.nr as f64),
+            _ => self.vars.get(name).cloned().unwrap_or(Value::Uninit),
+        }
+    }
+
+    fn set_var(&mut self, name: &str, value: Value) -> Result<(), CommandError> {
+        match name {
+            "NF" => {
+                let count = value.num().max(0.0);
+                if count > MAX_FIELDS as f64 {
+                    return Err(too_many_fields());
+                }
+                self.fields.resize(count as usize, String::new());
+                self.rebuild_record()?;
+            }
+            "NR" | "FNR" => self.nr = value.num().max(0.0) as usize,
+            _ => {
+                self.vars.insert(name.to_string(), value);
+            }
+        }
+        Ok(())
+    }
+
+    fn field_index(&mut self, expr: &Expr) -> Result<usize, CommandError> {
+        let index = self.eval(expr)?.num();
+        if index < 0.0 {
+            return Err(CommandError::ExecutionError(format!(
+                "awk: run time error: negative field index ${}",
+                format_number(index)
+            )));
+        }
+        Ok(index as usize)
+    }
+
+    fn get_field(&self, index: usize) -> Value {
+        match index {
+            0 => Value::StrNum(self.record.clone()),
+            n => Value::StrNum(self.fields.get(n - 1).cloned().unwrap_or_default()),
+        }
+    }
+
+    fn set_field(&mut self, index: usize, value: String) -> Result<(), CommandError> {
+        if index == 0 {
+            self.set_record(value);
+            return Ok(());
+        }
+        if index > MAX_FIELDS {
+            return Err(too_many_fields());
+        }
+        if self.fields.len() < index {
+            self.fields.resize(index, String::new());
+        }
+        self.fields[index - 1] = value;
+        self.rebuild_record()
+    }
+
+    fn assign(&mut self, target: &Expr, value: Value) -> Result<(), CommandError> {
+        match target {
+            Expr::Var(name) => self.set_var(name, value)?,
+            Expr::Field(index) => {
+                let index = self.field_index(index)?;
+                self.set_field(index, value.str())?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn arithmetic(op: BinOp, left: f64, right: f64) -> Result<f64, CommandError> {
+        Ok(match op {
+            BinOp::Add => left + right,
+            BinOp::Sub => left - right,
+            BinOp::Mul => left * right,
+            BinOp::Div if right == 0.0 => {
+                return Err(CommandError::ExecutionError(
+                    "awk: division by zero".to_string(),
+                ));
+            }
+            BinOp::Div => left / right,
+            BinOp::Mod if right == 0.0 => {
+                return Err(CommandError::ExecutionError(
+                    "awk: division by zero in %".to_string(),
+                ));
+            }
+            BinOp::Mod => left % right,
+            BinOp::Pow => left.powf(right),
+            _ => unreachable!("not an arithmetic operator"),
+        })
+    }
+
+    /// Count `bytes` of work, Err once the program has done MAX_WORK
+    fn charge(&mut self, bytes: usize) -> Result<(), CommandError> {
+        self.work += bytes;
+        if self.work > MAX_WORK {
+            log::debug!("awk program stopped after {} bytes of strings", MAX_WORK);
+            return Err(out_of_memory());
+        }
+        Ok(())
+    }
+
+    /// `expr`'s value, charged by its length: each value is a fresh string
+    fn eval(&mut self, expr: &Expr) -> Result<Value, CommandError> {
+        let value = self.evaluate(expr)?;
+        match &value {
+            Value::Str(s) | Value::StrNum(s) => self.charge(1 + s.len())?,
+            _ => self.charge(1)?,
+        }
+        Ok(value)
+    }
+
+    fn evaluate(&mut self, expr: &Expr) -> Result<Value, CommandError> {
+        Ok(match expr {
+            Expr::Num(n) => Value::Num(*n),
+            Expr::Str(s) => Value::Str(s.clone()),
+            Expr::Regex(source) => {
+                let regex = self.regex(source)?;
+                self.charge(self.record.len())?;
+                bool_value(regex.is_match(&self.record))
+            }
+            Expr::Var(name) => self.get_var(name),
+            Expr::Field(index) => {
+                let index = self.field_index(index)?;
+                self.get_field(index)
+            }
+            Expr::Group(list) => {
+                let separator = self.get_var("SUBSEP").str();
+                let mut parts = Vec::new();
+                for expr in list {
+                    parts.push(self.eval(expr)?.str());
+                }
+                Value::Str(parts.join(&separator))
+            }
+            Expr::Assign(target, op, value) => {
+                let mut value = self.eval(value)?;
+                if let Some(op) = op {
+                    let current = self.eval(target)?.num();
+                    value = Value::Num(Self::arithmetic(*op, current, value.num())?);
+                }
+                self.assign(target, value.clone())?;
+                value
+            }
+            Expr::Incr {
+                target,
+                delta,
+                prefix,
+            } => {
+                let current = self.eval(target)?.num();
+                self.assign(target, Value::Num(current + delta))?;
+                Value::Num(if *prefix { current + delta } else { current })
+            }
+            Expr::Not(operand) => bool_value(!self.eval(operand)?.truthy()),
+            Expr::Neg(operand) => Value::Num(-self.eval(operand)?.num()),
+            Expr::Plus(operand) => Value::Num(self.eval(operand)?.num()),
+            Expr::Binary(op, left, right) => {
+                let left = self.eval(left)?;
+                let right = self.eval(right)?;
+                match op {
+                    BinOp::Concat => {
+                        let (left, right) = (left.str(), right.str());
+                        if left.len() + right.len() > MAX_STRING_LENGTH {
+                            return Err(out_of_memory());
+                        }
+                        Value::Str(left + &right)
+                    }
+                    BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge | BinOp::Eq | BinOp::Ne => {
+                        let ordering = match left.is_numeric() && right.is_numeric() {
+                            true => left
+                                .num()
+                                .partial_cmp(&right.num())
+                                .unwrap_or(Ordering::Equal),
+                            false => left.str().cmp(&right.str()),
+                        };
+                        bool_value(match op {
+                            BinOp::Lt => ordering == Ordering::Less,
+                            BinOp::Le => ordering != Ordering::Greater,
+                            BinOp::Gt => ordering == Ordering::Greater,
+                            BinOp::Ge => ordering != Ordering::Less,
+                            BinOp::Eq => ordering == Ordering::Equal,
+                            _ => ordering != Ordering::Equal,
+                        })
+                    }
+                    _ => Value::Num(Self::arithmetic(*op, left.num(), right.num())?),
+                }
+            }
+            Expr::Match(negate, subject, pattern) => {
+                let subject = self.eval(subject)?.str();
+                let regex = self.regex_of(pattern)?;
+                bool_value(regex.is_match(&subject) != *negate)
+            }
+            Expr::And(left, right) => {
+                bool_value(self.eval(left)?.truthy() && self.eval(right)?.truthy())
+            }
+            Expr::Or(left, right) => {
+                bool_value(self.eval(left)?.truthy() || self.eval(right)?.truthy())
+            }
+            Expr::Cond(condition, then, otherwise) => match self.eval(condition)?.truthy() {
+                true => self.eval(then)?,
+                false => self.eval(otherwise)?,
+            },
+            Expr::Call(name, args) => self.call(name, args)?,
+        })
+    }
+
+    fn call(&mut self, name: &str, args: &[Expr]) -> Result<Value, CommandError> {
+        if matches!(name, "sub" | "gsub") {
+            return self.substitute(name == "gsub", args);
+        }
+        if name == "match" {
+            let subject = self.eval(&args[0])?.str();
+            let regex = self.regex_of(&args[1])?;
+            let (start, length) = match regex.find(&subject) {
+                Some(found) => (
+                    subject[..found.start()].chars().count() + 1,
+                    found.as_str().chars().count() as f64,
+                ),
+                None => (0, -1.0),
+            };
+            self.set_var("RSTART", Value::Num(start as f64))?;
+            self.set_var("RLENGTH", Value::Num(length))?;
+            return Ok(Value::Num(start as f64));
+        }
+        let mut values = Vec::new();
+        for arg in args {
+            values.push(self.eval(arg)?);
+        }
+        let num = |i: usize| values.get(i).map_or(0.0, Value::num);
+        let string = |i: usize| values.get(i).map_or(String::new(), Value::str);
+        Ok(match name {
+            "length" => match values.is_empty() {
+                true => Value::Num(self.record.chars().count() as f64),
+                false => Value::Num(string(0).chars().count() as f64),
+            },
+            "substr" => {
+                let chars: Vec<char> = string(0).chars().collect();
+                // Positions are rounded and clipped to the string, counted from 1
+                let start = num(1).round();
+                let end = match values.len() {
+                    3 => start + num(2).round(),
+                    _ => f64::INFINITY,
+                };
+                let from = start.max(1.0) as usize - 1;
+                let to = end.min(chars.len() as f64 + 1.0).max(1.0) as usize - 1;
+                Value::Str(chars[from.min(to)..to].iter().collect())
+            }
+            "index" => {
+                let haystack = string(0);
+                let position = haystack
+                    .find(&string(1))
+                    .map_or(0, |at| haystack[..at].chars().count() + 1);
+                Value::Num(position as f64)
+            }
+            "sprintf" => {
+                let formatted = sprintf(&string(0), &values[1..]);
+                if formatted.len() > MAX_STRING_LENGTH {
+                    return Err(out_of_memory());
+                }
+                Value::Str(formatted)
+            }
+            "tolower" => Value::Str(string(0).to_lowercase()),
+            "toupper" => Value::Str(string(0).to_uppercase()),
+            "int" => Value::Num(num(0).trunc()),
+            "sqrt" => Value::Num(num(0).sqrt()),
+            "exp" => Value::Num(num(0).exp()),
+            "log" => Value::Num(num(0).ln()),
+            "sin" => Value::Num(num(0).sin()),
+            "cos" => Value::Num(num(0).cos()),
+            "atan2" => Value::Num(num(0).atan2(num(1))),
+            // Nothing is ever opened or buffered
+            "close" => Value::Num(-1.0),
+            _ => Value::Num(0.0),
+        })
+    }
+
+    /// `sub` and `gsub`: `&` in the replacement is the match, `\&` a plain `&`
+    fn substitute(&mut self, global: bool, args: &[Expr]) -> Result<Value, CommandError> {
+        let regex = self.regex_of(&args[0])?;
+        let replacement = self.eval(&args[1])?.str();
+        let record = Expr::Field(Box::new(Expr::Num(0.0)));
+        let target = args.get(2).unwrap_or(&record);
+        let subject = self.eval(target)?.str();
+
+        let mut out = String::new();
+        let mut copied = 0;
+        let mut count = 0;
+        for found in regex.find_iter(&subject) {
+            out.push_str(&subject[copied..found.start()]);
+            let mut chars = replacement.chars().peekable();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' if chars.peek() == Some(&'&') => {
+                        chars.next();
+                        out.push('&');
+                    }
+                    '&' => out.push_str(found.as_str()),
+                    c => out.push(c),
+                }
+            }
+            if out.len() > MAX_STRING_LENGTH {
+                return Err(out_of_memory());
+            }
+            self.charge(found.len() + replacement.len())?;
+            copied = found.end();
+            count += 1;
+            if !global {
+                break;
+            }
+        }
+        if count > 0 {
+            out.push_str(&subject[copied..]);
+            self.assign(target, Value::Str(out))?;
+        }
+        Ok(Value::Num(count as f64))
+    }
+
+    fn exec_block(&mut self, statements: &[Stmt]) -> Result<Flow, CommandError> {
+        for statement in statements {
+            let flow = self.exec(statement)?;
+            if flow != Flow::Normal {
+                return Ok(flow);
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    /// Count a loop iteration, false once the program has used up its budget
+    fn iterate(&mut self) -> bool {
+        self.iterations += 1;
+        if self.iterations == MAX_LOOP_ITERATIONS {
+            log::debug!(
+                "awk program stopped after {} loop iterations",
+                MAX_LOOP_ITERATIONS
+            );
+        }
+        self.iterations < MAX_LOOP_ITERATIONS
+    }
+
+    /// Stop the program once it printed more than any command may
+    fn output_flow(&self) -> Flow {
+        match self.out.len() > MAX_STRING_LENGTH {
+            true => {
+                log::debug!(
+                    "awk program stopped after {} bytes of output",
+                    self.out.len()
+                );
+                Flow::Exit
+            }
+            false => Flow::Normal,
+        }
+    }
+
+    fn exec(&mut self, statement: &Stmt) -> Result<Flow, CommandError> {
+        match statement {
+            Stmt::Expr(expr) => {
+                self.eval(expr)?;
+            }
+            Stmt::Print(args, redirected) => {
+                let line = match args.is_empty() {
+                    true => self.record.clone(),
+                    false => {
+                        let mut parts = Vec::new();
+                        for arg in args {
+                            parts.push(self.eval(arg)?.str());
+                        }
+                        parts.join(&self.get_var("OFS").str())
+                    }
+                };
+                if !redirected {
+                    self.charge(line.len())?;
+                    self.out.push_str(&line);
+                    self.out.push_str(&self.get_var("ORS").str());
+                }
+                return Ok(self.output_flow());
+            }
+            Stmt::Printf(args, redirected) => {
+                let mut values = Vec::new();
+                for arg in args {
+                    values.push(self.eval(arg)?);
+                }
+                if !redirected {
+                    let formatted = sprintf(&values[0].str(), &values[1..]);
+                    if formatted.len() > MAX_STRING_LENGTH {
+                        return Err(out_of_memory());
+                    }
+                    self.charge(formatted.len())?;
+                    self.out.push_str(&formatted);
+                }
+                return Ok(self.output_flow());
+            }
+            Stmt::If(condition, then, otherwise) => {
+                if self.eval(condition)?.truthy() {
+                    return self.exec(then);
+                }
+                if let Some(otherwise) = otherwise {
+                    return self.exec(otherwise);
+                }
+            }
+            Stmt::While(condition, body) => {
+                while self.eval(condition)?.truthy() {
+                    if !self.iterate() {
+                        return Ok(Flow::Exit);
+                    }
+                    match self.exec(body)? {
+                        Flow::Break => break,
+                        Flow::Next => return Ok(Flow::Next),
+                        Flow::Exit => return Ok(Flow::Exit),
+                        Flow::Normal | Flow::Continue => {}
+                    }
+                }
+            }
+            Stmt::For(init, condition, step, body) => {
+                if let Some(init) = init {
+                    self.eval(init)?;
+                }
+                loop {
+                    if let Some(condition) = condition
+                        && !self.eval(condition)?.truthy()
+                    {
+                        break;
+                    }
+                    if !self.iterate() {
+                        return Ok(Flow::Exit);
+                    }
+                    match self.exec(body)? {
+                        Flow::Break => break,
+                        Flow::Next => return Ok(Flow::Next),
+                        Flow::Exit => return Ok(Flow::Exit),
+                        Flow::Normal | Flow::Continue => {}
+                    }
+                    if let Some(step) = step {
+                        self.eval(step)?;
+                    }
+                }
+            }
+            Stmt::Block(statements) => return self.exec_block(statements),
+            Stmt::Next => return Ok(Flow::Next),
+            Stmt::Exit(code) => {
+                if let Some(code) = code {
+                    self.eval(code)?;
+                }
+                return Ok(Flow::Exit);
+            }
+            Stmt::Break => return Ok(Flow::Break),
+            Stmt::Continue => return Ok(Flow::Continue),
+        }
+        Ok(Flow::Normal)
+    }
+
+    /// Run `program` over the lines of `input`, returning what it printed
+    fn run(mut self, program: &Program, input: &str) -> Result<String, CommandError> {
+        let mut exited = false;
+        for block in &program.begin {
+            if self.exec_block(block)? == Flow::Exit {
+                exited = true;
+                break;
+            }
+        }
+
+        // A program of only BEGIN actions doesn't read its input
+        let reads_input = !program.items.is_empty() || !program.end.is_empty();
+        let mut in_range = vec![false; program.items.len()];
+        if !exited && reads_input {
+            'records: for line in input.lines() {
+                self.nr += 1;
+                self.charge(line.len())?;
+                self.set_record(line.to_string());
+                for (item, in_range) in program.items.iter().zip(in_range.iter_mut()) {
+                    let selected = match &item.pattern {
+                        Pattern::All => true,
+                        Pattern::Expr(expr) => self.eval(expr)?.truthy(),
+                        Pattern::Range(start, end) => {
+                            if !*in_range && self.eval(start)?.truthy() {
+                                *in_range = true;
+                            }
+                            let selected = *in_range;
+                            if *in_range && self.eval(end)?.truthy() {
+                                *in_range = false;
+                            }
+                            selected
+                        }
+                    };
+                    if !selected {
+                        continue;
+                    }
+                    let flow = match &item.action {
+                        Some(action) => self.exec_block(action)?,
+                        None => {
+                            self.charge(self.record.len())?;
+                            self.out.push_str(&self.record);
+                            self.out.push_str(&self.get_var("ORS").str());
+                            self.output_flow()
+                        }
+                    };
+                    match flow {
+                        Flow::Next => continue 'records,
+                        Flow::Exit => break 'records,
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        for block in &program.end {
+            if self.exec_block(block)? == Flow::Exit {
+                break;
+            }
+        }
+        Ok(self.out)
+    }
+}
+
+/// `\t` and the other escapes in the values of `-F` and `-v`
+fn unescape(value: &str) -> String {
+    let mut out = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('\\') => out.push('\\'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// awk's command line
+#[derive(Debug, Default)]
+struct Options {
+    separator: Option<String>,
+    assignments: Vec<(String, String)>,
+    program_file: Option<String>,
+    operands: Vec<String>,
+}
+
+/// `name=value`, as given to `-v` or as an operand
+fn assignment(arg: &str) -> Option<(String, String)> {
+    let (name, value) = arg.split_once('=')?;
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    valid.then(|| (name.to_string(), unescape(value)))
+}
+
+fn parse_options(args: &[String]) -> Result<Options, CommandError> {
+    let mut options = Options::default();
+    let mut iter = args.iter();
+    let missing = |flag: &str| {
+        CommandError::InvalidArguments(format!(
+            "awk: option requires an argument -- {}\r\n{}",
+            flag, USAGE
+        ))
+    };
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--" => {
+                options.operands.extend(iter.by_ref().cloned());
+                break;
+            }
+            "-F" => options.separator = Some(iter.next().ok_or_else(|| missing("F"))?.clone()),
+            "-f" => options.program_file = Some(iter.next().ok_or_else(|| missing("f"))?.clone()),
+            "-v" => {
+                let value = iter.next().ok_or_else(|| missing("v"))?;
+                options.assignments.extend(assignment(value));
+            }
+            "-W" => {
+                iter.next();
+            }
+            "-" => options.operands.push(arg.clone()),
+            flag if flag.starts_with("-F") => options.separator = Some(flag[2..].to_string()),
+            flag if flag.starts_with("-f") => options.program_file = Some(flag[2..].to_string()),
+            flag if flag.starts_with("-v") => options.assignments.extend(assignment(&flag[2..])),
+            flag if flag.starts_with("-W") => {}
+            flag if flag.starts_with('-') => {
+                return Err(CommandError::InvalidArguments(format!(
+                    "awk: not an option: {}",
+                    flag
+                )));
+            }
+            _ => {
+                options.operands.push(arg.clone());
+                options.operands.extend(iter.by_ref().cloned());
+                break;
+            }
+        }
+    }
+    Ok(options)
+}
+
+fn absolute(path: &str, context: &CommandContext) -> String {
+    match path.starts_with('/') {
+        true => path.to_string(),
+        false => format!("{}/{}", context.cwd.trim_end_matches('/'), path),
+    }
+}
+
+/// mawk 1.3.4, the common subset of awk described at the top of this file
+#[derive(Clone, Copy, Debug)]
+pub struct AwkCommand;
+
+impl AwkCommand {
+    /// Run the program over `piped` or the files named in `args`
+    pub(crate) async fn run(
+        &self,
+        args: &[String],
+        piped: &str,
+        context: &CommandContext,
+    ) -> CommandResult {
+        let mut options = parse_options(args)?;
+        let fs = context.filesystem.read().await;
+        let source = match &options.program_file {
+            Some(path) => {
+                let absolute = absolute(path, context);
+                match fs.follow_symlink(&absolute).map(|entry| entry.file_content) {
+                    Ok(Some(FileContent::RegularFile(bytes))) => {
+                        context.note_read(&fs, &absolute);
+                        String::from_utf8_lossy(&bytes).into_owned()
+                    }
+                    _ => {
+                        return Err(CommandError::FilesystemError(format!(
+                            "awk: couldn't open file {}",
+                            path
+                        )));
+                    }
+                }
+            }
+            None if options.operands.is_empty() => {
+                return Err(CommandError::InvalidArguments(USAGE.to_string()));
+            }
+            None => options.operands.remove(0),
+        };
+        let program = Parser {
+            tokens: tokenize(&source)?,
+            pos: 0,
+            no_gt: false,
+        }
+        .program()?;
+
+        let mut interpreter = Interpreter::new();
+        if let Some(separator) = &options.separator {
+            let separator = match separator.as_str() {
+                "t" => "\t".to_string(),
+                other => unescape(other),
+            };
+            interpreter.set_var("FS", Value::Str(separator))?;
+        }
+        for (name, value) in &options.assignments {
+            interpreter.set_var(name, Value::StrNum(value.clone()))?;
+        }
+
+        let mut input = String::new();
+        let mut errors = Vec::new();
+        let mut files = 0;
+        for operand in &options.operands {
+            if let Some((name, value)) = assignment(operand) {
+                interpreter.set_var(&name, Value::StrNum(value))?;
+                continue;
+            }
+            files += 1;
+            if operand == "-" {
+                input.push_str(piped);
+                continue;
+            }
+            let absolute = absolute(operand, context);
+            match fs.follow_symlink(&absolute).map(|entry| entry.file_content) {
+                Ok(Some(FileContent::RegularFile(bytes))) => {
+                    context.note_read(&fs, &absolute);
+                    input.push_str(&String::from_utf8_lossy(&bytes));
+                    if !input.is_empty() && !input.ends_with('\n') {
+                        input.push('\n');
+                    }
+                }
+                // mawk reads a directory as if it were empty
+                Ok(Some(FileContent::Directory(_))) => {}
+                _ => errors.push(format!(
+                    "awk: cannot open {} (No such file or directory)",
+                    operand
+                )),
+            }
+        }
+        drop(fs);
+        if files == 0 {
+            input.push_str(piped);
+        }
+
+        // The program may run for a while, the session's thread isn't held up
+        let out = match tokio::task::spawn_blocking(move || interpreter.run(&program, &input)).await
+        {
+            Ok(out) => out?,
+            Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+            Err(err) => return Err(CommandError::ExecutionError(format!("awk: {}", err))),
+        };
+        if !errors.is_empty() && out.is_empty() {
+            return Err(CommandError::FilesystemError(errors.join("\r\n")));
+        }
+        Ok(out.replace('\n', "\r\n"))
+    }
+}
+
+#[async_trait]
+impl Command for AwkCommand {
+    fn name(&self) -> &'static str {
+        "awk"
+    }
+
+    fn help(&self) -> String {
+        format!("{}\r\n", USAGE)
+    }
+
+    fn version(&self) -> String {
+        VERSION.to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        let first = args.first().map(String::as_str);
+        let second = args.get(1).map(String::as_str);
+        match (first, second) {
+            (Some("--version" | "-Wversion" | "-Wv"), _) => Ok(self.version()),
+            (Some("-W"), Some(option)) if option.starts_with('v') => Ok(self.version()),
+            (Some("--help" | "-Wusage"), _) => Ok(self.help()),
+            (Some("-W"), Some("usage")) => Ok(self.help()),
+            _ => self.run(args, "", context).await,
+        }
+    }
+}
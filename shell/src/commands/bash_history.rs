@@ -0,0 +1,240 @@
+use super::accounts::home_dir;
+use super::busybox::write_file;
+use crate::filesystem::fs2::{FileContent, FileSystem};
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex as StdMutex};
+
+/// Lines bash keeps in ~/.bash_history, Ubuntu's default HISTFILESIZE
+const HISTFILESIZE: usize = 2000;
+
+/// Histories of typed commands kept at once. Commands for new ones are dropped
+/// past it, a scan trying every login name can't fill memory
+const MAX_TYPED_HISTORIES: usize = 10_000;
+
+/// What the admin of a web server running the services in the process table
+/// would have typed: apache, mysql, redis, postgres and docker
+const DEFAULT_HISTORY: &str = "\
+sudo apt update
+sudo apt upgrade -y
+sudo apt install -y apache2 mysql-server redis-server
+sudo systemctl enable --now apache2
+sudo systemctl status apache2
+sudo mysql_secure_installation
+sudo mysql -u root -p
+sudo nano /etc/redis/redis.conf
+sudo systemctl restart redis-server
+redis-cli ping
+cd /var/www/html
+ls -la
+sudo nano index.html
+cd
+sudo apt install -y docker.io
+sudo usermod -aG docker $USER
+docker ps
+docker compose up -d
+docker ps -a
+docker logs app
+cd /var/www/app
+git status
+git pull
+sudo chown -R www-data:www-data /var/www/app
+sudo a2enmod rewrite
+sudo apache2ctl configtest
+sudo systemctl reload apache2
+tail -f /var/log/apache2/error.log
+sudo apt install -y postgresql-14
+sudo -u postgres psql
+df -h
+free -m
+htop
+sudo ufw allow 80/tcp
+sudo ufw allow 443/tcp
+sudo ufw status
+crontab -e
+sudo journalctl -u mysql --since today
+ls
+exit
+";
+
+/// What goes into ~/.bash_history: the configured history or the built-in one,
+/// and the commands typed in sessions when they are recorded
+#[derive(Debug, Clone, Default)]
+pub struct BashHistory {
+    configured: Option<Arc<str>>,
+    typed: Option<TypedHistory>,
+}
+
+/// Commands typed in sessions, kept apart from the filesystem so a rollback to
+/// the snapshot doesn't lose them. A session's ~/.bash_history is written from them
+#[derive(Debug, Clone)]
+struct TypedHistory {
+    /// Every client address has its own history instead of sharing the user's
+    per_ip: bool,
+    /// Commands only show up in later sessions, as bash writes the file at logout
+    at_logout: bool,
+    histories: Arc<StdMutex<TypedHistories>>,
+}
+
+#[derive(Debug, Default)]
+struct TypedHistories {
+    /// The history each home had before anything typed was added to it
+    bases: HashMap<String, String>,
+    /// Typed commands by client address (None unless per_ip) and user
+    commands: HashMap<(Option<IpAddr>, String), VecDeque<String>>,
+}
+
+impl BashHistory {
+    pub fn new(configured: Option<String>) -> Self {
+        Self {
+            configured: configured.map(Arc::from),
+            typed: None,
+        }
+    }
+
+    /// Add the commands typed in sessions to ~/.bash_history from now on. Clones
+    /// share what was typed, one is made per filesystem
+    pub fn with_typed_commands(mut self, per_ip: bool, at_logout: bool) -> Self {
+        self.typed = Some(TypedHistory {
+            per_ip,
+            at_logout,
+            histories: Arc::default(),
+        });
+        self
+    }
+
+    /// The history `user` starts with. The built-in one leaves out sudo for root
+    pub fn for_user(&self, user: &str) -> String {
+        if let Some(configured) = &self.configured {
+            return configured.to_string();
+        }
+        if user != "root" {
+            return DEFAULT_HISTORY.to_string();
+        }
+        DEFAULT_HISTORY
+            .lines()
+            .filter(|line| !line.starts_with("sudo usermod"))
+            .map(|line| match line.strip_prefix("sudo ") {
+                // Running something as another user still takes sudo
+                Some(command) if !command.starts_with("-u ") => format!("{}\n", command),
+                _ => format!("{}\n", line),
+            })
+            .collect()
+    }
+
+    /// Write the history of `user` from `ip` with what was typed in earlier sessions,
+    /// when typed commands are recorded
+    pub fn session_started(
+        &self,
+        fs: &mut FileSystem,
+        ip: IpAddr,
+        user: &str,
+    ) -> std::io::Result<()> {
+        match &self.typed {
+            Some(typed) => typed.write(fs, ip, user),
+            None => Ok(()),
+        }
+    }
+
+    /// Record a command typed in a session the way bash does with
+    /// HISTCONTROL=ignoreboth, Ubuntu's default: lines starting with a space and
+    /// repeats of the previous line are left out
+    pub fn command_typed(
+        &self,
+        fs: &mut FileSystem,
+        ip: IpAddr,
+        user: &str,
+        command: &str,
+    ) -> std::io::Result<()> {
+        let Some(typed) = &self.typed else {
+            return Ok(());
+        };
+        if command.trim().is_empty() || command.starts_with(' ') {
+            return Ok(());
+        }
+        {
+            let mut histories = typed.histories.lock().unwrap();
+            let key = (typed.per_ip.then_some(ip), user.to_string());
+            if !histories.commands.contains_key(&key)
+                && histories.commands.len() >= MAX_TYPED_HISTORIES
+            {
+                return Ok(());
+            }
+            let commands = histories.commands.entry(key).or_default();
+            if commands.back().map(String::as_str) == Some(command) {
+                return Ok(());
+            }
+            if commands.len() >= HISTFILESIZE {
+                commands.pop_front();
+            }
+            commands.push_back(command.to_string());
+        }
+        match typed.at_logout {
+            true => Ok(()),
+            false => typed.write(fs, ip, user),
+        }
+    }
+}
+
+impl TypedHistory {
+    /// Replace ~/.bash_history of `user` with the history it started with and the
+    /// commands typed for it, the last HISTFILESIZE lines like bash truncates it to.
+    /// The home is the one /etc/passwd gives `user`
+    fn write(&self, fs: &mut FileSystem, ip: IpAddr, user: &str) -> std::io::Result<()> {
+        let home = home_dir(fs, user);
+        let path = format!("{}/.bash_history", home);
+        let mut histories = self.histories.lock().unwrap();
+        let base = histories
+            .bases
+            .entry(home)
+            .or_insert_with(
+                || match fs.get_file(&path).map(|entry| entry.file_content) {
+                    Ok(Some(FileContent::RegularFile(bytes))) => {
+                        String::from_utf8_lossy(&bytes).into_owned()
+                    }
+                    _ => String::new(),
+                },
+            )
+            .clone();
+        let commands = histories
+            .commands
+            .get(&(self.per_ip.then_some(ip), user.to_string()));
+        let lines: Vec<&str> = base
+            .lines()
+            .chain(commands.into_iter().flatten().map(String::as_str))
+            .collect();
+        let content: String = lines[lines.len().saturating_sub(HISTFILESIZE)..]
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect();
+        write_file(fs, &path, content.into_bytes(), 0o600)
+    }
+}
+
+/// Whether the file at `path` already holds a history. Images often ship the
+/// file with its size but nothing, or only zeros, in it
+fn has_history(fs: &FileSystem, path: &str) -> bool {
+    match fs.get_file(path).map(|entry| entry.file_content) {
+        Ok(Some(FileContent::RegularFile(bytes))) => bytes.iter().any(|&byte| byte != 0),
+        Ok(_) => true,
+        Err(_) => false,
+    }
+}
+
+/// Write `history` to .bash_history in `home`, creating the directory if needed.
+/// A history the image already has is kept
+pub fn write_bash_history(fs: &mut FileSystem, home: &str, history: &str) -> std::io::Result<()> {
+    let path = format!("{}/.bash_history", home.trim_end_matches('/'));
+    if has_history(fs, &path) {
+        return Ok(());
+    }
+    let mut dir = String::new();
+    for component in home.split('/').filter(|c| !c.is_empty()) {
+        dir.push('/');
+        dir.push_str(component);
+        if fs.get_file(&dir).is_err() {
+            fs.create_directory(&dir)?.inode.i_mode = 0o755;
+        }
+    }
+    write_file(fs, &path, history.as_bytes().to_vec(), 0o600)
+}
@@ -1,6 +1,9 @@
+use super::accounts::AccountTable;
 use super::command_trait::{Command, CommandError, CommandResult, StatefulCommand};
 use super::context::CommandContext;
+use super::fetch_policy::{Host, url_host, url_port};
 use crate::filesystem::fs2::FileContent;
+use crate::parser::parse_assignment;
 use async_trait::async_trait;
 
 /// PWD command - print working directory
@@ -17,6 +20,27 @@ impl Command for PwdCommand {
     }
 }
 
+/// ANSI sequence that clears the screen and homes the cursor
+pub const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+/// CLEAR command - clear the terminal (`reset` does the same here)
+pub struct ClearCommand;
+
+#[async_trait]
+impl Command for ClearCommand {
+    fn name(&self) -> &'static str {
+        "clear"
+    }
+
+    fn aliases(&self) -> Vec<&'static str> {
+        vec!["reset"]
+    }
+
+    async fn execute(&self, _args: &[String], _context: &mut CommandContext) -> CommandResult {
+        Ok(CLEAR_SCREEN.to_string())
+    }
+}
+
 /// WHOAMI command - print current username
 pub struct WhoamiCommand;
 
@@ -64,12 +88,87 @@ impl Command for IdCommand {
             return Ok("id (GNU coreutils) 8.32\n".to_string());
         }
 
-        // Simple implementation - just return fake but realistic ID info
-        let username = &context.username;
-        Ok(format!(
-            "uid=1000({}) gid=1000({}) groups=1000({}),4(adm),24(cdrom),27(sudo),30(dip),46(plugdev),120(lpadmin),131(lxd),132(sambashare)\r\n",
-            username, username, username
-        ))
+        let mut only: Vec<char> = Vec::new();
+        let mut names = false;
+        let mut user = None;
+        for arg in args {
+            match arg.as_str() {
+                "--user" => only.push('u'),
+                "--group" => only.push('g'),
+                "--groups" => only.push('G'),
+                "--name" => names = true,
+                "--real" => {}
+                option if option.starts_with('-') && option.len() > 1 => {
+                    for flag in option.chars().skip(1) {
+                        match flag {
+                            'u' | 'g' | 'G' => only.push(flag),
+                            'n' => names = true,
+                            'r' | 'z' => {}
+                            _ => {
+                                return Err(CommandError::InvalidArguments(format!(
+                                    "id: invalid option -- '{}'\r\nTry 'id --help' for more information.",
+                                    flag
+                                )));
+                            }
+                        }
+                    }
+                }
+                name => user = Some(name),
+            }
+        }
+        only.dedup();
+        if only.len() > 1 {
+            return Err(CommandError::InvalidArguments(
+                "id: cannot print \"only\" of more than one choice".to_string(),
+            ));
+        }
+        if names && only.is_empty() {
+            return Err(CommandError::InvalidArguments(
+                "id: cannot print only names or real IDs in default format".to_string(),
+            ));
+        }
+
+        let table = AccountTable::read(&*context.filesystem.read().await, &context.username);
+        let account = match user {
+            Some(user) => table.user(user).ok_or_else(|| {
+                CommandError::ExecutionError(format!("id: ‘{}’: no such user", user))
+            })?,
+            None => table.user(&context.username).ok_or_else(|| {
+                CommandError::ExecutionError("id: cannot find name for user ID".to_string())
+            })?,
+        };
+        let groups = table.groups_of(account);
+        let show = |id: u32, name: Option<&str>| match name {
+            Some(name) if names => name.to_string(),
+            _ => id.to_string(),
+        };
+
+        let line = match only.first() {
+            Some('u') => show(account.uid, Some(&account.name)),
+            Some('g') => show(groups[0].0, groups[0].1),
+            Some(_) => groups
+                .iter()
+                .map(|&(gid, name)| show(gid, name))
+                .collect::<Vec<_>>()
+                .join(" "),
+            None => {
+                let named = |id: u32, name: Option<&str>| match name {
+                    Some(name) => format!("{}({})", id, name),
+                    None => id.to_string(),
+                };
+                format!(
+                    "uid={} gid={} groups={}",
+                    named(account.uid, Some(&account.name)),
+                    named(groups[0].0, groups[0].1),
+                    groups
+                        .iter()
+                        .map(|&(gid, name)| named(gid, name))
+                        .collect::<Vec<_>>()
+                        .join(",")
+                )
+            }
+        };
+        Ok(format!("{}\r\n", line))
     }
 }
 
@@ -109,17 +208,37 @@ impl StatefulCommand for CdCommand {
         // Determine target directory
         let raw = args
             .iter()
-            .find(|a| !a.starts_with('-'))
+            .find(|a| *a == "-" || !a.starts_with('-'))
             .map(|s| s.as_str())
             .unwrap_or("");
-        let target_dir = if raw.is_empty() || raw == "~" {
-            // Go to home directory
-            format!("/home/{}", context.username)
-        } else if raw == "-" {
-            // Go to previous directory (simplified: just go to home)
-            format!("/home/{}", context.username)
+        let home = context
+            .get_env("HOME")
+            .cloned()
+            .unwrap_or_else(|| format!("/home/{}", context.username));
+        // `cd -` goes back to $OLDPWD and prints where it went
+        let back = raw == "-";
+        let target_dir = if raw.is_empty() {
+            match context.get_env("HOME") {
+                Some(home) => home.clone(),
+                None => {
+                    return Err(CommandError::ExecutionError(
+                        "bash: cd: HOME not set".to_string(),
+                    ));
+                }
+            }
+        } else if raw == "~" {
+            home
+        } else if back {
+            match context.get_env("OLDPWD") {
+                Some(oldpwd) => oldpwd.clone(),
+                None => {
+                    return Err(CommandError::ExecutionError(
+                        "bash: cd: OLDPWD not set".to_string(),
+                    ));
+                }
+            }
         } else if let Some(expanded) = raw.strip_prefix("~/") {
-            format!("/home/{}/{}", context.username, expanded)
+            format!("{}/{}", home.trim_end_matches('/'), expanded)
         } else if raw.starts_with('/') {
             // Absolute path
             raw.to_string()
@@ -141,26 +260,31 @@ impl StatefulCommand for CdCommand {
                 match &entry.file_content {
                     Some(FileContent::Directory(_)) => {
                         drop(fs); // Release the filesystem lock before modifying context
-                        // Update the current working directory
-                        context.set_cwd(resolved);
+                        // Update the current working directory, the one left becomes $OLDPWD
+                        let left = context.cwd.clone();
+                        context.set_env("OLDPWD".to_string(), left);
+                        context.set_cwd(resolved.clone());
+                        if back {
+                            return Ok(format!("{}\r\n", resolved));
+                        }
                         Ok(String::new()) // cd doesn't output anything on success
                     }
-                    Some(FileContent::RegularFile(_)) => {
-                        Ok(format!("bash: cd: {}: Not a directory\r\n", resolved))
-                    }
-                    Some(FileContent::SymbolicLink(_)) => {
-                        Ok(format!("bash: cd: {}: Not a directory\r\n", resolved))
+                    Some(FileContent::RegularFile(_)) | Some(FileContent::SymbolicLink(_)) => {
+                        Err(CommandError::FilesystemError(format!(
+                            "bash: cd: {}: Not a directory",
+                            resolved
+                        )))
                     }
-                    None => Ok(format!(
-                        "bash: cd: {}: No such file or directory\r\n",
+                    None => Err(CommandError::FilesystemError(format!(
+                        "bash: cd: {}: No such file or directory",
                         resolved
-                    )),
+                    ))),
                 }
             }
-            Err(_) => Ok(format!(
-                "bash: cd: {}: No such file or directory\r\n",
+            Err(_) => Err(CommandError::FilesystemError(format!(
+                "bash: cd: {}: No such file or directory",
                 resolved
-            )),
+            ))),
         }
     }
 }
@@ -181,7 +305,7 @@ impl Command for WgetCommand {
             .to_string()
     }
 
-    async fn execute(&self, args: &[String], _context: &mut CommandContext) -> CommandResult {
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
         if args.iter().any(|a| a == "--help") {
             return Ok(self.help());
         }
@@ -190,7 +314,37 @@ impl Command for WgetCommand {
             return Ok("GNU Wget 1.20.3\n".to_string());
         }
 
-        Ok("wget: missing URL\r\nUsage: wget [OPTION]... [URL]...\r\n\r\nTry `wget --help' for more options.\r\n".to_string())
+        let urls = fetch_targets(args, &["-O", "-o", "-P", "-U", "-t", "-T", "-e"]);
+        if urls.is_empty() {
+            return Ok("wget: missing URL\r\nUsage: wget [OPTION]... [URL]...\r\n\r\nTry `wget --help' for more options.\r\n".to_string());
+        }
+
+        // Nothing is downloaded, every URL fails the way an unreachable host would
+        let mut output = String::new();
+        for url in urls {
+            check_fetch_policy("wget", url, context);
+            output.push_str(&format!(
+                "--{}--  {}\r\n",
+                chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+                url
+            ));
+            match url_host(url) {
+                Some(Host::Ip(ip)) => output.push_str(&format!(
+                    "Connecting to {}:{}... failed: Connection refused.\r\n",
+                    ip,
+                    url_port(url)
+                )),
+                Some(Host::Domain(name)) => output.push_str(&format!(
+                    "Resolving {0} ({0})... failed: Temporary failure in name resolution.\r\n\
+                    wget: unable to resolve host address \u{2018}{0}\u{2019}\r\n",
+                    name
+                )),
+                None => output.push_str(&format!("{}: Invalid host name.\r\n", url)),
+            }
+        }
+        Err(CommandError::ExecutionError(
+            output.trim_end_matches("\r\n").to_string(),
+        ))
     }
 }
 
@@ -210,7 +364,7 @@ impl Command for CurlCommand {
             .to_string()
     }
 
-    async fn execute(&self, args: &[String], _context: &mut CommandContext) -> CommandResult {
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
         if args.iter().any(|a| a == "--help") {
             return Ok(self.help());
         }
@@ -219,7 +373,83 @@ impl Command for CurlCommand {
             return Ok("curl 7.68.0\n".to_string());
         }
 
-        Ok("curl: try 'curl --help' or 'curl --manual' for more information\r\n".to_string())
+        let urls = fetch_targets(
+            args,
+            &[
+                "-o",
+                "-X",
+                "-H",
+                "-d",
+                "-A",
+                "-u",
+                "-e",
+                "-T",
+                "-x",
+                "-F",
+                "--output",
+                "--request",
+                "--header",
+                "--data",
+                "--user-agent",
+                "--user",
+                "--referer",
+                "--proxy",
+            ],
+        );
+        let Some(url) = urls.first() else {
+            return Ok(
+                "curl: try 'curl --help' or 'curl --manual' for more information\r\n".to_string(),
+            );
+        };
+
+        // curl gives up on the first URL it can't reach
+        for url in &urls {
+            check_fetch_policy("curl", url, context);
+        }
+        Err(CommandError::ExecutionError(match url_host(url) {
+            Some(Host::Ip(ip)) => format!(
+                "curl: (7) Failed to connect to {} port {}: Connection refused",
+                ip,
+                url_port(url)
+            ),
+            Some(Host::Domain(name)) => format!("curl: (6) Could not resolve host: {}", name),
+            None => "curl: (3) URL using bad/illegal format or missing URL".to_string(),
+        }))
+    }
+}
+
+/// URL arguments of wget/curl, skipping options and the values of `value_options`
+fn fetch_targets<'a>(args: &'a [String], value_options: &[&str]) -> Vec<&'a str> {
+    let mut urls = Vec::new();
+    let mut skip_value = false;
+    for arg in args {
+        if skip_value {
+            skip_value = false;
+        } else if value_options.contains(&arg.as_str()) {
+            skip_value = true;
+        } else if !arg.starts_with('-') {
+            urls.push(arg.as_str());
+        }
+    }
+    urls
+}
+
+/// Record a URL an attacker tried to download and whether the fetch policy lets it through
+fn check_fetch_policy(command: &str, url: &str, context: &CommandContext) {
+    match context.fetch_policy.check_url(url) {
+        Ok(()) => log::info!(
+            "[{}] {} {}: allowed by fetch policy",
+            context.auth_id,
+            command,
+            url
+        ),
+        Err(reason) => log::warn!(
+            "[{}] {} {}: blocked by fetch policy, {}",
+            context.auth_id,
+            command,
+            url,
+            reason
+        ),
     }
 }
 
@@ -327,6 +557,118 @@ impl Command for ExportCommand {
     }
 }
 
+/// ENV command - print the environment, with any `NAME=value` arguments added.
+/// `env NAME=value command` is run by the dispatcher.
+pub struct EnvCommand;
+
+#[async_trait]
+impl Command for EnvCommand {
+    fn name(&self) -> &'static str {
+        "env"
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        let mut env = context.env_vars.clone();
+        env.extend(args.iter().filter_map(|arg| parse_assignment(arg)));
+        let mut names: Vec<&String> = env.keys().collect();
+        names.sort();
+        Ok(names
+            .into_iter()
+            .map(|name| format!("{}={}\r\n", name, env[name]))
+            .collect())
+    }
+}
+
+/// Second line of history's usage errors
+const HISTORY_USAGE: &str = "history: usage: history [-c] [-d offset] [n] or history -anrw [filename] or history -ps arg [arg...]";
+
+/// HISTORY command - list the lines typed in this session, numbered like bash.
+pub struct HistoryCommand;
+
+#[async_trait]
+impl Command for HistoryCommand {
+    fn name(&self) -> &'static str {
+        "history"
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        let mut listed = true;
+        let mut operands = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let flags = match arg.strip_prefix('-') {
+                Some(flags) if !flags.is_empty() && operands.is_empty() => flags,
+                _ => {
+                    operands.push(arg);
+                    continue;
+                }
+            };
+            // With an option history does that instead of listing
+            listed = false;
+            for flag in flags.chars() {
+                match flag {
+                    'c' => context.history.clear(),
+                    'd' => {
+                        let Some(offset) = args.next() else {
+                            return Err(CommandError::InvalidArguments(format!(
+                                "bash: history: -d: option requires an argument\r\n{}",
+                                HISTORY_USAGE
+                            )));
+                        };
+                        match offset.parse::<usize>() {
+                            Ok(line) if (1..=context.history.len()).contains(&line) => {
+                                context.history.remove(line - 1);
+                            }
+                            _ => {
+                                return Err(CommandError::ExecutionError(format!(
+                                    "bash: history: {}: history position out of range",
+                                    offset
+                                )));
+                            }
+                        }
+                    }
+                    // Nothing is read from or written to ~/.bash_history here
+                    'a' | 'n' | 'r' | 'w' => {}
+                    _ => {
+                        return Err(CommandError::InvalidArguments(format!(
+                            "bash: history: -{}: invalid option\r\n{}",
+                            flag, HISTORY_USAGE
+                        )));
+                    }
+                }
+            }
+        }
+        if !listed {
+            return Ok(String::new());
+        }
+
+        let skip = match operands.as_slice() {
+            [] => 0,
+            [count] => match count.parse::<usize>() {
+                Ok(count) => context.history.len().saturating_sub(count),
+                Err(_) => {
+                    return Err(CommandError::InvalidArguments(format!(
+                        "bash: history: {}: numeric argument required",
+                        count
+                    )));
+                }
+            },
+            _ => {
+                return Err(CommandError::InvalidArguments(
+                    "bash: history: too many arguments".to_string(),
+                ));
+            }
+        };
+        Ok(context
+            .history
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .map(|(index, line)| format!("{:>5}  {}\r\n", index + 1, line))
+            .collect())
+    }
+}
+
 /// UNSET command - remove environment variables.
 pub struct UnsetCommand;
 
@@ -0,0 +1,254 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use super::os_identity::OsIdentity;
+use crate::filesystem::fs2::{FileContent, FileSystem};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// Applets of the fake busybox build. On the embedded persona the command
+/// registry is trimmed down to these and every one of them is a symlink to
+/// /bin/busybox in the embedded filesystem.
+pub const BUSYBOX_APPLETS: &[&str] = &[
+    "ash", "bg", "busybox", "cat", "cd", "clear", "date", "dd", "echo", "env", "export", "exit",
+    "false", "fg", "free", "grep", "halt", "head", "id", "jobs", "kill", "ls", "mount", "nohup",
+    "pgrep", "pidof", "pkill", "poweroff", "ps", "pwd", "reboot", "reset", "setsid", "sh", "sleep",
+    "tail", "test", "timeout", "true", "uname", "unset", "watch", "wget", "whoami", ":", "[",
+];
+
+/// Shell builtins in the applet list, they have no /bin symlink and busybox doesn't list them
+fn is_shell_builtin(applet: &str) -> bool {
+    matches!(
+        applet,
+        "bg" | "cd" | "export" | "exit" | "fg" | "jobs" | "unset" | ":"
+    )
+}
+
+/// First line busybox prints on `busybox`, `--help` and in the ash login banner
+const BUSYBOX_VERSION: &str = "BusyBox v1.28.4 () multi-call binary.";
+
+/// Busybox multi-call binary. `busybox <applet>` for known applets is
+/// resolved by the dispatcher, this only answers the binary itself and
+/// unknown applets (Mirai checks for "applet not found" with random names).
+pub struct BusyboxCommand;
+
+#[async_trait]
+impl Command for BusyboxCommand {
+    fn name(&self) -> &'static str {
+        "busybox"
+    }
+
+    fn aliases(&self) -> Vec<&'static str> {
+        vec!["/bin/busybox"]
+    }
+
+    fn help(&self) -> String {
+        let mut output = format!(
+            "{}\r\n\
+            BusyBox is copyrighted by many authors between 1998-2015.\r\n\
+            Licensed under GPLv2. See source distribution for detailed\r\n\
+            copyright notices.\r\n\
+            \r\n\
+            Usage: busybox [function [arguments]...]\r\n\
+            \x20  or: busybox --list\r\n\
+            \x20  or: function [arguments]...\r\n\
+            \r\n\
+            Currently defined functions:\r\n\t",
+            BUSYBOX_VERSION
+        );
+        output.push_str(&Self::applets().join(", "));
+        output.push_str("\r\n");
+        output
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        let Some(applet) = args.first() else {
+            return Ok(self.help());
+        };
+
+        match applet.as_str() {
+            "--help" => Ok(self.help()),
+            "--list" => Ok(Self::applets()
+                .iter()
+                .map(|a| format!("{}\r\n", a))
+                .collect()),
+            _ => {
+                log::info!(
+                    "[{}] busybox called with unknown applet {:?}",
+                    context.auth_id,
+                    applet
+                );
+                Err(CommandError::ExecutionError(format!(
+                    "{}: applet not found",
+                    applet
+                )))
+            }
+        }
+    }
+}
+
+impl BusyboxCommand {
+    /// Applets as busybox lists them: sorted, without shell builtins
+    fn applets() -> Vec<&'static str> {
+        let mut applets: Vec<&str> = BUSYBOX_APPLETS
+            .iter()
+            .copied()
+            .filter(|a| *a != "busybox" && !is_shell_builtin(a))
+            .collect();
+        applets.sort_unstable();
+        applets
+    }
+}
+
+/// Contents of /etc/banner for the given release description
+fn openwrt_banner(description: &str) -> String {
+    format!(
+        "  _______                     ________        __\n \
+        |       |.-----.-----.-----.|  |  |  |.----.|  |_\n \
+        |   -   ||  _  |  -__|     ||  |  |  ||   _||   _|\n \
+        |_______||   __|_____|__|__||________||__|  |____|\n\
+        \x20         |__| W I R E L E S S   F R E E D O M\n \
+        -----------------------------------------------------\n \
+        {}\n \
+        -----------------------------------------------------\n",
+        description
+    )
+}
+
+/// What ash prints after login on the embedded persona: the busybox shell
+/// line followed by /etc/banner
+pub fn login_banner(description: &str) -> String {
+    format!(
+        "\r\n\r\nBusyBox v1.28.4 () built-in shell (ash)\r\n\r\n{}\r\n",
+        openwrt_banner(description).replace('\n', "\r\n")
+    )
+}
+
+/// Build the filesystem of a small OpenWrt router: a handful of directories,
+/// busybox with its applet symlinks and the usual /etc files.
+pub fn write_embedded_layout(fs: &mut FileSystem, os: &OsIdentity) -> std::io::Result<()> {
+    for dir in [
+        "/bin",
+        "/dev",
+        "/etc",
+        "/lib",
+        "/mnt",
+        "/overlay",
+        "/proc",
+        "/rom",
+        "/root",
+        "/sbin",
+        "/sys",
+        "/tmp",
+        "/usr",
+        "/usr/bin",
+        "/usr/sbin",
+        "/var",
+        "/www",
+    ] {
+        if fs.get_file(dir).is_err() {
+            fs.create_directory(dir)?.inode.i_mode = 0o755;
+        }
+    }
+
+    // Just enough of an ELF header for `head -c` and `file`-style probes
+    let mut busybox =
+        b"\x7fELF\x01\x02\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x02\x00\x08".to_vec();
+    busybox.resize(4096, 0);
+    write_file(fs, "/bin/busybox", busybox, 0o755)?;
+
+    for applet in BUSYBOX_APPLETS {
+        if *applet == "busybox" || is_shell_builtin(applet) {
+            continue;
+        }
+        // The system administration applets live in /sbin
+        let (dir, target) = if matches!(*applet, "halt" | "poweroff" | "reboot") {
+            ("/sbin", "../bin/busybox")
+        } else {
+            ("/bin", "busybox")
+        };
+        fs.create_symlink(&format!("{}/{}", dir, applet), target)?
+            .inode
+            .i_mode = 0o777;
+    }
+
+    let files = [
+        (
+            "/etc/passwd",
+            "root:x:0:0:root:/root:/bin/ash\n\
+            daemon:*:1:1:daemon:/var:/bin/false\n\
+            ftp:*:55:55:ftp:/home/ftp:/bin/false\n\
+            network:*:101:101:network:/var:/bin/false\n\
+            nobody:*:65534:65534:nobody:/var:/bin/false\n\
+            dnsmasq:x:453:453:dnsmasq:/var/run/dnsmasq:/bin/false\n",
+            0o644,
+        ),
+        (
+            "/etc/group",
+            "root:x:0:\n\
+            daemon:x:1:\n\
+            adm:x:4:\n\
+            mail:x:8:\n\
+            dialout:x:20:\n\
+            audio:x:29:\n\
+            www-data:x:33:\n\
+            ftp:x:55:\n\
+            users:x:100:\n\
+            network:x:101:\n\
+            nogroup:x:65534:\n\
+            dnsmasq:x:453:dnsmasq\n",
+            0o644,
+        ),
+        (
+            "/etc/shadow",
+            "root:$1$wPDpvMzm$6Hm/Uq2PHaxJX5xR7XyA00:18103:0:99999:7:::\n\
+            daemon:*:0:0:99999:7:::\n\
+            ftp:*:0:0:99999:7:::\n\
+            network:*:0:0:99999:7:::\n\
+            nobody:*:0:0:99999:7:::\n\
+            dnsmasq:x:0:0:99999:7:::\n",
+            0o600,
+        ),
+        ("/etc/shells", "/bin/ash\n", 0o644),
+        (
+            "/etc/profile",
+            "[ -f /etc/banner ] && cat /etc/banner\n\
+            export PATH=\"/usr/sbin:/usr/bin:/sbin:/bin\"\n\
+            export HOME=$(grep -e \"^${USER:-root}:\" /etc/passwd | cut -d \":\" -f 6)\n\
+            export PS1='\\u@\\h:\\w\\$ '\n",
+            0o644,
+        ),
+        ("/etc/openwrt_version", "r7808-ef686b7292\n", 0o644),
+    ];
+    for (path, content, mode) in files {
+        write_file(fs, path, content.as_bytes().to_vec(), mode)?;
+    }
+    write_file(
+        fs,
+        "/etc/banner",
+        openwrt_banner(&os.description).into_bytes(),
+        0o644,
+    )?;
+
+    os.write_release_files(fs)
+}
+
+pub(crate) fn write_file(
+    fs: &mut FileSystem,
+    path: &str,
+    content: Vec<u8>,
+    mode: u16,
+) -> std::io::Result<()> {
+    let existing = fs
+        .get_file(path)
+        .ok()
+        .map(|entry| entry.inode.i_size_lo as u64);
+    fs.ensure_space(existing.unwrap_or(0), content.len() as u64)?;
+    if existing.is_some() {
+        fs.remove_file(path)?;
+    }
+    let entry = fs.create_file(path)?;
+    entry.inode.i_mode = mode;
+    entry.inode.i_size_lo = content.len() as u32;
+    entry.content = Some(FileContent::RegularFile(Arc::new(content)));
+    Ok(())
+}
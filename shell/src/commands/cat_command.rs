@@ -1,7 +1,57 @@
 use super::command_trait::{Command, CommandResult};
 use super::context::CommandContext;
+use super::procfs::{self, ProcView};
 use crate::filesystem::fs2::FileContent;
 use async_trait::async_trait;
+use std::io::ErrorKind;
+
+/// What cat shows for a file that isn't text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BinaryFiles {
+    /// The bytes, with the control characters that could drive the terminal replaced
+    #[default]
+    Neutralized,
+    /// `cat: FILE: TYPE (binary file)` instead of the content
+    Described,
+}
+
+/// Type of `bytes` when they aren't text: what infer recognises, or `data` for
+/// anything else with a NUL in its first 8 KiB, the check grep and diff make
+pub(crate) fn binary_type(bytes: &[u8]) -> Option<String> {
+    if let Some(kind) = infer::get(bytes)
+        && kind.matcher_type() != infer::MatcherType::Text
+    {
+        return Some(kind.mime_type().to_string());
+    }
+    bytes
+        .iter()
+        .take(8192)
+        .any(|&b| b == 0)
+        .then(|| "data".to_string())
+}
+
+/// Binary content made safe to send to the terminal. Escape sequences, carriage
+/// returns and C1 controls could move the cursor, rewrite what the honeypot printed
+/// or make the client answer back, so they become U+FFFD like invalid UTF-8 does.
+/// NULs, tabs and newlines stay, terminals show them as a real cat would
+pub(crate) fn neutralize(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .chars()
+        .map(|c| match c {
+            '\0' | '\t' | '\n' => c,
+            c if c.is_control() => '\u{FFFD}',
+            c => c,
+        })
+        .collect()
+}
+
+/// Absolute form of `path` relative to the working directory
+fn absolute(path: &str, context: &CommandContext) -> String {
+    match path.starts_with('/') {
+        true => path.to_string(),
+        false => format!("{}/{}", context.cwd.trim_end_matches('/'), path),
+    }
+}
 
 /// Cat command implementation using the new trait system
 pub struct CatCommand;
@@ -31,8 +81,26 @@ impl Command for CatCommand {
         let mut errors = String::new();
         let fs = context.filesystem.read().await;
 
+        // Reading /proc shows cat itself in the process table, like on a real system
+        let table = context.processes.clone();
+        let mut processes = table.write().await;
+        let own_pid = files
+            .iter()
+            .any(|path| procfs::is_proc_path(&context.cwd, path))
+            .then(|| {
+                processes.attach_session(&context.username);
+                processes.spawn(&context.username, &format!("cat {}", args.join(" ")))
+            });
+        let proc_view = own_pid.map(|pid| ProcView::new(&processes, pid, &fs, context));
+
         for file_path in files {
-            match fs.follow_symlink(file_path) {
+            // /proc files are made up here, NULs and all, and never count as binary
+            let (entry, generated) =
+                match proc_view.as_ref().and_then(|view| view.lookup(file_path)) {
+                    Some(entry) => (entry, true),
+                    None => (fs.follow_symlink(&absolute(file_path, context)), false),
+                };
+            match entry {
                 Ok(entry) => match &entry.file_content {
                     None => {
                         errors.push_str(&format!(
@@ -44,13 +112,32 @@ impl Command for CatCommand {
                         errors.push_str(&format!("cat: {}: Is a directory\r\n", file_path));
                     }
                     Some(FileContent::RegularFile(bytes)) => {
-                        let content = String::from_utf8_lossy(bytes);
-                        output.push_str(&content.replace("\r\n", "\n").replace('\n', "\r\n"));
+                        context.note_read(&fs, &absolute(file_path, context));
+                        let binary = if generated { None } else { binary_type(bytes) };
+                        match binary {
+                            None => {
+                                let content = String::from_utf8_lossy(bytes);
+                                output
+                                    .push_str(&content.replace("\r\n", "\n").replace('\n', "\r\n"));
+                            }
+                            Some(kind) if context.binary_files == BinaryFiles::Described => {
+                                errors.push_str(&format!(
+                                    "cat: {}: {} (binary file)\r\n",
+                                    file_path, kind
+                                ));
+                            }
+                            Some(_) => {
+                                output.push_str(&neutralize(bytes).replace('\n', "\r\n"));
+                            }
+                        }
                     }
                     Some(FileContent::SymbolicLink(_)) => {
                         errors.push_str(&format!("cat: {}: Is a symbolic link\r\n", file_path));
                     }
                 },
+                Err(err) if err.kind() == ErrorKind::PermissionDenied => {
+                    errors.push_str(&format!("cat: {}: Permission denied\r\n", file_path));
+                }
                 Err(_) => {
                     errors.push_str(&format!(
                         "cat: {}: No such file or directory\r\n",
@@ -59,11 +146,18 @@ impl Command for CatCommand {
                 }
             }
         }
+        drop(proc_view);
+        if let Some(pid) = own_pid {
+            processes.remove(pid);
+        }
 
         // Successful content goes to stdout; error diagnostics go to stderr (Err).
         // When both are present we prefer stdout so pipe consumers get real content.
         if output.is_empty() && !errors.is_empty() {
-            Err(super::command_trait::CommandError::ExecutionError(errors))
+            // The dispatcher ends the message with the last newline
+            Err(super::command_trait::CommandError::ExecutionError(
+                errors.trim_end_matches("\r\n").to_string(),
+            ))
         } else {
             Ok(output)
         }
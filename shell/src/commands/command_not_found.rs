@@ -0,0 +1,100 @@
+use super::context::CommandContext;
+use std::collections::HashMap;
+
+/// Mimics Ubuntu's `command-not-found` package: when an unknown command has a
+/// known package that provides it, suggest installing that package instead of
+/// printing the bare bash error.
+#[derive(Clone, Debug)]
+pub struct CommandNotFoundHandler {
+    /// Map of command name to the package that provides it
+    suggestions: HashMap<String, String>,
+}
+
+impl CommandNotFoundHandler {
+    /// Create a handler with the given command → package mapping
+    pub fn new(suggestions: HashMap<String, String>) -> Self {
+        Self { suggestions }
+    }
+
+    /// Create a handler with a built-in mapping of tools attackers commonly reach for
+    pub fn with_default_suggestions() -> Self {
+        let defaults: &[(&str, &str)] = &[
+            ("htop", "htop"),
+            ("nmap", "nmap"),
+            ("masscan", "masscan"),
+            ("zmap", "zmap"),
+            ("gcc", "gcc"),
+            ("cc", "gcc"),
+            ("make", "make"),
+            ("python", "python3"),
+            ("pip", "python3-pip"),
+            ("pip3", "python3-pip"),
+            ("ifconfig", "net-tools"),
+            ("netstat", "net-tools"),
+            ("route", "net-tools"),
+            ("telnet", "telnet"),
+            ("nc", "netcat-openbsd"),
+            ("netcat", "netcat-openbsd"),
+            ("ncat", "ncat"),
+            ("socat", "socat"),
+            ("git", "git"),
+            ("zip", "zip"),
+            ("unzip", "unzip"),
+            ("screen", "screen"),
+            ("tmux", "tmux"),
+            ("docker", "docker.io"),
+            ("hydra", "hydra"),
+            ("john", "john"),
+            ("tcpdump", "tcpdump"),
+            ("traceroute", "traceroute"),
+            ("whois", "whois"),
+            ("ftp", "ftp"),
+            ("tor", "tor"),
+            ("proxychains", "proxychains"),
+            ("php", "php7.4-cli"),
+            ("perl", "perl"),
+            ("ruby", "ruby"),
+            ("node", "nodejs"),
+            ("java", "default-jre"),
+        ];
+
+        Self::new(
+            defaults
+                .iter()
+                .map(|(cmd, pkg)| (cmd.to_string(), pkg.to_string()))
+                .collect(),
+        )
+    }
+
+    /// Get the package suggested for a command, if any
+    pub fn suggestion_for(&self, command_name: &str) -> Option<&str> {
+        self.suggestions.get(command_name).map(|s| s.as_str())
+    }
+
+    /// Build the message printed for an unknown command. Commands with a known
+    /// package get the Ubuntu suggestion format, everything else falls back to
+    /// the plain bash error.
+    pub fn message(&self, command_name: &str, context: &CommandContext) -> String {
+        match self.suggestion_for(command_name) {
+            Some(package) => {
+                // root does not need sudo, everyone else gets the sudo prefix
+                let sudo = if context.username == "root" {
+                    ""
+                } else {
+                    "sudo "
+                };
+                format!(
+                    "\r\nCommand '{}' not found, but can be installed with:\r\n\r\n{}apt install {}\r\n\r\n",
+                    command_name, sudo, package
+                )
+            }
+            None => format!("{}: command not found\r\n", command_name),
+        }
+    }
+}
+
+impl Default for CommandNotFoundHandler {
+    fn default() -> Self {
+        Self::with_default_suggestions()
+    }
+}
@@ -1,5 +1,17 @@
+use super::canary::Canaries;
+use super::cat_command::BinaryFiles;
+use super::dns::{DnsResolver, FakeDns};
+use super::fetch_policy::FetchPolicy;
+use super::firewall::Firewall;
+use super::host_identity::HostIdentity;
+use super::latency::CommandLatency;
+use super::login_history::{LoginHistory, NoLoginHistory};
+use super::os_identity::OsIdentity;
+use super::process_table::ProcessTable;
+use super::sleep_command::DEFAULT_MAX_SLEEP;
 use crate::filesystem::fs2::FileSystem;
-use std::sync::Arc;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex as StdMutex};
 use tokio::sync::RwLock;
 
 /// Context passed to all command implementations containing shared state
@@ -18,6 +30,99 @@ pub struct CommandContext {
     pub auth_id: String,
     /// Environment variables (simplified)
     pub env_vars: std::collections::HashMap<String, String>,
+    /// Lines typed in this session, oldest first, what `history` lists
+    pub history: VecDeque<String>,
+    /// Identity of the fake operating system (kernel, distribution)
+    pub os: OsIdentity,
+    /// Machine-id, MACs and host keys of the fake host, shared by every session
+    pub host: Arc<HostIdentity>,
+    /// Fake process list of this session, shared by ps/kill/pkill
+    pub processes: Arc<RwLock<ProcessTable>>,
+    /// iptables and ufw state of this session
+    pub firewall: Arc<RwLock<Firewall>>,
+    /// Columns of the client's terminal (pty-req / window-change), used by ls
+    pub terminal_width: usize,
+    /// Which URLs given to wget/curl may be fetched
+    pub fetch_policy: Arc<FetchPolicy>,
+    /// Delay for commands that take a while on a real system
+    pub latency: CommandLatency,
+    /// Longest sleep actually waited, longer ones are cut short
+    pub max_sleep: std::time::Duration,
+    /// What cat does with files that aren't text
+    pub binary_files: BinaryFiles,
+    /// Files whose reading gets recorded as a canary event
+    pub canaries: Arc<Canaries>,
+    /// Answers dig, host and nslookup
+    pub dns: Arc<dyn DnsResolver>,
+    /// wtmp and btmp for last and lastb
+    pub logins: Arc<dyn LoginHistory>,
+    /// Things worth recording that happened while running the current command
+    /// line, taken by the server
+    pub events: Arc<StdMutex<Vec<ShellEvent>>>,
+}
+
+/// Something a command did that the server records on its own, besides the
+/// command line
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShellEvent {
+    /// A canary file was read
+    CanaryRead { path: String },
+    /// A file from the virtual filesystem was run, `./x`, `/tmp/x` or found on PATH
+    Execution {
+        path: String,
+        command: String,
+        /// What bash made of it, e.g. "executed" or "permission denied"
+        outcome: &'static str,
+        /// The file as it was when run, None for directories and missing files
+        content: Option<Arc<Vec<u8>>>,
+    },
+    /// dig, host or nslookup asked about a name
+    DnsQuery {
+        name: String,
+        record_type: String,
+        command: String,
+        /// NOERROR, NXDOMAIN or SERVFAIL
+        status: &'static str,
+        answers: Vec<String>,
+    },
+    /// reboot, poweroff, halt or shutdown was run
+    Shutdown {
+        command: String,
+        /// reboot, poweroff or halt
+        action: &'static str,
+        /// Run as root, anyone else is refused
+        permitted: bool,
+        /// The machine goes down now rather than at a later time
+        immediate: bool,
+    },
+    /// iptables or ufw was asked to change the firewall
+    FirewallChange {
+        /// iptables or ufw
+        tool: &'static str,
+        command: String,
+        /// Run as root, anyone else is refused
+        permitted: bool,
+    },
+    /// kill or pkill signalled processes of the session's process table
+    ProcessKill {
+        /// kill or pkill
+        tool: &'static str,
+        command: String,
+        /// Signal name without the SIG prefix, e.g. TERM or KILL, 0 for `kill -0`
+        signal: String,
+        /// Processes the signal was aimed at, whether or not it was delivered
+        pids: Vec<u32>,
+        /// Their command names, in the order of `pids`
+        names: Vec<String>,
+        /// The user may signal every one of them
+        permitted: bool,
+    },
+    /// A redirection (`>`, `>>`) wrote a command's output to a file
+    FileWritten {
+        path: String,
+        /// The whole file after the write
+        content: Arc<Vec<u8>>,
+    },
 }
 
 #[allow(dead_code)]
@@ -32,7 +137,11 @@ impl CommandContext {
     ) -> Self {
         let mut env_vars = std::collections::HashMap::new();
         env_vars.insert("USER".to_string(), username.clone());
-        env_vars.insert("HOME".to_string(), format!("/home/{}", username));
+        let home = match username.as_str() {
+            "root" => "/root".to_string(),
+            _ => format!("/home/{}", username),
+        };
+        env_vars.insert("HOME".to_string(), home);
         env_vars.insert("PWD".to_string(), cwd.clone());
         env_vars.insert("HOSTNAME".to_string(), hostname.clone());
         env_vars.insert("SHELL".to_string(), "/bin/bash".to_string());
@@ -48,6 +157,20 @@ impl CommandContext {
             filesystem,
             auth_id,
             env_vars,
+            history: VecDeque::new(),
+            os: OsIdentity::default(),
+            host: Arc::new(HostIdentity::default()),
+            processes: Arc::new(RwLock::new(ProcessTable::new())),
+            firewall: Arc::new(RwLock::new(Firewall::default())),
+            terminal_width: 80,
+            fetch_policy: Arc::new(FetchPolicy::default()),
+            latency: CommandLatency::default(),
+            max_sleep: DEFAULT_MAX_SLEEP,
+            binary_files: BinaryFiles::default(),
+            canaries: Arc::new(Canaries::default()),
+            dns: Arc::new(FakeDns::default()),
+            logins: Arc::new(NoLoginHistory),
+            events: Arc::new(StdMutex::new(Vec::new())),
         }
     }
 
@@ -67,17 +190,47 @@ impl CommandContext {
         self.env_vars.insert(key, value);
     }
 
+    /// Note that a command read `path`, remembered when it is one of the canaries
+    pub fn note_read(&self, fs: &FileSystem, path: &str) {
+        if let Some(canary) = self.canaries.lookup(fs, path) {
+            log::warn!("[{}] Canary file read: {}", self.auth_id, canary);
+            self.push_event(ShellEvent::CanaryRead { path: canary });
+        }
+    }
+
+    /// Remember `event` for the server
+    pub fn push_event(&self, event: ShellEvent) {
+        self.events.lock().unwrap().push(event);
+    }
+
+    /// Events since the last call
+    pub fn take_events(&self) -> Vec<ShellEvent> {
+        std::mem::take(&mut *self.events.lock().unwrap())
+    }
+
     /// Get the command prompt string
     pub fn get_prompt(&self) -> String {
+        // bash's \w, $HOME and what's below it start with ~
+        let home = self.get_env("HOME").filter(|home| !home.is_empty());
+        let path = match home.and_then(|home| self.cwd.strip_prefix(home.as_str())) {
+            Some("") => "~".to_string(),
+            Some(below) if below.starts_with('/') => format!("~{}", below),
+            _ => self.cwd.clone(),
+        };
         format!(
-            "{}@{}:{}$ ",
+            "{}@{}:{}{} ",
             self.username,
             self.hostname,
-            if self.cwd == format!("/home/{}", self.username) {
-                "~"
-            } else {
-                &self.cwd
-            }
+            path,
+            prompt_sign(&self.username)
         )
     }
 }
+
+/// bash's \$ at the end of the prompt: # for root, $ for everyone else
+pub fn prompt_sign(username: &str) -> char {
+    match username {
+        "root" => '#',
+        _ => '$',
+    }
+}
@@ -0,0 +1,516 @@
+use super::busybox::write_file;
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use crate::filesystem::fs2::FileContent;
+use async_trait::async_trait;
+use rand::{Rng, RngExt, rng};
+use std::time::Duration;
+
+/// Size reported for whole disks such as /dev/sda
+const DISK_SIZE_BYTES: u64 = 40 * 1024 * 1024 * 1024;
+/// At most this much of a file written by dd is kept, the inode still carries
+/// the full size. Keeps `dd if=/dev/zero bs=1G count=20` from eating our memory
+const MAX_STORED_BYTES: u64 = 4 * 1024 * 1024;
+/// At most this much is printed when writing to the terminal
+const MAX_STDOUT_BYTES: u64 = 64 * 1024;
+/// The simulated copy really takes this long at most
+const MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// Block devices that exist on the fake host
+const BLOCK_DEVICES: &[&str] = &[
+    "/dev/sda",
+    "/dev/sda1",
+    "/dev/sda2",
+    "/dev/vda",
+    "/dev/vda1",
+];
+
+/// Where the data comes from
+enum Source {
+    /// No if=, the terminal. Nothing was typed, so end of file right away
+    Stdin,
+    Zero,
+    Random,
+    Null,
+    /// Whole disk or partition, reads the MBR and then zeros
+    BlockDevice,
+    File(Vec<u8>),
+}
+
+impl Source {
+    /// Bytes available after `offset`, None for endless sources
+    fn len(&self, offset: u64) -> Option<u64> {
+        match self {
+            Source::Zero | Source::Random => None,
+            Source::Stdin | Source::Null => Some(0),
+            Source::BlockDevice => Some(DISK_SIZE_BYTES.saturating_sub(offset)),
+            Source::File(content) => Some((content.len() as u64).saturating_sub(offset)),
+        }
+    }
+
+    /// `len` bytes starting at `offset`, only called for what is actually kept
+    fn read(&self, offset: u64, len: u64) -> Vec<u8> {
+        let mut data = vec![0u8; len as usize];
+        match self {
+            Source::Random => rng().fill_bytes(&mut data),
+            Source::BlockDevice => {
+                // MBR boot signature, fdisk-style probes look for it
+                for (position, byte) in [(510u64, 0x55u8), (511, 0xaa)] {
+                    if position >= offset && position < offset + len {
+                        data[(position - offset) as usize] = byte;
+                    }
+                }
+            }
+            Source::File(content) => {
+                let start = (offset as usize).min(content.len());
+                let end = (start + len as usize).min(content.len());
+                data[..end - start].copy_from_slice(&content[start..end]);
+            }
+            Source::Zero | Source::Stdin | Source::Null => {}
+        }
+        data
+    }
+
+    /// Throughput in bytes per second the copy pretends to run at
+    fn throughput(&self) -> f64 {
+        let mut rng = rng();
+        match self {
+            Source::Random => rng.random_range(180.0e6..320.0e6),
+            Source::BlockDevice => rng.random_range(350.0e6..520.0e6),
+            _ => rng.random_range(0.9e9..1.6e9),
+        }
+    }
+}
+
+/// Where the data goes
+enum Sink {
+    Stdout,
+    Null,
+    BlockDevice,
+    File(String),
+}
+
+/// Parsed operands
+struct Operands {
+    input: Option<String>,
+    output: Option<String>,
+    ibs: u64,
+    obs: u64,
+    count: Option<u64>,
+    skip: u64,
+    seek: u64,
+    notrunc: bool,
+    status: Status,
+}
+
+#[derive(PartialEq)]
+enum Status {
+    Default,
+    NoXfer,
+    None,
+}
+
+/// dd from coreutils. Copies are simulated against the virtual filesystem
+/// (zeros from /dev/zero, the MBR from /dev/sda, ...), nothing touches a real disk
+pub struct DdCommand;
+
+#[async_trait]
+impl Command for DdCommand {
+    fn name(&self) -> &'static str {
+        "dd"
+    }
+
+    fn help(&self) -> String {
+        "Usage: dd [OPERAND]...\r\n\
+        \x20 or:  dd OPTION\r\n\
+        Copy a file, converting and formatting according to the operands.\r\n\
+        \r\n\
+        \x20 bs=BYTES        read and write up to BYTES bytes at a time (default: 512);\r\n\
+        \x20                 overrides ibs and obs\r\n\
+        \x20 cbs=BYTES       convert BYTES bytes at a time\r\n\
+        \x20 conv=CONVS      convert the file as per the comma separated symbol list\r\n\
+        \x20 count=N         copy only N input blocks\r\n\
+        \x20 ibs=BYTES       read up to BYTES bytes at a time (default: 512)\r\n\
+        \x20 if=FILE         read from FILE instead of stdin\r\n\
+        \x20 iflag=FLAGS     read as per the comma separated symbol list\r\n\
+        \x20 obs=BYTES       write BYTES bytes at a time (default: 512)\r\n\
+        \x20 of=FILE         write to FILE instead of stdout\r\n\
+        \x20 oflag=FLAGS     write as per the comma separated symbol list\r\n\
+        \x20 seek=N          skip N obs-sized blocks at start of output\r\n\
+        \x20 skip=N          skip N ibs-sized blocks at start of input\r\n\
+        \x20 status=LEVEL    The LEVEL of information to print to stderr;\r\n\
+        \x20                 'none' suppresses everything but error messages,\r\n\
+        \x20                 'noxfer' suppresses the final transfer statistics,\r\n\
+        \x20                 'progress' shows periodic transfer statistics\r\n\
+        \r\n\
+        N and BYTES may be followed by the following multiplicative suffixes:\r\n\
+        c =1, w =2, b =512, kB =1000, K =1024, MB =1000*1000, M =1024*1024, xM =M,\r\n\
+        GB =1000*1000*1000, G =1024*1024*1024, and so on for T, P, E, Z, Y.\r\n"
+            .to_string()
+    }
+
+    fn version(&self) -> String {
+        "dd (coreutils) 8.32\r\n\
+        Copyright (C) 2020 Free Software Foundation, Inc.\r\n\
+        License GPLv3+: GNU GPL version 3 or later <https://gnu.org/licenses/gpl.html>.\r\n\
+        This is free software: you are free to change and redistribute it.\r\n\
+        There is NO WARRANTY, to the extent permitted by law.\r\n\
+        \r\n\
+        Written by Paul Rubin, David MacKenzie, and Stuart Kemp.\r\n"
+            .to_string()
+    }
+
+    fn modifies_filesystem(&self) -> bool {
+        true
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        if args.iter().any(|a| a == "--help") {
+            return Ok(self.help());
+        }
+        if args.iter().any(|a| a == "--version") {
+            return Ok(self.version());
+        }
+        let ops = parse_operands(args)?;
+        let is_root = context.username == "root";
+
+        let source = match ops.input.as_deref() {
+            None | Some("-") | Some("/dev/stdin") => Source::Stdin,
+            Some("/dev/zero") => Source::Zero,
+            Some("/dev/urandom") | Some("/dev/random") => Source::Random,
+            Some("/dev/null") => Source::Null,
+            Some(device) if BLOCK_DEVICES.contains(&device) => {
+                if !is_root {
+                    return Err(open_error(device, "Permission denied"));
+                }
+                Source::BlockDevice
+            }
+            Some(path) => {
+                let fs = context.filesystem.read().await;
+                match fs.follow_symlink(&absolute(path, context)) {
+                    Ok(entry) => match &entry.file_content {
+                        Some(FileContent::RegularFile(bytes)) => Source::File(bytes.to_vec()),
+                        Some(FileContent::Directory(_)) => {
+                            return Err(CommandError::ExecutionError(format!(
+                                "dd: error reading '{}': Is a directory\r\n{}",
+                                path,
+                                summary(0, 0, &ops, 0.000_04).trim_end_matches("\r\n")
+                            )));
+                        }
+                        _ => return Err(open_error(path, "No such file or directory")),
+                    },
+                    Err(_) => return Err(open_error(path, "No such file or directory")),
+                }
+            }
+        };
+
+        let sink = match ops.output.as_deref() {
+            None | Some("-") | Some("/dev/stdout") => Sink::Stdout,
+            Some("/dev/null") => Sink::Null,
+            Some(device) if BLOCK_DEVICES.contains(&device) => {
+                if !is_root {
+                    return Err(open_error(device, "Permission denied"));
+                }
+                Sink::BlockDevice
+            }
+            Some(path) => Sink::File(absolute(path, context)),
+        };
+
+        // Writes beyond the free space of the fake disk fail with ENOSPC
+        let disk_free = context.filesystem.read().await.disk_usage().available();
+        let skip = ops.skip.saturating_mul(ops.ibs);
+        let wanted = ops.count.map(|count| count.saturating_mul(ops.ibs));
+        let mut bytes = match (source.len(skip), wanted) {
+            (Some(available), Some(wanted)) => available.min(wanted),
+            (Some(available), None) => available,
+            (None, Some(wanted)) => wanted,
+            // Endless source without count, runs until something gives
+            (None, None) => disk_free + 1,
+        };
+
+        if !matches!(sink, Sink::File(_)) {
+            bytes = bytes.min(disk_free);
+        }
+
+        let mut output = String::new();
+        let mut write_error = None;
+        match &sink {
+            Sink::Stdout => {
+                let shown = bytes.min(MAX_STDOUT_BYTES);
+                output.push_str(&String::from_utf8_lossy(&source.read(skip, shown)));
+            }
+            Sink::Null | Sink::BlockDevice => {}
+            Sink::File(path) => {
+                let mut fs = context.filesystem.write().await;
+                let mut existing_len = 0;
+                let existing = match fs.follow_symlink(path) {
+                    Ok(entry) => match &entry.file_content {
+                        Some(FileContent::RegularFile(content)) => {
+                            existing_len = entry.inode.i_size_lo as u64;
+                            Some(content.to_vec())
+                        }
+                        Some(FileContent::Directory(_)) => {
+                            return Err(open_error(
+                                ops.output.as_deref().unwrap_or(path),
+                                "Is a directory",
+                            ));
+                        }
+                        _ => None,
+                    },
+                    Err(_) => None,
+                };
+                if !is_root && !writable_by_user(path, context) {
+                    return Err(open_error(
+                        ops.output.as_deref().unwrap_or(path),
+                        "Permission denied",
+                    ));
+                }
+
+                // The file's own blocks are rewritten, only what it grows by needs room
+                let room = disk_free + existing_len;
+                let seek = ops.seek.saturating_mul(ops.obs);
+                if seek.saturating_add(bytes) > room {
+                    bytes = room.saturating_sub(seek);
+                    write_error = Some(format!(
+                        "dd: error writing '{}': No space left on device",
+                        ops.output.as_deref().unwrap_or(path)
+                    ));
+                }
+
+                let mut content = existing.unwrap_or_default();
+                let end = seek + bytes;
+                let new_len = if ops.notrunc {
+                    end.max(content.len() as u64)
+                } else {
+                    end
+                };
+                // Truncates at the end of the copy, or pads the gap seek= left with zeros
+                let stored = new_len.min(MAX_STORED_BYTES) as usize;
+                content.resize(stored, 0);
+                let start = (seek as usize).min(stored);
+                let kept = (end.min(MAX_STORED_BYTES) as usize).saturating_sub(start);
+                content[start..start + kept].copy_from_slice(&source.read(skip, kept as u64));
+
+                if let Err(err) = write_file(&mut fs, path, content, 0o644) {
+                    log::debug!("dd could not create {}: {}", path, err);
+                    let reason = match err.kind() {
+                        std::io::ErrorKind::StorageFull => "No space left on device",
+                        _ => "No such file or directory",
+                    };
+                    return Err(open_error(ops.output.as_deref().unwrap_or(path), reason));
+                }
+                if let Ok(entry) = fs.get_file_mut(path) {
+                    entry.inode.i_size_lo = new_len.min(u32::MAX as u64) as u32;
+                }
+            }
+        }
+
+        let seconds = bytes as f64 / source.throughput() + 0.000_05;
+        tokio::time::sleep(Duration::from_secs_f64(seconds).min(MAX_DELAY)).await;
+
+        let records = summary(bytes, bytes, &ops, seconds);
+        if ops.status != Status::None {
+            output.push_str(&records);
+        }
+        match write_error {
+            Some(error) => Err(CommandError::ExecutionError(format!(
+                "{}\r\n{}",
+                error,
+                records.trim_end_matches("\r\n")
+            ))),
+            None => Ok(output),
+        }
+    }
+}
+
+fn open_error(path: &str, reason: &str) -> CommandError {
+    CommandError::ExecutionError(format!("dd: failed to open '{}': {}", path, reason))
+}
+
+fn absolute(path: &str, context: &CommandContext) -> String {
+    if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("{}/{}", context.cwd.trim_end_matches('/'), path)
+    }
+}
+
+/// Regular users may only create files in their home and the temp directories
+pub(crate) fn writable_by_user(path: &str, context: &CommandContext) -> bool {
+    let home = format!("/home/{}/", context.username);
+    path.starts_with(&home)
+        || path.starts_with("/tmp/")
+        || path.starts_with("/var/tmp/")
+        || path.starts_with("/dev/shm/")
+}
+
+fn parse_operands(args: &[String]) -> Result<Operands, CommandError> {
+    let mut ops = Operands {
+        input: None,
+        output: None,
+        ibs: 512,
+        obs: 512,
+        count: None,
+        skip: 0,
+        seek: 0,
+        notrunc: false,
+        status: Status::Default,
+    };
+    for arg in args {
+        let Some((key, value)) = arg.split_once('=') else {
+            return Err(CommandError::InvalidArguments(format!(
+                "dd: unrecognized operand '{}'\r\nTry 'dd --help' for more information.",
+                arg
+            )));
+        };
+        match key {
+            "if" => ops.input = Some(value.to_string()),
+            "of" => ops.output = Some(value.to_string()),
+            "bs" => {
+                ops.ibs = parse_block_size(value)?;
+                ops.obs = ops.ibs;
+            }
+            "ibs" => ops.ibs = parse_block_size(value)?,
+            "obs" => ops.obs = parse_block_size(value)?,
+            "count" => ops.count = Some(parse_number(value)?),
+            "skip" | "iseek" => ops.skip = parse_number(value)?,
+            "seek" | "oseek" => ops.seek = parse_number(value)?,
+            "conv" => ops.notrunc = value.split(',').any(|c| c == "notrunc"),
+            "status" => {
+                ops.status = match value {
+                    "none" => Status::None,
+                    "noxfer" => Status::NoXfer,
+                    "progress" => Status::Default,
+                    _ => {
+                        return Err(CommandError::InvalidArguments(format!(
+                            "dd: invalid status level: '{}'\r\nTry 'dd --help' for more information.",
+                            value
+                        )));
+                    }
+                }
+            }
+            "cbs" | "iflag" | "oflag" => {}
+            _ => {
+                return Err(CommandError::InvalidArguments(format!(
+                    "dd: unrecognized operand '{}'\r\nTry 'dd --help' for more information.",
+                    arg
+                )));
+            }
+        }
+    }
+    Ok(ops)
+}
+
+fn parse_block_size(value: &str) -> Result<u64, CommandError> {
+    match parse_number(value)? {
+        0 => Err(CommandError::InvalidArguments(format!(
+            "dd: invalid number: '{}'",
+            value
+        ))),
+        size => Ok(size),
+    }
+}
+
+/// Number with the multiplicative suffixes dd accepts, `x` multiplies factors (2x512)
+fn parse_number(value: &str) -> Result<u64, CommandError> {
+    let invalid = || CommandError::InvalidArguments(format!("dd: invalid number: '{}'", value));
+    value.split('x').try_fold(1u64, |product, factor| {
+        let digits = factor.chars().take_while(char::is_ascii_digit).count();
+        if digits == 0 {
+            return Err(invalid());
+        }
+        let number: u64 = factor[..digits].parse().map_err(|_| invalid())?;
+        let multiplier: u64 = match &factor[digits..] {
+            "" | "c" => 1,
+            "w" => 2,
+            "b" => 512,
+            "kB" => 1000,
+            "K" | "k" | "KiB" => 1024,
+            "MB" => 1000_u64.pow(2),
+            "M" | "MiB" => 1024_u64.pow(2),
+            "GB" => 1000_u64.pow(3),
+            "G" | "GiB" => 1024_u64.pow(3),
+            "TB" => 1000_u64.pow(4),
+            "T" | "TiB" => 1024_u64.pow(4),
+            _ => return Err(invalid()),
+        };
+        number
+            .checked_mul(multiplier)
+            .and_then(|n| n.checked_mul(product))
+            .ok_or_else(invalid)
+    })
+}
+
+/// The records and transfer lines dd prints when it finishes
+fn summary(bytes_in: u64, bytes_out: u64, ops: &Operands, seconds: f64) -> String {
+    let records = |bytes: u64, block: u64| {
+        format!(
+            "{}+{}",
+            bytes / block,
+            u64::from(!bytes.is_multiple_of(block))
+        )
+    };
+    let mut summary = format!(
+        "{} records in\r\n{} records out\r\n",
+        records(bytes_in, ops.ibs),
+        records(bytes_out, ops.obs)
+    );
+    if ops.status == Status::Default {
+        let size = if bytes_out < 1000 {
+            String::new()
+        } else if bytes_out < 1024 {
+            format!(
+                " ({})",
+                human(bytes_out as f64, 1000.0, &["B", "kB", "MB", "GB", "TB"])
+            )
+        } else {
+            format!(
+                " ({}, {})",
+                human(bytes_out as f64, 1000.0, &["B", "kB", "MB", "GB", "TB"]),
+                human(bytes_out as f64, 1024.0, &["B", "KiB", "MiB", "GiB", "TiB"])
+            )
+        };
+        summary.push_str(&format!(
+            "{} bytes{} copied, {} s, {}/s\r\n",
+            bytes_out,
+            size,
+            significant(seconds),
+            human(
+                bytes_out as f64 / seconds,
+                1000.0,
+                &["B", "kB", "MB", "GB", "TB"]
+            )
+        ));
+    }
+    summary
+}
+
+/// One decimal below 10, whole numbers above, rounded to nearest like dd's human_readable call
+fn human(mut value: f64, base: f64, units: &[&str]) -> String {
+    let mut unit = 0;
+    while value >= base && unit < units.len() - 1 {
+        value /= base;
+        unit += 1;
+    }
+    let tenths = (value * 10.0).round() / 10.0;
+    if tenths < 10.0 {
+        format!("{:.1} {}", tenths, units[unit])
+    } else {
+        format!("{} {}", value.round(), units[unit])
+    }
+}
+
+/// printf's %g: six significant digits, no trailing zeros
+fn significant(value: f64) -> String {
+    let magnitude = value.abs().log10().floor() as i32;
+    let decimals = (5 - magnitude).max(0) as usize;
+    let formatted = format!("{:.*}", decimals, value);
+    if formatted.contains('.') {
+        formatted
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string()
+    } else {
+        formatted
+    }
+}
@@ -0,0 +1,384 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use super::du_command::human;
+use crate::filesystem::fs2::FileSystem;
+use async_trait::async_trait;
+
+/// tmpfs and devtmpfs mounts df lists besides the root filesystem, with sizes
+/// matching the size= options of the mount table: (source, type, target,
+/// 1K-blocks, used 1K-blocks, inodes, used inodes)
+const MEMORY_FILESYSTEMS: &[(&str, &str, &str, u64, u64, u64, u64)] = &[
+    ("udev", "devtmpfs", "/dev", 1988172, 0, 497043, 395),
+    ("tmpfs", "tmpfs", "/run", 403084, 1064, 503853, 676),
+    ("tmpfs", "tmpfs", "/dev/shm", 2015412, 0, 503853, 1),
+    ("tmpfs", "tmpfs", "/run/lock", 5120, 0, 503853, 3),
+    ("tmpfs", "tmpfs", "/sys/fs/cgroup", 2015412, 0, 503853, 18),
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Units {
+    /// Multiples of the given number of bytes, rounded up (-k, -m)
+    Blocks(u64),
+    /// -h
+    Human,
+}
+
+struct Options {
+    units: Units,
+    inodes: bool,
+    print_type: bool,
+    /// -t, only filesystems of these types
+    types: Vec<String>,
+    /// -x, no filesystems of these types
+    exclude_types: Vec<String>,
+    paths: Vec<String>,
+}
+
+/// One line of df, sizes in bytes or inode counts
+struct Row {
+    source: String,
+    fs_type: &'static str,
+    target: &'static str,
+    size: u64,
+    used: u64,
+    available: u64,
+}
+
+fn invalid_option(option: &str) -> CommandError {
+    CommandError::InvalidArguments(format!(
+        "df: {}\r\nTry 'df --help' for more information.",
+        option
+    ))
+}
+
+fn parse_args(args: &[String]) -> Result<Options, CommandError> {
+    let mut options = Options {
+        units: Units::Blocks(1024),
+        inodes: false,
+        print_type: false,
+        types: Vec::new(),
+        exclude_types: Vec::new(),
+        paths: Vec::new(),
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--human-readable" | "--si" => options.units = Units::Human,
+            "--inodes" => options.inodes = true,
+            "--print-type" => options.print_type = true,
+            "--all" | "--local" | "--portability" | "--sync" | "--no-sync" | "--total" => {}
+            arg if arg.starts_with("--type=") => options.types.push(arg[7..].to_string()),
+            arg if arg.starts_with("--exclude-type=") => {
+                options.exclude_types.push(arg[15..].to_string())
+            }
+            arg if arg.starts_with("--") => {
+                return Err(invalid_option(&format!("unrecognized option '{}'", arg)));
+            }
+            arg if arg.len() > 1 && arg.starts_with('-') => {
+                let flags = &arg[1..];
+                for (i, flag) in flags.char_indices() {
+                    match flag {
+                        'h' | 'H' => options.units = Units::Human,
+                        'k' => options.units = Units::Blocks(1024),
+                        'm' => options.units = Units::Blocks(1024 * 1024),
+                        'i' => options.inodes = true,
+                        'T' => options.print_type = true,
+                        // All, local and POSIX output don't change the listing
+                        'a' | 'l' | 'P' => {}
+                        't' | 'x' => {
+                            let value = match &flags[i + 1..] {
+                                "" => args.next().ok_or_else(|| {
+                                    invalid_option(&format!(
+                                        "option requires an argument -- '{}'",
+                                        flag
+                                    ))
+                                })?,
+                                rest => rest,
+                            };
+                            if flag == 't' {
+                                options.types.push(value.to_string());
+                            } else {
+                                options.exclude_types.push(value.to_string());
+                            }
+                            break;
+                        }
+                        other => {
+                            return Err(invalid_option(&format!("invalid option -- '{}'", other)));
+                        }
+                    }
+                }
+            }
+            path => options.paths.push(path.to_string()),
+        }
+    }
+    Ok(options)
+}
+
+/// Every filesystem df lists, the root one with the fill of the fake disk
+fn rows(fs: &FileSystem, inodes: bool) -> Vec<Row> {
+    let disk = fs.disk_usage();
+    let root = if inodes {
+        Row {
+            source: fs.device().to_string(),
+            fs_type: "ext4",
+            target: "/",
+            size: disk.inodes,
+            used: disk.inodes_used,
+            available: disk.inodes_available(),
+        }
+    } else {
+        Row {
+            source: fs.device().to_string(),
+            fs_type: "ext4",
+            target: "/",
+            size: disk.size,
+            used: disk.used,
+            available: disk.available(),
+        }
+    };
+
+    let mut rows: Vec<Row> = MEMORY_FILESYSTEMS
+        .iter()
+        .map(
+            |&(source, fs_type, target, blocks, used_blocks, inode_count, used_inodes)| {
+                let (size, used) = if inodes {
+                    (inode_count, used_inodes)
+                } else {
+                    (blocks * 1024, used_blocks * 1024)
+                };
+                Row {
+                    source: source.to_string(),
+                    fs_type,
+                    target,
+                    size,
+                    used,
+                    available: size - used,
+                }
+            },
+        )
+        .collect();
+    // Mounted third, after /dev and /run
+    rows.insert(2, root);
+    rows
+}
+
+/// Percentage used, rounded up like df does
+fn percent(row: &Row) -> String {
+    match row.used + row.available {
+        0 => "-".to_string(),
+        total => format!("{}%", (row.used * 100).div_ceil(total)),
+    }
+}
+
+/// Columns padded to the widest value, at least as wide as coreutils makes
+/// them. Sources, types and targets are left aligned, numbers right aligned
+fn table(rows: &[Vec<String>], min_widths: &[usize]) -> String {
+    let widths: Vec<usize> = min_widths
+        .iter()
+        .enumerate()
+        .map(|(column, min)| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .max()
+                .unwrap_or(0)
+                .max(*min)
+        })
+        .collect();
+    let last = widths.len() - 1;
+    let mut output = String::new();
+    for row in rows {
+        let mut line = String::new();
+        for (column, value) in row.iter().enumerate() {
+            if column > 0 {
+                line.push(' ');
+            }
+            let left_aligned = column == 0 || (column == 1 && last == 6);
+            if column == last {
+                line.push_str(value);
+            } else if left_aligned {
+                line.push_str(&format!("{:<width$}", value, width = widths[column]));
+            } else {
+                line.push_str(&format!("{:>width$}", value, width = widths[column]));
+            }
+        }
+        output.push_str(&line);
+        output.push_str("\r\n");
+    }
+    output
+}
+
+/// df command, disk space of the mounted filesystems. The root filesystem is
+/// the fake one: what attackers write fills it, and with --disk-size-mb set
+/// writes fail once it is full
+pub struct DfCommand;
+
+#[async_trait]
+impl Command for DfCommand {
+    fn name(&self) -> &'static str {
+        "df"
+    }
+
+    fn help(&self) -> String {
+        "Usage: df [OPTION]... [FILE]...\r\n\
+        Show information about the file system on which each FILE resides,\r\n\
+        or all file systems by default.\r\n\
+        \r\n\
+        Mandatory arguments to long options are mandatory for short options too.\r\n\
+        \x20 -a, --all             include pseudo, duplicate, inaccessible file systems\r\n\
+        \x20 -B, --block-size=SIZE  scale sizes by SIZE before printing them; e.g.,\r\n\
+        \x20                          '-BM' prints sizes in units of 1,048,576 bytes;\r\n\
+        \x20                          see SIZE format below\r\n\
+        \x20 -h, --human-readable  print sizes in powers of 1024 (e.g., 1023M)\r\n\
+        \x20 -H, --si              print sizes in powers of 1000 (e.g., 1.1G)\r\n\
+        \x20 -i, --inodes          list inode information instead of block usage\r\n\
+        \x20 -k                    like --block-size=1K\r\n\
+        \x20 -l, --local           limit listing to local file systems\r\n\
+        \x20     --no-sync         do not invoke sync before getting usage info (default)\r\n\
+        \x20     --output[=FIELD_LIST]  use the output format defined by FIELD_LIST,\r\n\
+        \x20                              or print all fields if FIELD_LIST is omitted.\r\n\
+        \x20 -P, --portability     use the POSIX output format\r\n\
+        \x20     --sync            invoke sync before getting usage info\r\n\
+        \x20     --total           elide all entries insignificant to available space,\r\n\
+        \x20                         and produce a grand total\r\n\
+        \x20 -t, --type=TYPE       limit listing to file systems of type TYPE\r\n\
+        \x20 -T, --print-type      print file system type\r\n\
+        \x20 -x, --exclude-type=TYPE   limit listing to file systems not of type TYPE\r\n\
+        \x20 -v                    (ignored)\r\n\
+        \x20     --help     display this help and exit\r\n\
+        \x20     --version  output version information and exit\r\n\
+        \r\n\
+        Display values are in units of the first available SIZE from --block-size,\r\n\
+        and the DF_BLOCK_SIZE, BLOCK_SIZE and BLOCKSIZE environment variables.\r\n\
+        Otherwise, units default to 1024 bytes (or 512 if POSIXLY_CORRECT is set).\r\n\
+        \r\n\
+        GNU coreutils online help: <https://www.gnu.org/software/coreutils/>\r\n\
+        Full documentation <https://www.gnu.org/software/coreutils/df>\r\n\
+        or available locally via: info '(coreutils) df invocation'\r\n"
+            .to_string()
+    }
+
+    fn version(&self) -> String {
+        "df (GNU coreutils) 8.32\r\n\
+        Copyright (C) 2020 Free Software Foundation, Inc.\r\n\
+        License GPLv3+: GNU GPL version 3 or later <https://gnu.org/licenses/gpl.html>.\r\n\
+        This is free software: you are free to change and redistribute it.\r\n\
+        There is NO WARRANTY, to the extent permitted by law.\r\n\
+        \r\n\
+        Written by Torbjorn Granlund, David MacKenzie, and Paul Eggert.\r\n"
+            .to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        if args.iter().any(|a| a == "--help") {
+            return Ok(self.help());
+        }
+        if args.iter().any(|a| a == "--version") {
+            return Ok(self.version());
+        }
+        let options = parse_args(args)?;
+
+        let fs = context.filesystem.read().await;
+        let mut rows = rows(&fs, options.inodes);
+        rows.retain(|row| {
+            (options.types.is_empty() || options.types.iter().any(|t| t == row.fs_type))
+                && !options.exclude_types.iter().any(|t| t == row.fs_type)
+        });
+
+        // With operands, the filesystem each one is on, in their order
+        let mut errors = Vec::new();
+        if !options.paths.is_empty() {
+            let mut selected = Vec::new();
+            for path in &options.paths {
+                let absolute = if path.starts_with('/') {
+                    path.clone()
+                } else {
+                    format!("{}/{}", context.cwd.trim_end_matches('/'), path)
+                };
+                let absolute = fs.resolve_absolute_path(&absolute);
+                if fs.get_file(&absolute).is_err() {
+                    errors.push(format!("df: '{}': No such file or directory", path));
+                    continue;
+                }
+                let mount = rows
+                    .iter()
+                    .filter(|row| {
+                        row.target == "/"
+                            || absolute == row.target
+                            || absolute.starts_with(&format!("{}/", row.target))
+                    })
+                    .max_by_key(|row| row.target.len());
+                if let Some(mount) = mount {
+                    selected.push(Row {
+                        source: mount.source.clone(),
+                        ..*mount
+                    });
+                }
+            }
+            rows = selected;
+        }
+
+        let size_header = match (options.inodes, options.units) {
+            (true, _) => "Inodes".to_string(),
+            (false, Units::Human) => "Size".to_string(),
+            (false, Units::Blocks(1024)) => "1K-blocks".to_string(),
+            (false, Units::Blocks(_)) => "1M-blocks".to_string(),
+        };
+        let (used_header, available_header, percent_header) = match (options.inodes, options.units)
+        {
+            (true, _) => ("IUsed", "IFree", "IUse%"),
+            (false, Units::Human) => ("Used", "Avail", "Use%"),
+            (false, Units::Blocks(_)) => ("Used", "Available", "Use%"),
+        };
+        let number = |value: u64| match (options.inodes, options.units) {
+            (true, Units::Human) => human(value),
+            (true, _) => value.to_string(),
+            (false, Units::Human) => human(value),
+            (false, Units::Blocks(size)) => value.div_ceil(size).to_string(),
+        };
+
+        let mut table_rows = vec![];
+        let mut header = vec!["Filesystem".to_string()];
+        if options.print_type {
+            header.push("Type".to_string());
+        }
+        header.extend([
+            size_header,
+            used_header.to_string(),
+            available_header.to_string(),
+            percent_header.to_string(),
+            "Mounted on".to_string(),
+        ]);
+        table_rows.push(header);
+        for row in &rows {
+            let mut line = vec![row.source.clone()];
+            if options.print_type {
+                line.push(row.fs_type.to_string());
+            }
+            line.extend([
+                number(row.size),
+                number(row.used),
+                number(row.available),
+                percent(row),
+                row.target.to_string(),
+            ]);
+            table_rows.push(line);
+        }
+        let mut min_widths = vec![14];
+        if options.print_type {
+            min_widths.push(4);
+        }
+        min_widths.extend([5, 5, 5, 4, 0]);
+
+        let output = if rows.is_empty() {
+            String::new()
+        } else {
+            table(&table_rows, &min_widths)
+        };
+        // Like du, what could be listed wins over the errors
+        if output.is_empty() && !errors.is_empty() {
+            Err(CommandError::FilesystemError(errors.join("\r\n")))
+        } else {
+            Ok(output)
+        }
+    }
+}
@@ -0,0 +1,429 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use super::dns::{self, DnsAnswer, DnsRecord, RECORD_TYPES};
+use crate::filesystem::fs2::FileContent;
+use async_trait::async_trait;
+use rand::{RngExt, rng};
+
+/// systemd-resolved's stub, what Ubuntu's /etc/resolv.conf points at
+const DEFAULT_NAMESERVER: &str = "127.0.0.53";
+
+/// First nameserver in the fake /etc/resolv.conf, so the three tools and `cat` agree
+async fn nameserver(context: &CommandContext) -> String {
+    let fs = context.filesystem.read().await;
+    let Ok(entry) = fs.follow_symlink("/etc/resolv.conf") else {
+        return DEFAULT_NAMESERVER.to_string();
+    };
+    let Some(FileContent::RegularFile(bytes)) = &entry.file_content else {
+        return DEFAULT_NAMESERVER.to_string();
+    };
+    String::from_utf8_lossy(bytes)
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("nameserver"))
+        .map(|server| server.trim().to_string())
+        .filter(|server| !server.is_empty())
+        .unwrap_or_else(|| DEFAULT_NAMESERVER.to_string())
+}
+
+/// `name` with the trailing dot dig puts on every owner name
+fn fqdn(name: &str) -> String {
+    format!("{}.", name.trim_end_matches('.'))
+}
+
+/// `text` followed by tabs up to `column`, at least one, like dig lines up its sections
+fn tab_to(text: &str, column: usize) -> String {
+    let tabs = column.saturating_sub(text.len()).div_ceil(8).max(1);
+    format!("{}{}", text, "\t".repeat(tabs))
+}
+
+/// Record type given as a word, e.g. `mx` in `dig example.com mx`
+fn record_type(arg: &str) -> Option<&'static str> {
+    RECORD_TYPES
+        .iter()
+        .find(|known| known.eq_ignore_ascii_case(arg))
+        .copied()
+}
+
+/// dig command, prints the sections of the answer like BIND's dig 9.16
+pub struct DigCommand;
+
+#[async_trait]
+impl Command for DigCommand {
+    fn name(&self) -> &'static str {
+        "dig"
+    }
+
+    fn help(&self) -> String {
+        "Usage:  dig [@global-server] [domain] [q-type] [q-class] {q-opt}\r\n\
+        \x20           {global-d-opt} host [@local-server] {local-d-opt}\r\n\
+        \x20           [ host [@local-server] {local-d-opt} [...]]\r\n\
+        Where:  domain\t  is in the Domain Name System\r\n\
+        \x20       q-class  is one of (in,hs,ch,...) [default: in]\r\n\
+        \x20       q-type   is one of (a,any,mx,ns,soa,hinfo,axfr,txt,...) [default:a]\r\n\
+        \x20                (Use ixfr=version for type ixfr)\r\n\
+        \x20       q-opt    is one of:\r\n\
+        \x20                -4                  (use IPv4 query transport only)\r\n\
+        \x20                -6                  (use IPv6 query transport only)\r\n\
+        \x20                -t type             (specify query type)\r\n\
+        \x20                -x dot-notation     (shortcut for reverse lookups)\r\n\
+        \x20       d-opt    is of the form +keyword[=value], where keyword is:\r\n\
+        \x20                +[no]short          (Display nothing except short\r\n\
+        \x20                                    form of answers - global option)\r\n"
+            .to_string()
+    }
+
+    fn version(&self) -> String {
+        "DiG 9.16.1-Ubuntu\r\n".to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        let command_line = args.join(" ");
+        let mut name = None;
+        let mut qtype = None;
+        let mut server = None;
+        let mut short = false;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" => return Ok(self.help()),
+                "-v" => {
+                    return Err(CommandError::ExecutionError(
+                        self.version().trim_end().to_string(),
+                    ));
+                }
+                "+short" => short = true,
+                "+noshort" => short = false,
+                "-t" => match args.next().and_then(|arg| record_type(arg)) {
+                    Some(ty) => qtype = Some(ty),
+                    None => {
+                        return Err(CommandError::InvalidArguments(
+                            ";; Warning, ignoring invalid type".to_string(),
+                        ));
+                    }
+                },
+                "-x" => match args.next().and_then(|addr| dns::reverse_name(addr)) {
+                    Some(reverse) => {
+                        name = Some(reverse);
+                        qtype = Some("PTR");
+                    }
+                    None => {
+                        return Err(CommandError::InvalidArguments(
+                            "dig: 'reverse' is not a legal IP address".to_string(),
+                        ));
+                    }
+                },
+                arg if arg.starts_with('@') => {
+                    server = Some(arg.trim_start_matches('@').to_string())
+                }
+                // +tries=2, +noall +answer, -4 and friends change nothing we print
+                arg if arg.starts_with('+') || arg.starts_with('-') => {}
+                arg if arg.eq_ignore_ascii_case("IN") => {}
+                arg => match record_type(arg) {
+                    Some(ty) if qtype.is_none() => qtype = Some(ty),
+                    _ => name = Some(arg.to_string()),
+                },
+            }
+        }
+
+        // Without a name dig asks for the root servers
+        let (name, qtype) = match name {
+            Some(name) => (name, qtype.unwrap_or("A")),
+            None => (".".to_string(), qtype.unwrap_or("NS")),
+        };
+        let server = match server {
+            Some(server) => server,
+            None => nameserver(context).await,
+        };
+        let answer = dns::lookup(context, "dig", &name, qtype).await;
+
+        if short {
+            return Ok(answer
+                .records()
+                .iter()
+                .map(|record| format!("{}\r\n", record.data))
+                .collect());
+        }
+
+        let owner = fqdn(&name);
+        let records = answer.records();
+        let mut out = format!(
+            "\r\n; <<>> DiG 9.16.1-Ubuntu <<>> {}\r\n\
+            ;; global options: +cmd\r\n\
+            ;; Got answer:\r\n\
+            ;; ->>HEADER<<- opcode: QUERY, status: {}, id: {}\r\n\
+            ;; flags: qr rd ra; QUERY: 1, ANSWER: {}, AUTHORITY: 0, ADDITIONAL: 1\r\n\
+            \r\n\
+            ;; OPT PSEUDOSECTION:\r\n\
+            ; EDNS: version: 0, flags:; udp: 65494\r\n\
+            ;; QUESTION SECTION:\r\n\
+            {}IN\t{}\r\n\
+            \r\n",
+            command_line,
+            answer.status(),
+            rng().random_range(1..u16::MAX),
+            records.len(),
+            tab_to(&format!(";{}", owner), 32),
+            qtype
+        );
+        if !records.is_empty() {
+            out.push_str(";; ANSWER SECTION:\r\n");
+            for DnsRecord { ttl, data } in records {
+                out.push_str(&format!(
+                    "{}{}\tIN\t{}\t{}\r\n",
+                    tab_to(&owner, 24),
+                    ttl,
+                    qtype,
+                    data
+                ));
+            }
+            out.push_str("\r\n");
+        }
+        out.push_str(&format!(
+            ";; Query time: {} msec\r\n\
+            ;; SERVER: {}#53({})\r\n\
+            ;; WHEN: {}\r\n\
+            ;; MSG SIZE  rcvd: {}\r\n\
+            \r\n",
+            rng().random_range(8..60),
+            server,
+            server,
+            chrono::Local::now().format("%a %b %d %H:%M:%S %Z %Y"),
+            40 + name.len() + records.iter().map(|r| 12 + r.data.len()).sum::<usize>()
+        ));
+        Ok(out)
+    }
+}
+
+/// host command, one line per record and `not found` for failed lookups
+pub struct HostCommand;
+
+impl HostCommand {
+    /// What host says about one record of `name`
+    fn describe(name: &str, qtype: &str, record: &DnsRecord) -> String {
+        let what = match qtype {
+            "A" => "has address",
+            "AAAA" => "has IPv6 address",
+            "MX" => "mail is handled by",
+            "NS" => "name server",
+            "CNAME" => "is an alias for",
+            "TXT" => "descriptive text",
+            "PTR" => "domain name pointer",
+            _ => return format!("{} has {} record {}\r\n", name, qtype, record.data),
+        };
+        format!("{} {} {}\r\n", name, what, record.data)
+    }
+}
+
+#[async_trait]
+impl Command for HostCommand {
+    fn name(&self) -> &'static str {
+        "host"
+    }
+
+    fn help(&self) -> String {
+        "Usage: host [-aCdilrTvVw] [-c class] [-N ndots] [-t type] [-W time]\r\n\
+        \x20           [-R number] [-m flag] [-p port] hostname [server]\r\n\
+        \x20      -a is equivalent to -v -t ANY\r\n\
+        \x20      -A is like -a but omits RRSIG, NSEC, NSEC3\r\n\
+        \x20      -c specifies query class for non-IN data\r\n\
+        \x20      -C compares SOA records on authoritative nameservers\r\n\
+        \x20      -d is equivalent to -v\r\n\
+        \x20      -l lists all hosts in a domain, using AXFR\r\n\
+        \x20      -m set memory debugging flag (trace|record|usage)\r\n\
+        \x20      -N changes the number of dots allowed before root lookup is done\r\n\
+        \x20      -p specifies the port on the server to query\r\n\
+        \x20      -r disables recursive processing\r\n\
+        \x20      -R specifies number of retries for UDP packets\r\n\
+        \x20      -s a SERVFAIL response should stop query\r\n\
+        \x20      -t specifies the query type\r\n\
+        \x20      -T enables TCP/IP mode\r\n\
+        \x20      -U enables UDP mode\r\n\
+        \x20      -v enables verbose output\r\n\
+        \x20      -V print version number and exit\r\n\
+        \x20      -w specifies to wait forever for a reply\r\n\
+        \x20      -W specifies how long to wait for a reply\r\n\
+        \x20      -4 use IPv4 query transport only\r\n\
+        \x20      -6 use IPv6 query transport only\r\n"
+            .to_string()
+    }
+
+    fn version(&self) -> String {
+        "host 9.16.1-Ubuntu\r\n".to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        let mut qtype = None;
+        let mut operands = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-V" => return Ok(self.version()),
+                "-h" | "--help" => return Err(CommandError::InvalidArguments(self.help())),
+                "-t" => match args.next().and_then(|arg| record_type(arg)) {
+                    Some(ty) => qtype = Some(ty),
+                    None => {
+                        return Err(CommandError::InvalidArguments(
+                            "host: invalid type".to_string(),
+                        ));
+                    }
+                },
+                "-a" => qtype = Some("ANY"),
+                // Options taking a value
+                "-c" | "-N" | "-R" | "-m" | "-p" | "-W" => {
+                    args.next();
+                }
+                arg if arg.starts_with('-') => {}
+                _ => operands.push(arg.clone()),
+            }
+        }
+        let Some(target) = operands.first() else {
+            return Err(CommandError::InvalidArguments(self.help()));
+        };
+
+        let mut out = String::new();
+        if let Some(server) = operands.get(1) {
+            out.push_str(&format!(
+                "Using domain server:\r\nName: {}\r\nAddress: {}#53\r\nAliases: \r\n\r\n",
+                server, server
+            ));
+        }
+
+        // An address is looked up in reverse, a name for its addresses and mail servers
+        let (name, qtypes) = match (dns::reverse_name(target), qtype) {
+            (Some(reverse), None) => (reverse, vec!["PTR"]),
+            (_, Some(qtype)) => (target.clone(), vec![qtype]),
+            (None, None) => (target.clone(), vec!["A", "AAAA", "MX"]),
+        };
+        let explicit = qtype.is_some();
+        for qtype in qtypes {
+            let answer = dns::lookup(context, "host", &name, qtype).await;
+            match &answer {
+                DnsAnswer::Records(records) if records.is_empty() => {
+                    if explicit {
+                        out.push_str(&format!("{} has no {} record\r\n", name, qtype));
+                    }
+                }
+                DnsAnswer::Records(records) => {
+                    for record in records {
+                        out.push_str(&Self::describe(&name, qtype, record));
+                    }
+                }
+                failed => {
+                    out.push_str(&format!(
+                        "Host {} not found: {}({})",
+                        name,
+                        failed.rcode(),
+                        failed.status()
+                    ));
+                    return Err(CommandError::ExecutionError(out));
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// nslookup command, non-interactive mode only
+pub struct NslookupCommand;
+
+#[async_trait]
+impl Command for NslookupCommand {
+    fn name(&self) -> &'static str {
+        "nslookup"
+    }
+
+    fn help(&self) -> String {
+        "Usage:\r\n\
+        \x20  nslookup [-opt ...]             # interactive mode using default server\r\n\
+        \x20  nslookup [-opt ...] - server    # interactive mode using 'server'\r\n\
+        \x20  nslookup [-opt ...] host        # just look up 'host' using default server\r\n\
+        \x20  nslookup [-opt ...] host server # just look up 'host' using 'server'\r\n"
+            .to_string()
+    }
+
+    fn version(&self) -> String {
+        "nslookup 9.16.1-Ubuntu\r\n".to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        let mut qtype = None;
+        let mut operands = Vec::new();
+        for arg in args {
+            match arg.as_str() {
+                "-version" => return Ok(self.version()),
+                "-h" | "-help" | "--help" => return Ok(self.help()),
+                arg if arg.starts_with('-') => {
+                    let option = arg.trim_start_matches('-').to_ascii_lowercase();
+                    if let Some(ty) = option
+                        .strip_prefix("type=")
+                        .or_else(|| option.strip_prefix("query="))
+                        .or_else(|| option.strip_prefix("querytype="))
+                        .or_else(|| option.strip_prefix("q="))
+                    {
+                        match record_type(ty) {
+                            Some(ty) => qtype = Some(ty),
+                            None => {
+                                return Err(CommandError::InvalidArguments(format!(
+                                    "unknown query type: {}",
+                                    ty
+                                )));
+                            }
+                        }
+                    }
+                }
+                _ => operands.push(arg.clone()),
+            }
+        }
+        let Some(target) = operands.first() else {
+            return Err(CommandError::InvalidArguments(self.help()));
+        };
+        let server = match operands.get(1) {
+            Some(server) => server.clone(),
+            None => nameserver(context).await,
+        };
+
+        let mut out = format!("Server:\t\t{}\r\nAddress:\t{}#53\r\n\r\n", server, server);
+        let (name, qtypes) = match (dns::reverse_name(target), qtype) {
+            (Some(reverse), None) => (reverse, vec!["PTR"]),
+            (_, Some(qtype)) => (target.clone(), vec![qtype]),
+            (None, None) => (target.clone(), vec!["A", "AAAA"]),
+        };
+
+        let mut answers = Vec::new();
+        for qtype in qtypes {
+            let answer = dns::lookup(context, "nslookup", &name, qtype).await;
+            if !matches!(answer, DnsAnswer::Records(_)) {
+                out.push_str(&format!(
+                    "** server can't find {}: {}\r\n",
+                    name,
+                    answer.status()
+                ));
+                return Err(CommandError::ExecutionError(out));
+            }
+            answers.extend(
+                answer
+                    .records()
+                    .iter()
+                    .map(|record| (qtype, record.clone())),
+            );
+        }
+
+        out.push_str("Non-authoritative answer:\r\n");
+        if answers.is_empty() {
+            out.push_str(&format!("*** Can't find {}: No answer\r\n", name));
+        }
+        for (qtype, DnsRecord { data, .. }) in answers {
+            out.push_str(&match qtype {
+                "A" | "AAAA" => format!("Name:\t{}\r\nAddress: {}\r\n", name, data),
+                "MX" => format!("{}\tmail exchanger = {}\r\n", name, data),
+                "NS" => format!("{}\tnameserver = {}\r\n", name, data),
+                "CNAME" => format!("{}\tcanonical name = {}\r\n", name, data),
+                "TXT" => format!("{}\ttext = {}\r\n", name, data),
+                "PTR" => format!("{}\tname = {}\r\n", name, data),
+                _ => format!("{}\trdata_{} = {}\r\n", name, qtype, data),
+            });
+        }
+        out.push_str("\r\n");
+        Ok(out)
+    }
+}
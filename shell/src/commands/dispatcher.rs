@@ -1,10 +1,25 @@
-use super::command_trait::CommandError;
-use super::context::CommandContext;
+use super::busybox::write_file;
+use super::command_not_found::CommandNotFoundHandler;
+use super::command_trait::{CommandError, CommandResult};
+use super::context::{CommandContext, ShellEvent};
+use super::executable::{self, FileRun};
+use super::help_command;
+use super::nohup_command;
 use super::registry::CommandRegistry;
+use super::timeout_command;
+use super::watch_command;
+use crate::filesystem::fs2::{FileContent, FileSystem};
 use crate::filters;
 use crate::parser::{self, AndOp, CommandList, Redirect};
+use chrono::Local;
 use std::future::Future;
+use std::panic::AssertUnwindSafe;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Poll;
+
+/// Bytes of output one command may produce before it is cut off
+pub const DEFAULT_MAX_OUTPUT: usize = 10 * 1024 * 1024;
 
 /// Outcome of executing a full command line.
 pub struct ExecutionOutcome {
@@ -17,6 +32,10 @@ pub struct ExecutionOutcome {
 /// Handles command parsing and execution.
 pub struct CommandDispatcher {
     registry: CommandRegistry,
+    /// Optional Ubuntu-style `command-not-found` suggestions for unknown commands
+    command_not_found: Option<CommandNotFoundHandler>,
+    /// Bytes of stdout and stderr one command may produce
+    max_output: usize,
 }
 
 /// Internal result of running a single pipeline.
@@ -33,12 +52,28 @@ impl CommandDispatcher {
     pub fn new() -> Self {
         Self {
             registry: CommandRegistry::new(),
+            command_not_found: None,
+            max_output: DEFAULT_MAX_OUTPUT,
         }
     }
 
     /// Create a new command dispatcher with the given registry
     pub fn with_registry(registry: CommandRegistry) -> Self {
-        Self { registry }
+        Self {
+            registry,
+            command_not_found: None,
+            max_output: DEFAULT_MAX_OUTPUT,
+        }
+    }
+
+    /// Enable (or disable with `None`) command-not-found package suggestions
+    pub fn set_command_not_found_handler(&mut self, handler: Option<CommandNotFoundHandler>) {
+        self.command_not_found = handler;
+    }
+
+    /// Limit the output of every command to `bytes`
+    pub fn set_max_output(&mut self, bytes: usize) {
+        self.max_output = bytes;
     }
 
     /// Get a mutable reference to the registry for command registration
@@ -60,6 +95,21 @@ impl CommandDispatcher {
             };
         }
 
+        // Commands have their own guard in dispatch_one, this one is for the parser
+        match catch_panic(self.run_line(command_line, context)).await {
+            Ok(outcome) => outcome,
+            Err(message) => {
+                log::error!("Command line {:?} panicked: {}", command_line, message);
+                ExecutionOutcome {
+                    output: String::new(),
+                    exit_requested: false,
+                }
+            }
+        }
+    }
+
+    /// Substitute, parse and run `command_line`
+    async fn run_line(&self, command_line: &str, context: &mut CommandContext) -> ExecutionOutcome {
         let resolved = self.resolve_substitutions(command_line, context).await;
         if resolved.trim().is_empty() {
             return ExecutionOutcome {
@@ -68,15 +118,9 @@ impl CommandDispatcher {
             };
         }
 
-        let home = context
-            .get_env("HOME")
-            .cloned()
-            .unwrap_or_else(|| format!("/home/{}", context.username));
-        let script = parser::parse_script(&resolved, &context.env_vars, &home);
+        let script = parser::parse_script_unexpanded(&resolved);
 
-        let (stdout, stderr, exit, _) = self.run_nodes(&script.nodes, context).await;
-        let mut output = stdout;
-        output.push_str(&stderr);
+        let (_stdout, output, exit, _) = self.run_nodes(&script.nodes, context).await;
 
         ExecutionOutcome {
             output,
@@ -161,25 +205,23 @@ impl CommandDispatcher {
             if resolved.trim().is_empty() {
                 return String::new();
             }
-            let home = context
-                .get_env("HOME")
-                .cloned()
-                .unwrap_or_else(|| format!("/home/{}", context.username));
-            let script = parser::parse_script(&resolved, &context.env_vars, &home);
-            let (stdout, _stderr, _exit, _) = self.run_nodes(&script.nodes, context).await;
+            let script = parser::parse_script_unexpanded(&resolved);
+            let (stdout, _output, _exit, _) = self.run_nodes(&script.nodes, context).await;
             stdout.trim().to_string()
         })
     }
 
     /// Run a full command list honouring `&&` / `||` / `;` sequencing.
-    /// Returns `(stdout, stderr, exit_requested, last_success)`.
+    /// Returns `(stdout, output, exit_requested, last_success)`, where output is
+    /// what the terminal shows: each pipeline's stdout and then its stderr, in
+    /// the order the pipelines ran.
     async fn run_command_list(
         &self,
         list: &CommandList,
         context: &mut CommandContext,
     ) -> (String, String, bool, bool) {
         let mut stdout = String::new();
-        let mut stderr = String::new();
+        let mut output = String::new();
         let mut exit = false;
         let mut last_success = true;
         let mut should_run = true;
@@ -188,7 +230,8 @@ impl CommandDispatcher {
             if should_run {
                 let r = self.run_pipeline(&item.pipeline, context).await;
                 stdout.push_str(&r.stdout);
-                stderr.push_str(&r.stderr);
+                output.push_str(&r.stdout);
+                output.push_str(&r.stderr);
                 exit |= r.exit_requested;
                 last_success = r.success;
             }
@@ -199,11 +242,11 @@ impl CommandDispatcher {
             };
         }
 
-        (stdout, stderr, exit, last_success)
+        (stdout, output, exit, last_success)
     }
 
     /// Run a sequence of parsed nodes (control flow + command lists).
-    /// Returns `(stdout, stderr, exit_requested, last_success)`.
+    /// Returns `(stdout, output, exit_requested, last_success)`.
     /// Boxed to break the async recursion cycle with `run_node` (nested control
     /// flow like `if` inside `if`).
     fn run_nodes<'a>(
@@ -213,24 +256,24 @@ impl CommandDispatcher {
     ) -> Pin<Box<dyn Future<Output = (String, String, bool, bool)> + Send + 'a>> {
         Box::pin(async move {
             let mut stdout = String::new();
-            let mut stderr = String::new();
+            let mut output = String::new();
             let mut exit = false;
             let mut last_success = true;
             for node in nodes {
                 let (o, e, ex, succ) = self.run_node(node, context).await;
                 stdout.push_str(&o);
-                stderr.push_str(&e);
+                output.push_str(&e);
                 exit |= ex;
                 last_success = succ;
                 if exit {
                     break;
                 }
             }
-            (stdout, stderr, exit, last_success)
+            (stdout, output, exit, last_success)
         })
     }
 
-    /// Execute a single parsed node. Returns `(stdout, stderr, exit, success)`.
+    /// Execute a single parsed node. Returns `(stdout, output, exit, success)`.
     async fn run_node(
         &self,
         node: &parser::Node,
@@ -243,37 +286,42 @@ impl CommandDispatcher {
                 else_body,
             } => {
                 let mut stdout = String::new();
-                let mut stderr = String::new();
+                let mut output = String::new();
                 for (cond, body) in branches {
                     let (o, e, _ex, succ) = self.run_nodes(cond, context).await;
                     stdout.push_str(&o);
-                    stderr.push_str(&e);
+                    output.push_str(&e);
                     if succ {
                         let (o2, e2, ex2, _) = self.run_nodes(body, context).await;
                         stdout.push_str(&o2);
-                        stderr.push_str(&e2);
-                        return (stdout, stderr, ex2, true);
+                        output.push_str(&e2);
+                        return (stdout, output, ex2, true);
                     }
                 }
                 if let Some(els) = else_body {
                     let (o, e, ex, _) = self.run_nodes(els, context).await;
                     stdout.push_str(&o);
-                    stderr.push_str(&e);
-                    return (stdout, stderr, ex, true);
+                    output.push_str(&e);
+                    return (stdout, output, ex, true);
                 }
-                (stdout, stderr, false, true)
+                (stdout, output, false, true)
             }
             parser::Node::For { var, words, body } => {
                 let mut stdout = String::new();
-                let mut stderr = String::new();
+                let mut output = String::new();
                 let mut exit = false;
                 let mut last_success = true;
                 let saved = context.env_vars.get(var).cloned();
+                let home = home_dir(context);
+                let words: Vec<String> = words
+                    .iter()
+                    .map(|w| parser::expand_word(w, &context.env_vars, &home))
+                    .collect();
                 for w in words {
                     context.env_vars.insert(var.clone(), w.clone());
                     let (o, e, ex, succ) = self.run_nodes(body, context).await;
                     stdout.push_str(&o);
-                    stderr.push_str(&e);
+                    output.push_str(&e);
                     exit |= ex;
                     last_success = succ;
                     if exit {
@@ -288,11 +336,11 @@ impl CommandDispatcher {
                         context.env_vars.remove(var);
                     }
                 }
-                (stdout, stderr, exit, last_success)
+                (stdout, output, exit, last_success)
             }
             parser::Node::While { cond, body, until } => {
                 let mut stdout = String::new();
-                let mut stderr = String::new();
+                let mut output = String::new();
                 let mut exit = false;
                 for _ in 0..10000 {
                     let (_o, _e, _ex, succ) = self.run_nodes(cond, context).await;
@@ -302,13 +350,13 @@ impl CommandDispatcher {
                     }
                     let (o, e, ex, _) = self.run_nodes(body, context).await;
                     stdout.push_str(&o);
-                    stderr.push_str(&e);
+                    output.push_str(&e);
                     exit |= ex;
                     if exit {
                         break;
                     }
                 }
-                (stdout, stderr, exit, true)
+                (stdout, output, exit, true)
             }
         }
     }
@@ -326,6 +374,8 @@ impl CommandDispatcher {
         let mut exit_requested = false;
 
         for (idx, cmd) in pipeline.commands.iter().enumerate() {
+            // Expanded now, after what ran before it on the line
+            let cmd = &cmd.expand(&context.env_vars, &home_dir(context));
             if cmd.name.is_empty() {
                 continue;
             }
@@ -341,6 +391,15 @@ impl CommandDispatcher {
                 stdout.clone()
             };
 
+            // bash opens the files before running the command, and doesn't run it
+            // when one of them can't be
+            if let Err(err) = Self::open_redirects(&cmd.redirects, context).await {
+                stdout = String::new();
+                stderr_acc.push_str(&err);
+                success = false;
+                continue;
+            }
+
             // Bare variable assignment `VAR=value` (RHS already expanded).
             if let Some((var, val)) = parser::parse_assignment(&cmd.name) {
                 context.env_vars.insert(var, val);
@@ -354,7 +413,8 @@ impl CommandDispatcher {
                 let (mut out, mut err, succ) = self
                     .dispatch_one(&real_name, &real_args, &stdin, is_first, context)
                     .await;
-                Self::apply_redirects(&mut out, &mut err, &cmd.redirects);
+                Self::apply_redirects(&mut out, &mut err, &real_name, &cmd.redirects, context)
+                    .await;
                 stdout = out;
                 stderr_acc.push_str(&err);
                 success = succ;
@@ -364,7 +424,7 @@ impl CommandDispatcher {
             let (mut out, mut err, succ) = self
                 .dispatch_one(&cmd.name, &cmd.args, &stdin, is_first, context)
                 .await;
-            Self::apply_redirects(&mut out, &mut err, &cmd.redirects);
+            Self::apply_redirects(&mut out, &mut err, &cmd.name, &cmd.redirects, context).await;
             stdout = out;
             stderr_acc.push_str(&err);
             success = succ;
@@ -379,6 +439,11 @@ impl CommandDispatcher {
     }
 
     /// Dispatch a single command/filter, returning `(stdout, stderr, success)`.
+    /// A panic ends only this command: it is logged with the command and the
+    /// client sees what bash prints for a crashed program, the session goes on.
+    /// Output past the limit is cut at a line end and the command shown as
+    /// killed, so `ls -R /` or `cat` of a huge upload can't pile up in pipes,
+    /// loops and the session's output
     async fn dispatch_one(
         &self,
         name: &str,
@@ -386,59 +451,433 @@ impl CommandDispatcher {
         stdin: &str,
         is_first: bool,
         context: &mut CommandContext,
+    ) -> (String, String, bool) {
+        let (mut stdout, mut stderr, success) =
+            match catch_panic(self.run_one(name, args, stdin, is_first, context)).await {
+                Ok(result) => result,
+                Err(message) => {
+                    log::error!("Command {} {:?} panicked: {}", name, args, message);
+                    (String::new(), CRASH_MESSAGE.to_string(), false)
+                }
+            };
+        let size = stdout.len() + stderr.len();
+        if size <= self.max_output {
+            return (stdout, stderr, success);
+        }
+        log::warn!(
+            "Output of {} cut from {} to {} bytes for {}",
+            name,
+            size,
+            self.max_output,
+            context.auth_id
+        );
+        truncate_at_line(&mut stdout, self.max_output);
+        truncate_at_line(&mut stderr, self.max_output - stdout.len());
+        stderr.push_str(KILLED_MESSAGE);
+        (stdout, stderr, false)
+    }
+
+    /// dispatch_one without the guard
+    async fn run_one(
+        &self,
+        name: &str,
+        args: &[String],
+        stdin: &str,
+        is_first: bool,
+        context: &mut CommandContext,
     ) -> (String, String, bool) {
         if name.is_empty() {
             return (String::new(), String::new(), true);
         }
+        // `busybox <applet> ...` runs the applet, unknown applets are left to busybox itself
+        let (name, args) = match args.split_first() {
+            Some((applet, rest))
+                if matches!(name, "busybox" | "/bin/busybox")
+                    && self.registry.has_command(name)
+                    && (self.registry.has_command(applet) || filters::is_filter(applet)) =>
+            {
+                (applet.as_str(), rest)
+            }
+            _ => (name, args),
+        };
+        // `env NAME=value command` sets the variables and runs the command, plain
+        // `env` and its options are left to env itself
+        let (name, args) = match args
+            .iter()
+            .position(|arg| parser::parse_assignment(arg).is_none())
+        {
+            Some(index)
+                if name == "env"
+                    && self.registry.has_command(name)
+                    && !args[index].starts_with('-') =>
+            {
+                for (var, value) in args[..index]
+                    .iter()
+                    .filter_map(|a| parser::parse_assignment(a))
+                {
+                    context.env_vars.insert(var, value);
+                }
+                (args[index].as_str(), &args[index + 1..])
+            }
+            _ => (name, args),
+        };
+        if matches!(name, "nohup" | "setsid")
+            && self.registry.has_command(name)
+            && let Some(index) = nohup_command::wrapped_command(name, args)
+        {
+            return self
+                .run_detached(name, &args[index..], stdin, is_first, context)
+                .await;
+        }
+        if name == "watch"
+            && self.registry.has_command(name)
+            && let Some(options) = watch_command::watch_options(args)
+        {
+            return self.run_watch(options, context).await;
+        }
+        if name == "timeout"
+            && self.registry.has_command(name)
+            && let Ok(options) = timeout_command::timeout_options(args)
+        {
+            return self
+                .run_timeout(options, args, stdin, is_first, context)
+                .await;
+        }
         if filters::is_filter(name) && (!is_first || !self.registry.has_command(name)) {
             match filters::apply_filter(name, args, stdin, context).await {
-                Some((out, succ)) => (out, String::new(), succ),
+                Some((out, succ)) => {
+                    context.latency.wait(name, args, &out).await;
+                    (out, String::new(), succ)
+                }
                 None => (
                     String::new(),
-                    format!("bash: {}: command not found\r\n", name),
+                    self.command_not_found_message(name, context),
                     false,
                 ),
             }
         } else if self.registry.has_command(name) {
-            match self.registry.execute_command(name, args, context).await {
-                Ok(out) => (out, String::new(), true),
+            let result = match name {
+                // compgen -c lists what this shell actually runs
+                "compgen" => {
+                    let mut names = self.registry.get_command_names();
+                    names.extend(filters::FILTER_NAMES.iter().map(|f| f.to_string()));
+                    names.sort();
+                    names.dedup();
+                    help_command::compgen(args, &names, context).await
+                }
+                _ => self.registry.execute_command(name, args, context).await,
+            };
+            match result {
+                Ok(out) => {
+                    context.latency.wait(name, args, &out).await;
+                    (out, String::new(), true)
+                }
+                Err(CommandError::SilentFailure) => (String::new(), String::new(), false),
                 Err(e) => {
-                    let succ = matches!(e, CommandError::SilentFailure);
                     let msg = format!("{}\r\n", e);
-                    (String::new(), msg, succ)
+                    (String::new(), msg, false)
                 }
             }
         } else {
+            let path = executable::locate(
+                &*context.filesystem.read().await,
+                name,
+                &context.cwd,
+                context.get_env("PATH").map_or("", String::as_str),
+            );
+            match path {
+                Some(path) => match execute_file(name, &path, args, context)
+                    .await
+                    .bash_error(name)
+                {
+                    Some(error) => (String::new(), format!("{}\r\n", error), false),
+                    None => (String::new(), String::new(), true),
+                },
+                None => (
+                    String::new(),
+                    self.command_not_found_message(name, context),
+                    false,
+                ),
+            }
+        }
+    }
+
+    /// Run the command given to `nohup`/`setsid`. It stays in the process table
+    /// since it would keep running after the session ends, nohup appends its
+    /// output to nohup.out like it does when stdout is a terminal. Boxed to
+    /// break the async recursion cycle with `dispatch_one`.
+    fn run_detached<'a>(
+        &'a self,
+        wrapper: &'a str,
+        command: &'a [String],
+        stdin: &'a str,
+        is_first: bool,
+        context: &'a mut CommandContext,
+    ) -> Pin<Box<dyn Future<Output = (String, String, bool)> + Send + 'a>> {
+        Box::pin(async move {
+            let name = command[0].as_str();
+            let command_line = command.join(" ");
+            log::info!(
+                "[{}] {} started detached command: {}",
+                context.auth_id,
+                wrapper,
+                command_line
+            );
+
+            let builtin = self.registry.has_command(name) || filters::is_filter(name);
+            let run = if builtin {
+                FileRun::Executed
+            } else {
+                let path = executable::locate(
+                    &*context.filesystem.read().await,
+                    name,
+                    &context.cwd,
+                    context.get_env("PATH").map_or("", String::as_str),
+                );
+                match path {
+                    Some(path) => execute_file(name, &path, &command[1..], context).await,
+                    None => FileRun::NotFound,
+                }
+            };
+            if let Some(error) = run.exec_error() {
+                let msg = match wrapper {
+                    "nohup" => format!("nohup: failed to run command '{}': {}\r\n", name, error),
+                    _ => format!("setsid: failed to execute {}: {}\r\n", name, error),
+                };
+                return (String::new(), msg, false);
+            }
+
+            context
+                .processes
+                .write()
+                .await
+                .spawn(&context.username, &command_line);
+            let (stdout, stderr, success) = if builtin {
+                self.dispatch_one(name, &command[1..], stdin, is_first, context)
+                    .await
+            } else {
+                (String::new(), String::new(), true)
+            };
+            if wrapper == "setsid" {
+                return (stdout, stderr, success);
+            }
+
+            let path = absolute_path("nohup.out", &context.cwd);
+            let mut fs = context.filesystem.write().await;
+            let mut content = match fs.follow_symlink(&path) {
+                Ok(entry) => match &entry.file_content {
+                    Some(FileContent::RegularFile(content)) => content.to_vec(),
+                    _ => Vec::new(),
+                },
+                Err(_) => Vec::new(),
+            };
+            content.extend_from_slice(stdout.replace("\r\n", "\n").as_bytes());
+            content.extend_from_slice(stderr.replace("\r\n", "\n").as_bytes());
+            if let Err(err) = write_file(&mut fs, &path, content, 0o600) {
+                log::debug!("nohup could not write {}: {}", path, err);
+            }
             (
                 String::new(),
-                format!("bash: {}: command not found\r\n", name),
-                false,
+                "nohup: ignoring input and appending output to 'nohup.out'\r\n".to_string(),
+                success,
             )
+        })
+    }
+
+    /// Run the command given to `timeout` and drop it when the deadline passes, as
+    /// if the signal ended it; what it printed so far is lost. Boxed to break the
+    /// async recursion cycle with `dispatch_one`.
+    fn run_timeout<'a>(
+        &'a self,
+        options: timeout_command::TimeoutOptions,
+        args: &'a [String],
+        stdin: &'a str,
+        is_first: bool,
+        context: &'a mut CommandContext,
+    ) -> Pin<Box<dyn Future<Output = (String, String, bool)> + Send + 'a>> {
+        Box::pin(async move {
+            let name = args[options.command].as_str();
+            let run =
+                self.dispatch_one(name, &args[options.command + 1..], stdin, is_first, context);
+            if options.duration.is_zero() {
+                return run.await;
+            }
+            match tokio::time::timeout(options.duration, run).await {
+                Ok(result) => result,
+                Err(_) => {
+                    log::debug!("timeout {:?} ended {}", options.duration, name);
+                    let stderr = if options.verbose {
+                        format!(
+                            "timeout: sending signal {} to command '{}'\r\n",
+                            options.signal, name
+                        )
+                    } else {
+                        String::new()
+                    };
+                    (String::new(), stderr, false)
+                }
+            }
+        })
+    }
+
+    /// Run the command given to `watch` once through `sh -c` and show its output
+    /// under watch's header. There is no refresh loop, watch returns right after
+    /// the first round. Boxed to break the async recursion cycle with `dispatch_one`.
+    fn run_watch<'a>(
+        &'a self,
+        options: watch_command::WatchOptions,
+        context: &'a mut CommandContext,
+    ) -> Pin<Box<dyn Future<Output = (String, String, bool)> + Send + 'a>> {
+        Box::pin(async move {
+            log::info!(
+                "[{}] watch -n {} ran once: {}",
+                context.auth_id,
+                options.interval,
+                options.command
+            );
+
+            let resolved = self.resolve_substitutions(&options.command, context).await;
+            let script = parser::parse_script_unexpanded(&resolved);
+            let (_stdout, shown, _exit, _) = self.run_nodes(&script.nodes, context).await;
+
+            let mut output = String::new();
+            if options.title {
+                let right = format!(
+                    "{}: {}",
+                    context.hostname,
+                    Local::now().format("%a %b %e %H:%M:%S %Y")
+                );
+                output.push_str(&watch_command::watch_header(
+                    options.interval,
+                    &options.command,
+                    &right,
+                    context.terminal_width,
+                ));
+                output.push_str("\r\n\r\n");
+            }
+            // watch shows what the command wrote to either stream
+            output.push_str(&shown);
+            (output, String::new(), true)
+        })
+    }
+
+    /// Message for an unknown command, consulting the command-not-found handler when enabled
+    fn command_not_found_message(&self, name: &str, context: &CommandContext) -> String {
+        match &self.command_not_found {
+            Some(handler) => handler.message(name, context),
+            None => format!("bash: {}: command not found\r\n", name),
+        }
+    }
+
+    /// Open the files `redirects` send output to: `>` empties the file, `>>`
+    /// creates it when missing. Err is what bash prints when one can't be opened
+    async fn open_redirects(
+        redirects: &[Redirect],
+        context: &CommandContext,
+    ) -> Result<(), String> {
+        for r in redirects {
+            let parser::RedirTarget::File(target) = &r.target else {
+                continue;
+            };
+            // `< file` is read, not written
+            if r.fd == 0 {
+                continue;
+            }
+            let path = absolute_path(target, &context.cwd);
+            let mut fs = context.filesystem.write().await;
+            if let Err(reason) = open_for_redirect(&mut fs, &path, r.append) {
+                return Err(format!("bash: {}: {}\r\n", target, reason));
+            }
         }
+        Ok(())
     }
 
-    /// Apply redirections to a command's stdout/stderr. File-target redirections
-    /// discard the stream (best-effort for the honeypot); `/dev/null` discards;
-    /// `N>&M` merges streams.
-    fn apply_redirects(stdout: &mut String, stderr: &mut String, redirects: &[Redirect]) {
+    /// Send a command's stdout/stderr where its redirections point, in order like
+    /// bash: `N>&M` points N where M points at that moment, `/dev/null` discards
+    /// and files opened by `open_redirects` get the output added. What still goes
+    /// to the terminal is left in `stdout`/`stderr`
+    async fn apply_redirects(
+        stdout: &mut String,
+        stderr: &mut String,
+        name: &str,
+        redirects: &[Redirect],
+        context: &CommandContext,
+    ) {
+        #[derive(Clone, Copy)]
+        enum Sink<'a> {
+            Stdout,
+            Stderr,
+            Null,
+            File(&'a str),
+        }
+
+        let (mut out_sink, mut err_sink) = (Sink::Stdout, Sink::Stderr);
         for r in redirects {
-            let devnull = matches!(&r.target, parser::RedirTarget::DevNull);
+            let sink = match &r.target {
+                parser::RedirTarget::DevNull => Sink::Null,
+                parser::RedirTarget::Fd(1) => out_sink,
+                parser::RedirTarget::Fd(2) => err_sink,
+                parser::RedirTarget::Fd(_) => continue,
+                parser::RedirTarget::File(target) => Sink::File(target),
+            };
             match r.fd {
-                1 if devnull => stdout.clear(),
-                2 if devnull => stderr.clear(),
-                1 if matches!(&r.target, parser::RedirTarget::Fd(2)) => {
-                    let s = std::mem::take(stdout);
-                    stderr.push_str(&s);
-                }
-                2 if matches!(&r.target, parser::RedirTarget::Fd(1)) => {
-                    let s = std::mem::take(stderr);
-                    stdout.push_str(&s);
-                }
-                1 if matches!(&r.target, parser::RedirTarget::File(_)) => stdout.clear(),
-                2 if matches!(&r.target, parser::RedirTarget::File(_)) => stderr.clear(),
+                1 => out_sink = sink,
+                2 => err_sink = sink,
                 _ => {}
             }
         }
+
+        let streams = [std::mem::take(stdout), std::mem::take(stderr)];
+        let mut written: Vec<(String, Arc<Vec<u8>>)> = Vec::new();
+        for (output, sink) in streams.into_iter().zip([out_sink, err_sink]) {
+            match sink {
+                Sink::Stdout => stdout.push_str(&output),
+                Sink::Stderr => stderr.push_str(&output),
+                Sink::Null => {}
+                Sink::File(_) if output.is_empty() => {}
+                Sink::File(target) => {
+                    let path = absolute_path(target, &context.cwd);
+                    let mut fs = context.filesystem.write().await;
+                    match append_redirected(&mut fs, &path, &output) {
+                        Ok(content) => {
+                            written.retain(|(p, _)| *p != path);
+                            written.push((path, content));
+                        }
+                        Err(err) => {
+                            log::debug!("Redirection could not write {}: {}", path, err);
+                            let prefix = if help_command::BUILTINS.contains(&name) {
+                                "bash: "
+                            } else {
+                                ""
+                            };
+                            let reason = match err.kind() {
+                                std::io::ErrorKind::StorageFull => "No space left on device",
+                                _ => "Input/output error",
+                            };
+                            stderr.push_str(&format!(
+                                "{}{}: write error: {}\r\n",
+                                prefix, name, reason
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        for (path, content) in written {
+            context.push_event(ShellEvent::FileWritten { path, content });
+        }
+    }
+
+    /// Run one registered command with `args` as given, without parsing a line
+    pub async fn execute_command(
+        &self,
+        command_name: &str,
+        args: &[String],
+        context: &mut CommandContext,
+    ) -> CommandResult {
+        self.registry
+            .execute_command(command_name, args, context)
+            .await
     }
 
     /// Check if a command exists in the registry
@@ -463,6 +902,144 @@ impl Default for CommandDispatcher {
     }
 }
 
+/// What bash prints when the program it ran died of SIGSEGV
+const CRASH_MESSAGE: &str = "Segmentation fault (core dumped)\r\n";
+
+/// What bash prints for a program killed with SIGKILL, as the OOM killer does
+const KILLED_MESSAGE: &str = "Killed\r\n";
+
+/// Poll `future` to completion, turning a panic into `Err` with its message
+async fn catch_panic<F: Future>(future: F) -> Result<F::Output, String> {
+    let mut future = Box::pin(future);
+    std::future::poll_fn(move |cx| {
+        match std::panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => Poll::Ready(Err(payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default())),
+        }
+    })
+    .await
+}
+
+/// Cut `output` to at most `max` bytes, after the last whole line that fits or
+/// mid-line when not even one does
+fn truncate_at_line(output: &mut String, max: usize) {
+    if output.len() <= max {
+        return;
+    }
+    let mut end = max;
+    while !output.is_char_boundary(end) {
+        end -= 1;
+    }
+    if let Some(newline) = output[..end].rfind('\n') {
+        end = newline + 1;
+    }
+    output.truncate(end);
+}
+
+/// Run `name`, found in the virtual filesystem at `path`, and record the attempt
+/// along with the file
+async fn execute_file(
+    name: &str,
+    path: &str,
+    args: &[String],
+    context: &CommandContext,
+) -> FileRun {
+    let (run, content) =
+        executable::run_file(&*context.filesystem.read().await, path, &context.os.machine);
+    log::info!(
+        "[{}] Ran {} from the filesystem: {}",
+        context.auth_id,
+        path,
+        run.outcome()
+    );
+    let command = std::iter::once(name)
+        .chain(args.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(" ");
+    context.push_event(ShellEvent::Execution {
+        path: path.to_string(),
+        command,
+        outcome: run.outcome(),
+        content,
+    });
+    run
+}
+
+/// Open `path` for a redirection: `>` empties the file, `>>` only creates it when
+/// missing. Err is the reason bash gives when it can't
+fn open_for_redirect(fs: &mut FileSystem, path: &str, append: bool) -> Result<(), &'static str> {
+    let mode = match fs.follow_symlink(path) {
+        Ok(entry) => match entry.file_content {
+            Some(FileContent::Directory(_)) => return Err("Is a directory"),
+            _ if append => return Ok(()),
+            _ => entry.inode.i_mode,
+        },
+        Err(_) => {
+            let parent = match path.rsplit_once('/') {
+                Some(("", _)) | None => "/",
+                Some((parent, _)) => parent,
+            };
+            match fs.follow_symlink(parent).map(|entry| entry.file_content) {
+                Ok(Some(FileContent::Directory(_))) => 0o644,
+                Ok(_) => return Err("Not a directory"),
+                Err(_) => return Err("No such file or directory"),
+            }
+        }
+    };
+    // Through a symlink to the file it points at
+    let path = fs
+        .resolve_symlinks(path)
+        .unwrap_or_else(|_| path.to_string());
+    write_file(fs, &path, Vec::new(), mode).map_err(|err| match err.kind() {
+        std::io::ErrorKind::StorageFull => "No space left on device",
+        _ => "No such file or directory",
+    })
+}
+
+/// Add `output` to the end of a file `open_for_redirect` opened, as the
+/// terminal-less program would have written it. Returns the whole file
+fn append_redirected(
+    fs: &mut FileSystem,
+    path: &str,
+    output: &str,
+) -> std::io::Result<Arc<Vec<u8>>> {
+    let path = fs
+        .resolve_symlinks(path)
+        .unwrap_or_else(|_| path.to_string());
+    let (mut content, mode) = match fs.get_file(&path) {
+        Ok(entry) => match entry.file_content {
+            Some(FileContent::RegularFile(content)) => (content.to_vec(), entry.inode.i_mode),
+            _ => (Vec::new(), entry.inode.i_mode),
+        },
+        Err(_) => (Vec::new(), 0o644),
+    };
+    content.extend_from_slice(output.replace("\r\n", "\n").as_bytes());
+    write_file(fs, &path, content.clone(), mode)?;
+    Ok(Arc::new(content))
+}
+
+/// What `~` expands to
+fn home_dir(context: &CommandContext) -> String {
+    context
+        .get_env("HOME")
+        .cloned()
+        .unwrap_or_else(|| format!("/home/{}", context.username))
+}
+
+/// Absolute form of `path` relative to `cwd`
+fn absolute_path(path: &str, cwd: &str) -> String {
+    if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("{}/{}", cwd.trim_end_matches('/'), path)
+    }
+}
+
 /// Find the end of a `$(...)` command substitution. `start` is the index just
 /// after `$(`. Returns `(body, end_index)` where `end_index` is the first char
 /// after the closing `)`.
@@ -556,9 +1133,18 @@ fn find_arith_end(chars: &[char], start: usize) -> (String, usize) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::commands::accounts::write_account_files;
     use crate::commands::{
-        CatCommand, ColonCommand, DateCommand, EchoCommand, ExitCommand, ExportCommand,
-        FalseCommand, LsCommand, TestCommand, TrueCommand, UnameCommand, UnsetCommand,
+        ArchCommand, AwkCommand, BUSYBOX_APPLETS, BgCommand, BinaryFiles, BusyboxCommand, Canaries,
+        CatCommand, CdCommand, ColonCommand, CompgenCommand, DateCommand, DdCommand, DfCommand,
+        DigCommand, DisownCommand, DnsAnswer, DnsRecord, DnsResolver, DuCommand, EchoCommand,
+        EnvCommand, ExitCommand, ExportCommand, FalseCommand, FgCommand, GetconfCommand,
+        GetentCommand, HelpCommand, HexdumpCommand, HistoryCommand, HostCommand, IdCommand,
+        IptablesCommand, JobsCommand, KillCommand, LastCommand, LastbCommand, LoginHistory,
+        LoginRecord, LsCommand, MountCommand, NohupCommand, NslookupCommand, OsIdentity,
+        PkillCommand, PowerCommand, PsCommand, SedCommand, SetsidCommand, SleepCommand,
+        TestCommand, TimeoutCommand, TrueCommand, UfwCommand, UnameCommand, UnsetCommand,
+        WatchCommand, write_mount_files,
     };
     use crate::filesystem::fs2::FileSystem;
     use std::sync::Arc;
@@ -592,6 +1178,36 @@ mod tests {
         )
     }
 
+    struct PanicCommand;
+
+    #[async_trait::async_trait]
+    impl crate::commands::Command for PanicCommand {
+        fn name(&self) -> &'static str {
+            "boom"
+        }
+
+        async fn execute(&self, args: &[String], _context: &mut CommandContext) -> CommandResult {
+            Ok(args[3].clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn panicking_command_only_ends_itself() {
+        let mut d = make_dispatcher();
+        d.registry_mut().register_command(Arc::new(PanicCommand));
+        let mut ctx = make_context();
+        let out = d.execute("boom; echo still here", &mut ctx).await;
+        assert!(
+            out.output.contains("Segmentation fault (core dumped)\r\n")
+                && out.output.contains("still here"),
+            "output was: {}",
+            out.output
+        );
+        assert!(!out.exit_requested);
+        let out = d.execute("boom || echo failed", &mut ctx).await;
+        assert!(out.output.contains("failed"), "output was: {}", out.output);
+    }
+
     #[tokio::test]
     async fn basic_echo() {
         let d = make_dispatcher();
@@ -612,6 +1228,1559 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn command_not_found_suggestions() {
+        let mut d = make_dispatcher();
+        let mut ctx = make_context();
+        let out = d.execute("nmap -sS 10.0.0.1", &mut ctx).await;
+        assert_eq!(out.output, "bash: nmap: command not found\r\n");
+
+        d.set_command_not_found_handler(Some(CommandNotFoundHandler::default()));
+        let out = d.execute("nmap -sS 10.0.0.1", &mut ctx).await;
+        assert!(
+            out.output
+                .contains("Command 'nmap' not found, but can be installed with:"),
+            "output was: {}",
+            out.output
+        );
+        assert!(out.output.contains("\r\napt install nmap\r\n"));
+
+        let out = d.execute("notarealtool", &mut ctx).await;
+        assert_eq!(out.output, "notarealtool: command not found\r\n");
+    }
+
+    #[tokio::test]
+    async fn power_commands_need_root() {
+        let mut d = make_dispatcher();
+        d.registry_mut()
+            .register_command(Arc::new(PowerCommand::Reboot));
+        d.registry_mut()
+            .register_command(Arc::new(PowerCommand::Shutdown));
+        let mut ctx = make_context();
+
+        ctx.username = "ubuntu".to_string();
+        let out = d.execute("reboot", &mut ctx).await;
+        assert!(
+            out.output
+                .contains("Failed to reboot system via logind: Access denied"),
+            "output was: {}",
+            out.output
+        );
+        assert!(matches!(
+            &ctx.take_events()[..],
+            [ShellEvent::Shutdown {
+                action: "reboot",
+                permitted: false,
+                ..
+            }]
+        ));
+
+        ctx.username = "root".to_string();
+        let out = d.execute("shutdown +5 maintenance", &mut ctx).await;
+        assert!(out.output.starts_with("Shutdown scheduled for "));
+        assert!(out.output.contains("\r\nmaintenance\r\n"));
+        let out = d.execute("shutdown -r now", &mut ctx).await;
+        assert!(
+            out.output
+                .contains("Broadcast message from root@host on pts/0")
+                && out
+                    .output
+                    .contains("The system is going down for reboot NOW!"),
+            "output was: {}",
+            out.output
+        );
+        assert!(matches!(
+            &ctx.take_events()[..],
+            [
+                ShellEvent::Shutdown {
+                    immediate: false,
+                    ..
+                },
+                ShellEvent::Shutdown {
+                    command,
+                    action: "reboot",
+                    permitted: true,
+                    immediate: true,
+                },
+            ] if command == "shutdown -r now"
+        ));
+    }
+
+    #[tokio::test]
+    async fn iptables_and_ufw_keep_session_state() {
+        let mut d = make_dispatcher();
+        d.registry_mut().register_command(Arc::new(IptablesCommand));
+        d.registry_mut().register_command(Arc::new(UfwCommand));
+        let mut ctx = make_context();
+
+        ctx.username = "ubuntu".to_string();
+        let out = d.execute("iptables -F", &mut ctx).await;
+        assert!(out.output.contains("Permission denied (you must be root)"));
+        let out = d.execute("ufw status", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "ERROR: You need to be root to run this script\r\n"
+        );
+        assert!(matches!(
+            &ctx.take_events()[..],
+            [ShellEvent::FirewallChange {
+                tool: "iptables",
+                permitted: false,
+                ..
+            }]
+        ));
+
+        ctx.username = "root".to_string();
+        let out = d
+            .execute("iptables -A INPUT -p tcp --dport 22 -j DROP", &mut ctx)
+            .await;
+        assert_eq!(out.output, "");
+        let out = d.execute("iptables -S INPUT", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "-P INPUT ACCEPT\r\n-A INPUT -p tcp -m tcp --dport 22 -j DROP\r\n"
+        );
+        let out = d.execute("iptables -nL INPUT", &mut ctx).await;
+        assert!(out.output.contains("DROP       tcp  --  0.0.0.0/0"));
+        assert!(out.output.contains("tcp dpt:22"));
+        let out = d.execute("iptables -D INPUT 2", &mut ctx).await;
+        assert_eq!(out.output, "iptables: Index of deletion too big.\r\n");
+        d.execute("iptables -F", &mut ctx).await;
+        let out = d.execute("iptables -S INPUT", &mut ctx).await;
+        assert_eq!(out.output, "-P INPUT ACCEPT\r\n");
+
+        let out = d.execute("ufw status", &mut ctx).await;
+        assert_eq!(out.output, "Status: inactive\r\n");
+        let out = d.execute("ufw allow 22/tcp", &mut ctx).await;
+        assert_eq!(out.output, "Rules updated\r\nRules updated (v6)\r\n");
+        d.execute("ufw --force enable", &mut ctx).await;
+        let out = d.execute("ufw status", &mut ctx).await;
+        assert!(out.output.starts_with("Status: active\r\n"));
+        assert!(
+            out.output
+                .contains("22/tcp (v6)                ALLOW       Anywhere (v6)")
+        );
+        d.execute("ufw disable", &mut ctx).await;
+        let out = d.execute("ufw status", &mut ctx).await;
+        assert_eq!(out.output, "Status: inactive\r\n");
+
+        let changes = ctx
+            .take_events()
+            .into_iter()
+            .filter(|event| {
+                matches!(
+                    event,
+                    ShellEvent::FirewallChange {
+                        permitted: true,
+                        ..
+                    }
+                )
+            })
+            .count();
+        assert_eq!(changes, 6);
+    }
+
+    #[tokio::test]
+    async fn output_past_the_limit_is_cut() {
+        let mut d = make_dispatcher();
+        d.set_max_output(16);
+        let mut ctx = make_context();
+
+        let out = d.execute("echo 0123456789abcdefghij", &mut ctx).await;
+        assert_eq!(out.output, "0123456789abcdefKilled\r\n");
+        let out = d.execute("echo short && echo done", &mut ctx).await;
+        assert_eq!(out.output, "short\r\ndone\r\n");
+
+        let mut text = "line one\r\nline two\r\n".to_string();
+        truncate_at_line(&mut text, 16);
+        assert_eq!(text, "line one\r\n");
+        let mut text = "ééé".to_string();
+        truncate_at_line(&mut text, 3);
+        assert_eq!(text, "é");
+    }
+
+    #[tokio::test]
+    async fn sleep_is_capped_and_timeout_ends_commands() {
+        let mut d = make_dispatcher();
+        d.registry_mut().register_command(Arc::new(SleepCommand));
+        d.registry_mut().register_command(Arc::new(TimeoutCommand));
+        let mut ctx = make_context();
+        ctx.max_sleep = std::time::Duration::from_millis(50);
+
+        let started = std::time::Instant::now();
+        let out = d.execute("sleep 5", &mut ctx).await;
+        assert_eq!(out.output, "");
+        assert!(started.elapsed() >= std::time::Duration::from_millis(50));
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        let out = d.execute("sleep abc", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "sleep: invalid time interval 'abc'\r\nTry 'sleep --help' for more information.\r\n"
+        );
+
+        ctx.max_sleep = std::time::Duration::from_secs(10);
+        let started = std::time::Instant::now();
+        let out = d
+            .execute("timeout -v 0.05 sleep 5 || echo expired", &mut ctx)
+            .await;
+        assert_eq!(
+            out.output,
+            "timeout: sending signal TERM to command 'sleep'\r\nexpired\r\n"
+        );
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        let out = d.execute("timeout 1 echo hi", &mut ctx).await;
+        assert_eq!(out.output, "hi\r\n");
+        let out = d.execute("timeout 5", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "timeout: missing operand after '5'\r\nTry 'timeout --help' for more information.\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn getent_and_id_agree_with_passwd() {
+        let mut d = make_dispatcher();
+        d.registry_mut().register_command(Arc::new(GetentCommand));
+        d.registry_mut().register_command(Arc::new(IdCommand));
+        let mut ctx = make_context();
+        write_account_files(
+            &mut *ctx.filesystem.write().await,
+            "host",
+            &["admin".to_string()],
+        )
+        .unwrap();
+
+        let out = d.execute("getent passwd admin 0", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "admin:x:1000:1000:admin,,,:/home/admin:/bin/bash\r\nroot:x:0:0:root:/root:/bin/bash\r\n"
+        );
+        let out = d.execute("id", &mut ctx).await;
+        assert_eq!(out.output, "uid=0(root) gid=0(root) groups=0(root)\r\n");
+        let out = d.execute("id -Gn admin", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "admin adm cdrom sudo dip plugdev lpadmin lxd sambashare\r\n"
+        );
+        let out = d.execute("getent group sudo", &mut ctx).await;
+        assert_eq!(out.output, "sudo:x:27:admin\r\n");
+        let out = d
+            .execute("getent passwd nobody-here || echo missing", &mut ctx)
+            .await;
+        assert_eq!(out.output, "missing\r\n");
+        let out = d.execute("id nobody-here", &mut ctx).await;
+        assert_eq!(out.output, "id: ‘nobody-here’: no such user\r\n");
+        let out = d.execute("getent hosts host", &mut ctx).await;
+        assert_eq!(out.output, "127.0.1.1       host\r\n");
+
+        // Any name logs in, it gets the next uid and the login user's groups
+        ctx.username = "oracle".to_string();
+        let out = d.execute("id", &mut ctx).await;
+        assert!(
+            out.output
+                .starts_with("uid=1001(oracle) gid=1001(oracle) groups=1001(oracle),4(adm)"),
+            "{}",
+            out.output
+        );
+        let out = d.execute("getent passwd", &mut ctx).await;
+        assert!(
+            out.output
+                .ends_with("oracle:x:1001:1001:oracle,,,:/home/oracle:/bin/bash\r\n")
+        );
+        let out = d.execute("getent services", &mut ctx).await;
+        assert_eq!(out.output, "");
+        let out = d.execute("getent users", &mut ctx).await;
+        assert!(out.output.starts_with("Unknown database: users\r\n"));
+    }
+
+    #[tokio::test]
+    async fn proc_agrees_with_the_process_table() {
+        let mut d = make_dispatcher();
+        d.registry_mut().register_command(Arc::new(PsCommand));
+        let mut ctx = make_context();
+
+        let out = d.execute("cat /proc/1/cmdline", &mut ctx).await;
+        assert_eq!(out.output, "/sbin/init\0splash\0");
+        let shell = ctx.processes.write().await.attach_session("root");
+
+        let out = d.execute("cat /proc/self/status", &mut ctx).await;
+        assert!(out.output.contains("Name:\tcat\r\n"), "{}", out.output);
+        assert!(out.output.contains(&format!("PPid:\t{}\r\n", shell)));
+        assert!(out.output.contains("Uid:\t0\t0\t0\t0\r\n"));
+        let cat = ctx.processes.read().await.find("cat", false, true).len();
+        assert_eq!(cat, 0, "cat left its process behind");
+
+        let out = d
+            .execute(&format!("cat /proc/{}/stat", shell), &mut ctx)
+            .await;
+        assert!(out.output.starts_with(&format!("{} (bash) S", shell)));
+        assert_eq!(out.output.split_whitespace().count(), 52);
+        let out = d.execute("ps -ef", &mut ctx).await;
+        let bash = out.output.lines().find(|l| l.contains("-bash")).unwrap();
+        let ppid = bash.split_whitespace().nth(2).unwrap();
+        let out = d
+            .execute(&format!("cat /proc/{}/status", shell), &mut ctx)
+            .await;
+        assert!(out.output.contains(&format!("PPid:\t{}\r\n", ppid)));
+
+        let out = d.execute("ls /proc", &mut ctx).await;
+        let names: Vec<&str> = out.output.split_whitespace().collect();
+        assert!(names.contains(&"1") && names.contains(&"self"));
+        let out = d.execute("ls -1 /proc/1", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "cmdline\r\ncomm\r\nenviron\r\nstat\r\nstatus\r\n"
+        );
+
+        let out = d.execute("cat /proc/99999/status", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "cat: /proc/99999/status: No such file or directory\r\n"
+        );
+        ctx.username = "ubuntu".to_string();
+        let out = d.execute("cat /proc/1/environ", &mut ctx).await;
+        assert_eq!(out.output, "cat: /proc/1/environ: Permission denied\r\n");
+    }
+
+    #[tokio::test]
+    async fn hex_dumps_show_the_stored_bytes() {
+        let mut d = make_dispatcher();
+        for command in [
+            HexdumpCommand::Xxd,
+            HexdumpCommand::Hexdump,
+            HexdumpCommand::Od,
+        ] {
+            d.registry_mut().register_command(Arc::new(command));
+        }
+        let mut ctx = make_context();
+        {
+            let mut fs = ctx.filesystem.write().await;
+            fs.create_directory("/tmp").unwrap();
+            let mut elf = b"\x7fELF\x02\x01\x01\0".to_vec();
+            elf.resize(48, 0);
+            elf.extend_from_slice(b"hi\n");
+            fs.create_file("/tmp/bot").unwrap().content =
+                Some(FileContent::RegularFile(Arc::new(elf)));
+        }
+
+        let out = d.execute("xxd -l 16 /tmp/bot", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "00000000: 7f45 4c46 0201 0100 0000 0000 0000 0000  .ELF............\r\n"
+        );
+        let out = d.execute("hexdump -C /tmp/bot", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "00000000  7f 45 4c 46 02 01 01 00  00 00 00 00 00 00 00 00  |.ELF............|\r\n\
+            00000010  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|\r\n\
+            *\r\n\
+            00000030  68 69 0a                                          |hi.|\r\n\
+            00000033\r\n"
+        );
+        let out = d.execute("hexdump -s 48 /tmp/bot", &mut ctx).await;
+        assert_eq!(out.output, "0000030 6968 000a\r\n0000033\r\n");
+        let out = d.execute("od -A x -t x1 -N 4 /tmp/bot", &mut ctx).await;
+        assert_eq!(out.output, "000000 7f 45 4c 46\r\n000004\r\n");
+        let out = d.execute("od -c -j 48 /tmp/bot", &mut ctx).await;
+        assert_eq!(out.output, "0000060   h   i  \\n\r\n0000063\r\n");
+
+        let out = d.execute("echo hi | xxd", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "00000000: 6869 0a                                  hi.\r\n"
+        );
+        let out = d.execute("echo hi | xxd -p", &mut ctx).await;
+        assert_eq!(out.output, "68690a\r\n");
+        let out = d.execute("xxd /tmp/nothing", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "xxd: /tmp/nothing: No such file or directory\r\n"
+        );
+        let out = d.execute("xxd /tmp/nothing || echo failed", &mut ctx).await;
+        assert!(out.output.ends_with("failed\r\n"));
+
+        // Option letters past ASCII are refused, not split mid-char
+        let out = d.execute("xxd -é /tmp/bot", &mut ctx).await;
+        assert!(out.output.starts_with("xxd: invalid option -- 'é'\r\n"));
+        let out = d.execute("od -€ /tmp/bot", &mut ctx).await;
+        assert!(out.output.starts_with("od: invalid option -- '€'\r\n"));
+        let out = d.execute("hexdump -é1 /tmp/bot", &mut ctx).await;
+        assert!(out.output.starts_with("hexdump: invalid option -- 'é'\r\n"));
+        let out = d.execute("xxd -l€ /tmp/bot", &mut ctx).await;
+        assert!(out.output.starts_with("xxd: "));
+    }
+
+    #[tokio::test]
+    async fn cat_keeps_binary_files_off_the_terminal() {
+        let d = make_dispatcher();
+        let mut ctx = make_context();
+        {
+            let mut fs = ctx.filesystem.write().await;
+            fs.create_directory("/tmp").unwrap();
+            let mut elf = b"\x7fELF\x02\x01\x01\0".to_vec();
+            elf.resize(64, 0);
+            elf.extend_from_slice(b"\x1b]0;owned\x07\x1b[2J\rbin\tsh\n");
+            fs.create_file("/tmp/bot").unwrap().content =
+                Some(FileContent::RegularFile(Arc::new(elf)));
+            fs.create_file("/tmp/motd").unwrap().content = Some(FileContent::RegularFile(
+                Arc::new(b"\x1b[1mWelcome\x1b[0m\n".to_vec()),
+            ));
+        }
+
+        let out = d.execute("cat /tmp/bot", &mut ctx).await;
+        assert!(!out.output.contains(['\x1b', '\x07']), "{:?}", out.output);
+        assert!(
+            out.output.ends_with("[2J\u{FFFD}bin\tsh\r\n"),
+            "{:?}",
+            out.output
+        );
+        let out = d.execute("cat /tmp/motd", &mut ctx).await;
+        assert_eq!(out.output, "\x1b[1mWelcome\x1b[0m\r\n");
+
+        ctx.binary_files = BinaryFiles::Described;
+        let out = d.execute("cat /tmp/bot", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "cat: /tmp/bot: application/x-executable (binary file)\r\n"
+        );
+        let out = d.execute("cat /proc/1/cmdline", &mut ctx).await;
+        assert_eq!(out.output, "/sbin/init\0splash\0");
+    }
+
+    #[tokio::test]
+    async fn busybox_applets() {
+        let mut d = make_dispatcher();
+        d.registry_mut().register_command(Arc::new(BusyboxCommand));
+        d.registry_mut().retain_commands(BUSYBOX_APPLETS);
+        let mut ctx = make_context();
+
+        let out = d.execute("/bin/busybox echo hi", &mut ctx).await;
+        assert!(out.output.contains("hi"), "output was: {}", out.output);
+
+        let out = d.execute("/bin/busybox ECCHI", &mut ctx).await;
+        assert_eq!(out.output, "ECCHI: applet not found\r\n");
+
+        let out = d.execute("lsb_release -a", &mut ctx).await;
+        assert_eq!(out.output, "bash: lsb_release: command not found\r\n");
+    }
+
+    #[tokio::test]
+    async fn dd_writes_into_virtual_filesystem() {
+        let mut d = make_dispatcher();
+        d.registry_mut().register_command(Arc::new(DdCommand));
+        let mut ctx = make_context();
+
+        let out = d
+            .execute("dd if=/dev/zero of=/test bs=1M count=3", &mut ctx)
+            .await;
+        assert!(
+            out.output.starts_with(
+                "3+0 records in\r\n3+0 records out\r\n3145728 bytes (3.1 MB, 3.0 MiB) copied, "
+            ),
+            "output was: {}",
+            out.output
+        );
+        let fs = ctx.filesystem.read().await;
+        assert_eq!(
+            fs.get_file("/test").unwrap().inode.i_size_lo,
+            3 * 1024 * 1024
+        );
+        drop(fs);
+
+        let out = d
+            .execute(
+                "dd if=/test bs=1000 count=2 skip=1 status=noxfer of=/dev/null",
+                &mut ctx,
+            )
+            .await;
+        assert_eq!(out.output, "2+0 records in\r\n2+0 records out\r\n");
+
+        let out = d.execute("dd if=/nope of=/dev/null", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "dd: failed to open '/nope': No such file or directory\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn nohup_and_setsid_run_wrapped_command() {
+        let mut d = make_dispatcher();
+        d.registry_mut().register_command(Arc::new(NohupCommand));
+        d.registry_mut().register_command(Arc::new(SetsidCommand));
+        let mut ctx = make_context();
+
+        let out = d.execute("nohup echo started", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "nohup: ignoring input and appending output to 'nohup.out'\r\n"
+        );
+        let fs = ctx.filesystem.read().await;
+        assert_eq!(fs.get_file("/nohup.out").unwrap().inode.i_size_lo, 8);
+        drop(fs);
+
+        let out = d.execute("setsid -f echo started", &mut ctx).await;
+        assert_eq!(out.output, "started\r\n");
+
+        let out = d.execute("nohup ./miner", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "nohup: failed to run command './miner': No such file or directory\r\n"
+        );
+        let out = d.execute("nohup", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "nohup: missing operand\r\nTry 'nohup --help' for more information.\r\n"
+        );
+
+        let processes = ctx.processes.read().await;
+        let commands: Vec<&str> = processes
+            .processes()
+            .iter()
+            .map(|p| p.command.as_str())
+            .filter(|c| c.starts_with("echo"))
+            .collect();
+        assert_eq!(commands, ["echo started", "echo started"]);
+    }
+
+    #[tokio::test]
+    async fn watch_runs_command_once_under_header() {
+        let mut d = make_dispatcher();
+        d.registry_mut().register_command(Arc::new(WatchCommand));
+        let mut ctx = make_context();
+
+        let out = d.execute("watch -n1 'echo a; echo b'", &mut ctx).await;
+        let (header, body) = out.output.split_once("\r\n\r\n").unwrap();
+        assert!(header.starts_with("Every 1.0s: echo a; echo b  "));
+        assert!(header.contains(&format!("{}: ", ctx.hostname)));
+        assert_eq!(header.chars().count(), ctx.terminal_width);
+        assert_eq!(body, "a\r\nb\r\n");
+
+        let out = d.execute("watch -t -n 0.5 uname", &mut ctx).await;
+        assert_eq!(out.output, "Linux\r\n");
+
+        let out = d.execute("watch -n abc uname", &mut ctx).await;
+        assert_eq!(out.output, "watch: failed to parse argument: 'abc'\r\n");
+        let out = d.execute("watch", &mut ctx).await;
+        assert!(
+            out.output
+                .starts_with("\r\nUsage:\r\n watch [options] command")
+        );
+    }
+
+    #[tokio::test]
+    async fn reading_canaries_is_noted() {
+        let d = make_dispatcher();
+        let mut ctx = make_context();
+        let canaries = Canaries::new(["/root/.aws/credentials".to_string()]);
+        canaries.plant(&mut *ctx.filesystem.write().await).unwrap();
+        ctx.canaries = Arc::new(canaries);
+
+        let out = d.execute("ls /root; echo done", &mut ctx).await;
+        assert!(out.output.ends_with("done\r\n"));
+        assert!(ctx.take_events().is_empty());
+
+        d.execute("cat /root/.aws/credentials", &mut ctx).await;
+        d.execute("head -n 1 /root/.aws/../.aws/credentials | wc -l", &mut ctx)
+            .await;
+        let read = ShellEvent::CanaryRead {
+            path: "/root/.aws/credentials".to_string(),
+        };
+        assert_eq!(ctx.take_events(), [read.clone(), read]);
+        assert!(ctx.take_events().is_empty());
+    }
+
+    /// Answers every A query with one address, everything else with NODATA
+    struct OneAddress;
+
+    #[async_trait::async_trait]
+    impl DnsResolver for OneAddress {
+        async fn resolve(&self, _name: &str, record_type: &str) -> DnsAnswer {
+            match record_type {
+                "A" => DnsAnswer::Records(vec![DnsRecord {
+                    ttl: 300,
+                    data: "203.0.113.7".to_string(),
+                }]),
+                _ => DnsAnswer::Records(Vec::new()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn dns_lookups_are_answered_and_recorded() {
+        let mut d = make_dispatcher();
+        d.registry_mut().register_command(Arc::new(DigCommand));
+        d.registry_mut().register_command(Arc::new(HostCommand));
+        d.registry_mut().register_command(Arc::new(NslookupCommand));
+        let mut ctx = make_context();
+
+        let out = d.execute("dig C2.Example.net", &mut ctx).await;
+        assert!(
+            out.output.contains("status: NXDOMAIN")
+                && out.output.contains(";C2.Example.net.\t\tIN\tA\r\n")
+                && out.output.contains(";; SERVER: 127.0.0.53#53(127.0.0.53)"),
+            "output was: {}",
+            out.output
+        );
+        let out = d
+            .execute("host c2.example.net || echo failed", &mut ctx)
+            .await;
+        assert_eq!(
+            out.output,
+            "Host c2.example.net not found: 3(NXDOMAIN)\r\nfailed\r\n"
+        );
+        let events = ctx.take_events();
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            events[0],
+            ShellEvent::DnsQuery {
+                name: "c2.example.net".to_string(),
+                record_type: "A".to_string(),
+                command: "dig".to_string(),
+                status: "NXDOMAIN",
+                answers: Vec::new(),
+            }
+        );
+
+        ctx.dns = Arc::new(OneAddress);
+        let out = d.execute("dig +short pool.example.org", &mut ctx).await;
+        assert_eq!(out.output, "203.0.113.7\r\n");
+        let out = d.execute("host pool.example.org", &mut ctx).await;
+        assert_eq!(out.output, "pool.example.org has address 203.0.113.7\r\n");
+        let out = d.execute("nslookup pool.example.org", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "Server:\t\t127.0.0.53\r\nAddress:\t127.0.0.53#53\r\n\r\n\
+             Non-authoritative answer:\r\n\
+             Name:\tpool.example.org\r\nAddress: 203.0.113.7\r\n\r\n"
+        );
+        assert_eq!(ctx.take_events().len(), 6);
+    }
+
+    /// One open session and one failed guess
+    struct TwoLogins;
+
+    #[async_trait::async_trait]
+    impl LoginHistory for TwoLogins {
+        async fn logins(&self, _limit: usize) -> Vec<LoginRecord> {
+            vec![LoginRecord {
+                username: "administrator".to_string(),
+                host: "198.51.100.23".to_string(),
+                start: chrono::Utc::now(),
+                end: None,
+            }]
+        }
+
+        async fn failed_logins(&self, _limit: usize) -> Vec<LoginRecord> {
+            let at = chrono::Utc::now() - chrono::Duration::minutes(5);
+            vec![LoginRecord {
+                username: "oracle".to_string(),
+                host: "203.0.113.99".to_string(),
+                start: at,
+                end: Some(at),
+            }]
+        }
+    }
+
+    #[tokio::test]
+    async fn last_and_lastb_show_the_login_history() {
+        let mut d = make_dispatcher();
+        d.registry_mut().register_command(Arc::new(LastCommand));
+        d.registry_mut().register_command(Arc::new(LastbCommand));
+        let mut ctx = make_context();
+
+        let out = d.execute("last", &mut ctx).await;
+        assert!(out.output.starts_with("\r\nwtmp begins "), "{}", out.output);
+
+        ctx.logins = Arc::new(TwoLogins);
+        let out = d.execute("last", &mut ctx).await;
+        assert!(
+            out.output
+                .starts_with("administ pts/0        198.51.100.23    ")
+                && out
+                    .output
+                    .contains("   still logged in\r\n\r\nwtmp begins "),
+            "{}",
+            out.output
+        );
+        let out = d.execute("lastb -n 10", &mut ctx).await;
+        assert!(
+            out.output
+                .starts_with("oracle   ssh:notty    203.0.113.99     ")
+                && out.output.contains("  (00:00)\r\n\r\nbtmp begins "),
+            "{}",
+            out.output
+        );
+        let out = d.execute("lastb nobody", &mut ctx).await;
+        assert!(out.output.starts_with("\r\nbtmp begins "), "{}", out.output);
+
+        ctx.username = "ubuntu".to_string();
+        let out = d.execute("lastb", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "lastb: cannot open /var/log/btmp: Permission denied\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn full_disk_fails_writes_and_shows_in_df() {
+        let mut d = make_dispatcher();
+        d.registry_mut().register_command(Arc::new(DdCommand));
+        d.registry_mut().register_command(Arc::new(DfCommand));
+        let mut ctx = make_context();
+        ctx.filesystem.write().await.set_disk_size(Some(64 * 1024));
+
+        let out = d
+            .execute("dd if=/dev/zero of=/fill bs=1K count=100", &mut ctx)
+            .await;
+        assert!(
+            out.output.starts_with(
+                "dd: error writing '/fill': No space left on device\r\n60+0 records in\r\n"
+            ),
+            "output was: {}",
+            out.output
+        );
+        let fs = ctx.filesystem.read().await;
+        assert_eq!(fs.get_file("/fill").unwrap().inode.i_size_lo, 60 * 1024);
+        drop(fs);
+
+        let out = d.execute("df -h /", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "Filesystem      Size  Used Avail Use% Mounted on\r\n\
+             /dev/sda1        64K   64K     0 100% /\r\n"
+        );
+        {
+            let mut fs = ctx.filesystem.write().await;
+            fs.create_directory("/dev").unwrap();
+            fs.create_directory("/dev/shm").unwrap();
+        }
+        let out = d.execute("df -T /dev/shm", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "Filesystem     Type  1K-blocks  Used Available Use% Mounted on\r\n\
+             tmpfs          tmpfs   2015412     0   2015412   0% /dev/shm\r\n"
+        );
+        let out = d.execute("df /nope", &mut ctx).await;
+        assert_eq!(out.output, "df: '/nope': No such file or directory\r\n");
+    }
+
+    #[tokio::test]
+    async fn du_agrees_with_ls() {
+        let mut d = make_dispatcher();
+        d.registry_mut().register_command(Arc::new(DuCommand));
+        let mut ctx = make_context();
+        {
+            let mut fs = ctx.filesystem.write().await;
+            for dir in ["/home", "/home/user", "/home/user/sub"] {
+                fs.create_directory(dir).unwrap();
+            }
+            write_file(&mut fs, "/home/user/big", vec![b'x'; 5000], 0o644).unwrap();
+            write_file(
+                &mut fs,
+                "/home/user/sub/small",
+                b"0123456789".to_vec(),
+                0o644,
+            )
+            .unwrap();
+        }
+
+        let out = d.execute("du -sh /home/user", &mut ctx).await;
+        assert_eq!(out.output, "20K\t/home/user\r\n");
+
+        let out = d.execute("du --max-depth=1 /home/user", &mut ctx).await;
+        assert_eq!(out.output, "8\t/home/user/sub\r\n20\t/home/user\r\n");
+
+        ctx.set_cwd("/home/user".to_string());
+        let out = d.execute("du -ab sub; ls -l", &mut ctx).await;
+        assert!(
+            out.output
+                .starts_with("10\tsub/small\r\n4106\tsub\r\ntotal 2\r\n"),
+            "output was: {}",
+            out.output
+        );
+        assert!(
+            out.output.contains("    5000 "),
+            "output was: {}",
+            out.output
+        );
+
+        let out = d.execute("du -s missing", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "du: cannot access 'missing': No such file or directory\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn running_files_is_recorded() {
+        let d = make_dispatcher();
+        let mut ctx = make_context();
+        {
+            let mut fs = ctx.filesystem.write().await;
+            fs.create_directory("/tmp").unwrap();
+            write_file(&mut fs, "/tmp/bot", b"#!/bin/sh\n".to_vec(), 0o755).unwrap();
+            write_file(&mut fs, "/tmp/notes", b"todo\n".to_vec(), 0o644).unwrap();
+        }
+        ctx.set_cwd("/tmp".to_string());
+
+        let out = d.execute("./bot -o pool", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "bash: ./bot: /bin/sh: bad interpreter: No such file or directory\r\n"
+        );
+        match &ctx.take_events()[..] {
+            [
+                ShellEvent::Execution {
+                    path,
+                    command,
+                    outcome,
+                    content,
+                },
+            ] => {
+                assert_eq!(path, "/tmp/bot");
+                assert_eq!(command, "./bot -o pool");
+                assert_eq!(*outcome, "bad interpreter");
+                assert_eq!(
+                    content.as_deref().map(Vec::as_slice),
+                    Some(&b"#!/bin/sh\n"[..])
+                );
+            }
+            other => panic!("expected one execution, got {:?}", other),
+        }
+
+        let out = d.execute("./notes; /tmp/missing; bot", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "bash: ./notes: Permission denied\r\n\
+             bash: /tmp/missing: No such file or directory\r\n\
+             bash: bot: command not found\r\n"
+        );
+        assert_eq!(ctx.take_events().len(), 2);
+
+        {
+            let mut fs = ctx.filesystem.write().await;
+            fs.create_directory("/bin").unwrap();
+            write_file(&mut fs, "/bin/sh", Vec::new(), 0o755).unwrap();
+        }
+        ctx.set_env("PATH".to_string(), "/usr/bin:/tmp".to_string());
+        let out = d.execute("bot && echo ran", &mut ctx).await;
+        assert_eq!(out.output, "ran\r\n");
+    }
+
+    #[tokio::test]
+    async fn job_control_follows_background_jobs() {
+        let mut d = make_dispatcher();
+        d.registry_mut().register_command(Arc::new(JobsCommand));
+        d.registry_mut().register_command(Arc::new(FgCommand));
+        d.registry_mut().register_command(Arc::new(BgCommand));
+        d.registry_mut().register_command(Arc::new(DisownCommand));
+        d.registry_mut().register_command(Arc::new(KillCommand));
+        let mut ctx = make_context();
+
+        let (miner, sleeper) = {
+            let mut table = ctx.processes.write().await;
+            let miner = table.spawn("root", "nohup ./miner");
+            table.add_job(miner, "nohup ./miner");
+            let sleeper = table.spawn("root", "sleep 600");
+            table.add_job(sleeper, "sleep 600");
+            (miner, sleeper)
+        };
+
+        let out = d.execute("jobs", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "[1]-  Running                 nohup ./miner &\r\n\
+             [2]+  Running                 sleep 600 &\r\n"
+        );
+        let out = d.execute("jobs -p %1", &mut ctx).await;
+        assert_eq!(out.output, format!("{}\r\n", miner));
+        let out = d.execute("bg", &mut ctx).await;
+        assert_eq!(out.output, "bash: bg: job 2 already in background\r\n");
+
+        let out = d.execute("kill %2; fg %2", &mut ctx).await;
+        assert_eq!(out.output, "bash: fg: %2: no such job\r\n");
+        assert!(ctx.processes.read().await.get(sleeper).is_none());
+
+        let out = d.execute("disown; jobs", &mut ctx).await;
+        assert_eq!(out.output, "");
+        assert!(ctx.processes.read().await.get(miner).is_some());
+        let out = d.execute("fg", &mut ctx).await;
+        assert_eq!(out.output, "bash: fg: current: no such job\r\n");
+    }
+
+    #[tokio::test]
+    async fn kill_and_pkill_are_recorded() {
+        let mut d = make_dispatcher();
+        d.registry_mut().register_command(Arc::new(KillCommand));
+        d.registry_mut().register_command(Arc::new(PkillCommand));
+        let mut ctx = make_context();
+        let (miner, rival) = {
+            let mut table = ctx.processes.write().await;
+            (
+                table.spawn("ubuntu", "./xmrig -o pool.example:3333"),
+                table.spawn("root", "/tmp/.x/xmrig --donate-level 1"),
+            )
+        };
+
+        let out = d.execute("kill 99999", &mut ctx).await;
+        assert_eq!(out.output, "bash: kill: (99999) - No such process\r\n");
+        assert_eq!(
+            ctx.take_events(),
+            [ShellEvent::ProcessKill {
+                tool: "kill",
+                command: "kill 99999".to_string(),
+                signal: "TERM".to_string(),
+                pids: vec![],
+                names: vec![],
+                permitted: true,
+            }]
+        );
+
+        // init and kernel threads shrug off even root's SIGKILL
+        let out = d.execute("kill -9 1 2", &mut ctx).await;
+        assert_eq!(out.output, "");
+        let table = ctx.processes.read().await;
+        assert!(table.get(1).is_some() && table.get(2).is_some());
+        drop(table);
+        assert!(matches!(
+            &ctx.take_events()[..],
+            [ShellEvent::ProcessKill { signal, pids, permitted: true, .. }]
+                if signal == "KILL" && pids == &[1, 2]
+        ));
+
+        ctx.username = "ubuntu".to_string();
+        let out = d.execute("kill -s KILL 1", &mut ctx).await;
+        assert_eq!(out.output, "bash: kill: (1) - Operation not permitted\r\n");
+        assert!(matches!(
+            &ctx.take_events()[..],
+            [ShellEvent::ProcessKill {
+                permitted: false,
+                ..
+            }]
+        ));
+        let out = d.execute("pkill xmrig", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            format!(
+                "pkill: killing pid {} failed: Operation not permitted\r\n",
+                rival
+            )
+        );
+        assert!(ctx.processes.read().await.get(miner).is_none());
+        assert!(ctx.processes.read().await.get(rival).is_some());
+        assert_eq!(
+            ctx.take_events(),
+            [ShellEvent::ProcessKill {
+                tool: "pkill",
+                command: "pkill xmrig".to_string(),
+                signal: "TERM".to_string(),
+                pids: vec![miner, rival],
+                names: vec!["xmrig".to_string(), "xmrig".to_string()],
+                permitted: false,
+            }]
+        );
+
+        ctx.username = "root".to_string();
+        let out = d.execute("pkill -9 -e xmrig", &mut ctx).await;
+        assert_eq!(out.output, format!("xmrig killed (pid {})\r\n", rival));
+        let out = d.execute("pkill xmrig || echo gone", &mut ctx).await;
+        assert_eq!(out.output, "gone\r\n");
+        assert!(matches!(
+            &ctx.take_events()[..],
+            [
+                ShellEvent::ProcessKill { pids: first, .. },
+                ShellEvent::ProcessKill { pids: second, .. },
+            ] if first == &[rival] && second.is_empty()
+        ));
+    }
+
+    #[tokio::test]
+    async fn mount_agrees_with_proc_mounts_and_fstab() {
+        let mut d = make_dispatcher();
+        d.registry_mut().register_command(Arc::new(MountCommand));
+        let mut ctx = make_context();
+        write_mount_files(
+            &mut *ctx.filesystem.write().await,
+            &ctx.os,
+            "0b0c7a6e-5a3c-4f8e-9d55-4e2f3c1d7b21",
+        )
+        .unwrap();
+
+        let out = d.execute("mount -t ext4", &mut ctx).await;
+        assert_eq!(out.output, "/dev/sda1 on / type ext4 (rw,relatime)\r\n");
+        let out = d.execute("cat /etc/mtab", &mut ctx).await;
+        assert!(
+            out.output
+                .contains("\r\n/dev/sda1 / ext4 rw,relatime 0 0\r\n")
+                && !out.output.contains("overlay"),
+            "output was: {}",
+            out.output
+        );
+        let out = d.execute("cat /etc/fstab", &mut ctx).await;
+        assert!(
+            out.output
+                .contains("# / was on /dev/sda1 during installation\r\n")
+                && out
+                    .output
+                    .contains("UUID=0b0c7a6e-5a3c-4f8e-9d55-4e2f3c1d7b21 /"),
+            "output was: {}",
+            out.output
+        );
+
+        ctx.username = "user".to_string();
+        let out = d.execute("mount /dev/sdb1 /mnt", &mut ctx).await;
+        assert_eq!(out.output, "mount: only root can do that\r\n");
+    }
+
+    #[tokio::test]
+    async fn architecture_probes_agree() {
+        let mut d = make_dispatcher();
+        d.registry_mut().register_command(Arc::new(ArchCommand));
+        d.registry_mut().register_command(Arc::new(GetconfCommand));
+        let mut ctx = make_context();
+        ctx.os
+            .write_release_files(&mut *ctx.filesystem.write().await)
+            .unwrap();
+
+        let out = d
+            .execute(
+                "uname -m; arch; getconf LONG_BIT; getconf PAGE_SIZE",
+                &mut ctx,
+            )
+            .await;
+        assert_eq!(out.output, "x86_64\r\nx86_64\r\n64\r\n4096\r\n");
+        let out = d.execute("cat /proc/version", &mut ctx).await;
+        assert!(
+            out.output
+                .starts_with("Linux version 5.4.0-109-generic (buildd@"),
+            "output was: {}",
+            out.output
+        );
+
+        ctx.os = OsIdentity::embedded();
+        let out = d.execute("arch; getconf LONG_BIT", &mut ctx).await;
+        assert_eq!(out.output, "mips\r\n32\r\n");
+        let out = d.execute("getconf NOPE", &mut ctx).await;
+        assert_eq!(out.output, "getconf: Unrecognized variable 'NOPE'\r\n");
+    }
+
+    #[tokio::test]
+    async fn bash_history_reads_like_a_real_one() {
+        use crate::commands::bash_history::{BashHistory, write_bash_history};
+
+        let d = make_dispatcher();
+        let mut ctx = make_context();
+        let history = BashHistory::new(None).with_typed_commands(false, false);
+        let ip = "192.0.2.1".parse().unwrap();
+        {
+            let mut fs = ctx.filesystem.write().await;
+            write_bash_history(&mut fs, "/root", &history.for_user("root")).unwrap();
+            write_bash_history(&mut fs, "/home/ubuntu", &history.for_user("ubuntu")).unwrap();
+            // An image's own history is kept
+            write_bash_history(&mut fs, "/root", "ls\n").unwrap();
+            history.session_started(&mut fs, ip, "root").unwrap();
+            for command in ["uname -a", "uname -a", " history -c", "cat /etc/passwd"] {
+                history.command_typed(&mut fs, ip, "root", command).unwrap();
+            }
+        }
+        let out = d.execute("cat /root/.bash_history", &mut ctx).await;
+        assert!(
+            out.output.starts_with("apt update\r\napt upgrade -y\r\n"),
+            "{}",
+            out.output
+        );
+        assert!(out.output.contains("sudo -u postgres psql"));
+        assert!(
+            out.output
+                .ends_with("exit\r\nuname -a\r\ncat /etc/passwd\r\n"),
+            "{}",
+            out.output
+        );
+        let out = d.execute("cat /home/ubuntu/.bash_history", &mut ctx).await;
+        assert!(out.output.starts_with("sudo apt update\r\n"));
+    }
+
+    #[tokio::test]
+    async fn typed_history_goes_to_the_home_in_passwd() {
+        use crate::commands::bash_history::BashHistory;
+        use crate::commands::busybox::write_file;
+
+        let d = make_dispatcher();
+        let mut ctx = make_context();
+        let history = BashHistory::new(Some("ls\n".to_string())).with_typed_commands(false, false);
+        let ip = "192.0.2.1".parse().unwrap();
+        {
+            let mut fs = ctx.filesystem.write().await;
+            let passwd = "root:x:0:0:root:/root:/bin/bash\n\
+                deploy:x:1001:1001:,,,:/srv/deploy:/bin/bash\n";
+            for dir in ["/etc", "/root", "/srv", "/srv/deploy"] {
+                fs.create_directory(dir).unwrap();
+            }
+            write_file(&mut fs, "/etc/passwd", passwd.into(), 0o644).unwrap();
+            history.command_typed(&mut fs, ip, "deploy", "id").unwrap();
+            history.command_typed(&mut fs, ip, "root", "w").unwrap();
+        }
+        let out = d.execute("cat /srv/deploy/.bash_history", &mut ctx).await;
+        assert_eq!(out.output, "id\r\n");
+        let out = d.execute("cat /root/.bash_history", &mut ctx).await;
+        assert_eq!(out.output, "w\r\n");
+    }
+
+    #[tokio::test]
+    async fn typed_history_per_address_shows_after_logout() {
+        use crate::commands::bash_history::{BashHistory, write_bash_history};
+
+        let d = make_dispatcher();
+        let mut ctx = make_context();
+        let history = BashHistory::new(Some("ls\n".to_string())).with_typed_commands(true, true);
+        let (first, second) = ("192.0.2.1".parse().unwrap(), "192.0.2.2".parse().unwrap());
+        {
+            let mut fs = ctx.filesystem.write().await;
+            write_bash_history(&mut fs, "/root", &history.for_user("root")).unwrap();
+            history.session_started(&mut fs, first, "root").unwrap();
+            history.command_typed(&mut fs, first, "root", "id").unwrap();
+        }
+        // bash writes the history when the session ends
+        let out = d.execute("cat /root/.bash_history", &mut ctx).await;
+        assert_eq!(out.output, "ls\r\n");
+
+        history
+            .session_started(&mut *ctx.filesystem.write().await, second, "root")
+            .unwrap();
+        let out = d.execute("cat /root/.bash_history", &mut ctx).await;
+        assert_eq!(out.output, "ls\r\n");
+
+        {
+            let mut fs = ctx.filesystem.write().await;
+            for _ in 0..2100 {
+                history.command_typed(&mut fs, first, "root", "w").unwrap();
+                history.command_typed(&mut fs, first, "root", "id").unwrap();
+            }
+            history.session_started(&mut fs, first, "root").unwrap();
+        }
+        let out = d.execute("cat /root/.bash_history", &mut ctx).await;
+        assert!(
+            out.output.starts_with("w\r\nid\r\n"),
+            "{}",
+            &out.output[..20]
+        );
+        assert_eq!(out.output.lines().count(), 2000);
+    }
+
+    #[tokio::test]
+    async fn compgen_lists_registered_commands() {
+        let mut d = make_dispatcher();
+        d.registry_mut().register_command(Arc::new(CompgenCommand));
+        d.registry_mut().register_command(Arc::new(HelpCommand));
+        let mut ctx = make_context();
+        let out = d.execute("compgen -c", &mut ctx).await;
+        let words: Vec<&str> = out.output.split("\r\n").collect();
+        for name in ["if", "cd", "ls", "grep", "compgen"] {
+            assert!(words.contains(&name), "missing {}: {}", name, out.output);
+        }
+        let out = d.execute("compgen -b -- ec", &mut ctx).await;
+        assert_eq!(out.output, "echo\r\n");
+        let out = d.execute("compgen -z", &mut ctx).await;
+        assert!(
+            out.output
+                .starts_with("bash: compgen: -z: invalid option\r\n")
+        );
+        let out = d.execute("help | head -1", &mut ctx).await;
+        assert!(
+            out.output
+                .starts_with("GNU bash, version 5.0.17(1)-release")
+        );
+        let out = d.execute("help cd", &mut ctx).await;
+        assert_eq!(out.output, "cd: cd [-L|[-P [-e]] [-@]] [dir]\r\n");
+    }
+
+    #[tokio::test]
+    async fn cd_returns_to_oldpwd_and_home() {
+        let mut d = make_dispatcher();
+        d.registry_mut()
+            .register_stateful_command(Arc::new(CdCommand));
+        let mut ctx = make_context();
+        {
+            let mut fs = ctx.filesystem.write().await;
+            fs.create_directory("/root").unwrap();
+            fs.create_directory("/tmp").unwrap();
+        }
+
+        let out = d.execute("cd -", &mut ctx).await;
+        assert_eq!(out.output, "bash: cd: OLDPWD not set\r\n");
+        d.execute("cd /tmp", &mut ctx).await;
+        let out = d.execute("cd -", &mut ctx).await;
+        assert_eq!(out.output, "/\r\n");
+        assert_eq!(ctx.cwd, "/");
+        d.execute("cd -", &mut ctx).await;
+        assert_eq!(ctx.cwd, "/tmp");
+
+        ctx.set_env("HOME".to_string(), "/root".to_string());
+        d.execute("cd", &mut ctx).await;
+        assert_eq!(ctx.cwd, "/root");
+        assert_eq!(ctx.get_env("OLDPWD").map(String::as_str), Some("/tmp"));
+    }
+
+    #[tokio::test]
+    async fn prompt_follows_cd() {
+        let mut d = make_dispatcher();
+        d.registry_mut()
+            .register_stateful_command(Arc::new(CdCommand));
+        let mut ctx = make_context();
+        ctx.username = "user".to_string();
+        ctx.set_env("HOME".to_string(), "/home/user".to_string());
+        {
+            let mut fs = ctx.filesystem.write().await;
+            for dir in [
+                "/home",
+                "/home/user",
+                "/home/user/sub",
+                "/home/username",
+                "/var",
+            ] {
+                fs.create_directory(dir).unwrap();
+            }
+        }
+
+        d.execute("cd /home/user", &mut ctx).await;
+        assert_eq!(ctx.get_prompt(), "user@host:~$ ");
+        d.execute("cd sub", &mut ctx).await;
+        assert_eq!(ctx.get_prompt(), "user@host:~/sub$ ");
+        d.execute("cd /home/username", &mut ctx).await;
+        assert_eq!(ctx.get_prompt(), "user@host:/home/username$ ");
+        d.execute("cd /var", &mut ctx).await;
+        assert_eq!(ctx.get_prompt(), "user@host:/var$ ");
+    }
+
+    #[tokio::test]
+    async fn root_prompt_ends_with_hash() {
+        let mut d = make_dispatcher();
+        d.registry_mut()
+            .register_stateful_command(Arc::new(CdCommand));
+        let mut ctx = make_context();
+        {
+            let mut fs = ctx.filesystem.write().await;
+            for dir in ["/root", "/root/.ssh", "/home", "/home/root", "/srv"] {
+                fs.create_directory(dir).unwrap();
+            }
+        }
+
+        d.execute("cd", &mut ctx).await;
+        assert_eq!(ctx.get_prompt(), "root@host:~# ");
+        d.execute("cd .ssh", &mut ctx).await;
+        assert_eq!(ctx.get_prompt(), "root@host:~/.ssh# ");
+        d.execute("cd /home/root", &mut ctx).await;
+        assert_eq!(ctx.get_prompt(), "root@host:/home/root# ");
+        // The home is whatever HOME says
+        d.execute("export HOME=/srv; cd", &mut ctx).await;
+        assert_eq!(ctx.get_prompt(), "root@host:~# ");
+    }
+
+    #[tokio::test]
+    async fn redirection_writes_files() {
+        let d = make_dispatcher();
+        let mut ctx = make_context();
+        ctx.filesystem
+            .write()
+            .await
+            .create_directory("/tmp")
+            .unwrap();
+
+        let out = d.execute("echo 'evil' > /tmp/x.sh", &mut ctx).await;
+        assert_eq!(out.output, "");
+        d.execute("echo more >> /tmp/x.sh", &mut ctx).await;
+        let out = d.execute("cat /tmp/x.sh", &mut ctx).await;
+        assert_eq!(out.output, "evil\r\nmore\r\n");
+        let events = ctx.take_events();
+        assert!(
+            matches!(
+                events.as_slice(),
+                [
+                    ShellEvent::FileWritten { path, .. },
+                    ShellEvent::FileWritten { content, .. },
+                ] if path == "/tmp/x.sh" && content.as_slice() == b"evil\nmore\n"
+            ),
+            "events were: {:?}",
+            events
+        );
+
+        // Truncated before cat reads it, like bash
+        d.execute("cat /tmp/x.sh > /tmp/x.sh", &mut ctx).await;
+        let out = d.execute("cat /tmp/x.sh", &mut ctx).await;
+        assert_eq!(out.output, "");
+
+        let out = d.execute("cat /nope 2>&1 > /tmp/err", &mut ctx).await;
+        assert_eq!(out.output, "cat: /nope: No such file or directory\r\n");
+        let out = d.execute("cat /nope > /tmp/err 2>&1", &mut ctx).await;
+        assert_eq!(out.output, "");
+        let out = d.execute("cat /tmp/err", &mut ctx).await;
+        assert_eq!(out.output, "cat: /nope: No such file or directory\r\n");
+
+        let out = d.execute("echo hi > /nope/x", &mut ctx).await;
+        assert_eq!(out.output, "bash: /nope/x: No such file or directory\r\n");
+        let out = d.execute("echo hi > /tmp", &mut ctx).await;
+        assert_eq!(out.output, "bash: /tmp: Is a directory\r\n");
+
+        let mut ctx = make_context();
+        {
+            let mut fs = ctx.filesystem.write().await;
+            fs.create_directory("/tmp").unwrap();
+            fs.set_disk_size(Some(64 * 1024));
+            let free = fs.disk_usage().available();
+            write_file(&mut fs, "/tmp/fill", vec![b'x'; free as usize], 0o644).unwrap();
+        }
+        let out = d.execute("echo hi >> /tmp/fill", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "bash: echo: write error: No space left on device\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn chain_runs_in_order_and_keeps_cd() {
+        let mut d = make_dispatcher();
+        d.registry_mut().register_command(Arc::new(CdCommand));
+        let mut ctx = make_context();
+        ctx.filesystem
+            .write()
+            .await
+            .create_directory("/tmp")
+            .unwrap();
+
+        let out = d
+            .execute(
+                "cd /tmp && echo a > f && cat f; cat nope; false && echo no || echo yes",
+                &mut ctx,
+            )
+            .await;
+        assert_eq!(
+            out.output,
+            "a\r\ncat: nope: No such file or directory\r\nyes\r\n"
+        );
+        assert_eq!(ctx.cwd, "/tmp");
+
+        let out = d
+            .execute("cd /nope || echo failed; grep a f", &mut ctx)
+            .await;
+        assert_eq!(
+            out.output,
+            "bash: cd: /nope: No such file or directory\r\nfailed\r\na\r\n"
+        );
+        let out = d.execute("cd /tmp/f && echo no", &mut ctx).await;
+        assert_eq!(out.output, "bash: cd: /tmp/f: Not a directory\r\n");
+    }
+
+    #[tokio::test]
+    async fn variables_are_expanded_as_commands_run() {
+        let mut d = make_dispatcher();
+        d.registry_mut().register_command(Arc::new(CdCommand));
+        d.registry_mut().register_command(Arc::new(EnvCommand));
+        let mut ctx = make_context();
+        ctx.filesystem
+            .write()
+            .await
+            .create_directory("/tmp")
+            .unwrap();
+
+        let out = d
+            .execute("export URL=http://x/a; echo $URL ${URL}b", &mut ctx)
+            .await;
+        assert_eq!(out.output, "http://x/a http://x/ab\r\n");
+        let out = d
+            .execute("cd /tmp && echo $PWD '$PWD' \"$NOPE\"x", &mut ctx)
+            .await;
+        assert_eq!(out.output, "/tmp $PWD x\r\n");
+        let out = d.execute("for i in a b; do echo $i; done", &mut ctx).await;
+        assert_eq!(out.output, "a\r\nb\r\n");
+
+        let out = d.execute("env", &mut ctx).await;
+        assert!(
+            out.output.contains("\r\nURL=http://x/a\r\n")
+                && out.output.contains("\r\nPWD=/tmp\r\n")
+                && out.output.starts_with("HOME=/root\r\n"),
+            "output was: {}",
+            out.output
+        );
+        let out = d.execute("env X=1 env | grep X=", &mut ctx).await;
+        assert_eq!(out.output, "X=1\r\n");
+    }
+
+    #[tokio::test]
+    async fn history_lists_typed_lines() {
+        let mut d = make_dispatcher();
+        d.registry_mut().register_command(Arc::new(HistoryCommand));
+        let mut ctx = make_context();
+        ctx.history = ["ls -la", "cat /etc/passwd", "uname -a", "history"]
+            .map(String::from)
+            .into();
+
+        let out = d.execute("history", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "    1  ls -la\r\n    2  cat /etc/passwd\r\n    3  uname -a\r\n    4  history\r\n"
+        );
+        let out = d.execute("history 2", &mut ctx).await;
+        assert_eq!(out.output, "    3  uname -a\r\n    4  history\r\n");
+        let out = d.execute("history x || echo failed", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "bash: history: x: numeric argument required\r\nfailed\r\n"
+        );
+
+        let out = d.execute("history -d 2 && history 1", &mut ctx).await;
+        assert_eq!(out.output, "    3  history\r\n");
+        let out = d.execute("history -c && history -w", &mut ctx).await;
+        assert_eq!(out.output, "");
+        assert!(ctx.history.is_empty());
+    }
+
+    #[tokio::test]
+    async fn sed_edits_files_and_pipes() {
+        let mut d = make_dispatcher();
+        d.registry_mut().register_command(Arc::new(SedCommand));
+        let mut ctx = make_context();
+        {
+            let mut fs = ctx.filesystem.write().await;
+            fs.create_directory("/etc").unwrap();
+            let config = "Port 22\nPermitRootLogin no\n#PasswordAuthentication yes\nUsePAM yes\n";
+            write_file(&mut fs, "/etc/sshd_config", config.into(), 0o644).unwrap();
+        }
+
+        let out = d.execute("sed -n '1,2p' /etc/sshd_config", &mut ctx).await;
+        assert_eq!(out.output, "Port 22\r\nPermitRootLogin no\r\n");
+        let out = d
+            .execute("cat /etc/sshd_config | sed 's/no/yes/;/^#/d'", &mut ctx)
+            .await;
+        assert_eq!(
+            out.output,
+            "Port 22\r\nPermitRootLogin yes\r\nUsePAM yes\r\n"
+        );
+        let out = d
+            .execute(
+                r"sed 's/\(Port\) 22/\1 2222/g;2,/PAM/d' /etc/sshd_config",
+                &mut ctx,
+            )
+            .await;
+        assert_eq!(out.output, "Port 2222\r\n");
+        let out = d
+            .execute(
+                r"cat /etc/sshd_config | sed -E 's/^(\w+) (.*)/\2 \1/' | head -1",
+                &mut ctx,
+            )
+            .await;
+        assert_eq!(out.output, "22 Port\r\n");
+
+        d.execute("sed -i 's/^Port .*/Port 2022/' /etc/sshd_config", &mut ctx)
+            .await;
+        let out = d.execute("sed 1q /etc/sshd_config", &mut ctx).await;
+        assert_eq!(out.output, "Port 2022\r\n");
+        let out = d.execute("sed x /etc/sshd_config", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "sed: -e expression #1, char 1: unknown command: `x'\r\n"
+        );
+        let out = d
+            .execute(
+                "sed 's/a/b/99999999999999999999' /etc/sshd_config",
+                &mut ctx,
+            )
+            .await;
+        assert_eq!(
+            out.output,
+            "sed: -e expression #1, char 26: invalid number option to `s' command\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn awk_splits_fields() {
+        let mut d = make_dispatcher();
+        d.registry_mut().register_command(Arc::new(AwkCommand));
+        let mut ctx = make_context();
+        {
+            let mut fs = ctx.filesystem.write().await;
+            fs.create_directory("/etc").unwrap();
+            let passwd = "root:x:0:0:root:/root:/bin/bash\n\
+                daemon:x:1:1:daemon:/usr/sbin:/usr/sbin/nologin\n\
+                ubuntu:x:1000:1000:Ubuntu:/home/ubuntu:/bin/bash\n";
+            write_file(&mut fs, "/etc/passwd", passwd.into(), 0o644).unwrap();
+        }
+
+        let out = d
+            .execute("awk -F: '$3 >= 1000 {print $1}' /etc/passwd", &mut ctx)
+            .await;
+        assert_eq!(out.output, "ubuntu\r\n");
+        let out = d
+            .execute(
+                "cat /etc/passwd | awk -F: '{print $1, NF}' | head -1",
+                &mut ctx,
+            )
+            .await;
+        assert_eq!(out.output, "root 7\r\n");
+        let out = d
+            .execute(
+                "awk -F: 'NR==2' /etc/passwd | awk -F: '/nologin/'",
+                &mut ctx,
+            )
+            .await;
+        assert_eq!(
+            out.output,
+            "daemon:x:1:1:daemon:/usr/sbin:/usr/sbin/nologin\r\n"
+        );
+        let out = d
+            .execute(
+                r#"awk -F: '{ s += $3 } END { printf "%d users, %.1f\n", NR, s / NR }' /etc/passwd"#,
+                &mut ctx,
+            )
+            .await;
+        assert_eq!(out.output, "3 users, 333.7\r\n");
+        let out = d.execute("awk '{print $1'", &mut ctx).await;
+        assert_eq!(
+            out.output,
+            "awk: line 1: syntax error at or near end of file\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn awk_scripts_cant_exhaust_the_honeypot() {
+        let mut d = make_dispatcher();
+        d.registry_mut().register_command(Arc::new(AwkCommand));
+        let mut ctx = make_context();
+
+        for script in [
+            r#"awk 'BEGIN{printf "%*d", 1e18, 1}'"#,
+            r#"awk 'BEGIN{printf "%*d", -9223372036854775808, 1}'"#,
+            r#"awk 'BEGIN{printf "%.*f", 1e18, 1}'"#,
+            r#"awk 'BEGIN{s = sprintf("%.*d", 1e18, 1); print length(s)}'"#,
+        ] {
+            let out = d.execute(script, &mut ctx).await;
+            assert!(
+                out.output.len() <= 8192 + 4,
+                "{}: {} bytes",
+                script,
+                out.output.len()
+            );
+        }
+
+        let too_many = "awk: program limit exceeded: maximum number of fields size=32767\r\n";
+        let out = d.execute("awk 'BEGIN{$1e9=1; print}'", &mut ctx).await;
+        assert_eq!(out.output, too_many);
+        let out = d.execute("awk 'BEGIN{NF=1e9; print}'", &mut ctx).await;
+        assert_eq!(out.output, too_many);
+        let out = d
+            .execute("awk 'BEGIN{$0=\"a b\"; NF=1; $3=\"c\"; print}'", &mut ctx)
+            .await;
+        assert_eq!(out.output, "a  c\r\n");
+
+        // Doubling a string runs out of memory, growing it slowly out of time
+        for script in [
+            r#"awk 'BEGIN{s="x"; while(1) s=s s}'"#,
+            r#"awk 'BEGIN{while(1) s = s "xxxxxxxxxx"}'"#,
+        ] {
+            let out = d.execute(script, &mut ctx).await;
+            assert_eq!(out.output, "awk: out of memory\r\n", "{}", script);
+        }
+    }
+
     #[tokio::test]
     async fn arithmetic_in_echo() {
         let d = make_dispatcher();
@@ -0,0 +1,132 @@
+use super::context::{CommandContext, ShellEvent};
+use async_trait::async_trait;
+
+/// Record types dig, host and nslookup accept
+pub const RECORD_TYPES: &[&str] = &[
+    "A", "AAAA", "ANY", "CAA", "CNAME", "MX", "NS", "PTR", "SOA", "SRV", "TXT",
+];
+
+/// One record of an answer, `data` formatted the way dig prints it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsRecord {
+    pub ttl: u32,
+    pub data: String,
+}
+
+/// What the resolver made of a query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DnsAnswer {
+    /// NOERROR, empty when the name exists without records of the type
+    Records(Vec<DnsRecord>),
+    NxDomain,
+    ServFail,
+}
+
+impl DnsAnswer {
+    /// Response code as dig prints it
+    pub fn status(&self) -> &'static str {
+        match self {
+            DnsAnswer::Records(_) => "NOERROR",
+            DnsAnswer::NxDomain => "NXDOMAIN",
+            DnsAnswer::ServFail => "SERVFAIL",
+        }
+    }
+
+    /// Numeric response code, host prints it next to the name
+    pub fn rcode(&self) -> u8 {
+        match self {
+            DnsAnswer::Records(_) => 0,
+            DnsAnswer::NxDomain => 3,
+            DnsAnswer::ServFail => 2,
+        }
+    }
+
+    pub fn records(&self) -> &[DnsRecord] {
+        match self {
+            DnsAnswer::Records(records) => records,
+            _ => &[],
+        }
+    }
+}
+
+/// Answers the lookups of dig, host and nslookup
+#[async_trait]
+pub trait DnsResolver: Send + Sync {
+    /// Resolve `name` (lowercase, without the trailing dot, `.` for the root) for
+    /// `record_type`, one of [`RECORD_TYPES`]
+    async fn resolve(&self, name: &str, record_type: &str) -> DnsAnswer;
+}
+
+/// Resolver that never reaches the network and gives every name the same answer.
+/// The default, a honeypot shouldn't look up attacker domains unless told to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FakeDns {
+    #[default]
+    NxDomain,
+    ServFail,
+}
+
+#[async_trait]
+impl DnsResolver for FakeDns {
+    async fn resolve(&self, _name: &str, _record_type: &str) -> DnsAnswer {
+        match self {
+            FakeDns::NxDomain => DnsAnswer::NxDomain,
+            FakeDns::ServFail => DnsAnswer::ServFail,
+        }
+    }
+}
+
+/// Resolve `name` for `command` and remember the query for the server, the
+/// domains attackers look up are usually their C2 and download hosts
+pub async fn lookup(
+    context: &CommandContext,
+    command: &str,
+    name: &str,
+    record_type: &str,
+) -> DnsAnswer {
+    let name = match name.trim_end_matches('.') {
+        "" => ".".to_string(),
+        name => name.to_ascii_lowercase(),
+    };
+    let answer = context.dns.resolve(&name, record_type).await;
+    log::info!(
+        "[{}] {} looked up {} {}: {}",
+        context.auth_id,
+        command,
+        name,
+        record_type,
+        answer.status()
+    );
+    context.push_event(ShellEvent::DnsQuery {
+        name,
+        record_type: record_type.to_string(),
+        command: command.to_string(),
+        status: answer.status(),
+        answers: answer
+            .records()
+            .iter()
+            .map(|record| record.data.clone())
+            .collect(),
+    });
+    answer
+}
+
+/// `in-addr.arpa`/`ip6.arpa` name of `addr` for PTR lookups, None if it isn't an address
+pub fn reverse_name(addr: &str) -> Option<String> {
+    match addr.parse::<std::net::IpAddr>().ok()? {
+        std::net::IpAddr::V4(ip) => {
+            let [a, b, c, d] = ip.octets();
+            Some(format!("{}.{}.{}.{}.in-addr.arpa", d, c, b, a))
+        }
+        std::net::IpAddr::V6(ip) => {
+            let nibbles = ip
+                .octets()
+                .iter()
+                .rev()
+                .flat_map(|byte| [byte & 0xf, byte >> 4])
+                .map(|nibble| format!("{:x}", nibble))
+                .collect::<Vec<_>>();
+            Some(format!("{}.ip6.arpa", nibbles.join(".")))
+        }
+    }
+}
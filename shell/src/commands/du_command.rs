@@ -0,0 +1,310 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use crate::filesystem::fs2::{FileContent, FileSystem};
+use async_trait::async_trait;
+use std::collections::HashSet;
+
+/// Size ls -l shows for a directory, also what one occupies on disk
+const DIRECTORY_SIZE: u64 = 4096;
+/// Files take whole filesystem blocks
+const BLOCK_SIZE: u64 = 4096;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Units {
+    /// Multiples of the given number of bytes, rounded up (-k, -m, -b)
+    Blocks(u64),
+    /// -h
+    Human,
+}
+
+struct Options {
+    all: bool,
+    total: bool,
+    /// Byte counts as ls -l shows them instead of the blocks they take up
+    apparent: bool,
+    units: Units,
+    max_depth: Option<usize>,
+    paths: Vec<String>,
+}
+
+fn invalid_option(option: &str) -> CommandError {
+    CommandError::InvalidArguments(format!(
+        "du: {}\r\nTry 'du --help' for more information.",
+        option
+    ))
+}
+
+fn parse_depth(value: &str) -> Result<usize, CommandError> {
+    value.parse().map_err(|_| {
+        CommandError::InvalidArguments(format!("du: invalid maximum depth '{}'", value))
+    })
+}
+
+fn parse_args(args: &[String]) -> Result<Options, CommandError> {
+    let mut options = Options {
+        all: false,
+        total: false,
+        apparent: false,
+        units: Units::Blocks(1024),
+        max_depth: None,
+        paths: Vec::new(),
+    };
+    let mut summarize = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--all" => options.all = true,
+            "--total" => options.total = true,
+            "--summarize" => summarize = true,
+            "--human-readable" => options.units = Units::Human,
+            "--apparent-size" => options.apparent = true,
+            "--bytes" => {
+                options.apparent = true;
+                options.units = Units::Blocks(1);
+            }
+            "--max-depth" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| invalid_option("option '--max-depth' requires an argument"))?;
+                options.max_depth = Some(parse_depth(value)?);
+            }
+            arg if arg.starts_with("--max-depth=") => {
+                options.max_depth = Some(parse_depth(&arg["--max-depth=".len()..])?);
+            }
+            arg if arg.starts_with("--") => {
+                return Err(invalid_option(&format!("unrecognized option '{}'", arg)));
+            }
+            arg if arg.len() > 1 && arg.starts_with('-') => {
+                let flags = &arg[1..];
+                for (i, flag) in flags.char_indices() {
+                    match flag {
+                        'a' => options.all = true,
+                        'c' => options.total = true,
+                        's' => summarize = true,
+                        'h' => options.units = Units::Human,
+                        'k' => options.units = Units::Blocks(1024),
+                        'm' => options.units = Units::Blocks(1024 * 1024),
+                        'b' => {
+                            options.apparent = true;
+                            options.units = Units::Blocks(1);
+                        }
+                        // Links, filesystems and -0 don't change anything here
+                        'x' | 'L' | 'P' | 'D' | 'H' | 'l' | '0' => {}
+                        'd' => {
+                            let value = match &flags[i + 1..] {
+                                "" => args.next().ok_or_else(|| {
+                                    invalid_option("option requires an argument -- 'd'")
+                                })?,
+                                rest => rest,
+                            };
+                            options.max_depth = Some(parse_depth(value)?);
+                            break;
+                        }
+                        other => {
+                            return Err(invalid_option(&format!("invalid option -- '{}'", other)));
+                        }
+                    }
+                }
+            }
+            path => options.paths.push(path.to_string()),
+        }
+    }
+
+    if summarize {
+        if options.all {
+            return Err(invalid_option("cannot both summarize and show all entries"));
+        }
+        if options.max_depth.is_some_and(|depth| depth > 0) {
+            return Err(invalid_option(&format!(
+                "warning: summarizing conflicts with --max-depth={}",
+                options.max_depth.unwrap_or_default()
+            )));
+        }
+        options.max_depth = Some(0);
+    }
+    if options.paths.is_empty() {
+        options.paths.push(".".to_string());
+    }
+    Ok(options)
+}
+
+/// Rounded up to one decimal below 10 and to whole numbers above, like du -h
+pub(super) fn human(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["K", "M", "G", "T", "P"];
+    if bytes < 1024 {
+        return bytes.to_string();
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    let tenths = (value * 10.0).ceil() / 10.0;
+    if tenths < 10.0 {
+        format!("{:.1}{}", tenths, UNITS[unit])
+    } else if value.ceil() >= 1024.0 && unit < UNITS.len() - 1 {
+        format!("1.0{}", UNITS[unit + 1])
+    } else {
+        format!("{}{}", value.ceil(), UNITS[unit])
+    }
+}
+
+/// Walks a tree, printing entries and adding up what they use
+struct Walk<'a> {
+    fs: &'a FileSystem,
+    options: &'a Options,
+    /// Hard linked files are counted once
+    seen: HashSet<u64>,
+    output: String,
+}
+
+impl Walk<'_> {
+    fn usage(&self, content: Option<&FileContent>) -> u64 {
+        let bytes = match content {
+            Some(FileContent::Directory(_)) => DIRECTORY_SIZE,
+            Some(FileContent::RegularFile(data)) => data.len() as u64,
+            Some(FileContent::SymbolicLink(_)) | None => 0,
+        };
+        if self.options.apparent {
+            bytes
+        } else {
+            bytes.div_ceil(BLOCK_SIZE) * BLOCK_SIZE
+        }
+    }
+
+    fn print(&mut self, bytes: u64, name: &str) {
+        let size = match self.options.units {
+            Units::Human => human(bytes),
+            Units::Blocks(size) => bytes.div_ceil(size).to_string(),
+        };
+        self.output.push_str(&format!("{}\t{}\r\n", size, name));
+    }
+
+    /// Bytes used below and including `path`, `name` is how it's printed
+    fn visit(&mut self, path: &str, name: &str, depth: usize) -> u64 {
+        let Ok(entry) = self.fs.get_file(path) else {
+            return 0;
+        };
+        let shown = self.options.max_depth.is_none_or(|max| depth <= max);
+        let mut total = self.usage(entry.file_content.as_ref());
+        match &entry.file_content {
+            Some(FileContent::Directory(children)) => {
+                for child in children {
+                    if !self.seen.insert(child.inode_number) {
+                        continue;
+                    }
+                    total += self.visit(
+                        &format!("{}/{}", path.trim_end_matches('/'), child.name),
+                        &format!("{}/{}", name.trim_end_matches('/'), child.name),
+                        depth + 1,
+                    );
+                }
+                if shown {
+                    self.print(total, name);
+                }
+            }
+            // Files are only listed on their own with -a or when named
+            _ if shown && (self.options.all || depth == 0) => self.print(total, name),
+            _ => {}
+        }
+        total
+    }
+}
+
+/// du command, disk usage over the fake filesystem. Sizes agree with ls -l: a
+/// file takes its length rounded up to 4 KiB blocks, a directory one block
+pub struct DuCommand;
+
+#[async_trait]
+impl Command for DuCommand {
+    fn name(&self) -> &'static str {
+        "du"
+    }
+
+    fn help(&self) -> String {
+        "Usage: du [OPTION]... [FILE]...\r\n\
+        \x20 or:  du [OPTION]... --files0-from=F\r\n\
+        Summarize disk usage of the set of FILEs, recursively for directories.\r\n\
+        \r\n\
+        Mandatory arguments to long options are mandatory for short options too.\r\n\
+        \x20 -0, --null            end each output line with NUL, not newline\r\n\
+        \x20 -a, --all             write counts for all files, not just directories\r\n\
+        \x20     --apparent-size   print apparent sizes, rather than disk usage\r\n\
+        \x20 -b, --bytes           equivalent to '--apparent-size --block-size=1'\r\n\
+        \x20 -c, --total           produce a grand total\r\n\
+        \x20 -d, --max-depth=N     print the total for a directory (or file, with --all)\r\n\
+        \x20                         only if it is N or fewer levels below the command\r\n\
+        \x20                         line argument;  --max-depth=0 is the same as\r\n\
+        \x20                         --summarize\r\n\
+        \x20 -h, --human-readable  print sizes in human readable format (e.g., 1K 234M 2G)\r\n\
+        \x20 -k                    like --block-size=1K\r\n\
+        \x20 -m                    like --block-size=1M\r\n\
+        \x20 -s, --summarize       display only a total for each argument\r\n\
+        \x20 -x, --one-file-system    skip directories on different file systems\r\n\
+        \x20     --help     display this help and exit\r\n\
+        \x20     --version  output version information and exit\r\n\
+        \r\n\
+        GNU coreutils online help: <https://www.gnu.org/software/coreutils/>\r\n\
+        Full documentation <https://www.gnu.org/software/coreutils/du>\r\n\
+        or available locally via: info '(coreutils) du invocation'\r\n"
+            .to_string()
+    }
+
+    fn version(&self) -> String {
+        "du (GNU coreutils) 8.32\r\n\
+        Copyright (C) 2020 Free Software Foundation, Inc.\r\n\
+        License GPLv3+: GNU GPL version 3 or later <https://gnu.org/licenses/gpl.html>.\r\n\
+        This is free software: you are free to change and redistribute it.\r\n\
+        There is NO WARRANTY, to the extent permitted by law.\r\n\
+        \r\n\
+        Written by Torbjorn Granlund, David MacKenzie, Paul Eggert,\r\n\
+        and Jim Meyering.\r\n"
+            .to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        if args.iter().any(|a| a == "--help") {
+            return Ok(self.help());
+        }
+        if args.iter().any(|a| a == "--version") {
+            return Ok(self.version());
+        }
+        let options = parse_args(args)?;
+
+        let fs = context.filesystem.read().await;
+        let mut walk = Walk {
+            fs: &fs,
+            options: &options,
+            seen: HashSet::new(),
+            output: String::new(),
+        };
+        let mut errors = Vec::new();
+        let mut total = 0;
+        for path in &options.paths {
+            let absolute = if path.starts_with('/') {
+                path.clone()
+            } else {
+                format!("{}/{}", context.cwd.trim_end_matches('/'), path)
+            };
+            if fs.get_file(&absolute).is_err() {
+                errors.push(format!(
+                    "du: cannot access '{}': No such file or directory",
+                    path
+                ));
+                continue;
+            }
+            total += walk.visit(&absolute, path, 0);
+        }
+        if options.total {
+            walk.print(total, "total");
+        }
+
+        // Like cat, sizes that could be computed win over the errors
+        if walk.output.is_empty() && !errors.is_empty() {
+            Err(CommandError::FilesystemError(errors.join("\r\n")))
+        } else {
+            Ok(walk.output)
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use crate::filesystem::fs2::{FileContent, FileSystem};
+use std::sync::Arc;
+
+/// What bash does when told to run a file from the virtual filesystem. Nothing
+/// really runs: binaries and scripts that could run finish silently
+#[derive(Debug, PartialEq)]
+pub(crate) enum FileRun {
+    NotFound,
+    IsDirectory,
+    /// No execute bit
+    PermissionDenied,
+    /// ELF binary for another architecture
+    ExecFormatError,
+    /// The `#!` interpreter doesn't exist
+    BadInterpreter(String),
+    Executed,
+}
+
+impl FileRun {
+    /// Recorded with the execution attempt
+    pub(crate) fn outcome(&self) -> &'static str {
+        match self {
+            FileRun::NotFound => "not found",
+            FileRun::IsDirectory => "is a directory",
+            FileRun::PermissionDenied => "permission denied",
+            FileRun::ExecFormatError => "exec format error",
+            FileRun::BadInterpreter(_) => "bad interpreter",
+            FileRun::Executed => "executed",
+        }
+    }
+
+    /// bash's complaint about `name`, None if the file ran
+    pub(crate) fn bash_error(&self, name: &str) -> Option<String> {
+        Some(match self {
+            FileRun::NotFound => format!("bash: {}: No such file or directory", name),
+            FileRun::IsDirectory => format!("bash: {}: Is a directory", name),
+            FileRun::PermissionDenied => format!("bash: {}: Permission denied", name),
+            FileRun::ExecFormatError => {
+                format!(
+                    "bash: {}: cannot execute binary file: Exec format error",
+                    name
+                )
+            }
+            FileRun::BadInterpreter(interpreter) => format!(
+                "bash: {}: {}: bad interpreter: No such file or directory",
+                name, interpreter
+            ),
+            FileRun::Executed => return None,
+        })
+    }
+
+    /// strerror of the failed execve, as nohup and setsid report it
+    pub(crate) fn exec_error(&self) -> Option<&'static str> {
+        match self {
+            FileRun::NotFound | FileRun::BadInterpreter(_) => Some("No such file or directory"),
+            FileRun::IsDirectory | FileRun::PermissionDenied => Some("Permission denied"),
+            FileRun::ExecFormatError => Some("Exec format error"),
+            FileRun::Executed => None,
+        }
+    }
+}
+
+/// Absolute path bash would run for `name`: relative to `cwd` when it contains a
+/// slash, otherwise the first regular file of that name in a `PATH` directory.
+/// None for bare names that aren't on `PATH`
+pub(crate) fn locate(fs: &FileSystem, name: &str, cwd: &str, path_env: &str) -> Option<String> {
+    if name.contains('/') {
+        let path = if name.starts_with('/') {
+            name.to_string()
+        } else {
+            format!("{}/{}", cwd.trim_end_matches('/'), name)
+        };
+        return Some(fs.resolve_absolute_path(&path));
+    }
+    path_env
+        .split(':')
+        .filter(|dir| dir.starts_with('/'))
+        .map(|dir| format!("{}/{}", dir.trim_end_matches('/'), name))
+        .find(|path| {
+            matches!(
+                fs.follow_symlink(path).map(|entry| entry.file_content),
+                Ok(Some(FileContent::RegularFile(_)))
+            )
+        })
+}
+
+/// Run the file at `path` on a `machine` (uname -m) system. Returns the file's
+/// content when there is one, for recording
+pub(crate) fn run_file(
+    fs: &FileSystem,
+    path: &str,
+    machine: &str,
+) -> (FileRun, Option<Arc<Vec<u8>>>) {
+    let Ok(entry) = fs.follow_symlink(path) else {
+        return (FileRun::NotFound, None);
+    };
+    let content = match entry.file_content {
+        Some(FileContent::RegularFile(content)) => content,
+        Some(FileContent::Directory(_)) => return (FileRun::IsDirectory, None),
+        _ => return (FileRun::NotFound, None),
+    };
+    if entry.inode.i_mode & 0o111 == 0 {
+        return (FileRun::PermissionDenied, Some(content));
+    }
+
+    if content.starts_with(b"\x7fELF") && !elf_runs_on(&content, machine) {
+        return (FileRun::ExecFormatError, Some(content));
+    }
+    if let Some(interpreter) = shebang_interpreter(&content)
+        && fs.follow_symlink(&interpreter).is_err()
+    {
+        return (FileRun::BadInterpreter(interpreter), Some(content));
+    }
+    (FileRun::Executed, Some(content))
+}
+
+/// Whether an ELF file's `e_machine` fits the fake CPU. Unreadable headers are let
+/// through, the kernel would refuse them but the point is to look like it ran
+fn elf_runs_on(elf: &[u8], machine: &str) -> bool {
+    let (Some(&data), Some(raw)) = (elf.get(5), elf.get(18..20)) else {
+        return true;
+    };
+    let e_machine = match data {
+        2 => u16::from_be_bytes([raw[0], raw[1]]),
+        _ => u16::from_le_bytes([raw[0], raw[1]]),
+    };
+    let accepted: &[u16] = match machine {
+        // i386 binaries run on amd64 kernels
+        "x86_64" => &[0x3e, 0x03],
+        "i386" | "i686" => &[0x03],
+        "aarch64" => &[0xb7, 0x28],
+        "armv7l" | "armv6l" | "arm" => &[0x28],
+        "mips" | "mipsel" => &[0x08],
+        _ => return true,
+    };
+    accepted.contains(&e_machine)
+}
+
+/// Interpreter named on a `#!` line
+fn shebang_interpreter(content: &[u8]) -> Option<String> {
+    let line = content.strip_prefix(b"#!")?;
+    let line = &line[..line.iter().position(|&b| b == b'\n').unwrap_or(line.len())];
+    String::from_utf8_lossy(line)
+        .split_whitespace()
+        .next()
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::busybox::write_file;
+
+    fn elf(e_machine: u16) -> Vec<u8> {
+        let mut elf = b"\x7fELF\x02\x01\x01".to_vec();
+        elf.resize(18, 0);
+        elf.extend_from_slice(&e_machine.to_le_bytes());
+        elf
+    }
+
+    #[test]
+    fn runs_files_like_bash() {
+        let mut fs = FileSystem::default();
+        fs.create_directory("/tmp").unwrap();
+        fs.create_directory("/bin").unwrap();
+        write_file(&mut fs, "/bin/sh", b"sh".to_vec(), 0o755).unwrap();
+        write_file(&mut fs, "/tmp/x86", elf(0x3e), 0o755).unwrap();
+        write_file(&mut fs, "/tmp/arm", elf(0x28), 0o755).unwrap();
+        write_file(&mut fs, "/tmp/plain", b"echo hi\n".to_vec(), 0o644).unwrap();
+        write_file(&mut fs, "/tmp/ok.sh", b"#!/bin/sh\nid\n".to_vec(), 0o755).unwrap();
+        write_file(&mut fs, "/tmp/py", b"#!/usr/bin/python3\n".to_vec(), 0o755).unwrap();
+
+        let run = |path: &str| run_file(&fs, path, "x86_64").0;
+        assert_eq!(run("/tmp/x86"), FileRun::Executed);
+        assert_eq!(run("/tmp/arm"), FileRun::ExecFormatError);
+        assert_eq!(run("/tmp/plain"), FileRun::PermissionDenied);
+        assert_eq!(run("/tmp/ok.sh"), FileRun::Executed);
+        assert_eq!(
+            run("/tmp/py"),
+            FileRun::BadInterpreter("/usr/bin/python3".to_string())
+        );
+        assert_eq!(run("/tmp"), FileRun::IsDirectory);
+        assert_eq!(run("/tmp/nope"), FileRun::NotFound);
+        assert_eq!(
+            run_file(&fs, "/tmp/arm", "mips").0,
+            FileRun::ExecFormatError
+        );
+    }
+
+    #[test]
+    fn locates_paths_and_path_lookups() {
+        let mut fs = FileSystem::default();
+        fs.create_directory("/tmp").unwrap();
+        write_file(&mut fs, "/tmp/kit", Vec::new(), 0o755).unwrap();
+
+        assert_eq!(
+            locate(&fs, "./kit", "/tmp", "/bin"),
+            Some("/tmp/kit".to_string())
+        );
+        assert_eq!(
+            locate(&fs, "../tmp/./kit", "/tmp", "/bin"),
+            Some("/tmp/kit".to_string())
+        );
+        assert_eq!(
+            locate(&fs, "kit", "/", "/usr/bin:/tmp"),
+            Some("/tmp/kit".to_string())
+        );
+        assert_eq!(locate(&fs, "kit", "/tmp", "/usr/bin"), None);
+        assert_eq!(locate(&fs, "tmp", "/", "/"), None);
+    }
+}
@@ -0,0 +1,290 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// Ranges nobody outside should be able to make the honeypot talk to
+const DEFAULT_DENIED_NETWORKS: &[&str] = &[
+    "0.0.0.0/8",
+    "10.0.0.0/8",
+    "100.64.0.0/10",
+    "127.0.0.0/8",
+    "169.254.0.0/16",
+    "172.16.0.0/12",
+    "192.168.0.0/16",
+    "224.0.0.0/4",
+    "240.0.0.0/4",
+    "::/128",
+    "::1/128",
+    "fc00::/7",
+    "fe80::/10",
+    "ff00::/8",
+];
+
+/// One entry of the fetch allow- or denylist: a network in CIDR notation (a bare
+/// address is a single host) or a domain, which also covers its subdomains
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FetchRule {
+    Network { addr: IpAddr, prefix: u8 },
+    Domain(String),
+}
+
+impl FromStr for FetchRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err("empty rule".to_string());
+        }
+
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        if let Ok(addr) = addr.parse::<IpAddr>() {
+            let max = if addr.is_ipv4() { 32 } else { 128 };
+            let prefix = match prefix {
+                Some(prefix) => match prefix.parse::<u8>() {
+                    Ok(prefix) if prefix <= max => prefix,
+                    _ => return Err(format!("invalid prefix length in \"{}\"", s)),
+                },
+                None => max,
+            };
+            return Ok(FetchRule::Network { addr, prefix });
+        }
+        if prefix.is_some() {
+            return Err(format!("\"{}\" is not a valid network", s));
+        }
+
+        let domain = s
+            .trim_start_matches("*.")
+            .trim_start_matches('.')
+            .trim_end_matches('.')
+            .to_ascii_lowercase();
+        if domain.is_empty()
+            || !domain
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+        {
+            return Err(format!("\"{}\" is neither a network nor a domain", s));
+        }
+        Ok(FetchRule::Domain(domain))
+    }
+}
+
+impl std::fmt::Display for FetchRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FetchRule::Network { addr, prefix } => write!(f, "{}/{}", addr, prefix),
+            FetchRule::Domain(domain) => write!(f, "{}", domain),
+        }
+    }
+}
+
+impl FetchRule {
+    fn matches(&self, host: &Host) -> bool {
+        match (self, host) {
+            (FetchRule::Network { addr, prefix }, Host::Ip(ip)) => {
+                network_contains(*addr, *prefix, *ip)
+            }
+            (FetchRule::Domain(domain), Host::Domain(name)) => {
+                name == domain
+                    || name
+                        .strip_suffix(domain.as_str())
+                        .is_some_and(|rest| rest.ends_with('.'))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Decides which URLs typed into wget/curl may actually be fetched.
+///
+/// The denylist always wins. Private, loopback and link-local ranges are denied
+/// unless a network on the allowlist covers them explicitly, and a non-empty
+/// allowlist blocks everything it doesn't match. Domains are checked by name
+/// only, whatever fetches them has to run [`FetchPolicy::check_ip`] on the
+/// resolved addresses before connecting.
+#[derive(Debug, Clone)]
+pub struct FetchPolicy {
+    allow: Vec<FetchRule>,
+    deny: Vec<FetchRule>,
+    default_deny: Vec<FetchRule>,
+}
+
+impl Default for FetchPolicy {
+    fn default() -> Self {
+        Self::new(Vec::new(), Vec::new())
+    }
+}
+
+impl FetchPolicy {
+    pub fn new(allow: Vec<FetchRule>, deny: Vec<FetchRule>) -> Self {
+        let mut default_deny: Vec<FetchRule> = DEFAULT_DENIED_NETWORKS
+            .iter()
+            .map(|network| network.parse().expect("built-in network is valid"))
+            .collect();
+        default_deny.push(FetchRule::Domain("localhost".to_string()));
+
+        Self {
+            allow,
+            deny,
+            default_deny,
+        }
+    }
+
+    /// The allowlist as configured, without the built-in denied ranges
+    pub fn allowed(&self) -> &[FetchRule] {
+        &self.allow
+    }
+
+    /// The denylist as configured, without the built-in denied ranges
+    pub fn denied(&self) -> &[FetchRule] {
+        &self.deny
+    }
+
+    /// Check a URL as given to wget/curl. Returns why it is blocked
+    pub fn check_url(&self, url: &str) -> Result<(), String> {
+        match url_host(url) {
+            Some(host) => self.check_host(&host),
+            None => Err("no host in URL".to_string()),
+        }
+    }
+
+    /// Check an address a domain resolved to
+    pub fn check_ip(&self, ip: IpAddr) -> Result<(), String> {
+        self.check_host(&Host::Ip(ip))
+    }
+
+    fn check_host(&self, host: &Host) -> Result<(), String> {
+        if self.deny.iter().any(|rule| rule.matches(host)) {
+            return Err(format!("{} is on the denylist", host));
+        }
+
+        let allowed = self.allow.iter().any(|rule| rule.matches(host));
+        if !allowed && self.default_deny.iter().any(|rule| rule.matches(host)) {
+            return Err(format!(
+                "{} is a private, loopback or link-local address",
+                host
+            ));
+        }
+        if !allowed && !self.allow.is_empty() {
+            return Err(format!("{} is not on the allowlist", host));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Host {
+    Ip(IpAddr),
+    Domain(String),
+}
+
+impl std::fmt::Display for Host {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Host::Ip(ip) => write!(f, "{}", ip),
+            Host::Domain(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Host part of a URL. Like wget, a missing scheme is fine. Numeric hosts such as
+/// `http://2130706433/` are read the way inet_aton does, since droppers use them
+/// to sneak past naive filters
+pub(crate) fn url_host(url: &str) -> Option<Host> {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host_port = authority.rsplit_once('@').map_or(authority, |(_, hp)| hp);
+
+    let host = if let Some(bracketed) = host_port.strip_prefix('[') {
+        let v6 = bracketed.split_once(']')?.0;
+        return v6
+            .parse::<Ipv6Addr>()
+            .ok()
+            .map(|ip| Host::Ip(IpAddr::V6(ip)));
+    } else {
+        host_port.split(':').next().unwrap_or_default()
+    };
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    if host.is_empty() {
+        return None;
+    }
+
+    match parse_inet_aton(&host) {
+        Some(ip) => Some(Host::Ip(IpAddr::V4(ip))),
+        None => Some(Host::Domain(host)),
+    }
+}
+
+/// Port of a URL, explicit or the scheme's default
+pub(crate) fn url_port(url: &str) -> u16 {
+    let (scheme, rest) = url.split_once("://").unwrap_or(("http", url));
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host_port = authority.rsplit_once('@').map_or(authority, |(_, hp)| hp);
+    let port = match host_port.rsplit_once(']') {
+        Some((_, after)) => after.strip_prefix(':'),
+        None => host_port.split_once(':').map(|(_, port)| port),
+    };
+    port.and_then(|port| port.parse().ok())
+        .unwrap_or(match scheme.to_ascii_lowercase().as_str() {
+            "https" => 443,
+            "ftp" => 21,
+            _ => 80,
+        })
+}
+
+/// 1 to 4 dot separated decimal, octal (leading 0) or hex (leading 0x) parts,
+/// the last one filling the remaining bytes
+fn parse_inet_aton(host: &str) -> Option<Ipv4Addr> {
+    let parts: Vec<u32> = host
+        .split('.')
+        .map(|part| {
+            if let Some(hex) = part.strip_prefix("0x") {
+                u32::from_str_radix(hex, 16).ok()
+            } else if part.len() > 1
+                && let Some(octal) = part.strip_prefix('0')
+            {
+                u32::from_str_radix(octal, 8).ok()
+            } else {
+                part.parse::<u32>().ok()
+            }
+        })
+        .collect::<Option<_>>()?;
+    if parts.is_empty() || parts.len() > 4 {
+        return None;
+    }
+
+    let (last, leading) = parts.split_last()?;
+    if leading.iter().any(|part| *part > 255) {
+        return None;
+    }
+    let last_bits = 32 - 8 * leading.len() as u32;
+    if last_bits < 32 && *last >= 1 << last_bits {
+        return None;
+    }
+    let value = leading
+        .iter()
+        .enumerate()
+        .fold(*last, |acc, (i, part)| acc | part << (24 - 8 * i as u32));
+    Some(Ipv4Addr::from(value))
+}
+
+fn network_contains(network: IpAddr, prefix: u8, ip: IpAddr) -> bool {
+    // IPv4-mapped IPv6 addresses are treated as the IPv4 address they carry
+    let ip = match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        IpAddr::V4(_) => ip,
+    };
+    match (network, ip) {
+        (IpAddr::V4(network), IpAddr::V4(ip)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(network) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(network), IpAddr::V6(ip)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(network) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
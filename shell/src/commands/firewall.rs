@@ -0,0 +1,264 @@
+/// One iptables rule, kept as the matches and target it was added with
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Rule {
+    /// -j, empty for rules that only count packets
+    pub target: String,
+    /// -p, "all" when not given
+    pub protocol: String,
+    /// -s, with the prefix length, e.g. "203.0.113.7/32"
+    pub source: String,
+    /// -d
+    pub destination: String,
+    /// -i
+    pub in_interface: Option<String>,
+    /// -o
+    pub out_interface: Option<String>,
+    /// --sport
+    pub source_port: Option<String>,
+    /// --dport
+    pub destination_port: Option<String>,
+    /// --state or --ctstate, as given
+    pub state: Option<String>,
+}
+
+/// Service names iptables -L shows instead of port numbers
+fn service_name(port: &str) -> &str {
+    match port {
+        "21" => "ftp",
+        "22" => "ssh",
+        "23" => "telnet",
+        "25" => "smtp",
+        "53" => "domain",
+        "80" => "http",
+        "443" => "https",
+        "3306" => "mysql",
+        "6379" => "redis",
+        other => other,
+    }
+}
+
+/// Address as iptables -L shows it, names unless `numeric`
+fn address(address: &str, numeric: bool) -> String {
+    match address {
+        "0.0.0.0/0" if !numeric => "anywhere".to_string(),
+        address if !numeric => address.trim_end_matches("/32").to_string(),
+        address => address.to_string(),
+    }
+}
+
+impl Rule {
+    pub fn new() -> Self {
+        Self {
+            protocol: "all".to_string(),
+            source: "0.0.0.0/0".to_string(),
+            destination: "0.0.0.0/0".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Match extensions shown after the addresses, e.g. "tcp dpt:ssh"
+    fn matches(&self, numeric: bool) -> String {
+        let mut matches = Vec::new();
+        if let Some(state) = &self.state {
+            matches.push(format!("state {}", state));
+        }
+        let port = |port: &str| {
+            if numeric {
+                port.to_string()
+            } else {
+                service_name(port).to_string()
+            }
+        };
+        if self.source_port.is_some() || self.destination_port.is_some() {
+            matches.push(self.protocol.clone());
+        }
+        if let Some(source_port) = &self.source_port {
+            matches.push(format!("spt:{}", port(source_port)));
+        }
+        if let Some(destination_port) = &self.destination_port {
+            matches.push(format!("dpt:{}", port(destination_port)));
+        }
+        matches.join(" ")
+    }
+
+    /// Line of iptables -L, `verbose` adds counters and interfaces
+    pub fn listing(&self, numeric: bool, verbose: bool) -> String {
+        let mut line = String::new();
+        if verbose {
+            line.push_str(&format!("{:>5} {:>5} ", 0, 0));
+        }
+        line.push_str(&format!("{:<10} {:<4} --  ", self.target, self.protocol));
+        if verbose {
+            line.push_str(&format!(
+                "{:<6} {:<6} ",
+                self.in_interface.as_deref().unwrap_or("*"),
+                self.out_interface.as_deref().unwrap_or("*")
+            ));
+        }
+        line.push_str(&format!(
+            "{:<20} {:<20} {}",
+            address(&self.source, numeric),
+            address(&self.destination, numeric),
+            self.matches(numeric)
+        ));
+        line
+    }
+
+    /// The rule as iptables -S prints it, e.g. "-A INPUT -p tcp -m tcp --dport 22 -j ACCEPT"
+    pub fn specification(&self, chain: &str) -> String {
+        let mut spec = vec!["-A", chain];
+        if self.source != "0.0.0.0/0" {
+            spec.extend(["-s", &self.source]);
+        }
+        if self.destination != "0.0.0.0/0" {
+            spec.extend(["-d", &self.destination]);
+        }
+        if let Some(interface) = &self.in_interface {
+            spec.extend(["-i", interface]);
+        }
+        if let Some(interface) = &self.out_interface {
+            spec.extend(["-o", interface]);
+        }
+        if self.protocol != "all" {
+            spec.extend(["-p", &self.protocol]);
+        }
+        if let Some(state) = &self.state {
+            spec.extend(["-m", "state", "--state", state]);
+        }
+        if self.source_port.is_some() || self.destination_port.is_some() {
+            spec.extend(["-m", &self.protocol]);
+        }
+        if let Some(port) = &self.source_port {
+            spec.extend(["--sport", port]);
+        }
+        if let Some(port) = &self.destination_port {
+            spec.extend(["--dport", port]);
+        }
+        if !self.target.is_empty() {
+            spec.extend(["-j", &self.target]);
+        }
+        spec.join(" ")
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Chain {
+    pub name: String,
+    /// None for user defined chains, they have no policy
+    pub policy: Option<String>,
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Table {
+    pub name: &'static str,
+    pub chains: Vec<Chain>,
+}
+
+impl Table {
+    fn new(name: &'static str, chains: &[&str]) -> Self {
+        Self {
+            name,
+            chains: chains
+                .iter()
+                .map(|chain| Chain {
+                    name: chain.to_string(),
+                    policy: Some("ACCEPT".to_string()),
+                    rules: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn chain(&self, name: &str) -> Option<&Chain> {
+        self.chains.iter().find(|chain| chain.name == name)
+    }
+
+    pub fn chain_mut(&mut self, name: &str) -> Option<&mut Chain> {
+        self.chains.iter_mut().find(|chain| chain.name == name)
+    }
+
+    /// Rules in other chains jumping to `name`
+    pub fn references(&self, name: &str) -> usize {
+        self.chains
+            .iter()
+            .flat_map(|chain| &chain.rules)
+            .filter(|rule| rule.target == name)
+            .count()
+    }
+}
+
+/// One rule of ufw status
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UfwRule {
+    /// ALLOW, DENY, REJECT or LIMIT
+    pub action: String,
+    /// IN or OUT
+    pub direction: String,
+    /// Port, port/protocol, application or address
+    pub to: String,
+    /// Address or "Anywhere"
+    pub from: String,
+    /// The IPv6 twin ufw adds for rules without addresses
+    pub v6: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Ufw {
+    pub active: bool,
+    pub rules: Vec<UfwRule>,
+    /// Default policy for incoming, outgoing and routed traffic
+    pub defaults: [String; 3],
+    /// As ufw status verbose shows it, e.g. "on (low)"
+    pub logging: String,
+}
+
+impl Default for Ufw {
+    fn default() -> Self {
+        Self {
+            active: false,
+            rules: Vec::new(),
+            defaults: [
+                "deny".to_string(),
+                "allow".to_string(),
+                "disabled".to_string(),
+            ],
+            logging: "on (low)".to_string(),
+        }
+    }
+}
+
+/// Netfilter tables and ufw's state of one session. A fresh Ubuntu server:
+/// everything accepted and ufw installed but inactive
+#[derive(Clone, Debug)]
+pub struct Firewall {
+    pub tables: Vec<Table>,
+    pub ufw: Ufw,
+}
+
+impl Default for Firewall {
+    fn default() -> Self {
+        Self {
+            tables: vec![
+                Table::new("filter", &["INPUT", "FORWARD", "OUTPUT"]),
+                Table::new("nat", &["PREROUTING", "INPUT", "OUTPUT", "POSTROUTING"]),
+                Table::new(
+                    "mangle",
+                    &["PREROUTING", "INPUT", "FORWARD", "OUTPUT", "POSTROUTING"],
+                ),
+                Table::new("raw", &["PREROUTING", "OUTPUT"]),
+            ],
+            ufw: Ufw::default(),
+        }
+    }
+}
+
+impl Firewall {
+    pub fn table(&self, name: &str) -> Option<&Table> {
+        self.tables.iter().find(|table| table.name == name)
+    }
+
+    pub fn table_mut(&mut self, name: &str) -> Option<&mut Table> {
+        self.tables.iter_mut().find(|table| table.name == name)
+    }
+}
@@ -0,0 +1,98 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use async_trait::async_trait;
+
+/// Getconf command: answers the configuration variables scripts ask for before
+/// picking a binary, LONG_BIT agrees with `uname -m` and `arch`
+pub struct GetconfCommand;
+
+/// Values that don't depend on the machine, in `getconf -a` order
+const FIXED_VARIABLES: &[(&str, &str)] = &[
+    ("LINK_MAX", "127"),
+    ("_POSIX_LINK_MAX", "127"),
+    ("MAX_CANON", "255"),
+    ("MAX_INPUT", "255"),
+    ("NAME_MAX", "255"),
+    ("PATH_MAX", "4096"),
+    ("PIPE_BUF", "4096"),
+    ("CHAR_BIT", "8"),
+    ("WORD_BIT", "32"),
+    ("ARG_MAX", "2097152"),
+    ("CHILD_MAX", "31406"),
+    ("CLK_TCK", "100"),
+    ("NGROUPS_MAX", "65536"),
+    ("OPEN_MAX", "1024"),
+    ("HOST_NAME_MAX", "64"),
+    ("LOGIN_NAME_MAX", "256"),
+];
+
+/// Usage message when the variable is missing
+const USAGE: &str =
+    "Usage: getconf [-v specification] variable_name [pathname]\r\n       getconf -a [pathname]";
+
+fn variables(context: &CommandContext) -> Vec<(&'static str, String)> {
+    let mut variables: Vec<(&'static str, String)> = FIXED_VARIABLES
+        .iter()
+        .map(|(name, value)| (*name, value.to_string()))
+        .collect();
+    variables.push(("PAGESIZE", "4096".to_string()));
+    variables.push(("PAGE_SIZE", "4096".to_string()));
+    variables.push(("LONG_BIT", context.os.long_bit().to_string()));
+    variables.push(("GNU_LIBC_VERSION", "glibc 2.31".to_string()));
+    variables.push(("GNU_LIBPTHREAD_VERSION", "NPTL 2.31".to_string()));
+    variables
+}
+
+#[async_trait]
+impl Command for GetconfCommand {
+    fn name(&self) -> &'static str {
+        "getconf"
+    }
+
+    fn help(&self) -> String {
+        format!("{}\r\n", USAGE)
+    }
+
+    fn version(&self) -> String {
+        "getconf (Ubuntu GLIBC 2.31-0ubuntu9.9) 2.31\r\n".to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        let mut args = args.iter().map(String::as_str);
+        let mut variable = None;
+        while let Some(arg) = args.next() {
+            match arg {
+                "-h" | "--help" => return Ok(self.help()),
+                "-V" | "--version" => return Ok(self.version()),
+                "-a" => {
+                    return Ok(variables(context)
+                        .into_iter()
+                        .map(|(name, value)| format!("{:<31}{}\r\n", name, value))
+                        .collect());
+                }
+                // The specification only matters for the compilation environment values
+                "-v" => {
+                    args.next();
+                }
+                name => {
+                    variable = Some(name);
+                    break;
+                }
+            }
+        }
+
+        let Some(variable) = variable else {
+            return Err(CommandError::InvalidArguments(USAGE.to_string()));
+        };
+        match variables(context)
+            .into_iter()
+            .find(|(name, _)| *name == variable)
+        {
+            Some((_, value)) => Ok(format!("{}\r\n", value)),
+            None => Err(CommandError::InvalidArguments(format!(
+                "getconf: Unrecognized variable '{}'",
+                variable
+            ))),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use super::accounts::AccountTable;
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use crate::filesystem::fs2::FileContent;
+use async_trait::async_trait;
+
+/// Databases glibc knows that have nothing to show here: listing them prints
+/// nothing, looking a key up finds nothing
+const EMPTY_DATABASES: &[&str] = &[
+    "ahosts",
+    "ahostsv4",
+    "ahostsv6",
+    "aliases",
+    "ethers",
+    "gshadow",
+    "initgroups",
+    "netgroup",
+    "networks",
+    "protocols",
+    "rpc",
+    "services",
+    "shadow",
+];
+
+fn usage_error(message: &str) -> CommandError {
+    CommandError::InvalidArguments(format!(
+        "{}\r\nTry `getent --help' or `getent --usage' for more information.",
+        message
+    ))
+}
+
+/// `(address, names)` of each entry in /etc/hosts
+fn hosts(text: &str) -> Vec<(String, Vec<String>)> {
+    text.lines()
+        .filter_map(|line| {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut fields = line.split_whitespace().map(str::to_string);
+            let address = fields.next()?;
+            let names: Vec<String> = fields.collect();
+            (!names.is_empty()).then_some((address, names))
+        })
+        .collect()
+}
+
+/// Getent command: passwd and group entries from the same accounts `id` and
+/// /etc/passwd show, hosts from /etc/hosts. Keys that aren't found print
+/// nothing and fail the command, the real one exits with 2
+pub struct GetentCommand;
+
+#[async_trait]
+impl Command for GetentCommand {
+    fn name(&self) -> &'static str {
+        "getent"
+    }
+
+    fn help(&self) -> String {
+        "Usage: getent [OPTION...] database [key ...]\r\n\
+        Get entries from administrative database.\r\n\
+        \r\n\
+        \x20 -i, --no-idn               disable IDN encoding\r\n\
+        \x20 -s, --service=CONFIG       Service configuration to be used\r\n\
+        \x20 -?, --help                 Give this help list\r\n\
+        \x20     --usage                Give a short usage message\r\n\
+        \x20 -V, --version              Print program version\r\n\
+        \r\n\
+        Mandatory or optional arguments to long options are also mandatory or optional\r\n\
+        for any corresponding short options.\r\n\
+        \r\n\
+        Supported databases:\r\n\
+        ahosts ahostsv4 ahostsv6 aliases ethers group gshadow hosts initgroups\r\n\
+        netgroup networks passwd protocols rpc services shadow\r\n\
+        \r\n\
+        For bug reporting instructions, please see:\r\n\
+        <https://bugs.launchpad.net/ubuntu/+source/glibc/+bugs>.\r\n"
+            .to_string()
+    }
+
+    fn version(&self) -> String {
+        "getent (Ubuntu GLIBC 2.31-0ubuntu9.9) 2.31\r\n".to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        match args.first().map(String::as_str) {
+            Some("--help" | "-?") => return Ok(self.help()),
+            Some("--version" | "-V") => return Ok(self.version()),
+            _ => {}
+        }
+        let operands: Vec<&String> = args
+            .iter()
+            .filter(|arg| !matches!(arg.as_str(), "-i" | "--no-idn"))
+            .collect();
+        let Some((database, keys)) = operands.split_first() else {
+            return Err(usage_error("getent: wrong number of arguments"));
+        };
+
+        let fs = context.filesystem.read().await;
+        let mut entries: Vec<String> = Vec::new();
+        let mut missing = false;
+        match database.as_str() {
+            "passwd" | "group" => {
+                let table = AccountTable::read(&fs, &context.username);
+                let passwd = database.as_str() == "passwd";
+                if keys.is_empty() && passwd {
+                    entries.extend(table.accounts.iter().map(ToString::to_string));
+                } else if keys.is_empty() {
+                    entries.extend(table.groups.iter().map(ToString::to_string));
+                }
+                for key in keys {
+                    let entry = match passwd {
+                        true => table.user(key).map(ToString::to_string),
+                        false => table.group(key).map(ToString::to_string),
+                    };
+                    match entry {
+                        Some(entry) => entries.push(entry),
+                        None => missing = true,
+                    }
+                }
+            }
+            "hosts" => {
+                let text = match fs.follow_symlink("/etc/hosts").map(|e| e.file_content) {
+                    Ok(Some(FileContent::RegularFile(bytes))) => {
+                        String::from_utf8_lossy(&bytes).into_owned()
+                    }
+                    _ => String::new(),
+                };
+                let hosts = hosts(&text);
+                let line = |(address, names): &(String, Vec<String>)| {
+                    format!("{:<15} {}", address, names.join(" "))
+                };
+                if keys.is_empty() {
+                    entries.extend(hosts.iter().map(line));
+                }
+                for key in keys {
+                    let found = hosts
+                        .iter()
+                        .find(|(address, names)| address == *key || names.contains(key));
+                    match found {
+                        Some(host) => entries.push(line(host)),
+                        None => missing = true,
+                    }
+                }
+            }
+            known if EMPTY_DATABASES.contains(&known) => missing = !keys.is_empty(),
+            unknown => {
+                return Err(usage_error(&format!("Unknown database: {}", unknown)));
+            }
+        }
+        // Found entries still print when others are missing, losing the status
+        if missing && entries.is_empty() {
+            return Err(CommandError::SilentFailure);
+        }
+        Ok(entries
+            .iter()
+            .map(|entry| format!("{}\r\n", entry))
+            .collect())
+    }
+}
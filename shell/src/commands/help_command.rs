@@ -0,0 +1,488 @@
+use super::accounts::AccountTable;
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use crate::filesystem::fs2::{FileContent, FileSystem};
+use async_trait::async_trait;
+
+/// Reserved words, in the order `compgen -k` lists them
+const KEYWORDS: &[&str] = &[
+    "if", "then", "else", "elif", "fi", "case", "esac", "for", "select", "while", "until", "do",
+    "done", "in", "function", "time", "{", "}", "!", "[[", "]]", "coproc",
+];
+
+/// Builtins of bash 5.0, in the order `compgen -b` lists them
+pub(super) const BUILTINS: &[&str] = &[
+    ".",
+    ":",
+    "[",
+    "alias",
+    "bg",
+    "bind",
+    "break",
+    "builtin",
+    "caller",
+    "cd",
+    "command",
+    "compgen",
+    "complete",
+    "compopt",
+    "continue",
+    "declare",
+    "dirs",
+    "disown",
+    "echo",
+    "enable",
+    "eval",
+    "exec",
+    "exit",
+    "export",
+    "false",
+    "fc",
+    "fg",
+    "getopts",
+    "hash",
+    "help",
+    "history",
+    "jobs",
+    "kill",
+    "let",
+    "local",
+    "logout",
+    "mapfile",
+    "popd",
+    "printf",
+    "pushd",
+    "pwd",
+    "read",
+    "readarray",
+    "readonly",
+    "return",
+    "set",
+    "shift",
+    "shopt",
+    "source",
+    "suspend",
+    "test",
+    "times",
+    "trap",
+    "true",
+    "type",
+    "typeset",
+    "ulimit",
+    "umask",
+    "unalias",
+    "unset",
+    "wait",
+];
+
+/// `help` topics with their synopsis, in the order bash 5.0 lists them
+const HELP_TOPICS: &[(&str, &str)] = &[
+    ("job_spec", "job_spec [&]"),
+    ("((", "(( expression ))"),
+    (".", ". filename [arguments]"),
+    (":", ":"),
+    ("[", "[ arg... ]"),
+    ("[[", "[[ expression ]]"),
+    ("alias", "alias [-p] [name[=value] ... ]"),
+    ("bg", "bg [job_spec ...]"),
+    (
+        "bind",
+        "bind [-lpsvPSVX] [-m keymap] [-f filename] [-q name] [-u name] [-r keyseq] [-x keyseq:shell-command] [keyseq:readline-function or readline-command]",
+    ),
+    ("break", "break [n]"),
+    ("builtin", "builtin [shell-builtin [arg ...]]"),
+    ("caller", "caller [expr]"),
+    (
+        "case",
+        "case WORD in [PATTERN [| PATTERN]...) COMMANDS ;;]... esac",
+    ),
+    ("cd", "cd [-L|[-P [-e]] [-@]] [dir]"),
+    ("command", "command [-pVv] command [arg ...]"),
+    ("compgen", COMPGEN_SYNOPSIS),
+    (
+        "complete",
+        "complete [-abcdefgjksuv] [-pr] [-DEI] [-o option] [-A action] [-G globpat] [-W wordlist]  [-F function] [-C command] [-X filterpat] [-P prefix] [-S suffix] [name ...]",
+    ),
+    ("compopt", "compopt [-o|+o option] [-DEI] [name ...]"),
+    ("continue", "continue [n]"),
+    ("coproc", "coproc [NAME] command [redirections]"),
+    ("declare", "declare [-aAfFgilnrtux] [-p] [name[=value] ...]"),
+    ("dirs", "dirs [-clpv] [+N] [-N]"),
+    ("disown", "disown [-h] [-ar] [jobspec ... | pid ...]"),
+    ("echo", "echo [-neE] [arg ...]"),
+    ("enable", "enable [-a] [-dnps] [-f filename] [name ...]"),
+    ("eval", "eval [arg ...]"),
+    (
+        "exec",
+        "exec [-cl] [-a name] [command [arguments ...]] [redirection ...]",
+    ),
+    ("exit", "exit [n]"),
+    ("export", "export [-fn] [name[=value] ...] or export -p"),
+    ("false", "false"),
+    (
+        "fc",
+        "fc [-e ename] [-lnr] [first] [last] or fc -s [pat=rep] [command]",
+    ),
+    ("fg", "fg [job_spec]"),
+    ("for", "for NAME [in WORDS ... ] ; do COMMANDS; done"),
+    ("for ((", "for (( exp1; exp2; exp3 )); do COMMANDS; done"),
+    (
+        "function",
+        "function name { COMMANDS ; } or name () { COMMANDS ; }",
+    ),
+    ("getopts", "getopts optstring name [arg]"),
+    ("hash", "hash [-lr] [-p pathname] [-dt] [name ...]"),
+    ("help", "help [-dms] [pattern ...]"),
+    (
+        "history",
+        "history [-c] [-d offset] [n] or history -anrw [filename] or history -ps arg [arg...]",
+    ),
+    (
+        "if",
+        "if COMMANDS; then COMMANDS; [ elif COMMANDS; then COMMANDS; ]... [ else COMMANDS; ] fi",
+    ),
+    (
+        "jobs",
+        "jobs [-lnprs] [jobspec ...] or jobs -x command [args]",
+    ),
+    (
+        "kill",
+        "kill [-s sigspec | -n signum | -sigspec] pid | jobspec ... or kill -l [sigspec]",
+    ),
+    ("let", "let arg [arg ...]"),
+    ("local", "local [option] name[=value] ..."),
+    ("logout", "logout [n]"),
+    (
+        "mapfile",
+        "mapfile [-d delim] [-n count] [-O origin] [-s count] [-t] [-u fd] [-C callback] [-c quantum] [array]",
+    ),
+    ("popd", "popd [-n] [+N | -N]"),
+    ("printf", "printf [-v var] format [arguments]"),
+    ("pushd", "pushd [-n] [+N | -N | dir]"),
+    ("pwd", "pwd [-LP]"),
+    (
+        "read",
+        "read [-ers] [-a array] [-d delim] [-i text] [-n nchars] [-N nchars] [-p prompt] [-t timeout] [-u fd] [name ...]",
+    ),
+    (
+        "readarray",
+        "readarray [-d delim] [-n count] [-O origin] [-s count] [-t] [-u fd] [-C callback] [-c quantum] [array]",
+    ),
+    (
+        "readonly",
+        "readonly [-aAf] [name[=value] ...] or readonly -p",
+    ),
+    ("return", "return [n]"),
+    ("select", "select NAME [in WORDS ... ;] do COMMANDS; done"),
+    (
+        "set",
+        "set [-abefhkmnptuvxBCHP] [-o option-name] [--] [arg ...]",
+    ),
+    ("shift", "shift [n]"),
+    ("shopt", "shopt [-pqsu] [-o] [optname ...]"),
+    ("source", "source filename [arguments]"),
+    ("suspend", "suspend [-f]"),
+    ("test", "test [expr]"),
+    ("time", "time [-p] pipeline"),
+    ("times", "times"),
+    ("trap", "trap [-lp] [[arg] signal_spec ...]"),
+    ("true", "true"),
+    ("type", "type [-afptP] name [name ...]"),
+    ("typeset", "typeset [-aAfFgilnrtux] [-p] name[=value] ..."),
+    ("ulimit", "ulimit [-SHabcdefiklmnpqrstuvxPT] [limit]"),
+    ("umask", "umask [-p] [-S] [mode]"),
+    ("unalias", "unalias [-a] name [name ...]"),
+    ("unset", "unset [-f] [-v] [-n] [name ...]"),
+    ("until", "until COMMANDS; do COMMANDS; done"),
+    (
+        "variables",
+        "variables - Names and meanings of some shell variables",
+    ),
+    ("wait", "wait [-fn] [id ...]"),
+    ("while", "while COMMANDS; do COMMANDS; done"),
+    ("{", "{ COMMANDS ; }"),
+];
+
+const COMPGEN_SYNOPSIS: &str = "compgen [-abcdefgjksuv] [-o option] [-A action] [-G globpat] [-W wordlist]  [-F function] [-C command] [-X filterpat] [-P prefix] [-S suffix] [word]";
+
+/// Half of an 80 column terminal, the width of a column of `help`
+const HELP_COLUMN: usize = 40;
+
+/// One cell of the `help` list: a space and the synopsis, cut with a `>` where
+/// it doesn't fit the column
+fn help_cell(synopsis: &str) -> String {
+    if synopsis.len() < HELP_COLUMN - 3 {
+        format!(" {}", synopsis)
+    } else {
+        format!(" {}>", &synopsis[..HELP_COLUMN - 3])
+    }
+}
+
+/// Help command: bash's list of builtins and their synopses. There is no long
+/// help text, `help NAME` shows the synopsis as `help -s` does
+pub struct HelpCommand;
+
+#[async_trait]
+impl Command for HelpCommand {
+    fn name(&self) -> &'static str {
+        "help"
+    }
+
+    fn help(&self) -> String {
+        "help: help [-dms] [pattern ...]\r\n".to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        let mut patterns = Vec::new();
+        for arg in args {
+            match arg.strip_prefix('-') {
+                Some(flags) if !flags.is_empty() && patterns.is_empty() => {
+                    if let Some(flag) = flags.chars().find(|flag| !"dms".contains(*flag)) {
+                        return Err(CommandError::InvalidArguments(format!(
+                            "bash: help: -{}: invalid option\r\nhelp: usage: help [-dms] [pattern ...]",
+                            flag
+                        )));
+                    }
+                }
+                _ => patterns.push(arg.as_str()),
+            }
+        }
+
+        if patterns.is_empty() {
+            let mut out = format!(
+                "GNU bash, version 5.0.17(1)-release ({}-pc-linux-gnu)\r\n\
+                These shell commands are defined internally.  Type `help' to see this list.\r\n\
+                Type `help name' to find out more about the function `name'.\r\n\
+                Use `info bash' to find out more about the shell in general.\r\n\
+                Use `man -k' or `info' to find out more about commands not in this list.\r\n\
+                \r\n\
+                A star (*) next to a name means that the command is disabled.\r\n\
+                \r\n",
+                context.os.machine
+            );
+            let (left, right) = HELP_TOPICS.split_at(HELP_TOPICS.len().div_ceil(2));
+            for (row, (_, synopsis)) in left.iter().enumerate() {
+                let left = help_cell(synopsis);
+                match right.get(row) {
+                    Some((_, right)) => out.push_str(&format!(
+                        "{:<width$}{}\r\n",
+                        left,
+                        help_cell(right),
+                        width = HELP_COLUMN
+                    )),
+                    None => out.push_str(&format!("{}\r\n", left)),
+                }
+            }
+            return Ok(out);
+        }
+
+        let mut out = String::new();
+        for pattern in &patterns {
+            for (topic, synopsis) in HELP_TOPICS.iter().filter(|(t, _)| t.starts_with(pattern)) {
+                out.push_str(&format!("{}: {}\r\n", topic, synopsis));
+            }
+        }
+        if out.is_empty() {
+            let pattern = patterns.join(" ");
+            return Err(CommandError::ExecutionError(format!(
+                "bash: help: no help topics match `{}'.  Try `help help' or `man -k {}' or `info {}'.",
+                pattern, pattern, pattern
+            )));
+        }
+        Ok(out)
+    }
+}
+
+fn compgen_usage(message: &str) -> CommandError {
+    CommandError::InvalidArguments(format!(
+        "bash: compgen: {}\r\ncompgen: usage: {}",
+        message, COMPGEN_SYNOPSIS
+    ))
+}
+
+/// Entries of the directory part of `word` whose path starts with `word`, only
+/// directories with `directories`
+fn paths(fs: &FileSystem, word: &str, cwd: &str, directories: bool) -> Vec<String> {
+    let (dir, shown) = match word.rfind('/') {
+        Some(slash) => (&word[..=slash], &word[..=slash]),
+        None => ("", ""),
+    };
+    let absolute = match dir {
+        "" => cwd.to_string(),
+        dir if dir.starts_with('/') => dir.to_string(),
+        dir => format!("{}/{}", cwd.trim_end_matches('/'), dir),
+    };
+    let Ok(entries) = fs.list_directory(&absolute) else {
+        return Vec::new();
+    };
+    let mut found: Vec<String> = entries
+        .into_iter()
+        .filter(|entry| {
+            !directories
+                || matches!(
+                    fs.follow_symlink(&format!(
+                        "{}/{}",
+                        absolute.trim_end_matches('/'),
+                        entry.name
+                    ))
+                    .map(|entry| entry.file_content),
+                    Ok(Some(FileContent::Directory(_)))
+                )
+        })
+        .map(|entry| format!("{}{}", shown, entry.name))
+        .filter(|path| {
+            path.starts_with(word) && (word.contains('.') || !path[shown.len()..].starts_with('.'))
+        })
+        .collect();
+    found.sort();
+    found
+}
+
+/// Regular files in the `PATH` directories, what bash finds for a command word
+fn path_commands(fs: &FileSystem, path_env: &str) -> Vec<String> {
+    let mut found = Vec::new();
+    for dir in path_env.split(':').filter(|dir| dir.starts_with('/')) {
+        let Ok(entries) = fs.list_directory(dir) else {
+            continue;
+        };
+        for entry in entries {
+            let path = format!("{}/{}", dir.trim_end_matches('/'), entry.name);
+            if matches!(
+                fs.follow_symlink(&path).map(|entry| entry.file_content),
+                Ok(Some(FileContent::RegularFile(_)))
+            ) {
+                found.push(entry.name);
+            }
+        }
+    }
+    found
+}
+
+/// `compgen`: the words bash would complete. `commands` are the names the shell
+/// runs without a file on `PATH`, the dispatcher passes every registered command
+/// so that `compgen -c` shows what is actually implemented
+pub(crate) async fn compgen(
+    args: &[String],
+    commands: &[String],
+    context: &CommandContext,
+) -> CommandResult {
+    let mut actions: Vec<String> = Vec::new();
+    let mut wordlist: Option<&str> = None;
+    let mut prefix = "";
+    let mut suffix = "";
+    let mut word = "";
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let Some(flags) = arg.strip_prefix('-').filter(|flags| !flags.is_empty()) else {
+            word = arg;
+            continue;
+        };
+        if flags == "-" {
+            word = args.next().map_or("", String::as_str);
+            break;
+        }
+        for (index, flag) in flags.char_indices() {
+            let action = match flag {
+                'a' => "alias",
+                'b' => "builtin",
+                'c' => "command",
+                'd' => "directory",
+                'e' => "export",
+                'f' => "file",
+                'g' => "group",
+                'j' => "job",
+                'k' => "keyword",
+                's' => "service",
+                'u' => "user",
+                'v' => "variable",
+                'o' | 'A' | 'G' | 'W' | 'F' | 'C' | 'X' | 'P' | 'S' => {
+                    let rest = &flags[index + 1..];
+                    let value = match rest.is_empty() {
+                        true => args.next().map(String::as_str).ok_or_else(|| {
+                            compgen_usage(&format!("-{}: option requires an argument", flag))
+                        })?,
+                        false => rest,
+                    };
+                    match flag {
+                        'A' => actions.push(value.to_string()),
+                        'W' => wordlist = Some(value),
+                        'P' => prefix = value,
+                        'S' => suffix = value,
+                        _ => {}
+                    }
+                    break;
+                }
+                _ => return Err(compgen_usage(&format!("-{}: invalid option", flag))),
+            };
+            actions.push(action.to_string());
+        }
+    }
+
+    let fs = context.filesystem.read().await;
+    let mut words: Vec<String> = Vec::new();
+    for action in &actions {
+        match action.as_str() {
+            "builtin" | "enabled" => words.extend(BUILTINS.iter().map(|b| b.to_string())),
+            "keyword" => words.extend(KEYWORDS.iter().map(|k| k.to_string())),
+            "helptopic" => words.extend(HELP_TOPICS.iter().map(|(t, _)| t.to_string())),
+            "command" => {
+                words.extend(KEYWORDS.iter().map(|k| k.to_string()));
+                words.extend(BUILTINS.iter().map(|b| b.to_string()));
+                words.extend(commands.iter().cloned());
+                let path_env = context.get_env("PATH").map_or("", String::as_str);
+                words.extend(path_commands(&fs, path_env));
+            }
+            "file" => words.extend(paths(&fs, word, &context.cwd, false)),
+            "directory" => words.extend(paths(&fs, word, &context.cwd, true)),
+            "export" | "variable" => {
+                let mut names: Vec<&String> = context.env_vars.keys().collect();
+                names.sort();
+                words.extend(names.into_iter().cloned());
+            }
+            "user" => {
+                let table = AccountTable::read(&fs, &context.username);
+                words.extend(table.accounts.iter().map(|a| a.name.clone()));
+            }
+            "group" => {
+                let table = AccountTable::read(&fs, &context.username);
+                words.extend(table.groups.iter().map(|g| g.name.clone()));
+            }
+            "alias" | "arrayvar" | "binding" | "disabled" | "function" | "hostname" | "job"
+            | "running" | "service" | "setopt" | "shopt" | "signal" | "stopped" => {}
+            unknown => return Err(compgen_usage(&format!("{}: invalid action name", unknown))),
+        }
+    }
+    if let Some(wordlist) = wordlist {
+        words.extend(wordlist.split_whitespace().map(str::to_string));
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    let out: String = words
+        .into_iter()
+        .filter(|candidate| candidate.starts_with(word) && seen.insert(candidate.clone()))
+        .map(|candidate| format!("{}{}{}\r\n", prefix, candidate, suffix))
+        .collect();
+    if out.is_empty() {
+        return Err(CommandError::SilentFailure);
+    }
+    Ok(out)
+}
+
+/// Compgen command. The dispatcher runs [`compgen`] with the registered
+/// commands, this answers without them
+pub struct CompgenCommand;
+
+#[async_trait]
+impl Command for CompgenCommand {
+    fn name(&self) -> &'static str {
+        "compgen"
+    }
+
+    fn help(&self) -> String {
+        format!("compgen: {}\r\n", COMPGEN_SYNOPSIS)
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        compgen(args, &[], context).await
+    }
+}
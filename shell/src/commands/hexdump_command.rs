@@ -0,0 +1,691 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use crate::filesystem::fs2::FileContent;
+use async_trait::async_trait;
+
+/// Bytes shown per line by hexdump and od
+const LINE_BYTES: usize = 16;
+
+/// How one column of hexdump or od shows the bytes of its line
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Field {
+    /// Zero-padded octal of `size` little-endian bytes
+    Octal(usize),
+    /// Zero-padded hex of `size` little-endian bytes
+    Hex(usize),
+    Unsigned(usize),
+    Signed(usize),
+    /// Printable characters, C escapes and octal for the rest
+    Char,
+}
+
+impl Field {
+    fn size(self) -> usize {
+        match self {
+            Field::Octal(size) | Field::Hex(size) | Field::Unsigned(size) | Field::Signed(size) => {
+                size
+            }
+            Field::Char => 1,
+        }
+    }
+
+    /// Width od gives a value, the separating space included
+    fn od_width(self) -> usize {
+        match self {
+            Field::Octal(1) => 4,
+            Field::Octal(2) => 7,
+            Field::Octal(_) => 12,
+            Field::Hex(size) => size * 2 + 1,
+            Field::Unsigned(1) => 4,
+            Field::Unsigned(2) => 6,
+            Field::Unsigned(_) => 11,
+            Field::Signed(1) => 5,
+            Field::Signed(2) => 7,
+            Field::Signed(_) => 12,
+            Field::Char => 4,
+        }
+    }
+
+    fn show(self, bytes: &[u8]) -> String {
+        let mut value = [0u8; 4];
+        value[..bytes.len()].copy_from_slice(bytes);
+        let value = u32::from_le_bytes(value);
+        match self {
+            Field::Octal(size) => format!("{:0width$o}", value, width = [3, 6, 0, 11][size - 1]),
+            Field::Hex(size) => format!("{:0width$x}", value, width = size * 2),
+            Field::Unsigned(_) => value.to_string(),
+            Field::Signed(1) => (value as u8 as i8).to_string(),
+            Field::Signed(2) => (value as u16 as i16).to_string(),
+            Field::Signed(_) => (value as i32).to_string(),
+            Field::Char => match bytes[0] {
+                0 => "\\0".to_string(),
+                7 => "\\a".to_string(),
+                8 => "\\b".to_string(),
+                b'\t' => "\\t".to_string(),
+                b'\n' => "\\n".to_string(),
+                11 => "\\v".to_string(),
+                12 => "\\f".to_string(),
+                b'\r' => "\\r".to_string(),
+                byte @ 0x20..=0x7e => (byte as char).to_string(),
+                byte => format!("{:03o}", byte),
+            },
+        }
+    }
+}
+
+/// od's `-t` type, None when it isn't one
+fn od_type(spec: &str) -> Option<Field> {
+    let mut chars = spec.chars();
+    let kind = chars.next()?;
+    let size = match chars.as_str() {
+        "" if kind == 'c' => return Some(Field::Char),
+        "" => 2,
+        "C" => 1,
+        "S" => 2,
+        "I" | "L" => 4,
+        number => number
+            .parse()
+            .ok()
+            .filter(|size| [1, 2, 4].contains(size))?,
+    };
+    match kind {
+        'o' => Some(Field::Octal(size)),
+        'x' => Some(Field::Hex(size)),
+        'u' => Some(Field::Unsigned(size)),
+        'd' => Some(Field::Signed(size)),
+        _ => None,
+    }
+}
+
+/// What the address column of od shows
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Radix {
+    Octal,
+    Hex,
+    Decimal,
+    None,
+}
+
+impl Radix {
+    fn show(self, offset: usize) -> String {
+        match self {
+            Radix::Octal => format!("{:07o}", offset),
+            Radix::Hex => format!("{:06x}", offset),
+            Radix::Decimal => format!("{:07}", offset),
+            Radix::None => String::new(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Layout {
+    /// xxd: `cols` bytes per line in groups of `group`, -p for plain hex
+    Xxd {
+        cols: usize,
+        group: usize,
+        plain: bool,
+        upper: bool,
+    },
+    /// hexdump -C
+    Canonical,
+    /// hexdump without -C, one field `width` wide over the line
+    Hexdump(Field, usize),
+    Od(Radix, Vec<Field>),
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    layout: Layout,
+    skip: usize,
+    length: Option<usize>,
+    /// Repeated lines are kept instead of shown as `*`
+    verbose: bool,
+    files: Vec<String>,
+}
+
+/// `16`, `0x10` or `020` like strtol with base 0, od's `k`, `m` and `b`
+/// suffixes too
+fn parse_count(value: &str) -> Option<usize> {
+    let (number, scale) = match value.chars().last()? {
+        'b' if !value.starts_with("0x") => (&value[..value.len() - 1], 512),
+        'k' | 'K' => (&value[..value.len() - 1], 1024),
+        'm' | 'M' => (&value[..value.len() - 1], 1024 * 1024),
+        _ => (value, 1),
+    };
+    let number = number.trim_start_matches('+');
+    let parsed = if let Some(hex) = number
+        .strip_prefix("0x")
+        .or_else(|| number.strip_prefix("0X"))
+    {
+        usize::from_str_radix(hex, 16).ok()?
+    } else if number.len() > 1 && number.starts_with('0') {
+        usize::from_str_radix(&number[1..], 8).ok()?
+    } else {
+        number.parse().ok()?
+    };
+    parsed.checked_mul(scale)
+}
+
+/// xxd's hex and text columns for `bytes` read from `offset`
+fn xxd(bytes: &[u8], offset: usize, cols: usize, group: usize, upper: bool) -> String {
+    let hex = |byte: &u8| match upper {
+        true => format!("{:02X}", byte),
+        false => format!("{:02x}", byte),
+    };
+    // Hex column wide enough for a full line, -g0 doesn't group
+    let width = match group {
+        0 => cols * 2 + 1,
+        _ => cols * 2 + cols.div_ceil(group),
+    };
+    let mut out = String::new();
+    for (index, line) in bytes.chunks(cols).enumerate() {
+        let mut column = String::new();
+        for (position, byte) in line.iter().enumerate() {
+            column.push_str(&hex(byte));
+            if group > 0 && (position + 1) % group == 0 {
+                column.push(' ');
+            }
+        }
+        if group == 0 || line.len() % group != 0 {
+            column.push(' ');
+        }
+        let ascii: String = line
+            .iter()
+            .map(|&byte| match byte {
+                0x20..=0x7e => byte as char,
+                _ => '.',
+            })
+            .collect();
+        out.push_str(&format!(
+            "{:08x}: {:<width$} {}\r\n",
+            offset + index * cols,
+            column,
+            ascii,
+            width = width
+        ));
+    }
+    out
+}
+
+/// xxd -p, 30 bytes a line
+fn xxd_plain(bytes: &[u8], upper: bool) -> String {
+    bytes
+        .chunks(30)
+        .map(|line| {
+            let hex: String = line
+                .iter()
+                .map(|byte| match upper {
+                    true => format!("{:02X}", byte),
+                    false => format!("{:02x}", byte),
+                })
+                .collect();
+            format!("{}\r\n", hex)
+        })
+        .collect()
+}
+
+/// Lines of `bytes` from `offset`, identical full lines after the first
+/// replaced by one `*` unless `verbose`. `line` gets the offset and the bytes
+fn squeezed(
+    bytes: &[u8],
+    offset: usize,
+    verbose: bool,
+    mut line: impl FnMut(usize, &[u8]) -> String,
+) -> String {
+    let mut out = String::new();
+    let mut previous: Option<&[u8]> = None;
+    let mut skipping = false;
+    for (index, chunk) in bytes.chunks(LINE_BYTES).enumerate() {
+        if !verbose && previous == Some(chunk) && chunk.len() == LINE_BYTES {
+            if !skipping {
+                out.push_str("*\r\n");
+                skipping = true;
+            }
+            continue;
+        }
+        skipping = false;
+        previous = Some(chunk);
+        out.push_str(&line(offset + index * LINE_BYTES, chunk));
+    }
+    out
+}
+
+fn hexdump_canonical(bytes: &[u8], offset: usize, verbose: bool) -> String {
+    let mut out = squeezed(bytes, offset, verbose, |offset, chunk| {
+        let mut column = String::new();
+        for (position, byte) in chunk.iter().enumerate() {
+            column.push_str(&format!("{:02x} ", byte));
+            if position == 7 {
+                column.push(' ');
+            }
+        }
+        let ascii: String = chunk
+            .iter()
+            .map(|&byte| match byte {
+                0x20..=0x7e => byte as char,
+                _ => '.',
+            })
+            .collect();
+        format!("{:08x}  {:<49} |{}|\r\n", offset, column, ascii)
+    });
+    if !bytes.is_empty() {
+        out.push_str(&format!("{:08x}\r\n", offset + bytes.len()));
+    }
+    out
+}
+
+/// hexdump's other formats, `field` over the whole line
+fn hexdump_fields(
+    bytes: &[u8],
+    offset: usize,
+    field: Field,
+    width: usize,
+    verbose: bool,
+) -> String {
+    let mut out = squeezed(bytes, offset, verbose, |offset, chunk| {
+        let values: String = chunk
+            .chunks(field.size())
+            .map(|value| format!("{:>width$}", field.show(value), width = width))
+            .collect();
+        format!("{:07x}{}\r\n", offset, values)
+    });
+    if !bytes.is_empty() {
+        out.push_str(&format!("{:07x}\r\n", offset + bytes.len()));
+    }
+    out
+}
+
+fn od(bytes: &[u8], offset: usize, radix: Radix, fields: &[Field], verbose: bool) -> String {
+    let address_width = radix.show(0).len();
+    let mut out = squeezed(bytes, offset, verbose, |offset, chunk| {
+        let mut lines = String::new();
+        for (index, field) in fields.iter().enumerate() {
+            let address = match index {
+                0 => radix.show(offset),
+                _ => " ".repeat(address_width),
+            };
+            let values: String = chunk
+                .chunks(field.size())
+                .map(|value| format!("{:>width$}", field.show(value), width = field.od_width()))
+                .collect();
+            lines.push_str(&format!("{}{}\r\n", address, values));
+        }
+        lines
+    });
+    if radix != Radix::None {
+        out.push_str(&format!("{}\r\n", radix.show(offset + bytes.len())));
+    }
+    out
+}
+
+/// xxd, hexdump and od. Files are read as the bytes stored, piped input is
+/// the previous command's output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HexdumpCommand {
+    Xxd,
+    Hexdump,
+    Od,
+}
+
+impl HexdumpCommand {
+    fn usage(&self, message: String) -> CommandError {
+        let hint = match self {
+            HexdumpCommand::Xxd => "Usage:\r\n       xxd [options] [infile [outfile]]".to_string(),
+            HexdumpCommand::Hexdump => {
+                "Usage:\r\n hexdump [options] <file>...\r\n\r\nTry 'hexdump --help' for more information.".to_string()
+            }
+            HexdumpCommand::Od => "Try 'od --help' for more information.".to_string(),
+        };
+        CommandError::InvalidArguments(format!("{}: {}\r\n{}", self.name(), message, hint))
+    }
+
+    fn parse(&self, args: &[String]) -> Result<Options, CommandError> {
+        let mut options = Options {
+            layout: match self {
+                HexdumpCommand::Xxd => Layout::Xxd {
+                    cols: 16,
+                    group: 2,
+                    plain: false,
+                    upper: false,
+                },
+                HexdumpCommand::Hexdump => Layout::Hexdump(Field::Hex(2), 5),
+                HexdumpCommand::Od => Layout::Od(Radix::Octal, Vec::new()),
+            },
+            skip: 0,
+            length: None,
+            verbose: false,
+            files: Vec::new(),
+        };
+        let mut cols = None;
+        let mut group = None;
+        let mut index = 0;
+        while let Some(arg) = args.get(index) {
+            index += 1;
+            // xxd takes its options spelled out too
+            let arg = match (self, arg.as_str()) {
+                (HexdumpCommand::Xxd, "-ps" | "-postscript" | "-plain") => "-p",
+                (HexdumpCommand::Xxd, "-len") => "-l",
+                (HexdumpCommand::Xxd, "-seek") => "-s",
+                (HexdumpCommand::Xxd, "-cols") => "-c",
+                (HexdumpCommand::Xxd, "-groupsize") => "-g",
+                (HexdumpCommand::Xxd, "-upper") => "-u",
+                (_, arg) => arg,
+            };
+            if !arg.starts_with('-') || arg == "-" {
+                options.files.push(arg.to_string());
+                continue;
+            }
+            if arg == "--" {
+                options.files.extend(args[index..].iter().cloned());
+                break;
+            }
+            // Options that take a value, attached (`-n16`, `--skip-bytes=4`) or next
+            let (option, attached) = match arg.split_once('=') {
+                Some((option, value)) if arg.starts_with("--") => (option, Some(value)),
+                _ if !arg.starts_with("--") => {
+                    // The option letter may be any char, not just one byte
+                    let at = 1 + arg[1..].chars().next().map_or(0, char::len_utf8);
+                    match arg.split_at(at) {
+                        (option, "") => (option, None),
+                        (option, value) => (option, Some(value)),
+                    }
+                }
+                _ => (arg, None),
+            };
+            let takes_value = match self {
+                HexdumpCommand::Xxd => matches!(option, "-l" | "-s" | "-c" | "-g"),
+                HexdumpCommand::Hexdump => matches!(option, "-n" | "-s" | "--length" | "--skip"),
+                HexdumpCommand::Od => matches!(
+                    option,
+                    "-N" | "-j"
+                        | "-A"
+                        | "-t"
+                        | "--read-bytes"
+                        | "--skip-bytes"
+                        | "--address-radix"
+                        | "--format"
+                ),
+            };
+            if !takes_value {
+                self.flags(arg, &mut options)?;
+                continue;
+            }
+            let value = match attached {
+                Some(value) => value.to_string(),
+                None => {
+                    index += 1;
+                    args.get(index - 1).cloned().ok_or_else(|| {
+                        self.usage(format!(
+                            "option requires an argument -- '{}'",
+                            option.trim_start_matches('-')
+                        ))
+                    })?
+                }
+            };
+            let count = || {
+                parse_count(&value).ok_or_else(|| match self {
+                    HexdumpCommand::Od => self.usage(format!("invalid argument '{}'", value)),
+                    _ => self.usage(format!("invalid number: {}", value)),
+                })
+            };
+            match option {
+                "-l" | "-n" | "-N" | "--length" | "--read-bytes" => options.length = Some(count()?),
+                "-s" | "-j" | "--skip" | "--skip-bytes" => options.skip = count()?,
+                "-c" => cols = Some(count()?.clamp(1, 256)),
+                "-g" => group = Some(count()?),
+                "-A" | "--address-radix" => {
+                    let radix = match value.as_str() {
+                        "o" => Radix::Octal,
+                        "x" => Radix::Hex,
+                        "d" => Radix::Decimal,
+                        "n" => Radix::None,
+                        _ => {
+                            return Err(self.usage(format!(
+                                "invalid output address radix '{}'; it must be one character from [doxn]",
+                                value
+                            )));
+                        }
+                    };
+                    if let Layout::Od(current, _) = &mut options.layout {
+                        *current = radix;
+                    }
+                }
+                _ => {
+                    let field = od_type(&value)
+                        .ok_or_else(|| self.usage(format!("invalid type string '{}'", value)))?;
+                    if let Layout::Od(_, fields) = &mut options.layout {
+                        fields.push(field);
+                    }
+                }
+            }
+        }
+
+        match &mut options.layout {
+            Layout::Xxd {
+                cols: c,
+                group: g,
+                plain,
+                ..
+            } => {
+                if *plain {
+                    *c = 30;
+                } else {
+                    *c = cols.unwrap_or(16);
+                    *g = group.unwrap_or(2);
+                }
+            }
+            Layout::Od(_, fields) if fields.is_empty() => fields.push(Field::Octal(2)),
+            _ => {}
+        }
+        Ok(options)
+    }
+
+    /// Options without a value, `-Cv` style bundles included
+    fn flags(&self, arg: &str, options: &mut Options) -> Result<(), CommandError> {
+        let flags: Vec<char> = match arg {
+            "--canonical" => vec!['C'],
+            "--no-squeezing" | "--output-duplicates" => vec!['v'],
+            long if long.starts_with("--") => {
+                return Err(self.usage(format!("unrecognized option '{}'", long)));
+            }
+            short => short.chars().skip(1).collect(),
+        };
+        for flag in flags {
+            match (self, flag) {
+                (_, 'v') => options.verbose = true,
+                (HexdumpCommand::Xxd, 'p') => {
+                    if let Layout::Xxd { plain, .. } = &mut options.layout {
+                        *plain = true;
+                    }
+                }
+                (HexdumpCommand::Xxd, 'u') => {
+                    if let Layout::Xxd { upper, .. } = &mut options.layout {
+                        *upper = true;
+                    }
+                }
+                (HexdumpCommand::Hexdump, 'C') => options.layout = Layout::Canonical,
+                (HexdumpCommand::Hexdump, 'x' | 'o' | 'b' | 'c') => {
+                    options.layout = match flag {
+                        'x' => Layout::Hexdump(Field::Hex(2), 8),
+                        'o' => Layout::Hexdump(Field::Octal(2), 8),
+                        'b' => Layout::Hexdump(Field::Octal(1), 4),
+                        _ => Layout::Hexdump(Field::Char, 4),
+                    };
+                }
+                (HexdumpCommand::Od, 'b' | 'c' | 'd' | 'o' | 'x') => {
+                    let field = match flag {
+                        'b' => Field::Octal(1),
+                        'c' => Field::Char,
+                        'd' => Field::Unsigned(2),
+                        'o' => Field::Octal(2),
+                        _ => Field::Hex(2),
+                    };
+                    if let Layout::Od(_, fields) = &mut options.layout {
+                        fields.push(field);
+                    }
+                }
+                _ => return Err(self.usage(format!("invalid option -- '{}'", flag))),
+            }
+        }
+        Ok(())
+    }
+
+    /// Dump `piped` or the files named in `args`
+    pub(crate) async fn dump(
+        &self,
+        args: &[String],
+        piped: &[u8],
+        context: &CommandContext,
+    ) -> CommandResult {
+        let options = self.parse(args)?;
+
+        let mut input = Vec::new();
+        let mut errors = Vec::new();
+        let files = match self {
+            // The second operand is where xxd writes
+            HexdumpCommand::Xxd => &options.files[..options.files.len().min(1)],
+            _ => &options.files[..],
+        };
+        if files.is_empty() {
+            input.extend_from_slice(piped);
+        }
+        let fs = context.filesystem.read().await;
+        for path in files {
+            if path == "-" {
+                input.extend_from_slice(piped);
+                continue;
+            }
+            let absolute = match path.starts_with('/') {
+                true => path.clone(),
+                false => format!("{}/{}", context.cwd.trim_end_matches('/'), path),
+            };
+            match fs.follow_symlink(&absolute).map(|entry| entry.file_content) {
+                Ok(Some(FileContent::RegularFile(bytes))) => {
+                    context.note_read(&fs, &absolute);
+                    input.extend_from_slice(&bytes);
+                }
+                Ok(Some(FileContent::Directory(_))) => match self {
+                    HexdumpCommand::Od => {
+                        errors.push(format!("od: {}: read error: Is a directory", path))
+                    }
+                    _ => errors.push(format!("{}: {}: Is a directory", self.name(), path)),
+                },
+                _ => errors.push(format!(
+                    "{}: {}: No such file or directory",
+                    self.name(),
+                    path
+                )),
+            }
+        }
+
+        if !errors.is_empty() && (input.is_empty() || *self == HexdumpCommand::Xxd) {
+            if *self == HexdumpCommand::Hexdump {
+                errors.push("hexdump: all input file arguments failed".to_string());
+            }
+            return Err(CommandError::FilesystemError(errors.join("\r\n")));
+        }
+
+        let start = options.skip.min(input.len());
+        let end = match options.length {
+            Some(length) => start.saturating_add(length).min(input.len()),
+            None => input.len(),
+        };
+        let bytes = &input[start..end];
+        let offset = options.skip;
+        Ok(match &options.layout {
+            Layout::Xxd {
+                plain: true, upper, ..
+            } => xxd_plain(bytes, *upper),
+            Layout::Xxd {
+                cols, group, upper, ..
+            } => xxd(bytes, offset, *cols, *group, *upper),
+            Layout::Canonical => hexdump_canonical(bytes, offset, options.verbose),
+            Layout::Hexdump(field, width) => {
+                hexdump_fields(bytes, offset, *field, *width, options.verbose)
+            }
+            Layout::Od(radix, fields) => od(bytes, offset, *radix, fields, options.verbose),
+        })
+    }
+}
+
+#[async_trait]
+impl Command for HexdumpCommand {
+    fn name(&self) -> &'static str {
+        match self {
+            HexdumpCommand::Xxd => "xxd",
+            HexdumpCommand::Hexdump => "hexdump",
+            HexdumpCommand::Od => "od",
+        }
+    }
+
+    fn help(&self) -> String {
+        match self {
+            HexdumpCommand::Xxd => "Usage:\r\n\
+                \x20      xxd [options] [infile [outfile]]\r\n\
+                \x20   or\r\n\
+                \x20      xxd -r [-s [-]offset] [-c cols] [-ps] [infile [outfile]]\r\n\
+                Options:\r\n\
+                \x20   -c cols     format <cols> octets per line. Default 16 (-i: 12, -ps: 30).\r\n\
+                \x20   -g          number of octets per group in normal output. Default 2 (-e: 4).\r\n\
+                \x20   -h          print this summary.\r\n\
+                \x20   -l len      stop after <len> octets.\r\n\
+                \x20   -p          output in postscript plain hexdump style.\r\n\
+                \x20   -s [+][-]seek  start at <seek> bytes abs. (or +: rel.) infile offset.\r\n\
+                \x20   -u          use upper case hex letters.\r\n\
+                \x20   -v          show version: \"xxd V1.10 27oct98 by Juergen Weigert\".\r\n"
+                .to_string(),
+            HexdumpCommand::Hexdump => "\r\n\
+                Usage:\r\n\
+                \x20hexdump [options] <file>...\r\n\
+                \r\n\
+                Display file contents in hexadecimal, decimal, octal, or ascii.\r\n\
+                \r\n\
+                Options:\r\n\
+                \x20-b, --one-byte-octal      one-byte octal display\r\n\
+                \x20-c, --one-byte-char       one-byte character display\r\n\
+                \x20-C, --canonical           canonical hex+ASCII display\r\n\
+                \x20-o, --two-bytes-octal     two-byte octal display\r\n\
+                \x20-x, --two-bytes-hex       two-byte hexadecimal display\r\n\
+                \x20-n, --length <length>     interpret only length bytes of input\r\n\
+                \x20-s, --skip <offset>       skip offset bytes from the beginning\r\n\
+                \x20-v, --no-squeezing        output identical lines\r\n\
+                \r\n\
+                \x20-h, --help                display this help\r\n\
+                \x20-V, --version             display version\r\n"
+                .to_string(),
+            HexdumpCommand::Od => "Usage: od [OPTION]... [FILE]...\r\n\
+                Write an unambiguous representation, octal bytes by default,\r\n\
+                of FILE to standard output.  With more than one FILE argument,\r\n\
+                concatenate them in the listed order to form the input.\r\n\
+                \r\n\
+                With no FILE, or when FILE is -, read standard input.\r\n\
+                \r\n\
+                \x20 -A, --address-radix=RADIX   output format for file offsets; RADIX is one\r\n\
+                \x20                               of [doxn], for Decimal, Octal, Hex or None\r\n\
+                \x20 -j, --skip-bytes=BYTES      skip BYTES input bytes first\r\n\
+                \x20 -N, --read-bytes=BYTES      limit dump to BYTES input bytes\r\n\
+                \x20 -t, --format=TYPE           select output format or formats\r\n\
+                \x20 -v, --output-duplicates     do not use * to mark line suppression\r\n\
+                \x20     --help     display this help and exit\r\n\
+                \x20     --version  output version information and exit\r\n"
+                .to_string(),
+        }
+    }
+
+    fn version(&self) -> String {
+        match self {
+            HexdumpCommand::Xxd => "xxd V1.10 27oct98 by Juergen Weigert\r\n".to_string(),
+            HexdumpCommand::Hexdump => "hexdump from util-linux 2.34\r\n".to_string(),
+            HexdumpCommand::Od => "od (GNU coreutils) 8.32\r\n".to_string(),
+        }
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        match (self, args.first().map(String::as_str)) {
+            (_, Some("--help")) | (HexdumpCommand::Xxd, Some("-h")) => Ok(self.help()),
+            (_, Some("--version")) | (HexdumpCommand::Hexdump, Some("-V")) => Ok(self.version()),
+            (HexdumpCommand::Xxd, Some("-v" | "-version")) => Ok(self.version()),
+            _ => self.dump(args, &[], context).await,
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use crate::filesystem::fs2::{FileContent, FileSystem};
+use rand::{Rng, rng};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+/// A network interface of the fake host as shown by `ifconfig`
+#[derive(Clone, Debug)]
+pub struct NetworkInterface {
+    pub name: String,
+    pub mac: String,
+    pub ipv4: [u8; 4],
+}
+
+impl NetworkInterface {
+    /// IPv6 link-local address derived from the MAC (EUI-64), as the kernel assigns it
+    pub fn ipv6_link_local(&self) -> String {
+        let mut bytes: Vec<u8> = self
+            .mac
+            .split(':')
+            .filter_map(|b| u8::from_str_radix(b, 16).ok())
+            .collect();
+        if bytes.len() != 6 {
+            return "fe80::1".to_string();
+        }
+        bytes[0] ^= 0x02;
+        bytes.splice(3..3, [0xff, 0xfe]);
+        format!(
+            "fe80::{:x}:{:x}:{:x}:{:x}",
+            u16::from_be_bytes([bytes[0], bytes[1]]),
+            u16::from_be_bytes([bytes[2], bytes[3]]),
+            u16::from_be_bytes([bytes[4], bytes[5]]),
+            u16::from_be_bytes([bytes[6], bytes[7]])
+        )
+    }
+}
+
+/// Fingerprint of one of the server's SSH host keys
+#[derive(Clone, Debug)]
+pub struct HostKeyFingerprint {
+    /// Key type as ssh-keygen prints it in parentheses (e.g. "ED25519")
+    pub key_type: String,
+    /// Name used in /etc/ssh/ssh_host_<name>_key (e.g. "ed25519")
+    pub file_name: String,
+    pub bits: u32,
+    /// e.g. "SHA256:uNiVztksCsDhcc0u9e8BujQXVUpKZIDTMczCvj3tD2s"
+    pub sha256: String,
+    /// Public key in authorized_keys form without a comment, e.g. "ssh-ed25519 AAAAC3Nza..."
+    pub public_key: String,
+}
+
+/// Host specific values that several commands show: machine-id, interface MACs
+/// and host key fingerprints. Created once per process and shared by every
+/// session, so `hostnamectl`, `ifconfig`, `ssh-keygen -l` and `/etc/machine-id`
+/// agree with each other and don't change between commands.
+#[derive(Clone, Debug)]
+pub struct HostIdentity {
+    pub machine_id: String,
+    /// UUID of the root filesystem in /etc/fstab
+    pub root_fs_uuid: String,
+    /// Changes on every restart like it would on a reboot
+    pub boot_id: String,
+    pub interfaces: Vec<NetworkInterface>,
+    /// Fingerprints of the keys the server really presents, so they match what the client saw
+    pub host_keys: Vec<HostKeyFingerprint>,
+}
+
+impl Default for HostIdentity {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl HostIdentity {
+    /// Random values, or values derived from `seed` so a restart (or a second
+    /// honeypot with the same seed) shows the same host
+    pub fn new(seed: Option<&str>) -> Self {
+        let derive = |label: &str| -> [u8; 32] {
+            let mut bytes = [0u8; 32];
+            match seed {
+                Some(seed) => bytes.copy_from_slice(
+                    &Sha256::new()
+                        .chain_update(seed.as_bytes())
+                        .chain_update([0])
+                        .chain_update(label.as_bytes())
+                        .finalize(),
+                ),
+                None => rng().fill_bytes(&mut bytes),
+            }
+            bytes
+        };
+
+        let machine_id = hex::encode(&derive("machine-id")[..16]);
+        let root_fs = hex::encode(&derive("root-fs")[..16]);
+        let mut boot_id = [0u8; 16];
+        rng().fill_bytes(&mut boot_id);
+
+        let eth0 = derive("eth0");
+        Self {
+            machine_id,
+            root_fs_uuid: format!(
+                "{}-{}-{}-{}-{}",
+                &root_fs[..8],
+                &root_fs[8..12],
+                &root_fs[12..16],
+                &root_fs[16..20],
+                &root_fs[20..]
+            ),
+            boot_id: hex::encode(boot_id),
+            interfaces: vec![NetworkInterface {
+                name: "eth0".to_string(),
+                // 52:54:00 is the QEMU/KVM prefix, matching "Virtualization: kvm"
+                mac: format!("52:54:00:{:02x}:{:02x}:{:02x}", eth0[0], eth0[1], eth0[2]),
+                ipv4: [10, eth0[3] % 32, eth0[4], eth0[5] % 250 + 2],
+            }],
+            host_keys: Vec::new(),
+        }
+    }
+
+    /// Fingerprint of the host key file `path` refers to (private or .pub)
+    pub fn host_key_for_path(&self, path: &str) -> Option<&HostKeyFingerprint> {
+        let file = path.rsplit('/').next()?.trim_end_matches(".pub");
+        self.host_keys
+            .iter()
+            .find(|key| file == format!("ssh_host_{}_key", key.file_name))
+    }
+
+    /// Write /etc/machine-id and /sys/class/net/<interface>/address into the
+    /// virtual filesystem, overriding whatever the base image shipped
+    pub fn write_files(&self, fs: &mut FileSystem) -> std::io::Result<()> {
+        let mut files = vec![("/etc/machine-id".to_string(), self.machine_id.clone())];
+        for interface in &self.interfaces {
+            files.push((
+                format!("/sys/class/net/{}/address", interface.name),
+                interface.mac.clone(),
+            ));
+        }
+
+        for (path, content) in files {
+            let content = format!("{}\n", content);
+            let parent = path.rsplit_once('/').map_or("", |(parent, _)| parent);
+            let mut dir = String::new();
+            for component in parent.split('/').filter(|c| !c.is_empty()) {
+                dir.push('/');
+                dir.push_str(component);
+                if fs.get_file(&dir).is_err() {
+                    fs.create_directory(&dir)?.inode.i_mode = 0o755;
+                }
+            }
+            if fs.get_file(&path).is_ok() {
+                fs.remove_file(&path)?;
+            }
+            let entry = fs.create_file(&path)?;
+            entry.inode.i_mode = 0o444;
+            entry.inode.i_size_lo = content.len() as u32;
+            entry.content = Some(FileContent::RegularFile(Arc::new(content.into_bytes())));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use async_trait::async_trait;
+
+/// hostnamectl command, reporting the configured hostname and the shared OS identity
+pub struct HostnamectlCommand;
+
+#[async_trait]
+impl Command for HostnamectlCommand {
+    fn name(&self) -> &'static str {
+        "hostnamectl"
+    }
+
+    fn help(&self) -> String {
+        "hostnamectl [OPTIONS...] COMMAND ...\r\n\
+        \r\n\
+        Query or change system hostname.\r\n\
+        \r\n\
+        Commands:\r\n\
+        \x20 status                 Show current hostname settings\r\n\
+        \x20 set-hostname NAME      Set system hostname\r\n\
+        \x20 set-icon-name NAME     Set icon name for host\r\n\
+        \x20 set-chassis NAME       Set chassis type for host\r\n\
+        \x20 set-deployment NAME    Set deployment environment for host\r\n\
+        \x20 set-location NAME      Set location for host\r\n\
+        \r\n\
+        Options:\r\n\
+        \x20 -h --help              Show this help\r\n\
+        \x20    --version           Show package version\r\n\
+        \x20    --no-ask-password   Do not prompt for password\r\n\
+        \x20 -H --host=[USER@]HOST  Operate on remote host\r\n\
+        \x20 -M --machine=CONTAINER Operate on local container\r\n\
+        \x20    --transient         Only set transient hostname\r\n\
+        \x20    --static            Only set static hostname\r\n\
+        \x20    --pretty            Only set pretty hostname\r\n"
+            .to_string()
+    }
+
+    fn version(&self) -> String {
+        "systemd 245 (245.4-4ubuntu3.15)\r\n\
+        +PAM +AUDIT +SELINUX +IMA +APPARMOR +SMACK +SYSVINIT +UTMP +LIBCRYPTSETUP +GCRYPT +GNUTLS +ACL +XZ +LZ4 +SECCOMP +BLKID +ELFUTILS +KMOD +IDN2 -IDN +PCRE2 default-hierarchy=hybrid\r\n"
+            .to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        if args.iter().any(|a| a == "-h" || a == "--help") {
+            return Ok(self.help());
+        }
+        if args.iter().any(|a| a == "--version") {
+            return Ok(self.version());
+        }
+
+        let command = args.iter().find(|a| !a.starts_with('-'));
+        match command.map(|c| c.as_str()) {
+            None | Some("status") => {}
+            Some(c) if c.starts_with("set-") => {
+                // Changing the hostname needs polkit authentication for non-root users
+                if context.username != "root" {
+                    return Err(CommandError::PermissionDenied(
+                        "Could not set property: Interactive authentication required.".to_string(),
+                    ));
+                }
+                return Ok(String::new());
+            }
+            Some(c) => {
+                return Err(CommandError::InvalidArguments(format!(
+                    "Unknown operation {}",
+                    c
+                )));
+            }
+        }
+
+        let os = &context.os;
+        Ok(format!(
+            "   Static hostname: {}\r\n\
+            \x20        Icon name: computer-vm\r\n\
+            \x20          Chassis: vm\r\n\
+            \x20       Machine ID: {}\r\n\
+            \x20          Boot ID: {}\r\n\
+            \x20   Virtualization: kvm\r\n\
+            \x20 Operating System: {}\r\n\
+            \x20           Kernel: {} {}\r\n\
+            \x20     Architecture: {}\r\n",
+            context.hostname,
+            context.host.machine_id,
+            context.host.boot_id,
+            os.description,
+            os.kernel_name,
+            os.kernel_release,
+            os.architecture()
+        ))
+    }
+}
@@ -0,0 +1,121 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use super::host_identity::NetworkInterface;
+use async_trait::async_trait;
+
+/// ifconfig from net-tools, listing the interfaces of the shared host identity
+pub struct IfconfigCommand;
+
+#[async_trait]
+impl Command for IfconfigCommand {
+    fn name(&self) -> &'static str {
+        "ifconfig"
+    }
+
+    fn aliases(&self) -> Vec<&'static str> {
+        vec!["/sbin/ifconfig"]
+    }
+
+    fn help(&self) -> String {
+        "Usage:\r\n\
+        \x20 ifconfig [-a] [-v] [-s] <interface> [[<AF>] <address>]\r\n\
+        \x20 [add <address>[/<prefixlen>]]\r\n\
+        \x20 [del <address>[/<prefixlen>]]\r\n\
+        \x20 [[-]broadcast [<address>]]  [[-]pointopoint [<address>]]\r\n\
+        \x20 [netmask <address>]  [dstaddr <address>]  [tunnel <address>]\r\n\
+        \x20 [mtu <NN>]\r\n\
+        \x20 [up|down] ...\r\n"
+            .to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        if args.iter().any(|a| a == "-h" || a == "--help") {
+            return Ok(self.help());
+        }
+
+        let names: Vec<&String> = args.iter().filter(|a| !a.starts_with('-')).collect();
+        match names.as_slice() {
+            [] => {
+                let mut output: Vec<String> = context
+                    .host
+                    .interfaces
+                    .iter()
+                    .map(interface_block)
+                    .collect();
+                output.push(loopback_block());
+                Ok(output.join("\r\n"))
+            }
+            [name] if name.as_str() == "lo" => Ok(loopback_block()),
+            [name] => match context.host.interfaces.iter().find(|i| &i.name == *name) {
+                Some(interface) => Ok(interface_block(interface)),
+                None => Err(CommandError::ExecutionError(format!(
+                    "{}: error fetching interface information: Device not found",
+                    name
+                ))),
+            },
+            // Changing addresses needs CAP_NET_ADMIN
+            [..] => Err(CommandError::PermissionDenied(
+                "SIOCSIFADDR: Operation not permitted".to_string(),
+            )),
+        }
+    }
+}
+
+fn interface_block(interface: &NetworkInterface) -> String {
+    let [a, b, c, d] = interface.ipv4;
+    // Counters only need to look lived-in and stay the same between calls
+    let rx_packets = 180_000 + u64::from(c) * 1_311 + u64::from(d) * 17;
+    let tx_packets = rx_packets * 3 / 5;
+    let rx_bytes = rx_packets * 1_283;
+    let tx_bytes = tx_packets * 412;
+    format!(
+        "{}: flags=4163<UP,BROADCAST,RUNNING,MULTICAST>  mtu 1500\r\n\
+        \x20       inet {}.{}.{}.{}  netmask 255.255.255.0  broadcast {}.{}.{}.255\r\n\
+        \x20       inet6 {}  prefixlen 64  scopeid 0x20<link>\r\n\
+        \x20       ether {}  txqueuelen 1000  (Ethernet)\r\n\
+        \x20       RX packets {}  bytes {} ({})\r\n\
+        \x20       RX errors 0  dropped 0  overruns 0  frame 0\r\n\
+        \x20       TX packets {}  bytes {} ({})\r\n\
+        \x20       TX errors 0  dropped 0 overruns 0  carrier 0  collisions 0\r\n",
+        interface.name,
+        a,
+        b,
+        c,
+        d,
+        a,
+        b,
+        c,
+        interface.ipv6_link_local(),
+        interface.mac,
+        rx_packets,
+        rx_bytes,
+        human_bytes(rx_bytes),
+        tx_packets,
+        tx_bytes,
+        human_bytes(tx_bytes)
+    )
+}
+
+fn loopback_block() -> String {
+    "lo: flags=73<UP,LOOPBACK,RUNNING>  mtu 65536\r\n\
+    \x20       inet 127.0.0.1  netmask 255.0.0.0\r\n\
+    \x20       inet6 ::1  prefixlen 128  scopeid 0x10<host>\r\n\
+    \x20       loop  txqueuelen 1000  (Local Loopback)\r\n\
+    \x20       RX packets 4211  bytes 389214 (389.2 KB)\r\n\
+    \x20       RX errors 0  dropped 0  overruns 0  frame 0\r\n\
+    \x20       TX packets 4211  bytes 389214 (389.2 KB)\r\n\
+    \x20       TX errors 0  dropped 0 overruns 0  carrier 0  collisions 0\r\n"
+        .to_string()
+}
+
+/// Byte counts the way net-tools abbreviates them (decimal units, one decimal)
+fn human_bytes(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < units.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, units[unit])
+}
@@ -0,0 +1,508 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::{CommandContext, ShellEvent};
+use super::firewall::{Chain, Rule, Table};
+use async_trait::async_trait;
+
+const VERSION: &str = "iptables v1.8.4 (legacy)";
+
+/// Targets iptables can load besides user defined chains
+const TARGETS: &[&str] = &[
+    "ACCEPT",
+    "DROP",
+    "REJECT",
+    "RETURN",
+    "LOG",
+    "MASQUERADE",
+    "DNAT",
+    "SNAT",
+    "REDIRECT",
+    "MARK",
+    "NOTRACK",
+];
+
+fn usage_error(message: String) -> CommandError {
+    CommandError::InvalidArguments(format!(
+        "{}: {}\r\nTry `iptables -h' or 'iptables --help' for more information.",
+        VERSION, message
+    ))
+}
+
+fn failure(message: &str) -> CommandError {
+    CommandError::ExecutionError(format!("iptables: {}", message))
+}
+
+/// What to do, the chain it applies to comes with it
+#[derive(Debug, PartialEq)]
+enum Operation {
+    List(Option<String>),
+    ListRules(Option<String>),
+    Append(String),
+    Insert(String, usize),
+    Delete(String, Option<usize>),
+    Check(String),
+    Flush(Option<String>),
+    Zero,
+    Policy(String, String),
+    NewChain(String),
+    DeleteChain(Option<String>),
+}
+
+impl Operation {
+    fn modifies(&self) -> bool {
+        !matches!(
+            self,
+            Operation::List(_) | Operation::ListRules(_) | Operation::Check(_) | Operation::Zero
+        )
+    }
+}
+
+struct Invocation {
+    table: String,
+    operation: Operation,
+    rule: Rule,
+    numeric: bool,
+    verbose: bool,
+    line_numbers: bool,
+}
+
+/// `-nvL` is `-n -v -L`
+fn expand_clusters(args: &[String]) -> Vec<String> {
+    args.iter()
+        .flat_map(|arg| {
+            let flags = arg.strip_prefix('-').unwrap_or_default();
+            if flags.len() > 1
+                && !flags.starts_with('-')
+                && flags.chars().all(|flag| "nvxLSFZ".contains(flag))
+            {
+                flags.chars().map(|flag| format!("-{}", flag)).collect()
+            } else {
+                vec![arg.clone()]
+            }
+        })
+        .collect()
+}
+
+/// Only one of -L, -A, -D and the like per invocation
+fn set_operation(current: &mut Option<Operation>, new: Operation) -> Result<(), CommandError> {
+    if current.is_some() {
+        return Err(usage_error(
+            "Cannot use more than one command at a time".to_string(),
+        ));
+    }
+    *current = Some(new);
+    Ok(())
+}
+
+/// `-s 203.0.113.7` is stored as iptables shows it, with the prefix length
+fn with_prefix(address: String) -> String {
+    if address.contains('/') {
+        address
+    } else {
+        format!("{}/32", address)
+    }
+}
+
+fn parse(args: &[String]) -> Result<Invocation, CommandError> {
+    let args = expand_clusters(args);
+    let mut table = "filter".to_string();
+    let mut operation = None;
+    let mut rule = Rule::new();
+    let (mut numeric, mut verbose, mut line_numbers) = (false, false, false);
+    let mut args = args.iter().peekable();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .cloned()
+                .ok_or_else(|| usage_error(format!("option \"{}\" requires an argument", name)))
+        };
+        match arg.as_str() {
+            "-t" | "--table" => table = value(arg)?,
+            "-n" | "--numeric" => numeric = true,
+            "-v" | "--verbose" => verbose = true,
+            "--line-numbers" => line_numbers = true,
+            "-x" | "--exact" | "!" => {}
+            "-w" | "--wait" | "-W" | "--wait-interval" => {
+                args.next_if(|next| next.parse::<u32>().is_ok());
+            }
+            "-L" | "--list" | "-S" | "--list-rules" | "-F" | "--flush" | "-X"
+            | "--delete-chain" => {
+                let chain = args.next_if(|next| !next.starts_with('-')).cloned();
+                let new = match arg.as_str() {
+                    "-L" | "--list" => Operation::List(chain),
+                    "-S" | "--list-rules" => Operation::ListRules(chain),
+                    "-F" | "--flush" => Operation::Flush(chain),
+                    _ => Operation::DeleteChain(chain),
+                };
+                set_operation(&mut operation, new)?;
+            }
+            "-Z" | "--zero" => {
+                args.next_if(|next| !next.starts_with('-'));
+                set_operation(&mut operation, Operation::Zero)?;
+            }
+            "-A" | "--append" => {
+                let chain = value(arg)?;
+                set_operation(&mut operation, Operation::Append(chain))?;
+            }
+            "-C" | "--check" => {
+                let chain = value(arg)?;
+                set_operation(&mut operation, Operation::Check(chain))?;
+            }
+            "-N" | "--new-chain" => {
+                let chain = value(arg)?;
+                set_operation(&mut operation, Operation::NewChain(chain))?;
+            }
+            "-I" | "--insert" | "-D" | "--delete" => {
+                let chain = value(arg)?;
+                let number = args
+                    .next_if(|next| next.parse::<usize>().is_ok())
+                    .and_then(|next| next.parse().ok());
+                let new = if matches!(arg.as_str(), "-I" | "--insert") {
+                    Operation::Insert(chain, number.unwrap_or(1))
+                } else {
+                    Operation::Delete(chain, number)
+                };
+                set_operation(&mut operation, new)?;
+            }
+            "-P" | "--policy" => {
+                let chain = value(arg)?;
+                let target = value(arg)?;
+                set_operation(&mut operation, Operation::Policy(chain, target))?;
+            }
+            "-p" | "--protocol" => rule.protocol = value(arg)?.to_lowercase(),
+            "-s" | "--source" | "--src" => rule.source = with_prefix(value(arg)?),
+            "-d" | "--destination" | "--dst" => rule.destination = with_prefix(value(arg)?),
+            "-i" | "--in-interface" => rule.in_interface = Some(value(arg)?),
+            "-o" | "--out-interface" => rule.out_interface = Some(value(arg)?),
+            "-j" | "--jump" | "-g" | "--goto" => rule.target = value(arg)?,
+            "--dport" | "--destination-port" => rule.destination_port = Some(value(arg)?),
+            "--sport" | "--source-port" => rule.source_port = Some(value(arg)?),
+            "--state" | "--ctstate" => rule.state = Some(value(arg)?),
+            // Match modules and target options that don't show up in the listing
+            "-m" | "--match" | "--comment" | "--to-destination" | "--to-source" | "--to-ports"
+            | "--reject-with" | "--log-prefix" | "--log-level" | "--set-mark" | "--icmp-type"
+            | "--limit" | "--limit-burst" | "--dports" | "--sports" => {
+                value(arg)?;
+            }
+            unknown if unknown.starts_with('-') => {
+                return Err(usage_error(format!("unknown option \"{}\"", unknown)));
+            }
+            extra => return Err(usage_error(format!("Bad argument `{}'", extra))),
+        }
+    }
+    Ok(Invocation {
+        table,
+        operation: operation.ok_or_else(|| usage_error("no command specified".to_string()))?,
+        rule,
+        numeric,
+        verbose,
+        line_numbers,
+    })
+}
+
+fn list(table: &Table, chain: Option<&str>, invocation: &Invocation) -> CommandResult {
+    let chains: Vec<&Chain> = match chain {
+        Some(name) => vec![
+            table
+                .chain(name)
+                .ok_or_else(|| failure("No chain/target/match by that name."))?,
+        ],
+        None => table.chains.iter().collect(),
+    };
+    let mut sections = Vec::new();
+    for chain in chains {
+        let mut section = match (&chain.policy, invocation.verbose) {
+            (Some(policy), false) => format!("Chain {} (policy {})\r\n", chain.name, policy),
+            (Some(policy), true) => format!(
+                "Chain {} (policy {} 0 packets, 0 bytes)\r\n",
+                chain.name, policy
+            ),
+            (None, _) => format!(
+                "Chain {} ({} references)\r\n",
+                chain.name,
+                table.references(&chain.name)
+            ),
+        };
+        if invocation.line_numbers {
+            section.push_str("num  ");
+        }
+        if invocation.verbose {
+            section.push_str(
+                " pkts bytes target     prot opt in     out     source               destination         \r\n",
+            );
+        } else {
+            section.push_str("target     prot opt source               destination         \r\n");
+        }
+        for (index, rule) in chain.rules.iter().enumerate() {
+            if invocation.line_numbers {
+                section.push_str(&format!("{:<4} ", index + 1));
+            }
+            section.push_str(&rule.listing(invocation.numeric, invocation.verbose));
+            section.push_str("\r\n");
+        }
+        sections.push(section);
+    }
+    Ok(sections.join("\r\n"))
+}
+
+fn list_rules(table: &Table, chain: Option<&str>) -> CommandResult {
+    let chains: Vec<&Chain> = match chain {
+        Some(name) => vec![
+            table
+                .chain(name)
+                .ok_or_else(|| failure("No chain/target/match by that name."))?,
+        ],
+        None => table.chains.iter().collect(),
+    };
+    let mut output = String::new();
+    for chain in &chains {
+        match &chain.policy {
+            Some(policy) => output.push_str(&format!("-P {} {}\r\n", chain.name, policy)),
+            None => output.push_str(&format!("-N {}\r\n", chain.name)),
+        }
+    }
+    for chain in &chains {
+        for rule in &chain.rules {
+            output.push_str(&rule.specification(&chain.name));
+            output.push_str("\r\n");
+        }
+    }
+    Ok(output)
+}
+
+/// Change `table` as `invocation` asks
+fn modify(table: &mut Table, invocation: Invocation) -> CommandResult {
+    let rule = invocation.rule;
+    let known_target = |table: &Table, target: &str| {
+        target.is_empty() || TARGETS.contains(&target) || table.chain(target).is_some()
+    };
+    if matches!(
+        invocation.operation,
+        Operation::Append(_) | Operation::Insert(..) | Operation::Check(_)
+    ) && !known_target(table, &rule.target)
+    {
+        return Err(usage_error(format!(
+            "Couldn't load target `{}':No such file or directory\r\n",
+            rule.target
+        )));
+    }
+    match invocation.operation {
+        Operation::Append(chain) => {
+            let chain = table
+                .chain_mut(&chain)
+                .ok_or_else(|| failure("No chain/target/match by that name."))?;
+            chain.rules.push(rule);
+            Ok(String::new())
+        }
+        Operation::Insert(chain, number) => {
+            let chain = table
+                .chain_mut(&chain)
+                .ok_or_else(|| failure("No chain/target/match by that name."))?;
+            if number == 0 || number > chain.rules.len() + 1 {
+                return Err(failure("Index of insertion too big."));
+            }
+            chain.rules.insert(number - 1, rule);
+            Ok(String::new())
+        }
+        Operation::Delete(chain, number) => {
+            let chain = table
+                .chain_mut(&chain)
+                .ok_or_else(|| failure("No chain/target/match by that name."))?;
+            let index = match number {
+                Some(number) if number == 0 || number > chain.rules.len() => {
+                    return Err(failure("Index of deletion too big."));
+                }
+                Some(number) => number - 1,
+                None => chain
+                    .rules
+                    .iter()
+                    .position(|existing| *existing == rule)
+                    .ok_or_else(|| {
+                        failure("Bad rule (does a matching rule exist in that chain?).")
+                    })?,
+            };
+            chain.rules.remove(index);
+            Ok(String::new())
+        }
+        Operation::Check(chain) => {
+            let chain = table
+                .chain(&chain)
+                .ok_or_else(|| failure("No chain/target/match by that name."))?;
+            if chain.rules.contains(&rule) {
+                Ok(String::new())
+            } else {
+                Err(failure(
+                    "Bad rule (does a matching rule exist in that chain?).",
+                ))
+            }
+        }
+        Operation::Flush(Some(chain)) => {
+            table
+                .chain_mut(&chain)
+                .ok_or_else(|| failure("No chain/target/match by that name."))?
+                .rules
+                .clear();
+            Ok(String::new())
+        }
+        Operation::Flush(None) => {
+            table
+                .chains
+                .iter_mut()
+                .for_each(|chain| chain.rules.clear());
+            Ok(String::new())
+        }
+        Operation::Policy(chain, policy) => {
+            if !matches!(policy.as_str(), "ACCEPT" | "DROP") {
+                return Err(failure(
+                    "Bad policy name. Run `dmesg' for more information.",
+                ));
+            }
+            match table.chain_mut(&chain) {
+                Some(Chain {
+                    policy: Some(current),
+                    ..
+                }) => {
+                    *current = policy;
+                    Ok(String::new())
+                }
+                _ => Err(failure("Bad built-in chain name.")),
+            }
+        }
+        Operation::NewChain(chain) => {
+            if table.chain(&chain).is_some() || TARGETS.contains(&chain.as_str()) {
+                return Err(failure("Chain already exists."));
+            }
+            table.chains.push(Chain {
+                name: chain,
+                policy: None,
+                rules: Vec::new(),
+            });
+            Ok(String::new())
+        }
+        Operation::DeleteChain(Some(chain)) => {
+            match table.chain(&chain) {
+                None => return Err(failure("No chain/target/match by that name.")),
+                Some(Chain {
+                    policy: Some(_), ..
+                }) => return Err(failure("Invalid argument.")),
+                Some(existing) if !existing.rules.is_empty() => {
+                    return Err(failure("Directory not empty."));
+                }
+                Some(_) if table.references(&chain) > 0 => {
+                    return Err(failure("Too many links."));
+                }
+                Some(_) => {}
+            }
+            table.chains.retain(|existing| existing.name != chain);
+            Ok(String::new())
+        }
+        Operation::DeleteChain(None) => {
+            table.chains.retain(|chain| chain.policy.is_some());
+            Ok(String::new())
+        }
+        Operation::List(_) | Operation::ListRules(_) | Operation::Zero => Ok(String::new()),
+    }
+}
+
+/// iptables command. Lists and changes the session's netfilter tables, which
+/// start out as a fresh server's, and reports every change as a
+/// ShellEvent::FirewallChange
+pub struct IptablesCommand;
+
+#[async_trait]
+impl Command for IptablesCommand {
+    fn name(&self) -> &'static str {
+        "iptables"
+    }
+
+    fn aliases(&self) -> Vec<&'static str> {
+        vec!["iptables-legacy"]
+    }
+
+    fn help(&self) -> String {
+        format!(
+            "{}\r\n\
+            \r\n\
+            Usage: iptables -[ACD] chain rule-specification [options]\r\n\
+            \x20      iptables -I chain [rulenum] rule-specification [options]\r\n\
+            \x20      iptables -D chain rulenum [options]\r\n\
+            \x20      iptables -[LS] [chain [rulenum]] [options]\r\n\
+            \x20      iptables -[FZ] [chain] [options]\r\n\
+            \x20      iptables -[NX] chain\r\n\
+            \x20      iptables -P chain target [options]\r\n\
+            \x20      iptables -h (print this help information)\r\n\
+            \r\n\
+            Commands:\r\n\
+            Either long or short options are allowed.\r\n\
+            \x20 --append  -A chain\t\tAppend to chain\r\n\
+            \x20 --check   -C chain\t\tCheck for the existence of a rule\r\n\
+            \x20 --delete  -D chain\t\tDelete matching rule from chain\r\n\
+            \x20 --delete  -D chain rulenum\r\n\
+            \x20\t\t\t\tDelete rule rulenum (1 = first) from chain\r\n\
+            \x20 --insert  -I chain [rulenum]\r\n\
+            \x20\t\t\t\tInsert in chain as rulenum (default 1=first)\r\n\
+            \x20 --list    -L [chain [rulenum]]\r\n\
+            \x20\t\t\t\tList the rules in a chain or all chains\r\n\
+            \x20 --list-rules -S [chain [rulenum]]\r\n\
+            \x20\t\t\t\tPrint the rules in a chain or all chains\r\n\
+            \x20 --flush   -F [chain]\t\tDelete all rules in  chain or all chains\r\n\
+            \x20 --zero    -Z [chain [rulenum]]\r\n\
+            \x20\t\t\t\tZero counters in chain or all chains\r\n\
+            \x20 --new     -N chain\t\tCreate a new user-defined chain\r\n\
+            \x20 --delete-chain\r\n\
+            \x20           -X [chain]\t\tDelete a user-defined chain\r\n\
+            \x20 --policy  -P chain target\r\n\
+            \x20\t\t\t\tChange policy on chain to target\r\n",
+            VERSION
+        )
+    }
+
+    fn version(&self) -> String {
+        format!("{}\r\n", VERSION)
+    }
+
+    fn requires_privileges(&self) -> bool {
+        true
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        if args.iter().any(|a| a == "-h" || a == "--help") {
+            return Ok(self.help());
+        }
+        if args.iter().any(|a| a == "-V" || a == "--version") {
+            return Ok(self.version());
+        }
+        let invocation = parse(args)?;
+        let root = context.username == "root";
+        if invocation.operation.modifies() {
+            context.push_event(ShellEvent::FirewallChange {
+                tool: "iptables",
+                command: std::iter::once("iptables")
+                    .chain(args.iter().map(String::as_str))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                permitted: root,
+            });
+        }
+        if !root {
+            return Err(CommandError::PermissionDenied(format!(
+                "{}: can't initialize iptables table `{}': Permission denied (you must be root)\r\n\
+                Perhaps iptables or your kernel needs to be upgraded.",
+                VERSION, invocation.table
+            )));
+        }
+
+        let mut firewall = context.firewall.write().await;
+        let Some(table) = firewall.table_mut(&invocation.table) else {
+            return Err(CommandError::ExecutionError(format!(
+                "{}: can't initialize iptables table `{}': Table does not exist (do you need to insmod?)\r\n\
+                Perhaps iptables or your kernel needs to be upgraded.",
+                VERSION, invocation.table
+            )));
+        };
+        match &invocation.operation {
+            Operation::List(chain) => list(table, chain.as_deref(), &invocation),
+            Operation::ListRules(chain) => list_rules(table, chain.as_deref()),
+            _ => modify(table, invocation),
+        }
+    }
+}
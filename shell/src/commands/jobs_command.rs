@@ -0,0 +1,260 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use super::process_table::{Job, ProcessTable};
+use async_trait::async_trait;
+
+/// `+` for the current job, `-` for the previous one
+fn job_marker(table: &ProcessTable, job: &Job) -> char {
+    let mut jobs = table.jobs().iter().rev();
+    if jobs.next().is_some_and(|j| j.number == job.number) {
+        '+'
+    } else if jobs.next().is_some_and(|j| j.number == job.number) {
+        '-'
+    } else {
+        ' '
+    }
+}
+
+/// Resolve a job spec, `current` when none was given
+fn find_job<'a>(
+    builtin: &str,
+    table: &'a ProcessTable,
+    spec: Option<&str>,
+) -> Result<&'a Job, CommandError> {
+    table.find_job(spec.unwrap_or("%+")).ok_or_else(|| {
+        CommandError::ExecutionError(format!(
+            "bash: {}: {}: no such job",
+            builtin,
+            spec.unwrap_or("current")
+        ))
+    })
+}
+
+/// Jobs builtin: lists the jobs the session sent to the background with `&`
+pub struct JobsCommand;
+
+#[async_trait]
+impl Command for JobsCommand {
+    fn name(&self) -> &'static str {
+        "jobs"
+    }
+
+    fn help(&self) -> String {
+        "jobs: jobs [-lnprs] [jobspec ...] or jobs -x command [args]\r\n\
+        \x20   Display status of jobs.\r\n\
+        \r\n\
+        \x20   Lists the active jobs.  JOBSPEC restricts output to that job.\r\n\
+        \x20   Without options, the status of all active jobs is displayed.\r\n"
+            .to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        let mut long = false;
+        let mut pids_only = false;
+        let mut stopped_only = false;
+        let mut specs = Vec::new();
+        for arg in args {
+            match arg.strip_prefix('-') {
+                Some(flags) if !flags.is_empty() => {
+                    for flag in flags.chars() {
+                        match flag {
+                            'l' => long = true,
+                            'p' => pids_only = true,
+                            's' => stopped_only = true,
+                            'n' | 'r' => {}
+                            _ => {
+                                return Err(CommandError::InvalidArguments(format!(
+                                    "bash: jobs: -{}: invalid option\r\n\
+                                    jobs: usage: jobs [-lnprs] [jobspec ...] or jobs -x command [args]",
+                                    flag
+                                )));
+                            }
+                        }
+                    }
+                }
+                _ => specs.push(arg.as_str()),
+            }
+        }
+
+        let table = context.processes.read().await;
+        let mut jobs: Vec<&Job> = Vec::new();
+        for spec in specs {
+            jobs.push(find_job("jobs", &table, Some(spec))?);
+        }
+        if jobs.is_empty() {
+            jobs = table.jobs().iter().collect();
+        }
+        // Nothing is ever stopped, background jobs run until they're killed
+        if stopped_only {
+            return Ok(String::new());
+        }
+
+        let mut output = String::new();
+        for job in jobs {
+            if pids_only {
+                output.push_str(&format!("{}\r\n", job.pid));
+            } else if long {
+                output.push_str(&format!(
+                    "[{}]{} {} {:<24}{} &\r\n",
+                    job.number,
+                    job_marker(&table, job),
+                    job.pid,
+                    "Running",
+                    job.command
+                ));
+            } else {
+                output.push_str(&format!(
+                    "[{}]{}  {:<24}{} &\r\n",
+                    job.number,
+                    job_marker(&table, job),
+                    "Running",
+                    job.command
+                ));
+            }
+        }
+        Ok(output)
+    }
+}
+
+/// Fg builtin. The job "finishes" in the foreground right away and is gone afterwards
+pub struct FgCommand;
+
+#[async_trait]
+impl Command for FgCommand {
+    fn name(&self) -> &'static str {
+        "fg"
+    }
+
+    fn help(&self) -> String {
+        "fg: fg [job_spec]\r\n\
+        \x20   Move job to the foreground.\r\n\
+        \r\n\
+        \x20   Place the job identified by JOB_SPEC in the foreground, making it the\r\n\
+        \x20   current job.  If JOB_SPEC is not present, the shell's notion of the\r\n\
+        \x20   current job is used.\r\n"
+            .to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        let mut table = context.processes.write().await;
+        let job = find_job("fg", &table, args.first().map(String::as_str))?.clone();
+        log::info!(
+            "[{}] fg brought job {} to the foreground: {}",
+            context.auth_id,
+            job.number,
+            job.command
+        );
+        table.remove_job(job.number);
+        table.remove(job.pid);
+        Ok(format!("{}\r\n", job.command))
+    }
+}
+
+/// Bg builtin. Every job already runs in the background, there is nothing to resume
+pub struct BgCommand;
+
+#[async_trait]
+impl Command for BgCommand {
+    fn name(&self) -> &'static str {
+        "bg"
+    }
+
+    fn help(&self) -> String {
+        "bg: bg [job_spec ...]\r\n\
+        \x20   Move jobs to the background.\r\n\
+        \r\n\
+        \x20   Place the jobs identified by each JOB_SPEC in the background, as if they\r\n\
+        \x20   had been started with `&'.  If JOB_SPEC is not present, the shell's notion\r\n\
+        \x20   of the current job is used.\r\n"
+            .to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        let table = context.processes.read().await;
+        let specs: Vec<Option<&str>> = if args.is_empty() {
+            vec![None]
+        } else {
+            args.iter().map(|a| Some(a.as_str())).collect()
+        };
+        let mut errors = Vec::new();
+        for spec in specs {
+            match find_job("bg", &table, spec) {
+                Ok(job) => errors.push(format!(
+                    "bash: bg: job {} already in background",
+                    job.number
+                )),
+                Err(err) => errors.push(err.to_string()),
+            }
+        }
+        Err(CommandError::ExecutionError(errors.join("\r\n")))
+    }
+}
+
+/// Disown builtin: drops jobs from the list, their processes keep running
+pub struct DisownCommand;
+
+#[async_trait]
+impl Command for DisownCommand {
+    fn name(&self) -> &'static str {
+        "disown"
+    }
+
+    fn help(&self) -> String {
+        "disown: disown [-h] [-ar] [jobspec ... | pid ...]\r\n\
+        \x20   Remove jobs from current shell.\r\n\
+        \r\n\
+        \x20   Removes each JOBSPEC argument from the table of active jobs.  Without\r\n\
+        \x20   any JOBSPECs, the shell uses its notion of the current job.\r\n"
+            .to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        let mut all = false;
+        let mut keep = false;
+        let mut specs = Vec::new();
+        for arg in args {
+            match arg.as_str() {
+                "-a" | "-ar" | "-ra" => all = true,
+                "-h" => keep = true,
+                "-r" => {}
+                _ => specs.push(arg.as_str()),
+            }
+        }
+
+        let mut table = context.processes.write().await;
+        let numbers: Vec<usize> = if all && specs.is_empty() {
+            table.jobs().iter().map(|j| j.number).collect()
+        } else if specs.is_empty() {
+            vec![find_job("disown", &table, None)?.number]
+        } else {
+            let mut numbers = Vec::new();
+            for spec in specs {
+                // A plain number is a PID here, unlike for fg and bg
+                let job = match spec.parse::<u32>() {
+                    Ok(pid) => table.jobs().iter().find(|j| j.pid == pid),
+                    Err(_) => table.find_job(spec),
+                };
+                match job {
+                    Some(job) => numbers.push(job.number),
+                    None => {
+                        return Err(CommandError::ExecutionError(format!(
+                            "bash: disown: {}: no such job",
+                            spec
+                        )));
+                    }
+                }
+            }
+            numbers
+        };
+
+        // -h only stops SIGHUP from reaching the job, it stays in the list
+        if !keep {
+            for number in numbers {
+                if let Some(job) = table.remove_job(number) {
+                    log::info!("[{}] disowned job: {}", context.auth_id, job.command);
+                }
+            }
+        }
+        Ok(String::new())
+    }
+}
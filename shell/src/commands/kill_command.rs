@@ -0,0 +1,323 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::{CommandContext, ShellEvent};
+use async_trait::async_trait;
+
+/// Signal names in the order printed by `kill -l`, index + 1 is the signal number
+const SIGNALS: &[&str] = &[
+    "HUP", "INT", "QUIT", "ILL", "TRAP", "ABRT", "BUS", "FPE", "KILL", "USR1", "SEGV", "USR2",
+    "PIPE", "ALRM", "TERM", "STKFLT", "CHLD", "CONT", "STOP", "TSTP", "TTIN", "TTOU", "URG",
+    "XCPU", "XFSZ", "VTALRM", "PROF", "WINCH", "POLL", "PWR", "SYS",
+];
+
+/// Parse a signal given as a number, name or SIG-prefixed name
+fn parse_signal(signal: &str) -> Option<usize> {
+    if let Ok(number) = signal.parse::<usize>() {
+        return (number <= SIGNALS.len()).then_some(number);
+    }
+    let name = signal.to_uppercase();
+    let name = name.strip_prefix("SIG").unwrap_or(&name);
+    SIGNALS.iter().position(|s| *s == name).map(|i| i + 1)
+}
+
+/// Name of a signal without the SIG prefix, its number for 0
+fn signal_name(signal: usize) -> String {
+    signal
+        .checked_sub(1)
+        .and_then(|i| SIGNALS.get(i))
+        .map_or_else(|| signal.to_string(), |name| name.to_string())
+}
+
+/// `kill -l` listing, five signals per row like bash
+fn list_signals() -> String {
+    let mut output = String::new();
+    for (i, name) in SIGNALS.iter().enumerate() {
+        let entry = format!("{:>2}) SIG{}", i + 1, name);
+        output.push_str(&format!("{:<12}", entry));
+        if (i + 1) % 5 == 0 {
+            output.push_str("\r\n");
+        } else {
+            output.push('\t');
+        }
+    }
+    output.push_str("\r\n");
+    output
+}
+
+/// Whether a signal ends the process (as opposed to stop/continue/ignored ones)
+fn terminates(signal: usize) -> bool {
+    // 0 only checks for existence; CHLD, CONT, STOP, TSTP, TTIN, TTOU, URG and WINCH don't kill
+    !matches!(signal, 0 | 17..=23 | 28)
+}
+
+/// Whether the session user may signal a process owned by `owner`
+fn may_signal(context: &CommandContext, owner: &str) -> bool {
+    context.username == "root" || context.username == owner
+}
+
+/// Kill command: acknowledges PIDs from the session's process table
+pub struct KillCommand;
+
+#[async_trait]
+impl Command for KillCommand {
+    fn name(&self) -> &'static str {
+        "kill"
+    }
+
+    fn help(&self) -> String {
+        "kill: kill [-s sigspec | -n signum | -sigspec] pid | jobspec ... or kill -l [sigspec]\r\n\
+        \x20   Send a signal to a job.\r\n\
+        \r\n\
+        \x20   Send the processes identified by PID or JOBSPEC the signal named by\r\n\
+        \x20   SIGSPEC or SIGNUM.  If neither SIGSPEC nor SIGNUM is present, then\r\n\
+        \x20   SIGTERM is assumed.\r\n"
+            .to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        if args.iter().any(|a| a == "--help") {
+            return Ok(self.help());
+        }
+
+        let mut signal = 15;
+        let mut targets = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "-l" | "-L" => return Ok(list_signals()),
+                "-s" | "-n" => {
+                    let Some(spec) = iter.next() else {
+                        return Err(CommandError::InvalidArguments(format!(
+                            "bash: kill: {}: option requires an argument",
+                            arg
+                        )));
+                    };
+                    signal = parse_signal(spec).ok_or_else(|| {
+                        CommandError::InvalidArguments(format!(
+                            "bash: kill: {}: invalid signal specification",
+                            spec
+                        ))
+                    })?;
+                }
+                "--" => targets.extend(iter.by_ref().cloned()),
+                flag if flag.starts_with('-') && targets.is_empty() => {
+                    signal = parse_signal(&flag[1..]).ok_or_else(|| {
+                        CommandError::InvalidArguments(format!(
+                            "bash: kill: {}: invalid signal specification",
+                            &flag[1..]
+                        ))
+                    })?;
+                }
+                _ => targets.push(arg.clone()),
+            }
+        }
+
+        if targets.is_empty() {
+            return Err(CommandError::InvalidArguments(
+                "kill: usage: kill [-s sigspec | -n signum | -sigspec] pid | jobspec ... or kill -l [sigspec]"
+                    .to_string(),
+            ));
+        }
+
+        log::info!(
+            "[{}] kill attempt: signal {} -> {}",
+            context.auth_id,
+            signal,
+            targets.join(" ")
+        );
+
+        let mut table = context.processes.write().await;
+        let mut errors = Vec::new();
+        let mut pids = Vec::new();
+        let mut names = Vec::new();
+        let mut permitted = true;
+        for target in &targets {
+            let pid = if target.starts_with('%') {
+                match table.find_job(target) {
+                    Some(job) => Ok(job.pid),
+                    None => {
+                        errors.push(format!("bash: kill: {}: no such job", target));
+                        continue;
+                    }
+                }
+            } else {
+                target.parse::<u32>()
+            };
+            let Ok(pid) = pid else {
+                errors.push(format!(
+                    "bash: kill: {}: arguments must be process or job IDs",
+                    target
+                ));
+                continue;
+            };
+            let Some(process) = table.get(pid) else {
+                errors.push(format!("bash: kill: ({}) - No such process", pid));
+                continue;
+            };
+            pids.push(pid);
+            names.push(process.name().to_string());
+            if !may_signal(context, &process.user) {
+                permitted = false;
+                errors.push(format!("bash: kill: ({}) - Operation not permitted", pid));
+            } else if terminates(signal) {
+                table.remove(pid);
+            }
+        }
+        drop(table);
+
+        context.push_event(ShellEvent::ProcessKill {
+            tool: "kill",
+            command: std::iter::once(self.name())
+                .chain(args.iter().map(String::as_str))
+                .collect::<Vec<_>>()
+                .join(" "),
+            signal: signal_name(signal),
+            pids,
+            names,
+            permitted,
+        });
+
+        if errors.is_empty() {
+            Ok(String::new())
+        } else {
+            Err(CommandError::ExecutionError(errors.join("\r\n")))
+        }
+    }
+}
+
+/// Pkill command: signals processes from the session's process table by name
+pub struct PkillCommand;
+
+#[async_trait]
+impl Command for PkillCommand {
+    fn name(&self) -> &'static str {
+        "pkill"
+    }
+
+    fn help(&self) -> String {
+        "\r\n\
+        Usage:\r\n\
+        \x20pkill [options] <pattern>\r\n\
+        \r\n\
+        Options:\r\n\
+        \x20-<sig>, --signal <sig>    signal to send (either number or name)\r\n\
+        \x20-e, --echo                display what is killed\r\n\
+        \x20-f, --full                use full process name to match\r\n\
+        \x20-u, --euid <ID,...>       match by effective IDs\r\n\
+        \x20-x, --exact               match exactly with the command name\r\n\
+        \r\n\
+        \x20-h, --help     display this help and exit\r\n\
+        \x20-V, --version  output version information and exit\r\n\
+        \r\n\
+        For more details see pgrep(1).\r\n"
+            .to_string()
+    }
+
+    fn version(&self) -> String {
+        "pkill from procps-ng 3.3.16\r\n".to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        let mut full = false;
+        let mut exact = false;
+        let mut echo = false;
+        let mut signal = 15;
+        let mut pattern: Option<&String> = None;
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(self.help()),
+                "-V" | "--version" => return Ok(self.version()),
+                "-f" | "--full" => full = true,
+                "-x" | "--exact" => exact = true,
+                "-e" | "--echo" => echo = true,
+                "--signal" => {
+                    if let Some(spec) = iter.next() {
+                        signal = parse_signal(spec).ok_or_else(|| {
+                            CommandError::InvalidArguments(format!(
+                                "pkill: Unknown signal \"{}\".",
+                                spec
+                            ))
+                        })?;
+                    }
+                }
+                flag if flag.starts_with('-') && flag.len() > 1 => {
+                    signal = parse_signal(&flag[1..]).ok_or_else(|| {
+                        CommandError::InvalidArguments(format!(
+                            "pkill: invalid option -- '{}'\r\n{}",
+                            &flag[1..],
+                            self.help().trim_end()
+                        ))
+                    })?;
+                }
+                _ => pattern = Some(arg),
+            }
+        }
+
+        let Some(pattern) = pattern else {
+            return Err(CommandError::InvalidArguments(
+                "pkill: no matching criteria specified\r\nTry `pkill --help' for more information."
+                    .to_string(),
+            ));
+        };
+
+        log::info!(
+            "[{}] pkill attempt: signal {} -> pattern {:?} (full: {})",
+            context.auth_id,
+            signal,
+            pattern,
+            full
+        );
+
+        let mut table = context.processes.write().await;
+        let matches: Vec<(u32, String, String)> = table
+            .find(pattern, full, exact)
+            .into_iter()
+            .map(|p| (p.pid, p.user.clone(), p.name().to_string()))
+            .collect();
+
+        let mut output = String::new();
+        let mut errors = Vec::new();
+        let mut pids = Vec::new();
+        let mut names = Vec::new();
+        for (pid, owner, name) in matches {
+            pids.push(pid);
+            if may_signal(context, &owner) {
+                if terminates(signal) {
+                    table.remove(pid);
+                }
+                if echo {
+                    output.push_str(&format!("{} killed (pid {})\r\n", name, pid));
+                }
+            } else {
+                errors.push(format!(
+                    "pkill: killing pid {} failed: Operation not permitted",
+                    pid
+                ));
+            }
+            names.push(name);
+        }
+        drop(table);
+
+        context.push_event(ShellEvent::ProcessKill {
+            tool: "pkill",
+            command: std::iter::once(self.name())
+                .chain(args.iter().map(String::as_str))
+                .collect::<Vec<_>>()
+                .join(" "),
+            signal: signal_name(signal),
+            pids: pids.clone(),
+            names,
+            permitted: errors.is_empty(),
+        });
+
+        if pids.is_empty() {
+            return Err(CommandError::SilentFailure);
+        }
+        if errors.is_empty() {
+            Ok(output)
+        } else {
+            Err(CommandError::ExecutionError(errors.join("\r\n")))
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use super::login_history::LoginRecord;
+use async_trait::async_trait;
+use chrono::{DateTime, Local, Utc};
+
+/// Lines shown when no limit is given. Real last prints the whole file, nobody
+/// scrolls past this
+const DEFAULT_LIMIT: usize = 50;
+
+fn usage(command: &str, file: &str) -> String {
+    format!(
+        "\r\n\
+        Usage:\r\n\
+        \x20{command} [options] [<username>...] [<tty>...]\r\n\
+        \r\n\
+        Show a listing of last logged in users.\r\n\
+        \r\n\
+        Options:\r\n\
+        \x20-<number>            how many lines to show\r\n\
+        \x20-a, --hostlast       display hostnames in the last column\r\n\
+        \x20-d, --dns            translate the IP number back into a hostname\r\n\
+        \x20-f, --file <file>    use a specific file instead of {file}\r\n\
+        \x20-F, --fulltimes      print full login and logout times and dates\r\n\
+        \x20-i, --ip             display IP numbers in numbers-and-dots notation\r\n\
+        \x20-n, --limit <number> how many lines to show\r\n\
+        \x20-R, --nohostname     don't display the hostname field\r\n\
+        \x20-s, --since <time>   display the lines since the specified time\r\n\
+        \x20-t, --until <time>   display the lines until the specified time\r\n\
+        \x20-p, --present <time> display who were present at the specified time\r\n\
+        \x20-w, --fullnames      display full user and domain names\r\n\
+        \x20-x, --system         display system shutdown entries and run level changes\r\n\
+        \x20    --time-format <format>  show timestamps in the specified <format>:\r\n\
+        \x20                              notime|short|full|iso\r\n\
+        \r\n\
+        \x20-h, --help           display this help\r\n\
+        \x20-V, --version        display version\r\n\
+        \r\n\
+        For more details see last(1).\r\n"
+    )
+}
+
+/// What was asked for on the command line
+struct Listing {
+    limit: usize,
+    users: Vec<String>,
+    full_names: bool,
+}
+
+fn parse_args(command: &str, args: &[String]) -> Result<Listing, CommandError> {
+    let mut listing = Listing {
+        limit: DEFAULT_LIMIT,
+        users: Vec::new(),
+        full_names: false,
+    };
+    let invalid_limit = |value: &str| {
+        CommandError::InvalidArguments(format!("{}: failed to parse number: '{}'", command, value))
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-n" | "--limit" => {
+                let value = args.next().ok_or_else(|| {
+                    CommandError::InvalidArguments(format!(
+                        "{}: option requires an argument -- 'n'\r\nTry '{} --help' for more information.",
+                        command, command
+                    ))
+                })?;
+                listing.limit = value.parse().map_err(|_| invalid_limit(value))?;
+            }
+            "-w" | "--fullnames" => listing.full_names = true,
+            // Files, times and formats take a value we don't use
+            "-f" | "--file" | "-s" | "--since" | "-t" | "--until" | "-p" | "--present"
+            | "--time-format" => {
+                args.next();
+            }
+            arg if arg.starts_with("--limit=") => {
+                let value = &arg["--limit=".len()..];
+                listing.limit = value.parse().map_err(|_| invalid_limit(value))?;
+            }
+            arg if arg
+                .strip_prefix('-')
+                .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit())) =>
+            {
+                let value = &arg[1..];
+                listing.limit = value.parse().map_err(|_| invalid_limit(value))?;
+            }
+            // -a, -d, -F, -i, -R, -x only change columns that look the same here
+            arg if arg.starts_with('-') => {}
+            user => listing.users.push(user.to_string()),
+        }
+    }
+    Ok(listing)
+}
+
+fn local(time: DateTime<Utc>) -> DateTime<Local> {
+    time.with_timezone(&Local)
+}
+
+/// `(hh:mm)`, `(d+hh:mm)` past a day
+fn duration(start: DateTime<Utc>, end: DateTime<Utc>) -> String {
+    let minutes = (end - start).num_minutes().max(0);
+    let (days, hours, minutes) = (minutes / 1440, minutes / 60 % 24, minutes % 60);
+    if days > 0 {
+        format!("({}+{:02}:{:02})", days, hours, minutes)
+    } else {
+        format!("({:02}:{:02})", hours, minutes)
+    }
+}
+
+/// Records as util-linux prints them, followed by the line telling when the file starts
+fn render(
+    records: &[LoginRecord],
+    listing: &Listing,
+    tty: impl Fn(usize) -> String,
+    file: &str,
+) -> String {
+    let mut output = String::new();
+    let records = records
+        .iter()
+        .filter(|record| listing.users.is_empty() || listing.users.contains(&record.username))
+        .take(listing.limit);
+    let mut begins = Utc::now();
+    for (i, record) in records.enumerate() {
+        // Attackers pick the user names, cut by characters, not bytes
+        let (user, host): (String, String) = if listing.full_names {
+            (record.username.clone(), record.host.clone())
+        } else {
+            (
+                record.username.chars().take(8).collect(),
+                record.host.chars().take(16).collect(),
+            )
+        };
+        let login = local(record.start).format("%a %b %e %H:%M");
+        let session = match record.end {
+            Some(end) => format!(
+                "{} - {}  {}",
+                login,
+                local(end).format("%H:%M"),
+                duration(record.start, end)
+            ),
+            None => format!("{}   still logged in", login),
+        };
+        output.push_str(&format!(
+            "{:<8} {:<12} {:<16} {}\r\n",
+            user,
+            tty(i),
+            host,
+            session
+        ));
+        begins = begins.min(record.start);
+    }
+    output.push_str(&format!(
+        "\r\n{} begins {}\r\n",
+        file,
+        local(begins).format("%a %b %e %H:%M:%S %Y")
+    ));
+    output
+}
+
+/// last command, listing sessions from wtmp
+pub struct LastCommand;
+
+#[async_trait]
+impl Command for LastCommand {
+    fn name(&self) -> &'static str {
+        "last"
+    }
+
+    fn help(&self) -> String {
+        usage("last", "/var/log/wtmp")
+    }
+
+    fn version(&self) -> String {
+        "last from util-linux 2.34\r\n".to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        if args.iter().any(|a| a == "-h" || a == "--help") {
+            return Ok(self.help());
+        }
+        if args.iter().any(|a| a == "-V" || a == "--version") {
+            return Ok(self.version());
+        }
+        let listing = parse_args("last", args)?;
+        let records = context.logins.logins(listing.limit).await;
+        Ok(render(
+            &records,
+            &listing,
+            |i| format!("pts/{}", i % 4),
+            "wtmp",
+        ))
+    }
+}
+
+/// lastb command, listing failed logins from btmp. Only root may read it
+pub struct LastbCommand;
+
+#[async_trait]
+impl Command for LastbCommand {
+    fn name(&self) -> &'static str {
+        "lastb"
+    }
+
+    fn help(&self) -> String {
+        usage("lastb", "/var/log/btmp")
+    }
+
+    fn version(&self) -> String {
+        "lastb from util-linux 2.34\r\n".to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        if args.iter().any(|a| a == "-h" || a == "--help") {
+            return Ok(self.help());
+        }
+        if args.iter().any(|a| a == "-V" || a == "--version") {
+            return Ok(self.version());
+        }
+        if context.username != "root" {
+            return Err(CommandError::PermissionDenied(
+                "lastb: cannot open /var/log/btmp: Permission denied".to_string(),
+            ));
+        }
+        let listing = parse_args("lastb", args)?;
+        let records = context.logins.failed_logins(listing.limit).await;
+        Ok(render(
+            &records,
+            &listing,
+            |_| "ssh:notty".to_string(),
+            "btmp",
+        ))
+    }
+}
@@ -0,0 +1,118 @@
+use rand::{RngExt, rng};
+use std::time::Duration;
+
+/// Artificial delay for commands that take measurable time on a real system.
+/// `find /` or `ls -R` answering instantly is a timing tell, so their output is
+/// held back in proportion to how much they printed, with some jitter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CommandLatency {
+    /// Upper bound of a single delay, zero disables the delays
+    max: Duration,
+}
+
+impl CommandLatency {
+    pub fn new(max: Duration) -> Self {
+        Self { max }
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.max.is_zero()
+    }
+
+    /// How long `name args` should appear to have taken to print `output`
+    pub fn delay(&self, name: &str, args: &[String], output: &str) -> Duration {
+        if !self.is_enabled() {
+            return Duration::ZERO;
+        }
+        let Some(cost) = cost(name, args, output.matches('\n').count() as u32) else {
+            return Duration::ZERO;
+        };
+        cost.mul_f64(rng().random_range(0.75..1.25)).min(self.max)
+    }
+
+    /// Wait out [`Self::delay`]
+    pub async fn wait(&self, name: &str, args: &[String], output: &str) {
+        let delay = self.delay(name, args, output);
+        if !delay.is_zero() {
+            log::trace!("Delaying {} by {}ms", name, delay.as_millis());
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// Rough time a real system spends on `name`, None for commands that are
+/// effectively instant. `lines` is the number of output lines
+fn cost(name: &str, args: &[String], lines: u32) -> Option<Duration> {
+    let per_line = |base_ms: u64, line_us: u64| {
+        Some(Duration::from_millis(base_ms) + Duration::from_micros(line_us) * lines)
+    };
+    let recursive = args.iter().any(|arg| {
+        arg == "--recursive"
+            || (arg.starts_with('-') && !arg.starts_with("--") && arg.contains(['r', 'R']))
+    });
+    match name {
+        // Walk the whole tree
+        "find" | "du" | "tree" | "updatedb" => per_line(30, 300),
+        "ls" | "grep" | "egrep" | "fgrep" | "chmod" | "chown" | "cp" | "rm" if recursive => {
+            per_line(15, 200)
+        }
+        // Read all of /proc
+        "ps" | "top" | "netstat" | "ss" | "lsof" => per_line(10, 50),
+        "apt" | "apt-get" | "yum" | "dnf"
+            if args
+                .iter()
+                .any(|arg| matches!(arg.as_str(), "update" | "upgrade" | "install")) =>
+        {
+            Some(Duration::from_millis(2500))
+        }
+        // Key generation, listing fingerprints is instant
+        "ssh-keygen" if !args.iter().any(|arg| arg == "-l" || arg == "-lf") => {
+            Some(Duration::from_millis(120))
+        }
+        "sync" => Some(Duration::from_millis(200)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn slow_commands_are_delayed_within_bounds() {
+        let latency = CommandLatency::new(Duration::from_secs(1));
+        let listing = "a\n".repeat(1000);
+
+        let find = latency.delay("find", &args(&["/"]), &listing);
+        assert!(find >= Duration::from_millis(247) && find <= Duration::from_millis(413));
+        let ls = latency.delay("ls", &args(&["-laR", "/"]), &listing);
+        assert!(ls > Duration::ZERO);
+        assert_eq!(
+            latency.delay("ls", &args(&["-la"]), &listing),
+            Duration::ZERO
+        );
+        assert_eq!(
+            latency.delay("echo", &args(&["hi"]), "hi\n"),
+            Duration::ZERO
+        );
+        assert_eq!(
+            latency.delay("find", &args(&["/"]), &listing.repeat(10)),
+            Duration::from_secs(1)
+        );
+
+        let disabled = CommandLatency::default();
+        assert!(!disabled.is_enabled());
+        assert_eq!(
+            disabled.delay("find", &args(&["/"]), &listing),
+            Duration::ZERO
+        );
+    }
+}
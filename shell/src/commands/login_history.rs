@@ -0,0 +1,37 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+/// One line of `last` or `lastb`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoginRecord {
+    pub username: String,
+    /// Address the login came from
+    pub host: String,
+    pub start: DateTime<Utc>,
+    /// None while the session is still open, failed logins end where they start
+    pub end: Option<DateTime<Utc>>,
+}
+
+/// Where `last` and `lastb` read wtmp and btmp from
+#[async_trait]
+pub trait LoginHistory: Send + Sync {
+    /// Sessions, most recent first
+    async fn logins(&self, limit: usize) -> Vec<LoginRecord>;
+    /// Login attempts that never got a shell, most recent first
+    async fn failed_logins(&self, limit: usize) -> Vec<LoginRecord>;
+}
+
+/// Empty wtmp and btmp. The default, showing recorded logins needs the database
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoLoginHistory;
+
+#[async_trait]
+impl LoginHistory for NoLoginHistory {
+    async fn logins(&self, _limit: usize) -> Vec<LoginRecord> {
+        Vec::new()
+    }
+
+    async fn failed_logins(&self, _limit: usize) -> Vec<LoginRecord> {
+        Vec::new()
+    }
+}
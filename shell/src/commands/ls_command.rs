@@ -1,7 +1,9 @@
 use super::command_trait::{Command, CommandResult};
 use super::context::CommandContext;
+use super::procfs::{self, ProcView};
 use crate::filesystem::fs2::FileContent;
 use async_trait::async_trait;
+use std::io::ErrorKind;
 
 /// LS command implementation using the new trait system
 pub struct LsCommand;
@@ -86,7 +88,31 @@ impl Command for LsCommand {
             path.to_string()
         };
 
-        match fs.list_directory(&list_path) {
+        // Listing /proc shows ls itself in the process table, like on a real system
+        let table = context.processes.clone();
+        let mut processes = table.write().await;
+        let own_pid = procfs::is_proc_path(&context.cwd, &list_path).then(|| {
+            processes.attach_session(&context.username);
+            processes.spawn(&context.username, &format!("ls {}", args.join(" ")))
+        });
+        let listing = match own_pid {
+            Some(pid) => ProcView::new(&processes, pid, &fs, context).list(&fs, &list_path),
+            None => None,
+        };
+        if let Some(pid) = own_pid {
+            processes.remove(pid);
+        }
+        drop(processes);
+        let listing = match listing {
+            Some(Err(err)) if err.kind() == ErrorKind::NotADirectory => {
+                let filename = list_path.rsplit('/').next().unwrap_or(&list_path);
+                return Ok(format!("{}\r\n", filename));
+            }
+            Some(listing) => listing,
+            None => fs.list_directory(&list_path),
+        };
+
+        match listing {
             Ok(entries) => {
                 let mut result = String::new();
 
@@ -131,8 +157,7 @@ impl Command for LsCommand {
                     if names.is_empty() {
                         // Empty directory
                     } else {
-                        result.push_str(&names.join("  "));
-                        result.push_str("\r\n");
+                        result.push_str(&format_columns(&names, context.terminal_width));
                     }
                 }
 
@@ -167,3 +192,43 @@ impl Command for LsCommand {
         }
     }
 }
+
+/// Lay names out top-to-bottom in as many columns as fit the terminal, like GNU ls
+fn format_columns(names: &[&str], width: usize) -> String {
+    const SEPARATOR: usize = 2;
+    let lengths: Vec<usize> = names.iter().map(|n| n.chars().count()).collect();
+
+    // Widest layout first; each column is as wide as its longest name
+    let mut rows = names.len();
+    let mut column_widths = vec![lengths.iter().copied().max().unwrap_or(0)];
+    for columns in (2..=names.len()).rev() {
+        let candidate_rows = names.len().div_ceil(columns);
+        let widths: Vec<usize> = lengths
+            .chunks(candidate_rows)
+            .map(|column| column.iter().copied().max().unwrap_or(0))
+            .collect();
+        let total = widths.iter().sum::<usize>() + SEPARATOR * (widths.len() - 1);
+        if total <= width {
+            rows = candidate_rows;
+            column_widths = widths;
+            break;
+        }
+    }
+
+    let mut output = String::new();
+    for row in 0..rows {
+        let mut line = String::new();
+        for (column, column_width) in column_widths.iter().enumerate() {
+            let Some(name) = names.get(column * rows + row) else {
+                break;
+            };
+            if !line.is_empty() {
+                line.push_str(&" ".repeat(SEPARATOR));
+            }
+            line.push_str(&format!("{:<width$}", name, width = column_width));
+        }
+        output.push_str(line.trim_end());
+        output.push_str("\r\n");
+    }
+    output
+}
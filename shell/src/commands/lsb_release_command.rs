@@ -0,0 +1,116 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use async_trait::async_trait;
+
+/// lsb_release command, reporting the distribution from the shared OS identity
+pub struct LsbReleaseCommand;
+
+#[async_trait]
+impl Command for LsbReleaseCommand {
+    fn name(&self) -> &'static str {
+        "lsb_release"
+    }
+
+    fn help(&self) -> String {
+        "Usage: lsb_release [options]\r\n\
+        \r\n\
+        Options:\r\n\
+        \x20 -h, --help         show this help message and exit\r\n\
+        \x20 -v, --version      show LSB modules this system supports\r\n\
+        \x20 -i, --id           show distributor ID\r\n\
+        \x20 -d, --description  show description of this distribution\r\n\
+        \x20 -r, --release      show release number of this distribution\r\n\
+        \x20 -c, --codename     show code name of this distribution\r\n\
+        \x20 -a, --all          show all of the above information\r\n\
+        \x20 -s, --short        show requested information in short format\r\n"
+            .to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        let (mut id, mut description, mut release, mut codename) = (false, false, false, false);
+        let mut short = false;
+        let mut all = false;
+
+        for arg in args {
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(self.help()),
+                "-v" | "--version" => return Ok("No LSB modules are available.\r\n".to_string()),
+                "--id" => id = true,
+                "--description" => description = true,
+                "--release" => release = true,
+                "--codename" => codename = true,
+                "--all" => all = true,
+                "--short" => short = true,
+                flag if flag.starts_with('-') && !flag.starts_with("--") => {
+                    // Combined short flags like -sc or -a
+                    for c in flag.chars().skip(1) {
+                        match c {
+                            'i' => id = true,
+                            'd' => description = true,
+                            'r' => release = true,
+                            'c' => codename = true,
+                            'a' => all = true,
+                            's' => short = true,
+                            'h' => return Ok(self.help()),
+                            'v' => return Ok("No LSB modules are available.\r\n".to_string()),
+                            _ => {
+                                return Err(CommandError::InvalidArguments(format!(
+                                    "lsb_release: error: no such option: -{}",
+                                    c
+                                )));
+                            }
+                        }
+                    }
+                }
+                other => {
+                    return Err(CommandError::InvalidArguments(format!(
+                        "lsb_release: error: no such option: {}",
+                        other
+                    )));
+                }
+            }
+        }
+
+        if all {
+            id = true;
+            description = true;
+            release = true;
+            codename = true;
+        }
+
+        let os = &context.os;
+        let mut output = String::new();
+
+        // Without any option lsb_release only prints the LSB module line
+        if all || !(id || description || release || codename) {
+            output.push_str("No LSB modules are available.\r\n");
+        }
+
+        let fields = [
+            (id, "Distributor ID", &os.distributor_id),
+            (description, "Description", &os.description),
+            (release, "Release", &os.release),
+            (codename, "Codename", &os.codename),
+        ];
+
+        if short {
+            let values: Vec<&str> = fields
+                .iter()
+                .filter(|(wanted, _, _)| *wanted)
+                .map(|(_, _, value)| value.as_str())
+                .collect();
+            if !values.is_empty() {
+                output.push_str(&values.join(" "));
+                output.push_str("\r\n");
+            }
+        } else {
+            for (wanted, label, value) in fields {
+                if wanted {
+                    output.push_str(&format!("{}:\t{}\r\n", label, value));
+                }
+            }
+        }
+
+        Ok(output)
+    }
+}
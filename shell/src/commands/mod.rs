@@ -1,36 +1,124 @@
 // Legacy command handlers
 
 // New trait-based command system
+pub mod accounts;
+pub mod arch_command;
+pub mod awk_command;
+pub mod bash_history;
 pub mod builtin_commands;
+pub mod busybox;
+pub mod canary;
 pub mod cat_command;
+pub mod command_not_found;
 pub mod command_trait;
 pub mod context;
 pub mod date_command;
+pub mod dd_command;
+pub mod df_command;
+pub mod dig_command;
 pub mod dispatcher;
+pub mod dns;
+pub mod du_command;
 pub mod echo_command;
+pub mod executable;
+pub mod fetch_policy;
+pub mod firewall;
 pub mod free_command;
+pub mod getconf_command;
+pub mod getent_command;
+pub mod help_command;
+pub mod hexdump_command;
+pub mod host_identity;
+pub mod hostnamectl_command;
+pub mod ifconfig_command;
+pub mod iptables_command;
+pub mod jobs_command;
+pub mod kill_command;
+pub mod last_command;
+pub mod latency;
+pub mod login_history;
 pub mod ls_command;
+pub mod lsb_release_command;
+pub mod mount_command;
+pub mod mounts;
+pub mod nohup_command;
+pub mod os_identity;
+pub mod pgrep_command;
+pub mod pidof_command;
+pub mod power_command;
+pub mod process_table;
+pub mod procfs;
 pub mod ps_command;
 pub mod registry;
+pub mod sed_command;
+pub mod sleep_command;
+pub mod ssh_keygen_command;
+pub mod sshd_config;
 pub mod test_command;
+pub mod timeout_command;
+pub mod ufw_command;
 pub mod uname_command;
+pub mod watch_command;
 
 // New trait-based exports
+pub use arch_command::ArchCommand;
+pub use awk_command::AwkCommand;
+pub use bash_history::BashHistory;
 pub use builtin_commands::{
-    CdCommand, ColonCommand, CurlCommand, ExitCommand, ExportCommand, FalseCommand, IdCommand,
-    PwdCommand, SudoCommand, TrueCommand, UnsetCommand, WgetCommand, WhoamiCommand,
+    CLEAR_SCREEN, CdCommand, ClearCommand, ColonCommand, CurlCommand, EnvCommand, ExitCommand,
+    ExportCommand, FalseCommand, HistoryCommand, IdCommand, PwdCommand, SudoCommand, TrueCommand,
+    UnsetCommand, WgetCommand, WhoamiCommand,
 };
-pub use cat_command::CatCommand;
+pub use busybox::{BUSYBOX_APPLETS, BusyboxCommand};
+pub use canary::Canaries;
+pub use cat_command::{BinaryFiles, CatCommand};
+pub use command_not_found::CommandNotFoundHandler;
 #[allow(unused)]
 pub use command_trait::{Command, CommandError, CommandResult, StatefulCommand};
-pub use context::CommandContext;
+pub use context::{CommandContext, ShellEvent, prompt_sign};
 pub use date_command::DateCommand;
-pub use dispatcher::CommandDispatcher;
+pub use dd_command::DdCommand;
+pub use df_command::DfCommand;
+pub use dig_command::{DigCommand, HostCommand, NslookupCommand};
+pub use dispatcher::{CommandDispatcher, DEFAULT_MAX_OUTPUT};
+pub use dns::{DnsAnswer, DnsRecord, DnsResolver, FakeDns};
+pub use du_command::DuCommand;
 pub use echo_command::EchoCommand;
+pub use fetch_policy::{FetchPolicy, FetchRule};
+pub use firewall::Firewall;
 pub use free_command::FreeCommand;
+pub use getconf_command::GetconfCommand;
+pub use getent_command::GetentCommand;
+pub use help_command::{CompgenCommand, HelpCommand};
+pub use hexdump_command::HexdumpCommand;
+pub use host_identity::{HostIdentity, HostKeyFingerprint, NetworkInterface};
+pub use hostnamectl_command::HostnamectlCommand;
+pub use ifconfig_command::IfconfigCommand;
+pub use iptables_command::IptablesCommand;
+pub use jobs_command::{BgCommand, DisownCommand, FgCommand, JobsCommand};
+pub use kill_command::{KillCommand, PkillCommand};
+pub use last_command::{LastCommand, LastbCommand};
+pub use latency::CommandLatency;
+pub use login_history::{LoginHistory, LoginRecord, NoLoginHistory};
 pub use ls_command::LsCommand;
+pub use lsb_release_command::LsbReleaseCommand;
+pub use mount_command::MountCommand;
+pub use mounts::{Mount, mount_table, write_mount_files};
+pub use nohup_command::{NohupCommand, SetsidCommand};
+pub use os_identity::OsIdentity;
+pub use pgrep_command::PgrepCommand;
+pub use pidof_command::PidofCommand;
+pub use power_command::PowerCommand;
+pub use process_table::{Job, Process, ProcessTable};
 pub use ps_command::PsCommand;
 #[allow(unused)]
 pub use registry::CommandRegistry;
+pub use sed_command::SedCommand;
+pub use sleep_command::{DEFAULT_MAX_SLEEP, SleepCommand};
+pub use ssh_keygen_command::SshKeygenCommand;
+pub use sshd_config::{SshdSettings, write_sshd_files};
 pub use test_command::TestCommand;
+pub use timeout_command::TimeoutCommand;
+pub use ufw_command::UfwCommand;
 pub use uname_command::UnameCommand;
+pub use watch_command::WatchCommand;
@@ -0,0 +1,115 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use super::mounts::mount_table;
+use async_trait::async_trait;
+
+/// mount command. Lists the shared mount table; mounting is refused the way a
+/// real system refuses a user without the device
+pub struct MountCommand;
+
+#[async_trait]
+impl Command for MountCommand {
+    fn name(&self) -> &'static str {
+        "mount"
+    }
+
+    fn help(&self) -> String {
+        "\r\n\
+        Usage:\r\n\
+        \x20mount [-lhV]\r\n\
+        \x20mount -a [options]\r\n\
+        \x20mount [options] [--source] <source> | [--target] <directory>\r\n\
+        \x20mount [options] <source> <directory>\r\n\
+        \x20mount <operation> <mountpoint> [<target>]\r\n\
+        \r\n\
+        Mount a filesystem.\r\n\
+        \r\n\
+        Options:\r\n\
+        \x20-a, --all               mount all filesystems mentioned in fstab\r\n\
+        \x20-f, --fake              dry run; skip the mount(2) syscall\r\n\
+        \x20-l, --show-labels       show also filesystem labels\r\n\
+        \x20-n, --no-mtab           don't write to /etc/mtab\r\n\
+        \x20-o, --options <list>    comma-separated list of mount options\r\n\
+        \x20-r, --read-only         mount the filesystem read-only (same as -o ro)\r\n\
+        \x20-t, --types <list>      limit the set of filesystem types\r\n\
+        \x20-v, --verbose           say what is being done\r\n\
+        \x20-w, --rw, --read-write  mount the filesystem read-write (default)\r\n\
+        \r\n\
+        \x20-h, --help              display this help\r\n\
+        \x20-V, --version           display version\r\n\
+        \r\n\
+        For more details see mount(8).\r\n"
+            .to_string()
+    }
+
+    fn version(&self) -> String {
+        "mount from util-linux 2.34 (libmount 2.34.0: selinux, smack, btrfs, namespaces, assert, debug)\r\n"
+            .to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        let mut types: Option<Vec<String>> = None;
+        let mut options = Vec::new();
+        let mut all = false;
+        let mut operands = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(self.help()),
+                "-V" | "--version" => return Ok(self.version()),
+                "-a" | "--all" => all = true,
+                "-t" | "--types" | "-o" | "--options" => {
+                    let value = args.next().ok_or_else(|| {
+                        CommandError::InvalidArguments(format!(
+                            "mount: option requires an argument -- '{}'\r\nTry 'mount --help' for more information.",
+                            arg.trim_start_matches('-').chars().next().unwrap_or('t')
+                        ))
+                    })?;
+                    if matches!(arg.as_str(), "-t" | "--types") {
+                        types = Some(value.split(',').map(str::to_string).collect());
+                    } else {
+                        options.extend(value.split(',').map(str::to_string));
+                    }
+                }
+                // -l, -n, -v, -r, -w and friends don't change the listing
+                flag if flag.starts_with('-') => {}
+                operand => operands.push(operand.to_string()),
+            }
+        }
+
+        if operands.is_empty() && !all {
+            let fs = context.filesystem.read().await;
+            let listing = mount_table(&context.os, fs.device())
+                .iter()
+                .filter(|mount| {
+                    types
+                        .as_ref()
+                        .is_none_or(|types| types.contains(&mount.fs_type))
+                })
+                .map(|mount| format!("{}\r\n", mount.listing()))
+                .collect();
+            return Ok(listing);
+        }
+
+        if context.username != "root" {
+            return Err(CommandError::PermissionDenied(
+                "mount: only root can do that".to_string(),
+            ));
+        }
+        // Everything in fstab is mounted already
+        if all || options.iter().any(|option| option == "remount") {
+            return Ok(String::new());
+        }
+        match &operands[..] {
+            [source, target, ..] => Err(CommandError::ExecutionError(format!(
+                "mount: {}: special device {} does not exist.",
+                target, source
+            ))),
+            [target] => Err(CommandError::ExecutionError(format!(
+                "mount: {}: can't find in /etc/fstab.",
+                target
+            ))),
+            [] => Ok(String::new()),
+        }
+    }
+}
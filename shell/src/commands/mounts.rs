@@ -0,0 +1,263 @@
+use super::busybox::write_file;
+use super::os_identity::OsIdentity;
+use crate::filesystem::fs2::FileSystem;
+
+/// One line of /proc/mounts
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mount {
+    pub source: String,
+    pub target: String,
+    pub fs_type: String,
+    pub options: String,
+}
+
+impl Mount {
+    fn new(source: &str, target: &str, fs_type: &str, options: &str) -> Self {
+        Self {
+            source: source.to_string(),
+            target: target.to_string(),
+            fs_type: fs_type.to_string(),
+            options: options.to_string(),
+        }
+    }
+
+    /// As `mount` prints it, e.g. "/dev/sda1 on / type ext4 (rw,relatime)"
+    pub fn listing(&self) -> String {
+        format!(
+            "{} on {} type {} ({})",
+            self.source, self.target, self.fs_type, self.options
+        )
+    }
+}
+
+/// Cgroup v1 controllers systemd mounts on Ubuntu 20.04, in its order
+const CGROUP_CONTROLLERS: &[&str] = &[
+    "cpu,cpuacct",
+    "net_cls,net_prio",
+    "memory",
+    "devices",
+    "freezer",
+    "blkio",
+    "perf_event",
+    "hugetlb",
+    "pids",
+    "cpuset",
+    "rdma",
+];
+
+/// What the fake system has mounted. `mount`, /proc/mounts and /etc/fstab are all
+/// built from this, with the filesystem's device as root, so disk layout checks
+/// see a bare metal (or plain VM) install and no overlay or container mounts.
+/// The embedded persona gets the squashfs and overlay layout every OpenWrt has
+pub fn mount_table(os: &OsIdentity, root_device: &str) -> Vec<Mount> {
+    if !os.is_ubuntu() {
+        return vec![
+            Mount::new("/dev/root", "/rom", "squashfs", "ro,relatime"),
+            Mount::new("proc", "/proc", "proc", "rw,nosuid,nodev,noexec,noatime"),
+            Mount::new("sysfs", "/sys", "sysfs", "rw,nosuid,nodev,noexec,noatime"),
+            Mount::new("tmpfs", "/tmp", "tmpfs", "rw,nosuid,nodev,noatime"),
+            Mount::new("/dev/mtdblock6", "/overlay", "jffs2", "rw,noatime"),
+            Mount::new(
+                "overlayfs:/overlay",
+                "/",
+                "overlay",
+                "rw,noatime,lowerdir=/,upperdir=/overlay/upper,workdir=/overlay/work",
+            ),
+            Mount::new(
+                "tmpfs",
+                "/dev",
+                "tmpfs",
+                "rw,nosuid,relatime,size=512k,mode=755",
+            ),
+            Mount::new(
+                "devpts",
+                "/dev/pts",
+                "devpts",
+                "rw,nosuid,noexec,relatime,mode=600,ptmxmode=000",
+            ),
+            Mount::new("debugfs", "/sys/kernel/debug", "debugfs", "rw,noatime"),
+        ];
+    }
+
+    let mut mounts = vec![
+        Mount::new("sysfs", "/sys", "sysfs", "rw,nosuid,nodev,noexec,relatime"),
+        Mount::new("proc", "/proc", "proc", "rw,nosuid,nodev,noexec,relatime"),
+        Mount::new(
+            "udev",
+            "/dev",
+            "devtmpfs",
+            "rw,nosuid,noexec,relatime,size=1988172k,nr_inodes=497043,mode=755",
+        ),
+        Mount::new(
+            "devpts",
+            "/dev/pts",
+            "devpts",
+            "rw,nosuid,noexec,relatime,gid=5,mode=620,ptmxmode=000",
+        ),
+        Mount::new(
+            "tmpfs",
+            "/run",
+            "tmpfs",
+            "rw,nosuid,nodev,noexec,relatime,size=403084k,mode=755",
+        ),
+        Mount::new(root_device, "/", "ext4", "rw,relatime"),
+        Mount::new(
+            "securityfs",
+            "/sys/kernel/security",
+            "securityfs",
+            "rw,nosuid,nodev,noexec,relatime",
+        ),
+        Mount::new("tmpfs", "/dev/shm", "tmpfs", "rw,nosuid,nodev"),
+        Mount::new(
+            "tmpfs",
+            "/run/lock",
+            "tmpfs",
+            "rw,nosuid,nodev,noexec,relatime,size=5120k",
+        ),
+        Mount::new(
+            "tmpfs",
+            "/sys/fs/cgroup",
+            "tmpfs",
+            "ro,nosuid,nodev,noexec,mode=755",
+        ),
+        Mount::new(
+            "cgroup2",
+            "/sys/fs/cgroup/unified",
+            "cgroup2",
+            "rw,nosuid,nodev,noexec,relatime,nsdelegate",
+        ),
+        Mount::new(
+            "cgroup",
+            "/sys/fs/cgroup/systemd",
+            "cgroup",
+            "rw,nosuid,nodev,noexec,relatime,xattr,name=systemd",
+        ),
+        Mount::new(
+            "pstore",
+            "/sys/fs/pstore",
+            "pstore",
+            "rw,nosuid,nodev,noexec,relatime",
+        ),
+        Mount::new(
+            "bpf",
+            "/sys/fs/bpf",
+            "bpf",
+            "rw,nosuid,nodev,noexec,relatime,mode=700",
+        ),
+    ];
+    for controller in CGROUP_CONTROLLERS {
+        mounts.push(Mount::new(
+            "cgroup",
+            &format!("/sys/fs/cgroup/{}", controller),
+            "cgroup",
+            &format!("rw,nosuid,nodev,noexec,relatime,{}", controller),
+        ));
+    }
+    mounts.extend([
+        Mount::new(
+            "systemd-1",
+            "/proc/sys/fs/binfmt_misc",
+            "autofs",
+            "rw,relatime,fd=28,pgrp=1,timeout=0,minproto=5,maxproto=5,direct,pipe_ino=15621",
+        ),
+        Mount::new(
+            "mqueue",
+            "/dev/mqueue",
+            "mqueue",
+            "rw,nosuid,nodev,noexec,relatime",
+        ),
+        Mount::new(
+            "hugetlbfs",
+            "/dev/hugepages",
+            "hugetlbfs",
+            "rw,relatime,pagesize=2M",
+        ),
+        Mount::new(
+            "debugfs",
+            "/sys/kernel/debug",
+            "debugfs",
+            "rw,nosuid,nodev,noexec,relatime",
+        ),
+        Mount::new(
+            "tracefs",
+            "/sys/kernel/tracing",
+            "tracefs",
+            "rw,nosuid,nodev,noexec,relatime",
+        ),
+        Mount::new(
+            "fusectl",
+            "/sys/fs/fuse/connections",
+            "fusectl",
+            "rw,nosuid,nodev,noexec,relatime",
+        ),
+        Mount::new(
+            "configfs",
+            "/sys/kernel/config",
+            "configfs",
+            "rw,nosuid,nodev,noexec,relatime",
+        ),
+    ]);
+    mounts
+}
+
+/// Contents of /proc/mounts
+pub fn proc_mounts(mounts: &[Mount]) -> String {
+    mounts
+        .iter()
+        .map(|mount| {
+            format!(
+                "{} {} {} {} 0 0\n",
+                mount.source, mount.target, mount.fs_type, mount.options
+            )
+        })
+        .collect()
+}
+
+/// Contents of /etc/fstab as the Ubuntu installer writes it, `free` shows swap so
+/// there is a swapfile. OpenWrt has none
+pub fn fstab(os: &OsIdentity, root_device: &str, root_uuid: &str) -> Option<String> {
+    os.is_ubuntu().then(|| {
+        format!(
+            "# /etc/fstab: static file system information.\n\
+            #\n\
+            # Use 'blkid' to print the universally unique identifier for a\n\
+            # device; this may be used with UUID= as a more robust way to name devices\n\
+            # that works even if disks are added and removed. See fstab(5).\n\
+            #\n\
+            # <file system> <mount point>   <type>  <options>       <dump>  <pass>\n\
+            # / was on {} during installation\n\
+            UUID={} /               ext4    errors=remount-ro 0       1\n\
+            /swapfile                                 none            swap    sw              0       0\n",
+            root_device, root_uuid
+        )
+    })
+}
+
+/// Write /proc/mounts and /etc/fstab into the virtual filesystem and point
+/// /etc/mtab at the former, overriding whatever the base image shipped.
+/// `root_uuid` is the UUID fstab names the root filesystem by
+pub fn write_mount_files(
+    fs: &mut FileSystem,
+    os: &OsIdentity,
+    root_uuid: &str,
+) -> std::io::Result<()> {
+    for dir in ["/etc", "/proc"] {
+        if fs.get_file(dir).is_err() {
+            fs.create_directory(dir)?;
+        }
+    }
+
+    let root_device = fs.device().to_string();
+    let mounts = proc_mounts(&mount_table(os, &root_device));
+    write_file(fs, "/proc/mounts", mounts.into_bytes(), 0o444)?;
+    if let Some(fstab) = fstab(os, &root_device, root_uuid) {
+        write_file(fs, "/etc/fstab", fstab.into_bytes(), 0o644)?;
+    }
+
+    // Ubuntu links it to ../proc/self/mounts, which isn't there
+    if fs.get_file("/etc/mtab").is_ok() {
+        fs.remove_file("/etc/mtab")?;
+    }
+    fs.create_symlink("/etc/mtab", "/proc/mounts")?.inode.i_mode = 0o777;
+    Ok(())
+}
@@ -0,0 +1,115 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use async_trait::async_trait;
+
+/// Nohup command. With a command to run the dispatcher starts it detached (see
+/// [`wrapped_command`]), this only handles the missing operand and options
+pub struct NohupCommand;
+
+#[async_trait]
+impl Command for NohupCommand {
+    fn name(&self) -> &'static str {
+        "nohup"
+    }
+
+    fn help(&self) -> String {
+        "Usage: nohup COMMAND [ARG]...\r\n\
+        \x20 or:  nohup OPTION\r\n\
+        Run COMMAND, ignoring hangup signals.\r\n\
+        \r\n\
+        \x20     --help     display this help and exit\r\n\
+        \x20     --version  output version information and exit\r\n\
+        \r\n\
+        If standard input is a terminal, redirect it from an unreadable file.\r\n\
+        If standard output is a terminal, append output to 'nohup.out' if possible,\r\n\
+        '$HOME/nohup.out' otherwise.\r\n\
+        If standard error is a terminal, redirect it to standard output.\r\n\
+        To save output to FILE, use 'nohup COMMAND > FILE'.\r\n"
+            .to_string()
+    }
+
+    fn version(&self) -> String {
+        "nohup (GNU coreutils) 8.32\r\n".to_string()
+    }
+
+    async fn execute(&self, args: &[String], _context: &mut CommandContext) -> CommandResult {
+        match args.first().map(String::as_str) {
+            Some("--help") => Ok(self.help()),
+            Some("--version") => Ok(self.version()),
+            Some(option) if option.starts_with('-') && option != "-" => {
+                Err(CommandError::ExecutionError(format!(
+                    "nohup: unrecognized option '{}'\r\nTry 'nohup --help' for more information.",
+                    option
+                )))
+            }
+            _ => Err(CommandError::ExecutionError(
+                "nohup: missing operand\r\nTry 'nohup --help' for more information.".to_string(),
+            )),
+        }
+    }
+}
+
+/// Setsid command, runs the wrapped command in a new session like [`NohupCommand`]
+pub struct SetsidCommand;
+
+#[async_trait]
+impl Command for SetsidCommand {
+    fn name(&self) -> &'static str {
+        "setsid"
+    }
+
+    fn help(&self) -> String {
+        "\r\n\
+        Usage:\r\n\
+        \x20setsid [options] <program> [arguments ...]\r\n\
+        \r\n\
+        Run a program in a new session.\r\n\
+        \r\n\
+        Options:\r\n\
+        \x20-c, --ctty     set the controlling terminal to the current one\r\n\
+        \x20-f, --fork     always fork\r\n\
+        \x20-w, --wait     wait program to exit, and use the same return\r\n\
+        \r\n\
+        \x20-h, --help     display this help\r\n\
+        \x20-V, --version  display version\r\n"
+            .to_string()
+    }
+
+    fn version(&self) -> String {
+        "setsid from util-linux 2.37.2\r\n".to_string()
+    }
+
+    async fn execute(&self, args: &[String], _context: &mut CommandContext) -> CommandResult {
+        for arg in args {
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(self.help()),
+                "-V" | "--version" => return Ok(self.version()),
+                "-c" | "--ctty" | "-f" | "--fork" | "-w" | "--wait" => {}
+                option => {
+                    return Err(CommandError::ExecutionError(format!(
+                        "setsid: invalid option -- '{}'\r\nTry 'setsid --help' for more information.",
+                        option.trim_start_matches('-')
+                    )));
+                }
+            }
+        }
+        Err(CommandError::ExecutionError(
+            "setsid: no command specified\r\nTry 'setsid --help' for more information.".to_string(),
+        ))
+    }
+}
+
+/// Index of the command `nohup`/`setsid` would run in `args`, None when they
+/// only get options and report that themselves
+pub(crate) fn wrapped_command(wrapper: &str, args: &[String]) -> Option<usize> {
+    let mut index = 0;
+    while let Some(arg) = args.get(index) {
+        match (wrapper, arg.as_str()) {
+            (_, "--") => return (index + 1 < args.len()).then_some(index + 1),
+            ("setsid", "-c" | "--ctty" | "-f" | "--fork" | "-w" | "--wait") => index += 1,
+            (_, option) if option.starts_with('-') && option != "-" => return None,
+            _ => return Some(index),
+        }
+    }
+    None
+}
@@ -0,0 +1,192 @@
+use crate::filesystem::fs2::{FileContent, FileSystem};
+use std::sync::Arc;
+
+/// Identity of the fake operating system. `uname`, `lsb_release`, `hostnamectl`,
+/// the login banner and `/etc/os-release` all read from here so fingerprinting
+/// tools see one consistent system.
+#[derive(Clone, Debug)]
+pub struct OsIdentity {
+    pub kernel_name: String,
+    pub kernel_release: String,
+    pub kernel_version: String,
+    pub machine: String,
+    pub operating_system: String,
+    /// Distributor ID as printed by `lsb_release -i` (e.g. "Ubuntu")
+    pub distributor_id: String,
+    /// Full release name (e.g. "Ubuntu 20.04.4 LTS")
+    pub description: String,
+    /// Release number (e.g. "20.04")
+    pub release: String,
+    /// Release codename (e.g. "focal")
+    pub codename: String,
+    /// VERSION field of /etc/os-release (e.g. "20.04.4 LTS (Focal Fossa)")
+    pub version: String,
+}
+
+impl Default for OsIdentity {
+    fn default() -> Self {
+        Self {
+            kernel_name: "Linux".to_string(),
+            kernel_release: "5.4.0-109-generic".to_string(),
+            kernel_version: "#123-Ubuntu SMP Fri Apr 8 09:10:54 UTC 2022".to_string(),
+            machine: "x86_64".to_string(),
+            operating_system: "GNU/Linux".to_string(),
+            distributor_id: "Ubuntu".to_string(),
+            description: "Ubuntu 20.04.4 LTS".to_string(),
+            release: "20.04".to_string(),
+            codename: "focal".to_string(),
+            version: "20.04.4 LTS (Focal Fossa)".to_string(),
+        }
+    }
+}
+
+impl OsIdentity {
+    /// OpenWrt router running busybox, the kind of device Mirai-style botnets look for
+    pub fn embedded() -> Self {
+        Self {
+            kernel_name: "Linux".to_string(),
+            kernel_release: "4.14.131".to_string(),
+            kernel_version: "#0 Thu Jul 25 18:36:47 2019".to_string(),
+            machine: "mips".to_string(),
+            operating_system: "GNU/Linux".to_string(),
+            distributor_id: "OpenWrt".to_string(),
+            description: "OpenWrt 18.06.4 r7808-ef686b7292".to_string(),
+            release: "18.06.4".to_string(),
+            codename: String::new(),
+            version: "18.06.4".to_string(),
+        }
+    }
+
+    /// Whether this is an Ubuntu system (os-release and lsb-release layout differ)
+    pub(crate) fn is_ubuntu(&self) -> bool {
+        self.distributor_id == "Ubuntu"
+    }
+
+    /// System description used in the login banner,
+    /// e.g. "Ubuntu 20.04.4 LTS (GNU/Linux 5.4.0-109-generic x86_64)"
+    pub fn system_description(&self) -> String {
+        format!(
+            "{} ({} {} {})",
+            self.description, self.operating_system, self.kernel_release, self.machine
+        )
+    }
+
+    /// Architecture in systemd notation as printed by `hostnamectl`
+    pub fn architecture(&self) -> String {
+        self.machine.replace('_', "-")
+    }
+
+    /// Word size of the machine as printed by `getconf LONG_BIT`
+    pub fn long_bit(&self) -> u32 {
+        match self.machine.as_str() {
+            "x86_64" | "aarch64" | "mips64" | "ppc64" | "ppc64le" | "s390x" | "riscv64" => 64,
+            _ => 32,
+        }
+    }
+
+    /// Contents of /proc/version
+    pub fn proc_version(&self) -> String {
+        let compiler = if self.is_ubuntu() {
+            "buildd@lcy02-amd64-006) (gcc version 9.4.0 (Ubuntu 9.4.0-1ubuntu1~20.04.1)"
+        } else {
+            "buildbot@builds) (gcc version 7.3.0 (OpenWrt GCC 7.3.0 r7808-ef686b7292)"
+        };
+        format!(
+            "{} version {} ({}) {}\n",
+            self.kernel_name, self.kernel_release, compiler, self.kernel_version
+        )
+    }
+
+    /// Contents of /etc/os-release
+    pub fn os_release(&self) -> String {
+        if !self.is_ubuntu() {
+            return format!(
+                "NAME=\"{id}\"\n\
+                VERSION=\"{version}\"\n\
+                ID=\"{lower_id}\"\n\
+                ID_LIKE=\"lede {lower_id}\"\n\
+                PRETTY_NAME=\"{id} {version}\"\n\
+                VERSION_ID=\"{release}\"\n\
+                HOME_URL=\"https://openwrt.org/\"\n\
+                BUG_URL=\"https://bugs.openwrt.org/\"\n\
+                SUPPORT_URL=\"https://forum.openwrt.org/\"\n\
+                BUILD_ID=\"{build}\"\n",
+                id = self.distributor_id,
+                version = self.version,
+                lower_id = self.distributor_id.to_lowercase(),
+                release = self.release,
+                build = self.description.rsplit(' ').next().unwrap_or_default(),
+            );
+        }
+
+        format!(
+            "NAME=\"{id}\"\n\
+            VERSION=\"{version}\"\n\
+            ID={lower_id}\n\
+            ID_LIKE=debian\n\
+            PRETTY_NAME=\"{description}\"\n\
+            VERSION_ID=\"{release}\"\n\
+            HOME_URL=\"https://www.ubuntu.com/\"\n\
+            SUPPORT_URL=\"https://help.ubuntu.com/\"\n\
+            BUG_REPORT_URL=\"https://bugs.launchpad.net/ubuntu/\"\n\
+            PRIVACY_POLICY_URL=\"https://www.ubuntu.com/legal/terms-and-policies/privacy-policy\"\n\
+            VERSION_CODENAME={codename}\n\
+            UBUNTU_CODENAME={codename}\n",
+            id = self.distributor_id,
+            version = self.version,
+            lower_id = self.distributor_id.to_lowercase(),
+            description = self.description,
+            release = self.release,
+            codename = self.codename,
+        )
+    }
+
+    /// Contents of /etc/lsb-release
+    pub fn lsb_release(&self) -> String {
+        format!(
+            "DISTRIB_ID={}\nDISTRIB_RELEASE={}\nDISTRIB_CODENAME={}\nDISTRIB_DESCRIPTION=\"{}\"\n",
+            self.distributor_id, self.release, self.codename, self.description
+        )
+    }
+
+    /// Write /etc/os-release, /etc/lsb-release (/etc/openwrt_release on OpenWrt) and
+    /// /proc/version into the virtual filesystem, overriding whatever the base image shipped
+    pub fn write_release_files(&self, fs: &mut FileSystem) -> std::io::Result<()> {
+        for dir in ["/etc", "/proc"] {
+            if fs.get_file(dir).is_err() {
+                fs.create_directory(dir)?;
+            }
+        }
+
+        let files = [
+            ("/etc/os-release", self.os_release()),
+            (
+                // OpenWrt keeps the same DISTRIB_* keys under its own name
+                if self.is_ubuntu() {
+                    "/etc/lsb-release"
+                } else {
+                    "/etc/openwrt_release"
+                },
+                self.lsb_release(),
+            ),
+            ("/proc/version", self.proc_version()),
+        ];
+
+        for (path, content) in files {
+            // Debian images ship os-release as a symlink into /usr/lib, replace it
+            if fs.get_file(path).is_ok() {
+                fs.remove_file(path)?;
+            }
+            let entry = fs.create_file(path)?;
+            entry.inode.i_mode = if path.starts_with("/proc/") {
+                0o444
+            } else {
+                0o644
+            };
+            entry.inode.i_size_lo = content.len() as u32;
+            entry.content = Some(FileContent::RegularFile(Arc::new(content.into_bytes())));
+        }
+
+        Ok(())
+    }
+}
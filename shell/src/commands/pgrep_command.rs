@@ -0,0 +1,137 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use async_trait::async_trait;
+
+/// Pgrep command: looks up PIDs in the session's process table
+pub struct PgrepCommand;
+
+#[async_trait]
+impl Command for PgrepCommand {
+    fn name(&self) -> &'static str {
+        "pgrep"
+    }
+
+    fn help(&self) -> String {
+        "\r\n\
+        Usage:\r\n\
+        \x20pgrep [options] <pattern>\r\n\
+        \r\n\
+        Options:\r\n\
+        \x20-d, --delimiter <string>  specify output delimiter\r\n\
+        \x20-l, --list-name           list PID and process name\r\n\
+        \x20-a, --list-full           list PID and full command line\r\n\
+        \x20-c, --count               count of matching processes\r\n\
+        \x20-f, --full                use full process name to match\r\n\
+        \x20-u, --euid <ID,...>       match by effective IDs\r\n\
+        \x20-x, --exact               match exactly with the command name\r\n\
+        \r\n\
+        \x20-h, --help     display this help and exit\r\n\
+        \x20-V, --version  output version information and exit\r\n\
+        \r\n\
+        For more details see pgrep(1).\r\n"
+            .to_string()
+    }
+
+    fn version(&self) -> String {
+        "pgrep from procps-ng 3.3.16\r\n".to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        let mut full = false;
+        let mut exact = false;
+        let mut list_name = false;
+        let mut list_full = false;
+        let mut count = false;
+        let mut delimiter = "\r\n".to_string();
+        let mut users: Option<Vec<String>> = None;
+        let mut pattern: Option<&String> = None;
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(self.help()),
+                "-V" | "--version" => return Ok(self.version()),
+                "-d" | "--delimiter" => {
+                    if let Some(d) = iter.next() {
+                        delimiter = d.clone();
+                    }
+                }
+                "-u" | "--euid" => {
+                    if let Some(list) = iter.next() {
+                        users = Some(list.split(',').map(str::to_string).collect());
+                    }
+                }
+                flag if flag.starts_with("--") => match flag {
+                    "--full" => full = true,
+                    "--exact" => exact = true,
+                    "--list-name" => list_name = true,
+                    "--list-full" => list_full = true,
+                    "--count" => count = true,
+                    _ => {
+                        return Err(CommandError::InvalidArguments(format!(
+                            "pgrep: unrecognized option '{}'\r\n{}",
+                            flag,
+                            self.help().trim_end()
+                        )));
+                    }
+                },
+                flag if flag.starts_with('-') && flag.len() > 1 => {
+                    for c in flag.chars().skip(1) {
+                        match c {
+                            'f' => full = true,
+                            'x' => exact = true,
+                            'l' => list_name = true,
+                            'a' => list_full = true,
+                            'c' => count = true,
+                            _ => {
+                                return Err(CommandError::InvalidArguments(format!(
+                                    "pgrep: invalid option -- '{}'\r\n{}",
+                                    c,
+                                    self.help().trim_end()
+                                )));
+                            }
+                        }
+                    }
+                }
+                _ => pattern = Some(arg),
+            }
+        }
+
+        if pattern.is_none() && users.is_none() {
+            return Err(CommandError::InvalidArguments(
+                "pgrep: no matching criteria specified\r\nTry `pgrep --help' for more information."
+                    .to_string(),
+            ));
+        }
+
+        let table = context.processes.read().await;
+        let matches: Vec<_> = table
+            .find(pattern.map(|p| p.as_str()).unwrap_or(""), full, exact)
+            .into_iter()
+            .filter(|p| users.as_ref().is_none_or(|u| u.contains(&p.user)))
+            .collect();
+
+        if count {
+            return Ok(format!("{}\r\n", matches.len()));
+        }
+
+        if matches.is_empty() {
+            return Err(CommandError::SilentFailure);
+        }
+
+        let lines: Vec<String> = matches
+            .iter()
+            .map(|p| {
+                if list_full {
+                    format!("{} {}", p.pid, p.command)
+                } else if list_name {
+                    format!("{} {}", p.pid, p.name())
+                } else {
+                    p.pid.to_string()
+                }
+            })
+            .collect();
+
+        Ok(format!("{}\r\n", lines.join(&delimiter)))
+    }
+}
@@ -0,0 +1,78 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use async_trait::async_trait;
+
+/// Pidof command: prints the PIDs of running programs from the session's process table
+pub struct PidofCommand;
+
+#[async_trait]
+impl Command for PidofCommand {
+    fn name(&self) -> &'static str {
+        "pidof"
+    }
+
+    fn help(&self) -> String {
+        "Usage: pidof [options] [program [...]]\r\n\
+        \r\n\
+        Options:\r\n\
+        \x20-s              return one PID only\r\n\
+        \x20-o <pid,...>    omit processes with PID\r\n\
+        \x20-h, --help      display this help and exit\r\n\
+        \x20-V, --version   output version information and exit\r\n"
+            .to_string()
+    }
+
+    fn version(&self) -> String {
+        "pidof from procps-ng 3.3.16\r\n".to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        let mut single = false;
+        let mut omit: Vec<u32> = Vec::new();
+        let mut programs = Vec::new();
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(self.help()),
+                "-V" | "--version" => return Ok(self.version()),
+                "-s" => single = true,
+                "-o" => {
+                    if let Some(list) = iter.next() {
+                        omit.extend(list.split(',').filter_map(|p| p.parse::<u32>().ok()));
+                    }
+                }
+                // Ignore other options such as -x (scripts) or -n
+                flag if flag.starts_with('-') => {}
+                program => programs.push(program),
+            }
+        }
+
+        let table = context.processes.read().await;
+        let mut pids = Vec::new();
+        for program in programs {
+            // pidof lists the most recently started process first
+            let mut matches: Vec<u32> = table
+                .processes()
+                .into_iter()
+                .filter(|p| {
+                    p.name() == program || p.command.split_whitespace().next() == Some(program)
+                })
+                .map(|p| p.pid)
+                .filter(|pid| !omit.contains(pid))
+                .collect();
+            matches.reverse();
+            if single {
+                matches.truncate(1);
+            }
+            pids.extend(matches);
+        }
+
+        if pids.is_empty() {
+            return Err(CommandError::SilentFailure);
+        }
+
+        let pids: Vec<String> = pids.iter().map(u32::to_string).collect();
+        Ok(format!("{}\r\n", pids.join(" ")))
+    }
+}
@@ -0,0 +1,330 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::{CommandContext, ShellEvent};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Local, NaiveTime};
+
+/// What the machine is asked to do
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Action {
+    Reboot,
+    Poweroff,
+    Halt,
+}
+
+impl Action {
+    fn as_str(self) -> &'static str {
+        match self {
+            Action::Reboot => "reboot",
+            Action::Poweroff => "poweroff",
+            Action::Halt => "halt",
+        }
+    }
+
+    /// logind's error for a user that isn't allowed to
+    fn denied(self) -> String {
+        let verb = match self {
+            Action::Reboot => "reboot",
+            Action::Poweroff => "power off",
+            Action::Halt => "halt",
+        };
+        format!(
+            "Failed to set wall message, ignoring: Access denied\r\n\
+            Failed to {} system via logind: Access denied\r\n\
+            Failed to start {}.target: Access denied\r\n\
+            See system logs and 'systemctl status {}.target' for details.",
+            verb,
+            self.as_str(),
+            self.as_str()
+        )
+    }
+}
+
+/// When the machine goes down
+enum When {
+    Now,
+    At(DateTime<Local>),
+}
+
+struct Request {
+    action: Action,
+    when: When,
+    /// shutdown -k, only the wall message is sent
+    wall_only: bool,
+    /// shutdown -c
+    cancel: bool,
+    /// reboot -w, only the wtmp record is written
+    wtmp_only: bool,
+    no_wall: bool,
+    /// Wall message given to shutdown instead of the default one
+    message: Option<String>,
+}
+
+/// reboot, poweroff, halt and shutdown. Anyone but root is turned away by
+/// logind. A root shell gets systemd's wall message and a ShellEvent::Shutdown,
+/// on which the server closes the session as if the machine went down; a
+/// shutdown scheduled for later only gets the message
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerCommand {
+    Reboot,
+    Poweroff,
+    Halt,
+    Shutdown,
+}
+
+impl PowerCommand {
+    fn invalid(&self, message: String) -> CommandError {
+        CommandError::InvalidArguments(format!("{}: {}", self.name(), message))
+    }
+
+    fn parse(&self, args: &[String]) -> Result<Request, CommandError> {
+        let mut request = Request {
+            action: match self {
+                PowerCommand::Reboot => Action::Reboot,
+                PowerCommand::Halt => Action::Halt,
+                PowerCommand::Poweroff | PowerCommand::Shutdown => Action::Poweroff,
+            },
+            when: When::Now,
+            wall_only: false,
+            cancel: false,
+            wtmp_only: false,
+            no_wall: false,
+            message: None,
+        };
+        let mut operands = Vec::new();
+        for arg in args {
+            match arg.as_str() {
+                "--reboot" => request.action = Action::Reboot,
+                "--poweroff" => request.action = Action::Poweroff,
+                "--halt" => request.action = Action::Halt,
+                "--wtmp-only" if *self != PowerCommand::Shutdown => request.wtmp_only = true,
+                "--no-wall" => request.no_wall = true,
+                "--force" | "--no-wtmp" | "--no-sync" => {}
+                long if long.starts_with("--") => {
+                    return Err(self.invalid(format!("unrecognized option '{}'", long)));
+                }
+                short if short.len() > 1 && short.starts_with('-') => {
+                    for flag in short[1..].chars() {
+                        match (self, flag) {
+                            (PowerCommand::Shutdown, 'r') => request.action = Action::Reboot,
+                            (PowerCommand::Shutdown, 'h' | 'P') => {
+                                request.action = Action::Poweroff
+                            }
+                            (PowerCommand::Shutdown, 'H') => request.action = Action::Halt,
+                            (PowerCommand::Shutdown, 'k') => request.wall_only = true,
+                            (PowerCommand::Shutdown, 'c') => request.cancel = true,
+                            (PowerCommand::Shutdown, 't') => {}
+                            (_, 'p') => request.action = Action::Poweroff,
+                            (_, 'w') => request.wtmp_only = true,
+                            (_, 'f' | 'd' | 'n' | 'i') => {}
+                            (_, other) => {
+                                return Err(self.invalid(format!("invalid option -- '{}'", other)));
+                            }
+                        }
+                    }
+                }
+                operand => operands.push(operand.to_string()),
+            }
+        }
+
+        if *self == PowerCommand::Shutdown {
+            // Without a time shutdown waits a minute
+            let time = operands.first().map_or("+1", String::as_str);
+            request.when = parse_time(time).ok_or_else(|| {
+                self.invalid(format!("Failed to parse time specification: {}", time))
+            })?;
+            if operands.len() > 1 {
+                request.message = Some(operands[1..].join(" "));
+            }
+        } else if !operands.is_empty() {
+            return Err(self.invalid("This command takes no arguments.".to_string()));
+        }
+        Ok(request)
+    }
+}
+
+/// `now`, `+MINUTES` or `HH:MM` as shutdown takes them
+fn parse_time(time: &str) -> Option<When> {
+    if time == "now" {
+        return Some(When::Now);
+    }
+    if let Some(minutes) = time.strip_prefix('+') {
+        let minutes: u32 = minutes.parse().ok()?;
+        return Some(if minutes == 0 {
+            When::Now
+        } else {
+            When::At(Local::now().checked_add_signed(Duration::minutes(minutes.into()))?)
+        });
+    }
+    let at = NaiveTime::parse_from_str(time, "%H:%M").ok()?;
+    let now = Local::now();
+    let today = now.with_time(at).earliest()?;
+    Some(When::At(if today > now {
+        today
+    } else {
+        today + Duration::days(1)
+    }))
+}
+
+/// systemd's wall message, shown on every terminal of the machine
+fn broadcast(hostname: &str, message: &str) -> String {
+    format!(
+        "\r\nBroadcast message from root@{} on pts/0 ({}):\r\n\r\n{}\r\n\r\n",
+        hostname,
+        Local::now().format("%a %Y-%m-%d %H:%M:%S %Z"),
+        message
+    )
+}
+
+#[async_trait]
+impl Command for PowerCommand {
+    fn name(&self) -> &'static str {
+        match self {
+            PowerCommand::Reboot => "reboot",
+            PowerCommand::Poweroff => "poweroff",
+            PowerCommand::Halt => "halt",
+            PowerCommand::Shutdown => "shutdown",
+        }
+    }
+
+    fn help(&self) -> String {
+        if *self == PowerCommand::Shutdown {
+            return "shutdown [OPTIONS...] [TIME] [WALL...]\r\n\
+            \r\n\
+            Shut down the system.\r\n\
+            \r\n\
+            \x20    --help      Show this help\r\n\
+            \x20 -H --halt      Halt the machine\r\n\
+            \x20 -P --poweroff  Power-off the machine\r\n\
+            \x20 -r --reboot    Reboot the machine\r\n\
+            \x20 -h             Equivalent to --poweroff, overridden by --halt\r\n\
+            \x20 -k             Don't halt/power-off/reboot, just send warnings\r\n\
+            \x20    --no-wall   Don't send wall message before halt/power-off/reboot\r\n\
+            \x20 -c             Cancel a pending shutdown\r\n\
+            \r\n\
+            See the shutdown(8) man page for details.\r\n"
+                .to_string();
+        }
+        format!(
+            "{} [OPTIONS...]\r\n\
+            \r\n\
+            {} the system.\r\n\
+            \r\n\
+            \x20    --help      Show this help\r\n\
+            \x20    --halt      Halt the machine\r\n\
+            \x20 -p --poweroff  Switch off the machine\r\n\
+            \x20    --reboot    Reboot the machine\r\n\
+            \x20 -f --force     Force immediate halt/power-off/reboot\r\n\
+            \x20 -w --wtmp-only Don't halt/power-off/reboot, just write wtmp record\r\n\
+            \x20 -d --no-wtmp   Don't write wtmp record\r\n\
+            \x20    --no-wall   Don't send wall message before halt/power-off/reboot\r\n\
+            \r\n\
+            See the halt(8) man page for details.\r\n",
+            self.name(),
+            match self {
+                PowerCommand::Reboot => "Reboot",
+                PowerCommand::Halt => "Halt",
+                _ => "Power off",
+            }
+        )
+    }
+
+    fn version(&self) -> String {
+        "systemd 245 (245.4-4ubuntu3.22)\r\n\
+        +PAM +AUDIT +SELINUX +IMA +APPARMOR +SMACK +SYSVINIT +UTMP +LIBCRYPTSETUP +GCRYPT +GNUTLS +ACL +XZ +LZ4 +SECCOMP +BLKID +ELFUTILS +KMOD +IDN2 -IDN +PCRE2 default-hierarchy=hybrid\r\n"
+            .to_string()
+    }
+
+    fn requires_privileges(&self) -> bool {
+        true
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        if args.iter().any(|a| a == "--help") {
+            return Ok(self.help());
+        }
+        if args.iter().any(|a| a == "--version") {
+            return Ok(self.version());
+        }
+        let request = self.parse(args)?;
+        let root = context.username == "root";
+        let immediate = matches!(request.when, When::Now)
+            && !request.wall_only
+            && !request.cancel
+            && !request.wtmp_only;
+        if !request.cancel && !request.wtmp_only {
+            context.push_event(ShellEvent::Shutdown {
+                command: std::iter::once(self.name())
+                    .chain(args.iter().map(String::as_str))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                action: request.action.as_str(),
+                permitted: root,
+                immediate,
+            });
+        }
+
+        // Busybox has no logind to ask and no wall message
+        if !context.os.is_ubuntu() {
+            return if root {
+                Ok(String::new())
+            } else {
+                Err(CommandError::PermissionDenied(format!(
+                    "{}: Operation not permitted",
+                    self.name()
+                )))
+            };
+        }
+
+        if !root {
+            return Err(CommandError::PermissionDenied(match self {
+                PowerCommand::Shutdown if request.cancel => {
+                    "Failed to talk to logind, shutdown hasn't been cancelled: Access denied"
+                        .to_string()
+                }
+                PowerCommand::Shutdown => "Failed to set wall message, ignoring: Access denied\r\n\
+                    Failed to call ScheduleShutdown in logind, proceeding with immediate shutdown: Access denied\r\n\
+                    Failed to talk to init daemon."
+                    .to_string(),
+                _ => request.action.denied(),
+            }));
+        }
+        if request.cancel || request.wtmp_only {
+            return Ok(String::new());
+        }
+
+        let (mut output, default_message) = match request.when {
+            When::Now => (
+                String::new(),
+                format!(
+                    "The system is going down for {} NOW!",
+                    request.action.as_str()
+                ),
+            ),
+            When::At(at) => {
+                let at = at.format("%a %Y-%m-%d %H:%M:%S %Z");
+                (
+                    if request.wall_only {
+                        String::new()
+                    } else {
+                        format!(
+                            "Shutdown scheduled for {}, use 'shutdown -c' to cancel.\r\n",
+                            at
+                        )
+                    },
+                    format!(
+                        "The system is going down for {} at {}!",
+                        request.action.as_str(),
+                        at
+                    ),
+                )
+            }
+        };
+        if !request.no_wall {
+            output.push_str(&broadcast(
+                &context.hostname,
+                request.message.as_deref().unwrap_or(&default_message),
+            ));
+        }
+        Ok(output)
+    }
+}
@@ -0,0 +1,628 @@
+use chrono::{DateTime, Duration, Local};
+use rand::{RngExt, rng};
+
+/// Categorizes a simulated process so its randomized resource usage looks realistic.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProcessType {
+    /// Kernel thread, shown in brackets (e.g. `[kthreadd]`). Essentially zero resources.
+    KernelThread,
+    /// Core systemd / early-boot daemon (journald, udevd, logind, ...). Small, stable usage.
+    SystemdDaemon,
+    /// Long-running service daemon (apache, mysqld, dockerd, ...). Larger memory footprint.
+    ServiceDaemon,
+    /// Interactive user process (bash, ps, ...). On a TTY, recent start time.
+    UserProcess,
+}
+
+/// Static template of system processes: (pid, user, command, process_type).
+const SYSTEM_PROCESSES: &[(u32, &str, &str, ProcessType)] = &[
+    // --- PID 1: the init system ---
+    (1, "root", "/sbin/init splash", ProcessType::SystemdDaemon),
+    // --- Kernel threads (spawned by kthreadd, PIDs 2..~200) ---
+    (2, "root", "[kthreadd]", ProcessType::KernelThread),
+    (3, "root", "[rcu_gp]", ProcessType::KernelThread),
+    (4, "root", "[rcu_par_gp]", ProcessType::KernelThread),
+    (
+        6,
+        "root",
+        "[kworker/0:0H-kblockd]",
+        ProcessType::KernelThread,
+    ),
+    (8, "root", "[mm_percpu_wq]", ProcessType::KernelThread),
+    (9, "root", "[ksoftirqd/0]", ProcessType::KernelThread),
+    (10, "root", "[rcu_tasks_rude_]", ProcessType::KernelThread),
+    (11, "root", "[rcu_tasks_trace]", ProcessType::KernelThread),
+    (12, "root", "[cpuhp/0]", ProcessType::KernelThread),
+    (13, "root", "[idle_inject/0]", ProcessType::KernelThread),
+    (14, "root", "[migration/0]", ProcessType::KernelThread),
+    (15, "root", "[cpuhp/1]", ProcessType::KernelThread),
+    (16, "root", "[idle_inject/1]", ProcessType::KernelThread),
+    (17, "root", "[migration/1]", ProcessType::KernelThread),
+    (18, "root", "[ksoftirqd/1]", ProcessType::KernelThread),
+    (
+        20,
+        "root",
+        "[kworker/1:0H-kblockd]",
+        ProcessType::KernelThread,
+    ),
+    (22, "root", "[kdevtmpfs]", ProcessType::KernelThread),
+    (23, "root", "[netns]", ProcessType::KernelThread),
+    (24, "root", "[rcu_tasks_kthre]", ProcessType::KernelThread),
+    (25, "root", "[kauditd]", ProcessType::KernelThread),
+    (26, "root", "[khungtaskd]", ProcessType::KernelThread),
+    (27, "root", "[oom_reaper]", ProcessType::KernelThread),
+    (28, "root", "[writeback]", ProcessType::KernelThread),
+    (29, "root", "[kcompactd0]", ProcessType::KernelThread),
+    (30, "root", "[ksmd]", ProcessType::KernelThread),
+    (31, "root", "[khugepaged]", ProcessType::KernelThread),
+    (33, "root", "[kintegrityd]", ProcessType::KernelThread),
+    (34, "root", "[kblockd]", ProcessType::KernelThread),
+    (35, "root", "[blkcg_punt_bio]", ProcessType::KernelThread),
+    (40, "root", "[tpm_dev_wq]", ProcessType::KernelThread),
+    (41, "root", "[ata_sff]", ProcessType::KernelThread),
+    (43, "root", "[md]", ProcessType::KernelThread),
+    (44, "root", "[edac-poller]", ProcessType::KernelThread),
+    (45, "root", "[devfreq_wq]", ProcessType::KernelThread),
+    (46, "root", "[watchdogd]", ProcessType::KernelThread),
+    (48, "root", "[kworker/0:1]", ProcessType::KernelThread),
+    (49, "root", "[kworker/1:1]", ProcessType::KernelThread),
+    (50, "root", "[kswapd0]", ProcessType::KernelThread),
+    (51, "root", "[ecryptfs-kthrea]", ProcessType::KernelThread),
+    (52, "root", "[kworker/u32:0]", ProcessType::KernelThread),
+    (53, "root", "[kworker/u32:1]", ProcessType::KernelThread),
+    (54, "root", "[kworker/u32:2]", ProcessType::KernelThread),
+    (60, "root", "[cryptd]", ProcessType::KernelThread),
+    (61, "root", "[kstrp]", ProcessType::KernelThread),
+    (62, "root", "[charger_manager]", ProcessType::KernelThread),
+    (150, "root", "[kworker/0:1H]", ProcessType::KernelThread),
+    (151, "root", "[kworker/1:1H]", ProcessType::KernelThread),
+    (152, "root", "[kworker/0:2]", ProcessType::KernelThread),
+    (153, "root", "[kworker/1:2]", ProcessType::KernelThread),
+    (154, "root", "[kworker/u32:3]", ProcessType::KernelThread),
+    (190, "root", "[jbd2/sda1-8]", ProcessType::KernelThread),
+    (191, "root", "[ext4-rsv-conver]", ProcessType::KernelThread),
+    (192, "root", "[ipv6_addrconf]", ProcessType::KernelThread),
+    // --- Core systemd / early-boot daemons ---
+    (
+        260,
+        "root",
+        "/lib/systemd/systemd-journald",
+        ProcessType::SystemdDaemon,
+    ),
+    (
+        275,
+        "root",
+        "/lib/systemd/systemd-udevd",
+        ProcessType::SystemdDaemon,
+    ),
+    (
+        285,
+        "systemd+",
+        "/lib/systemd/systemd-resolved",
+        ProcessType::SystemdDaemon,
+    ),
+    (
+        290,
+        "systemd+",
+        "/lib/systemd/systemd-networkd",
+        ProcessType::SystemdDaemon,
+    ),
+    (
+        300,
+        "systemd+",
+        "/lib/systemd/systemd-timesyncd",
+        ProcessType::SystemdDaemon,
+    ),
+    (305, "root", "/usr/sbin/cron -f", ProcessType::SystemdDaemon),
+    (
+        310,
+        "message+",
+        "/usr/bin/dbus-daemon --system --address=systemd --nofork --nopidfile",
+        ProcessType::SystemdDaemon,
+    ),
+    (
+        315,
+        "root",
+        "/lib/systemd/systemd-logind",
+        ProcessType::SystemdDaemon,
+    ),
+    (
+        320,
+        "syslog",
+        "/usr/sbin/rsyslogd -n -iNONE",
+        ProcessType::SystemdDaemon,
+    ),
+    (
+        325,
+        "root",
+        "/usr/sbin/irqbalance --foreground",
+        ProcessType::SystemdDaemon,
+    ),
+    (
+        330,
+        "root",
+        "/usr/lib/accountsservice/accounts-daemon",
+        ProcessType::SystemdDaemon,
+    ),
+    (
+        335,
+        "root",
+        "/usr/sbin/NetworkManager --no-daemon",
+        ProcessType::SystemdDaemon,
+    ),
+    (
+        340,
+        "root",
+        "/usr/lib/policykit-1/polkitd --no-debug",
+        ProcessType::SystemdDaemon,
+    ),
+    (
+        345,
+        "root",
+        "/usr/sbin/thermald --systemd",
+        ProcessType::SystemdDaemon,
+    ),
+    (
+        350,
+        "root",
+        "/usr/lib/snapd/snapd",
+        ProcessType::SystemdDaemon,
+    ),
+    (355, "root", "/usr/sbin/sshd -D", ProcessType::SystemdDaemon),
+    (360, "root", "/usr/sbin/atd -f", ProcessType::SystemdDaemon),
+    (
+        365,
+        "root",
+        "/lib/systemd/systemd-machined",
+        ProcessType::SystemdDaemon,
+    ),
+    (
+        370,
+        "root",
+        "/sbin/multipathd -d -s",
+        ProcessType::SystemdDaemon,
+    ),
+    (
+        375,
+        "root",
+        "/usr/libexec/fwupd/fwupd",
+        ProcessType::SystemdDaemon,
+    ),
+    (
+        380,
+        "root",
+        "/usr/sbin/ModemManager",
+        ProcessType::SystemdDaemon,
+    ),
+    (
+        385,
+        "root",
+        "/lib/systemd/systemd-networkd-wait-online",
+        ProcessType::SystemdDaemon,
+    ),
+    // --- Long-running service daemons ---
+    (
+        500,
+        "root",
+        "/usr/sbin/apache2 -k start",
+        ProcessType::ServiceDaemon,
+    ),
+    (
+        501,
+        "www-data",
+        "/usr/sbin/apache2 -k start",
+        ProcessType::ServiceDaemon,
+    ),
+    (
+        502,
+        "www-data",
+        "/usr/sbin/apache2 -k start",
+        ProcessType::ServiceDaemon,
+    ),
+    (
+        503,
+        "www-data",
+        "/usr/sbin/apache2 -k start",
+        ProcessType::ServiceDaemon,
+    ),
+    (
+        504,
+        "www-data",
+        "/usr/sbin/apache2 -k start",
+        ProcessType::ServiceDaemon,
+    ),
+    (
+        505,
+        "www-data",
+        "/usr/sbin/apache2 -k start",
+        ProcessType::ServiceDaemon,
+    ),
+    (510, "mysql", "/usr/sbin/mysqld", ProcessType::ServiceDaemon),
+    (
+        520,
+        "redis",
+        "redis-server 127.0.0.1:6379",
+        ProcessType::ServiceDaemon,
+    ),
+    (
+        530,
+        "root",
+        "/usr/bin/containerd",
+        ProcessType::ServiceDaemon,
+    ),
+    (
+        531,
+        "root",
+        "/usr/bin/dockerd -H fd:// --containerd=/run/containerd/containerd.sock",
+        ProcessType::ServiceDaemon,
+    ),
+    (
+        540,
+        "root",
+        "/usr/bin/docker-proxy -proto tcp -host-ip 0.0.0.0 -host-port 8080",
+        ProcessType::ServiceDaemon,
+    ),
+    (
+        541,
+        "root",
+        "/usr/bin/docker-proxy -proto tcp -host-ip 0.0.0.0 -host-port 3306",
+        ProcessType::ServiceDaemon,
+    ),
+    (
+        550,
+        "postgres",
+        "/usr/lib/postgresql/14/bin/postgres -D /var/lib/postgresql/14/main -c config_file=/etc/postgresql/14/main/postgresql.conf",
+        ProcessType::ServiceDaemon,
+    ),
+];
+
+/// Represents a simulated process
+#[derive(Clone, Debug)]
+pub struct Process {
+    pub pid: u32,
+    /// Parent process, 0 for init and kthreadd
+    pub ppid: u32,
+    pub user: String,
+    pub command: String,
+    pub ptype: ProcessType,
+    pub cpu_percent: f32,
+    pub mem_percent: f32,
+    pub vsz: u32,
+    pub rss: u32,
+    pub tty: String,
+    pub stat: String,
+    pub start_time: DateTime<Local>,
+    pub elapsed: Duration,
+}
+
+impl Process {
+    /// Builds a process with resource usage randomized to fit its category.
+    /// `boot_time` is shared by all system processes so their STIME is consistent.
+    pub fn new(
+        pid: u32,
+        user: String,
+        command: String,
+        ptype: ProcessType,
+        boot_time: DateTime<Local>,
+    ) -> Self {
+        let mut rng = rng();
+        let mut p = Process {
+            pid,
+            ppid: match (pid, ptype) {
+                (1 | 2, _) => 0,
+                (_, ProcessType::KernelThread) => 2,
+                _ => 1,
+            },
+            user,
+            command,
+            ptype,
+            cpu_percent: 0.0,
+            mem_percent: 0.0,
+            vsz: 0,
+            rss: 0,
+            tty: "?".to_string(),
+            stat: "S".to_string(),
+            start_time: boot_time,
+            elapsed: Duration::zero(),
+        };
+
+        match ptype {
+            ProcessType::KernelThread => {
+                // Kernel threads use essentially no CPU/memory.
+                p.cpu_percent = rng.random_range(0.0..0.1);
+                p.mem_percent = 0.0;
+                p.vsz = 0;
+                p.rss = 0;
+                p.tty = "?".to_string();
+                p.stat = "S".to_string();
+                p.start_time = boot_time;
+                p.elapsed = Duration::seconds(rng.random_range(0..50));
+            }
+            ProcessType::SystemdDaemon => {
+                p.cpu_percent = rng.random_range(0.0..0.3);
+                p.mem_percent = rng.random_range(0.1..1.0);
+                p.vsz = rng.random_range(40000..180000);
+                p.rss = rng.random_range(3000..25000);
+                p.tty = "?".to_string();
+                p.stat = "Ss".to_string();
+                p.start_time = boot_time;
+                p.elapsed = Duration::seconds(rng.random_range(0..180));
+            }
+            ProcessType::ServiceDaemon => {
+                p.cpu_percent = rng.random_range(0.0..2.0);
+                p.mem_percent = rng.random_range(0.3..5.0);
+                p.vsz = rng.random_range(100000..1_200_000);
+                p.rss = rng.random_range(8000..150_000);
+                p.tty = "?".to_string();
+                let states = ["Ss", "Sl", "S"];
+                p.stat = states[rng.random_range(0..states.len())].to_string();
+                p.start_time = boot_time;
+                p.elapsed = Duration::seconds(rng.random_range(0..600));
+            }
+            ProcessType::UserProcess => {
+                p.cpu_percent = rng.random_range(0.0..5.0);
+                p.mem_percent = rng.random_range(0.0..2.0);
+                p.vsz = rng.random_range(1000..300000);
+                p.rss = rng.random_range(500..50000);
+                p.tty = format!("pts/{}", rng.random_range(0..4));
+                let states = ["R", "S", "D", "Z", "T"];
+                let flags = ["", "+", "<", "s", "l", "N"];
+                p.stat = format!(
+                    "{}{}",
+                    states[rng.random_range(0..states.len())],
+                    flags[rng.random_range(0..flags.len())]
+                );
+                p.start_time = Local::now() - Duration::minutes(rng.random_range(0..120));
+                p.elapsed = Duration::minutes(rng.random_range(0..500));
+            }
+        }
+
+        p
+    }
+
+    /// Process name as matched by `pkill`/`pgrep`: the basename of argv[0],
+    /// or the bracketed name for kernel threads
+    pub fn name(&self) -> &str {
+        if let Some(name) = self.command.strip_prefix('[') {
+            return name.trim_end_matches(']');
+        }
+        let argv0 = self
+            .command
+            .split_whitespace()
+            .next()
+            .unwrap_or(&self.command);
+        argv0.rsplit('/').next().unwrap_or(argv0)
+    }
+
+    pub fn format_time(&self) -> String {
+        let minutes = self.elapsed.num_minutes();
+        if minutes < 60 {
+            format!("0:{:02}", minutes)
+        } else {
+            format!("{}:{:02}", minutes / 60, minutes % 60)
+        }
+    }
+}
+
+/// A command the session shell sent to the background with `&`
+#[derive(Clone, Debug)]
+pub struct Job {
+    pub number: usize,
+    pub pid: u32,
+    /// Command line without the trailing `&`
+    pub command: String,
+}
+
+/// Per-session view of the fake process list. Holds a believable base system
+/// plus the attacker's own sshd/bash entries with stable PIDs, so `ps`, `kill`
+/// and `pkill` agree with each other and `kill` can actually remove entries.
+#[derive(Clone, Debug)]
+pub struct ProcessTable {
+    processes: Vec<Process>,
+    boot_time: DateTime<Local>,
+    next_pid: u32,
+    shell_pid: Option<u32>,
+    /// Background jobs in the order they were started, the last one is the current job
+    jobs: Vec<Job>,
+}
+
+impl Default for ProcessTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProcessTable {
+    /// Create a table with the base system processes
+    pub fn new() -> Self {
+        let mut rng = rng();
+
+        // Single shared boot time so every system process reports the same STIME.
+        // Pretends the machine has been up between ~8h and ~23h.
+        let boot_time = Local::now() - Duration::minutes(rng.random_range(480..1400));
+
+        let mut processes: Vec<Process> = SYSTEM_PROCESSES
+            .iter()
+            .map(|(pid, user, cmd, ptype)| {
+                Process::new(*pid, user.to_string(), cmd.to_string(), *ptype, boot_time)
+            })
+            .collect();
+        // Workers (apache's www-data children) belong to the root process they were forked from
+        for index in 0..processes.len() {
+            let parent = processes[..index]
+                .iter()
+                .find(|p| p.user == "root" && p.command == processes[index].command)
+                .map(|p| p.pid);
+            if let Some(parent) = parent {
+                processes[index].ppid = parent;
+            }
+        }
+
+        Self {
+            processes,
+            boot_time,
+            next_pid: rng.random_range(1000..4000),
+            shell_pid: None,
+            jobs: Vec::new(),
+        }
+    }
+
+    /// Add the session's own sshd and login shell processes. Idempotent,
+    /// returns the PID of the session shell.
+    pub fn attach_session(&mut self, user: &str) -> u32 {
+        if let Some(pid) = self.shell_pid {
+            return pid;
+        }
+
+        let session = [
+            ("root", format!("sshd: {} [priv]", user), "?", "Ss"),
+            (user, format!("sshd: {}@pts/0", user), "?", "S"),
+            (user, "-bash".to_string(), "pts/0", "Ss"),
+        ];
+
+        // The listening sshd forks the session's privileged monitor
+        let mut shell_pid = self
+            .processes
+            .iter()
+            .find(|p| p.command.starts_with("/usr/sbin/sshd"))
+            .map_or(1, |p| p.pid);
+        for (owner, command, tty, stat) in session {
+            let pid = self.allocate_pid();
+            let mut process = Process::new(
+                pid,
+                owner.to_string(),
+                command,
+                ProcessType::UserProcess,
+                self.boot_time,
+            );
+            process.ppid = shell_pid;
+            process.tty = tty.to_string();
+            process.stat = stat.to_string();
+            process.start_time = Local::now();
+            process.elapsed = Duration::zero();
+            self.processes.push(process);
+            shell_pid = pid;
+        }
+
+        self.shell_pid = Some(shell_pid);
+        shell_pid
+    }
+
+    /// PID of the session's login shell, if a session is attached
+    pub fn shell_pid(&self) -> Option<u32> {
+        self.shell_pid
+    }
+
+    /// Hand out the next PID, increasing like a busy system would
+    pub fn allocate_pid(&mut self) -> u32 {
+        let pid = self.next_pid;
+        self.next_pid += rng().random_range(1..40);
+        pid
+    }
+
+    /// When the machine pretends to have booted
+    pub fn boot_time(&self) -> DateTime<Local> {
+        self.boot_time
+    }
+
+    /// Start a new process owned by `user` on the session terminal
+    pub fn spawn(&mut self, user: &str, command: &str) -> u32 {
+        let pid = self.allocate_pid();
+        let mut process = Process::new(
+            pid,
+            user.to_string(),
+            command.to_string(),
+            ProcessType::UserProcess,
+            self.boot_time,
+        );
+        process.ppid = self.shell_pid.unwrap_or(1);
+        process.tty = "pts/0".to_string();
+        process.stat = "R+".to_string();
+        process.start_time = Local::now();
+        process.elapsed = Duration::zero();
+        self.processes.push(process);
+        pid
+    }
+
+    /// All processes, sorted by PID
+    pub fn processes(&self) -> Vec<&Process> {
+        let mut processes: Vec<&Process> = self.processes.iter().collect();
+        processes.sort_by_key(|p| p.pid);
+        processes
+    }
+
+    /// Look up a process by PID
+    pub fn get(&self, pid: u32) -> Option<&Process> {
+        self.processes.iter().find(|p| p.pid == pid)
+    }
+
+    /// Find processes by name (or full command line with `full`), as `pgrep`/`pkill` do
+    pub fn find(&self, pattern: &str, full: bool, exact: bool) -> Vec<&Process> {
+        self.processes()
+            .into_iter()
+            .filter(|p| {
+                let haystack = if full { p.command.as_str() } else { p.name() };
+                if exact {
+                    haystack == pattern
+                } else {
+                    haystack.contains(pattern)
+                }
+            })
+            .collect()
+    }
+
+    /// Remove a process after it was killed. init, kernel threads and the
+    /// session shell ignore the signal and stay, like on a real system.
+    pub fn remove(&mut self, pid: u32) -> Option<Process> {
+        if pid == 1 || self.shell_pid == Some(pid) {
+            return None;
+        }
+        let index = self
+            .processes
+            .iter()
+            .position(|p| p.pid == pid && p.ptype != ProcessType::KernelThread)?;
+        self.jobs.retain(|job| job.pid != pid);
+        Some(self.processes.remove(index))
+    }
+
+    /// Register `pid` as a background job, returns the job number
+    pub fn add_job(&mut self, pid: u32, command: &str) -> usize {
+        let number = self.jobs.iter().map(|j| j.number).max().unwrap_or(0) + 1;
+        self.jobs.push(Job {
+            number,
+            pid,
+            command: command.to_string(),
+        });
+        number
+    }
+
+    /// Background jobs, oldest first
+    pub fn jobs(&self) -> &[Job] {
+        &self.jobs
+    }
+
+    /// Resolve a job spec like bash: `%%`, `%+` or nothing for the current job,
+    /// `%-` for the previous one, `%N` or `N`, `%name` (prefix) and `%?text`
+    pub fn find_job(&self, spec: &str) -> Option<&Job> {
+        let spec = spec.strip_prefix('%').unwrap_or(spec);
+        match spec {
+            "" | "%" | "+" => self.jobs.last(),
+            "-" => self.jobs.iter().rev().nth(1).or(self.jobs.last()),
+            _ => {
+                if let Ok(number) = spec.parse::<usize>() {
+                    self.jobs.iter().find(|j| j.number == number)
+                } else if let Some(text) = spec.strip_prefix('?') {
+                    self.jobs.iter().rev().find(|j| j.command.contains(text))
+                } else {
+                    self.jobs.iter().rev().find(|j| j.command.starts_with(spec))
+                }
+            }
+        }
+    }
+
+    /// Forget a job, its process keeps running
+    pub fn remove_job(&mut self, number: usize) -> Option<Job> {
+        let index = self.jobs.iter().position(|j| j.number == number)?;
+        Some(self.jobs.remove(index))
+    }
+}
@@ -0,0 +1,397 @@
+//! The per-process part of /proc, made up from the session's process table.
+//!
+//! `/proc/<pid>` only exists for processes `ps` lists and says the same about
+//! them: owner, parent, state, memory and command line. `/proc/self` points at
+//! the process doing the reading, which the reading command registers for the
+//! duration like `ps` does, so `cat /proc/self/status` names `cat` and its
+//! parent is the login shell. Nothing is written to the filesystem, which is
+//! shared by all sessions while the process table is not.
+
+use super::accounts::AccountTable;
+use super::context::CommandContext;
+use super::process_table::{Process, ProcessTable, ProcessType};
+use crate::filesystem::fs2::{FileContent, FileEntryView, FileSystem, Inode};
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+/// Files in every /proc/<pid>
+const PROCESS_FILES: &[&str] = &["cmdline", "comm", "environ", "stat", "status"];
+
+/// USER_HZ, the unit of the times in /proc/<pid>/stat
+const CLOCK_TICKS: i64 = 100;
+
+/// Environment of the daemons started by systemd
+const DAEMON_ENVIRON: &str =
+    "LANG=C.UTF-8\0PATH=/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin\0";
+
+/// Whether `path`, relative to `cwd`, is /proc or below it
+pub(crate) fn is_proc_path(cwd: &str, path: &str) -> bool {
+    components(cwd, path).first().is_some_and(|c| c == "proc")
+}
+
+/// What /proc shows one reading command
+pub(crate) struct ProcView<'a> {
+    processes: &'a ProcessTable,
+    /// The reading command, what /proc/self points at
+    own_pid: u32,
+    cwd: String,
+    username: String,
+    /// The session's environment, `NAME=value` sorted by name
+    environment: Vec<String>,
+    /// Accounts, for the uids and gids in status
+    accounts: AccountTable,
+}
+
+impl<'a> ProcView<'a> {
+    pub(crate) fn new(
+        processes: &'a ProcessTable,
+        own_pid: u32,
+        fs: &FileSystem,
+        context: &CommandContext,
+    ) -> Self {
+        let mut environment: Vec<String> = context
+            .env_vars
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        environment.sort();
+
+        Self {
+            processes,
+            own_pid,
+            cwd: context.cwd.clone(),
+            username: context.username.clone(),
+            environment,
+            accounts: AccountTable::read(fs, &context.username),
+        }
+    }
+
+    /// The entry at `path`, symlinks followed. None when `path` isn't in the
+    /// per-process part of /proc and the filesystem has to answer
+    pub(crate) fn lookup(&self, path: &str) -> Option<std::io::Result<FileEntryView>> {
+        let components = components(&self.cwd, path);
+        let (pid, rest) = match components.as_slice() {
+            [proc, pid, rest @ ..] if proc == "proc" => (self.pid(pid)?, rest),
+            _ => return None,
+        };
+        let Some(process) = self.processes.get(pid) else {
+            return Some(Err(Error::from(ErrorKind::NotFound)));
+        };
+        Some(match rest {
+            [] => Ok(directory(&pid.to_string())),
+            [file] => self.file(process, file),
+            _ => Err(Error::from(ErrorKind::NotFound)),
+        })
+    }
+
+    /// The listing of /proc with the fs's own entries, or of a process
+    /// directory. None for anything else
+    pub(crate) fn list(
+        &self,
+        fs: &FileSystem,
+        path: &str,
+    ) -> Option<std::io::Result<Vec<FileEntryView>>> {
+        if components(&self.cwd, path) != ["proc"] {
+            return Some(self.lookup(path)?.and_then(|entry| {
+                match entry.file_content {
+                    Some(FileContent::Directory(_)) => Ok(PROCESS_FILES
+                        .iter()
+                        .map(|name| file(name, Vec::new()))
+                        .collect()),
+                    _ => Err(Error::from(ErrorKind::NotADirectory)),
+                }
+            }));
+        }
+
+        let mut entries: Vec<FileEntryView> = self
+            .processes
+            .processes()
+            .iter()
+            .map(|p| directory(&p.pid.to_string()))
+            .collect();
+        entries.push(FileEntryView {
+            name: "self".to_string(),
+            inode: Inode::default(),
+            file_content: Some(FileContent::SymbolicLink(self.own_pid.to_string())),
+        });
+        entries.extend(fs.list_directory("/proc").unwrap_or_default());
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        entries.dedup_by(|a, b| a.name == b.name);
+        Some(Ok(entries))
+    }
+
+    /// PID a /proc entry name stands for, None for names that aren't processes
+    fn pid(&self, name: &str) -> Option<u32> {
+        match name {
+            "self" => Some(self.own_pid),
+            name => name.parse().ok(),
+        }
+    }
+
+    fn file(&self, process: &Process, name: &str) -> std::io::Result<FileEntryView> {
+        let content = match name {
+            "cmdline" => cmdline(process),
+            "comm" => format!("{}\n", comm(process)).into_bytes(),
+            "environ" => self.environ(process)?,
+            "stat" => self.stat(process).into_bytes(),
+            "status" => self.status(process).into_bytes(),
+            _ => return Err(Error::from(ErrorKind::NotFound)),
+        };
+        Ok(file(name, content))
+    }
+
+    /// Only the owner and root may read it
+    fn environ(&self, process: &Process) -> std::io::Result<Vec<u8>> {
+        if self.username != "root" && self.username != process.user {
+            return Err(Error::from(ErrorKind::PermissionDenied));
+        }
+        Ok(match process.ptype {
+            ProcessType::KernelThread => Vec::new(),
+            ProcessType::SystemdDaemon | ProcessType::ServiceDaemon => {
+                DAEMON_ENVIRON.as_bytes().to_vec()
+            }
+            ProcessType::UserProcess => self
+                .environment
+                .iter()
+                .flat_map(|variable| variable.bytes().chain([0]))
+                .collect(),
+        })
+    }
+
+    /// uid and gid of `user` from the accounts. ps shortens long names with
+    /// a `+`, those match the first account starting the same way
+    fn ids(&self, user: &str) -> (u32, u32) {
+        let matches = |name: &str| match user.strip_suffix('+') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == user,
+        };
+        self.accounts
+            .accounts
+            .iter()
+            .find(|account| matches(&account.name))
+            .map(|account| (account.uid, account.gid))
+            .unwrap_or(if user == "root" { (0, 0) } else { (1000, 1000) })
+    }
+
+    /// Session of the process: the login shell's for processes on its
+    /// terminal, its own for daemons
+    fn session(&self, process: &Process) -> u32 {
+        match self.processes.shell_pid() {
+            Some(shell) if process.tty.starts_with("pts/") => shell,
+            _ => process.pid,
+        }
+    }
+
+    fn stat(&self, process: &Process) -> String {
+        let (tty_nr, tpgid) = match process.tty.strip_prefix("pts/") {
+            Some(number) => (
+                136 * 256 + number.parse::<i64>().unwrap_or(0),
+                process.pid as i64,
+            ),
+            None => (0, -1),
+        };
+        let cpu_ticks = process.elapsed.num_seconds() * CLOCK_TICKS;
+        let start_ticks = (process.start_time - self.processes.boot_time())
+            .num_milliseconds()
+            .max(0)
+            * CLOCK_TICKS
+            / 1000
+            + process.pid as i64;
+        let fields: Vec<String> = vec![
+            process.pid.to_string(),
+            format!("({})", comm(process)),
+            state(process).to_string(),
+            process.ppid.to_string(),
+            process.pid.to_string(),
+            self.session(process).to_string(),
+            tty_nr.to_string(),
+            tpgid.to_string(),
+            if process.ptype == ProcessType::KernelThread {
+                "69238880"
+            } else {
+                "4194560"
+            }
+            .to_string(),
+            (process.rss / 2).to_string(),
+            "0".to_string(),
+            (process.pid % 40).to_string(),
+            "0".to_string(),
+            (cpu_ticks * 3 / 4).to_string(),
+            (cpu_ticks / 4).to_string(),
+            "0".to_string(),
+            "0".to_string(),
+            "20".to_string(),
+            "0".to_string(),
+            threads(process).to_string(),
+            "0".to_string(),
+            start_ticks.to_string(),
+            (process.vsz as u64 * 1024).to_string(),
+            (process.rss / 4).to_string(),
+            "18446744073709551615".to_string(),
+        ];
+        // Addresses, signal masks and accounting nobody reads, 52 fields in all
+        let rest = "0 0 0 0 0 0 0 0 0 0 0 0 17 0 0 0 0 0 0 0 0 0 0 0 0 0 0";
+        format!("{} {}\n", fields.join(" "), rest)
+    }
+
+    fn status(&self, process: &Process) -> String {
+        let (uid, gid) = self.ids(&process.user);
+        let kernel_thread = process.ptype == ProcessType::KernelThread;
+        let session = self.session(process);
+        let mut status = format!(
+            "Name:\t{}\n\
+             Umask:\t{}\n\
+             State:\t{}\n\
+             Tgid:\t{pid}\n\
+             Ngid:\t0\n\
+             Pid:\t{pid}\n\
+             PPid:\t{}\n\
+             TracerPid:\t0\n\
+             Uid:\t{uid}\t{uid}\t{uid}\t{uid}\n\
+             Gid:\t{gid}\t{gid}\t{gid}\t{gid}\n\
+             FDSize:\t{}\n\
+             Groups:\t\n\
+             NStgid:\t{pid}\n\
+             NSpid:\t{pid}\n\
+             NSpgid:\t{pid}\n\
+             NSsid:\t{}\n",
+            comm(process),
+            if kernel_thread { "0000" } else { "0022" },
+            state_name(process),
+            process.ppid,
+            if kernel_thread { 64 } else { 256 },
+            session,
+            pid = process.pid,
+            uid = uid,
+            gid = gid,
+        );
+        if !kernel_thread {
+            status.push_str(&format!(
+                "VmPeak:\t{:>8} kB\n\
+                 VmSize:\t{:>8} kB\n\
+                 VmLck:\t       0 kB\n\
+                 VmPin:\t       0 kB\n\
+                 VmHWM:\t{:>8} kB\n\
+                 VmRSS:\t{:>8} kB\n",
+                process.vsz + process.vsz / 50,
+                process.vsz,
+                process.rss + process.rss / 20,
+                process.rss,
+            ));
+        }
+        let capabilities = if uid == 0 {
+            "000001ffffffffff"
+        } else {
+            "0000000000000000"
+        };
+        status.push_str(&format!(
+            "Threads:\t{}\n\
+             SigQ:\t0/15439\n\
+             SigPnd:\t0000000000000000\n\
+             ShdPnd:\t0000000000000000\n\
+             SigBlk:\t0000000000000000\n\
+             SigIgn:\t{}\n\
+             SigCgt:\t{}\n\
+             CapInh:\t0000000000000000\n\
+             CapPrm:\t{capabilities}\n\
+             CapEff:\t{capabilities}\n\
+             CapBnd:\t000001ffffffffff\n\
+             CapAmb:\t0000000000000000\n\
+             NoNewPrivs:\t0\n\
+             Seccomp:\t0\n\
+             Cpus_allowed:\t3\n\
+             Cpus_allowed_list:\t0-1\n\
+             voluntary_ctxt_switches:\t{}\n\
+             nonvoluntary_ctxt_switches:\t{}\n",
+            threads(process),
+            if kernel_thread {
+                "ffffffffffffffff"
+            } else {
+                "0000000000001000"
+            },
+            if kernel_thread {
+                "0000000000000000"
+            } else {
+                "0000000180014a02"
+            },
+            process.pid * 7 + process.elapsed.num_seconds() as u32,
+            process.pid % 13,
+            capabilities = capabilities,
+        ));
+        status
+    }
+}
+
+/// Path components of `path` resolved against `cwd`, `.` and `..` applied
+fn components(cwd: &str, path: &str) -> Vec<String> {
+    let mut components: Vec<String> = Vec::new();
+    let start = if path.starts_with('/') { "" } else { cwd };
+    for component in start.split('/').chain(path.split('/')) {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component.to_string()),
+        }
+    }
+    components
+}
+
+fn directory(name: &str) -> FileEntryView {
+    FileEntryView {
+        name: name.to_string(),
+        inode: Inode::default(),
+        file_content: Some(FileContent::Directory(Vec::new())),
+    }
+}
+
+fn file(name: &str, content: Vec<u8>) -> FileEntryView {
+    FileEntryView {
+        name: name.to_string(),
+        inode: Inode::default(),
+        file_content: Some(FileContent::RegularFile(Arc::new(content))),
+    }
+}
+
+/// The kernel's 15 character process name
+fn comm(process: &Process) -> String {
+    let name = process.name().trim_start_matches('-');
+    name.chars().take(15).collect()
+}
+
+/// argv separated and ended by NUL, empty for kernel threads
+fn cmdline(process: &Process) -> Vec<u8> {
+    if process.ptype == ProcessType::KernelThread {
+        return Vec::new();
+    }
+    process
+        .command
+        .split_whitespace()
+        .flat_map(|arg| arg.bytes().chain([0]))
+        .collect()
+}
+
+/// First letter of ps's STAT
+fn state(process: &Process) -> char {
+    process.stat.chars().next().unwrap_or('S')
+}
+
+fn state_name(process: &Process) -> &'static str {
+    match state(process) {
+        'R' => "R (running)",
+        'D' => "D (disk sleep)",
+        'Z' => "Z (zombie)",
+        'T' => "T (stopped)",
+        _ => "S (sleeping)",
+    }
+}
+
+/// ps marks multi-threaded processes with `l`
+fn threads(process: &Process) -> u32 {
+    if process.stat.contains('l') {
+        4 + process.pid % 5
+    } else {
+        1
+    }
+}
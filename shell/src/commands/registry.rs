@@ -47,6 +47,15 @@ impl CommandRegistry {
         }
     }
 
+    /// Drop every command (and alias) whose command name is not in `names`,
+    /// used to trim the registry down to what a persona's system would have
+    pub fn retain_commands(&mut self, names: &[&str]) {
+        self.commands
+            .retain(|_, command| names.contains(&command.name()));
+        self.stateful_commands
+            .retain(|_, command| names.contains(&command.name()));
+    }
+
     /// Execute a command by name with the given arguments and context
     pub async fn execute_command(
         &self,
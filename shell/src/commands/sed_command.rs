@@ -0,0 +1,860 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use super::dd_command::writable_by_user;
+use crate::filesystem::fs2::FileContent;
+use async_trait::async_trait;
+use regex::{Captures, Regex, RegexBuilder};
+use std::sync::Arc;
+
+const USAGE: &str = "Usage: sed [OPTION]... {script-only-if-no-other-script} [input-file]...\r\n\
+    \r\n\
+    \x20 -n, --quiet, --silent\r\n\
+    \x20                suppress automatic printing of pattern space\r\n\
+    \x20 -e script, --expression=script\r\n\
+    \x20                add the script to the commands to be executed\r\n\
+    \x20 -i[SUFFIX], --in-place[=SUFFIX]\r\n\
+    \x20                edit files in place (makes backup if SUFFIX supplied)\r\n\
+    \x20 -E, -r, --regexp-extended\r\n\
+    \x20                use extended regular expressions in the script\r\n\
+    \x20                (for portability use POSIX -E).\r\n\
+    \x20 -s, --separate\r\n\
+    \x20                consider files as separate rather than as a single\r\n\
+    \x20                continuous long stream.\r\n\
+    \x20     --help     display this help and exit\r\n\
+    \x20     --version  output version information and exit\r\n\
+    \r\n\
+    If no -e, --expression, -f, or --file option is given, then the first\r\n\
+    non-option argument is taken as the sed script to interpret.  All\r\n\
+    remaining arguments are names of input files; if no input files are\r\n\
+    specified, then the standard input is read.\r\n\
+    \r\n\
+    GNU sed home page: <https://www.gnu.org/software/sed/>.\r\n\
+    General help using GNU software: <https://www.gnu.org/gethelp/>.\r\n";
+
+/// Which lines an instruction applies to
+#[derive(Debug)]
+enum Address {
+    Line(usize),
+    Last,
+    Match(Regex),
+}
+
+impl Address {
+    fn matches(&self, line: &str, number: usize, last: bool) -> bool {
+        match self {
+            Address::Line(n) => number == *n,
+            Address::Last => last,
+            Address::Match(regex) => regex.is_match(line),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Action {
+    Substitute {
+        regex: Regex,
+        replacement: String,
+        global: bool,
+        /// Replace starting with this match, 1 unless a number flag was given
+        occurrence: usize,
+        print: bool,
+    },
+    Transliterate(Vec<(char, char)>),
+    Print,
+    Delete,
+    LineNumber,
+    Append(String),
+    Insert(String),
+    Change(String),
+    /// `q` prints the line before quitting, `Q` doesn't
+    Quit {
+        print: bool,
+    },
+}
+
+#[derive(Debug)]
+struct Instruction {
+    start: Option<Address>,
+    end: Option<Address>,
+    negate: bool,
+    action: Action,
+}
+
+impl Instruction {
+    /// Whether the instruction runs on this line. `in_range` is whether an
+    /// address range is open, updated as ranges start and end
+    fn selects(&self, in_range: &mut bool, line: &str, number: usize, last: bool) -> bool {
+        let selected = match (&self.start, &self.end) {
+            (None, _) => true,
+            (Some(start), None) => start.matches(line, number, last),
+            (Some(_), Some(end)) if *in_range => {
+                *in_range = match end {
+                    Address::Line(n) => number < *n,
+                    _ => !end.matches(line, number, last),
+                };
+                true
+            }
+            (Some(start), Some(end)) => {
+                if start.matches(line, number, last) {
+                    // A line number at or before the start closes the range at once,
+                    // a regex is only tried from the next line
+                    *in_range = match end {
+                        Address::Line(n) => number < *n,
+                        Address::Last => !last,
+                        Address::Match(_) => true,
+                    };
+                    true
+                } else {
+                    false
+                }
+            }
+        };
+        selected != self.negate
+    }
+}
+
+/// sed's command line
+#[derive(Debug, Default)]
+struct Options {
+    quiet: bool,
+    extended: bool,
+    /// Backup suffix when editing in place, empty for no backup
+    in_place: Option<String>,
+    scripts: Vec<String>,
+    files: Vec<String>,
+}
+
+fn usage_error(message: String) -> CommandError {
+    CommandError::InvalidArguments(format!("sed: {}\r\n{}", message, USAGE.trim_end()))
+}
+
+fn parse_options(args: &[String]) -> Result<Options, CommandError> {
+    let mut options = Options::default();
+    let mut operands = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--" => {
+                operands.extend(iter.by_ref().cloned());
+                break;
+            }
+            "-n" | "--quiet" | "--silent" => options.quiet = true,
+            "-E" | "-r" | "--regexp-extended" => options.extended = true,
+            "-s" | "-u" | "--separate" | "--unbuffered" | "--posix" => {}
+            "--in-place" => options.in_place = Some(String::new()),
+            "--expression" => match iter.next() {
+                Some(script) => options.scripts.push(script.clone()),
+                None => {
+                    return Err(usage_error(
+                        "option '--expression' requires an argument".into(),
+                    ));
+                }
+            },
+            long if long.starts_with("--in-place=") => {
+                options.in_place = Some(long["--in-place=".len()..].to_string())
+            }
+            long if long.starts_with("--expression=") => options
+                .scripts
+                .push(long["--expression=".len()..].to_string()),
+            long if long.starts_with("--") => {
+                return Err(usage_error(format!("unrecognized option '{}'", long)));
+            }
+            short if short.starts_with('-') && short.len() > 1 => {
+                let flags = &short[1..];
+                for (at, flag) in flags.char_indices() {
+                    let rest = &flags[at + flag.len_utf8()..];
+                    match flag {
+                        'n' => options.quiet = true,
+                        'E' | 'r' => options.extended = true,
+                        's' | 'u' => {}
+                        'i' => {
+                            options.in_place = Some(rest.to_string());
+                            break;
+                        }
+                        'e' if !rest.is_empty() => {
+                            options.scripts.push(rest.to_string());
+                            break;
+                        }
+                        'e' => match iter.next() {
+                            Some(script) => {
+                                options.scripts.push(script.clone());
+                                break;
+                            }
+                            None => {
+                                return Err(usage_error(
+                                    "option requires an argument -- 'e'".into(),
+                                ));
+                            }
+                        },
+                        other => return Err(usage_error(format!("invalid option -- '{}'", other))),
+                    }
+                }
+            }
+            _ => operands.push(arg.clone()),
+        }
+    }
+
+    let mut operands = operands.into_iter();
+    if options.scripts.is_empty() {
+        match operands.next() {
+            Some(script) => options.scripts.push(script),
+            None => return Err(CommandError::InvalidArguments(USAGE.trim_end().to_string())),
+        }
+    }
+    options.files = operands.collect();
+    Ok(options)
+}
+
+/// Reads a script, keeping the character position GNU sed puts in its errors
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    extended: bool,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> CommandError {
+        CommandError::InvalidArguments(format!(
+            "sed: -e expression #1, char {}: {}",
+            self.pos, message
+        ))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += c.is_some() as usize;
+        c
+    }
+
+    fn skip_blanks(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn parse(mut self) -> Result<Vec<Instruction>, CommandError> {
+        let mut instructions = Vec::new();
+        loop {
+            while matches!(self.peek(), Some(' ' | '\t' | '\n' | ';')) {
+                self.pos += 1;
+            }
+            if self.peek().is_none() {
+                return Ok(instructions);
+            }
+            let start = self.address()?;
+            let mut end = None;
+            self.skip_blanks();
+            if self.peek() == Some(',') {
+                self.pos += 1;
+                self.skip_blanks();
+                end = Some(
+                    self.address()?
+                        .ok_or_else(|| self.error("unexpected `,'"))?,
+                );
+            }
+            self.skip_blanks();
+            let negate = self.peek() == Some('!');
+            if negate {
+                self.pos += 1;
+                self.skip_blanks();
+            }
+            let action = self.action()?;
+            if end.is_some() && matches!(action, Action::Quit { .. }) {
+                return Err(self.error("command only uses one address"));
+            }
+            instructions.push(Instruction {
+                start,
+                end,
+                negate,
+                action,
+            });
+            self.skip_blanks();
+            match self.peek() {
+                None | Some(';' | '\n') => {}
+                Some('}') => return Err(self.error("unexpected `}'")),
+                Some(_) => {
+                    self.pos += 1;
+                    return Err(self.error("extra characters after command"));
+                }
+            }
+        }
+    }
+
+    fn address(&mut self) -> Result<Option<Address>, CommandError> {
+        match self.peek() {
+            Some(c) if c.is_ascii_digit() => {
+                let mut number = 0usize;
+                while let Some(digit) = self.peek().and_then(|c| c.to_digit(10)) {
+                    number = number.saturating_mul(10).saturating_add(digit as usize);
+                    self.pos += 1;
+                }
+                if number == 0 {
+                    return Err(self.error("invalid usage of line address 0"));
+                }
+                Ok(Some(Address::Line(number)))
+            }
+            Some('$') => {
+                self.pos += 1;
+                Ok(Some(Address::Last))
+            }
+            Some('/' | '\\') => {
+                if self.next() == Some('\\') && self.next().is_none() {
+                    return Err(self.error("unexpected end of file"));
+                }
+                let delimiter = self.chars[self.pos - 1];
+                let pattern = self
+                    .until(delimiter)
+                    .ok_or_else(|| self.error("unterminated address regex"))?;
+                let ignore_case = self.peek() == Some('I');
+                if ignore_case {
+                    self.pos += 1;
+                }
+                Ok(Some(Address::Match(self.regex(&pattern, ignore_case)?)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn action(&mut self) -> Result<Action, CommandError> {
+        let command = self.next().ok_or_else(|| self.error("missing command"))?;
+        Ok(match command {
+            'p' => Action::Print,
+            'd' => Action::Delete,
+            '=' => Action::LineNumber,
+            'q' => Action::Quit { print: true },
+            'Q' => Action::Quit { print: false },
+            'a' => Action::Append(self.text()),
+            'i' => Action::Insert(self.text()),
+            'c' => Action::Change(self.text()),
+            's' => self.substitute()?,
+            'y' => {
+                let delimiter = self
+                    .next()
+                    .ok_or_else(|| self.error("unterminated `y' command"))?;
+                let unterminated = |parser: &Parser| parser.error("unterminated `y' command");
+                let from = self.until(delimiter).ok_or_else(|| unterminated(self))?;
+                let to = self.until(delimiter).ok_or_else(|| unterminated(self))?;
+                let (from, to) = (unescape(&from), unescape(&to));
+                if from.chars().count() != to.chars().count() {
+                    return Err(self.error("strings for `y' command are different lengths"));
+                }
+                Action::Transliterate(from.chars().zip(to.chars()).collect())
+            }
+            other => {
+                log::debug!("sed command '{}' is not supported", other);
+                return Err(self.error(&format!("unknown command: `{}'", other)));
+            }
+        })
+    }
+
+    /// `s/regex/replacement/flags`
+    fn substitute(&mut self) -> Result<Action, CommandError> {
+        let unterminated = |parser: &Parser| parser.error("unterminated `s' command");
+        let delimiter = match self.next() {
+            Some('\n' | '\\') | None => return Err(unterminated(self)),
+            Some(delimiter) => delimiter,
+        };
+        let pattern = self.until(delimiter).ok_or_else(|| unterminated(self))?;
+        let replacement = self.until(delimiter).ok_or_else(|| unterminated(self))?;
+
+        let (mut global, mut print, mut ignore_case) = (false, false, false);
+        let mut occurrence: Option<usize> = None;
+        while let Some(flag) = self.peek() {
+            match flag {
+                'g' => global = true,
+                'p' => print = true,
+                'i' | 'I' => ignore_case = true,
+                digit if digit.is_ascii_digit() => {
+                    let digit = digit.to_digit(10).unwrap() as usize;
+                    occurrence = occurrence
+                        .unwrap_or(0)
+                        .checked_mul(10)
+                        .and_then(|n| n.checked_add(digit));
+                    if occurrence.is_none() {
+                        self.pos += 1;
+                        return Err(self.error("invalid number option to `s' command"));
+                    }
+                }
+                ';' | '\n' | '}' | ' ' | '\t' => break,
+                _ => {
+                    self.pos += 1;
+                    return Err(self.error("unknown option to `s'"));
+                }
+            }
+            self.pos += 1;
+        }
+        if occurrence == Some(0) {
+            return Err(self.error("number option to `s' command may not be zero"));
+        }
+        Ok(Action::Substitute {
+            regex: self.regex(&pattern, ignore_case)?,
+            replacement,
+            global,
+            occurrence: occurrence.unwrap_or(1),
+            print,
+        })
+    }
+
+    /// The text of `a`, `i` and `c`, up to the end of the line. GNU takes both
+    /// `a\` with the text on the next line and `a text`
+    fn text(&mut self) -> String {
+        self.skip_blanks();
+        if self.peek() == Some('\\') {
+            self.pos += 1;
+            if self.peek() == Some('\n') {
+                self.pos += 1;
+            }
+            self.skip_blanks();
+        }
+        let mut text = String::new();
+        while let Some(c) = self.next() {
+            match c {
+                '\n' => break,
+                '\\' => match self.next() {
+                    Some('t') => text.push('\t'),
+                    Some(c) => text.push(c),
+                    None => {}
+                },
+                c => text.push(c),
+            }
+        }
+        text
+    }
+
+    /// Everything up to the next unescaped `delimiter`, with `\delimiter` made
+    /// plain. None when the line or script ends first
+    fn until(&mut self, delimiter: char) -> Option<String> {
+        let mut out = String::new();
+        loop {
+            match self.next()? {
+                c if c == delimiter => return Some(out),
+                '\n' => return None,
+                '\\' => match self.next()? {
+                    c if c == delimiter => out.push(c),
+                    c => {
+                        out.push('\\');
+                        out.push(c);
+                    }
+                },
+                c => out.push(c),
+            }
+        }
+    }
+
+    fn regex(&self, pattern: &str, ignore_case: bool) -> Result<Regex, CommandError> {
+        RegexBuilder::new(&translate(pattern, self.extended))
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|err| {
+                log::debug!("sed regex {:?} did not compile: {}", pattern, err);
+                self.error("Invalid preceding regular expression")
+            })
+    }
+}
+
+/// A POSIX basic or extended regular expression in the syntax of the regex crate.
+/// BRE groups and repetition are written `\(`, `\{`, `\+` and so on, while the
+/// bare characters match themselves
+pub(crate) fn translate(pattern: &str, extended: bool) -> String {
+    let mut out = String::new();
+    let mut chars = pattern.chars().peekable();
+    let mut at_start = true;
+    while let Some(c) = chars.next() {
+        match c {
+            '[' => {
+                out.push('[');
+                if chars.peek() == Some(&'^') {
+                    out.push(chars.next().unwrap());
+                }
+                if chars.peek() == Some(&']') {
+                    chars.next();
+                    out.push_str("\\]");
+                }
+                while let Some(c) = chars.next() {
+                    match c {
+                        ']' => break,
+                        // Classes like [:digit:] are kept whole
+                        '[' if chars.peek() == Some(&':') => {
+                            out.push_str("[:");
+                            chars.next();
+                            for c in chars.by_ref() {
+                                out.push(c);
+                                if c == ']' {
+                                    break;
+                                }
+                            }
+                            continue;
+                        }
+                        '\\' | '[' | '&' | '~' => out.push('\\'),
+                        _ => {}
+                    }
+                    out.push(c);
+                }
+                out.push(']');
+            }
+            '\\' => match chars.next() {
+                Some(c @ ('(' | ')' | '{' | '}' | '+' | '?' | '|')) if !extended => out.push(c),
+                Some('<' | '>') => out.push_str("\\b"),
+                Some(c) if c.is_ascii_digit() => {
+                    // Back-references have no equivalent, the group is matched again
+                    log::debug!("sed back-reference \\{} is not supported", c);
+                    out.push_str(".*");
+                }
+                Some(c) => {
+                    out.push('\\');
+                    out.push(c);
+                }
+                None => out.push_str("\\\\"),
+            },
+            '(' | ')' | '{' | '}' | '+' | '?' | '|' if !extended => {
+                out.push('\\');
+                out.push(c);
+            }
+            '*' if at_start && !extended => out.push_str("\\*"),
+            c => out.push(c),
+        }
+        at_start = c == '^' && out.len() == 1;
+    }
+    out
+}
+
+/// `\n`, `\t` and `\\` in the strings of `y`
+fn unescape(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => out.push('\n'),
+            ('\\', Some('t')) => out.push('\t'),
+            ('\\', Some(other)) => out.push(other),
+            (c, _) => {
+                out.push(c);
+                continue;
+            }
+        }
+        chars.next();
+    }
+    out
+}
+
+/// The replacement for one match: `&` is the match, `\1` to `\9` its groups
+fn expand(replacement: &str, captures: &Captures) -> String {
+    let mut out = String::new();
+    let mut chars = replacement.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '&' => out.push_str(&captures[0]),
+            '\\' => match chars.next() {
+                Some(digit @ '0'..='9') => {
+                    let group = digit.to_digit(10).unwrap() as usize;
+                    out.push_str(captures.get(group).map_or("", |m| m.as_str()));
+                }
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some(other) => out.push(other),
+                None => {}
+            },
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// `line` with the selected matches replaced, None when nothing was
+fn substitute(
+    line: &str,
+    regex: &Regex,
+    replacement: &str,
+    global: bool,
+    occurrence: usize,
+) -> Option<String> {
+    let mut out = String::new();
+    let mut copied = 0;
+    let mut replaced = false;
+    for (index, captures) in regex.captures_iter(line).enumerate() {
+        let number = index + 1;
+        if number < occurrence {
+            continue;
+        }
+        let whole = captures.get(0).unwrap();
+        out.push_str(&line[copied..whole.start()]);
+        out.push_str(&expand(replacement, &captures));
+        copied = whole.end();
+        replaced = true;
+        if !global {
+            break;
+        }
+    }
+    if !replaced {
+        return None;
+    }
+    out.push_str(&line[copied..]);
+    Some(out)
+}
+
+/// Run the script over `input`, returning what sed prints
+fn run_script(instructions: &[Instruction], input: &str, quiet: bool) -> String {
+    let lines: Vec<&str> = input.lines().collect();
+    let mut in_range = vec![false; instructions.len()];
+    let mut out = String::new();
+    for (index, line) in lines.iter().enumerate() {
+        let number = index + 1;
+        let last = number == lines.len();
+        let mut pattern = line.to_string();
+        let mut appended = Vec::new();
+        let mut print = !quiet;
+        let mut quit = false;
+        for (instruction, in_range) in instructions.iter().zip(in_range.iter_mut()) {
+            if !instruction.selects(in_range, &pattern, number, last) {
+                continue;
+            }
+            match &instruction.action {
+                Action::Print => {
+                    out.push_str(&pattern);
+                    out.push('\n');
+                }
+                Action::Delete => {
+                    print = false;
+                    break;
+                }
+                Action::LineNumber => out.push_str(&format!("{}\n", number)),
+                Action::Append(text) => appended.push(text.as_str()),
+                Action::Insert(text) => {
+                    out.push_str(text);
+                    out.push('\n');
+                }
+                Action::Change(text) => {
+                    // A range is replaced by the text once, at its end
+                    if instruction.end.is_none() || instruction.negate || !*in_range {
+                        out.push_str(text);
+                        out.push('\n');
+                    }
+                    print = false;
+                    break;
+                }
+                Action::Substitute {
+                    regex,
+                    replacement,
+                    global,
+                    occurrence,
+                    print: print_substituted,
+                } => {
+                    if let Some(new) =
+                        substitute(&pattern, regex, replacement, *global, *occurrence)
+                    {
+                        pattern = new;
+                        if *print_substituted {
+                            out.push_str(&pattern);
+                            out.push('\n');
+                        }
+                    }
+                }
+                Action::Transliterate(map) => {
+                    pattern = pattern
+                        .chars()
+                        .map(|c| {
+                            map.iter()
+                                .find(|(from, _)| *from == c)
+                                .map_or(c, |(_, to)| *to)
+                        })
+                        .collect();
+                }
+                Action::Quit { print: print_line } => {
+                    print &= *print_line;
+                    quit = true;
+                    break;
+                }
+            }
+        }
+        if print {
+            out.push_str(&pattern);
+            out.push('\n');
+        }
+        for text in appended {
+            out.push_str(text);
+            out.push('\n');
+        }
+        if quit {
+            break;
+        }
+    }
+    out
+}
+
+/// GNU sed 4.8, scripts of `s`, `y`, `p`, `d`, `q`, `=`, `a`, `i` and `c` with
+/// line, `$` and regex addresses and ranges
+#[derive(Clone, Copy, Debug)]
+pub struct SedCommand;
+
+impl SedCommand {
+    /// Run sed over `piped` or the files named in `args`
+    pub(crate) async fn run(
+        &self,
+        args: &[String],
+        piped: &str,
+        context: &CommandContext,
+    ) -> CommandResult {
+        let options = parse_options(args)?;
+        let instructions = Parser {
+            chars: options.scripts.join("\n").chars().collect(),
+            pos: 0,
+            extended: options.extended,
+        }
+        .parse()?;
+
+        if options.in_place.is_some() {
+            return self.edit_in_place(&options, &instructions, context).await;
+        }
+
+        let mut input = String::new();
+        let mut errors = Vec::new();
+        if options.files.is_empty() {
+            input.push_str(piped);
+        }
+        let fs = context.filesystem.read().await;
+        for path in &options.files {
+            if path == "-" {
+                input.push_str(piped);
+                continue;
+            }
+            let absolute = absolute(path, context);
+            match fs.follow_symlink(&absolute).map(|entry| entry.file_content) {
+                Ok(Some(FileContent::RegularFile(bytes))) => {
+                    context.note_read(&fs, &absolute);
+                    input.push_str(&String::from_utf8_lossy(&bytes));
+                    if !input.is_empty() && !input.ends_with('\n') {
+                        input.push('\n');
+                    }
+                }
+                Ok(Some(FileContent::Directory(_))) => {
+                    errors.push(format!("sed: read error on {}: Is a directory", path))
+                }
+                _ => errors.push(format!(
+                    "sed: can't read {}: No such file or directory",
+                    path
+                )),
+            }
+        }
+        drop(fs);
+
+        let out = run_script(&instructions, &input, options.quiet);
+        if !errors.is_empty() && out.is_empty() {
+            return Err(CommandError::FilesystemError(errors.join("\r\n")));
+        }
+        Ok(out.replace('\n', "\r\n"))
+    }
+
+    /// `sed -i`: each file is run through the script on its own and replaced by
+    /// the result
+    async fn edit_in_place(
+        &self,
+        options: &Options,
+        instructions: &[Instruction],
+        context: &CommandContext,
+    ) -> CommandResult {
+        if options.files.is_empty() {
+            return Err(CommandError::InvalidArguments(
+                "sed: no input files".to_string(),
+            ));
+        }
+        let suffix = options.in_place.as_deref().unwrap_or_default();
+        let mut fs = context.filesystem.write().await;
+        for path in &options.files {
+            let absolute = absolute(path, context);
+            let content = match fs.follow_symlink(&absolute).map(|entry| entry.file_content) {
+                Ok(Some(FileContent::RegularFile(bytes))) => bytes,
+                Ok(Some(_)) => {
+                    return Err(CommandError::FilesystemError(format!(
+                        "sed: couldn't edit {}: not a regular file",
+                        path
+                    )));
+                }
+                _ => {
+                    return Err(CommandError::FilesystemError(format!(
+                        "sed: can't read {}: No such file or directory",
+                        path
+                    )));
+                }
+            };
+            if context.username != "root" && !writable_by_user(&absolute, context) {
+                return Err(CommandError::PermissionDenied(format!(
+                    "sed: couldn't open temporary file {}/sedAbC123: Permission denied",
+                    absolute.rsplit_once('/').map_or("", |(dir, _)| dir)
+                )));
+            }
+            context.note_read(&fs, &absolute);
+
+            let edited = run_script(
+                instructions,
+                &String::from_utf8_lossy(&content),
+                options.quiet,
+            );
+            if !suffix.is_empty() {
+                let backup = format!("{}{}", absolute, suffix);
+                let mode = fs
+                    .follow_symlink(&absolute)
+                    .map_or(0o644, |entry| entry.inode.i_mode);
+                let entry = match fs.get_file(&backup) {
+                    Ok(_) => fs.get_file_mut(&backup),
+                    Err(_) => fs.create_file(&backup),
+                };
+                if let Ok(entry) = entry {
+                    entry.inode.i_mode = mode;
+                    entry.inode.i_size_lo = content.len() as u32;
+                    entry.content = Some(FileContent::RegularFile(content.clone()));
+                }
+            }
+            if let Ok(entry) = fs
+                .resolve_symlinks(&absolute)
+                .and_then(|target| fs.get_file_mut(&target))
+            {
+                entry.inode.i_size_lo = edited.len() as u32;
+                entry.content = Some(FileContent::RegularFile(Arc::new(edited.into_bytes())));
+            }
+        }
+        Ok(String::new())
+    }
+}
+
+fn absolute(path: &str, context: &CommandContext) -> String {
+    match path.starts_with('/') {
+        true => path.to_string(),
+        false => format!("{}/{}", context.cwd.trim_end_matches('/'), path),
+    }
+}
+
+#[async_trait]
+impl Command for SedCommand {
+    fn name(&self) -> &'static str {
+        "sed"
+    }
+
+    fn help(&self) -> String {
+        USAGE.to_string()
+    }
+
+    fn version(&self) -> String {
+        "sed (GNU sed) 4.8\r\n\
+         Copyright (C) 2020 Free Software Foundation, Inc.\r\n\
+         License GPLv3+: GNU GPL version 3 or later <https://gnu.org/licenses/gpl.html>.\r\n\
+         This is free software: you are free to change and redistribute it.\r\n\
+         There is NO WARRANTY, to the extent permitted by law.\r\n"
+            .to_string()
+    }
+
+    fn modifies_filesystem(&self) -> bool {
+        true
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        match args.first().map(String::as_str) {
+            Some("--help") => Ok(self.help()),
+            Some("--version") => Ok(self.version()),
+            _ => self.run(args, "", context).await,
+        }
+    }
+}
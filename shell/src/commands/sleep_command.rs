@@ -0,0 +1,111 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use async_trait::async_trait;
+use std::time::Duration;
+
+/// Longest a sleep actually waits unless the server says otherwise
+pub const DEFAULT_MAX_SLEEP: Duration = Duration::from_secs(60);
+
+/// `5`, `0.5`, `2m`, `1h`, `1d` or `infinity` as sleep and timeout take them.
+/// Durations too long for a Duration are Duration::MAX
+pub(crate) fn parse_duration(value: &str) -> Option<Duration> {
+    let (number, scale) = match value.chars().last()? {
+        's' => (&value[..value.len() - 1], 1.0),
+        'm' => (&value[..value.len() - 1], 60.0),
+        'h' => (&value[..value.len() - 1], 3600.0),
+        'd' => (&value[..value.len() - 1], 86400.0),
+        _ => (value, 1.0),
+    };
+    let seconds: f64 = number.parse().ok()?;
+    if seconds.is_nan() || seconds < 0.0 {
+        return None;
+    }
+    Some(Duration::try_from_secs_f64(seconds * scale).unwrap_or(Duration::MAX))
+}
+
+fn usage_error(message: &str) -> CommandError {
+    CommandError::InvalidArguments(format!(
+        "sleep: {}\r\nTry 'sleep --help' for more information.",
+        message
+    ))
+}
+
+/// Sleep command. Really waits, an instant sleep in a paced bot script is a
+/// timing tell, but never longer than [`CommandContext::max_sleep`] so
+/// `sleep infinity` can't hold a session forever
+pub struct SleepCommand;
+
+#[async_trait]
+impl Command for SleepCommand {
+    fn name(&self) -> &'static str {
+        "sleep"
+    }
+
+    fn help(&self) -> String {
+        "Usage: sleep NUMBER[SUFFIX]...\r\n\
+        \x20 or:  sleep OPTION\r\n\
+        Pause for NUMBER seconds.  SUFFIX may be 's' for seconds (the default),\r\n\
+        'm' for minutes, 'h' for hours or 'd' for days.  NUMBER need not be an\r\n\
+        integer.  Given two or more arguments, pause for the amount of time\r\n\
+        specified by the sum of their values.\r\n\
+        \r\n\
+        \x20     --help     display this help and exit\r\n\
+        \x20     --version  output version information and exit\r\n"
+            .to_string()
+    }
+
+    fn version(&self) -> String {
+        "sleep (GNU coreutils) 8.32\r\n".to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        match args.first().map(String::as_str) {
+            Some("--help") => return Ok(self.help()),
+            Some("--version") => return Ok(self.version()),
+            _ => {}
+        }
+
+        let mut total = Duration::ZERO;
+        let mut operands = 0;
+        for arg in args {
+            match arg.as_str() {
+                "--" if operands == 0 => continue,
+                option if option.starts_with("--") => {
+                    return Err(usage_error(&format!("unrecognized option '{}'", option)));
+                }
+                option if option.starts_with('-') && option.len() > 1 => {
+                    let flag = option.chars().nth(1).unwrap_or('-');
+                    return Err(usage_error(&format!("invalid option -- '{}'", flag)));
+                }
+                operand => match parse_duration(operand) {
+                    Some(duration) => total = total.saturating_add(duration),
+                    None if context.os.is_ubuntu() => {
+                        return Err(usage_error(&format!("invalid time interval '{}'", operand)));
+                    }
+                    None => {
+                        return Err(CommandError::InvalidArguments(format!(
+                            "sleep: invalid number '{}'",
+                            operand
+                        )));
+                    }
+                },
+            }
+            operands += 1;
+        }
+        if operands == 0 {
+            return Err(usage_error("missing operand"));
+        }
+
+        let wait = total.min(context.max_sleep);
+        if wait < total {
+            log::debug!(
+                "[{}] sleep of {:?} cut to {:?}",
+                context.auth_id,
+                total,
+                wait
+            );
+        }
+        tokio::time::sleep(wait).await;
+        Ok(String::new())
+    }
+}
@@ -0,0 +1,87 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use async_trait::async_trait;
+
+/// ssh-keygen, only `-l` is implemented: fingerprints of the host keys match the
+/// ones the attacker's client was shown when connecting
+pub struct SshKeygenCommand;
+
+#[async_trait]
+impl Command for SshKeygenCommand {
+    fn name(&self) -> &'static str {
+        "ssh-keygen"
+    }
+
+    fn help(&self) -> String {
+        "usage: ssh-keygen [-q] [-b bits] [-C comment] [-f output_keyfile] [-m format]\r\n\
+        \x20                 [-N new_passphrase] [-t dsa | ecdsa | ecdsa-sk | ed25519 | ed25519-sk | rsa]\r\n\
+        \x20      ssh-keygen -p [-f keyfile] [-m format] [-N new_passphrase]\r\n\
+        \x20                  [-P old_passphrase]\r\n\
+        \x20      ssh-keygen -i [-f input_keyfile] [-m key_format]\r\n\
+        \x20      ssh-keygen -e [-f input_keyfile] [-m key_format]\r\n\
+        \x20      ssh-keygen -y [-f input_keyfile]\r\n\
+        \x20      ssh-keygen -c [-C comment] [-f keyfile] [-P passphrase]\r\n\
+        \x20      ssh-keygen -l [-v] [-E fingerprint_hash] [-f input_keyfile]\r\n\
+        \x20      ssh-keygen -B [-f input_keyfile]\r\n"
+            .to_string()
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        // Flags can be combined (-lf FILE) or separate (-l -f FILE)
+        let mut list = false;
+        let mut file = None;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let Some(flags) = arg.strip_prefix('-') else {
+                continue;
+            };
+            for (i, flag) in flags.char_indices() {
+                match flag {
+                    'l' => list = true,
+                    'f' => {
+                        let rest = &flags[i + 1..];
+                        file = if rest.is_empty() {
+                            args.next().cloned()
+                        } else {
+                            Some(rest.to_string())
+                        };
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if !list {
+            return Err(CommandError::InvalidArguments(
+                self.help().trim_end_matches("\r\n").to_string(),
+            ));
+        }
+
+        let file = file.unwrap_or_else(|| format!("/home/{}/.ssh/id_rsa", context.username));
+        if let Some(key) = context.host.host_key_for_path(&file) {
+            return Ok(format!(
+                "{} {} root@{} ({})\r\n",
+                key.bits, key.sha256, context.hostname, key.key_type
+            ));
+        }
+
+        let fs = context.filesystem.read().await;
+        let path = if file.starts_with('/') {
+            fs.resolve_absolute_path(&file)
+        } else {
+            fs.resolve_absolute_path(&format!("{}/{}", context.cwd, file))
+        };
+        if fs.get_file(&path).is_ok() {
+            Err(CommandError::ExecutionError(format!(
+                "{} is not a public key file.",
+                file
+            )))
+        } else {
+            Err(CommandError::ExecutionError(format!(
+                "{}: No such file or directory",
+                file
+            )))
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use super::busybox::write_file;
+use super::host_identity::HostIdentity;
+use crate::filesystem::fs2::FileSystem;
+
+/// What the honeypot's SSH server really does, for an /etc/ssh/sshd_config that
+/// agrees with it. Careful attackers compare the two
+#[derive(Clone, Debug)]
+pub struct SshdSettings {
+    pub hostname: String,
+    /// Whether logins succeed at all, root's included
+    pub permit_root_login: bool,
+    /// Failed attempts before the client is disconnected
+    pub max_auth_tries: u32,
+    /// Pre-authentication banner, written to /etc/issue.net
+    pub banner: Option<String>,
+    pub sftp: bool,
+}
+
+/// Ubuntu 20.04's stock sshd_config with the settings the server advertises
+/// uncommented, the way an admin who turned on root and password logins leaves it
+fn sshd_config(settings: &SshdSettings) -> String {
+    let permit_root_login = if settings.permit_root_login {
+        "PermitRootLogin yes"
+    } else {
+        "#PermitRootLogin prohibit-password"
+    };
+    let max_auth_tries = match settings.max_auth_tries {
+        6 => "#MaxAuthTries 6".to_string(),
+        tries => format!("MaxAuthTries {}", tries),
+    };
+    let banner = match settings.banner {
+        Some(_) => "Banner /etc/issue.net",
+        None => "#Banner none",
+    };
+    let subsystem = if settings.sftp { "" } else { "#" };
+    format!(
+        "#\t$OpenBSD: sshd_config,v 1.103 2018/04/09 20:41:22 tj Exp $
+
+# This is the sshd server system-wide configuration file.  See
+# sshd_config(5) for more information.
+
+# This sshd was compiled with PATH=/usr/bin:/bin:/usr/sbin:/sbin
+
+# The strategy used for options in the default sshd_config shipped with
+# OpenSSH is to specify options with their default value where
+# possible, but leave them commented.  Uncommented options override the
+# default value.
+
+Include /etc/ssh/sshd_config.d/*.conf
+
+#Port 22
+#AddressFamily any
+#ListenAddress 0.0.0.0
+#ListenAddress ::
+
+#HostKey /etc/ssh/ssh_host_rsa_key
+#HostKey /etc/ssh/ssh_host_ecdsa_key
+#HostKey /etc/ssh/ssh_host_ed25519_key
+
+# Ciphers and keying
+#RekeyLimit default none
+
+# Logging
+#SyslogFacility AUTH
+#LogLevel INFO
+
+# Authentication:
+
+#LoginGraceTime 2m
+{permit_root_login}
+#StrictModes yes
+{max_auth_tries}
+#MaxSessions 10
+
+#PubkeyAuthentication yes
+
+# Expect .ssh/authorized_keys2 to be disregarded by default in future.
+#AuthorizedKeysFile\t.ssh/authorized_keys .ssh/authorized_keys2
+
+#AuthorizedPrincipalsFile none
+
+#AuthorizedKeysCommand none
+#AuthorizedKeysCommandUser nobody
+
+# For this to work you will also need host keys in /etc/ssh/ssh_known_hosts
+#HostbasedAuthentication no
+# Change to yes if you don't trust ~/.ssh/known_hosts for
+# HostbasedAuthentication
+#IgnoreUserKnownHosts no
+# Don't read the user's ~/.rhosts and ~/.shosts files
+#IgnoreRhosts yes
+
+# To disable tunneled clear text passwords, change to no here!
+PasswordAuthentication yes
+#PermitEmptyPasswords no
+
+# Change to yes to enable challenge-response passwords (beware issues with
+# some PAM modules and threads)
+ChallengeResponseAuthentication yes
+
+# Kerberos options
+#KerberosAuthentication no
+#KerberosOrLocalPasswd yes
+#KerberosTicketCleanup yes
+#KerberosGetAFSToken no
+
+# GSSAPI options
+#GSSAPIAuthentication no
+#GSSAPICleanupCredentials yes
+#GSSAPIStrictAcceptorCheck yes
+#GSSAPIKeyExchange no
+
+# Set this to 'yes' to enable PAM authentication, account processing,
+# and session processing. If this is enabled, PAM authentication will
+# be allowed through the ChallengeResponseAuthentication and
+# PasswordAuthentication.  Depending on your PAM configuration,
+# PAM authentication via ChallengeResponseAuthentication may bypass
+# the setting of \"PermitRootLogin without-password\".
+# If you just want the PAM account and session checks to run without
+# PAM authentication, then enable this but set PasswordAuthentication
+# and ChallengeResponseAuthentication to 'no'.
+UsePAM yes
+
+#AllowAgentForwarding yes
+#AllowTcpForwarding yes
+#GatewayPorts no
+X11Forwarding yes
+#X11DisplayOffset 10
+#X11UseLocalhost yes
+#PermitTTY yes
+PrintMotd no
+#PrintLastLog yes
+#TCPKeepAlive yes
+#PermitUserEnvironment no
+#Compression delayed
+#ClientAliveInterval 0
+#ClientAliveCountMax 3
+#UseDNS no
+#PidFile /var/run/sshd.pid
+#MaxStartups 10:30:100
+#PermitTunnel no
+#ChrootDirectory none
+#VersionAddendum none
+
+# no default banner path
+{banner}
+
+# Allow client to pass locale environment variables
+AcceptEnv LANG LC_*
+
+# override default of no subsystems
+{subsystem}Subsystem\tsftp\t/usr/lib/openssh/sftp-server
+
+# Example of overriding settings on a per-user basis
+#Match User anoncvs
+#\tX11Forwarding no
+#\tAllowTcpForwarding no
+#\tPermitTTY no
+#\tForceCommand cvs server
+"
+    )
+}
+
+/// Write /etc/ssh/sshd_config matching `settings` and the public halves of the
+/// host keys the server presents, overriding whatever the base image shipped.
+/// The private keys are left out, the real ones must not leak into the fake shell
+pub fn write_sshd_files(
+    fs: &mut FileSystem,
+    host: &HostIdentity,
+    settings: &SshdSettings,
+) -> std::io::Result<()> {
+    for dir in ["/etc", "/etc/ssh", "/etc/ssh/sshd_config.d"] {
+        if fs.get_file(dir).is_err() {
+            fs.create_directory(dir)?.inode.i_mode = 0o755;
+        }
+    }
+
+    write_file(
+        fs,
+        "/etc/ssh/sshd_config",
+        sshd_config(settings).into_bytes(),
+        0o644,
+    )?;
+    if let Some(banner) = &settings.banner {
+        let mut banner = banner.clone();
+        if !banner.ends_with('\n') {
+            banner.push('\n');
+        }
+        write_file(fs, "/etc/issue.net", banner.into_bytes(), 0o644)?;
+    }
+    for key in &host.host_keys {
+        let path = format!("/etc/ssh/ssh_host_{}_key.pub", key.file_name);
+        let line = format!("{} root@{}\n", key.public_key, settings.hostname);
+        write_file(fs, &path, line.into_bytes(), 0o644)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> SshdSettings {
+        SshdSettings {
+            hostname: "server01".to_string(),
+            permit_root_login: true,
+            max_auth_tries: 6,
+            banner: None,
+            sftp: true,
+        }
+    }
+
+    #[test]
+    fn config_agrees_with_the_server() {
+        let config = sshd_config(&settings());
+        assert!(config.contains("\nPermitRootLogin yes\n"));
+        assert!(config.contains("\n#MaxAuthTries 6\n"));
+        assert!(config.contains("\n#Banner none\n"));
+        assert!(config.contains("\nSubsystem\tsftp\t/usr/lib/openssh/sftp-server\n"));
+
+        let config = sshd_config(&SshdSettings {
+            permit_root_login: false,
+            max_auth_tries: 3,
+            banner: Some("Authorized use only".to_string()),
+            sftp: false,
+            ..settings()
+        });
+        assert!(config.contains("\n#PermitRootLogin prohibit-password\n"));
+        assert!(config.contains("\nMaxAuthTries 3\n"));
+        assert!(config.contains("\nBanner /etc/issue.net\n"));
+        assert!(config.contains("\n#Subsystem\tsftp"));
+    }
+}
@@ -0,0 +1,163 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use super::sleep_command::parse_duration;
+use async_trait::async_trait;
+use std::time::Duration;
+
+/// What `timeout` was asked to run
+#[derive(Debug, PartialEq)]
+pub(crate) struct TimeoutOptions {
+    /// Zero runs the command without a deadline
+    pub duration: Duration,
+    /// Signal name without SIG, shown by --verbose
+    pub signal: String,
+    pub verbose: bool,
+    /// Index of the command in the arguments
+    pub command: usize,
+}
+
+fn usage_error(message: &str) -> CommandError {
+    CommandError::InvalidArguments(format!(
+        "timeout: {}\r\nTry 'timeout --help' for more information.",
+        message
+    ))
+}
+
+/// Name of the signal given to -s, numbers included
+fn signal_name(signal: &str) -> Option<String> {
+    let name = match signal {
+        "1" => "HUP",
+        "2" => "INT",
+        "9" => "KILL",
+        "15" => "TERM",
+        name => name.trim_start_matches("SIG"),
+    };
+    let name = name.to_uppercase();
+    matches!(
+        name.as_str(),
+        "HUP" | "INT" | "QUIT" | "KILL" | "USR1" | "USR2" | "ALRM" | "TERM" | "CONT" | "STOP"
+    )
+    .then_some(name)
+}
+
+/// Parse timeout's arguments. The dispatcher runs the command they name with
+/// the deadline, [`TimeoutCommand`] reports the error when there is none
+pub(crate) fn timeout_options(args: &[String]) -> Result<TimeoutOptions, CommandError> {
+    let mut signal = "TERM".to_string();
+    let mut verbose = false;
+    let mut index = 0;
+    while let Some(arg) = args.get(index) {
+        let (option, inline) = match arg.split_once('=') {
+            Some((option, value)) if option.starts_with("--") => (option, Some(value)),
+            _ => (arg.as_str(), None),
+        };
+        match option {
+            "--" => {
+                index += 1;
+                break;
+            }
+            "-s" | "--signal" | "-k" | "--kill-after" => {
+                let value = match inline {
+                    Some(value) => value,
+                    None => {
+                        index += 1;
+                        args.get(index).map(String::as_str).ok_or_else(|| {
+                            usage_error(&format!(
+                                "option requires an argument -- '{}'",
+                                option.trim_start_matches('-').chars().next().unwrap_or('s')
+                            ))
+                        })?
+                    }
+                };
+                if matches!(option, "-s" | "--signal") {
+                    signal = signal_name(value)
+                        .ok_or_else(|| usage_error(&format!("{}: invalid signal", value)))?;
+                } else if parse_duration(value).is_none() {
+                    return Err(usage_error(&format!("invalid time interval '{}'", value)));
+                }
+            }
+            "-v" | "--verbose" => verbose = true,
+            "--foreground" | "--preserve-status" => {}
+            long if long.starts_with("--") => {
+                return Err(usage_error(&format!("unrecognized option '{}'", arg)));
+            }
+            short if short.starts_with('-') && short.len() > 1 => {
+                let flag = short.chars().nth(1).unwrap_or('-');
+                return Err(usage_error(&format!("invalid option -- '{}'", flag)));
+            }
+            _ => break,
+        }
+        index += 1;
+    }
+
+    let duration = args
+        .get(index)
+        .ok_or_else(|| usage_error("missing operand"))?;
+    let duration = parse_duration(duration)
+        .ok_or_else(|| usage_error(&format!("invalid time interval '{}'", duration)))?;
+    if index + 1 >= args.len() {
+        return Err(usage_error(&format!(
+            "missing operand after '{}'",
+            args[index]
+        )));
+    }
+    Ok(TimeoutOptions {
+        duration,
+        signal,
+        verbose,
+        command: index + 1,
+    })
+}
+
+/// Timeout command. The dispatcher runs the wrapped command and drops it when
+/// the deadline passes (see [`timeout_options`]), this only reports bad arguments
+pub struct TimeoutCommand;
+
+#[async_trait]
+impl Command for TimeoutCommand {
+    fn name(&self) -> &'static str {
+        "timeout"
+    }
+
+    fn help(&self) -> String {
+        "Usage: timeout [OPTION] DURATION COMMAND [ARG]...\r\n\
+        \x20 or:  timeout [OPTION]\r\n\
+        Start COMMAND, and kill it if still running after DURATION.\r\n\
+        \r\n\
+        Mandatory arguments to long options are mandatory for short options too.\r\n\
+        \x20     --preserve-status\r\n\
+        \x20                exit with the same status as COMMAND, even when the\r\n\
+        \x20                  command times out\r\n\
+        \x20     --foreground\r\n\
+        \x20                when not running timeout directly from a shell prompt,\r\n\
+        \x20                  allow COMMAND to read from the TTY and get TTY signals;\r\n\
+        \x20                  in this mode, children of COMMAND will not be timed out\r\n\
+        \x20 -k, --kill-after=DURATION\r\n\
+        \x20                also send a KILL signal if COMMAND is still running\r\n\
+        \x20                  this long after the initial signal was sent\r\n\
+        \x20 -s, --signal=SIGNAL\r\n\
+        \x20                specify the signal to be sent on timeout;\r\n\
+        \x20                  SIGNAL may be a name like 'HUP' or a number;\r\n\
+        \x20                  see 'kill -l' for a list of signals\r\n\
+        \x20 -v, --verbose  diagnose to stderr any signal sent upon timeout\r\n\
+        \x20     --help     display this help and exit\r\n\
+        \x20     --version  output version information and exit\r\n\
+        \r\n\
+        DURATION is a floating point number with an optional suffix:\r\n\
+        's' for seconds (the default), 'm' for minutes, 'h' for hours or 'd' for days.\r\n\
+        A duration of 0 disables the associated timeout.\r\n"
+            .to_string()
+    }
+
+    fn version(&self) -> String {
+        "timeout (GNU coreutils) 8.32\r\n".to_string()
+    }
+
+    async fn execute(&self, args: &[String], _context: &mut CommandContext) -> CommandResult {
+        match args.first().map(String::as_str) {
+            Some("--help") => Ok(self.help()),
+            Some("--version") => Ok(self.version()),
+            _ => timeout_options(args).map(|_| String::new()),
+        }
+    }
+}
@@ -0,0 +1,554 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::{CommandContext, ShellEvent};
+use super::firewall::{Ufw, UfwRule};
+use async_trait::async_trait;
+use chrono::Local;
+
+const PROCEED: &str = "Proceed with operation (y|n)? ";
+
+fn invalid_syntax() -> CommandError {
+    CommandError::InvalidArguments("ERROR: Invalid syntax".to_string())
+}
+
+/// Ports ufw finds in /etc/services for `ufw allow ssh` and the like
+fn service_port(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "ftp" => "21/tcp",
+        "ssh" => "22/tcp",
+        "telnet" => "23/tcp",
+        "smtp" => "25/tcp",
+        "domain" => "53",
+        "http" => "80/tcp",
+        "https" => "443/tcp",
+        "mysql" => "3306/tcp",
+        "postgresql" => "5432/tcp",
+        _ => return None,
+    })
+}
+
+/// `22`, `22/tcp` or `6000:6007/udp`; ranges need a protocol
+fn port(spec: &str) -> Option<String> {
+    let (ports, protocol) = match spec.split_once('/') {
+        Some((ports, protocol)) if matches!(protocol, "tcp" | "udp") => (ports, Some(protocol)),
+        Some(_) => return None,
+        None => (spec, None),
+    };
+    let valid = |port: &str| port.parse::<u16>().is_ok_and(|port| port > 0);
+    let ports_valid = match ports.split_once(':') {
+        Some((first, last)) => protocol.is_some() && valid(first) && valid(last),
+        None => valid(ports),
+    };
+    ports_valid.then(|| spec.to_string())
+}
+
+fn is_address(value: &str) -> bool {
+    let address = value.split('/').next().unwrap_or_default();
+    address.parse::<std::net::IpAddr>().is_ok()
+}
+
+/// A rule as given to ufw, with the IPv6 twin ufw adds when no address is given
+struct Parsed {
+    rule: UfwRule,
+    twin: bool,
+}
+
+/// `allow 22/tcp`, `deny out 25`, `allow from 203.0.113.7 to any port 22 proto tcp`
+fn parse_rule(action: &str, args: &[String]) -> Result<Parsed, CommandError> {
+    let action = match action {
+        "allow" | "deny" | "reject" | "limit" => action.to_uppercase(),
+        _ => return Err(invalid_syntax()),
+    };
+    let mut args = args.iter().map(String::as_str).peekable();
+    let direction = match args.peek() {
+        Some(&"out") => "OUT",
+        _ => "IN",
+    };
+    args.next_if(|arg| matches!(*arg, "in" | "out"));
+    if args.next_if_eq(&"on").is_some() {
+        args.next().ok_or_else(invalid_syntax)?;
+    }
+    let rest: Vec<&str> = args.collect();
+
+    let rule = |to: String, from: String, v6: bool| UfwRule {
+        action: action.clone(),
+        direction: direction.to_string(),
+        to,
+        from,
+        v6,
+    };
+    match rest.as_slice() {
+        [] => Err(invalid_syntax()),
+        [spec] => {
+            let to = port(spec)
+                .or_else(|| service_port(spec).map(str::to_string))
+                .or_else(|| (*spec == "OpenSSH").then(|| spec.to_string()))
+                .ok_or_else(|| {
+                    CommandError::InvalidArguments(format!(
+                        "ERROR: Could not find a profile matching '{}'",
+                        spec
+                    ))
+                })?;
+            Ok(Parsed {
+                rule: rule(to, "Anywhere".to_string(), false),
+                twin: true,
+            })
+        }
+        extended => {
+            let (mut from, mut to, mut port_number, mut protocol) = ("any", "any", None, None);
+            let mut words = extended.iter();
+            while let Some(word) = words.next() {
+                let value = *words.next().ok_or_else(invalid_syntax)?;
+                match *word {
+                    "from" => from = value,
+                    "to" => to = value,
+                    "port" => port_number = Some(value),
+                    "proto" if matches!(value, "tcp" | "udp" | "any") => protocol = Some(value),
+                    _ => return Err(invalid_syntax()),
+                }
+            }
+            for address in [from, to] {
+                if address != "any" && !is_address(address) {
+                    return Err(CommandError::InvalidArguments(
+                        "ERROR: Bad source address".to_string(),
+                    ));
+                }
+            }
+            let port_column = match (port_number, protocol) {
+                (Some(number), Some(protocol)) if protocol != "any" => {
+                    Some(format!("{}/{}", number, protocol))
+                }
+                (Some(number), _) => Some(number.to_string()),
+                (None, _) => None,
+            };
+            if let Some(column) = &port_column {
+                port(column)
+                    .ok_or_else(|| CommandError::InvalidArguments("ERROR: Bad port".to_string()))?;
+            }
+            let to_column = match (to, port_column) {
+                ("any", None) => "Anywhere".to_string(),
+                ("any", Some(column)) => column,
+                (address, None) => address.to_string(),
+                (address, Some(column)) => format!("{} {}", address, column),
+            };
+            let from_column = match from {
+                "any" => "Anywhere".to_string(),
+                address => address.to_string(),
+            };
+            let v6 = from.contains(':') || to.contains(':');
+            Ok(Parsed {
+                rule: rule(to_column, from_column, v6),
+                twin: from == "any" && to == "any",
+            })
+        }
+    }
+}
+
+/// Indices of `ufw.rules` in the order ufw status numbers them, IPv4 rules first
+fn status_order(ufw: &Ufw) -> Vec<usize> {
+    let v4 = (0..ufw.rules.len()).filter(|&index| !ufw.rules[index].v6);
+    let v6 = (0..ufw.rules.len()).filter(|&index| ufw.rules[index].v6);
+    v4.chain(v6).collect()
+}
+
+/// To, Action and From columns of ufw status; `detailed` shows the direction of
+/// incoming rules too
+fn columns(rule: &UfwRule, detailed: bool) -> (String, String, String) {
+    let v6 = |column: &str| {
+        if rule.v6 && !column.contains(':') {
+            format!("{} (v6)", column)
+        } else {
+            column.to_string()
+        }
+    };
+    let action = if rule.direction == "IN" && !detailed {
+        rule.action.clone()
+    } else {
+        format!("{} {}", rule.action, rule.direction)
+    };
+    (v6(&rule.to), action, v6(&rule.from))
+}
+
+/// The rule as `ufw delete NUM` shows it before deleting
+fn specification(rule: &UfwRule) -> String {
+    let mut spec = rule.action.to_lowercase();
+    if rule.direction == "OUT" {
+        spec.push_str(" out");
+    }
+    if rule.from == "Anywhere" && !rule.to.contains(' ') && !is_address(&rule.to) {
+        spec.push(' ');
+        spec.push_str(&rule.to);
+        return spec;
+    }
+    let from = if rule.from == "Anywhere" {
+        "any"
+    } else {
+        &rule.from
+    };
+    let (address, port) = match rule.to.split_once(' ') {
+        Some((address, port)) => (address, Some(port)),
+        None if rule.to == "Anywhere" => ("any", None),
+        None if is_address(&rule.to) => (rule.to.as_str(), None),
+        None => ("any", Some(rule.to.as_str())),
+    };
+    spec.push_str(&format!(" from {} to {}", from, address));
+    if let Some(port) = port {
+        match port.split_once('/') {
+            Some((number, protocol)) => {
+                spec.push_str(&format!(" port {} proto {}", number, protocol))
+            }
+            None => spec.push_str(&format!(" port {}", port)),
+        }
+    }
+    spec
+}
+
+fn status(ufw: &Ufw, verbose: bool, numbered: bool) -> String {
+    if !ufw.active {
+        return "Status: inactive\r\n".to_string();
+    }
+    let mut output = "Status: active\r\n".to_string();
+    if verbose {
+        output.push_str(&format!(
+            "Logging: {}\r\nDefault: {} (incoming), {} (outgoing), {} (routed)\r\nNew profiles: skip\r\n",
+            ufw.logging, ufw.defaults[0], ufw.defaults[1], ufw.defaults[2]
+        ));
+    }
+    if ufw.rules.is_empty() {
+        return output;
+    }
+    let indent = if numbered { "     " } else { "" };
+    output.push_str("\r\n");
+    output.push_str(&format!(
+        "{}{:<27}{:<12}{}\r\n",
+        indent, "To", "Action", "From"
+    ));
+    output.push_str(&format!(
+        "{}{:<27}{:<12}{}\r\n",
+        indent, "--", "------", "----"
+    ));
+    for (number, index) in status_order(ufw).into_iter().enumerate() {
+        let (to, action, from) = columns(&ufw.rules[index], verbose || numbered);
+        if numbered {
+            output.push_str(&format!("[{:>2}] ", number + 1));
+        }
+        output.push_str(&format!("{:<27}{:<12}{}\r\n", to, action, from));
+    }
+    output.push_str("\r\n");
+    output
+}
+
+/// "Rules updated", "Rule added (v6)" and the like, one line for each of the
+/// rule and its twin
+fn updated(message: &str, rule: &UfwRule, twin: bool) -> String {
+    let mut output = String::new();
+    if twin || !rule.v6 {
+        output.push_str(message);
+        output.push_str("\r\n");
+    }
+    if twin || rule.v6 {
+        output.push_str(message);
+        output.push_str(" (v6)\r\n");
+    }
+    output
+}
+
+/// Add `parsed` at `position` in status order, or at the end of its address family
+fn add(ufw: &mut Ufw, parsed: Parsed, position: Option<usize>) -> String {
+    let Parsed { rule, twin } = parsed;
+    let message = if ufw.rules.contains(&rule) {
+        "Skipping adding existing rule"
+    } else if twin {
+        "Rules updated"
+    } else {
+        "Rule added"
+    };
+    let mut rules = vec![rule.clone()];
+    if twin {
+        rules.push(UfwRule {
+            v6: true,
+            ..rule.clone()
+        });
+    }
+    for rule in rules {
+        if ufw.rules.contains(&rule) {
+            continue;
+        }
+        match position {
+            Some(position) if position <= ufw.rules.len() => ufw.rules.insert(position, rule),
+            _ => ufw.rules.push(rule),
+        }
+    }
+    updated(message, &rule, twin)
+}
+
+/// ufw, Ubuntu's iptables frontend. Installed but inactive like on a fresh
+/// server; its rules live in the session's Firewall and every change is
+/// reported as a ShellEvent::FirewallChange
+pub struct UfwCommand;
+
+impl UfwCommand {
+    fn run(&self, args: &[String], ufw: &mut Ufw, force: bool) -> CommandResult {
+        let words: Vec<&str> = args.iter().map(String::as_str).collect();
+        match words.as_slice() {
+            [] => Err(CommandError::InvalidArguments(
+                "ERROR: not enough args".to_string(),
+            )),
+            ["version"] => Ok(self.version()),
+            ["help"] => Ok(self.help()),
+            ["status"] => Ok(status(ufw, false, false)),
+            ["status", "verbose"] => Ok(status(ufw, true, false)),
+            ["status", "numbered"] => Ok(status(ufw, false, true)),
+            ["show", "added"] => {
+                let mut output =
+                    "Added user rules (see 'ufw status' for running firewall):\r\n".to_string();
+                if ufw.rules.is_empty() {
+                    output.push_str("(None)\r\n");
+                }
+                for index in status_order(ufw) {
+                    let rule = &ufw.rules[index];
+                    if !rule.v6
+                        || !ufw.rules.contains(&UfwRule {
+                            v6: false,
+                            ..rule.clone()
+                        })
+                    {
+                        output.push_str(&format!("ufw {}\r\n", specification(rule)));
+                    }
+                }
+                Ok(output)
+            }
+            ["enable"] => {
+                ufw.active = true;
+                Ok(format!(
+                    "{}Firewall is active and enabled on system startup\r\n",
+                    if force {
+                        String::new()
+                    } else {
+                        format!("Command may disrupt existing ssh connections. {}", PROCEED)
+                    }
+                ))
+            }
+            ["disable"] => {
+                ufw.active = false;
+                Ok("Firewall stopped and disabled on system startup\r\n".to_string())
+            }
+            ["reload"] => Ok(if ufw.active {
+                "Firewall reloaded\r\n".to_string()
+            } else {
+                "Firewall not enabled (skipping reload)\r\n".to_string()
+            }),
+            ["reset"] => {
+                let mut output = "Resetting all rules to installed defaults. ".to_string();
+                if !force {
+                    output.push_str(PROCEED);
+                }
+                let stamp = Local::now().format("%Y%m%d_%H%M%S");
+                for file in [
+                    "user.rules",
+                    "before.rules",
+                    "after.rules",
+                    "user6.rules",
+                    "before6.rules",
+                    "after6.rules",
+                ] {
+                    output.push_str(&format!(
+                        "Backing up '{}' to '/etc/ufw/{}.{}'\r\n",
+                        file, file, stamp
+                    ));
+                }
+                *ufw = Ufw::default();
+                Ok(output)
+            }
+            ["logging", level] => {
+                ufw.logging = match *level {
+                    "on" => "on (low)".to_string(),
+                    "off" => "off".to_string(),
+                    "low" | "medium" | "high" | "full" => format!("on ({})", level),
+                    _ => return Err(invalid_syntax()),
+                };
+                Ok(if *level == "off" {
+                    "Logging disabled\r\n".to_string()
+                } else {
+                    "Logging enabled\r\n".to_string()
+                })
+            }
+            ["default", policy, direction @ ..] => {
+                if !matches!(*policy, "allow" | "deny" | "reject") {
+                    return Err(invalid_syntax());
+                }
+                let (index, name) = match direction {
+                    [] | ["incoming"] => (0, "incoming"),
+                    ["outgoing"] => (1, "outgoing"),
+                    ["routed"] => (2, "routed"),
+                    _ => return Err(invalid_syntax()),
+                };
+                ufw.defaults[index] = policy.to_string();
+                Ok(format!(
+                    "Default {} policy changed to '{}'\r\n(be sure to update your rules accordingly)\r\n",
+                    name, policy
+                ))
+            }
+            ["app", "list"] => Ok("Available applications:\r\n  OpenSSH\r\n".to_string()),
+            ["app", "info", "OpenSSH"] => Ok("Profile: OpenSSH\r\n\
+                Title: Secure shell server, an rshd replacement\r\n\
+                Description: OpenSSH is a free implementation of the Secure Shell protocol.\r\n\
+                \r\n\
+                Port:\r\n\
+                \x20 22/tcp\r\n"
+                .to_string()),
+            ["app", "info", name] => Err(CommandError::InvalidArguments(format!(
+                "ERROR: Could not find profile '{}'",
+                name
+            ))),
+            ["delete", number] if number.parse::<usize>().is_ok() => {
+                let order = status_order(ufw);
+                let index = number
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&number| number > 0)
+                    .and_then(|number| order.get(number - 1).copied())
+                    .ok_or_else(|| {
+                        CommandError::InvalidArguments(format!(
+                            "ERROR: Could not find rule '{}'",
+                            number
+                        ))
+                    })?;
+                let rule = ufw.rules.remove(index);
+                let mut output = String::new();
+                if !force {
+                    output.push_str(&format!(
+                        "Deleting:\r\n {}\r\n{}",
+                        specification(&rule),
+                        PROCEED
+                    ));
+                }
+                output.push_str(&updated("Rule deleted", &rule, false));
+                Ok(output)
+            }
+            ["delete", action, ..] => {
+                let Parsed { rule, twin } = parse_rule(action, &args[2..])?;
+                let mut rules = vec![rule.clone()];
+                if twin {
+                    rules.push(UfwRule {
+                        v6: true,
+                        ..rule.clone()
+                    });
+                }
+                let before = ufw.rules.len();
+                ufw.rules.retain(|existing| !rules.contains(existing));
+                Ok(if ufw.rules.len() < before {
+                    updated("Rule deleted", &rule, twin)
+                } else {
+                    updated("Could not delete non-existent rule", &rule, twin)
+                })
+            }
+            ["insert", number, action, ..] => {
+                let position = number
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&number| number > 0 && number <= ufw.rules.len())
+                    .ok_or_else(|| {
+                        CommandError::InvalidArguments(format!(
+                            "ERROR: Invalid position '{}'",
+                            number
+                        ))
+                    })?;
+                let parsed = parse_rule(action, &args[3..])?;
+                Ok(add(
+                    ufw,
+                    parsed,
+                    status_order(ufw).get(position - 1).copied(),
+                ))
+            }
+            [action, ..] => {
+                let parsed = parse_rule(action, &args[1..])?;
+                Ok(add(ufw, parsed, None))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Command for UfwCommand {
+    fn name(&self) -> &'static str {
+        "ufw"
+    }
+
+    fn help(&self) -> String {
+        "\r\n\
+        Usage: ufw COMMAND\r\n\
+        \r\n\
+        Commands:\r\n\
+        \x20enable                          enables the firewall\r\n\
+        \x20disable                         disables the firewall\r\n\
+        \x20default ARG                     set default policy\r\n\
+        \x20logging LEVEL                   set logging to LEVEL\r\n\
+        \x20allow ARGS                      add allow rule\r\n\
+        \x20deny ARGS                       add deny rule\r\n\
+        \x20reject ARGS                     add reject rule\r\n\
+        \x20limit ARGS                      add limit rule\r\n\
+        \x20delete RULE|NUM                 delete RULE\r\n\
+        \x20insert NUM RULE                 insert RULE at NUM\r\n\
+        \x20route RULE                      add route RULE\r\n\
+        \x20reload                          reload firewall\r\n\
+        \x20reset                           reset firewall\r\n\
+        \x20status                          show firewall status\r\n\
+        \x20status numbered                 show firewall status as numbered list of RULES\r\n\
+        \x20status verbose                  show verbose firewall status\r\n\
+        \x20show ARG                        show firewall report\r\n\
+        \x20version                         display version information\r\n\
+        \r\n\
+        Application profile commands:\r\n\
+        \x20app list                        list application profiles\r\n\
+        \x20app info PROFILE                show information on PROFILE\r\n\
+        \x20app update PROFILE              update PROFILE\r\n\
+        \x20app default ARG                 set default application policy\r\n\
+        \r\n"
+            .to_string()
+    }
+
+    fn version(&self) -> String {
+        "ufw 0.36\r\nCopyright 2008-2015 Canonical Ltd.\r\n".to_string()
+    }
+
+    fn requires_privileges(&self) -> bool {
+        true
+    }
+
+    async fn execute(&self, args: &[String], context: &mut CommandContext) -> CommandResult {
+        if args.iter().any(|a| a == "--help" || a == "-h") {
+            return Ok(self.help());
+        }
+        if args.iter().any(|a| a == "--version") {
+            return Ok(self.version());
+        }
+        let force = args.iter().any(|a| a == "--force");
+        let args: Vec<String> = args
+            .iter()
+            .filter(|a| !matches!(a.as_str(), "--force" | "--dry-run"))
+            .cloned()
+            .collect();
+        let root = context.username == "root";
+        let modifies = !matches!(
+            args.first().map(String::as_str),
+            None | Some("status" | "show" | "version" | "help" | "app" | "reload")
+        );
+        if modifies {
+            context.push_event(ShellEvent::FirewallChange {
+                tool: "ufw",
+                command: std::iter::once("ufw")
+                    .chain(args.iter().map(String::as_str))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                permitted: root,
+            });
+        }
+        if !root {
+            return Err(CommandError::PermissionDenied(
+                "ERROR: You need to be root to run this script".to_string(),
+            ));
+        }
+        let mut firewall = context.firewall.write().await;
+        self.run(&args, &mut firewall.ufw, force)
+    }
+}
@@ -50,14 +50,15 @@ impl Command for UnameCommand {
 
         let hostname = &context.hostname;
 
-        // Default values for system information
-        let kernel_name = "Linux";
-        let kernel_release = "5.4.0-109-generic";
-        let kernel_version = "#123-Ubuntu SMP Fri Apr 8 09:10:54 UTC 2022";
-        let machine = "x86_64";
-        let processor = "x86_64";
-        let hardware_platform = "x86_64";
-        let operating_system = "GNU/Linux";
+        // System information from the shared OS identity
+        let os = &context.os;
+        let kernel_name = os.kernel_name.as_str();
+        let kernel_release = os.kernel_release.as_str();
+        let kernel_version = os.kernel_version.as_str();
+        let machine = os.machine.as_str();
+        let processor = os.machine.as_str();
+        let hardware_platform = os.machine.as_str();
+        let operating_system = os.operating_system.as_str();
 
         let mut output_parts = Vec::new();
 
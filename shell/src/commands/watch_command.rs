@@ -0,0 +1,191 @@
+use super::command_trait::{Command, CommandError, CommandResult};
+use super::context::CommandContext;
+use async_trait::async_trait;
+
+/// What `watch` was asked to run
+#[derive(Debug, PartialEq)]
+pub(crate) struct WatchOptions {
+    pub interval: f64,
+    /// Show the `Every Ns: ...` header, off with `-t`
+    pub title: bool,
+    pub command: String,
+}
+
+/// Watch command. The dispatcher runs the watched command (see [`watch_options`]),
+/// but only once: there is no refreshing screen, the header and the first round of
+/// output are printed and watch returns as if it had been interrupted. This only
+/// handles help, version and bad arguments
+pub struct WatchCommand;
+
+#[async_trait]
+impl Command for WatchCommand {
+    fn name(&self) -> &'static str {
+        "watch"
+    }
+
+    fn help(&self) -> String {
+        "\r\n\
+        Usage:\r\n\
+        \x20watch [options] command\r\n\
+        \r\n\
+        Options:\r\n\
+        \x20 -b, --beep             beep if command has a non-zero exit\r\n\
+        \x20 -c, --color            interpret ANSI color and style sequences\r\n\
+        \x20 -d, --differences[=<permanent>]\r\n\
+        \x20                        highlight changes between updates\r\n\
+        \x20 -e, --errexit          exit if command has a non-zero exit\r\n\
+        \x20 -g, --chgexit          exit when output from command changes\r\n\
+        \x20 -n, --interval <secs>  seconds to wait between updates\r\n\
+        \x20 -p, --precise          attempt run command in precise intervals\r\n\
+        \x20 -t, --no-title         turn off header\r\n\
+        \x20 -w, --no-wrap          turn off line wrapping\r\n\
+        \x20 -x, --exec             pass command to exec instead of \"sh -c\"\r\n\
+        \r\n\
+        \x20-h, --help     display this help and exit\r\n\
+        \x20-v, --version  output version information and exit\r\n\
+        \r\n\
+        For more details see watch(1).\r\n"
+            .to_string()
+    }
+
+    fn version(&self) -> String {
+        "watch from procps-ng 3.3.17\r\n".to_string()
+    }
+
+    async fn execute(&self, args: &[String], _context: &mut CommandContext) -> CommandResult {
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if let Some(value) = inline_interval(arg) {
+                check_interval(value)?;
+                continue;
+            }
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(self.help()),
+                "-v" | "--version" => return Ok(self.version()),
+                "-n" | "--interval" => {
+                    let Some(value) = args.next() else {
+                        return Err(usage_error("option requires an argument -- 'n'"));
+                    };
+                    check_interval(value)?;
+                }
+                option if option.starts_with("--") => {
+                    if !LONG_FLAGS.contains(&option) && !option.starts_with("--differences") {
+                        return Err(usage_error(&format!("unrecognized option '{}'", option)));
+                    }
+                }
+                option if option.starts_with('-') && option.len() > 1 => {
+                    if let Some(flag) = option[1..].chars().find(|c| !FLAGS.contains(*c)) {
+                        return Err(usage_error(&format!("invalid option -- '{}'", flag)));
+                    }
+                }
+                _ => break,
+            }
+        }
+        Err(CommandError::ExecutionError(
+            self.help().trim_end_matches("\r\n").to_string(),
+        ))
+    }
+}
+
+const FLAGS: &str = "bcdegptwx";
+
+const LONG_FLAGS: &[&str] = &[
+    "--beep",
+    "--color",
+    "--errexit",
+    "--chgexit",
+    "--precise",
+    "--no-title",
+    "--no-wrap",
+    "--exec",
+];
+
+fn usage_error(message: &str) -> CommandError {
+    CommandError::InvalidArguments(format!(
+        "watch: {}\r\n\r\nUsage:\r\n watch [options] command\r\n\r\nFor more details see watch(1).",
+        message
+    ))
+}
+
+/// Interval given in the same argument, `-n1` or `--interval=1`
+fn inline_interval(arg: &str) -> Option<&str> {
+    arg.strip_prefix("--interval=")
+        .or_else(|| arg.strip_prefix("-n").filter(|value| !value.is_empty()))
+}
+
+fn check_interval(value: &str) -> Result<f64, CommandError> {
+    value
+        .replace(',', ".")
+        .parse::<f64>()
+        .ok()
+        .filter(|interval| interval.is_finite())
+        .ok_or_else(|| {
+            CommandError::InvalidArguments(format!("watch: failed to parse argument: '{}'", value))
+        })
+}
+
+/// Options and command line of a `watch` invocation, None when watch would
+/// only print help, its version or a usage error
+pub(crate) fn watch_options(args: &[String]) -> Option<WatchOptions> {
+    let mut interval = 2.0;
+    let mut title = true;
+    let mut index = 0;
+    while let Some(arg) = args.get(index) {
+        if let Some(value) = inline_interval(arg) {
+            interval = check_interval(value).ok()?;
+            index += 1;
+            continue;
+        }
+        match arg.as_str() {
+            "-n" | "--interval" => {
+                interval = check_interval(args.get(index + 1)?).ok()?;
+                index += 1;
+            }
+            "-t" | "--no-title" => title = false,
+            "--" => {
+                index += 1;
+                break;
+            }
+            option if option.starts_with("--") => {
+                if !LONG_FLAGS.contains(&option) && !option.starts_with("--differences") {
+                    return None;
+                }
+            }
+            option if option.starts_with('-') && option.len() > 1 => {
+                if option[1..].chars().any(|c| !FLAGS.contains(c)) {
+                    return None;
+                }
+                if option.contains('t') {
+                    title = false;
+                }
+            }
+            _ => break,
+        }
+        index += 1;
+    }
+
+    let command = args.get(index..)?.join(" ");
+    if command.is_empty() {
+        return None;
+    }
+    Some(WatchOptions {
+        // procps-ng doesn't go below a tenth of a second
+        interval: interval.max(0.1),
+        title,
+        command,
+    })
+}
+
+/// The header line watch draws above the output, the command on the left and
+/// host and time on the right, padded to the terminal `width`
+pub(crate) fn watch_header(interval: f64, command: &str, right: &str, width: usize) -> String {
+    let left = format!("Every {:.1}s: {}", interval, command);
+    let used = left.chars().count() + right.chars().count();
+    if used < width {
+        format!("{}{}{}", left, " ".repeat(width - used), right)
+    } else {
+        // Like watch, the command gets cut to keep host and time visible
+        let keep = width.saturating_sub(right.chars().count() + 1);
+        format!("{} {}", left.chars().take(keep).collect::<String>(), right)
+    }
+}
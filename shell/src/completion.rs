@@ -0,0 +1,247 @@
+//! Tab completion for the interactive shell.
+//!
+//! Completes command names for the first word of a command and paths from the
+//! virtual filesystem for everything else, roughly what readline does with
+//! bash's default settings: the first Tab inserts as much as all candidates
+//! have in common, a second Tab lists them.
+
+use crate::filesystem::fs2::{FileContent, FileSystem};
+
+/// Characters bash escapes with a backslash when completing a file name
+const ESCAPED: &[char] = &[
+    ' ', '\t', '\\', '\'', '"', '$', '&', ';', '|', '(', ')', '<', '>', '*', '?', '`', '!',
+];
+
+/// Result of completing the end of a line
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Completion {
+    /// Text to append to the line. A single candidate is finished with `/`
+    /// for a directory and a space for anything else
+    pub insert: String,
+    /// All candidates when more than one matches, directories with a trailing `/`
+    pub candidates: Vec<String>,
+}
+
+/// Complete the last word of `line`. `commands` are the names available in
+/// command position, paths are looked up relative to `cwd` with `~` being `home`
+pub fn complete(
+    line: &str,
+    commands: &[String],
+    fs: &FileSystem,
+    cwd: &str,
+    home: &str,
+) -> Completion {
+    let start = line
+        .rfind(|c: char| c.is_whitespace() || matches!(c, ';' | '|' | '&' | '(' | '<' | '>'))
+        .map_or(0, |i| i + 1);
+    let word = unescape(&line[start..]);
+    let before = line[..start].trim_end();
+    let command_position =
+        before.is_empty() || before.ends_with([';', '|', '&', '(']) || before.ends_with("$(");
+
+    if command_position && !word.contains('/') {
+        // Listing every command on an empty line isn't worth it
+        if word.is_empty() {
+            return Completion::default();
+        }
+        let matches: Vec<(String, bool)> = commands
+            .iter()
+            .filter(|name| name.starts_with(&word))
+            .map(|name| (name.clone(), false))
+            .collect();
+        return build(&word, matches);
+    }
+
+    let (dir, prefix) = match word.rfind('/') {
+        Some(i) => (&word[..=i], &word[i + 1..]),
+        None => ("", word.as_str()),
+    };
+    let lookup = if dir.is_empty() {
+        cwd.to_string()
+    } else if dir.starts_with('/') {
+        dir.to_string()
+    } else if let Some(rest) = dir.strip_prefix('~') {
+        format!("{}{}", home, rest)
+    } else {
+        format!("{}/{}", cwd.trim_end_matches('/'), dir)
+    };
+    let Ok(entries) = fs.list_directory(&lookup) else {
+        return Completion::default();
+    };
+
+    let mut matches: Vec<(String, bool)> = entries
+        .into_iter()
+        .filter(|entry| entry.name.starts_with(prefix))
+        // Dot files only show up once the prefix asks for them
+        .filter(|entry| prefix.starts_with('.') || !entry.name.starts_with('.'))
+        .map(|entry| {
+            let path = format!("{}/{}", lookup.trim_end_matches('/'), entry.name);
+            let is_dir = matches!(
+                fs.follow_symlink(&path).map(|e| e.file_content),
+                Ok(Some(FileContent::Directory(_)))
+            );
+            (entry.name, is_dir)
+        })
+        .collect();
+    matches.sort();
+    build(prefix, matches)
+}
+
+/// Turn the names matching `prefix` into a completion
+fn build(prefix: &str, matches: Vec<(String, bool)>) -> Completion {
+    match matches.as_slice() {
+        [] => Completion::default(),
+        [(name, is_dir)] => Completion {
+            insert: format!(
+                "{}{}",
+                escape(&name[prefix.len()..]),
+                if *is_dir { "/" } else { " " }
+            ),
+            candidates: Vec::new(),
+        },
+        [(first, _), rest @ ..] => {
+            // Byte length of the prefix every candidate shares
+            let common = rest.iter().fold(first.len(), |common, (name, _)| {
+                first[..common]
+                    .char_indices()
+                    .zip(name.chars())
+                    .take_while(|((_, a), b)| a == b)
+                    .map(|((i, a), _)| i + a.len_utf8())
+                    .last()
+                    .unwrap_or(0)
+            });
+            Completion {
+                insert: escape(&first[prefix.len()..common.max(prefix.len())]),
+                candidates: matches
+                    .iter()
+                    .map(|(name, is_dir)| {
+                        if *is_dir {
+                            format!("{}/", name)
+                        } else {
+                            name.clone()
+                        }
+                    })
+                    .collect(),
+            }
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if ESCAPED.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Drop the backslashes and quotes of a partially typed word
+fn unescape(word: &str) -> String {
+    let mut plain = String::with_capacity(word.len());
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => plain.extend(chars.next()),
+            '\'' | '"' => {}
+            _ => plain.push(c),
+        }
+    }
+    plain
+}
+
+/// Lay out candidates in columns across a terminal `width` wide, sorted down
+/// the columns like bash and ls do. Lines end with `\r\n`
+pub fn format_candidates(candidates: &[String], width: usize) -> String {
+    let column = candidates
+        .iter()
+        .map(|c| c.chars().count())
+        .max()
+        .unwrap_or(0)
+        + 2;
+    let columns = (width / column).max(1);
+    let rows = candidates.len().div_ceil(columns);
+
+    let mut output = String::new();
+    for row in 0..rows {
+        let mut line = String::new();
+        for col in 0..columns {
+            if let Some(candidate) = candidates.get(col * rows + row) {
+                line.push_str(&format!("{:<width$}", candidate, width = column));
+            }
+        }
+        output.push_str(line.trim_end());
+        output.push_str("\r\n");
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filesystem() -> FileSystem {
+        let mut fs = FileSystem::default();
+        fs.create_directory("/home").unwrap();
+        fs.create_directory("/home/root").unwrap();
+        fs.create_directory("/home/root/scripts").unwrap();
+        fs.create_file("/home/root/setup.sh").unwrap();
+        fs.create_file("/home/root/.bashrc").unwrap();
+        fs.create_file("/home/root/my file").unwrap();
+        fs.create_directory("/etc").unwrap();
+        fs.create_file("/etc/passwd").unwrap();
+        fs.create_file("/etc/profile").unwrap();
+        fs
+    }
+
+    fn complete_in_home(line: &str) -> Completion {
+        let commands: Vec<String> = ["cat", "cd", "clear", "uname", "unset"]
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        complete(line, &commands, &filesystem(), "/home/root", "/home/root")
+    }
+
+    #[test]
+    fn completes_command_names() {
+        assert_eq!(complete_in_home("una").insert, "me ");
+        assert_eq!(complete_in_home("ls; una").insert, "me ");
+        let ambiguous = complete_in_home("un");
+        assert_eq!(ambiguous.insert, "");
+        assert_eq!(ambiguous.candidates, ["uname", "unset"]);
+        assert_eq!(complete_in_home("c").candidates, ["cat", "cd", "clear"]);
+        assert_eq!(complete_in_home(""), Completion::default());
+    }
+
+    #[test]
+    fn completes_paths() {
+        assert_eq!(complete_in_home("cat /etc/pas").insert, "swd ");
+        assert_eq!(complete_in_home("cd scr").insert, "ipts/");
+        assert_eq!(complete_in_home("cat ~/se").insert, "tup.sh ");
+        assert_eq!(complete_in_home("cat my").insert, "\\ file ");
+        assert_eq!(complete_in_home("./set").insert, "up.sh ");
+        let ambiguous = complete_in_home("cat /etc/p");
+        assert_eq!(ambiguous.insert, "");
+        assert_eq!(ambiguous.candidates, ["passwd", "profile"]);
+        assert_eq!(
+            complete_in_home("ls ").candidates,
+            ["my file", "scripts/", "setup.sh"]
+        );
+        assert_eq!(complete_in_home("cat .b").insert, "ashrc ");
+        assert_eq!(complete_in_home("cat /nope/x"), Completion::default());
+    }
+
+    #[test]
+    fn lists_candidates_in_columns() {
+        let candidates: Vec<String> = ["alpha", "beta", "gamma", "delta", "epsilon"]
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        assert_eq!(
+            format_candidates(&candidates, 20),
+            "alpha    delta\r\nbeta     epsilon\r\ngamma\r\n"
+        );
+    }
+}
@@ -86,11 +86,23 @@ println!("Retrieved File: {:?}", file);
 
 This module creates a lightweight simulation of a file system, enabling basic operations such as navigation, file creation, and directory management.
 */
+use flate2::Compression;
 use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
 use std::collections::{HashMap, HashSet};
-use std::io::{Error, ErrorKind, Read};
+use std::io::{Error, ErrorKind, Read, Write};
 use std::sync::Arc;
-use tar::Archive;
+use tar::{Archive, Builder, EntryType, Header};
+
+/// Files take whole blocks of the disk, directories one
+const BLOCK_SIZE: u64 = 4096;
+/// Disk space per inode, what mke2fs gives an ext4 filesystem by default
+const BYTES_PER_INODE: u64 = 16384;
+/// Disk the filesystem claims to be on when no size is set, a small cloud VM
+const DEFAULT_DISK_SIZE: u64 = 40 * 1024 * 1024 * 1024;
+/// Taken on the default disk before any file of the fake filesystem. Its files
+/// are only a sliver of a real install, which would look empty next to the size
+const DEFAULT_DISK_BASE_USAGE: u64 = 19 * 1024 * 1024 * 1024;
 
 #[derive(Default, Copy, Clone, Debug)]
 #[allow(dead_code)]
@@ -185,8 +197,29 @@ pub struct FileSystem {
     snapshot: Option<FileSystemSnapshot>,
 
     // Device info
-    #[allow(dead_code)]
     device: String,
+
+    /// Bytes of the disk, None when writes never run out of space
+    disk_size: Option<u64>,
+}
+
+/// Size and fill of the disk the filesystem is on, what df shows
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiskUsage {
+    pub size: u64,
+    pub used: u64,
+    pub inodes: u64,
+    pub inodes_used: u64,
+}
+
+impl DiskUsage {
+    pub fn available(&self) -> u64 {
+        self.size.saturating_sub(self.used)
+    }
+
+    pub fn inodes_available(&self) -> u64 {
+        self.inodes.saturating_sub(self.inodes_used)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -215,11 +248,68 @@ impl Default for FileSystem {
             next_inode: 2, // Start allocating from inode 2
             snapshot: None,
             device: "/dev/sda1".to_string(),
+            disk_size: None,
         }
     }
 }
 
 impl FileSystem {
+    /// Block device the filesystem is mounted from, e.g. /dev/sda1
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+
+    /// Put the filesystem on a disk of `size` bytes. Once its files fill it, new
+    /// files and growing writes fail with ENOSPC. Survives snapshot restores
+    pub fn set_disk_size(&mut self, size: Option<u64>) {
+        self.disk_size = size;
+    }
+
+    /// Size and fill of the disk. Without a size set, a 40 GiB disk about half
+    /// full with the operating system
+    pub fn disk_usage(&self) -> DiskUsage {
+        let (size, base) = match self.disk_size {
+            Some(size) => (size, 0),
+            None => (DEFAULT_DISK_SIZE, DEFAULT_DISK_BASE_USAGE),
+        };
+        let files: u64 = self
+            .inodes
+            .values()
+            .map(|data| match &data.content {
+                Some(FileContent::Directory(_)) => BLOCK_SIZE,
+                Some(FileContent::RegularFile(_)) => blocks(data.inode.i_size_lo as u64),
+                Some(FileContent::SymbolicLink(_)) | None => 0,
+            })
+            .sum();
+        DiskUsage {
+            size,
+            used: base + files,
+            inodes: size / BYTES_PER_INODE,
+            inodes_used: self.inodes.len() as u64,
+        }
+    }
+
+    /// Check that a file of `old_len` bytes may grow to `new_len`, ENOSPC when
+    /// the disk has no room left for it. Writers call this before storing
+    pub fn ensure_space(&self, old_len: u64, new_len: u64) -> std::io::Result<()> {
+        if self.disk_size.is_none() {
+            return Ok(());
+        }
+        let growth = blocks(new_len).saturating_sub(blocks(old_len));
+        if growth > self.disk_usage().available() {
+            return Err(no_space());
+        }
+        Ok(())
+    }
+
+    /// ENOSPC when the disk has no inode left for another entry
+    fn ensure_inode(&self) -> std::io::Result<()> {
+        match self.disk_size {
+            Some(size) if self.inodes.len() as u64 >= size / BYTES_PER_INODE => Err(no_space()),
+            _ => Ok(()),
+        }
+    }
+
     /// Allocate a new inode number
     fn allocate_inode(&mut self) -> u64 {
         let inode_num = self.next_inode;
@@ -511,6 +601,7 @@ impl FileSystem {
         };
 
         // Now safe to allocate and insert
+        self.ensure_inode()?;
         let new_inode_num = self.allocate_inode();
         let mut inode = Inode::default();
         inode.i_links_count = 1;
@@ -573,6 +664,7 @@ impl FileSystem {
         };
 
         // Now safe to allocate and insert
+        self.ensure_inode()?;
         let new_inode_num = self.allocate_inode();
         let mut inode = Inode::default();
         inode.i_links_count = 1;
@@ -639,6 +731,7 @@ impl FileSystem {
         };
 
         // Now safe to allocate and insert
+        self.ensure_inode()?;
         let new_inode_num = self.allocate_inode();
         let mut inode = Inode::default();
         inode.i_links_count = 1;
@@ -686,6 +779,11 @@ impl FileSystem {
     }
 
     pub fn follow_symlink(&self, path: &str) -> std::io::Result<FileEntryView> {
+        self.get_file(&self.resolve_symlinks(path)?)
+    }
+
+    /// Absolute path of what `path` points at once symlinks are followed
+    pub fn resolve_symlinks(&self, path: &str) -> std::io::Result<String> {
         let mut current_path = self.resolve_absolute_path(path);
         let mut visited_paths = std::collections::HashSet::new();
 
@@ -701,7 +799,7 @@ impl FileSystem {
 
                     // Update current path to follow the symlink
                     current_path = if target.starts_with('/') {
-                        target.clone()
+                        self.resolve_absolute_path(target)
                     } else {
                         // Handle relative paths by combining with parent directory
                         let parent = current_path.rsplit_once('/').map(|(p, _)| p).unwrap_or("");
@@ -709,7 +807,7 @@ impl FileSystem {
                         self.resolve_absolute_path(&format!("{}/{}", parent, target))
                     };
                 }
-                _ => return Ok(entry), // Found non-symlink entry
+                _ => return Ok(current_path), // Found non-symlink entry
             }
         }
     }
@@ -1032,6 +1130,74 @@ impl FileSystem {
         Ok(())
     }
 
+    /// Write the filesystem as a gzipped tarball, the inverse of [`Self::process_targz`].
+    /// Entries carry the mode, owner and times of their inode. A file linked from
+    /// several directories is stored once, its other names as hard links to it
+    pub fn export_targz<W: Write>(&self, writer: W) -> std::io::Result<()> {
+        let mut builder = Builder::new(GzEncoder::new(writer, Compression::default()));
+        let mut exported = HashMap::new();
+        self.export_directory(&mut builder, self.root_inode, "", &mut exported)?;
+        builder.into_inner()?.finish()?;
+        Ok(())
+    }
+
+    /// Append the entries of a directory and everything below it. `exported` maps
+    /// the inodes already written to their path in the archive
+    fn export_directory<W: Write>(
+        &self,
+        builder: &mut Builder<W>,
+        directory: u64,
+        prefix: &str,
+        exported: &mut HashMap<u64, String>,
+    ) -> std::io::Result<()> {
+        let entries = match &self.get_inode(directory)?.content {
+            Some(FileContent::Directory(entries)) => entries,
+            _ => return Err(Error::new(ErrorKind::Other, "Not a directory")),
+        };
+
+        for entry in entries {
+            let path = format!("{}{}", prefix, entry.name);
+            let Ok(inode_data) = self.get_inode(entry.inode_number) else {
+                log::warn!("Skipping {} with a missing inode", path);
+                continue;
+            };
+
+            if let Some(target) = exported.get(&entry.inode_number) {
+                let mut header = tar_header(&inode_data.inode, EntryType::Link);
+                builder.append_link(&mut header, &path, target)?;
+                continue;
+            }
+            exported.insert(entry.inode_number, path.clone());
+
+            match &inode_data.content {
+                Some(FileContent::Directory(_)) => {
+                    let mut header = tar_header(&inode_data.inode, EntryType::Directory);
+                    builder.append_data(&mut header, &path, std::io::empty())?;
+                    self.export_directory(
+                        builder,
+                        entry.inode_number,
+                        &format!("{}/", path),
+                        exported,
+                    )?;
+                }
+                Some(FileContent::RegularFile(content)) => {
+                    let mut header = tar_header(&inode_data.inode, EntryType::Regular);
+                    header.set_size(content.len() as u64);
+                    builder.append_data(&mut header, &path, content.as_slice())?;
+                }
+                Some(FileContent::SymbolicLink(target)) => {
+                    let mut header = tar_header(&inode_data.inode, EntryType::Symlink);
+                    builder.append_link(&mut header, &path, target)?;
+                }
+                None => {
+                    log::warn!("Skipping {} without content", path);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Take a snapshot of the current filesystem state for later rollback.
     /// With Arc-wrapped file content, this is cheap (Arc reference count bumps, not deep copies).
     pub fn take_snapshot(&mut self) {
@@ -1060,6 +1226,31 @@ impl FileSystem {
     }
 }
 
+/// Bytes `len` takes on the disk
+fn blocks(len: u64) -> u64 {
+    len.div_ceil(BLOCK_SIZE) * BLOCK_SIZE
+}
+
+fn no_space() -> Error {
+    Error::new(ErrorKind::StorageFull, "No space left on device")
+}
+
+/// Tar header for an entry of the given type with the metadata of `inode`
+fn tar_header(inode: &Inode, entry_type: EntryType) -> Header {
+    let mut header = Header::new_gnu();
+    header.set_entry_type(entry_type);
+    header.set_mode(u32::from(inode.i_mode & 0o7777));
+    header.set_uid(u64::from(inode.i_uid_high) << 16 | u64::from(inode.i_uid));
+    header.set_gid(u64::from(inode.i_gid_high) << 16 | u64::from(inode.i_gid));
+    header.set_mtime(u64::from(inode.i_mtime));
+    header.set_size(0);
+    if let Some(gnu) = header.as_gnu_mut() {
+        gnu.set_atime(inode.i_atime);
+        gnu.set_ctime(inode.i_ctime);
+    }
+    header
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1695,4 +1886,83 @@ mod tests {
         assert!(result.is_err());
         assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
     }
+
+    #[test]
+    fn test_full_disk_refuses_writes() {
+        let mut fs = FileSystem::default();
+        fs.create_directory("/tmp").unwrap();
+        fs.set_disk_size(Some(64 * 1024));
+        let usage = fs.disk_usage();
+        assert_eq!(usage.used, 2 * BLOCK_SIZE);
+        assert_eq!(usage.inodes, 4);
+
+        fs.ensure_space(0, 56 * 1024).unwrap();
+        fs.create_file("/tmp/a").unwrap().inode.i_size_lo = 56 * 1024;
+        assert_eq!(fs.disk_usage().available(), 0);
+        let err = fs.ensure_space(0, 1).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::StorageFull);
+        assert_eq!(err.to_string(), "No space left on device");
+        // Rewriting within the blocks a file has takes nothing more
+        fs.ensure_space(56 * 1024, 55 * 1024 + 1).unwrap();
+
+        fs.create_file("/tmp/b").unwrap();
+        let err = fs.create_file("/tmp/c").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::StorageFull);
+
+        fs.set_disk_size(None);
+        fs.ensure_space(0, 1 << 30).unwrap();
+        assert_eq!(fs.disk_usage().size, DEFAULT_DISK_SIZE);
+    }
+
+    #[test]
+    fn test_export_targz_round_trips() {
+        let mut fs = FileSystem::default();
+        let dir = fs.create_directory("/home").unwrap();
+        dir.inode.i_mode = 0o755;
+        fs.create_directory("/home/user").unwrap();
+        let file = fs.create_file("/home/user/dropper.sh").unwrap();
+        file.inode.i_mode = 0o4755;
+        file.inode.i_uid = 1000;
+        file.inode.i_gid_high = 1;
+        file.inode.i_mtime = 1_700_000_000;
+        file.content = Some(FileContent::RegularFile(Arc::new(
+            b"#!/bin/sh\nwget x\n".to_vec(),
+        )));
+        fs.create_symlink("/home/user/run", "dropper.sh").unwrap();
+        fs.create_hard_link("/home/user/dropper.sh", "/home/copy.sh")
+            .unwrap();
+        let long_name = format!("/home/{}", "a".repeat(150));
+        fs.create_file(&long_name).unwrap();
+
+        let mut archive = Vec::new();
+        fs.export_targz(&mut archive).unwrap();
+        let mut imported = FileSystem::default();
+        imported.process_targz(archive.as_slice()).unwrap();
+
+        assert_eq!(imported.get_file("/home").unwrap().inode.i_mode, 0o755);
+        let file = imported.get_file("/home/user/dropper.sh").unwrap();
+        assert_eq!(file.inode.i_mode, 0o4755);
+        assert_eq!(file.inode.i_uid, 1000);
+        assert_eq!(file.inode.i_gid_high, 1);
+        assert_eq!(file.inode.i_mtime, 1_700_000_000);
+        match file.file_content {
+            Some(FileContent::RegularFile(content)) => {
+                assert_eq!(content.as_slice(), b"#!/bin/sh\nwget x\n")
+            }
+            other => panic!("Expected a regular file, got {:?}", other),
+        }
+        match imported.get_file("/home/user/run").unwrap().file_content {
+            Some(FileContent::SymbolicLink(target)) => assert_eq!(target, "dropper.sh"),
+            other => panic!("Expected a symlink, got {:?}", other),
+        }
+        assert_eq!(
+            imported
+                .get_file("/home/copy.sh")
+                .unwrap()
+                .inode
+                .i_links_count,
+            2
+        );
+        assert!(imported.get_file(&long_name).is_ok());
+    }
 }
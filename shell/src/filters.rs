@@ -7,40 +7,48 @@
 
 use sha2::{Digest, Sha256};
 
+use crate::commands::awk_command::AwkCommand;
 use crate::commands::context::CommandContext;
+use crate::commands::hexdump_command::HexdumpCommand;
+use crate::commands::sed_command::SedCommand;
 use crate::filesystem::fs2::FileContent;
 
 /// Result of running a filter: `(output, exit_success)`.
 pub type FilterOutcome = Option<(String, bool)>;
 
+/// Names of the filter commands, also offered by tab completion.
+pub const FILTER_NAMES: &[&str] = &[
+    "grep",
+    "egrep",
+    "fgrep",
+    "head",
+    "tail",
+    "sort",
+    "uniq",
+    "wc",
+    "cut",
+    "tr",
+    "rev",
+    "tac",
+    "nl",
+    "base64",
+    "strings",
+    "sed",
+    "awk",
+    "column",
+    "tee",
+    "cat",
+    "xargs",
+    "sha256sum",
+    "md5sum",
+    "xxd",
+    "hexdump",
+    "od",
+];
+
 /// Whether `name` is a recognized filter command.
 pub fn is_filter(name: &str) -> bool {
-    matches!(
-        name,
-        "grep"
-            | "egrep"
-            | "fgrep"
-            | "head"
-            | "tail"
-            | "sort"
-            | "uniq"
-            | "wc"
-            | "cut"
-            | "tr"
-            | "rev"
-            | "tac"
-            | "nl"
-            | "base64"
-            | "strings"
-            | "sed"
-            | "awk"
-            | "column"
-            | "tee"
-            | "cat"
-            | "xargs"
-            | "sha256sum"
-            | "md5sum"
-    )
+    FILTER_NAMES.contains(&name)
 }
 
 /// Apply a filter command. `input` is the piped stdin (empty for the first stage).
@@ -75,15 +83,24 @@ pub async fn apply_filter(
         "xargs" => Some(xargs_cmd(args, input)),
         "sha256sum" => Some(hash_cmd::<Sha256>(args, input, context).await),
         "md5sum" => Some((md5_stub(args, input, context).await, true)),
+        "xxd" | "hexdump" | "od" => Some(hexdump_cmd(name, args, input, context).await),
         _ => None,
     }
 }
 
 async fn read_file(path: &str, context: &CommandContext) -> Option<String> {
+    // Relative names are looked up from the directory the session cd'd into
+    let path = match path.starts_with('/') {
+        true => path.to_string(),
+        false => format!("{}/{}", context.cwd.trim_end_matches('/'), path),
+    };
     let fs = context.filesystem.read().await;
-    let entry = fs.follow_symlink(path).ok()?;
+    let entry = fs.follow_symlink(&path).ok()?;
     match entry.file_content.as_ref()? {
-        FileContent::RegularFile(bytes) => Some(String::from_utf8_lossy(bytes).into_owned()),
+        FileContent::RegularFile(bytes) => {
+            context.note_read(&fs, &path);
+            Some(String::from_utf8_lossy(bytes).into_owned())
+        }
         _ => None,
     }
 }
@@ -693,193 +710,19 @@ async fn strings_cmd(args: &[String], input: &str, context: &CommandContext) ->
 }
 
 async fn sed_cmd(args: &[String], input: &str, context: &CommandContext) -> (String, bool) {
-    let mut script: Option<String> = None;
-    let mut files: Vec<String> = Vec::new();
-    for a in args {
-        if script.is_none() && !a.starts_with('-') {
-            script = Some(a.clone());
-        } else if script.is_some() && !a.starts_with('-') {
-            files.push(a.clone());
-        }
-    }
-    let Some(script) = script else {
-        return (input.to_string(), true);
-    };
-    let source = gather_input(&files, input, context).await;
-    let mut out = String::new();
-
-    if let Some(spec) = script.strip_prefix("s") {
-        if let Some((pat, rep, flags)) = parse_subst(&spec) {
-            let global = flags.contains('g');
-            let ignore_case = flags.contains('i');
-            for line in source.lines() {
-                out.push_str(&subst_once_or_all(line, &pat, &rep, global, ignore_case));
-                out.push('\n');
-            }
-        }
-    } else if let Some(spec) = script.strip_prefix('/') {
-        if let Some(idx) = spec.find('/') {
-            let pat = &spec[..idx];
-            let action = &spec[idx + 1..];
-            for line in source.lines() {
-                let matches = line.contains(pat);
-                match action.chars().next() {
-                    Some('d') => {
-                        if !matches {
-                            out.push_str(line);
-                            out.push('\n');
-                        }
-                    }
-                    Some('p') => {
-                        if matches {
-                            out.push_str(line);
-                            out.push('\n');
-                            out.push_str(line);
-                            out.push('\n');
-                        } else {
-                            out.push_str(line);
-                            out.push('\n');
-                        }
-                    }
-                    _ => {
-                        out.push_str(line);
-                        out.push('\n');
-                    }
-                }
-            }
-        }
-    } else {
-        for line in source.lines() {
-            out.push_str(line);
-            out.push('\n');
-        }
-    }
-
-    (out.replace('\n', "\r\n"), true)
-}
-
-fn parse_subst(spec: &str) -> Option<(String, String, String)> {
-    let delim = spec.chars().next()?;
-    let rest = &spec[delim.len_utf8()..];
-    let parts: Vec<&str> = rest.splitn(3, delim).collect();
-    if parts.len() == 3 {
-        Some((
-            parts[0].to_string(),
-            parts[1].to_string(),
-            parts[2].to_string(),
-        ))
-    } else {
-        None
-    }
-}
-
-fn subst_once_or_all(line: &str, pat: &str, rep: &str, global: bool, ignore_case: bool) -> String {
-    let (haystack, needle, restore) = if ignore_case {
-        (
-            line.to_lowercase(),
-            pat.to_lowercase(),
-            Some(line.to_string()),
-        )
-    } else {
-        (line.to_string(), pat.to_string(), None)
-    };
-
-    let mut result = String::new();
-    let mut consumed = 0;
-    let mut last = 0;
-    let bytes_h = haystack.as_bytes();
-    let needle_b = needle.as_bytes();
-    while let Some(pos) = find_subslice(&bytes_h[last..], needle_b) {
-        let abs = last + pos;
-        result.push_str(&line[consumed..abs]);
-        result.push_str(rep);
-        consumed = abs + needle.len();
-        last = abs + 1;
-        if !global {
-            break;
-        }
-    }
-    result.push_str(&line[consumed..]);
-    let _ = restore;
-    result
-}
-
-fn find_subslice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
-    if needle.is_empty() {
-        return None;
+    match SedCommand.run(args, input, context).await {
+        Ok(out) => (out, true),
+        // The diagnostic goes to stderr, which a pipeline stage doesn't have
+        Err(_) => (String::new(), false),
     }
-    haystack.windows(needle.len()).position(|w| w == needle)
 }
 
 async fn awk_cmd(args: &[String], input: &str, context: &CommandContext) -> (String, bool) {
-    let mut program: Option<String> = None;
-    let mut field_sep = ' ';
-    let mut files: Vec<String> = Vec::new();
-    let mut i = 0;
-    while i < args.len() {
-        let a = &args[i];
-        if let Some(rest) = a.strip_prefix("-F") {
-            field_sep = rest.chars().next().unwrap_or(' ');
-        } else if program.is_none() && !a.starts_with('-') {
-            program = Some(a.clone());
-        } else if program.is_some() && !a.starts_with('-') {
-            files.push(a.clone());
-        }
-        i += 1;
-    }
-    let source = gather_input(&files, input, context).await;
-
-    let print_spec = program
-        .as_deref()
-        .and_then(|p| extract_print_spec(p))
-        .unwrap_or_default();
-
-    let mut out = String::new();
-    for line in source.lines() {
-        let fields: Vec<&str> = if field_sep == ' ' {
-            line.split_whitespace().collect()
-        } else {
-            line.split(field_sep).collect()
-        };
-        if print_spec.is_empty() {
-            out.push_str(line);
-        } else {
-            for (idx, spec) in print_spec.iter().enumerate() {
-                if idx > 0 {
-                    out.push(' ');
-                }
-                out.push_str(&eval_print(spec, &fields));
-            }
-        }
-        out.push('\n');
+    match AwkCommand.run(args, input, context).await {
+        Ok(out) => (out, true),
+        // The diagnostic goes to stderr, which a pipeline stage doesn't have
+        Err(_) => (String::new(), false),
     }
-    (out.replace('\n', "\r\n"), true)
-}
-
-fn extract_print_spec(program: &str) -> Option<Vec<String>> {
-    let trimmed = program.trim().trim_start_matches('{').trim_end_matches('}');
-    let body = trimmed.trim();
-    let body = body.strip_prefix("print ").unwrap_or(body);
-    Some(body.split(',').map(|s| s.trim().to_string()).collect())
-}
-
-fn eval_print(spec: &str, fields: &[&str]) -> String {
-    if spec == "$0" {
-        return fields.join(" ");
-    }
-    if let Some(rest) = spec.strip_prefix('$') {
-        if rest == "NF" {
-            return fields.len().to_string();
-        }
-        if let Ok(n) = rest.parse::<usize>() {
-            return fields
-                .get(n.wrapping_sub(1))
-                .copied()
-                .unwrap_or("")
-                .to_string();
-        }
-    }
-    spec.trim_matches('"').trim_matches('\'').to_string()
 }
 
 async fn column_cmd(args: &[String], input: &str, context: &CommandContext) -> (String, bool) {
@@ -957,6 +800,25 @@ async fn hash_cmd<D: Digest + Send>(
     (format!("{}  {}\r\n", hex, label), true)
 }
 
+async fn hexdump_cmd(
+    name: &str,
+    args: &[String],
+    input: &str,
+    context: &CommandContext,
+) -> (String, bool) {
+    let command = match name {
+        "xxd" => HexdumpCommand::Xxd,
+        "od" => HexdumpCommand::Od,
+        _ => HexdumpCommand::Hexdump,
+    };
+    // Piped output has the terminal's line endings, the program wrote \n
+    let input = input.replace("\r\n", "\n");
+    match command.dump(args, input.as_bytes(), context).await {
+        Ok(out) => (out, true),
+        Err(err) => (format!("{}\r\n", err), false),
+    }
+}
+
 async fn md5_stub(args: &[String], input: &str, context: &CommandContext) -> String {
     let files: Vec<String> = args
         .iter()
@@ -1,4 +1,5 @@
 pub mod commands;
+pub mod completion;
 pub mod filesystem;
 pub mod filters;
 pub mod parser;
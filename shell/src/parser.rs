@@ -4,7 +4,11 @@
 //! Handles quote-aware operator splitting (`;`, `&&`, `||`, `|`, `&`), redirection
 //! capture (`>`, `>>`, `<`, `2>`, `&>`, `2>&1`), subshell unwrapping (`(` `)`),
 //! comments (`#`), variable expansion (`$VAR`, `${VAR}`, `$?`), tilde expansion (`~`)
-//! and word tokenization via `shlex`.
+//! and quote-aware word splitting.
+//!
+//! `parse_script_unexpanded` keeps the words as written, quotes included, for the
+//! dispatcher to expand each command right before it runs, so `export`, `cd` and
+//! assignments earlier on the line are seen by the commands after them.
 //!
 //! Command substitution (`$(...)`) and arithmetic (`$((...))`) are resolved by the
 //! dispatcher (which can run sub-commands); this module provides the pure helpers
@@ -48,7 +52,7 @@ enum Span {
     Redir(Redirect),
 }
 
-/// A token after word-splitting + expansion, a carried-over operator, or a redirection.
+/// A word as written, a carried-over operator, or a redirection.
 #[derive(Debug, Clone)]
 enum Tok {
     Word(String),
@@ -56,7 +60,7 @@ enum Tok {
     Redir(Redirect),
 }
 
-/// A simple command: name + tokenized, expanded arguments + redirections.
+/// A simple command: name + tokenized arguments + redirections.
 #[derive(Debug, Clone)]
 pub struct SimpleCommand {
     pub name: String,
@@ -64,6 +68,34 @@ pub struct SimpleCommand {
     pub redirects: Vec<Redirect>,
 }
 
+impl SimpleCommand {
+    /// The command with its words and redirection targets expanded against `env`,
+    /// as the shell does right before running it
+    pub fn expand(&self, env: &HashMap<String, String>, home: &str) -> SimpleCommand {
+        let redirects = self
+            .redirects
+            .iter()
+            .map(|r| match &r.target {
+                // `> ~/.ssh/authorized_keys`, `>> $HOME/.bashrc`
+                RedirTarget::File(target) => Redirect {
+                    target: target_to_enum(expand_word(target, env, home)),
+                    ..r.clone()
+                },
+                _ => r.clone(),
+            })
+            .collect();
+        SimpleCommand {
+            name: expand_word(&self.name, env, home),
+            args: self
+                .args
+                .iter()
+                .map(|a| expand_word(a, env, home))
+                .collect(),
+            redirects,
+        }
+    }
+}
+
 /// How an and-or item connects to the next item.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AndOp {
@@ -173,7 +205,8 @@ fn is_op_char(c: char) -> bool {
 }
 
 /// Read a single redirection target word starting at `start`, skipping leading
-/// whitespace and honouring quotes. Returns the word and the index of the first
+/// whitespace. The word is kept as written, quotes included, and ends at unquoted
+/// whitespace or an operator. Returns the word and the index of the first
 /// unconsumed char.
 fn read_word(chars: &[char], start: usize) -> (String, usize) {
     let mut i = start;
@@ -181,26 +214,18 @@ fn read_word(chars: &[char], start: usize) -> (String, usize) {
         i += 1;
     }
     let mut s = String::new();
-    if i < chars.len() && (chars[i] == '"' || chars[i] == '\'') {
-        let q = chars[i];
-        i += 1;
-        while i < chars.len() && chars[i] != q {
-            s.push(chars[i]);
-            i += 1;
-        }
-        if i < chars.len() {
-            i += 1;
-        }
-    } else {
-        while i < chars.len()
-            && !chars[i].is_whitespace()
-            && !is_op_char(chars[i])
-            && chars[i] != '"'
-            && chars[i] != '\''
-        {
-            s.push(chars[i]);
-            i += 1;
+    let mut quote: Option<char> = None;
+    while i < chars.len() {
+        let c = chars[i];
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c.is_whitespace() || is_op_char(c) => break,
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None => {}
         }
+        s.push(c);
+        i += 1;
     }
     (s, i)
 }
@@ -423,10 +448,93 @@ fn flush_text(spans: &mut Vec<Span>, cur: &mut String) {
     }
 }
 
-/// Expand a single, already-unquoted word: tilde then variables.
-fn expand_word(word: &str, env: &HashMap<String, String>, home: &str) -> String {
-    let tilde = expand_tilde(word, home);
-    expand_vars(&tilde, env)
+/// Split raw text into words at unquoted whitespace. The quotes and backslashes
+/// stay in the words for `expand_word`, an unquoted `#` starting a word begins a
+/// comment.
+fn split_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut cur = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(q) => {
+                cur.push(c);
+                if c == q {
+                    quote = None;
+                } else if c == '\\' && q == '"' {
+                    cur.extend(chars.next());
+                }
+            }
+            None if c.is_whitespace() => {
+                if !cur.is_empty() {
+                    words.push(std::mem::take(&mut cur));
+                }
+            }
+            None if c == '#' && cur.is_empty() => break,
+            None => {
+                cur.push(c);
+                match c {
+                    '\'' | '"' => quote = Some(c),
+                    '\\' => cur.extend(chars.next()),
+                    _ => {}
+                }
+            }
+        }
+    }
+    if !cur.is_empty() {
+        words.push(cur);
+    }
+    words
+}
+
+/// Expand a word as written: `~` at its start, then variables outside single
+/// quotes, removing the quotes. A backslash escapes the next character outside
+/// quotes, and `$`, `` ` ``, `"` or `\` inside double quotes.
+pub fn expand_word(word: &str, env: &HashMap<String, String>, home: &str) -> String {
+    let chars: Vec<char> = expand_tilde(word, home).chars().collect();
+    let mut out = String::new();
+    let mut in_double = false;
+    let mut i = 0usize;
+    while i < chars.len() {
+        match chars[i] {
+            '\'' if !in_double => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|&ch| ch == '\'')
+                    .map_or(chars.len(), |rel| i + 1 + rel);
+                out.extend(&chars[i + 1..end]);
+                i = end + 1;
+            }
+            '"' => {
+                in_double = !in_double;
+                i += 1;
+            }
+            '\\' if i + 1 < chars.len() => {
+                let next = chars[i + 1];
+                if in_double && !matches!(next, '$' | '`' | '"' | '\\') {
+                    out.push('\\');
+                }
+                out.push(next);
+                i += 2;
+            }
+            '$' => match expand_dollar(&chars, i, env) {
+                Some((value, next)) => {
+                    out.push_str(&value);
+                    i = next;
+                }
+                None => {
+                    out.push('$');
+                    i += 1;
+                }
+            },
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    out
 }
 
 fn expand_tilde(word: &str, home: &str) -> String {
@@ -444,24 +552,29 @@ fn expand_vars(s: &str, env: &HashMap<String, String>) -> String {
     let mut out = String::new();
     let mut i = 0usize;
     while i < chars.len() {
-        let c = chars[i];
-        if c != '$' {
-            out.push(c);
-            i += 1;
-            continue;
-        }
-        let Some(&nc) = chars.get(i + 1) else {
-            out.push('$');
-            i += 1;
-            continue;
-        };
-        // Leave command substitution / arithmetic intact for the dispatcher.
-        if nc == '(' {
-            out.push('$');
-            i += 1;
+        if chars[i] == '$'
+            && let Some((value, next)) = expand_dollar(&chars, i, env)
+        {
+            out.push_str(&value);
+            i = next;
             continue;
         }
-        if nc == '{' {
+        out.push(chars[i]);
+        i += 1;
+    }
+    out
+}
+
+/// Value of the expansion whose `$` is at `chars[i]` and the index after it. None
+/// when the `$` is literal, like the one starting a command substitution, which
+/// is left intact for the dispatcher.
+fn expand_dollar(
+    chars: &[char],
+    i: usize,
+    env: &HashMap<String, String>,
+) -> Option<(String, usize)> {
+    match *chars.get(i + 1)? {
+        '{' => {
             // Find the matching closing brace (allowing nested ${...}).
             let mut depth = 1i32;
             let mut end_rel: Option<usize> = None;
@@ -478,47 +591,22 @@ fn expand_vars(s: &str, env: &HashMap<String, String>) -> String {
                     _ => {}
                 }
             }
-            let Some(rel) = end_rel else {
-                out.push('$');
-                i += 1;
-                continue;
-            };
-            let end = i + 2 + rel;
+            let end = i + 2 + end_rel?;
             let inner: String = chars[i + 2..end].iter().collect();
-            out.push_str(&expand_brace(&inner, env));
-            i = end + 1;
-            continue;
-        }
-        if nc == '?' {
-            out.push('0');
-            i += 2;
-            continue;
-        }
-        if nc == '$' {
-            out.push_str("1234");
-            i += 2;
-            continue;
-        }
-        if nc == '#' {
-            out.push('0');
-            i += 2;
-            continue;
+            Some((expand_brace(&inner, env), end + 1))
         }
-        if nc.is_ascii_alphabetic() || nc == '_' {
+        '?' | '#' => Some(("0".to_string(), i + 2)),
+        '$' => Some(("1234".to_string(), i + 2)),
+        c if c.is_ascii_alphabetic() || c == '_' => {
             let mut j = i + 1;
             while j < chars.len() && (chars[j].is_ascii_alphanumeric() || chars[j] == '_') {
                 j += 1;
             }
             let name: String = chars[i + 1..j].iter().collect();
-            let val = env.get(&name).cloned().unwrap_or_default();
-            out.push_str(&val);
-            i = j;
-            continue;
+            Some((env.get(&name).cloned().unwrap_or_default(), j))
         }
-        out.push('$');
-        i += 1;
+        _ => None,
     }
-    out
 }
 
 /// Evaluate the contents of `${...}` (the part between the braces) including
@@ -626,8 +714,8 @@ fn expand_brace(inner: &str, env: &HashMap<String, String>) -> String {
     val
 }
 
-/// Turn scanned spans into tokens (expanded words + operators + redirects).
-fn tokenize(spans: &[Span], env: &HashMap<String, String>, home: &str) -> Vec<Tok> {
+/// Turn scanned spans into tokens (words as written + operators + redirects).
+fn tokenize(spans: &[Span]) -> Vec<Tok> {
     let mut tokens: Vec<Tok> = Vec::new();
 
     for span in spans {
@@ -639,16 +727,56 @@ fn tokenize(spans: &[Span], env: &HashMap<String, String>, home: &str) -> Vec<To
                 tokens.push(Tok::Redir(r.clone()));
             }
             Span::Text(text) => {
-                let words = shlex::split(text).unwrap_or_default();
-                for w in words {
-                    tokens.push(Tok::Word(expand_word(&w, env, home)));
-                }
+                tokens.extend(split_words(text).into_iter().map(Tok::Word));
             }
         }
     }
     tokens
 }
 
+/// Expand the names and arguments of the commands in `nodes` and the words of
+/// `for` loops. Redirection targets stay as written.
+fn expand_nodes(nodes: &mut [Node], env: &HashMap<String, String>, home: &str) {
+    for node in nodes {
+        match node {
+            Node::Seq(list) => expand_list(list, env, home),
+            Node::If {
+                branches,
+                else_body,
+            } => {
+                for (cond, body) in branches {
+                    expand_nodes(cond, env, home);
+                    expand_nodes(body, env, home);
+                }
+                if let Some(body) = else_body {
+                    expand_nodes(body, env, home);
+                }
+            }
+            Node::For { words, body, .. } => {
+                for word in words.iter_mut() {
+                    *word = expand_word(word, env, home);
+                }
+                expand_nodes(body, env, home);
+            }
+            Node::While { cond, body, .. } => {
+                expand_nodes(cond, env, home);
+                expand_nodes(body, env, home);
+            }
+        }
+    }
+}
+
+fn expand_list(list: &mut CommandList, env: &HashMap<String, String>, home: &str) {
+    for item in &mut list.items {
+        for cmd in &mut item.pipeline.commands {
+            cmd.name = expand_word(&cmd.name, env, home);
+            for arg in &mut cmd.args {
+                *arg = expand_word(arg, env, home);
+            }
+        }
+    }
+}
+
 /// Assemble a flat token stream into a command list.
 fn assemble(tokens: Vec<Tok>) -> CommandList {
     assemble_until(&tokens, &[]).0
@@ -1003,11 +1131,12 @@ impl<'a> ScriptBuilder<'a> {
     }
 }
 
-/// Parse a full command line into an executable AST.
+/// Parse a full command line into an AST with the words expanded against `env`.
 pub fn parse_command_line(input: &str, env: &HashMap<String, String>, home: &str) -> CommandList {
     let spans = scan(input);
-    let tokens = tokenize(&spans, env, home);
-    assemble(tokens)
+    let mut list = assemble(tokenize(&spans));
+    expand_list(&mut list, env, home);
+    list
 }
 
 /// Normalise a multi-line script into a single logical line: handle backslash
@@ -1060,11 +1189,20 @@ fn normalize_newlines(input: &str) -> String {
 }
 
 /// Parse a (possibly multi-line) script into a sequence of nodes, recognising
-/// compound commands (`if`, `for`, `while`, `until`).
+/// compound commands (`if`, `for`, `while`, `until`), with the words expanded
+/// against `env`.
 pub fn parse_script(input: &str, env: &HashMap<String, String>, home: &str) -> Script {
+    let mut script = parse_script_unexpanded(input);
+    expand_nodes(&mut script.nodes, env, home);
+    script
+}
+
+/// `parse_script` with the words kept as written, for `SimpleCommand::expand`
+/// and `expand_word` to expand when they run.
+pub fn parse_script_unexpanded(input: &str) -> Script {
     let normalised = normalize_newlines(input);
     let spans = scan(&normalised);
-    let tokens = tokenize(&spans, env, home);
+    let tokens = tokenize(&spans);
     Script {
         nodes: build_script(&tokens),
     }
@@ -1172,6 +1310,19 @@ pub fn is_incomplete_block(text: &str) -> bool {
     balance != 0 || open_quote
 }
 
+/// If `line` ends in a `&` that sends it to the background, return the line
+/// without it. `&&`, `>&` and a quoted or escaped `&` don't count.
+pub fn strip_background(line: &str) -> Option<&str> {
+    let rest = line.trim_end().strip_suffix('&')?;
+    if rest.trim().is_empty() || rest.ends_with(['&', '>', '<', '|', '\\']) {
+        return None;
+    }
+    if analyze_block(rest).1 {
+        return None;
+    }
+    Some(rest.trim_end())
+}
+
 /// If `word` is a variable assignment (`IDENT=...`), return `(name, value)`.
 pub fn parse_assignment(word: &str) -> Option<(String, String)> {
     let bytes = word.as_bytes();
@@ -1475,6 +1626,33 @@ mod tests {
         assert!(matches!(&r[2].target, RedirTarget::File(f) if f == "in"));
     }
 
+    #[test]
+    fn redirection_target_is_expanded() {
+        let list = parse_command_line("echo key >> ~/.ssh/authorized_keys", &env(), "/root");
+        let cmd = &list.items[0].pipeline.commands[0];
+        assert!(
+            matches!(&cmd.redirects[0].target, RedirTarget::File(f) if f == "~/.ssh/authorized_keys")
+        );
+        let r = &cmd.expand(&env(), "/root").redirects;
+        assert!(r[0].append);
+        assert!(matches!(&r[0].target, RedirTarget::File(f) if f == "/root/.ssh/authorized_keys"));
+    }
+
+    #[test]
+    fn words_are_expanded_when_they_run() {
+        let script = parse_script_unexpanded("X=1; echo $X '$X' \"$X\"\\$X ~ \"~\" > \"$X\"");
+        let Node::Seq(list) = &script.nodes[0] else {
+            panic!("expected Seq, got {:?}", script.nodes[0]);
+        };
+        let cmd = &list.items[1].pipeline.commands[0];
+        assert_eq!(cmd.args, ["$X", "'$X'", "\"$X\"\\$X", "~", "\"~\""]);
+        let mut e = env();
+        e.insert("X".to_string(), "a b".to_string());
+        let cmd = cmd.expand(&e, "/root");
+        assert_eq!(cmd.args, ["a b", "$X", "a b$X", "/root", "~"]);
+        assert!(matches!(&cmd.redirects[0].target, RedirTarget::File(f) if f == "a b"));
+    }
+
     #[test]
     fn devnull_redirection() {
         let list = parse_command_line("cmd 2>/dev/null", &env(), "/home/root");
@@ -1502,6 +1680,20 @@ mod tests {
         assert_eq!(list.items[0].op, AndOp::Then);
     }
 
+    #[test]
+    fn trailing_amp_backgrounds_line() {
+        assert_eq!(strip_background("nohup ./miner &"), Some("nohup ./miner"));
+        assert_eq!(
+            strip_background("sleep 5 & echo done&  "),
+            Some("sleep 5 & echo done")
+        );
+        assert_eq!(strip_background("true &&"), None);
+        assert_eq!(strip_background("echo 'a &"), None);
+        assert_eq!(strip_background("echo a\\&"), None);
+        assert_eq!(strip_background("&"), None);
+        assert_eq!(strip_background("echo done"), None);
+    }
+
     #[test]
     fn subshell_is_unwrapped() {
         let list = parse_command_line("(nproc || grep -c x /f) | head -1", &env(), "/home/root");
@@ -5,20 +5,12 @@ use reqwest::{Certificate, Method, StatusCode};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex as StdMutex};
-use tokio::sync::{Notify, RwLock};
+use crate::single_flight::SingleFlight;
+use std::sync::Arc;
+use tokio::sync::{RwLock, Semaphore};
 use webpki_root_certs::TLS_SERVER_ROOT_CERTS;
 
 const DEFAULT_CACHE_TTL_HOURS: u8 = 24;
-const IN_FLIGHT_WAIT_TIMEOUT_MS: u64 = 500;
-
-/// Tracks an in-flight API lookup to prevent duplicate requests for the same IP
-struct InFlightLookup {
-    /// None = pending, Some(None) = failed, Some(Some(response)) = success
-    result: StdMutex<Option<Option<CheckResponse>>>,
-    /// Notifies waiters when the lookup completes
-    notify: Notify,
-}
 
 #[derive(Debug, Clone)]
 pub struct RateLimitInfo {
@@ -187,8 +179,8 @@ pub struct Client {
     pub memory_cache: Arc<RwLock<HashMap<String, CachedResult>>>,
     pool: PgPool,
     pub cache_ttl_hours: u8,
-    /// Tracks in-flight API lookups to prevent duplicate requests
-    in_flight: StdMutex<HashMap<String, Arc<InFlightLookup>>>,
+    /// Coalesces concurrent API lookups of the same IP into one request
+    in_flight: SingleFlight<CheckResponse>,
 }
 
 impl Client {
@@ -211,7 +203,7 @@ impl Client {
             memory_cache: Arc::new(RwLock::new(HashMap::new())),
             pool,
             cache_ttl_hours: cache_ttl_hours.unwrap_or(DEFAULT_CACHE_TTL_HOURS),
-            in_flight: StdMutex::new(HashMap::new()),
+            in_flight: SingleFlight::new(),
         }
     }
 
@@ -219,6 +211,27 @@ impl Client {
         &self,
         ip_address: &str,
     ) -> Result<CheckResponse, AbuseIpError> {
+        if let Some(response) = self.check_ip_cached(ip_address).await {
+            return Ok(response);
+        }
+
+        // Connections from the same IP arriving together share one API call
+        match self
+            .in_flight
+            .run(ip_address, self.lookup_and_cache(ip_address))
+            .await
+        {
+            Ok(response) => Ok(response),
+            Err(Some(e)) => Err(e),
+            Err(None) => {
+                log::debug!("In-flight lookup failed for IP: {}", ip_address);
+                Err(AbuseIpError::Other("In-flight lookup failed".to_string()))
+            }
+        }
+    }
+
+    /// The memory or database cache entry for `ip_address`, without asking the API
+    pub async fn check_ip_cached(&self, ip_address: &str) -> Option<CheckResponse> {
         log::trace!("Checking IP address: {} in cache", ip_address);
 
         // First check memory cache
@@ -232,7 +245,7 @@ impl Client {
             let age = Utc::now() - cached.cached_at;
             if age < Duration::hours(self.cache_ttl_hours as i64) {
                 log::debug!("AbuseIPDB memory cache hit for IP: {}", ip_address);
-                return Ok(cached.response.clone());
+                return Some(cached.response.clone());
             }
         }
         drop(cache); // Release read lock
@@ -267,154 +280,99 @@ impl Client {
                         },
                     );
 
-                    return Ok(response);
+                    return Some(response);
                 }
                 Err(e) => {
                     log::error!("Failed to deserialize cached AbuseIPDB data for {}: {}", ip_address, e);
-                    // Treat as cache miss, fall through to API call
+                    // Treat as cache miss
                 }
             },
             Ok(None) => {
-                // No cache entry or expired - continue to API call
+                // No cache entry or expired
                 log::trace!("No database cache entry found for IP: {}", ip_address);
             }
             Err(e) => {
                 log::error!("Failed to query AbuseIPDB cache: {}", e);
-                // Continue to API call on database error
             }
         }
+        None
+    }
 
-        // Check if there's already an in-flight lookup for this IP
-        let existing_lookup = {
-            let in_flight = self.in_flight.lock().unwrap();
-            in_flight.get(ip_address).cloned()
-        };
-
-        if let Some(lookup) = existing_lookup {
-            log::debug!(
-                "Found in-flight lookup for IP: {}, waiting up to {}ms",
-                ip_address,
-                IN_FLIGHT_WAIT_TIMEOUT_MS
-            );
-
-            // Wait for the in-flight lookup to complete (with timeout)
-            let wait_result = tokio::time::timeout(
-                std::time::Duration::from_millis(IN_FLIGHT_WAIT_TIMEOUT_MS),
-                lookup.notify.notified(),
-            )
-            .await;
-
-            if wait_result.is_err() {
+    /// `check_ip_with_cache` for a login waiting on the answer. The API is only
+    /// asked with one of `permits`, and when none is free right now the caches
+    /// answer alone (`Ok(None)` on a miss): waiting is left to the background
+    /// lookups, so a burst of connections can't stall authentication
+    pub async fn check_ip_for_auth(
+        &self,
+        ip_address: &str,
+        permits: Option<&Semaphore>,
+    ) -> Result<Option<CheckResponse>, AbuseIpError> {
+        let _permit = match permits.map(Semaphore::try_acquire) {
+            Some(Ok(permit)) => Some(permit),
+            Some(Err(_)) => {
                 log::debug!(
-                    "Timeout waiting for in-flight lookup for IP: {}",
+                    "No enrichment permit free, answering AbuseIPDB lookup of {} from the cache",
                     ip_address
                 );
-                return Err(AbuseIpError::Other(
-                    "Timeout waiting for in-flight lookup".to_string(),
-                ));
+                return Ok(self.check_ip_cached(ip_address).await);
             }
+            None => None,
+        };
+        self.check_ip_with_cache(ip_address).await.map(Some)
+    }
 
-            // Check the result
-            let result = lookup.result.lock().unwrap();
-            return match &*result {
-                Some(Some(response)) => {
-                    log::debug!("Got result from in-flight lookup for IP: {}", ip_address);
-                    Ok(response.clone())
-                }
-                Some(None) => {
-                    log::debug!("In-flight lookup failed for IP: {}", ip_address);
-                    Err(AbuseIpError::Other("In-flight lookup failed".to_string()))
-                }
-                None => {
-                    // This shouldn't happen - notified but no result
-                    log::warn!(
-                        "In-flight lookup notified but no result for IP: {}",
-                        ip_address
-                    );
-                    Err(AbuseIpError::Other(
-                        "In-flight lookup state error".to_string(),
-                    ))
-                }
-            };
-        }
-
-        // No in-flight lookup, register ourselves and make the API call
-        let lookup = Arc::new(InFlightLookup {
-            result: StdMutex::new(None),
-            notify: Notify::new(),
-        });
-
+    /// Query the API and store the answer in the memory and database caches
+    async fn lookup_and_cache(&self, ip_address: &str) -> Result<CheckResponse, AbuseIpError> {
+        // A lookup that finished while we were checking the database has the answer
+        if let Some(cached) = self.memory_cache.read().await.get(ip_address)
+            && Utc::now() - cached.cached_at < Duration::hours(self.cache_ttl_hours as i64)
         {
-            let mut in_flight = self.in_flight.lock().unwrap();
-            in_flight.insert(ip_address.to_string(), lookup.clone());
+            return Ok(cached.response.clone());
         }
 
         log::debug!(
             "AbuseIPDB cache miss for IP: {}, making API call",
             ip_address
         );
-        let api_result = self.check_ip_api(ip_address).await;
-
-        // Store result and notify waiters
-        let response = match &api_result {
-            Ok(response) => {
-                log::trace!("API call result: {:?}", response);
-
-                // Update memory cache
-                log::trace!("Updating memory cache");
-                let mut cache = self.memory_cache.write().await;
-                let now = Utc::now();
-                cache.insert(
-                    ip_address.to_string(),
-                    CachedResult {
-                        response: response.clone(),
-                        cached_at: now,
-                    },
-                );
-                drop(cache);
-                log::trace!("Memory cache updated");
-
-                // Store in database cache
-                log::trace!("Updating database cache");
-                if let Err(e) = record_abuse_ip_check(
-                    &self.pool,
-                    ip_address.to_string(),
-                    now,
-                    response.data.abuse_confidence_score,
-                    response.data.country_code.clone(),
-                    response.data.is_tor,
-                    response.data.is_allowlisted,
-                    response.data.total_reports,
-                    serde_json::to_string(&response.data).unwrap_or_default(),
-                )
-                .await
-                {
-                    log::error!("Failed to cache AbuseIPDB result in database: {}", e);
-                }
-                log::trace!("Database cache updated");
-
-                Some(response.clone())
-            }
-            Err(e) => {
-                log::debug!("API call failed for IP {}: {}", ip_address, e);
-                None
-            }
-        };
+        let response = self.check_ip_api(ip_address).await.inspect_err(|e| {
+            log::debug!("API call failed for IP {}: {}", ip_address, e);
+        })?;
+        log::trace!("API call result: {:?}", response);
 
-        // Update in-flight state and notify waiters
-        {
-            let mut result = lookup.result.lock().unwrap();
-            *result = Some(response);
-        }
-        lookup.notify.notify_waiters();
-
-        // Clean up in-flight entry
+        // Update memory cache
+        log::trace!("Updating memory cache");
+        let mut cache = self.memory_cache.write().await;
+        let now = Utc::now();
+        cache.insert(
+            ip_address.to_string(),
+            CachedResult {
+                response: response.clone(),
+                cached_at: now,
+            },
+        );
+        drop(cache);
+        log::trace!("Memory cache updated");
+
+        // Store in database cache
+        log::trace!("Updating database cache");
+        if let Err(e) = record_abuse_ip_check(
+            &self.pool,
+            ip_address.to_string(),
+            now,
+            response.data.abuse_confidence_score,
+            response.data.country_code.clone(),
+            response.data.is_tor,
+            response.data.is_allowlisted,
+            response.data.total_reports,
+            serde_json::to_string(&response.data).unwrap_or_default(),
+        )
+        .await
         {
-            let mut in_flight = self.in_flight.lock().unwrap();
-            in_flight.remove(ip_address);
+            log::error!("Failed to cache AbuseIPDB result in database: {}", e);
         }
+        log::trace!("Database cache updated");
 
-        api_result
+        Ok(response)
     }
 
     async fn check_ip_api(&self, ip_address: &str) -> Result<CheckResponse, AbuseIpError> {
@@ -535,3 +493,74 @@ impl Client {
         res.json().await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::postgres::PgPoolOptions;
+
+    /// A client whose database can't be reached, so only the memory cache answers
+    fn offline_client() -> Client {
+        let pool = PgPoolOptions::new()
+            .acquire_timeout(std::time::Duration::from_millis(200))
+            .connect_lazy("postgres://honeypot@127.0.0.1:1/honeypot")
+            .unwrap();
+        Client::new("key".to_string(), pool, None)
+    }
+
+    fn response(ip_address: &str) -> CheckResponse {
+        serde_json::from_value(serde_json::json!({
+            "data": {
+                "abuseConfidenceScore": 100,
+                "countryCode": "NL",
+                "domain": null,
+                "hostnames": [],
+                "ipAddress": ip_address,
+                "ipVersion": 4,
+                "isPublic": true,
+                "isTor": false,
+                "isWhitelisted": false,
+                "isp": null,
+                "lastReportedAt": null,
+                "numDistinctUsers": 3,
+                "totalReports": 7,
+                "usageType": null
+            }
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn auth_lookup_does_not_wait_for_a_permit() {
+        let client = offline_client();
+        client.memory_cache.write().await.insert(
+            "203.0.113.7".to_string(),
+            CachedResult {
+                response: response("203.0.113.7"),
+                cached_at: Utc::now(),
+            },
+        );
+        // Background lookups hold every permit
+        let permits = Semaphore::new(1);
+        let _held = permits.acquire().await.unwrap();
+
+        let wait = std::time::Duration::from_secs(5);
+        let cached = tokio::time::timeout(
+            wait,
+            client.check_ip_for_auth("203.0.113.7", Some(&permits)),
+        )
+        .await
+        .expect("waited for a permit");
+        assert_eq!(cached.unwrap(), Some(response("203.0.113.7")));
+
+        // A miss doesn't go to the API without a permit
+        let missed = tokio::time::timeout(
+            wait,
+            client.check_ip_for_auth("198.51.100.9", Some(&permits)),
+        )
+        .await
+        .expect("waited for a permit");
+        assert!(matches!(missed, Ok(None)));
+        assert_eq!(permits.available_permits(), 0);
+    }
+}
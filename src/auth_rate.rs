@@ -0,0 +1,62 @@
+//! `--auth-success-rate`: accept a random fraction of authentication attempts, so
+//! post-auth behaviour can be studied across many attackers without picking the
+//! credentials that get in.
+//!
+//! One generator is shared by every listener and connection. With
+//! `--auth-success-seed` it draws the same sequence of outcomes on every run; which
+//! attempt gets which draw still depends on the order the attempts arrive in.
+
+use rand::rngs::StdRng;
+use rand::{RngExt, SeedableRng, rng};
+use std::sync::Mutex as StdMutex;
+
+#[derive(Debug)]
+pub struct AuthSuccessRate {
+    rate: f64,
+    rng: StdMutex<StdRng>,
+}
+
+impl AuthSuccessRate {
+    /// `rate` between 0.0 and 1.0, checked when the configuration was loaded
+    pub fn new(rate: f64, seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(|| rng().random());
+        Self {
+            rate,
+            rng: StdMutex::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Whether the next attempt is accepted
+    pub fn draw(&self) -> bool {
+        self.rng.lock().unwrap().random_bool(self.rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draws(rate: &AuthSuccessRate, count: usize) -> Vec<bool> {
+        (0..count).map(|_| rate.draw()).collect()
+    }
+
+    #[test]
+    fn seeded_rates_repeat() {
+        let first = draws(&AuthSuccessRate::new(0.3, Some(7)), 200);
+        assert_eq!(first, draws(&AuthSuccessRate::new(0.3, Some(7)), 200));
+        assert_ne!(first, draws(&AuthSuccessRate::new(0.3, Some(8)), 200));
+
+        let accepted = first.iter().filter(|&&accepted| accepted).count();
+        assert!((30..90).contains(&accepted), "{} of 200 accepted", accepted);
+    }
+
+    #[test]
+    fn zero_and_one_are_absolute() {
+        assert!(!draws(&AuthSuccessRate::new(0.0, None), 100).contains(&true));
+        assert!(!draws(&AuthSuccessRate::new(1.0, None), 100).contains(&false));
+    }
+}
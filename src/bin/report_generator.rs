@@ -46,6 +46,24 @@ pub enum ReportMode {
         #[arg(env = "PASSWORD")]
         password: String,
     },
+    /// Group IPs that ran the same command sequence or tried the same credential list
+    Campaigns {
+        /// Only report campaigns seen from at least this many IPs
+        #[arg(long, default_value_t = 2, env = "MIN_IPS")]
+        min_ips: usize,
+    },
+    /// List IPs that sent malformed packets or impossible key exchanges
+    Anomalies {
+        /// Show at most this many IPs
+        #[arg(long, default_value_t = 50, env = "ANOMALY_LIMIT")]
+        limit: i64,
+    },
+    /// Export a session recorded with --record-raw-streams as an asciinema v2 cast (--format is ignored)
+    Cast {
+        /// Auth ID of the session to export
+        #[arg(env = "AUTH_ID")]
+        auth_id: String,
+    },
 }
 
 #[derive(Parser, Debug)]
@@ -55,7 +73,7 @@ pub enum ReportMode {
     long_about = "Generate reports for SSH honeypot data based on IP addresses or passwords"
 )]
 struct Args {
-    /// Report mode (ip or password)
+    /// Report mode (ip, password, campaigns, anomalies or cast)
     #[command(subcommand)]
     mode: ReportMode,
 
@@ -87,6 +105,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .generate_password_report(&password, &args.args.format)
                 .await?
         }
+        ReportMode::Campaigns { min_ips } => {
+            generator
+                .generate_campaign_report(min_ips, &args.args.format)
+                .await?
+        }
+        ReportMode::Anomalies { limit } => {
+            generator
+                .generate_anomaly_report(limit, &args.args.format)
+                .await?
+        }
+        ReportMode::Cast { auth_id } => generator.export_session_cast(&auth_id).await?,
     };
 
     // Output report
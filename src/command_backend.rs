@@ -0,0 +1,187 @@
+//! Real answers for some read-only commands, compiled in with the `command-backend`
+//! cargo feature.
+//!
+//! `--command-backend docker://<image>` runs every command line that is a plain
+//! invocation of one of `--command-backend-commands` (no pipes, redirections,
+//! expansions or quotes) in a fresh container of that image and shows its output
+//! instead of the simulation's. The container has no network, a read-only root,
+//! an unprivileged user and small memory, CPU and process limits, and is removed
+//! afterwards; one that runs longer than [`TIMEOUT`] is killed. Everything else,
+//! and anything the container can't answer, stays simulated. The output comes from
+//! a real kernel and userland so it can contradict the persona; only allow
+//! commands whose answers fit it.
+
+use std::time::Duration;
+
+/// Commands answered by the container unless --command-backend-commands says otherwise
+pub const DEFAULT_COMMANDS: &[&str] = &[
+    "uname", "nproc", "lscpu", "free", "uptime", "df", "lsblk", "getconf",
+];
+
+/// How long a container may run before it is killed and the command simulated
+#[cfg_attr(not(feature = "command-backend"), allow(dead_code))]
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// Output kept of one command, the rest is dropped
+#[cfg_attr(not(feature = "command-backend"), allow(dead_code))]
+const MAX_OUTPUT: usize = 64 * 1024;
+
+/// Characters that make a line more than a plain command with arguments
+const SHELL_SYNTAX: &str = "|&;<>()$`\\\"'*?[]{}~#=!%";
+
+#[derive(Debug)]
+pub struct CommandBackend {
+    image: String,
+    commands: Vec<String>,
+}
+
+impl CommandBackend {
+    /// Parse `docker://<image>`. Fails without the `command-backend` feature
+    pub fn parse(spec: &str, commands: Vec<String>) -> Result<Self, String> {
+        let image = spec
+            .strip_prefix("docker://")
+            .filter(|image| !image.is_empty() && !image.starts_with('-'))
+            .ok_or_else(|| format!("--command-backend {}: expected docker://<image>", spec))?;
+        if !cfg!(feature = "command-backend") {
+            return Err(format!(
+                "--command-backend {} needs container support, rebuild with `cargo build --release --features command-backend`",
+                spec
+            ));
+        }
+        Ok(Self {
+            image: image.to_string(),
+            commands,
+        })
+    }
+
+    pub fn image(&self) -> &str {
+        &self.image
+    }
+
+    pub fn commands(&self) -> &[String] {
+        &self.commands
+    }
+
+    /// The arguments to run in the container, None unless `line` is a plain
+    /// invocation of an allowed command
+    pub fn accepts(&self, line: &str) -> Option<Vec<String>> {
+        if line
+            .chars()
+            .any(|c| SHELL_SYNTAX.contains(c) || c.is_control())
+        {
+            return None;
+        }
+        let argv: Vec<String> = line.split_whitespace().map(str::to_string).collect();
+        let name = argv.first()?;
+        self.commands.contains(name).then_some(argv)
+    }
+
+    /// Run `argv` in a new container. Err when docker or the image couldn't run it,
+    /// the command is then simulated
+    #[cfg(feature = "command-backend")]
+    pub async fn run(&self, argv: &[String], hostname: &str) -> Result<String, String> {
+        use rand::{RngExt, rng};
+        use std::process::Stdio;
+
+        let name = format!("ssh-honeypot-{:016x}", rng().random::<u64>());
+        let mut command = tokio::process::Command::new("docker");
+        command
+            .args(["run", "--rm", "--name", &name, "--hostname", hostname])
+            .args([
+                "--network",
+                "none",
+                "--read-only",
+                "--tmpfs",
+                "/tmp:size=8m,noexec",
+            ])
+            .args(["--memory", "64m", "--memory-swap", "64m", "--cpus", "0.5"])
+            .args(["--pids-limit", "32", "--cap-drop", "ALL"])
+            .args([
+                "--security-opt",
+                "no-new-privileges",
+                "--user",
+                "65534:65534",
+            ])
+            .args(["--log-driver", "none", &self.image])
+            .args(argv)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        let child = command
+            .spawn()
+            .map_err(|err| format!("cannot start docker: {}", err))?;
+
+        let output = match tokio::time::timeout(TIMEOUT, child.wait_with_output()).await {
+            Ok(Ok(output)) => output,
+            Ok(Err(err)) => return Err(format!("docker failed: {}", err)),
+            Err(_) => {
+                // Killing the client leaves the container running
+                let _ = tokio::process::Command::new("docker")
+                    .args(["kill", &name])
+                    .output()
+                    .await;
+                return Err(format!("no answer after {}s", TIMEOUT.as_secs()));
+            }
+        };
+        // 125 is docker's own failure, 126 and 127 a command the image can't run
+        if let Some(code @ 125..=127) = output.status.code() {
+            return Err(format!(
+                "docker exited with {}: {}",
+                code,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let mut bytes = output.stdout;
+        bytes.extend_from_slice(&output.stderr);
+        bytes.truncate(MAX_OUTPUT);
+        Ok(String::from_utf8_lossy(&bytes)
+            .replace("\r\n", "\n")
+            .replace('\n', "\r\n"))
+    }
+
+    #[cfg(not(feature = "command-backend"))]
+    pub async fn run(&self, _argv: &[String], _hostname: &str) -> Result<String, String> {
+        Err("built without the command-backend feature".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backend() -> CommandBackend {
+        CommandBackend {
+            image: "ubuntu:20.04".to_string(),
+            commands: DEFAULT_COMMANDS.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn only_plain_allowed_commands_are_accepted() {
+        let backend = backend();
+        assert_eq!(
+            backend.accepts("uname  -a"),
+            Some(vec!["uname".to_string(), "-a".to_string()])
+        );
+        assert_eq!(backend.accepts("nproc"), Some(vec!["nproc".to_string()]));
+        assert_eq!(backend.accepts("cat /etc/shadow"), None);
+        assert_eq!(backend.accepts("/bin/uname -a"), None);
+        assert_eq!(backend.accepts("uname -a; id"), None);
+        assert_eq!(backend.accepts("uname $(id)"), None);
+        assert_eq!(backend.accepts("df > /tmp/x"), None);
+        assert_eq!(backend.accepts("df 'a b'"), None);
+        assert_eq!(backend.accepts("   "), None);
+    }
+
+    #[test]
+    fn spec_needs_docker_scheme() {
+        assert!(CommandBackend::parse("ubuntu:20.04", Vec::new()).is_err());
+        assert!(CommandBackend::parse("docker://", Vec::new()).is_err());
+        assert!(CommandBackend::parse("docker://--privileged", Vec::new()).is_err());
+        assert_eq!(
+            CommandBackend::parse("docker://ubuntu:20.04", Vec::new()).is_ok(),
+            cfg!(feature = "command-backend")
+        );
+    }
+}
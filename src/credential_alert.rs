@@ -0,0 +1,226 @@
+//! `--credential-alert-ips`: warn when one username/password pair is tried from
+//! many addresses at once, how a new credential stuffing campaign shows up.
+//!
+//! Every password attempt is counted against its pair with the address it came
+//! from. When `--credential-alert-ips` distinct addresses tried the same pair
+//! within `--credential-alert-window-secs`, a warning is logged and, with
+//! `--credential-alert-webhook`, POSTed as JSON. A pair alerts once per campaign:
+//! it only alerts again after a whole window passed without anyone trying it.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex as StdMutex;
+use std::time::{Duration, Instant};
+
+/// Pairs followed at once. A brute force with a fresh password on every attempt
+/// would otherwise grow the table without bound, new pairs are skipped until the
+/// next sweep frees room
+const MAX_TRACKED_PAIRS: usize = 100_000;
+
+/// Addresses that recently tried one pair
+#[derive(Debug, Default)]
+struct Sightings {
+    /// Address to when it last tried the pair
+    ips: HashMap<IpAddr, Instant>,
+    /// Alerted since the pair was last quiet for a window
+    alerted: bool,
+}
+
+impl Sightings {
+    fn forget_before(&mut self, cutoff: Option<Instant>) {
+        if let Some(cutoff) = cutoff {
+            self.ips.retain(|_, seen| *seen >= cutoff);
+        }
+        if self.ips.is_empty() {
+            self.alerted = false;
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Pairs {
+    sightings: HashMap<(String, String), Sightings>,
+    last_sweep: Instant,
+}
+
+/// Sliding window of the addresses behind each credential pair, shared by every
+/// listener and connection
+#[derive(Debug)]
+pub struct CredentialAlerts {
+    threshold: usize,
+    window: Duration,
+    pairs: StdMutex<Pairs>,
+    /// Client and URL alerts are POSTed to
+    webhook: Option<(reqwest::Client, String)>,
+}
+
+impl CredentialAlerts {
+    /// Alert when `threshold` addresses try a pair within `window`
+    pub fn new(threshold: usize, window: Duration) -> Self {
+        Self {
+            threshold,
+            window,
+            pairs: StdMutex::new(Pairs {
+                sightings: HashMap::new(),
+                last_sweep: Instant::now(),
+            }),
+            webhook: None,
+        }
+    }
+
+    /// POST every alert to `url` from now on
+    pub fn with_webhook(mut self, url: String) -> Self {
+        let certs = webpki_root_certs::TLS_SERVER_ROOT_CERTS
+            .iter()
+            .map(|cert| reqwest::Certificate::from_der(cert).unwrap())
+            .collect::<Vec<_>>();
+        let client = reqwest::Client::builder()
+            .min_tls_version(reqwest::tls::Version::TLS_1_2)
+            .tls_certs_only(certs)
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap();
+        self.webhook = Some((client, url));
+        self
+    }
+
+    /// Count a password attempt, alerting when it completes a campaign
+    pub fn observe(&self, username: &str, password: &str, ip: IpAddr) {
+        if let Some(ips) = self.sighting(username, password, ip, Instant::now()) {
+            self.alert(username, password, &ips);
+        }
+    }
+
+    /// Record `ip` trying the pair at `now`. The addresses behind the pair when this
+    /// is the attempt that crosses the threshold, None otherwise
+    fn sighting(
+        &self,
+        username: &str,
+        password: &str,
+        ip: IpAddr,
+        now: Instant,
+    ) -> Option<Vec<IpAddr>> {
+        let cutoff = now.checked_sub(self.window);
+        let mut pairs = self.pairs.lock().unwrap();
+        if now.duration_since(pairs.last_sweep) >= self.window {
+            pairs.sightings.retain(|_, sightings| {
+                sightings.forget_before(cutoff);
+                !sightings.ips.is_empty()
+            });
+            pairs.last_sweep = now;
+        }
+
+        let key = (username.to_string(), password.to_string());
+        if !pairs.sightings.contains_key(&key) && pairs.sightings.len() >= MAX_TRACKED_PAIRS {
+            return None;
+        }
+        let sightings = pairs.sightings.entry(key).or_default();
+        sightings.forget_before(cutoff);
+        sightings.ips.insert(ip, now);
+        if sightings.alerted || sightings.ips.len() < self.threshold {
+            return None;
+        }
+        sightings.alerted = true;
+        let mut ips: Vec<IpAddr> = sightings.ips.keys().copied().collect();
+        ips.sort();
+        Some(ips)
+    }
+
+    /// Log the alert and send it to the webhook in the background, if there is one.
+    /// Webhook failures are only logged
+    fn alert(&self, username: &str, password: &str, ips: &[IpAddr]) {
+        let addresses: Vec<String> = ips.iter().map(ToString::to_string).collect();
+        log::warn!(
+            "Credential campaign: username={:?} password={:?} ips={} window_secs={} addresses={}",
+            username,
+            password,
+            ips.len(),
+            self.window.as_secs(),
+            addresses.join(",")
+        );
+
+        let Some((client, url)) = self.webhook.clone() else {
+            return;
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            log::warn!(
+                "No runtime to send the credential alert for {} on",
+                username
+            );
+            return;
+        };
+        let body = serde_json::json!({
+            "username": username,
+            "password": password,
+            "window_secs": self.window.as_secs(),
+            "ips": addresses,
+        });
+        runtime.spawn(async move {
+            match client.post(&url).json(&body).send().await {
+                Ok(response) if response.status().is_success() => {
+                    log::debug!("Sent credential alert to {}", url)
+                }
+                Ok(response) => log::warn!(
+                    "Credential alert webhook {} answered {}",
+                    url,
+                    response.status()
+                ),
+                Err(err) => log::warn!("Failed to send credential alert to {}: {}", url, err),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::from([192, 0, 2, last])
+    }
+
+    #[test]
+    fn alerts_once_per_campaign() {
+        let alerts = CredentialAlerts::new(3, Duration::from_secs(60));
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert_eq!(alerts.sighting("root", "admin", ip(1), at(0)), None);
+        assert_eq!(alerts.sighting("root", "admin", ip(1), at(1)), None);
+        assert_eq!(alerts.sighting("root", "other", ip(2), at(2)), None);
+        assert_eq!(alerts.sighting("root", "admin", ip(2), at(3)), None);
+        assert_eq!(
+            alerts.sighting("root", "admin", ip(3), at(4)),
+            Some(vec![ip(1), ip(2), ip(3)])
+        );
+        // More addresses within the campaign don't alert again
+        assert_eq!(alerts.sighting("root", "admin", ip(4), at(30)), None);
+        assert_eq!(alerts.sighting("root", "admin", ip(5), at(80)), None);
+
+        // Quiet for a whole window, the next wave is a new campaign
+        for (last, secs) in [(6, 200), (7, 201)] {
+            assert_eq!(alerts.sighting("root", "admin", ip(last), at(secs)), None);
+        }
+        assert_eq!(
+            alerts.sighting("root", "admin", ip(8), at(202)),
+            Some(vec![ip(6), ip(7), ip(8)])
+        );
+    }
+
+    #[test]
+    fn addresses_fall_out_of_the_window() {
+        let alerts = CredentialAlerts::new(2, Duration::from_secs(60));
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert_eq!(alerts.sighting("admin", "123456", ip(1), at(0)), None);
+        assert_eq!(alerts.sighting("admin", "123456", ip(2), at(61)), None);
+        assert_eq!(
+            alerts.sighting("admin", "123456", ip(3), at(62)),
+            Some(vec![ip(2), ip(3)])
+        );
+        // The sweep drops pairs nobody tried within the window
+        assert_eq!(alerts.sighting("admin", "letmein", ip(1), at(200)), None);
+        assert_eq!(alerts.pairs.lock().unwrap().sightings.len(), 1);
+    }
+}
@@ -0,0 +1,143 @@
+//! Answers to `exec` requests from file transfer and version control tools.
+//!
+//! Tools like rsync and git don't ask for a shell, they exec their server side
+//! and talk a protocol over the channel. Anything but that protocol tells them
+//! (and whoever scripted them) they reached a honeypot, so these commands get
+//! what a real host would send instead of the taunt. Operators can add or
+//! override answers by command prefix with `exec_responses` in the config file.
+//! Every answered probe is recorded in `exec_probes` with the tool it was for.
+
+use std::collections::BTreeMap;
+
+/// rsync 3.1.3, what Ubuntu 20.04 ships, speaks protocol 31
+const RSYNC_PROTOCOL: u32 = 31;
+
+/// What to send back on the exec channel
+#[derive(Debug, PartialEq)]
+pub struct ExecAnswer {
+    /// Recorded as `exec_probes.tool`, the configured prefix for configured answers
+    pub tool: String,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// None leaves the channel open with whatever the client sends discarded,
+    /// for protocols where the client speaks next
+    pub exit_status: Option<u32>,
+}
+
+/// Built-in answers plus the ones configured by command prefix
+#[derive(Debug, Default)]
+pub struct ExecResponses {
+    /// `(prefix, stdout)`, longest prefix first
+    configured: Vec<(String, String)>,
+}
+
+impl ExecResponses {
+    pub fn new(configured: BTreeMap<String, String>) -> Self {
+        let mut configured: Vec<(String, String)> = configured.into_iter().collect();
+        configured.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+        Self { configured }
+    }
+
+    /// The answer to an exec'd `command`, None when it gets the usual treatment
+    pub fn answer(&self, command: &str) -> Option<ExecAnswer> {
+        let command = command.trim();
+        if let Some((prefix, stdout)) = self
+            .configured
+            .iter()
+            .find(|(prefix, _)| command.starts_with(prefix.as_str()))
+        {
+            return Some(ExecAnswer {
+                tool: prefix.clone(),
+                stdout: stdout.as_bytes().to_vec(),
+                stderr: Vec::new(),
+                exit_status: Some(0),
+            });
+        }
+
+        let mut words = command.split_whitespace();
+        let program = words.next()?.rsplit('/').next().unwrap_or_default();
+        let args: Vec<&str> = words.collect();
+        match program {
+            // The server side of rsync starts by sending its protocol version,
+            // then waits for the client's
+            "rsync" if args.contains(&"--server") => Some(ExecAnswer {
+                tool: "rsync".to_string(),
+                stdout: RSYNC_PROTOCOL.to_le_bytes().to_vec(),
+                stderr: Vec::new(),
+                exit_status: None,
+            }),
+            "git-upload-pack" | "git-receive-pack" => Some(missing_repository(program, &args)),
+            "git" if matches!(args.first(), Some(&("upload-pack" | "receive-pack"))) => {
+                Some(missing_repository(&format!("git-{}", args[0]), &args[1..]))
+            }
+            // Only sshd knows internal-sftp, the login shell gets asked to run it
+            "internal-sftp" => Some(ExecAnswer {
+                tool: "internal-sftp".to_string(),
+                stdout: Vec::new(),
+                stderr: b"bash: internal-sftp: command not found\n".to_vec(),
+                exit_status: Some(127),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// What git says when the repository a clone or push names isn't there
+fn missing_repository(tool: &str, args: &[&str]) -> ExecAnswer {
+    let path = args.join(" ");
+    let path = path.trim_matches(|c| c == '\'' || c == '"');
+    ExecAnswer {
+        tool: tool.to_string(),
+        stdout: Vec::new(),
+        stderr: format!("fatal: '{}' does not appear to be a git repository\n", path).into_bytes(),
+        exit_status: Some(128),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tools_get_their_protocol() {
+        let responses = ExecResponses::default();
+        let rsync = responses
+            .answer("rsync --server --sender -vlogDtpre.iLsfxC . /var/www")
+            .unwrap();
+        assert_eq!(rsync.stdout, vec![31, 0, 0, 0]);
+        assert_eq!(rsync.exit_status, None);
+
+        for command in [
+            "git-upload-pack '/srv/app.git'",
+            "git upload-pack '/srv/app.git'",
+        ] {
+            let git = responses.answer(command).unwrap();
+            assert_eq!(git.tool, "git-upload-pack");
+            assert_eq!(
+                git.stderr,
+                b"fatal: '/srv/app.git' does not appear to be a git repository\n"
+            );
+            assert_eq!(git.exit_status, Some(128));
+        }
+
+        assert_eq!(
+            responses.answer("internal-sftp").unwrap().exit_status,
+            Some(127)
+        );
+        assert_eq!(responses.answer("rsync --version"), None);
+        assert_eq!(responses.answer("uname -a"), None);
+    }
+
+    #[test]
+    fn longest_configured_prefix_wins() {
+        let responses = ExecResponses::new(BTreeMap::from([
+            ("rsync".to_string(), "rsync  version 3.2.7\n".to_string()),
+            ("rsync --server".to_string(), "@RSYNCD: 31.0\n".to_string()),
+        ]));
+        let answer = responses.answer("rsync --server -e.LsfxC . /tmp").unwrap();
+        assert_eq!(answer.tool, "rsync --server");
+        assert_eq!(answer.stdout, b"@RSYNCD: 31.0\n");
+        assert_eq!(answer.exit_status, Some(0));
+        assert_eq!(responses.answer("rsync --version").unwrap().tool, "rsync");
+    }
+}
@@ -0,0 +1,53 @@
+//! `ssh-honeypot export-fs`: write the fake filesystem a listener's sessions start
+//! from as a gzipped tarball, in the same form `base_tar_gz_path` loads.
+//!
+//! The tarball is built exactly as serving would build it: the persona's base
+//! tarball or embedded layout with the release, host identity, account and canary
+//! files written over it. Without an identity seed the machine-id and MACs are new
+//! on every run, so set one to compare exports.
+
+use shell::filesystem::fs2::FileSystem;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ExportFsArgs {
+    /// File to write the tarball to, - for stdout
+    #[arg(short = 'o', long = "output", default_value = "-")]
+    pub output: PathBuf,
+
+    /// Listener whose persona decides the filesystem. Defaults to the first configured interface
+    #[arg(long = "interface")]
+    pub interface: Option<SocketAddr>,
+}
+
+/// Write `fs` where `args.output` says. Returns the process exit code
+pub fn run(args: &ExportFsArgs, fs: &FileSystem) -> i32 {
+    let written = if args.output.as_os_str() == "-" {
+        let mut stdout = io::stdout().lock();
+        fs.export_targz(&mut stdout).and_then(|_| stdout.flush())
+    } else {
+        File::create(&args.output).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            fs.export_targz(&mut writer)?;
+            writer.flush()
+        })
+    };
+
+    match written {
+        Ok(()) => {
+            log::info!("Filesystem exported to {}", args.output.display());
+            0
+        }
+        Err(e) => {
+            log::error!(
+                "Failed to export the filesystem to {}: {}",
+                args.output.display(),
+                e
+            );
+            1
+        }
+    }
+}
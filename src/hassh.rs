@@ -0,0 +1,278 @@
+//! Passive capture of the client's SSH_MSG_KEXINIT for HASSH fingerprinting.
+//!
+//! russh negotiates algorithms internally and never hands the client's offered
+//! lists to the `Handler`, so the raw TCP stream is wrapped in a [`KexInitSniffer`]
+//! before it is given to russh. The sniffer copies the first bytes the client
+//! sends, parses the identification string and the (still unencrypted) KEXINIT
+//! packet, and reports the result once through a oneshot channel.
+//!
+//! HASSH is the MD5 of `kex;ciphers;macs;compression` as offered by the client,
+//! see <https://github.com/salesforce/hassh>.
+
+use md5::{Digest, Md5};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::oneshot;
+
+/// SSH_MSG_KEXINIT message number
+const MSG_KEXINIT: u8 = 20;
+/// Stop looking once the client sent this much without a parsable KEXINIT
+const MAX_SNIFF_BYTES: usize = 64 * 1024;
+/// RFC 4253 6.1: implementations should support packets of at least 35000 bytes
+const MAX_PACKET_LENGTH: usize = 35000;
+
+/// What the client offered during the key exchange
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientKexInit {
+    /// Identification string, e.g. "SSH-2.0-OpenSSH_9.6"
+    pub client_version: String,
+    pub kex_algorithms: String,
+    pub server_host_key_algorithms: String,
+    /// Client to server ciphers
+    pub encryption_algorithms: String,
+    /// Client to server MACs
+    pub mac_algorithms: String,
+    /// Client to server compression
+    pub compression_algorithms: String,
+}
+
+impl ClientKexInit {
+    /// The string HASSH is computed from: `kex;ciphers;macs;compression`
+    pub fn hassh_algorithms(&self) -> String {
+        format!(
+            "{};{};{};{}",
+            self.kex_algorithms,
+            self.encryption_algorithms,
+            self.mac_algorithms,
+            self.compression_algorithms
+        )
+    }
+
+    /// HASSH fingerprint (hex encoded MD5 of [`Self::hassh_algorithms`])
+    pub fn hassh(&self) -> String {
+        hex::encode(Md5::digest(self.hassh_algorithms().as_bytes()))
+    }
+}
+
+enum ParseState {
+    /// Not enough data yet
+    Incomplete,
+    /// Not something we understand, give up
+    Invalid,
+    Done(ClientKexInit),
+}
+
+/// Read an SSH name-list (uint32 length + comma separated ASCII)
+fn read_name_list(data: &[u8], pos: &mut usize) -> Option<String> {
+    let len_bytes = data.get(*pos..*pos + 4)?;
+    let len = u32::from_be_bytes(len_bytes.try_into().ok()?) as usize;
+    let list = data.get(*pos + 4..*pos + 4 + len)?;
+    *pos += 4 + len;
+    Some(String::from_utf8_lossy(list).into_owned())
+}
+
+/// Parse the client identification line(s) followed by the first binary packet
+fn parse_client_hello(data: &[u8]) -> ParseState {
+    // Servers must accept other lines before the identification string (RFC 4253 4.2)
+    let mut pos = 0;
+    let client_version = loop {
+        let Some(newline) = data[pos..].iter().position(|b| *b == b'\n') else {
+            return ParseState::Incomplete;
+        };
+        let line = &data[pos..pos + newline];
+        pos += newline + 1;
+        if line.starts_with(b"SSH-") {
+            break String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(line)).into_owned();
+        }
+    };
+
+    let packet = &data[pos..];
+    if packet.len() < 5 {
+        return ParseState::Incomplete;
+    }
+    let packet_length = u32::from_be_bytes([packet[0], packet[1], packet[2], packet[3]]) as usize;
+    let padding_length = packet[4] as usize;
+    if packet_length > MAX_PACKET_LENGTH || padding_length + 1 > packet_length {
+        return ParseState::Invalid;
+    }
+    if packet.len() < 4 + packet_length {
+        return ParseState::Incomplete;
+    }
+
+    let payload = &packet[5..4 + packet_length - padding_length];
+    if payload.first() != Some(&MSG_KEXINIT) {
+        return ParseState::Invalid;
+    }
+
+    // Skip message number and 16 byte cookie
+    let mut pos = 17;
+    let lists: Option<Vec<String>> = (0..8).map(|_| read_name_list(payload, &mut pos)).collect();
+    match lists {
+        Some(lists) => ParseState::Done(ClientKexInit {
+            client_version,
+            kex_algorithms: lists[0].clone(),
+            server_host_key_algorithms: lists[1].clone(),
+            encryption_algorithms: lists[2].clone(),
+            mac_algorithms: lists[4].clone(),
+            compression_algorithms: lists[6].clone(),
+        }),
+        None => ParseState::Invalid,
+    }
+}
+
+/// Transparent stream wrapper that reports the client's KEXINIT once it has been read
+pub struct KexInitSniffer<S> {
+    inner: S,
+    buffer: Vec<u8>,
+    tx: Option<oneshot::Sender<ClientKexInit>>,
+}
+
+impl<S> KexInitSniffer<S> {
+    /// Wrap a stream. The receiver resolves with the client's KEXINIT, or errors
+    /// if the connection closes (or sends garbage) before one was seen.
+    pub fn new(inner: S) -> (Self, oneshot::Receiver<ClientKexInit>) {
+        let (tx, rx) = oneshot::channel();
+        (
+            Self {
+                inner,
+                buffer: Vec::new(),
+                tx: Some(tx),
+            },
+            rx,
+        )
+    }
+
+    fn observe(&mut self, data: &[u8]) {
+        if self.tx.is_none() {
+            return;
+        }
+        self.buffer.extend_from_slice(data);
+
+        match parse_client_hello(&self.buffer) {
+            ParseState::Incomplete if self.buffer.len() < MAX_SNIFF_BYTES => {}
+            ParseState::Done(kex_init) => {
+                if let Some(tx) = self.tx.take() {
+                    let _ = tx.send(kex_init);
+                }
+                self.buffer = Vec::new();
+            }
+            ParseState::Incomplete | ParseState::Invalid => {
+                // Dropping the sender tells the receiver there is nothing to wait for
+                self.tx = None;
+                self.buffer = Vec::new();
+            }
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for KexInitSniffer<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = &poll {
+            this.observe(&buf.filled()[before..]);
+        }
+        poll
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for KexInitSniffer<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name_list(s: &str) -> Vec<u8> {
+        let mut out = (s.len() as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(s.as_bytes());
+        out
+    }
+
+    fn client_hello() -> Vec<u8> {
+        let mut payload = vec![MSG_KEXINIT];
+        payload.extend_from_slice(&[0xAB; 16]);
+        for list in [
+            "curve25519-sha256,diffie-hellman-group14-sha256",
+            "ssh-ed25519",
+            "aes128-ctr",
+            "aes128-ctr",
+            "hmac-sha2-256",
+            "hmac-sha2-256",
+            "none",
+            "none",
+            "",
+            "",
+        ] {
+            payload.extend(name_list(list));
+        }
+        payload.push(0); // first_kex_packet_follows
+        payload.extend_from_slice(&[0; 4]); // reserved
+
+        let padding = 8 - ((payload.len() + 5) % 8) + 4;
+        let packet_length = (payload.len() + padding + 1) as u32;
+        let mut data = b"SSH-2.0-Go\r\n".to_vec();
+        data.extend_from_slice(&packet_length.to_be_bytes());
+        data.push(padding as u8);
+        data.extend(payload);
+        data.extend(vec![0; padding]);
+        data
+    }
+
+    #[test]
+    fn parses_client_kexinit() {
+        let ParseState::Done(kex) = parse_client_hello(&client_hello()) else {
+            panic!("expected a parsed KEXINIT");
+        };
+        assert_eq!(kex.client_version, "SSH-2.0-Go");
+        assert_eq!(kex.server_host_key_algorithms, "ssh-ed25519");
+        assert_eq!(
+            kex.hassh_algorithms(),
+            "curve25519-sha256,diffie-hellman-group14-sha256;aes128-ctr;hmac-sha2-256;none"
+        );
+        assert_eq!(kex.hassh().len(), 32);
+    }
+
+    #[test]
+    fn waits_for_complete_packet() {
+        let data = client_hello();
+        assert!(matches!(
+            parse_client_hello(&data[..data.len() - 3]),
+            ParseState::Incomplete
+        ));
+        assert!(matches!(
+            parse_client_hello(b"SSH-2.0-Go\r"),
+            ParseState::Incomplete
+        ));
+    }
+
+    #[test]
+    fn rejects_non_ssh_traffic() {
+        assert!(matches!(
+            parse_client_hello(b"GET / HTTP/1.1\r\nSSH-2.0-x\r\n\x00\x00\xff\xff\x04"),
+            ParseState::Invalid
+        ));
+    }
+}
@@ -0,0 +1,137 @@
+//! Listen addresses as operators write them.
+//!
+//! Besides plain socket addresses (`0.0.0.0:2222`, `[::]:22`) an entry may be
+//! `*:PORT` for both the IPv4 and IPv6 wildcard, a `HOST:PORT` whose host is
+//! resolved once at startup, or several of these separated by commas. Larger
+//! deployments keep them in a file, one or more per line with `#` comments,
+//! given with `--interfaces-from-file`.
+
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::path::Path;
+
+/// Entries of an interfaces file, comments and blank lines dropped
+pub fn parse_file(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .flat_map(split)
+        .collect()
+}
+
+/// Entries of an interfaces file on disk
+pub fn read_file(path: &Path) -> Result<Vec<String>, String> {
+    std::fs::read_to_string(path)
+        .map(|content| parse_file(&content))
+        .map_err(|err| format!("interfaces_from_file: {}: {}", path.display(), err))
+}
+
+/// Comma separated entries of one value
+pub fn split(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Check that `entry` has a form [`resolve`] understands, without resolving it
+pub fn check(entry: &str) -> Result<(), String> {
+    if entry.parse::<SocketAddr>().is_ok() {
+        return Ok(());
+    }
+    let (host, port) = entry.rsplit_once(':').ok_or_else(|| {
+        format!(
+            "\"{}\" is not an address like 0.0.0.0:2222, [::]:2222, *:2222 or host:2222",
+            entry
+        )
+    })?;
+    port.parse::<u16>()
+        .map_err(|err| format!("\"{}\" has an invalid port ({})", entry, err))?;
+    if host.is_empty() || host.contains(':') {
+        return Err(format!(
+            "\"{}\" has no valid host, IPv6 addresses go in brackets like [::1]:2222",
+            entry
+        ));
+    }
+    Ok(())
+}
+
+/// Addresses `entry` stands for, hostnames are looked up with the system resolver
+pub fn resolve(entry: &str) -> Result<Vec<SocketAddr>, String> {
+    if let Ok(addr) = entry.parse::<SocketAddr>() {
+        return Ok(vec![addr]);
+    }
+    check(entry)?;
+    if let Some(port) = entry.strip_prefix("*:") {
+        let port = port.parse().unwrap_or_default();
+        return Ok(vec![
+            SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port),
+            SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), port),
+        ]);
+    }
+    let addrs = entry
+        .to_socket_addrs()
+        .map_err(|err| format!("\"{}\" could not be resolved ({})", entry, err))?
+        .collect::<Vec<_>>();
+    if addrs.is_empty() {
+        return Err(format!("\"{}\" resolved to no addresses", entry));
+    }
+    log::info!("Interface {} resolved to {:?}", entry, addrs);
+    Ok(addrs)
+}
+
+/// Addresses of all `entries` in order, each one once
+pub fn resolve_all(entries: &[String]) -> Result<Vec<SocketAddr>, String> {
+    let mut interfaces = Vec::new();
+    for entry in entries {
+        for addr in resolve(entry)? {
+            if !interfaces.contains(&addr) {
+                interfaces.push(addr);
+            }
+        }
+    }
+    Ok(interfaces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_have_comments_and_comma_lists() {
+        let entries = parse_file(
+            "# sensor ports\n\
+             0.0.0.0:22, [::]:22\n\
+             \n\
+             *:2222  # both stacks\n\
+             sensor.example:2223\n",
+        );
+        assert_eq!(
+            entries,
+            ["0.0.0.0:22", "[::]:22", "*:2222", "sensor.example:2223"]
+        );
+        assert!(entries.iter().all(|entry| check(entry).is_ok()));
+    }
+
+    #[test]
+    fn wildcards_expand_to_both_stacks_once() {
+        let entries = split("*:2222,0.0.0.0:2222,127.0.0.1:22");
+        assert_eq!(
+            resolve_all(&entries).unwrap(),
+            [
+                "0.0.0.0:2222".parse::<SocketAddr>().unwrap(),
+                "[::]:2222".parse().unwrap(),
+                "127.0.0.1:22".parse().unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_entries_are_rejected() {
+        assert!(check("0.0.0.0").is_err());
+        assert!(check("host:http").is_err());
+        assert!(check("::1:22").is_err());
+        assert!(check(":22").is_err());
+    }
+}
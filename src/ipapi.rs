@@ -1,4 +1,5 @@
 use crate::db::{get_ipapi_check, record_ipapi_check};
+use crate::single_flight::SingleFlight;
 use chrono::{DateTime, Duration, Utc};
 use reqwest::tls::Version;
 use reqwest::{Certificate, Method, StatusCode};
@@ -48,6 +49,8 @@ pub struct Client {
     pub memory_cache: Arc<RwLock<HashMap<String, CachedResult>>>,
     pool: PgPool,
     pub cache_ttl_hours: u8,
+    /// Coalesces concurrent API lookups of the same IP into one request
+    in_flight: SingleFlight<IpApiResponse>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialOrd, PartialEq)]
@@ -88,6 +91,7 @@ impl Client {
             memory_cache: Arc::new(RwLock::new(HashMap::new())),
             pool,
             cache_ttl_hours: cache_ttl_hours.unwrap_or(DEFAULT_CACHE_TTL_HOURS),
+            in_flight: SingleFlight::new(),
         }
     }
 
@@ -135,6 +139,30 @@ impl Client {
             }
         }
 
+        // Connections from the same IP arriving together share one API call
+        match self
+            .in_flight
+            .run(ip_address, self.lookup_and_cache(ip_address))
+            .await
+        {
+            Ok(response) => Ok(response),
+            Err(Some(e)) => Err(e),
+            Err(None) => {
+                log::debug!("In-flight IPAPI lookup failed for IP: {}", ip_address);
+                Err(IpApiError::Other("In-flight lookup failed".to_string()))
+            }
+        }
+    }
+
+    /// Query the API and store the answer in the memory and database caches
+    async fn lookup_and_cache(&self, ip_address: &str) -> Result<IpApiResponse, IpApiError> {
+        // A lookup that finished while we were checking the database has the answer
+        if let Some(cached) = self.memory_cache.read().await.get(ip_address)
+            && Utc::now() - cached.cached_at < Duration::hours(self.cache_ttl_hours as i64)
+        {
+            return Ok(cached.response.clone());
+        }
+
         // Cache miss or expired, make API call
         log::debug!("IPAPI cache miss for IP: {}, making API call", ip_address);
         let response = self.check_ip_api(ip_address).await?;
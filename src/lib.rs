@@ -1,3 +1,4 @@
 pub mod abuseipdb;
 pub mod ipapi;
-pub use common::{dashboard, dashboard_config, db, report};
+mod single_flight;
+pub use common::{dashboard, dashboard_config, db, ip, recording, report, sqlite};
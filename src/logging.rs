@@ -0,0 +1,165 @@
+//! Format of the diagnostic log on stderr.
+//!
+//! The default is env_logger's own human readable output. `json` writes one
+//! object per line and `logfmt` one `key=value` line, for log collectors. The
+//! logger starts before the config file is read so the format can only be
+//! chosen with `--log-format` or `LOG_FORMAT`.
+
+use chrono::{SecondsFormat, Utc};
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+#[derive(clap::ValueEnum, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// env_logger's default, for reading in a terminal
+    #[default]
+    Human,
+    /// One JSON object per line with timestamp, level, target and message
+    Json,
+    /// logfmt: ts=... level=... target=... msg="..."
+    Logfmt,
+}
+
+/// Format asked for on the command line or in LOG_FORMAT. Anything clap would
+/// reject, --help included, falls back to human and is reported by App::load
+pub fn requested_format() -> LogFormat {
+    crate::app::CliArgs::try_parse()
+        .ok()
+        .and_then(|cli| cli.log_format)
+        .unwrap_or_default()
+}
+
+/// Install the logger, with russh and the HTTP and database crates at info
+pub fn init(format: LogFormat) {
+    let mut builder = env_logger::builder();
+    builder
+        .parse_env(env_logger::Env::default())
+        .filter_level(log::LevelFilter::Debug)
+        .filter_module("russh", log::LevelFilter::Info)
+        .filter_module("hyper_util", log::LevelFilter::Info)
+        .filter_module("reqwest", log::LevelFilter::Info)
+        .filter_module("sqlx", log::LevelFilter::Info)
+        .filter_module("h2", log::LevelFilter::Info);
+
+    match format {
+        LogFormat::Human => {}
+        LogFormat::Json => {
+            builder.format(|buf, record| {
+                writeln!(
+                    buf,
+                    "{}",
+                    json_line(
+                        &timestamp(),
+                        record.level(),
+                        record.target(),
+                        &record.args().to_string()
+                    )
+                )
+            });
+        }
+        LogFormat::Logfmt => {
+            builder.format(|buf, record| {
+                writeln!(
+                    buf,
+                    "{}",
+                    logfmt_line(
+                        &timestamp(),
+                        record.level(),
+                        record.target(),
+                        &record.args().to_string()
+                    )
+                )
+            });
+        }
+    }
+    builder.init();
+}
+
+fn timestamp() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn json_line(timestamp: &str, level: log::Level, target: &str, message: &str) -> String {
+    serde_json::json!({
+        "timestamp": timestamp,
+        "level": level.as_str().to_lowercase(),
+        "target": target,
+        "message": message,
+    })
+    .to_string()
+}
+
+fn logfmt_line(timestamp: &str, level: log::Level, target: &str, message: &str) -> String {
+    format!(
+        "ts={} level={} target={} msg={}",
+        timestamp,
+        level.as_str().to_lowercase(),
+        logfmt_value(target),
+        logfmt_value(message)
+    )
+}
+
+/// Bare when it can be, quoted with escapes when it has spaces, quotes, `=` or
+/// control characters. Attackers choose much of what gets logged, a newline in
+/// a username mustn't start a line of its own
+fn logfmt_value(value: &str) -> String {
+    let bare = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| !c.is_whitespace() && !c.is_control() && c != '"' && c != '=');
+    if bare {
+        return value.to_string();
+    }
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logfmt_quotes_what_needs_it() {
+        assert_eq!(
+            logfmt_line(
+                "2024-05-01T12:00:00.000Z",
+                log::Level::Warn,
+                "ssh_honeypot::server",
+                "Login as \"root\"\nfrom 203.0.113.7"
+            ),
+            "ts=2024-05-01T12:00:00.000Z level=warn target=ssh_honeypot::server \
+             msg=\"Login as \\\"root\\\"\\nfrom 203.0.113.7\""
+        );
+        assert_eq!(logfmt_value(""), "\"\"");
+        assert_eq!(logfmt_value("a=b"), "\"a=b\"");
+    }
+
+    #[test]
+    fn json_is_one_line() {
+        let line = json_line(
+            "2024-05-01T12:00:00.000Z",
+            log::Level::Info,
+            "ssh_honeypot",
+            "two\nlines",
+        );
+        assert!(!line.contains('\n'));
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["level"], "info");
+        assert_eq!(value["message"], "two\nlines");
+    }
+}
@@ -0,0 +1,54 @@
+//! wtmp and btmp for the fake shell's `last` and `lastb`, read from what this
+//! honeypot recorded.
+//!
+//! An attacker checking who else got in, or who failed to, is shown the other
+//! attackers of the same sensor. Convincing, since a box on the internet is
+//! hammered like that, and it reveals nothing but addresses and user names
+//! that already went over the wire. Enabled with `--recorded-login-history`.
+
+use async_trait::async_trait;
+use shell::commands::{LoginHistory, LoginRecord};
+use ssh_honeypot::db::{self, RecentLogin};
+use sqlx::PgPool;
+
+pub struct RecordedLogins {
+    pool: PgPool,
+}
+
+impl RecordedLogins {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn login_record(login: RecentLogin) -> LoginRecord {
+    LoginRecord {
+        username: login.username,
+        host: login.ip,
+        start: login.start,
+        end: login.end,
+    }
+}
+
+#[async_trait]
+impl LoginHistory for RecordedLogins {
+    async fn logins(&self, limit: usize) -> Vec<LoginRecord> {
+        match db::get_recent_sessions(&self.pool, limit as i64).await {
+            Ok(logins) => logins.into_iter().map(login_record).collect(),
+            Err(e) => {
+                log::error!("Failed to read recent sessions for last: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    async fn failed_logins(&self, limit: usize) -> Vec<LoginRecord> {
+        match db::get_recent_failed_logins(&self.pool, limit as i64).await {
+            Ok(logins) => logins.into_iter().map(login_record).collect(),
+            Err(e) => {
+                log::error!("Failed to read recent failed logins for lastb: {}", e);
+                Vec::new()
+            }
+        }
+    }
+}
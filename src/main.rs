@@ -1,38 +1,63 @@
 mod app;
+mod auth_rate;
+mod command_backend;
+mod command_log;
+mod credential_alert;
+mod exec_probe;
+mod export_fs;
+mod hassh;
+mod interfaces;
 mod keys;
+mod logging;
+mod login_history;
 mod paths;
+mod preflight;
+mod probe_penalty;
+mod rdns;
+mod remote_banner;
+mod replay;
+mod scanners;
+mod scp;
+mod selfcheck;
+mod selftest;
 mod server;
 mod sftp;
+mod upload;
+mod vulnerable;
+mod yara;
 
 use app::App;
-use ssh_honeypot::db::{self, initialize_database_pool, run_db_handler};
+use ssh_honeypot::db::{self, PgSink, initialize_database_pool, run_db_handler};
 use ssh_honeypot::ipapi;
+use ssh_honeypot::report;
+use ssh_honeypot::sqlite::{self, SqliteSink, initialize_sqlite_pool};
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::fs::OpenOptions;
 
 use ssh_honeypot::abuseipdb::Client as AbuseIpClient;
 use crate::server::SshServerHandler;
 use russh::server::Server as _;
 use russh::*;
+use shell::commands::accounts::{home_dir, write_account_files};
+use shell::commands::bash_history::write_bash_history;
+use shell::commands::busybox::write_embedded_layout;
+use shell::commands::{
+    BashHistory, Canaries, CommandNotFoundHandler, DnsResolver, FakeDns, HostIdentity, LoginHistory,
+    NoLoginHistory, OsIdentity, SshdSettings, write_mount_files, write_sshd_files,
+};
 use shell::filesystem::fs2::FileSystem;
 use std::io;
 use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::net::{TcpListener, TcpSocket};
-use tokio::sync::{RwLock, mpsc};
+use tokio::sync::{RwLock, Semaphore, mpsc};
 use tokio::task::JoinHandle;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    env_logger::builder()
-        .parse_env(env_logger::Env::default())
-        .filter_level(log::LevelFilter::Debug)
-        .filter_module("russh", log::LevelFilter::Info)
-        .filter_module("hyper_util", log::LevelFilter::Info)
-        .filter_module("reqwest", log::LevelFilter::Info)
-        .filter_module("sqlx", log::LevelFilter::Info)
-        .filter_module("h2", log::LevelFilter::Info)
-        .init();
+    logging::init(logging::requested_format());
 
     let app = match App::load() {
         Ok(app) => app,
@@ -42,6 +67,69 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
+    if let Some(app::CliCommand::Selfcheck(args)) = &app.command {
+        std::process::exit(selfcheck::run(args, &app.interfaces).await);
+    }
+
+    if let Some(app::CliCommand::ExportFs(args)) = &app.command {
+        let keys = keys::load_or_generate_keys(&app);
+        let mut host_identity = HostIdentity::new(app.identity_seed.as_deref());
+        host_identity.host_keys = keys.fingerprints();
+        let interface = args.interface.unwrap_or(app.interfaces[0]);
+        let fs = build_filesystem(&app, &app.persona_for(interface), &host_identity);
+        std::process::exit(export_fs::run(args, &fs));
+    }
+
+    if let Some(app::CliCommand::Replay(args)) = &app.command {
+        let keys = keys::load_or_generate_keys(&app);
+        let mut host_identity = HostIdentity::new(app.identity_seed.as_deref());
+        host_identity.host_keys = keys.fingerprints();
+        let interface = args.interface.unwrap_or(app.interfaces[0]);
+        let persona = app.persona_for(interface);
+        let fs = build_filesystem(&app, &persona, &host_identity);
+        if sqlite::is_sqlite_url(&app.database_url) {
+            log::error!("replay reads sessions from PostgreSQL, it can't use an SQLite database");
+            std::process::exit(1);
+        }
+        let pool = match initialize_database_pool(&app.database_url, true).await {
+            Ok(pool) => pool,
+            Err(e) => {
+                log::error!("Failed to connect to the database: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let dispatcher = SshServerHandler::create_command_dispatcher(
+            command_not_found_handler(&app),
+            persona.kind,
+        );
+        let host_identity = Arc::new(host_identity);
+        let code = replay::run(args, pool, &dispatcher, |username| {
+            replay::session_context(username, &persona.hostname, persona.kind, host_identity, fs)
+        })
+        .await;
+        std::process::exit(code);
+    }
+
+    if app.print_effective_config {
+        match toml::to_string(&app.effective_config()) {
+            Ok(config) => print!("{}", config),
+            Err(e) => {
+                log::error!("Failed to print the effective configuration: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    if app.self_test {
+        std::process::exit(selftest::run().await);
+    }
+
+    if let Err(e) = preflight::check_privileged_ports(&app.interfaces) {
+        log::error!("{}", e);
+        std::process::exit(1);
+    }
+
     log::info!("Current config:");
     log::info!("Database URL: {}", app.database_url);
     for interface in &app.interfaces {
@@ -49,6 +137,57 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
     log::info!("Disable CLI interface: {}", app.disable_cli_interface);
     log::info!("Disable exec requests: {}", app.disable_exec);
+    log::info!("Persona: {:?}", app.persona);
+    log::info!("Login users in /etc/passwd: {}", app.login_users.join(", "));
+    log::info!("Authentication banner drip: {}", app.auth_banner_drip);
+    if let Some(rate) = app.auth_success_rate {
+        log::info!(
+            "Authentication success rate: {} (seed {})",
+            rate,
+            app.auth_success_seed.map_or("random".to_string(), |seed| seed.to_string())
+        );
+    }
+    log::info!("Fetch policy: {:?}", app.fetch_policy);
+    log::info!("DNS lookups in the shell: {:?}", app.dns_lookups);
+    if !app.canaries.is_empty() {
+        log::info!(
+            "Canary files: {:?}",
+            app.canaries.paths().collect::<Vec<_>>()
+        );
+    }
+    log::info!(
+        "Known scanners: {} (reverse DNS {})",
+        app.scanners
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", "),
+        if app.disable_reverse_dns {
+            "disabled"
+        } else {
+            "enabled"
+        }
+    );
+    log::info!(
+        "Uploads: first {} bytes kept, stored in {}{}",
+        app.max_upload_size,
+        app.upload_store
+            .dir()
+            .map_or("the database".to_string(), |dir| dir.display().to_string()),
+        if app.upload_store.dir().is_some() && app.upload_store.in_db() {
+            " and the database"
+        } else {
+            ""
+        }
+    );
+    match &app.record_raw_streams {
+        Some(dir) => log::info!("Recording raw session streams to {}", dir.display()),
+        None => log::info!("Raw session stream recording: disabled"),
+    }
+    log::info!(
+        "Command-not-found suggestions: {}",
+        app.enable_command_not_found_suggestions
+    );
     log::info!(
         "Authentication BANNER: {}",
         app.authentication_banner.clone().unwrap_or_default()
@@ -57,77 +196,170 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         "AbuseIPDB cache cleanup interval: {} hours",
         app.abuse_ip_cache_cleanup_interval_hours
     );
+    log::info!(
+        "Max concurrent enrichment lookups: {}",
+        app.max_concurrent_enrichment
+    );
+    for persona in &app.personas {
+        match persona.interface.parse::<SocketAddr>() {
+            Ok(interface) if app.interfaces.contains(&interface) => {
+                log::info!(
+                    "Persona for {}: {:?}",
+                    interface,
+                    app.persona_for(interface)
+                );
+            }
+            Ok(_) => log::warn!(
+                "Persona for {} does not match any configured interface and is ignored",
+                persona.interface
+            ),
+            Err(err) => log::warn!(
+                "Persona interface {} is not a valid socket address and is ignored: {}",
+                persona.interface,
+                err
+            ),
+        }
+    }
 
     log::trace!("Generating or loading keys");
     let keys = keys::load_or_generate_keys(&app);
 
-    // Initialize PostgreSQL connection pool
-    let pool = match initialize_database_pool(&app.database_url, false).await {
-        Ok(pool) => pool,
-        Err(e) => {
-            log::error!("Failed to initialize database pool: {}", e);
+    // Machine-id, MACs and host keys every session sees, derived from the seed if there is one
+    let mut host_identity = HostIdentity::new(app.identity_seed.as_deref());
+    host_identity.host_keys = keys.fingerprints();
+    log::info!(
+        "Host identity: machine-id {}{}",
+        host_identity.machine_id,
+        if app.identity_seed.is_some() {
+            " (from identity seed)"
+        } else {
+            ""
+        }
+    );
+    let host_identity = Arc::new(host_identity);
+
+    if let Some(dir) = &app.record_raw_streams
+        && let Err(e) = std::fs::create_dir_all(dir)
+    {
+        log::error!(
+            "Failed to create raw stream recording directory {}: {}",
+            dir.display(),
+            e
+        );
+        std::process::exit(1);
+    }
+
+    match app.upload_store.dir() {
+        Some(dir) => {
+            if let Err(e) = std::fs::create_dir_all(dir) {
+                log::error!(
+                    "Failed to create upload store directory {}: {}",
+                    dir.display(),
+                    e
+                );
+                std::process::exit(1);
+            }
+        }
+        None if !app.upload_store.in_db() => {
+            log::error!(
+                "Uploaded files would be thrown away: --store-uploads-in-db=false needs --upload-store-dir"
+            );
             std::process::exit(1);
         }
+        None => {}
+    }
+
+    // Compiled before anything listens so broken rules stop the start instead of
+    // silently leaving uploads unscanned
+    let yara_rules = match &app.yara_rules {
+        Some(path) => match yara::YaraRules::load(path) {
+            Ok(rules) => {
+                log::info!(
+                    "YARA: scanning uploads with {} rule file(s) from {}",
+                    rules.files(),
+                    path.display()
+                );
+                Some(Arc::new(match app.yara_webhook.clone() {
+                    Some(url) => rules.with_webhook(url),
+                    None => rules,
+                }))
+            }
+            Err(e) => {
+                log::error!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        None => {
+            if app.yara_webhook.is_some() {
+                log::warn!("--yara-webhook is ignored without --yara-rules");
+            }
+            None
+        }
+    };
+
+    let command_backend = match &app.command_backend {
+        Some(spec) => match command_backend::CommandBackend::parse(
+            spec,
+            app.command_backend_commands.clone(),
+        ) {
+            Ok(backend) => {
+                log::warn!(
+                    "Command backend: running {} in containers of {}",
+                    backend.commands().join(", "),
+                    backend.image()
+                );
+                Some(Arc::new(backend))
+            }
+            Err(e) => {
+                log::error!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
     };
 
     // Create a channel for database communications
     let (db_tx, db_rx) = mpsc::channel(100);
 
-    // Start the database handler in its own thread
-    let pool_for_db_handler = pool.clone();
-    let db_handle = tokio::spawn(async move {
-        run_db_handler(db_rx, pool_for_db_handler).await;
-    });
-
-    log::trace!("Creating server config");
-
-    // Set up the SSH server configuration
-    let config = russh::server::Config {
-        keepalive_max: 5,
-        keepalive_interval: Some(std::time::Duration::from_secs(20)),
-        inactivity_timeout: Some(std::time::Duration::from_secs(30)),
-        auth_rejection_time: std::time::Duration::from_secs(3),
-        auth_rejection_time_initial: Some(std::time::Duration::from_secs(0)),
-        server_id: SshId::Standard(Cow::from(app.server_id.clone())),
-        keys: vec![keys.ed25519, keys.rsa, keys.ecdsa],
-        methods: (&[
-            MethodKind::PublicKey,
-            MethodKind::Password,
-            MethodKind::KeyboardInteractive,
-        ])
-            .as_slice()
-            .into(),
-        preferred: Preferred {
-            kex: Cow::Borrowed(&[
-                // russh::negotiation::SAFE_KEX_ORDER
-                kex::MLKEM768X25519_SHA256,
-                kex::CURVE25519,
-                kex::CURVE25519_PRE_RFC_8731,
-                kex::DH_GEX_SHA256,
-                kex::DH_G18_SHA512,
-                kex::DH_G17_SHA512,
-                kex::DH_G16_SHA512,
-                kex::DH_G15_SHA512,
-                kex::DH_G14_SHA256,
-                kex::EXTENSION_SUPPORT_AS_CLIENT,
-                kex::EXTENSION_SUPPORT_AS_SERVER,
-                kex::EXTENSION_OPENSSH_STRICT_KEX_AS_CLIENT,
-                kex::EXTENSION_OPENSSH_STRICT_KEX_AS_SERVER,
-                // Old bad insecure cipher
-                kex::DH_G1_SHA1,
-                kex::DH_G14_SHA1,
-                kex::DH_GEX_SHA1,
-            ]),
-            key: Preferred::DEFAULT.key,
-            cipher: Preferred::DEFAULT.cipher,
-            mac: Preferred::DEFAULT.mac,
-            compression: Preferred::DEFAULT.compression,
-        },
-        ..Default::default()
+    // Start the database handler in its own thread. The PostgreSQL pool is kept
+    // for the enrichment caches, the activity summary and recorded logins, which
+    // an SQLite file goes without
+    let (pool, db_handle) = if sqlite::is_sqlite_url(&app.database_url) {
+        let pool = match initialize_sqlite_pool(&app.database_url, false).await {
+            Ok(pool) => pool,
+            Err(e) => {
+                log::error!("Failed to open SQLite database: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let db_sink = SqliteSink {
+            pool,
+            sensor_id: app.sensor_id.clone(),
+        };
+        let db_handle = tokio::spawn(async move {
+            run_db_handler(db_rx, db_sink).await;
+        });
+        (None, db_handle)
+    } else {
+        // Initialize PostgreSQL connection pool
+        let pool = match initialize_database_pool(&app.database_url, false).await {
+            Ok(pool) => pool,
+            Err(e) => {
+                log::error!("Failed to initialize database pool: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let db_sink = PgSink {
+            pool: pool.clone(),
+            sensor_id: app.sensor_id.clone(),
+        };
+        let db_handle = tokio::spawn(async move {
+            run_db_handler(db_rx, db_sink).await;
+        });
+        (Some(pool), db_handle)
     };
-    log::trace!("Finished generating keys");
 
-    let config = Arc::new(config);
+    log::trace!("Finished generating keys");
 
     log::info!("Recording authentication attempts and commands in database");
 
@@ -135,26 +367,43 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let mut tasks = Vec::with_capacity(app.interfaces.len());
 
-    log::trace!("Creating filesystem");
-    let fs2 = Arc::new(RwLock::new(FileSystem::default()));
-
     // Create AbuseIPDB client if API key is provided
-    let abuse_ip_client = if let Some(api_key) = &app.abuse_ip_db_api_key {
-        log::info!("AbuseIPDB integration enabled");
-        Some(Arc::new(AbuseIpClient::new(
-            api_key.clone(),
-            pool.clone(),
-            None,
-        )))
-    } else {
-        log::info!("AbuseIPDB integration disabled (no API key provided)");
-        None
+    let abuse_ip_client = match (&app.abuse_ip_db_api_key, &pool) {
+        (Some(api_key), Some(pool)) => {
+            log::info!("AbuseIPDB integration enabled");
+            Some(Arc::new(AbuseIpClient::new(
+                api_key.clone(),
+                pool.clone(),
+                None,
+            )))
+        }
+        (Some(_), None) => {
+            log::warn!("AbuseIPDB integration disabled (its cache needs PostgreSQL)");
+            None
+        }
+        (None, _) => {
+            log::info!("AbuseIPDB integration disabled (no API key provided)");
+            None
+        }
     };
 
-    let ip_api_client = if app.disable_ipapi {
-        None
-    } else {
-        Some(Arc::new(ipapi::Client::new(pool.clone(), None)))
+    let ip_api_client = match &pool {
+        _ if app.disable_ipapi => None,
+        Some(pool) => Some(Arc::new(ipapi::Client::new(pool.clone(), None))),
+        None => {
+            log::info!("ip-api.com lookups disabled (their cache needs PostgreSQL)");
+            None
+        }
+    };
+
+    // Shared by every listener so a flood of new addresses queues its lookups
+    // instead of opening thousands of HTTPS requests at once
+    let enrichment_permits = match app.max_concurrent_enrichment {
+        0 => {
+            log::info!("Background enrichment lookups of new connections disabled");
+            None
+        }
+        permits => Some(Arc::new(Semaphore::new(permits))),
     };
 
     // Start background cache cleanup task (only if AbuseIPDB client is enabled)
@@ -191,60 +440,62 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         });
     }
 
-    if !app.disable_base_tar_gz_loading {
-        if app.disable_cli_interface {
-            log::warn!(
-                "Loading base.tar.gz is useless when the command line interface is disabled. It is recommended to disable it with -g/--disable-base-tar-gz-loading. Sleeping for 5 seconds to let you cancel loading"
-            );
-            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
-        }
-        log::trace!("Reading base.tar.gz and processing it");
-
-        match OpenOptions::new()
-            .create(false)
-            .write(false)
-            .read(true)
-            .open(app.base_tar_gz_path.clone())
-        {
-            Ok(file) => {
-                match file.metadata() {
-                    Ok(meta) => {
-                        log::debug!("File size: {}", meta.len());
-                    }
-                    Err(err) => {
-                        log::error!(
-                            "Failed to get metadata for {}: {:?}",
-                            app.base_tar_gz_path.display(),
-                            err
-                        );
-                    }
-                }
-                log::trace!("Opened {}", app.base_tar_gz_path.display());
-                match fs2.write().await.process_targz(file) {
-                    Ok(_) => {
-                        log::debug!("Processed {} successfully", app.base_tar_gz_path.display());
-                        // Take a snapshot for periodic rollback
-                        fs2.write().await.take_snapshot();
-                        log::info!("Filesystem snapshot taken for rollback support");
-                    }
-                    Err(err) => {
-                        log::error!(
-                            "Failed to process {}: {:?}. Continuing anyway",
-                            app.base_tar_gz_path.display(),
-                            err
-                        );
-                    }
+    // Periodic one-line activity summary, for operators without the dashboard
+    if app.summary_interval.is_some() && pool.is_none() {
+        log::warn!("The activity summary queries PostgreSQL, summary_interval_mins is ignored");
+    }
+    if let (Some(interval), Some(pool)) = (app.summary_interval, &pool) {
+        let reports = report::ReportGenerator::new(pool.clone());
+        log::info!(
+            "Logging an activity summary every {} minutes",
+            interval.as_secs() / 60
+        );
+        tasks.push(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick completes immediately
+            ticker.tick().await;
+            let mut since = chrono::Utc::now();
+            loop {
+                ticker.tick().await;
+                let now = chrono::Utc::now();
+                match reports.activity_summary(since, 3).await {
+                    Ok(summary) => log::info!(
+                        "In the last {} minutes: {}",
+                        interval.as_secs() / 60,
+                        summary
+                    ),
+                    Err(e) => log::warn!("Failed to query the activity summary: {}", e),
                 }
+                since = now;
             }
-            Err(err) => {
-                log::error!("Failed to open base.tar.gz: {:?}. Continuing anyway", err);
-            }
-        }
+        }));
     }
 
-    // Periodically roll back the shared filesystem to its snapshot
-    // to prevent unbounded growth from attacker mutations
-    {
+    if !app.disable_base_tar_gz_loading && app.disable_cli_interface {
+        log::warn!(
+            "Loading base.tar.gz is useless when the command line interface is disabled. It is recommended to disable it with -g/--disable-base-tar-gz-loading. Sleeping for 5 seconds to let you cancel loading"
+        );
+        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+    }
+
+    // One filesystem per distinct base tarball and hostname, interfaces with the same persona
+    // tarball and hostname share it. Personas without a tarball (dropbear) share the built-in
+    // embedded layout
+    let mut filesystems: HashMap<(Option<PathBuf>, String), Arc<RwLock<FileSystem>>> =
+        HashMap::new();
+    // Commands typed into ~/.bash_history are kept with the filesystem they are written to
+    let mut bash_histories: HashMap<(Option<PathBuf>, String), BashHistory> = HashMap::new();
+    for interface in &app.interfaces {
+        let persona = app.persona_for(*interface);
+        let key = (persona.base_tar_gz_path.clone(), persona.hostname.clone());
+        if filesystems.contains_key(&key) {
+            continue;
+        }
+
+        let fs2 = Arc::new(RwLock::new(build_filesystem(&app, &persona, &host_identity)));
+
+        // Periodically roll back the filesystem to its snapshot
+        // to prevent unbounded growth from attacker mutations
         let fs2_for_rollback = fs2.clone();
         tasks.push(tokio::spawn(async move {
             let interval = std::time::Duration::from_secs(1800); // 30 minutes
@@ -257,30 +508,168 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             }
         }));
+
+        let bash_history = match app.append_bash_history {
+            true => BashHistory::new(app.bash_history.clone())
+                .with_typed_commands(app.bash_history_per_ip, app.bash_history_at_logout),
+            false => BashHistory::new(app.bash_history.clone()),
+        };
+        bash_histories.insert(key.clone(), bash_history);
+        filesystems.insert(key, fs2);
+    }
+
+    let command_not_found = command_not_found_handler(&app);
+
+    let fetch_policy = Arc::new(app.fetch_policy.clone());
+    let canaries = Arc::new(app.canaries.clone());
+    let upload_store = Arc::new(app.upload_store.clone());
+    let scanner_tagger = Arc::new(scanners::ScannerTagger::new(&app.scanners));
+    let reverse_dns = if app.disable_reverse_dns {
+        None
+    } else {
+        rdns::ReverseDns::new().map(Arc::new)
+    };
+    let dns_resolver: Arc<dyn DnsResolver> = match app.dns_lookups {
+        app::DnsLookupPolicy::Nxdomain => Arc::new(FakeDns::NxDomain),
+        app::DnsLookupPolicy::Servfail => Arc::new(FakeDns::ServFail),
+        app::DnsLookupPolicy::Resolve => match rdns::ReverseDns::new() {
+            Some(resolver) => Arc::new(resolver),
+            None => Arc::new(FakeDns::ServFail),
+        },
+    };
+
+    let login_history: Arc<dyn LoginHistory> = match &pool {
+        Some(pool) if app.recorded_login_history => {
+            Arc::new(login_history::RecordedLogins::new(pool.clone()))
+        }
+        None if app.recorded_login_history => {
+            log::warn!(
+                "Recorded logins are read from PostgreSQL, recorded_login_history is ignored"
+            );
+            Arc::new(NoLoginHistory)
+        }
+        _ => Arc::new(NoLoginHistory),
+    };
+
+    let auth_success_rate = app.auth_success_rate.map(|rate| {
+        if app.reject_all_auth {
+            log::warn!(
+                "--auth-success-rate {} decides which logins succeed, --reject-all-auth is ignored",
+                rate
+            );
+        }
+        Arc::new(auth_rate::AuthSuccessRate::new(rate, app.auth_success_seed))
+    });
+
+    let exec_responses = Arc::new(exec_probe::ExecResponses::new(app.exec_responses.clone()));
+    if !app.append_bash_history && (app.bash_history_per_ip || app.bash_history_at_logout) {
+        log::warn!(
+            "--bash-history-per-ip and --bash-history-at-logout are ignored without --append-bash-history"
+        );
     }
 
-    for interface in app.interfaces {
-        let conf = config.clone();
+    let credential_alerts = match app.credential_alert_ips {
+        Some(ips) => {
+            log::info!(
+                "Warning when {} addresses try the same credentials within {}s",
+                ips,
+                app.credential_alert_window.as_secs()
+            );
+            let alerts =
+                credential_alert::CredentialAlerts::new(ips as usize, app.credential_alert_window);
+            Some(Arc::new(match app.credential_alert_webhook.clone() {
+                Some(url) => alerts.with_webhook(url),
+                None => alerts,
+            }))
+        }
+        None => {
+            if app.credential_alert_webhook.is_some() {
+                log::warn!("--credential-alert-webhook is ignored without --credential-alert-ips");
+            }
+            None
+        }
+    };
+
+    let probe_penalty = app.drop_on_exploit_probe.then(|| {
+        log::info!(
+            "Dropping connections for {}s from addresses that send exploit probes",
+            app.exploit_probe_penalty.as_secs()
+        );
+        Arc::new(probe_penalty::ProbePenalty::new(app.exploit_probe_penalty))
+    });
+
+    // Listener settings that SIGHUP can update, with the persona each listener started as
+    let mut reloadable = Vec::with_capacity(app.interfaces.len());
+
+    for interface in app.interfaces.clone() {
+        let persona = app.persona_for(interface);
+        let started_as = persona.clone();
+        let conf = Arc::new(server_config(&persona, &keys, app.max_auth_tries));
+        let fs2 =
+            filesystems[&(persona.base_tar_gz_path.clone(), persona.hostname.clone())].clone();
+        let command_not_found = command_not_found.clone();
+        let bash_history =
+            bash_histories[&(persona.base_tar_gz_path.clone(), persona.hostname.clone())].clone();
 
         let mut server_handler = SshServerHandler::new(
             db_tx.clone(),
             app.disable_cli_interface,
             app.disable_exec,
-            app.authentication_banner.clone(),
+            persona.authentication_banner,
             app.tarpit,
-            fs2.clone(),
+            app.auth_banner_drip,
+            fs2,
             app.enable_sftp,
+            app.enable_scp,
+            app.max_upload_size,
+            upload_store.clone(),
+            yara_rules.clone(),
+            app.unknown_subsystems,
+            exec_responses.clone(),
             abuse_ip_client.clone(),
             app.reject_all_auth,
+            auth_success_rate.clone(),
             ip_api_client.clone(),
-            app.welcome_message.clone(),
-            app.hostname.clone(),
+            enrichment_permits.clone(),
+            persona.welcome_message,
+            app.legal_banner.clone(),
+            persona.hostname,
+            persona.kind,
+            persona.vulnerable_profile,
+            command_not_found,
+            fetch_policy.clone(),
+            host_identity.clone(),
+            canaries.clone(),
+            dns_resolver.clone(),
+            login_history.clone(),
+            app.command_latency,
+            app.shutdown_close_delay,
+            app.max_command_output,
+            app.max_sleep,
+            app.disconnect_after_commands,
+            app.max_input_line,
+            bash_history,
+            app.auth_delay.clone(),
+            app.max_auth_tries,
+            app.binary_files.into(),
+            command_backend.clone(),
+            scanner_tagger.clone(),
+            reverse_dns.clone(),
+            app.quiet_scanner_mode,
+            credential_alerts.clone(),
+            app.record_raw_streams.clone(),
+            probe_penalty.clone(),
             // Does not work the intended way because of NATting on dockers side if DNAT port != target port
             interface.port(),
         );
+        reloadable.push((interface, started_as, server_handler.reloadable_settings()));
         tasks.push(tokio::spawn(async move {
             // Start the SSH server
-            log::info!("Starting SSH honeypot on {}", interface);
+            log::info!(
+                "Starting SSH honeypot on {} as {}",
+                interface,
+                persona.server_id
+            );
             let socket = match create_socket_with_reuse(
                 interface,
                 app.disable_so_reuseaddr,
@@ -297,7 +686,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             };
 
-            match server_handler.run_on_socket(conf, &socket).await {
+            match server_handler
+                .run_on_socket_capturing_kex(conf, &socket)
+                .await
+            {
                 Ok(_) => {}
                 Err(err) => {
                     log::error!(
@@ -310,6 +702,28 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }))
     }
 
+    if let Some(url) = app.banner_from_url.clone() {
+        log::info!(
+            "Fetching the authentication banner from {} every {}s",
+            url,
+            app.banner_refresh.as_secs()
+        );
+        let listeners = reloadable
+            .iter()
+            .map(|(_, _, settings)| settings.clone())
+            .collect();
+        tasks.push(tokio::spawn(
+            remote_banner::RemoteBanner::new(url).run(app.banner_refresh, listeners),
+        ));
+    }
+
+    #[cfg(unix)]
+    tasks.push(tokio::spawn(reload_on_sighup(
+        app.interfaces.clone(),
+        reloadable,
+        app.banner_from_url.is_some(),
+    )));
+
     // Ctrl+C handler for graceful shutdown
     let handle = tokio::task::spawn(async move {
         log::info!("Waiting for shutdown signal");
@@ -354,13 +768,301 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Re-read the configuration on every SIGHUP and swap the authentication banner,
+/// welcome message, legal banner and reject_all_auth of each listener. Everything
+/// else needs the sockets or the filesystem to be rebuilt and is only reported as
+/// ignored. With `remote_banner` the banner is --banner-from-url's and kept.
+#[cfg(unix)]
+async fn reload_on_sighup(
+    interfaces: Vec<SocketAddr>,
+    listeners: Vec<(
+        SocketAddr,
+        app::Persona,
+        Arc<std::sync::RwLock<server::ReloadableSettings>>,
+    )>,
+    remote_banner: bool,
+) {
+    let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(err) => {
+            log::error!(
+                "Failed to listen for SIGHUP, config reload disabled: {}",
+                err
+            );
+            return;
+        }
+    };
+
+    while hangup.recv().await.is_some() {
+        log::info!("Received SIGHUP, reloading configuration");
+        let app = match App::load() {
+            Ok(app) => app,
+            Err(err) => {
+                log::error!(
+                    "Failed to reload configuration, keeping the current one: {}",
+                    err
+                );
+                continue;
+            }
+        };
+
+        if app.interfaces != interfaces {
+            log::warn!(
+                "Interfaces changed to {:?}, a restart is required to rebind, ignored",
+                app.interfaces
+            );
+        }
+
+        for (interface, started_as, settings) in &listeners {
+            let persona = app.persona_for(*interface);
+            if persona.kind != started_as.kind
+                || persona.server_id != started_as.server_id
+                || persona.vulnerable_profile != started_as.vulnerable_profile
+                || persona.hostname != started_as.hostname
+                || persona.base_tar_gz_path != started_as.base_tar_gz_path
+            {
+                log::warn!(
+                    "Persona, server_id, vulnerable_profile, hostname or base_tar_gz_path of {} changed, a restart is required to apply them, ignored",
+                    interface
+                );
+            }
+
+            let mut current = settings
+                .write()
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+            let reloaded = server::ReloadableSettings {
+                authentication_banner: if remote_banner {
+                    current.authentication_banner.clone()
+                } else {
+                    persona.authentication_banner
+                },
+                welcome_message: persona.welcome_message,
+                legal_banner: app.legal_banner.clone(),
+                reject_all_auth: app.reject_all_auth,
+            };
+            if *current != reloaded {
+                log::info!("Updated settings of {}: {:?}", interface, reloaded);
+                *current = reloaded;
+            }
+        }
+    }
+}
+
+/// Key exchange algorithms offered unless a vulnerable profile says otherwise
+const DEFAULT_KEX: &[kex::Name] = &[
+    // russh::negotiation::SAFE_KEX_ORDER
+    kex::MLKEM768X25519_SHA256,
+    kex::CURVE25519,
+    kex::CURVE25519_PRE_RFC_8731,
+    kex::DH_GEX_SHA256,
+    kex::DH_G18_SHA512,
+    kex::DH_G17_SHA512,
+    kex::DH_G16_SHA512,
+    kex::DH_G15_SHA512,
+    kex::DH_G14_SHA256,
+    kex::EXTENSION_SUPPORT_AS_CLIENT,
+    kex::EXTENSION_SUPPORT_AS_SERVER,
+    kex::EXTENSION_OPENSSH_STRICT_KEX_AS_CLIENT,
+    kex::EXTENSION_OPENSSH_STRICT_KEX_AS_SERVER,
+    // Old bad insecure cipher
+    kex::DH_G1_SHA1,
+    kex::DH_G14_SHA1,
+    kex::DH_GEX_SHA1,
+];
+
+/// SSH server configuration for one interface. Only the server id and, with a
+/// vulnerable profile, the key exchange algorithms differ between personas
+fn server_config(
+    persona: &app::Persona,
+    keys: &keys::Keys,
+    max_auth_tries: u32,
+) -> russh::server::Config {
+    russh::server::Config {
+        keepalive_max: 5,
+        keepalive_interval: Some(std::time::Duration::from_secs(20)),
+        inactivity_timeout: Some(std::time::Duration::from_secs(30)),
+        auth_rejection_time: std::time::Duration::from_secs(3),
+        auth_rejection_time_initial: Some(std::time::Duration::from_secs(0)),
+        // SshHandler disconnects at --max-auth-tries with sshd's message, russh must not cut in first
+        max_auth_attempts: max_auth_tries as usize + 1,
+        server_id: SshId::Standard(Cow::from(persona.server_id.clone())),
+        keys: vec![keys.ed25519.clone(), keys.rsa.clone(), keys.ecdsa.clone()],
+        methods: (&[
+            MethodKind::PublicKey,
+            MethodKind::Password,
+            MethodKind::KeyboardInteractive,
+        ])
+            .as_slice()
+            .into(),
+        preferred: Preferred {
+            kex: Cow::Borrowed(
+                persona
+                    .vulnerable_profile
+                    .map_or(DEFAULT_KEX, vulnerable::VulnerableProfile::kex),
+            ),
+            key: Preferred::DEFAULT.key,
+            cipher: Preferred::DEFAULT.cipher,
+            mac: Preferred::DEFAULT.mac,
+            compression: Preferred::DEFAULT.compression,
+        },
+        ..Default::default()
+    }
+}
+
+/// Suggestions for unknown commands, None when they are turned off
+fn command_not_found_handler(app: &App) -> Option<CommandNotFoundHandler> {
+    if !app.enable_command_not_found_suggestions {
+        return None;
+    }
+    Some(match &app.command_not_found_suggestions {
+        Some(suggestions) => CommandNotFoundHandler::new(suggestions.clone()),
+        None => CommandNotFoundHandler::default(),
+    })
+}
+
+/// The filesystem sessions on a listener with `persona` start from, snapshotted for
+/// periodic rollback
+fn build_filesystem(app: &App, persona: &app::Persona, host_identity: &HostIdentity) -> FileSystem {
+    let mut fs = FileSystem::default();
+    match &persona.base_tar_gz_path {
+        Some(path) => {
+            log::trace!(
+                "Creating filesystem for {} ({})",
+                path.display(),
+                persona.hostname
+            );
+            if !app.disable_base_tar_gz_loading {
+                let sshd = app.serve_sshd_config.then(|| SshdSettings {
+                    hostname: persona.hostname.clone(),
+                    permit_root_login: !app.reject_all_auth || app.auth_success_rate.is_some(),
+                    max_auth_tries: app.max_auth_tries,
+                    banner: persona.authentication_banner.clone(),
+                    sftp: app.enable_sftp,
+                });
+                load_base_tar_gz(
+                    path,
+                    &mut fs,
+                    host_identity,
+                    &persona.hostname,
+                    &app.login_users,
+                    &BashHistory::new(app.bash_history.clone()),
+                    &app.canaries,
+                    sshd.as_ref(),
+                );
+            }
+        }
+        None => {
+            log::trace!("Creating embedded filesystem layout");
+            let os = persona.kind.os_identity();
+            match write_embedded_layout(&mut fs, &os)
+                .and_then(|_| host_identity.write_files(&mut fs))
+                .and_then(|_| write_mount_files(&mut fs, &os, &host_identity.root_fs_uuid))
+                .and_then(|_| app.canaries.plant(&mut fs))
+            {
+                Ok(_) => fs.take_snapshot(),
+                Err(err) => log::error!("Failed to create embedded filesystem: {:?}", err),
+            }
+        }
+    }
+    // Set last, the base image itself takes up part of the disk
+    fs.set_disk_size(app.disk_size);
+    fs
+}
+
+/// Load a base filesystem archive and snapshot the result for periodic rollback
+#[allow(clippy::too_many_arguments)]
+fn load_base_tar_gz(
+    path: &Path,
+    fs: &mut FileSystem,
+    host_identity: &HostIdentity,
+    hostname: &str,
+    login_users: &[String],
+    bash_history: &BashHistory,
+    canaries: &Canaries,
+    sshd: Option<&SshdSettings>,
+) {
+    log::trace!("Reading {} and processing it", path.display());
+
+    match OpenOptions::new()
+        .create(false)
+        .write(false)
+        .read(true)
+        .open(path)
+    {
+        Ok(file) => {
+            match file.metadata() {
+                Ok(meta) => {
+                    log::debug!("File size: {}", meta.len());
+                }
+                Err(err) => {
+                    log::error!("Failed to get metadata for {}: {:?}", path.display(), err);
+                }
+            }
+            log::trace!("Opened {}", path.display());
+            match fs.process_targz(file) {
+                Ok(_) => {
+                    log::debug!("Processed {} successfully", path.display());
+                    // Make the release files agree with uname, lsb_release and the banner
+                    if let Err(err) = OsIdentity::default().write_release_files(fs) {
+                        log::warn!("Failed to write OS release files: {:?}", err);
+                    }
+                    if let Err(err) = host_identity.write_files(fs) {
+                        log::warn!("Failed to write host identity files: {:?}", err);
+                    }
+                    if let Err(err) =
+                        write_mount_files(fs, &OsIdentity::default(), &host_identity.root_fs_uuid)
+                    {
+                        log::warn!("Failed to write mount table files: {:?}", err);
+                    }
+                    if let Err(err) = write_account_files(fs, hostname, login_users) {
+                        log::warn!("Failed to write hostname and account files: {:?}", err);
+                    }
+                    if let Some(sshd) = sshd
+                        && let Err(err) = write_sshd_files(fs, host_identity, sshd)
+                    {
+                        log::warn!("Failed to write /etc/ssh files: {:?}", err);
+                    }
+                    let mut users = vec!["root"];
+                    users.extend(login_users.iter().map(String::as_str));
+                    for user in users {
+                        let home = home_dir(fs, user);
+                        let history = bash_history.for_user(user);
+                        if let Err(err) = write_bash_history(fs, &home, &history) {
+                            log::warn!("Failed to write {}/.bash_history: {:?}", home, err);
+                        }
+                    }
+                    if let Err(err) = canaries.plant(fs) {
+                        log::warn!("Failed to plant canary files: {:?}", err);
+                    }
+                    // Take a snapshot for periodic rollback
+                    fs.take_snapshot();
+                    log::info!("Filesystem snapshot taken for rollback support");
+                }
+                Err(err) => {
+                    log::error!(
+                        "Failed to process {}: {:?}. Continuing anyway",
+                        path.display(),
+                        err
+                    );
+                }
+            }
+        }
+        Err(err) => {
+            log::error!(
+                "Failed to open {}: {:?}. Continuing anyway",
+                path.display(),
+                err
+            );
+        }
+    }
+}
+
 /// Helper function to create a socket with SO_REUSEPORT and SO_REUSEADDR.
 ///
 /// Linux has an interesting implementation for net.ipv6.bindv6only = 0
 /// Where if you already listen on a port on IPv4 and then bind to the same port using IPv6,
 /// binding will fail due to conflicting ports.
 /// Linux wants to be helpful and allow IPv4 clients to connect to an IPv6 socket. But if something already listens...
-#[allow(unused_variables)]
 fn create_socket_with_reuse(
     addr: SocketAddr,
     disable_reuse_addr: bool,
@@ -375,6 +1077,20 @@ fn create_socket_with_reuse(
 
     #[cfg(all(unix, not(target_os = "solaris"), not(target_os = "illumos")))]
     socket.set_reuseport(!disable_reuse_port)?;
+    #[cfg(not(all(unix, not(target_os = "solaris"), not(target_os = "illumos"))))]
+    if !disable_reuse_port {
+        log::warn!(
+            "SO_REUSEPORT is not available on this platform, {} binds without it",
+            addr
+        );
+    }
+
+    log::debug!(
+        "Binding {} with SO_REUSEADDR {} and SO_REUSEPORT {}",
+        addr,
+        if disable_reuse_addr { "off" } else { "on" },
+        if disable_reuse_port { "off" } else { "on" }
+    );
 
     socket.bind(addr)?;
     socket.listen(1024)
@@ -0,0 +1,104 @@
+//! Startup check that every configured port can actually be bound.
+//!
+//! Ports below `net.ipv4.ip_unprivileged_port_start` (1024 unless changed, Docker
+//! sets it to 0) need root or `CAP_NET_BIND_SERVICE`. Without either, each listener
+//! would fail on its own with a bare "Permission denied", so this checks up front
+//! and explains how to fix it instead.
+
+use std::net::SocketAddr;
+
+const CAP_NET_BIND_SERVICE: u32 = 10;
+const DEFAULT_UNPRIVILEGED_PORT_START: u16 = 1024;
+
+/// Error message when a privileged port is configured but can't be bound, Ok when
+/// all ports are fine or the capabilities can't be determined (not Linux)
+pub fn check_privileged_ports(interfaces: &[SocketAddr]) -> Result<(), String> {
+    if !cfg!(target_os = "linux") {
+        return Ok(());
+    }
+
+    let unprivileged_start =
+        std::fs::read_to_string("/proc/sys/net/ipv4/ip_unprivileged_port_start")
+            .ok()
+            .and_then(|value| value.trim().parse().ok())
+            .unwrap_or(DEFAULT_UNPRIVILEGED_PORT_START);
+    let privileged = privileged_interfaces(interfaces, unprivileged_start);
+    if privileged.is_empty() {
+        return Ok(());
+    }
+
+    let Some(effective) = std::fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|status| effective_capabilities(&status))
+    else {
+        return Ok(());
+    };
+    if effective & (1 << CAP_NET_BIND_SERVICE) != 0 {
+        return Ok(());
+    }
+
+    let executable = std::env::current_exe()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| "/path/to/ssh-honeypot".to_string());
+    Err(format!(
+        "Cannot listen on {}: ports below {} need root or the CAP_NET_BIND_SERVICE \
+         capability, which this process doesn't have. Grant it once (again after every \
+         rebuild) with\n\n    sudo setcap cap_net_bind_service=+ep {}\n\n\
+         or listen on port {} or above, e.g. --interface 0.0.0.0:2222, and forward port 22 to it",
+        privileged
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", "),
+        unprivileged_start,
+        executable,
+        unprivileged_start
+    ))
+}
+
+/// Interfaces with a port below `unprivileged_start`. Port 0 picks a free port
+/// at bind time and never needs privileges
+fn privileged_interfaces(interfaces: &[SocketAddr], unprivileged_start: u16) -> Vec<SocketAddr> {
+    interfaces
+        .iter()
+        .filter(|interface| interface.port() != 0 && interface.port() < unprivileged_start)
+        .copied()
+        .collect()
+}
+
+/// The `CapEff` bit mask from the contents of /proc/self/status
+fn effective_capabilities(status: &str) -> Option<u64> {
+    let value = status
+        .lines()
+        .find_map(|line| line.strip_prefix("CapEff:"))?;
+    u64::from_str_radix(value.trim(), 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_privileged_ports() {
+        let interfaces: Vec<SocketAddr> = ["0.0.0.0:22", "[::]:22", "0.0.0.0:2222", "0.0.0.0:0"]
+            .iter()
+            .map(|interface| interface.parse().unwrap())
+            .collect();
+        assert_eq!(
+            privileged_interfaces(&interfaces, 1024),
+            interfaces[..2].to_vec()
+        );
+        assert!(privileged_interfaces(&interfaces, 0).is_empty());
+    }
+
+    #[test]
+    fn parses_effective_capabilities() {
+        let status = "Name:\tssh-honeypot\nCapInh:\t0000000000000000\n\
+                      CapPrm:\t0000000000000400\nCapEff:\t0000000000000400\n";
+        assert_eq!(
+            effective_capabilities(status),
+            Some(1 << CAP_NET_BIND_SERVICE)
+        );
+        assert_eq!(effective_capabilities("Name:\tx\n"), None);
+    }
+}
@@ -0,0 +1,115 @@
+//! `--drop-on-exploit-probe`: stop engaging with addresses that send exploit
+//! probes before authenticating.
+//!
+//! A session that fails on malformed packets (bad lengths, garbage encodings,
+//! oversized or out of order messages) is already over when the error surfaces,
+//! and its anomaly is recorded first. Its address then goes on this list, and
+//! new connections from it are closed right after accept, before a single SSH
+//! byte is exchanged, until `--exploit-probe-penalty-secs` have passed.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex as StdMutex;
+use std::time::{Duration, Instant};
+
+/// Addresses penalized at once. A probe from a spoofed or rotating source can't
+/// grow the table without bound, new addresses are skipped while it is full
+const MAX_PENALIZED_IPS: usize = 10_000;
+
+/// Protocol anomalies that count as exploit probes. Clients offering only
+/// algorithms this server lacks are old or picky, not hostile
+pub fn is_exploit_probe(kind: &str) -> bool {
+    matches!(
+        kind,
+        "invalid_length"
+            | "malformed_packet"
+            | "oversized_packet"
+            | "bad_kexinit"
+            | "strict_kex_violation"
+            | "protocol_violation"
+    )
+}
+
+/// Addresses whose connections are dropped on accept, shared by every listener
+#[derive(Debug)]
+pub struct ProbePenalty {
+    duration: Duration,
+    /// Address to when its penalty ends
+    until: StdMutex<HashMap<IpAddr, Instant>>,
+}
+
+impl ProbePenalty {
+    /// Drop connections of a penalized address for `duration`
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            until: StdMutex::new(HashMap::new()),
+        }
+    }
+
+    /// Penalize `ip` from now on. A repeated probe restarts the penalty
+    pub fn penalize(&self, ip: IpAddr) {
+        self.penalize_at(ip, Instant::now());
+    }
+
+    /// Whether connections from `ip` are to be dropped right now
+    pub fn is_penalized(&self, ip: IpAddr) -> bool {
+        self.is_penalized_at(ip, Instant::now())
+    }
+
+    fn penalize_at(&self, ip: IpAddr, now: Instant) {
+        let mut until = self.until.lock().unwrap();
+        if !until.contains_key(&ip) && until.len() >= MAX_PENALIZED_IPS {
+            until.retain(|_, end| *end > now);
+            if until.len() >= MAX_PENALIZED_IPS {
+                return;
+            }
+        }
+        until.insert(ip, now + self.duration);
+    }
+
+    fn is_penalized_at(&self, ip: IpAddr, now: Instant) -> bool {
+        let mut until = self.until.lock().unwrap();
+        match until.get(&ip) {
+            Some(end) if *end > now => true,
+            Some(_) => {
+                until.remove(&ip);
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::from([192, 0, 2, last])
+    }
+
+    #[test]
+    fn penalty_runs_out() {
+        let penalty = ProbePenalty::new(Duration::from_secs(60));
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        penalty.penalize_at(ip(1), at(0));
+        assert!(penalty.is_penalized_at(ip(1), at(59)));
+        assert!(!penalty.is_penalized_at(ip(2), at(1)));
+        assert!(!penalty.is_penalized_at(ip(1), at(60)));
+
+        // Probing again starts a new penalty
+        penalty.penalize_at(ip(1), at(100));
+        penalty.penalize_at(ip(1), at(150));
+        assert!(penalty.is_penalized_at(ip(1), at(200)));
+    }
+
+    #[test]
+    fn only_hostile_anomalies_are_probes() {
+        assert!(is_exploit_probe("invalid_length"));
+        assert!(is_exploit_probe("malformed_packet"));
+        assert!(!is_exploit_probe("no_common_algorithm"));
+    }
+}
@@ -0,0 +1,179 @@
+//! Reverse DNS (PTR) enrichment of client addresses.
+//!
+//! Lookups run next to the connection record in `new_client` and are bounded by
+//! [`RDNS_TIMEOUT`], so a slow or unreachable resolver never holds up a session.
+//! Results are kept in memory for [`DEFAULT_CACHE_TTL_HOURS`] since scanners
+//! and brute forcers reconnect from the same address over and over.
+//!
+//! With `--dns-lookups resolve` the same resolver answers dig, host and nslookup
+//! in the fake shell.
+
+use chrono::{DateTime, Duration, Utc};
+use hickory_resolver::TokioResolver;
+use hickory_resolver::proto::rr::RecordType;
+use shell::commands::{DnsAnswer, DnsRecord, DnsResolver};
+use ssh_honeypot::ip::canonical_ip;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::str::FromStr;
+use tokio::sync::RwLock;
+
+/// Upper bound for the PTR and confirming forward lookups together
+const RDNS_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+const DEFAULT_CACHE_TTL_HOURS: i64 = 6;
+/// Expired entries are only swept once the cache grows past this
+const CACHE_SWEEP_THRESHOLD: usize = 10_000;
+
+/// Outcome of a reverse lookup
+#[derive(Clone, Debug, Default)]
+pub struct PtrLookup {
+    /// PTR names, lowercase without the trailing dot. Empty on NXDOMAIN
+    pub names: Vec<String>,
+    /// First of `names` that resolves back to the address. PTR records are
+    /// controlled by whoever owns the address, only this one can be trusted
+    pub confirmed: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+struct CachedLookup {
+    lookup: PtrLookup,
+    cached_at: DateTime<Utc>,
+}
+
+pub struct ReverseDns {
+    resolver: TokioResolver,
+    memory_cache: RwLock<HashMap<IpAddr, CachedLookup>>,
+}
+
+impl ReverseDns {
+    /// Uses /etc/resolv.conf, so inside Docker or Kubernetes the cluster DNS answers.
+    /// None if the system resolver configuration can't be read
+    pub fn new() -> Option<Self> {
+        match TokioResolver::builder_tokio() {
+            Ok(builder) => Some(Self {
+                resolver: builder.build(),
+                memory_cache: RwLock::new(HashMap::new()),
+            }),
+            Err(err) => {
+                log::warn!(
+                    "Failed to read the system resolver configuration, reverse DNS lookups are disabled: {}",
+                    err
+                );
+                None
+            }
+        }
+    }
+
+    /// PTR names of `ip`. Timeouts and resolver errors give an empty result
+    /// that isn't cached, NXDOMAIN is cached like any other answer
+    pub async fn lookup(&self, ip: IpAddr) -> PtrLookup {
+        let ip = canonical_ip(ip);
+
+        let cache = self.memory_cache.read().await;
+        if let Some(cached) = cache.get(&ip)
+            && Utc::now() - cached.cached_at < Duration::hours(DEFAULT_CACHE_TTL_HOURS)
+        {
+            log::debug!("Reverse DNS memory cache hit for IP: {}", ip);
+            return cached.lookup.clone();
+        }
+        drop(cache); // Release read lock
+
+        let lookup = match tokio::time::timeout(RDNS_TIMEOUT, self.resolve(ip)).await {
+            Ok(Some(lookup)) => lookup,
+            Ok(None) => return PtrLookup::default(),
+            Err(_) => {
+                log::debug!("Reverse DNS lookup for {} timed out", ip);
+                return PtrLookup::default();
+            }
+        };
+
+        let mut cache = self.memory_cache.write().await;
+        if cache.len() >= CACHE_SWEEP_THRESHOLD {
+            let now = Utc::now();
+            cache.retain(|_, cached| {
+                now - cached.cached_at < Duration::hours(DEFAULT_CACHE_TTL_HOURS)
+            });
+        }
+        cache.insert(
+            ip,
+            CachedLookup {
+                lookup: lookup.clone(),
+                cached_at: Utc::now(),
+            },
+        );
+        lookup
+    }
+
+    /// None when the resolver failed for another reason than a missing record
+    async fn resolve(&self, ip: IpAddr) -> Option<PtrLookup> {
+        let names = match self.resolver.reverse_lookup(ip).await {
+            Ok(lookup) => lookup
+                .iter()
+                .map(|ptr| ptr.to_string().trim_end_matches('.').to_ascii_lowercase())
+                .collect::<Vec<_>>(),
+            // NXDOMAIN is the normal case for most of the internet
+            Err(err) if err.is_no_records_found() => {
+                log::trace!("No reverse DNS for {}: {}", ip, err);
+                return Some(PtrLookup::default());
+            }
+            Err(err) => {
+                log::debug!("Reverse DNS lookup for {} failed: {}", ip, err);
+                return None;
+            }
+        };
+
+        let mut confirmed = None;
+        for name in &names {
+            match self.resolver.lookup_ip(format!("{}.", name)).await {
+                Ok(addresses) if addresses.iter().any(|a| a == ip) => {
+                    confirmed = Some(name.clone());
+                    break;
+                }
+                Ok(_) => log::debug!("Reverse DNS name {} of {} does not resolve back", name, ip),
+                Err(err) => log::trace!("Forward lookup of {} failed: {}", name, err),
+            }
+        }
+        Some(PtrLookup { names, confirmed })
+    }
+}
+
+#[async_trait::async_trait]
+impl DnsResolver for ReverseDns {
+    /// Real answer for a shell command, not cached. Timeouts and resolver errors
+    /// look like SERVFAIL to the client
+    async fn resolve(&self, name: &str, record_type: &str) -> DnsAnswer {
+        let Ok(record_type) = RecordType::from_str(record_type) else {
+            return DnsAnswer::ServFail;
+        };
+        let name = match name {
+            "." => ".".to_string(),
+            name => format!("{}.", name),
+        };
+        let lookup = self.resolver.lookup(name.as_str(), record_type);
+        match tokio::time::timeout(RDNS_TIMEOUT, lookup).await {
+            Ok(Ok(lookup)) => DnsAnswer::Records(
+                lookup
+                    .records()
+                    .iter()
+                    .filter(|record| {
+                        record_type == RecordType::ANY || record.record_type() == record_type
+                    })
+                    .map(|record| DnsRecord {
+                        ttl: record.ttl(),
+                        data: record.data().to_string(),
+                    })
+                    .collect(),
+            ),
+            Ok(Err(err)) if err.is_nx_domain() => DnsAnswer::NxDomain,
+            Ok(Err(err)) if err.is_no_records_found() => DnsAnswer::Records(Vec::new()),
+            Ok(Err(err)) => {
+                log::debug!("Lookup of {} {} failed: {}", name, record_type, err);
+                DnsAnswer::ServFail
+            }
+            Err(_) => {
+                log::debug!("Lookup of {} {} timed out", name, record_type);
+                DnsAnswer::ServFail
+            }
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! `--banner-from-url`: the authentication banner is fetched from an HTTP(S)
+//! endpoint at startup and again every `--banner-refresh-secs`, for sensors whose
+//! banners are managed centrally.
+//!
+//! The fetched text replaces the banner of every listener in its
+//! [`ReloadableSettings`], so new connections see it right away. A failed fetch
+//! only logs a warning, the banner served before stays.
+
+use crate::server::ReloadableSettings;
+use std::sync::{Arc, PoisonError, RwLock as StdRwLock};
+use std::time::Duration;
+
+/// Longest banner taken. A URL pointing at a web page or a download by mistake
+/// shouldn't end up in every handshake
+const MAX_BANNER_BYTES: usize = 16 * 1024;
+
+/// Client and URL the banner is fetched from
+pub struct RemoteBanner {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl RemoteBanner {
+    pub fn new(url: String) -> Self {
+        let certs = webpki_root_certs::TLS_SERVER_ROOT_CERTS
+            .iter()
+            .map(|cert| reqwest::Certificate::from_der(cert).unwrap())
+            .collect::<Vec<_>>();
+        let client = reqwest::Client::builder()
+            .min_tls_version(reqwest::tls::Version::TLS_1_2)
+            .tls_certs_only(certs)
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap();
+        Self { client, url }
+    }
+
+    /// Fetch the banner now and every `interval` after, storing each one in the
+    /// settings of `listeners`. Runs until aborted
+    pub async fn run(self, interval: Duration, listeners: Vec<Arc<StdRwLock<ReloadableSettings>>>) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let banner = match self.fetch().await {
+                Ok(banner) => banner,
+                Err(err) => {
+                    log::warn!(
+                        "Failed to fetch the banner from {}, keeping the last one: {}",
+                        self.url,
+                        err
+                    );
+                    continue;
+                }
+            };
+            for settings in &listeners {
+                let mut settings = settings.write().unwrap_or_else(PoisonError::into_inner);
+                if settings.authentication_banner != banner {
+                    log::info!("Authentication banner from {}: {:?}", self.url, banner);
+                    settings.authentication_banner = banner.clone();
+                }
+            }
+        }
+    }
+
+    async fn fetch(&self) -> Result<Option<String>, String> {
+        let response = self
+            .client
+            .get(&self.url)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|err| err.to_string())?;
+        let body = response.bytes().await.map_err(|err| err.to_string())?;
+        banner_from_body(&body)
+    }
+}
+
+/// The banner a response body holds, None for an empty one
+fn banner_from_body(body: &[u8]) -> Result<Option<String>, String> {
+    if body.len() > MAX_BANNER_BYTES {
+        return Err(format!(
+            "{} bytes, more than the {} a banner may have",
+            body.len(),
+            MAX_BANNER_BYTES
+        ));
+    }
+    let text = std::str::from_utf8(body).map_err(|_| "not UTF-8".to_string())?;
+    Ok((!text.trim().is_empty()).then(|| text.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn body_becomes_the_banner() {
+        assert_eq!(
+            banner_from_body(b"Authorized access only\n"),
+            Ok(Some("Authorized access only\n".to_string()))
+        );
+        assert_eq!(banner_from_body(b" \n"), Ok(None));
+        assert!(banner_from_body(&[0xff, 0xfe]).is_err());
+        assert!(banner_from_body(&vec![b'x'; MAX_BANNER_BYTES + 1]).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+//! `ssh-honeypot replay <auth id>`: run the commands of a recorded session through
+//! the fake shell as it is now and print what it answers today.
+//!
+//! The commands are read from the database in the order they were typed and run
+//! one after the other, as the user the session logged in as, on a fresh
+//! filesystem of the listener's persona. A `cd` or a file written carries over to
+//! the next line like it did in the session. Nothing is recorded and wget/curl
+//! fetch nothing, so sessions full of droppers are safe to replay. Comparing the
+//! output across builds shows where a real attacker's sequence now gets an answer
+//! no real host would give.
+
+use crate::app::PersonaKind;
+use shell::commands::{CommandContext, CommandDispatcher, FetchPolicy, HostIdentity};
+use shell::filesystem::fs2::FileSystem;
+use sqlx::PgPool;
+use ssh_honeypot::dashboard::Dashboard;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ReplayArgs {
+    /// Auth ID of the session to replay
+    pub auth_id: String,
+
+    /// Listener whose persona answers. Defaults to the first configured interface
+    #[arg(long = "interface")]
+    pub interface: Option<SocketAddr>,
+}
+
+/// The shell a session of `username` starts in, the way the server sets it up
+pub fn session_context(
+    username: &str,
+    hostname: &str,
+    persona: PersonaKind,
+    host: Arc<HostIdentity>,
+    mut fs: FileSystem,
+) -> CommandContext {
+    let home = format!("/home/{}", username);
+    // Already there in most base images
+    let _ = fs.create_directory(&home);
+    let mut context = CommandContext::new(
+        home,
+        username.to_string(),
+        hostname.to_string(),
+        Arc::new(RwLock::new(fs)),
+        "replay".to_string(),
+    );
+    context.os = persona.os_identity();
+    context.host = host;
+    // Every address denied, a replay must not download what the attacker did
+    context.fetch_policy = Arc::new(FetchPolicy::new(
+        Vec::new(),
+        vec![
+            "0.0.0.0/0".parse().expect("valid network"),
+            "::/0".parse().expect("valid network"),
+        ],
+    ));
+    // A sleep in the session would only hold up the replay
+    context.max_sleep = Duration::ZERO;
+    context
+}
+
+/// Run `commands` in `context` and return the transcript: each command after
+/// the prompt it was typed at, followed by what the shell answers now. Stops at
+/// the command that ends the session
+pub async fn transcript(
+    dispatcher: &CommandDispatcher,
+    context: &mut CommandContext,
+    commands: &[String],
+) -> String {
+    let mut transcript = String::new();
+    for (i, command) in commands.iter().enumerate() {
+        transcript.push_str(&context.get_prompt());
+        transcript.push_str(command);
+        transcript.push('\n');
+        let outcome = dispatcher.execute(command, context).await;
+        transcript.push_str(&outcome.output.replace("\r\n", "\n"));
+        if outcome.exit_requested {
+            let rest = commands.len() - i - 1;
+            if rest > 0 {
+                transcript.push_str(&format!(
+                    "[session ends here, {} recorded command(s) not replayed]\n",
+                    rest
+                ));
+            }
+            break;
+        }
+    }
+    transcript
+}
+
+/// Replay the session of `args.auth_id` and print the transcript. Returns the
+/// process exit code
+pub async fn run(
+    args: &ReplayArgs,
+    pool: PgPool,
+    dispatcher: &CommandDispatcher,
+    context: impl FnOnce(&str) -> CommandContext,
+) -> i32 {
+    let session = match Dashboard::new(pool).session_detail(&args.auth_id).await {
+        Ok(session) => session,
+        Err(sqlx::Error::RowNotFound) => {
+            log::error!("No authentication attempt with id {}", args.auth_id);
+            return 1;
+        }
+        Err(e) => {
+            log::error!("Failed to load session {}: {}", args.auth_id, e);
+            return 1;
+        }
+    };
+    if session.commands.is_empty() {
+        log::info!("Session {} has no commands to replay", args.auth_id);
+        return 0;
+    }
+
+    log::info!(
+        "Replaying {} command(s) of {} from {} as of this build",
+        session.commands.len(),
+        session.username,
+        session.ip
+    );
+    let commands: Vec<String> = session.commands.into_iter().map(|c| c.command).collect();
+    let mut context = context(&session.username);
+    print!("{}", transcript(dispatcher, &mut context, &commands).await);
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::SshServerHandler;
+
+    #[tokio::test]
+    async fn state_carries_over_between_commands() {
+        let dispatcher = SshServerHandler::create_command_dispatcher(None, PersonaKind::Openssh);
+        let mut fs = FileSystem::default();
+        fs.create_directory("/home").unwrap();
+        fs.create_directory("/tmp").unwrap();
+        let host = Arc::new(HostIdentity::new(None));
+        let mut context = session_context("admin", "server01", PersonaKind::Openssh, host, fs);
+        let commands = ["cd /tmp", "echo hi", "exit", "uname -a"].map(String::from);
+
+        let transcript = transcript(&dispatcher, &mut context, &commands).await;
+        assert_eq!(
+            transcript,
+            "admin@server01:~$ cd /tmp\n\
+             admin@server01:/tmp$ echo hi\n\
+             hi\n\
+             admin@server01:/tmp$ exit\n\
+             [session ends here, 1 recorded command(s) not replayed]\n"
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! Tagging of connections from known internet-wide research scanners.
+//!
+//! Most of what a public honeypot records is Shodan, Censys and friends
+//! mapping the internet. Connections from their published ranges, or whose
+//! reverse DNS name belongs to one of them, are tagged with the scanner's name
+//! in `conn_track.scanner_source` so reports can tell them apart from attackers.
+//!
+//! PTR records are controlled by whoever owns the address, so a reverse DNS
+//! name only counts when it resolves back to the connecting address
+//! (forward-confirmed reverse DNS, see [`crate::rdns`]).
+
+use crate::rdns::PtrLookup;
+use serde::{Deserialize, Serialize};
+use ssh_honeypot::ip::canonical_ip;
+use std::net::IpAddr;
+
+/// One scanner operator: address ranges and reverse DNS domains it scans from
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ScannerConfig {
+    /// Stored in `scanner_source`, e.g. "censys"
+    pub name: String,
+    /// CIDR ranges, a bare address is a single host
+    #[serde(default)]
+    pub networks: Vec<String>,
+    /// Reverse DNS domains, subdomains included (e.g. "censys-scanner.com")
+    #[serde(default)]
+    pub rdns: Vec<String>,
+}
+
+/// Scanners recognised when the config file doesn't list its own
+pub fn builtin_scanners() -> Vec<ScannerConfig> {
+    let scanner = |name: &str, networks: &[&str], rdns: &[&str]| ScannerConfig {
+        name: name.to_string(),
+        networks: networks.iter().map(|n| n.to_string()).collect(),
+        rdns: rdns.iter().map(|d| d.to_string()).collect(),
+    };
+
+    vec![
+        scanner(
+            "censys",
+            &[
+                "162.142.125.0/24",
+                "167.94.138.0/24",
+                "167.94.145.0/24",
+                "167.94.146.0/24",
+                "167.248.133.0/24",
+                "199.45.154.0/24",
+                "199.45.155.0/24",
+                "206.168.34.0/24",
+            ],
+            &["censys-scanner.com"],
+        ),
+        scanner("shodan", &[], &["shodan.io"]),
+        scanner("binaryedge", &[], &["binaryedge.ninja"]),
+        scanner(
+            "internet-measurement",
+            &["87.236.176.0/24"],
+            &["internet-measurement.com"],
+        ),
+        scanner("shadowserver", &[], &["shadowserver.org"]),
+        scanner("rapid7", &[], &["sonar.rapid7.com"]),
+        scanner("stretchoid", &[], &["stretchoid.com"]),
+        scanner("onyphe", &[], &["onyphe.net"]),
+        scanner("alphastrike", &[], &["alphastrike.io"]),
+    ]
+}
+
+/// Parse a network in CIDR notation, a bare address being a single host
+pub fn parse_network(network: &str) -> Result<(IpAddr, u8), String> {
+    let (addr, prefix) = match network.split_once('/') {
+        Some((addr, prefix)) => (addr, Some(prefix)),
+        None => (network, None),
+    };
+    let addr: IpAddr = addr
+        .trim()
+        .parse()
+        .map_err(|_| format!("\"{}\" is not a network like 192.0.2.0/24", network))?;
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+        Some(prefix) => match prefix.trim().parse::<u8>() {
+            Ok(prefix) if prefix <= max => prefix,
+            _ => return Err(format!("invalid prefix length in \"{}\"", network)),
+        },
+        None => max,
+    };
+    Ok((addr, prefix))
+}
+
+fn network_contains((network, prefix): (IpAddr, u8), ip: IpAddr) -> bool {
+    match (network, ip) {
+        (IpAddr::V4(network), IpAddr::V4(ip)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(network) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(network), IpAddr::V6(ip)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(network) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
+struct Scanner {
+    name: String,
+    networks: Vec<(IpAddr, u8)>,
+    rdns: Vec<String>,
+}
+
+pub struct ScannerTagger {
+    scanners: Vec<Scanner>,
+}
+
+impl ScannerTagger {
+    /// Invalid networks are skipped, the config file was validated on load
+    pub fn new(configs: &[ScannerConfig]) -> Self {
+        let scanners = configs
+            .iter()
+            .map(|config| Scanner {
+                name: config.name.clone(),
+                networks: config
+                    .networks
+                    .iter()
+                    .filter_map(|network| parse_network(network).ok())
+                    .collect(),
+                rdns: config
+                    .rdns
+                    .iter()
+                    .map(|domain| {
+                        domain
+                            .trim_start_matches("*.")
+                            .trim_matches('.')
+                            .to_ascii_lowercase()
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Self { scanners }
+    }
+
+    /// Name of the scanner `ip` belongs to, if any. Only the forward-confirmed
+    /// reverse DNS name of `ptr` is matched against the scanners' domains
+    pub fn tag(&self, ip: IpAddr, ptr: &PtrLookup) -> Option<String> {
+        let ip = canonical_ip(ip);
+        if let Some(scanner) = self
+            .scanners
+            .iter()
+            .find(|s| s.networks.iter().any(|n| network_contains(*n, ip)))
+        {
+            return Some(scanner.name.clone());
+        }
+
+        let name = ptr.confirmed.as_deref()?;
+        self.scanners
+            .iter()
+            .find(|s| {
+                s.rdns.iter().any(|domain| {
+                    name == domain
+                        || name
+                            .strip_suffix(domain.as_str())
+                            .is_some_and(|rest| rest.ends_with('.'))
+                })
+            })
+            .map(|s| s.name.clone())
+    }
+}
@@ -0,0 +1,353 @@
+use crate::upload::UploadStream;
+use shell::filesystem::fs2::{FileContent, FileSystem};
+use std::sync::Arc;
+
+/*
+NOTE: Only the sink side of the SCP protocol (`scp -t`, what runs on the remote end
+when a client copies files *to* us) is implemented, that's the side droppers use.
+The protocol is line based: the client announces a file with `C<mode> <size> <name>\n`,
+a directory with `D<mode> 0 <name>\n` and leaves it with `E\n`, `-p` adds
+`T<mtime> 0 <atime> 0\n` lines. Every line and every file body (followed by a single
+`\0`) is acknowledged with `\0`, `\x02<message>\n` aborts the transfer.
+ */
+
+/// A file received in full
+#[derive(Debug, PartialEq)]
+pub struct ScpFile {
+    pub path: String,
+    pub mode: u16,
+    /// The file up to --max-upload-size
+    pub data: Vec<u8>,
+    /// Size and SHA256 of the whole file
+    pub size: u64,
+    pub file_hash: String,
+}
+
+/// What a chunk of client data resulted in
+#[derive(Debug, Default, PartialEq)]
+pub struct ScpOutput {
+    /// Bytes to send back to the client
+    pub reply: Vec<u8>,
+    /// Directories announced with `D` lines, in order
+    pub directories: Vec<String>,
+    pub files: Vec<ScpFile>,
+    /// The transfer was aborted, the channel should be closed
+    pub finished: bool,
+}
+
+#[derive(Debug)]
+enum State {
+    Control,
+    Body {
+        path: String,
+        mode: u16,
+        remaining: u64,
+        data: Vec<u8>,
+        upload: UploadStream,
+    },
+    Done,
+}
+
+/// Receiving end of one `scp -t` exec request
+#[derive(Debug)]
+pub struct ScpSink {
+    target: String,
+    target_is_dir: bool,
+    /// Directories entered with `D` lines during a recursive copy
+    dirs: Vec<String>,
+    state: State,
+    buffer: Vec<u8>,
+    /// Bytes of a file that are kept, the rest is only hashed
+    max_upload_size: u64,
+}
+
+impl ScpSink {
+    /// `target` is the absolute path from the command line. Files land inside it
+    /// when it's a directory, otherwise the (single) file is written to it
+    pub fn new(target: String, target_is_dir: bool, max_upload_size: u64) -> Self {
+        Self {
+            target,
+            target_is_dir,
+            dirs: Vec::new(),
+            state: State::Control,
+            buffer: Vec::new(),
+            max_upload_size,
+        }
+    }
+
+    /// Target of an `scp -t` command and whether `-d`/`-r` say it's a directory,
+    /// None for any other command. `scp -f` (sending files) isn't supported
+    pub fn parse_command(command: &str) -> Option<(String, bool)> {
+        let mut words = command.split_whitespace();
+        if words.next()?.rsplit('/').next()? != "scp" {
+            return None;
+        }
+        let mut sink = false;
+        let mut directory = false;
+        let mut target = None;
+        let mut options_done = false;
+        for word in words {
+            match word.strip_prefix('-') {
+                Some("-") if !options_done => options_done = true,
+                Some(flags) if !options_done && !flags.is_empty() => {
+                    for flag in flags.chars() {
+                        match flag {
+                            't' => sink = true,
+                            'd' | 'r' => directory = true,
+                            'f' => return None,
+                            _ => {}
+                        }
+                    }
+                }
+                _ => target = Some(word.to_string()),
+            }
+        }
+        sink.then(|| (target.unwrap_or_else(|| ".".to_string()), directory))
+    }
+
+    /// Feed bytes the client sent
+    pub fn feed(&mut self, data: &[u8]) -> ScpOutput {
+        let mut output = ScpOutput::default();
+        self.buffer.extend_from_slice(data);
+
+        loop {
+            match &mut self.state {
+                State::Done => break,
+                State::Body {
+                    path,
+                    mode,
+                    remaining,
+                    data,
+                    upload,
+                } => {
+                    if *remaining > 0 {
+                        let take = (*remaining).min(self.buffer.len() as u64) as usize;
+                        if take == 0 {
+                            break;
+                        }
+                        let chunk: Vec<u8> = self.buffer.drain(..take).collect();
+                        let kept = upload.write(upload.size(), &chunk);
+                        data.extend_from_slice(&chunk[..kept]);
+                        *remaining -= take as u64;
+                        continue;
+                    }
+                    // The body is followed by a single status byte
+                    let Some(&status) = self.buffer.first() else {
+                        break;
+                    };
+                    self.buffer.remove(0);
+                    if status != 0 {
+                        output.finished = true;
+                        self.state = State::Done;
+                        break;
+                    }
+                    let data = std::mem::take(data);
+                    let upload = std::mem::replace(upload, UploadStream::new(0));
+                    output.files.push(ScpFile {
+                        path: std::mem::take(path),
+                        mode: *mode,
+                        size: upload.size(),
+                        file_hash: upload.finish(&data),
+                        data,
+                    });
+                    output.reply.push(0);
+                    self.state = State::Control;
+                }
+                State::Control => {
+                    let Some(end) = self.buffer.iter().position(|&b| b == b'\n') else {
+                        break;
+                    };
+                    let line: Vec<u8> = self.buffer.drain(..=end).collect();
+                    let line = String::from_utf8_lossy(&line[..end]).into_owned();
+                    if let Err(message) = self.control(&line, &mut output) {
+                        output.reply.push(2);
+                        output
+                            .reply
+                            .extend_from_slice(format!("scp: {}\n", message).as_bytes());
+                        output.finished = true;
+                        self.state = State::Done;
+                    }
+                }
+            }
+        }
+        output
+    }
+
+    fn control(&mut self, line: &str, output: &mut ScpOutput) -> Result<(), String> {
+        let Some(kind) = line.chars().next() else {
+            return Err("protocol error: unexpected <newline>".to_string());
+        };
+        match kind {
+            'C' | 'D' => {
+                let mut fields = line[1..].splitn(3, ' ');
+                let (Some(mode), Some(size), Some(name)) =
+                    (fields.next(), fields.next(), fields.next())
+                else {
+                    return Err("protocol error: size not delimited".to_string());
+                };
+                let mode = u16::from_str_radix(mode, 8)
+                    .ok()
+                    .filter(|mode| *mode <= 0o7777)
+                    .ok_or_else(|| "protocol error: bad mode".to_string())?;
+                let size: u64 = size
+                    .parse()
+                    .map_err(|_| "protocol error: size not present".to_string())?;
+                if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+                    return Err(format!("error: unexpected filename: {}", name));
+                }
+
+                let path = self.destination(name);
+                if kind == 'D' {
+                    output.directories.push(path.clone());
+                    self.dirs.push(path);
+                } else {
+                    self.state = State::Body {
+                        path,
+                        mode,
+                        remaining: size,
+                        data: Vec::with_capacity(size.min(self.max_upload_size) as usize),
+                        upload: UploadStream::new(self.max_upload_size),
+                    };
+                }
+                output.reply.push(0);
+            }
+            'E' => {
+                self.dirs.pop();
+                output.reply.push(0);
+            }
+            'T' => output.reply.push(0),
+            // The client gave up and tells us why
+            '\x01' | '\x02' => {
+                log::debug!("SCP client reported an error: {}", &line[1..]);
+                output.finished = true;
+                self.state = State::Done;
+            }
+            _ => return Err("protocol error: expected control record".to_string()),
+        }
+        Ok(())
+    }
+
+    /// Where a file or directory called `name` goes
+    fn destination(&self, name: &str) -> String {
+        match self.dirs.last() {
+            Some(dir) => format!("{}/{}", dir, name),
+            None if self.target_is_dir => {
+                format!("{}/{}", self.target.trim_end_matches('/'), name)
+            }
+            None => self.target.clone(),
+        }
+    }
+}
+
+/// Put a received file into the virtual filesystem, replacing what was there
+pub fn store_file(fs: &mut FileSystem, file: &ScpFile) -> std::io::Result<()> {
+    let existing = fs
+        .get_file(&file.path)
+        .ok()
+        .map(|entry| entry.inode.i_size_lo as u64);
+    fs.ensure_space(existing.unwrap_or(0), file.size)?;
+    if existing.is_some() {
+        fs.remove_file(&file.path)?;
+    }
+    let entry = fs.create_file(&file.path)?;
+    entry.inode.i_mode = file.mode;
+    entry.inode.i_size_lo = file.size.min(u32::MAX as u64) as u32;
+    entry.content = Some(FileContent::RegularFile(Arc::new(file.data.clone())));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+
+    #[test]
+    fn parses_sink_commands() {
+        assert_eq!(
+            ScpSink::parse_command("scp -t /tmp/"),
+            Some(("/tmp/".to_string(), false))
+        );
+        assert_eq!(
+            ScpSink::parse_command("scp -r -d -t -- /tmp"),
+            Some(("/tmp".to_string(), true))
+        );
+        assert_eq!(
+            ScpSink::parse_command("/usr/bin/scp -qt x"),
+            Some(("x".to_string(), false))
+        );
+        assert_eq!(ScpSink::parse_command("scp -f /etc/passwd"), None);
+        assert_eq!(ScpSink::parse_command("uname -a"), None);
+    }
+
+    #[test]
+    fn receives_a_file_into_a_directory() {
+        let mut sink = ScpSink::new("/tmp".to_string(), true, 1024);
+        let output = sink.feed(b"C0755 5 bot.sh\n");
+        assert_eq!(output.reply, [0]);
+        assert!(output.files.is_empty());
+
+        // The body may arrive in pieces, together with its trailing status byte
+        assert_eq!(sink.feed(b"ech").reply, Vec::<u8>::new());
+        let output = sink.feed(b"o\n\0");
+        assert_eq!(output.reply, [0]);
+        assert_eq!(
+            output.files,
+            [ScpFile {
+                path: "/tmp/bot.sh".to_string(),
+                mode: 0o755,
+                data: b"echo\n".to_vec(),
+                size: 5,
+                file_hash: hex::encode(Sha256::digest(b"echo\n")),
+            }]
+        );
+    }
+
+    #[test]
+    fn keeps_only_the_beginning_of_big_files() {
+        let mut sink = ScpSink::new("/tmp".to_string(), true, 4);
+        sink.feed(
+            b"C0644 10 big
+",
+        );
+        sink.feed(b"0123456");
+        let output = sink.feed(b"789\0");
+        assert_eq!(output.reply, [0]);
+        let file = &output.files[0];
+        assert_eq!(file.data, b"0123");
+        assert_eq!(file.size, 10);
+        assert_eq!(file.file_hash, hex::encode(Sha256::digest(b"0123456789")));
+    }
+
+    #[test]
+    fn receives_recursive_copies() {
+        let mut sink = ScpSink::new("/tmp".to_string(), true, 1024);
+        let output = sink.feed(b"T1700000000 0 1700000000 0\nD0755 0 kit\nC0644 2 a\nhi\0E\n");
+        assert_eq!(output.reply, [0, 0, 0, 0, 0]);
+        assert_eq!(output.directories, ["/tmp/kit"]);
+        assert_eq!(output.files[0].path, "/tmp/kit/a");
+        assert!(!output.finished);
+    }
+
+    #[test]
+    fn single_file_target_is_the_file_name() {
+        let mut sink = ScpSink::new("/tmp/renamed".to_string(), false, 1024);
+        let output = sink.feed(b"C0644 0 original\n\0");
+        assert_eq!(output.files[0].path, "/tmp/renamed");
+    }
+
+    #[test]
+    fn rejects_bad_control_lines() {
+        let mut sink = ScpSink::new("/tmp".to_string(), true, 1024);
+        let output = sink.feed(b"C0644 3 ../x\n");
+        assert!(output.finished);
+        assert_eq!(output.reply[0], 2);
+
+        let mut sink = ScpSink::new("/tmp".to_string(), true, 1024);
+        let output = sink.feed(b"hello\n");
+        assert!(output.finished);
+        assert_eq!(
+            output.reply,
+            b"\x02scp: protocol error: expected control record\n"
+        );
+    }
+}
@@ -0,0 +1,428 @@
+//! `ssh-honeypot selfcheck`: connect to a running honeypot as a client and look for
+//! the tells fingerprinting tools (and careful attackers) use to spot honeypots.
+//!
+//! Every probe goes through the same SSH surface an attacker sees, so new commands
+//! that contradict the fake system show up here. The exit code is 1 when anything
+//! gave the honeypot away, which makes it usable as a regression check.
+
+use russh::client;
+use russh::keys::{HashAlg, PublicKey};
+use russh::{ChannelMsg, Disconnect};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncBufReadExt;
+
+/// How long the shell may stay quiet before a command counts as finished
+const IDLE_TIMEOUT: Duration = Duration::from_millis(1500);
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct SelfcheckArgs {
+    /// Honeypot to probe. Defaults to the first configured interface, on loopback
+    #[arg(long = "target")]
+    pub target: Option<SocketAddr>,
+
+    /// User to log in as
+    #[arg(long = "user", default_value = "root")]
+    pub user: String,
+
+    /// Password to log in with
+    #[arg(long = "password", default_value = "123456")]
+    pub password: String,
+}
+
+/// Outcome of the probes, in the order they ran
+#[derive(Debug, Default)]
+struct Report {
+    /// Whether the check passed, and what was seen
+    checks: Vec<(bool, String)>,
+}
+
+impl Report {
+    fn ok(&mut self, message: String) {
+        self.checks.push((true, message));
+    }
+
+    fn tell(&mut self, message: String) {
+        self.checks.push((false, message));
+    }
+
+    fn tells(&self) -> usize {
+        self.checks.iter().filter(|(passed, _)| !passed).count()
+    }
+}
+
+/// Run every probe against `args.target` (or `interfaces[0]`) and print the report.
+/// Returns the process exit code
+pub async fn run(args: &SelfcheckArgs, interfaces: &[SocketAddr]) -> i32 {
+    let Some(target) = args
+        .target
+        .or_else(|| interfaces.first().map(|i| loopback(*i)))
+    else {
+        eprintln!("No --target given and no interface configured");
+        return 2;
+    };
+    println!("Probing {} as {}", target, args.user);
+
+    let mut report = Report::default();
+    if let Err(err) = probe(target, args, &mut report).await {
+        report.tell(format!("probing stopped early: {}", err));
+    }
+
+    for (passed, message) in &report.checks {
+        println!("  {}  {}", if *passed { "ok  " } else { "TELL" }, message);
+    }
+    let tells = report.tells();
+    println!("{} check(s), {} tell(s) found", report.checks.len(), tells);
+    if tells == 0 { 0 } else { 1 }
+}
+
+/// Listening on the unspecified address means reachable on loopback
+fn loopback(interface: SocketAddr) -> SocketAddr {
+    match interface.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => {
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), interface.port())
+        }
+        IpAddr::V6(ip) if ip.is_unspecified() => {
+            SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), interface.port())
+        }
+        _ => interface,
+    }
+}
+
+async fn probe(
+    target: SocketAddr,
+    args: &SelfcheckArgs,
+    report: &mut Report,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // The identification string, read before any SSH library gets involved
+    let stream = tokio::net::TcpStream::connect(target).await?;
+    let mut server_id = String::new();
+    tokio::time::timeout(
+        Duration::from_secs(5),
+        tokio::io::BufReader::new(stream).read_line(&mut server_id),
+    )
+    .await??;
+    let server_id = server_id.trim_end().to_string();
+    let expected_release = ubuntu_release_for_server_id(&server_id);
+    if !server_id.starts_with("SSH-2.0-") {
+        report.tell(format!("server id {:?} is not SSH-2.0", server_id));
+    } else {
+        report.ok(format!("server id {}", server_id));
+    }
+
+    let first = connect(target, args).await?;
+    let second = connect(target, args).await?;
+    if first.host_key == second.host_key {
+        report.ok(format!(
+            "host key stable across connections ({})",
+            first.host_key
+        ));
+    } else {
+        report.tell(format!(
+            "host key changed between connections ({} then {})",
+            first.host_key, second.host_key
+        ));
+    }
+    second.close().await;
+
+    // A real sshd runs the command, whatever comes back must match the shell
+    let exec_uname = first.exec("uname -a").await?;
+    let mut shell = first.shell().await?;
+    let (shell_uname, _) = shell.run("uname -a").await?;
+    if exec_uname.trim() == shell_uname.trim() {
+        report.ok("exec and the interactive shell agree on uname -a".to_string());
+    } else {
+        report.tell(format!(
+            "exec answered {:?} where the shell printed {:?}",
+            exec_uname.trim(),
+            shell_uname.trim()
+        ));
+    }
+
+    let (kernel, _) = shell.run("uname -r").await?;
+    let (lsb_description, _) = shell.run("lsb_release -ds").await?;
+    let (os_release, _) = shell.run("cat /etc/os-release").await?;
+    let pretty_name = os_release_field(&os_release, "PRETTY_NAME");
+    let version_id = os_release_field(&os_release, "VERSION_ID");
+
+    match &pretty_name {
+        Some(pretty) if pretty == lsb_description.trim() => report.ok(format!(
+            "lsb_release and /etc/os-release agree ({})",
+            pretty
+        )),
+        _ => report.tell(format!(
+            "lsb_release says {:?}, /etc/os-release PRETTY_NAME is {:?}",
+            lsb_description.trim(),
+            pretty_name
+        )),
+    }
+
+    if let Some(expected) = expected_release {
+        match &version_id {
+            Some(version) if version == expected => {
+                report.ok(format!("OpenSSH version fits Ubuntu {}", version))
+            }
+            _ => report.tell(format!(
+                "server id ships with Ubuntu {}, /etc/os-release says {:?}",
+                expected, version_id
+            )),
+        }
+    }
+
+    if let Some(version) = &version_id
+        && let Some(kernels) = ubuntu_kernels(version)
+    {
+        let kernel = kernel.trim();
+        if kernels.iter().any(|prefix| kernel.starts_with(prefix)) {
+            report.ok(format!("kernel {} fits Ubuntu {}", kernel, version));
+        } else {
+            report.tell(format!(
+                "kernel {:?} never shipped with Ubuntu {} (expected {})",
+                kernel,
+                version,
+                kernels.join(" or ")
+            ));
+        }
+    }
+
+    // Commands that take time on a real system must take time here too
+    let (_, elapsed) = shell.run("sleep 1").await?;
+    if elapsed >= Duration::from_millis(900) {
+        report.ok(format!("sleep 1 took {} ms", elapsed.as_millis()));
+    } else {
+        report.tell(format!("sleep 1 returned after {} ms", elapsed.as_millis()));
+    }
+
+    let (missing, _) = shell.run("definitely-not-a-command").await?;
+    if missing.contains("command not found") {
+        report.ok("unknown commands answer \"command not found\"".to_string());
+    } else {
+        report.tell(format!("unknown command answered {:?}", missing.trim()));
+    }
+
+    first.close().await;
+    Ok(())
+}
+
+/// Accepts any host key, remembering its fingerprint
+struct ProbeClient {
+    host_key: Arc<StdMutex<Option<String>>>,
+}
+
+impl client::Handler for ProbeClient {
+    type Error = russh::Error;
+
+    fn check_server_key(
+        &mut self,
+        server_public_key: &PublicKey,
+    ) -> impl Future<Output = Result<bool, Self::Error>> + Send {
+        *self.host_key.lock().unwrap() =
+            Some(server_public_key.fingerprint(HashAlg::Sha256).to_string());
+        async { Ok(true) }
+    }
+}
+
+/// An authenticated connection
+struct Connection {
+    handle: client::Handle<ProbeClient>,
+    host_key: String,
+}
+
+async fn connect(
+    target: SocketAddr,
+    args: &SelfcheckArgs,
+) -> Result<Connection, Box<dyn std::error::Error>> {
+    let host_key = Arc::new(StdMutex::new(None));
+    let client = ProbeClient {
+        host_key: host_key.clone(),
+    };
+    let mut handle = client::connect(Arc::new(client::Config::default()), target, client).await?;
+    let auth = handle
+        .authenticate_password(args.user.clone(), args.password.clone())
+        .await?;
+    if !auth.success() {
+        return Err(format!("{}/{} was not accepted", args.user, args.password).into());
+    }
+    let host_key = host_key.lock().unwrap().take().unwrap_or_default();
+    Ok(Connection { handle, host_key })
+}
+
+impl Connection {
+    /// Output of an exec request
+    async fn exec(&self, command: &str) -> Result<String, russh::Error> {
+        let mut channel = self.handle.channel_open_session().await?;
+        channel.exec(true, command).await?;
+        let mut output = Vec::new();
+        while let Ok(Some(message)) = tokio::time::timeout(IDLE_TIMEOUT, channel.wait()).await {
+            match message {
+                ChannelMsg::Data { data } => output.extend_from_slice(&data),
+                ChannelMsg::Eof | ChannelMsg::Close => break,
+                _ => {}
+            }
+        }
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+
+    /// Interactive shell with a pty, past the login banner
+    async fn shell(&self) -> Result<Shell, russh::Error> {
+        let channel = self.handle.channel_open_session().await?;
+        channel
+            .request_pty(true, "xterm-256color", 120, 40, 0, 0, &[])
+            .await?;
+        channel.request_shell(true).await?;
+        let mut shell = Shell { channel };
+        shell.read_until_idle().await;
+        Ok(shell)
+    }
+
+    async fn close(self) {
+        let _ = self
+            .handle
+            .disconnect(Disconnect::ByApplication, "", "English")
+            .await;
+    }
+}
+
+struct Shell {
+    channel: russh::Channel<client::Msg>,
+}
+
+impl Shell {
+    /// Output of `command` without the echoed command line and the next prompt,
+    /// and how long it took until the prompt came back
+    async fn run(&mut self, command: &str) -> Result<(String, Duration), russh::Error> {
+        let started = Instant::now();
+        self.channel
+            .data(format!("{}\r", command).as_bytes())
+            .await?;
+        let (output, elapsed) = self.read_until_idle().await;
+        Ok((
+            strip_echo_and_prompt(&output),
+            elapsed.unwrap_or_else(|| started.elapsed()),
+        ))
+    }
+
+    /// Everything the shell sends until it goes quiet, and when the last byte came
+    async fn read_until_idle(&mut self) -> (String, Option<Duration>) {
+        let started = Instant::now();
+        let mut last_data = None;
+        let mut output = Vec::new();
+        while let Ok(Some(message)) = tokio::time::timeout(IDLE_TIMEOUT, self.channel.wait()).await
+        {
+            if let ChannelMsg::Data { data } = message {
+                output.extend_from_slice(&data);
+                last_data = Some(started.elapsed());
+            }
+        }
+        (String::from_utf8_lossy(&output).into_owned(), last_data)
+    }
+}
+
+/// Drop the first line (the terminal echo of the command) and the last one (the prompt)
+fn strip_echo_and_prompt(output: &str) -> String {
+    let output = output.replace("\r\n", "\n");
+    let mut lines: Vec<&str> = output.split('\n').collect();
+    if lines.len() < 2 {
+        return String::new();
+    }
+    lines.remove(0);
+    lines.pop();
+    lines.join("\n")
+}
+
+/// Ubuntu release whose OpenSSH package announces itself like `server_id`
+fn ubuntu_release_for_server_id(server_id: &str) -> Option<&'static str> {
+    let software = server_id.strip_prefix("SSH-2.0-")?;
+    if !software.contains("Ubuntu") {
+        return None;
+    }
+    [
+        ("OpenSSH_7.2p2", "16.04"),
+        ("OpenSSH_7.6p1", "18.04"),
+        ("OpenSSH_8.2p1", "20.04"),
+        ("OpenSSH_8.9p1", "22.04"),
+        ("OpenSSH_9.6p1", "24.04"),
+    ]
+    .iter()
+    .find(|(version, _)| software.starts_with(version))
+    .map(|(_, release)| *release)
+}
+
+/// Kernel release prefixes of the GA and HWE kernels of an Ubuntu release
+fn ubuntu_kernels(version_id: &str) -> Option<&'static [&'static str]> {
+    Some(match version_id {
+        "16.04" => &["4.4.", "4.15."],
+        "18.04" => &["4.15.", "5.4."],
+        "20.04" => &["5.4.", "5.15."],
+        "22.04" => &["5.15.", "6.5.", "6.8."],
+        "24.04" => &["6.8.", "6.11."],
+        _ => return None,
+    })
+}
+
+/// Unquoted value of `key` in os-release syntax
+fn os_release_field(os_release: &str, key: &str) -> Option<String> {
+    os_release.lines().find_map(|line| {
+        let (name, value) = line.trim().split_once('=')?;
+        (name == key).then(|| value.trim_matches('"').to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn server_id_maps_to_ubuntu_release() {
+        assert_eq!(
+            ubuntu_release_for_server_id("SSH-2.0-OpenSSH_8.2p1 Ubuntu-4ubuntu0.4"),
+            Some("20.04")
+        );
+        assert_eq!(
+            ubuntu_release_for_server_id("SSH-2.0-dropbear_2017.75"),
+            None
+        );
+        assert_eq!(ubuntu_release_for_server_id("SSH-2.0-OpenSSH_9.3"), None);
+    }
+
+    #[test]
+    fn reads_os_release_fields() {
+        let os_release =
+            "NAME=\"Ubuntu\"\nVERSION_ID=\"20.04\"\nPRETTY_NAME=\"Ubuntu 20.04.4 LTS\"\n";
+        assert_eq!(
+            os_release_field(os_release, "PRETTY_NAME").as_deref(),
+            Some("Ubuntu 20.04.4 LTS")
+        );
+        assert_eq!(
+            os_release_field(os_release, "VERSION_ID").as_deref(),
+            Some("20.04")
+        );
+        assert_eq!(os_release_field(os_release, "ID"), None);
+    }
+
+    #[test]
+    fn strips_echo_and_prompt() {
+        assert_eq!(
+            strip_echo_and_prompt("uname -r\r\n5.4.0-109-generic\r\nroot@server01:~# "),
+            "5.4.0-109-generic"
+        );
+        assert_eq!(strip_echo_and_prompt("sleep 1\r\nroot@server01:~# "), "");
+    }
+
+    #[test]
+    fn unspecified_interfaces_are_probed_on_loopback() {
+        assert_eq!(
+            loopback("0.0.0.0:2222".parse().unwrap()),
+            "127.0.0.1:2222".parse().unwrap()
+        );
+        assert_eq!(
+            loopback("[::]:22".parse().unwrap()),
+            "[::1]:22".parse().unwrap()
+        );
+        assert_eq!(
+            loopback("192.0.2.1:22".parse().unwrap()),
+            "192.0.2.1:22".parse().unwrap()
+        );
+    }
+}
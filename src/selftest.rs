@@ -0,0 +1,260 @@
+//! `ssh-honeypot --self-test`: run every registered shell command against edge case
+//! input and report the ones that panic or hang.
+//!
+//! A panic in an argument parser (an index past the end of `args`, a byte offset in
+//! the middle of a character) ends the command with a fake segfault, which careful
+//! attackers notice in commands that never crash on a real system. Each
+//! command is run as root on a scratch filesystem for every persona, once per set
+//! of arguments straight through `Command::execute` and once per malformed line
+//! through the parser. Errors are fine, that's what real commands do with garbage;
+//! the exit code is 1 when anything panicked or didn't finish.
+
+use crate::app::PersonaKind;
+use crate::server::SshServerHandler;
+use shell::commands::busybox::write_embedded_layout;
+use shell::commands::{CommandContext, CommandError, HostIdentity, write_mount_files};
+use shell::filesystem::fs2::FileSystem;
+use std::any::Any;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+/// How long one invocation may take before it counts as hung
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// Length of the oversized argument
+const HUGE_ARGUMENT: usize = 64 * 1024;
+/// Number of arguments in the oversized argument list
+const MANY_ARGUMENTS: usize = 10_000;
+
+/// Argument lists every command is run with
+fn argument_cases() -> Vec<Vec<String>> {
+    let fixed: &[&[&str]] = &[
+        &[],
+        &[""],
+        &["-"],
+        &["--"],
+        &["--help"],
+        &["--version"],
+        &["-="],
+        &["--="],
+        &["--no-such-option"],
+        &["-9999999999999999999999"],
+        &["-1", "-1", "-1"],
+        &["/"],
+        &["."],
+        &["../../../../../.."],
+        &["/no/such/file"],
+        &["*"],
+        &["\0"],
+        &["\t\r\n"],
+        &["é", "日本語", "\u{202e}gpj.exe", "🦀"],
+        &["\u{301}", "\u{200b}", "\u{fffd}", "a\u{301}\u{301}\u{301}"],
+        &["-é", "--日本語", "-🦀"],
+    ];
+    let mut cases: Vec<Vec<String>> = fixed
+        .iter()
+        .map(|case| case.iter().map(|arg| arg.to_string()).collect())
+        .collect();
+    // A lone short option, without the value it may need
+    for flag in ('a'..='z').chain('A'..='Z').chain('0'..='9') {
+        cases.push(vec![format!("-{}", flag)]);
+    }
+    cases.push(vec!["A".repeat(HUGE_ARGUMENT)]);
+    cases.push(vec![format!("-{}", "n".repeat(HUGE_ARGUMENT))]);
+    cases.push(vec!["-n".to_string(); MANY_ARGUMENTS]);
+    cases
+}
+
+/// What follows the command name in the lines given to the parser
+const LINE_SUFFIXES: &[&str] = &[
+    "'unterminated",
+    "\"unterminated",
+    "\"a'b",
+    "\\",
+    "$(",
+    "$((",
+    "$((1/0))",
+    "$((9223372036854775807+1))",
+    "${",
+    "${!}",
+    "`",
+    "| |",
+    ">",
+    "<",
+    "2>&",
+    "<<",
+    "&& ||",
+    ";;",
+    "$'\\x",
+    "\"$(echo 'x)\"",
+];
+
+/// A fresh filesystem laid out the way an embedded persona's is, so nothing one
+/// command does affects the next
+fn scratch_filesystem(kind: PersonaKind, host: &HostIdentity) -> std::io::Result<FileSystem> {
+    let mut fs = FileSystem::default();
+    let os = kind.os_identity();
+    write_embedded_layout(&mut fs, &os)?;
+    host.write_files(&mut fs)?;
+    write_mount_files(&mut fs, &os, &host.root_fs_uuid)?;
+    Ok(fs)
+}
+
+/// What went wrong with one invocation
+enum Failure {
+    Panicked(String),
+    TimedOut,
+    /// The registry doesn't know a command it listed
+    NotFound,
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "non-string panic payload".to_string())
+}
+
+/// Run `invocation` on its own task so a panic is caught, and give up after [`TIMEOUT`]
+async fn check<F, T>(invocation: F) -> Result<T, Failure>
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::time::timeout(TIMEOUT, tokio::spawn(invocation)).await {
+        Ok(Ok(output)) => Ok(output),
+        Ok(Err(err)) if err.is_panic() => Err(Failure::Panicked(panic_message(err.into_panic()))),
+        Ok(Err(err)) => Err(Failure::Panicked(err.to_string())),
+        Err(_) => Err(Failure::TimedOut),
+    }
+}
+
+/// Shortened for the report, the oversized cases would fill the terminal
+fn describe(args: &[String]) -> String {
+    let shown = format!("{:?}", args);
+    if shown.chars().count() <= 80 {
+        return shown;
+    }
+    let prefix: String = shown.chars().take(60).collect();
+    format!(
+        "{}... ({} arguments, {} bytes)",
+        prefix,
+        args.len(),
+        shown.len()
+    )
+}
+
+/// Run every command of both personas and print what failed. Returns the process
+/// exit code
+pub async fn run() -> i32 {
+    let host = Arc::new(HostIdentity::new(None));
+    let cases = argument_cases();
+    let mut invocations = 0;
+    let mut failures = Vec::new();
+
+    for kind in [PersonaKind::Openssh, PersonaKind::Dropbear] {
+        let dispatcher = Arc::new(SshServerHandler::create_command_dispatcher(None, kind));
+        let commands = dispatcher.list_commands();
+        println!(
+            "Testing {} commands of the {:?} persona with {} argument lists and {} lines each",
+            commands.len(),
+            kind,
+            cases.len(),
+            LINE_SUFFIXES.len()
+        );
+
+        for name in commands {
+            let fs = match scratch_filesystem(kind, &host) {
+                Ok(fs) => Arc::new(RwLock::new(fs)),
+                Err(err) => {
+                    eprintln!("Failed to create the scratch filesystem: {}", err);
+                    return 2;
+                }
+            };
+            let mut context = CommandContext::new(
+                "/root".to_string(),
+                "root".to_string(),
+                "selftest".to_string(),
+                fs,
+                "self-test".to_string(),
+            );
+            context.os = kind.os_identity();
+            context.host = host.clone();
+            // A sleep operand would otherwise outlast the hang timeout
+            context.max_sleep = Duration::ZERO;
+
+            for args in &cases {
+                invocations += 1;
+                let outcome = {
+                    let dispatcher = dispatcher.clone();
+                    let (name, args, mut context) = (name.clone(), args.clone(), context.clone());
+                    check(
+                        async move { dispatcher.execute_command(&name, &args, &mut context).await },
+                    )
+                    .await
+                };
+                let failure = match outcome {
+                    Ok(Err(CommandError::NotFound(_))) => Failure::NotFound,
+                    Ok(_) => continue,
+                    Err(failure) => failure,
+                };
+                failures.push((kind, format!("{} {}", name, describe(args)), failure));
+            }
+
+            for suffix in LINE_SUFFIXES {
+                invocations += 1;
+                let line = format!("{} {}", name, suffix);
+                let outcome = {
+                    let dispatcher = dispatcher.clone();
+                    let (line, mut context) = (line.clone(), context.clone());
+                    check(async move { dispatcher.execute(&line, &mut context).await }).await
+                };
+                if let Err(failure) = outcome {
+                    failures.push((kind, format!("line {:?}", line), failure));
+                }
+            }
+        }
+    }
+
+    for (kind, invocation, failure) in &failures {
+        match failure {
+            Failure::Panicked(message) => {
+                println!("[PANIC] {:?}: {}: {}", kind, invocation, message)
+            }
+            Failure::TimedOut => println!(
+                "[HANG]  {:?}: {}: no answer after {}s",
+                kind,
+                invocation,
+                TIMEOUT.as_secs()
+            ),
+            Failure::NotFound => println!(
+                "[LOST]  {:?}: {}: listed by the registry but not found",
+                kind, invocation
+            ),
+        }
+    }
+    println!("{} invocations, {} failed", invocations, failures.len());
+    if failures.is_empty() { 0 } else { 1 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_cases_are_shortened() {
+        assert_eq!(describe(&["-n".to_string()]), "[\"-n\"]");
+        let long = describe(&["A".repeat(HUGE_ARGUMENT)]);
+        assert!(long.ends_with("(1 arguments, 65540 bytes)"), "{}", long);
+    }
+
+    #[tokio::test]
+    async fn panics_are_caught() {
+        assert!(matches!(
+            check(async { panic!("index out of bounds") }).await,
+            Err(Failure::Panicked(message)) if message == "index out of bounds"
+        ));
+        assert!(matches!(check(async { 7 }).await, Ok(7)));
+    }
+}
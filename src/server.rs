@@ -1,37 +1,167 @@
 use ssh_honeypot::abuseipdb::{AbuseIpError, Client as AbuseIpClient};
 use ssh_honeypot::db::DbMessage;
+use ssh_honeypot::ip::canonical_ip;
 use ssh_honeypot::ipapi;
+use ssh_honeypot::recording::{RECORDING_VERSION, RawStreamWriter, RecordingHeader};
+use crate::app::{PersonaKind, UnknownSubsystemPolicy};
+use crate::auth_rate::AuthSuccessRate;
+use crate::command_backend::CommandBackend;
+use crate::command_log::parsed_command;
+use crate::credential_alert::CredentialAlerts;
+use crate::exec_probe::ExecResponses;
+use crate::hassh::{ClientKexInit, KexInitSniffer};
+use crate::keys;
+use crate::probe_penalty::{self, ProbePenalty};
+use crate::rdns::{PtrLookup, ReverseDns};
+use crate::scanners::ScannerTagger;
+use crate::scp::{self, ScpSink};
 use crate::sftp::HoneypotSftpSession;
+use crate::upload::UploadStore;
+use crate::vulnerable::{self, VulnerableProfile};
+use crate::yara::YaraRules;
+use shell::commands::accounts::home_dir;
+use shell::commands::bash_history::write_bash_history;
+use shell::commands::busybox::login_banner;
 use shell::commands::{
-    CatCommand, CdCommand, ColonCommand, CommandContext, CommandDispatcher, CurlCommand,
-    DateCommand, EchoCommand, ExitCommand, ExportCommand, FalseCommand, FreeCommand, IdCommand,
-    LsCommand, PsCommand, PwdCommand, SudoCommand, TestCommand, TrueCommand, UnameCommand,
-    UnsetCommand, WgetCommand, WhoamiCommand,
+    ArchCommand, AwkCommand, BUSYBOX_APPLETS, BashHistory, BgCommand, BinaryFiles, BusyboxCommand,
+    CLEAR_SCREEN, Canaries, CatCommand, CdCommand, ClearCommand, ColonCommand, CommandContext,
+    CommandDispatcher, CommandLatency, CommandNotFoundHandler, CompgenCommand, CurlCommand,
+    DateCommand, DdCommand, DfCommand, DigCommand, DisownCommand, DnsResolver, DuCommand,
+    EchoCommand, EnvCommand, ExitCommand, ExportCommand, FalseCommand, FetchPolicy, FgCommand,
+    Firewall, FreeCommand, GetconfCommand, GetentCommand, HelpCommand, HexdumpCommand,
+    HistoryCommand, HostCommand, HostIdentity, HostnamectlCommand, IdCommand, IfconfigCommand,
+    IptablesCommand, JobsCommand, KillCommand, LastCommand, LastbCommand, LoginHistory, LsCommand,
+    LsbReleaseCommand, MountCommand, NohupCommand, NslookupCommand, PgrepCommand, PidofCommand,
+    PkillCommand, PowerCommand, ProcessTable, PsCommand, PwdCommand, SedCommand, SetsidCommand,
+    ShellEvent, SleepCommand, SshKeygenCommand, SudoCommand, TestCommand, TimeoutCommand,
+    TrueCommand, UfwCommand, UnameCommand, UnsetCommand, WatchCommand, WgetCommand, WhoamiCommand,
+    prompt_sign,
 };
-use shell::filesystem::fs2::FileSystem;
+use shell::completion::{complete, format_candidates};
+use shell::filesystem::fs2::{FileContent, FileSystem};
+use shell::filters::FILTER_NAMES;
 use async_trait::async_trait;
 use chrono::{DateTime, Local, Utc};
-use rand::{Rng, RngExt, rng};
+use rand::{RngExt, rng};
 use russh::keys::{HashAlg, PublicKey};
 use russh::server::{Auth, ChannelOpenHandle, Handler, Msg, Session};
-use russh::{Channel, ChannelId, ChannelMsg, Error, server};
+use russh::{Channel, ChannelId, ChannelMsg, Disconnect, Error, Pty, Sig, server};
+use sha2::{Digest, Sha256};
 use ssh_encoding::Error as SshEncodingError;
+use std::collections::{HashMap, VecDeque};
 use std::io::ErrorKind;
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock, PoisonError};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
 use tokio::sync::RwLock;
-use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::SendTimeoutError;
+use tokio::sync::{Semaphore, mpsc, oneshot};
+
+/// Upper bound for holding back the authentication banner in drip mode. Stays
+/// below the 30 second inactivity timeout so the connection isn't dropped by us
+const AUTH_BANNER_DRIP_LIMIT: std::time::Duration = std::time::Duration::from_secs(25);
+
+/// How long a new connection's record waits for the client's KEXINIT
+const KEX_INIT_WAIT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Commands of a session kept in memory. Each one is sent to the database as it
+/// is entered, this only bounds what a bot looping in a tarpit for hours costs us
+const MAX_RETAINED_COMMANDS: usize = 1000;
+/// Protocol anomaly kind of a shell input line past --max-input-line
+const OVERSIZED_INPUT_KIND: &str = "oversized_input";
+/// How long an auth attempt waits for room in a full database channel before
+/// its record is handed to a background task
+const AUTH_RECORD_WAIT: std::time::Duration = std::time::Duration::from_millis(50);
+/// Bytes buffered each way between an SFTP channel and the SFTP server
+const SFTP_RELAY_BUFFER: usize = 64 * 1024;
 
 #[derive(Clone, Default)]
 // Store session data
 struct SessionData {
     auth_id: String,
     session_id: Option<String>,
-    commands: Vec<String>,
+    /// The most recent commands, at most [`MAX_RETAINED_COMMANDS`]
+    commands: VecDeque<String>,
     start_time: DateTime<Utc>,
     prompt: String,
 }
 
+impl SessionData {
+    /// Remember `command`, forgetting the oldest one once the limit is reached
+    fn remember_command(&mut self, command: String) {
+        if self.commands.len() >= MAX_RETAINED_COMMANDS {
+            self.commands.pop_front();
+        }
+        self.commands.push_back(command);
+    }
+}
+
+/// Auth records of a connection on their way to the database task. One that
+/// doesn't fit in the channel within [`AUTH_RECORD_WAIT`] is sent by a background
+/// task so authentication isn't held up, and [`AuthRecords::flush`] waits for
+/// those before anything that refers to the auth row is sent
+#[derive(Default)]
+struct AuthRecords {
+    /// Task sending the records queued in the background, oldest first
+    backlog: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl AuthRecords {
+    async fn send(
+        &mut self,
+        db_tx: &mpsc::Sender<DbMessage>,
+        message: DbMessage,
+        peer: SocketAddr,
+    ) {
+        // A record sent directly would pass the ones still waiting
+        if let Some(earlier) = self.backlog.take().filter(|backlog| !backlog.is_finished()) {
+            self.queue(db_tx, Some(earlier), message);
+            return;
+        }
+        match db_tx.send_timeout(message, AUTH_RECORD_WAIT).await {
+            Ok(()) => {}
+            Err(SendTimeoutError::Timeout(message)) => {
+                log::warn!(
+                    "Database channel full, recording auth from {} in the background",
+                    peer
+                );
+                self.queue(db_tx, None, message);
+            }
+            Err(SendTimeoutError::Closed(_)) => {
+                log::error!("Failed to send RecordAuth to db task: channel closed");
+            }
+        }
+    }
+
+    /// Send `message` from a background task, after the records of `earlier`
+    fn queue(
+        &mut self,
+        db_tx: &mpsc::Sender<DbMessage>,
+        earlier: Option<tokio::task::JoinHandle<()>>,
+        message: DbMessage,
+    ) {
+        let db_tx = db_tx.clone();
+        self.backlog = Some(tokio::spawn(async move {
+            if let Some(earlier) = earlier {
+                let _ = earlier.await;
+            }
+            if let Err(err) = db_tx.send(message).await {
+                log::error!("Failed to send RecordAuth to db task: {}", err);
+            }
+        }));
+    }
+
+    /// Wait until every record is in the database channel
+    async fn flush(&mut self) {
+        if let Some(backlog) = self.backlog.take() {
+            let _ = backlog.await;
+        }
+    }
+}
+
 // Define our SSH server handler
 pub struct SshHandler {
     peer: SocketAddr,
@@ -42,20 +172,104 @@ pub struct SshHandler {
     current_cmd: String,
     pending_block: String,
     cwd: String,
+    /// Environment the last command line left, with what it exported and the
+    /// OLDPWD `cd -` goes back to. None before the first one
+    env_vars: Option<HashMap<String, String>>,
     hostname: String,
     disable_cli_interface: bool,
     disable_exec: bool,
     authentication_banner: Option<String>,
     tarpit: bool,
+    auth_banner_drip: bool,
     fs2: Arc<RwLock<FileSystem>>,
     /*send_task: Option<tokio::task::JoinHandle<()>>,
     send_task_tx: Option<mpsc::Sender<String>>,*/
     enable_sftp: bool,
+    enable_scp: bool,
+    /// Bytes of an uploaded file that are kept, the rest is only hashed
+    max_upload_size: u64,
+    upload_store: Arc<UploadStore>,
+    yara_rules: Option<Arc<YaraRules>>,
+    unknown_subsystems: UnknownSubsystemPolicy,
+    /// Channels of accepted unknown subsystems, SFTP and answered exec probes, their data is
+    /// discarded (SFTP's is read by the channel's own task)
+    drained_channels: Vec<ChannelId>,
+    /// Hands the SFTP server to the task of a session channel that asks for the subsystem
+    sftp_handoffs: Vec<(ChannelId, oneshot::Sender<HoneypotSftpSession>)>,
+    /// What rsync, git and configured exec commands get instead of the taunt
+    exec_responses: Arc<ExecResponses>,
     abuse_ip_client: Option<Arc<AbuseIpClient>>,
     reject_all_auth: bool,
+    /// Decides each auth attempt instead of reject_all_auth, see --auth-success-rate
+    auth_success_rate: Option<Arc<AuthSuccessRate>>,
     command_dispatcher: CommandDispatcher,
+    /// Fake process list of this session, kept across commands so kill sticks
+    process_table: Arc<RwLock<ProcessTable>>,
+    /// iptables and ufw rules of this session
+    firewall: Arc<RwLock<Firewall>>,
     welcome_message: String,
+    /// Shown after the welcome message, see --legal-banner-file
+    legal_banner: Option<String>,
+    /// The legal banner was shown and nothing has been typed since
+    awaiting_banner_response: bool,
+    /// A root shell ran reboot or the like, the session ends after the current command
+    shutting_down: bool,
+    shutdown_close_delay: std::time::Duration,
+    persona: PersonaKind,
+    /// Columns and rows of the client's terminal, from pty-req and window-change
+    terminal_size: (u32, u32),
+    fetch_policy: Arc<FetchPolicy>,
+    host_identity: Arc<HostIdentity>,
+    canaries: Arc<Canaries>,
+    dns_resolver: Arc<dyn DnsResolver>,
+    login_history: Arc<dyn LoginHistory>,
+    command_latency: CommandLatency,
+    /// Longest a sleep really waits
+    max_sleep: std::time::Duration,
+    /// Shell commands run before the session is closed, see --disconnect-after-commands
+    disconnect_after_commands: Option<u32>,
+    /// Bytes an input line may grow to, see --max-input-line
+    max_input_line: usize,
+    /// Bytes of the current line dropped past max_input_line
+    dropped_input: usize,
+    /// Written to new home directories, typed commands are added with --append-bash-history
+    bash_history: BashHistory,
+    /// Milliseconds waited before answering an auth attempt, see --auth-delay-min-ms
+    auth_delay: RangeInclusive<u64>,
+    /// Failed auth attempts before the client is disconnected, see --max-auth-tries
+    max_auth_tries: u32,
+    /// Auth attempts of this connection rejected so far
+    auth_failures: u32,
+    /// Handle of the running session, set before its first message is handled. Auth
+    /// handlers get no [`Session`] to send the disconnect of --max-auth-tries with
+    session_handle: Arc<OnceLock<server::Handle>>,
+    /// Start of a UTF-8 character whose other bytes are in the next packet
+    partial_utf8: Vec<u8>,
+    /// What cat shows for binary files
+    binary_files: BinaryFiles,
+    /// Non-empty command lines run in the shell so far
+    commands_run: u32,
+    /// Container some read-only commands are answered by, see --command-backend
+    command_backend: Option<Arc<CommandBackend>>,
     ip_api_client: Option<Arc<ipapi::Client>>,
+    /// Bounds the AbuseIPDB / ip-api lookups running at once, None when the
+    /// background lookups of new connections are disabled
+    enrichment_permits: Option<Arc<Semaphore>>,
+    /// Directory for raw byte stream recordings, None when disabled
+    record_raw_streams: Option<PathBuf>,
+    /// Set with --quiet-scanner-mode, known scanners it tags get no shell
+    quiet_scanners: Option<Arc<ScannerTagger>>,
+    reverse_dns: Option<Arc<ReverseDns>>,
+    /// Counts password attempts by credential pair with --credential-alert-ips
+    credential_alerts: Option<Arc<CredentialAlerts>>,
+    /// Auth records not yet in the database channel
+    auth_records: AuthRecords,
+    /// Recording of the current interactive session
+    raw_stream: Option<RawStreamWriter>,
+    /// Whether the last key was Tab, a second one lists the completions
+    tab_pressed: bool,
+    /// Upload in progress from an `scp -t` exec request and the channel it runs on
+    scp: Option<(ChannelId, ScpSink)>,
 }
 
 // Implementation of the Handler trait for our SSH server
@@ -71,13 +285,8 @@ impl Handler for SshHandler {
         async move {
             self.user = Some(user.to_string());
             self.cwd = format!("/home/{}", user);
-            if !self.disable_cli_interface {
-                self.ensure_user_home_exists().await;
-            }
             let peer_str = self.peer.ip().to_string();
 
-            // We'll get the actual UUID back from the database
-
             log::info!(
                 "Password auth attempt - Username: {}, Password: {}, IP: {}",
                 user,
@@ -91,53 +300,34 @@ impl Handler for SshHandler {
             // Get cached IPAPI data
             let ipapi_data = self.get_ipapi_data().await;
 
-            // Record authentication attempt in database and get the UUID back
-            let (response_tx, response_rx) = tokio::sync::oneshot::channel();
-            match self
-                .db_tx
-                .send(DbMessage::RecordAuth {
-                    timestamp: Utc::now(),
-                    ip: peer_str,
-                    username: user.to_string(),
-                    auth_type: "password".to_string(),
-                    password: Some(password.to_string()),
-                    public_key: None,
-                    successful: !self.reject_all_auth, // Accept/reject based on flag
-                    abuseipdb_data,
-                    ipapi_data,
-                    response_tx,
-                })
-                .await
-            {
-                Ok(_) => match response_rx.await {
-                    Ok(Ok(auth_id)) => {
-                        log::trace!("Recorded auth with UUID: {}", auth_id);
-                        self.auth_id = Some(auth_id);
-                    }
-                    Ok(Err(e)) => {
-                        log::error!("Database error recording auth: {}", e);
-                    }
-                    Err(e) => {
-                        log::error!("Failed to receive auth response: {}", e);
-                    }
-                },
-                Err(err) => {
-                    log::error!("Failed to send RecordAuth to db task: {}", err)
-                }
-            };
-
-            // Simulate a small delay like a real SSH server
-            let delay = rng().next_u64() % 501;
-            log::trace!("Letting client wait for {}", delay);
-            tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
-            if self.reject_all_auth {
-                log::debug!("Rejected authentication attempt");
-                Ok(Auth::Reject {
-                    proceed_with_methods: None,
-                    partial_success: false,
-                })
+            let accepted = self.accepts_auth();
+            let auth_id = new_auth_id();
+            self.record_auth(DbMessage::RecordAuth {
+                auth_id: auth_id.clone(),
+                timestamp: Utc::now(),
+                ip: peer_str,
+                username: user.to_string(),
+                auth_type: "password".to_string(),
+                password: Some(password.to_string()),
+                public_key: None,
+                public_key_algorithm: None,
+                public_key_bits: None,
+                public_key_blob: None,
+                successful: accepted,
+                abuseipdb_data,
+                ipapi_data,
+            })
+            .await;
+            self.auth_id = Some(auth_id);
+
+            self.auth_delay().await;
+            if !accepted {
+                Ok(self.reject_auth().await)
             } else {
                 log::debug!("Accepted new connection");
+                if !self.disable_cli_interface {
+                    self.ensure_user_home_exists().await;
+                }
                 Ok(Auth::Accept)
             }
         }
@@ -152,18 +342,22 @@ impl Handler for SshHandler {
         async move {
             self.user = Some(user.to_string());
             self.cwd = format!("/home/{}", user);
-            if !self.disable_cli_interface {
-                self.ensure_user_home_exists().await;
-            }
             let key_str = format!("{}", public_key.key_data().fingerprint(HashAlg::Sha512));
+            let algorithm = public_key.algorithm().to_string();
+            let bits = keys::key_bits(public_key);
+            // authorized_keys form is `<algorithm> <base64> [comment]`
+            let blob = public_key
+                .to_openssh()
+                .ok()
+                .and_then(|line| line.split_whitespace().nth(1).map(str::to_string));
             let peer_str = self.peer.ip().to_string();
 
-            // We'll get the actual UUID back from the database
-
             log::info!(
-                "Public key auth attempt - Username: {}, Key: {}, IP: {}",
+                "Public key auth attempt - Username: {}, Key: {} {} ({} bits), IP: {}",
                 user,
+                algorithm,
                 key_str,
+                bits.map_or("?".to_string(), |bits| bits.to_string()),
                 peer_str
             );
 
@@ -173,54 +367,35 @@ impl Handler for SshHandler {
             // Get cached IPAPI data
             let ipapi_data = self.get_ipapi_data().await;
 
-            // Record authentication attempt in database and get the UUID back
-            let (response_tx, response_rx) = tokio::sync::oneshot::channel();
-            match self
-                .db_tx
-                .send(DbMessage::RecordAuth {
-                    timestamp: Utc::now(),
-                    ip: peer_str,
-                    username: user.to_string(),
-                    auth_type: "publickey".to_string(),
-                    password: None,
-                    public_key: Some(key_str),
-                    successful: !self.reject_all_auth, // Accept/reject based on flag
-                    abuseipdb_data,
-                    ipapi_data,
-                    response_tx,
-                })
-                .await
-            {
-                Ok(_) => match response_rx.await {
-                    Ok(Ok(auth_id)) => {
-                        log::trace!("Recorded auth with UUID: {}", auth_id);
-                        self.auth_id = Some(auth_id);
-                    }
-                    Ok(Err(e)) => {
-                        log::error!("Database error recording auth: {}", e);
-                    }
-                    Err(e) => {
-                        log::error!("Failed to receive auth response: {}", e);
-                    }
-                },
-                Err(err) => {
-                    log::error!("Failed to send RecordAuth to db task: {}", err)
-                }
-            };
-
-            // Simulate a small delay like a real SSH server
-            let delay = rng().next_u64() % 501;
-            log::trace!("Letting client wait for {}", delay);
-            tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
-
-            if self.reject_all_auth {
-                log::debug!("Rejected authentication attempt");
-                Ok(Auth::Reject {
-                    proceed_with_methods: None,
-                    partial_success: false,
-                })
+            let accepted = self.accepts_auth();
+            let auth_id = new_auth_id();
+            self.record_auth(DbMessage::RecordAuth {
+                auth_id: auth_id.clone(),
+                timestamp: Utc::now(),
+                ip: peer_str,
+                username: user.to_string(),
+                auth_type: "publickey".to_string(),
+                password: None,
+                public_key: Some(key_str),
+                public_key_algorithm: Some(algorithm),
+                public_key_bits: bits,
+                public_key_blob: blob,
+                successful: accepted,
+                abuseipdb_data,
+                ipapi_data,
+            })
+            .await;
+            self.auth_id = Some(auth_id);
+
+            self.auth_delay().await;
+
+            if !accepted {
+                Ok(self.reject_auth().await)
             } else {
                 log::debug!("Accepted new connection");
+                if !self.disable_cli_interface {
+                    self.ensure_user_home_exists().await;
+                }
                 Ok(Auth::Accept)
             }
         }
@@ -234,6 +409,21 @@ impl Handler for SshHandler {
                 "Displaying banner: {:?}",
                 self.authentication_banner.as_ref()
             );
+            if self.auth_banner_drip
+                && let Some(banner) = &self.authentication_banner
+            {
+                // The banner is a single SSH message, so it can't be sent byte by byte like
+                // tarpit_data does. Wait as long as dripping it would have taken instead
+                let delay = {
+                    let mut rng = rng();
+                    (0..banner.len())
+                        .map(|_| std::time::Duration::from_millis(rng.random_range(10..700)))
+                        .sum::<std::time::Duration>()
+                        .min(AUTH_BANNER_DRIP_LIMIT)
+                };
+                log::debug!("Dripping authentication banner for {:?}", delay);
+                tokio::time::sleep(delay).await;
+            }
             Ok(self.authentication_banner.clone())
         }
     }
@@ -245,6 +435,12 @@ impl Handler for SshHandler {
     ) -> impl Future<Output = Result<(), Self::Error>> + Send {
         async move {
             log::debug!("Channel EOF on channel: {}, closing channel", channel);
+            // The scp client closes its end once everything is sent
+            if self.scp.take().is_some() {
+                session.exit_status_request(channel, 0)?;
+            }
+            self.drained_channels.retain(|drained| *drained != channel);
+            self.sftp_handoffs.retain(|(id, _)| *id != channel);
             session.close(channel)?;
             Ok(())
         }
@@ -262,9 +458,43 @@ impl Handler for SshHandler {
                 channel.id(),
                 self.peer.ip()
             );
+            // Everything the channel records refers to the auth row
+            self.auth_records.flush().await;
             if let (Some(user), Some(auth_id)) = (&self.user, &self.auth_id) {
                 let start_time = Utc::now();
 
+                self.raw_stream = self.record_raw_streams.as_deref().and_then(|dir| {
+                    let header = RecordingHeader {
+                        version: RECORDING_VERSION,
+                        auth_id: auth_id.clone(),
+                        peer: self.peer.to_string(),
+                        started_at: start_time,
+                        width: self.terminal_size.0,
+                        height: self.terminal_size.1,
+                    };
+                    match RawStreamWriter::create(dir, &header, &channel.id().to_string()) {
+                        Ok(writer) => {
+                            log::debug!(
+                                "Recording raw session stream to {}",
+                                writer.path().display()
+                            );
+                            Some(writer)
+                        }
+                        Err(err) => {
+                            log::error!(
+                                "Failed to create raw stream recording in {}: {}",
+                                dir.display(),
+                                err
+                            );
+                            None
+                        }
+                    }
+                });
+                let raw_stream_path = self
+                    .raw_stream
+                    .as_ref()
+                    .map(|writer| writer.path().display().to_string());
+
                 // Record the start of a live session so the dashboard can show
                 // currently active sessions (end_time IS NULL).
                 let session_id = {
@@ -274,6 +504,7 @@ impl Handler for SshHandler {
                         .send(DbMessage::RecordSessionStart {
                             auth_id: auth_id.clone(),
                             start_time,
+                            raw_stream_path,
                             response_tx,
                         })
                         .await
@@ -300,9 +531,9 @@ impl Handler for SshHandler {
                 let data = SessionData {
                     auth_id: auth_id.clone(),
                     session_id,
-                    commands: Vec::new(),
+                    commands: VecDeque::new(),
                     start_time,
-                    prompt: format!("{}@{}:~$ ", user, self.hostname),
+                    prompt: format!("{}@{}:~{} ", user, self.hostname, prompt_sign(user)),
                 };
                 self.session_data = data.clone();
 
@@ -311,9 +542,11 @@ impl Handler for SshHandler {
                 //let (channel_reader, channel_writer) = channel.split();
 
                 // Handle the shell session within this future
+                let (sftp_tx, sftp_rx) = oneshot::channel();
+                self.sftp_handoffs.push((channel.id(), sftp_tx));
                 log::trace!("Starting tokio task for shell session saving");
                 tokio::spawn(async move {
-                    handle_shell_session(channel, data, db_tx).await;
+                    handle_shell_session(channel, data, db_tx, sftp_rx).await;
                 });
 
                 //let (sender_task, recv_task) = mpsc::channel::<String>(1000);
@@ -337,6 +570,23 @@ impl Handler for SshHandler {
         session: &mut Session,
     ) -> impl Future<Output = Result<(), Self::Error>> + Send {
         async move {
+            if self
+                .scp
+                .as_ref()
+                .is_some_and(|(scp_channel, _)| *scp_channel == channel)
+            {
+                return self.receive_scp(channel, data, session).await;
+            }
+            if self.drained_channels.contains(&channel) {
+                log::trace!("Discarding {} bytes on subsystem channel", data.len());
+                return Ok(());
+            }
+
+            self.record_raw(|writer| writer.input(data));
+            if data.contains(&b'\r') || data.contains(&b'\n') {
+                self.record_raw(RawStreamWriter::flush);
+            }
+
             if self.disable_cli_interface {
                 log::debug!("Cli interface is disabled");
                 session.channel_failure(channel)?;
@@ -394,24 +644,73 @@ impl Handler for SshHandler {
             // CTRL+C
             if data == [3] {
                 log::trace!("Received ctrl+c, clearing current command");
-                self.current_cmd = String::new();
-                let prompt = format!("\r\n{}", self.session_data.prompt);
-                match self.tarpit_data(session, channel, prompt.as_bytes()).await {
+                self.interrupt_line(session, channel).await;
+                return Ok(());
+            }
+
+            // TAB, completes the command name or path. A second Tab lists the candidates
+            let double_tab = std::mem::take(&mut self.tab_pressed);
+            if data == [9] {
+                self.tab_pressed = true;
+                self.complete_line(session, channel, double_tab).await;
+                return Ok(());
+            }
+
+            // CTRL+L, clears the screen and redraws the line being edited like readline
+            if data == [12] {
+                log::trace!("Received ctrl+l, clearing screen");
+                let prompt = if self.pending_block.is_empty() {
+                    self.session_data.prompt.as_str()
+                } else {
+                    "> "
+                };
+                let redraw = format!("{}{}{}", CLEAR_SCREEN, prompt, self.current_cmd);
+                match self.tarpit_data(session, channel, redraw.as_bytes()).await {
                     Ok(_) => {
-                        log::trace!("Send prompt to client")
+                        log::trace!("Sent clear screen to client")
                     }
                     Err(err) => {
-                        log::error!("Failed to send prompt to client: {}", err)
+                        log::error!("Failed to send clear screen to client: {}", err)
                     }
                 }
                 return Ok(());
             }
 
-            if let Ok(cmd) = String::from_utf8(data.to_vec()) {
+            let mut bytes = std::mem::take(&mut self.partial_utf8);
+            bytes.extend_from_slice(data);
+            if let Some((cmd, partial)) = decode_utf8(&bytes) {
+                let cmd = cmd.to_string();
+                self.partial_utf8 = partial.to_vec();
                 log::trace!("data: '{}' ({:?})", cmd, data);
 
                 if cmd.ends_with("\n") || cmd.ends_with("\r") {
-                    self.session_data.commands.push(self.current_cmd.clone());
+                    // A pasted line arrives in one piece with its newline
+                    self.append_input(session, channel, cmd.trim_end_matches(['\r', '\n']))
+                        .await;
+                    let input_bytes = (self.dropped_input > 0)
+                        .then_some(self.current_cmd.len() + self.dropped_input);
+                    if let Some(input_bytes) = input_bytes {
+                        log::info!(
+                            "[{}] Running the first {} of {} bytes typed on the line",
+                            self.session_data.auth_id,
+                            self.current_cmd.len(),
+                            input_bytes
+                        );
+                    }
+                    self.dropped_input = 0;
+                    self.session_data.remember_command(self.current_cmd.clone());
+                    {
+                        let user = self.user.as_deref().unwrap_or("user");
+                        let mut fs2 = self.fs2.write().await;
+                        if let Err(err) = self.bash_history.command_typed(
+                            &mut fs2,
+                            self.peer.ip(),
+                            user,
+                            &self.current_cmd,
+                        ) {
+                            log::debug!("Failed to add to the .bash_history of {}: {}", user, err);
+                        }
+                    }
 
                     // Record command in database
                     match self
@@ -420,6 +719,11 @@ impl Handler for SshHandler {
                             auth_id: self.session_data.auth_id.clone(),
                             timestamp: Utc::now(),
                             command: self.current_cmd.clone(),
+                            parsed_command: parsed_command(
+                                &self.current_cmd,
+                                self.user.as_deref().unwrap_or("user"),
+                            ),
+                            input_bytes,
                         })
                         .await
                     {
@@ -431,6 +735,18 @@ impl Handler for SshHandler {
                         }
                     };
 
+                    if self.awaiting_banner_response && !self.current_cmd.trim().is_empty() {
+                        self.awaiting_banner_response = false;
+                        let response = DbMessage::RecordLegalBannerResponse {
+                            auth_id: self.session_data.auth_id.clone(),
+                            timestamp: Utc::now(),
+                            command: self.current_cmd.clone(),
+                        };
+                        if let Err(err) = self.db_tx.send(response).await {
+                            log::error!("Failed to send legal banner response to db: {}", err);
+                        }
+                    }
+
                     if self.current_cmd == "exit" || self.current_cmd == "logout" {
                         log::debug!(
                             "Closing session {} due to exit command",
@@ -479,6 +795,9 @@ impl Handler for SshHandler {
                     }
 
                     // Process the command
+                    if !self.pending_block.trim().is_empty() {
+                        self.commands_run += 1;
+                    }
                     let (response, exit_requested) = self.process_command().await;
                     self.current_cmd = String::new();
                     self.pending_block = String::new();
@@ -527,7 +846,53 @@ impl Handler for SshHandler {
                         return Err(Error::Disconnect);
                     }
 
-                    let prompt = format!("\r\n{} ", self.session_data.prompt);
+                    if self.shutting_down {
+                        log::debug!(
+                            "Closing session {} as the system went down",
+                            self.session_data.auth_id
+                        );
+                        tokio::time::sleep(self.shutdown_close_delay).await;
+                        if let Err(err) = self
+                            .tarpit_data(
+                                session,
+                                channel,
+                                "\r\nConnection to host closed by remote host.\r\n".as_bytes(),
+                            )
+                            .await
+                        {
+                            log::error!("Failed to send closing connection to client: {}", err)
+                        }
+                        return Err(Error::Disconnect);
+                    }
+
+                    if let Some(limit) = self.disconnect_after_commands
+                        && self.commands_run >= limit
+                    {
+                        log::info!(
+                            "Closing session {} after {} commands",
+                            self.session_data.auth_id,
+                            self.commands_run
+                        );
+                        self.record_cut_short().await;
+                        if let Err(err) = self
+                            .tarpit_data(
+                                session,
+                                channel,
+                                "\r\nConnection to host closed by remote host.\r\n".as_bytes(),
+                            )
+                            .await
+                        {
+                            log::error!("Failed to send closing connection to client: {}", err)
+                        }
+                        return Err(Error::Disconnect);
+                    }
+
+                    // After clear the prompt goes to the top line
+                    let prompt = if response.ends_with(CLEAR_SCREEN) {
+                        self.session_data.prompt.clone()
+                    } else {
+                        format!("\r\n{} ", self.session_data.prompt)
+                    };
                     match self.tarpit_data(session, channel, prompt.as_bytes()).await {
                         Ok(_) => {
                             log::trace!("Sent prompt to client")
@@ -541,19 +906,16 @@ impl Handler for SshHandler {
                     };
                 } else {
                     log::trace!("Appending to command: {}", cmd);
-                    if !cmd.is_empty() {
-                        self.current_cmd += &*cmd;
-                        match self.tarpit_data(session, channel, cmd.as_bytes()).await {
-                            Ok(_) => {
-                                log::trace!("Sent character back to client")
-                            }
-                            Err(err) => {
-                                log::error!("Failed to send character back to client: {}", err)
-                            }
-                        };
-                    }
+                    self.append_input(session, channel, &cmd).await;
                 }
             } else {
+                let carried = bytes.len() - data.len();
+                if carried > 0 {
+                    log::debug!(
+                        "Dropping {} carried bytes of a UTF-8 character that wasn't continued",
+                        carried
+                    );
+                }
                 log::debug!("binary data ({:?})", data);
                 // Handle binary data (could be control characters)
                 // Check for CTRL+D (ASCII 4) in raw data
@@ -581,6 +943,83 @@ impl Handler for SshHandler {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
+    fn pty_request(
+        &mut self,
+        channel: ChannelId,
+        term: &str,
+        col_width: u32,
+        row_height: u32,
+        _pix_width: u32,
+        _pix_height: u32,
+        _modes: &[(Pty, u32)],
+        _session: &mut Session,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        async move {
+            log::debug!(
+                "PTY request on channel {}: {} {}x{}",
+                channel,
+                term,
+                col_width,
+                row_height
+            );
+            self.set_terminal_size(col_width, row_height);
+            self.record_terminal_event("pty-req", Some(term.to_string()))
+                .await;
+            Ok(())
+        }
+    }
+
+    fn window_change_request(
+        &mut self,
+        channel: ChannelId,
+        col_width: u32,
+        row_height: u32,
+        _pix_width: u32,
+        _pix_height: u32,
+        _session: &mut Session,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        async move {
+            log::debug!(
+                "Window change on channel {}: {}x{} for {:?}",
+                channel,
+                col_width,
+                row_height,
+                self.auth_id
+            );
+            self.set_terminal_size(col_width, row_height);
+            self.record_terminal_event("window-change", None).await;
+            Ok(())
+        }
+    }
+
+    fn signal(
+        &mut self,
+        channel: ChannelId,
+        signal: Sig,
+        session: &mut Session,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        async move {
+            let name = signal_name(&signal);
+            log::info!(
+                "Client sent SIG{} on channel {} for {:?}",
+                name,
+                channel,
+                self.auth_id
+            );
+            self.record_channel_signal(name).await;
+            // SIGINT does what Ctrl+C typed into the terminal does
+            if matches!(signal, Sig::INT)
+                && !self.disable_cli_interface
+                && !self.drained_channels.contains(&channel)
+                && self.scp.is_none()
+            {
+                self.interrupt_line(session, channel).await;
+            }
+            Ok(())
+        }
+    }
+
     fn shell_request(
         &mut self,
         channel: ChannelId,
@@ -593,9 +1032,30 @@ impl Handler for SshHandler {
                 session.channel_failure(channel)?;
                 return Ok(());
             }
+            if let Some(scanner) = self.quiet_scanner().await {
+                log::info!(
+                    "Refusing a shell to the {} scanner at {}: auth_id: {:?}",
+                    scanner,
+                    self.peer,
+                    self.auth_id
+                );
+                session.channel_failure(channel)?;
+                return Ok(());
+            }
+
+            {
+                let user = self.user.as_deref().unwrap_or("user");
+                let mut fs2 = self.fs2.write().await;
+                if let Err(err) = self.bash_history.session_started(&mut fs2, self.peer.ip(), user) {
+                    log::debug!("Failed to write the .bash_history of {}: {}", user, err);
+                }
+            }
 
             // Send a welcome message
-            let welcome = Self::generate_welcome_message(&self.welcome_message);
+            let welcome = match self.persona {
+                PersonaKind::Openssh => Self::generate_welcome_message(&self.welcome_message),
+                PersonaKind::Dropbear => login_banner(&self.welcome_message),
+            };
 
             match self.tarpit_data(session, channel, welcome.as_bytes()).await {
                 Ok(_) => {
@@ -606,6 +1066,10 @@ impl Handler for SshHandler {
                 }
             };
 
+            if let Some(banner) = self.legal_banner.clone() {
+                self.show_legal_banner(session, channel, &banner).await;
+            }
+
             // Send prompt
             let prompt = self.session_data.prompt.clone();
             match self.tarpit_data(session, channel, prompt.as_bytes()).await {
@@ -637,6 +1101,11 @@ impl Handler for SshHandler {
                     auth_id: self.session_data.auth_id.clone(),
                     timestamp: Utc::now(),
                     command: command.to_string(),
+                    parsed_command: parsed_command(
+                        &command,
+                        self.user.as_deref().unwrap_or("user"),
+                    ),
+                    input_bytes: None,
                 })
                 .await
             {
@@ -648,12 +1117,71 @@ impl Handler for SshHandler {
                 }
             };
 
+            if self.enable_scp
+                && let Some((target, target_is_dir)) = ScpSink::parse_command(&command)
+            {
+                let target = if target.starts_with('/') {
+                    target
+                } else {
+                    format!("{}/{}", self.cwd, target)
+                };
+                let target = self.fs2.read().await.resolve_absolute_path(&target);
+                let target_is_dir = target_is_dir
+                    || matches!(
+                        self.fs2
+                            .read()
+                            .await
+                            .follow_symlink(&target)
+                            .map(|e| e.file_content),
+                        Ok(Some(FileContent::Directory(_)))
+                    );
+                log::info!(
+                    "SCP upload to {} started: auth_id: {:?}",
+                    target,
+                    self.auth_id
+                );
+                self.scp = Some((
+                    channel,
+                    ScpSink::new(target, target_is_dir, self.max_upload_size),
+                ));
+                session.channel_success(channel)?;
+                // The sink speaks first, a single \0 tells the client to go ahead
+                session.data(channel, vec![0])?;
+                return Ok(());
+            }
+
             if self.disable_exec {
                 log::debug!("Exec request denied (exec disabled): {}", command);
                 session.channel_failure(channel)?;
                 return Ok(());
             }
 
+            if let Some(answer) = self.exec_responses.answer(&command) {
+                log::info!(
+                    "Answering {} exec probe: {}: auth_id: {:?}",
+                    answer.tool,
+                    command,
+                    self.auth_id
+                );
+                self.record_exec_probe(&answer.tool, &command).await;
+                session.channel_success(channel)?;
+                if !answer.stdout.is_empty() {
+                    session.data(channel, answer.stdout)?;
+                }
+                if !answer.stderr.is_empty() {
+                    session.extended_data(channel, 1, answer.stderr)?;
+                }
+                match answer.exit_status {
+                    Some(status) => {
+                        session.exit_status_request(channel, status)?;
+                        session.eof(channel)?;
+                        session.close(channel)?;
+                    }
+                    None => self.drained_channels.push(channel),
+                }
+                return Ok(());
+            }
+
             let answer = format!(
                 "You thought I'm going to execute '{}'. But jokes on you. You are now my slave.",
                 command
@@ -685,38 +1213,61 @@ impl Handler for SshHandler {
                         "SFTP subsystem request denied (SFTP disabled): auth_id: {:?}",
                         self.auth_id
                     );
+                    self.record_subsystem_request(name, false).await;
                     session.channel_failure(channel)?;
                     return Ok(());
                 }
 
                 log::info!("Starting SFTP subsystem for auth_id: {:?}", self.auth_id);
-
-                if let Some(auth_id) = &self.auth_id {
+                self.record_subsystem_request(name, self.auth_id.is_some())
+                    .await;
+
+                let handoff = self
+                    .sftp_handoffs
+                    .iter()
+                    .position(|(id, _)| *id == channel)
+                    .map(|i| self.sftp_handoffs.swap_remove(i).1);
+                if let (Some(auth_id), Some(handoff)) = (&self.auth_id, handoff) {
                     // Create SFTP session handler
-                    let _sftp_handler = HoneypotSftpSession::new(
+                    let sftp_handler = HoneypotSftpSession::new(
                         self.db_tx.clone(),
                         self.fs2.clone(),
                         auth_id.clone(),
+                        self.canaries.clone(),
+                        self.max_upload_size,
+                        self.upload_store.clone(),
+                        self.yara_rules.clone(),
                     );
 
                     // Accept the subsystem request
                     session.channel_success(channel)?;
 
-                    // Run the SFTP server on this channel
-                    // Note: The actual channel stream handling would need to be implemented
-                    // based on the specific russh-sftp requirements
-                    log::info!("SFTP subsystem started for channel {}", channel);
-
-                    // For now, just log that SFTP was requested
-                    // In a complete implementation, you would need to handle the channel data
-                    // and pass it to the SFTP handler
+                    // The channel's task runs the SFTP server until the channel closes
+                    self.drained_channels.push(channel);
+                    if handoff.send(sftp_handler).is_err() {
+                        log::warn!("Channel {} closed before SFTP started", channel);
+                    } else {
+                        log::info!("SFTP subsystem started for channel {}", channel);
+                    }
                 } else {
-                    log::error!("No auth_id available for SFTP session");
+                    log::error!("No session available for SFTP on channel {}", channel);
                     session.channel_failure(channel)?;
                 }
             } else {
-                log::debug!("Unsupported subsystem: {}", name);
-                session.channel_failure(channel)?;
+                let accept = self.unknown_subsystems == UnknownSubsystemPolicy::Accept;
+                log::info!(
+                    "Unsupported subsystem {} requested, {}: auth_id: {:?}",
+                    name,
+                    if accept { "accepting" } else { "refusing" },
+                    self.auth_id
+                );
+                self.record_subsystem_request(name, accept).await;
+                if accept {
+                    self.drained_channels.push(channel);
+                    session.channel_success(channel)?;
+                } else {
+                    session.channel_failure(channel)?;
+                }
             }
 
             Ok(())
@@ -734,34 +1285,643 @@ impl Handler for SshHandler {
 
 impl SshHandler {
     // Process commands and return fake responses, plus whether the session should end.
-    async fn process_command(&mut self) -> (String, bool) {
-        let cmd = if self.pending_block.is_empty() {
-            self.current_cmd.clone()
-        } else {
-            self.pending_block.clone()
+    /// Remember the terminal size. Clients may send 0 for unknown dimensions,
+    /// those keep the previous value
+    fn set_terminal_size(&mut self, columns: u32, rows: u32) {
+        if columns > 0 {
+            self.terminal_size.0 = columns;
+        }
+        if rows > 0 {
+            self.terminal_size.1 = rows;
+        }
+        let (columns, rows) = self.terminal_size;
+        self.record_raw(|writer| writer.resize(columns, rows));
+    }
+
+    /// Write to the raw stream recording, if any. A failed write stops the
+    /// recording instead of logging an error for every following keystroke
+    fn record_raw(&mut self, write: impl FnOnce(&mut RawStreamWriter) -> std::io::Result<()>) {
+        let Some(writer) = &mut self.raw_stream else {
+            return;
         };
-        log::debug!("Processing command: {}", cmd);
+        if let Err(err) = write(writer) {
+            log::error!(
+                "Failed to write raw stream recording {}, stopping it: {}",
+                writer.path().display(),
+                err
+            );
+            self.raw_stream = None;
+        }
+    }
 
-        // Create command context
-        let mut context = CommandContext::new(
-            self.cwd.clone(),
-            self.user.clone().unwrap_or_else(|| "user".to_string()),
-            self.hostname.clone(),
-            self.fs2.clone(),
-            self.session_data.auth_id.clone(),
-        );
+    /// Whether the current auth attempt succeeds: drawn when --auth-success-rate is set,
+    /// otherwise every attempt is accepted unless --reject-all-auth
+    fn accepts_auth(&self) -> bool {
+        match &self.auth_success_rate {
+            Some(rate) => rate.draw(),
+            None => !self.reject_all_auth,
+        }
+    }
 
-        // Use the new dispatcher for all commands (handles parsing, pipes, &&/||, sequencing)
-        let outcome = self.command_dispatcher.execute(&cmd, &mut context).await;
+    /// Queue an auth record without letting a flood of them hold up authentication,
+    /// see [`AuthRecords`]. Password attempts are counted for --credential-alert-ips
+    /// on the way
+    async fn record_auth(&mut self, message: DbMessage) {
+        if let (
+            Some(alerts),
+            DbMessage::RecordAuth {
+                username,
+                password: Some(password),
+                ..
+            },
+        ) = (&self.credential_alerts, &message)
+        {
+            alerts.observe(username, password, self.peer.ip());
+        }
+        self.auth_records.send(&self.db_tx, message, self.peer).await;
+    }
 
-        // Update cwd from context in case it changed (e.g., from cd command)
-        self.cwd = context.cwd.clone();
+    async fn record_terminal_event(&self, event_type: &'static str, term: Option<String>) {
+        let Some(auth_id) = self.auth_id.clone() else {
+            return;
+        };
+        if let Err(err) = self
+            .db_tx
+            .send(DbMessage::RecordTerminalEvent {
+                auth_id,
+                timestamp: Utc::now(),
+                event_type,
+                term,
+                columns: self.terminal_size.0,
+                rows: self.terminal_size.1,
+            })
+            .await
+        {
+            log::error!("Failed to send terminal event to db task: {}", err);
+        }
+    }
 
-        (outcome.output, outcome.exit_requested)
+    async fn record_channel_signal(&self, signal: String) {
+        let Some(auth_id) = self.auth_id.clone() else {
+            return;
+        };
+        if let Err(err) = self
+            .db_tx
+            .send(DbMessage::RecordChannelSignal {
+                auth_id,
+                timestamp: Utc::now(),
+                signal,
+            })
+            .await
+        {
+            log::error!("Failed to send channel signal to db task: {}", err);
+        }
     }
 
-    /// Handles the transmission of data over the provided session and channel, with an optional "tarpit" mode
-    /// to delay the data flow intentionally.
+    async fn record_subsystem_request(&self, name: &str, accepted: bool) {
+        let Some(auth_id) = self.auth_id.clone() else {
+            return;
+        };
+        if let Err(err) = self
+            .db_tx
+            .send(DbMessage::RecordSubsystemRequest {
+                auth_id,
+                timestamp: Utc::now(),
+                name: name.to_string(),
+                accepted,
+            })
+            .await
+        {
+            log::error!("Failed to send subsystem request to db task: {}", err);
+        }
+    }
+
+    /// Name of the known scanner the client is, with --quiet-scanner-mode only. The
+    /// reverse lookup made for the connection record is cached by now
+    async fn quiet_scanner(&self) -> Option<String> {
+        let tagger = self.quiet_scanners.as_ref()?;
+        let ptr = match &self.reverse_dns {
+            Some(reverse_dns) => reverse_dns.lookup(self.peer.ip()).await,
+            None => PtrLookup::default(),
+        };
+        tagger.tag(self.peer.ip(), &ptr)
+    }
+
+    async fn record_exec_probe(&self, tool: &str, command: &str) {
+        if let Err(err) = self
+            .db_tx
+            .send(DbMessage::RecordExecProbe {
+                auth_id: self.session_data.auth_id.clone(),
+                timestamp: Utc::now(),
+                tool: tool.to_string(),
+                command: command.to_string(),
+            })
+            .await
+        {
+            log::error!("Failed to send exec probe to db task: {}", err);
+        }
+    }
+
+    async fn record_canary_hit(&self, path: String, command: String) {
+        if let Err(err) = self
+            .db_tx
+            .send(DbMessage::RecordCanaryHit {
+                auth_id: self.session_data.auth_id.clone(),
+                timestamp: Utc::now(),
+                path,
+                command,
+            })
+            .await
+        {
+            log::error!("Failed to send canary hit to db task: {}", err);
+        }
+    }
+
+    async fn record_execution(
+        &self,
+        path: String,
+        command: String,
+        outcome: &str,
+        content: Option<Arc<Vec<u8>>>,
+    ) {
+        let file_hash = content
+            .as_ref()
+            .map(|content| hex::encode(Sha256::digest(content.as_slice())));
+        if let Err(err) = self
+            .db_tx
+            .send(DbMessage::RecordExecution {
+                auth_id: self.session_data.auth_id.clone(),
+                timestamp: Utc::now(),
+                path,
+                command,
+                outcome: outcome.to_string(),
+                file_hash,
+                content: content.map(|content| content.to_vec()),
+            })
+            .await
+        {
+            log::error!("Failed to send execution to db task: {}", err);
+        }
+    }
+
+    /// Store a file a shell redirection wrote like an upload, so what was echoed
+    /// into place can be looked at later
+    async fn record_written_file(&self, path: String, content: Arc<Vec<u8>>) {
+        log::info!(
+            "Shell wrote {} bytes to {} (auth_id: {:?})",
+            content.len(),
+            path,
+            self.auth_id
+        );
+        let file_size = content.len() as u64;
+        let file_hash = hex::encode(Sha256::digest(content.as_slice()));
+        let kept = content[..content.len().min(self.max_upload_size as usize)].to_vec();
+        if let Err(err) = self
+            .db_tx
+            .send(HoneypotSftpSession::upload_record(
+                self.session_data.auth_id.clone(),
+                path,
+                kept,
+                file_size,
+                file_hash,
+                &self.upload_store,
+                self.yara_rules.as_deref(),
+            ))
+            .await
+        {
+            log::error!("Failed to send written file to db task: {}", err);
+        }
+    }
+
+    async fn record_dns_query(
+        &self,
+        name: String,
+        record_type: String,
+        command: String,
+        status: &str,
+        answers: Vec<String>,
+    ) {
+        if let Err(err) = self
+            .db_tx
+            .send(DbMessage::RecordDnsQuery {
+                auth_id: self.session_data.auth_id.clone(),
+                timestamp: Utc::now(),
+                name,
+                record_type,
+                command,
+                status: status.to_string(),
+                answers,
+            })
+            .await
+        {
+            log::error!("Failed to send DNS query to db task: {}", err);
+        }
+    }
+
+    async fn record_shutdown_attempt(
+        &self,
+        command: String,
+        action: &str,
+        permitted: bool,
+        immediate: bool,
+    ) {
+        log::info!(
+            "[{}] Shutdown attempt ({}, {}): {}",
+            self.session_data.auth_id,
+            action,
+            if permitted { "as root" } else { "refused" },
+            command
+        );
+        if let Err(err) = self
+            .db_tx
+            .send(DbMessage::RecordShutdownAttempt {
+                auth_id: self.session_data.auth_id.clone(),
+                timestamp: Utc::now(),
+                command,
+                action: action.to_string(),
+                permitted,
+                immediate,
+            })
+            .await
+        {
+            log::error!("Failed to send shutdown attempt to db task: {}", err);
+        }
+    }
+
+    /// Wait like a real server checking credentials before answering an auth attempt
+    async fn auth_delay(&self) {
+        let delay = rng().random_range(self.auth_delay.clone());
+        log::trace!("Letting client wait for {}ms", delay);
+        tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
+    }
+
+    /// Drop the line being edited and show a fresh prompt, like an interrupted readline
+    async fn interrupt_line(&mut self, session: &mut Session, channel: ChannelId) {
+        self.current_cmd = String::new();
+        self.dropped_input = 0;
+        let prompt = format!("\r\n{}", self.session_data.prompt);
+        match self.tarpit_data(session, channel, prompt.as_bytes()).await {
+            Ok(_) => {
+                log::trace!("Send prompt to client")
+            }
+            Err(err) => {
+                log::error!("Failed to send prompt to client: {}", err)
+            }
+        }
+    }
+
+    /// Reject an auth attempt. The attempt reaching --max-auth-tries also disconnects
+    /// the client with "Too many authentication failures", as sshd does
+    async fn reject_auth(&mut self) -> Auth {
+        log::debug!("Rejected authentication attempt");
+        self.auth_failures += 1;
+        if self.auth_failures >= self.max_auth_tries
+            && let Some(handle) = self.session_handle.get()
+        {
+            log::info!(
+                "Disconnecting {} after {} failed authentication attempts",
+                self.peer.ip(),
+                self.auth_failures
+            );
+            if handle
+                .disconnect(
+                    Disconnect::ProtocolError,
+                    "Too many authentication failures".to_string(),
+                    String::new(),
+                )
+                .await
+                .is_err()
+            {
+                log::debug!("Session of {} ended before the disconnect", self.peer.ip());
+            }
+        }
+        Auth::Reject {
+            proceed_with_methods: None,
+            partial_success: false,
+        }
+    }
+
+    /// Add typed or pasted `text` to the line being edited and echo it. Past
+    /// --max-input-line the rest is taken but neither kept nor echoed, under
+    /// --tarpit a pasted megabyte would otherwise be echoed a byte at a time
+    async fn append_input(&mut self, session: &mut Session, channel: ChannelId, text: &str) {
+        let room = self.max_input_line.saturating_sub(self.current_cmd.len());
+        let mut kept = text.len().min(room);
+        while !text.is_char_boundary(kept) {
+            kept -= 1;
+        }
+        if kept < text.len() {
+            if self.dropped_input == 0 {
+                self.record_oversized_input().await;
+            }
+            self.dropped_input += text.len() - kept;
+        }
+        if kept == 0 {
+            return;
+        }
+
+        self.current_cmd.push_str(&text[..kept]);
+        match self.tarpit_data(session, channel, text[..kept].as_bytes()).await {
+            Ok(_) => {
+                log::trace!("Sent character back to client")
+            }
+            Err(err) => {
+                log::error!("Failed to send character back to client: {}", err)
+            }
+        };
+    }
+
+    /// Keep the source of a line that passed --max-input-line as a protocol anomaly
+    async fn record_oversized_input(&self) {
+        log::warn!(
+            "[{}] Input line from {} passed {} bytes, the rest is dropped",
+            self.session_data.auth_id,
+            self.peer.ip(),
+            self.max_input_line
+        );
+        let anomaly = DbMessage::RecordProtocolAnomaly {
+            timestamp: Utc::now(),
+            ip: self.peer.ip().to_string(),
+            port: self.peer.port(),
+            kind: OVERSIZED_INPUT_KIND.to_string(),
+            detail: format!(
+                "input line passed --max-input-line of {} bytes",
+                self.max_input_line
+            ),
+        };
+        if let Err(err) = self.db_tx.send(anomaly).await {
+            log::error!("Failed to send oversized input to db: {}", err);
+        }
+    }
+
+    /// Note on the session row that --disconnect-after-commands closed it
+    async fn record_cut_short(&self) {
+        let Some(session_id) = self.session_data.session_id.clone() else {
+            return;
+        };
+        let message = DbMessage::RecordSessionCutShort {
+            session_id,
+            commands: self.commands_run,
+        };
+        if let Err(err) = self.db_tx.send(message).await {
+            log::error!("Failed to send cut short session to db: {}", err);
+        }
+    }
+
+    async fn record_firewall_change(&self, tool: &str, command: String, permitted: bool) {
+        log::info!(
+            "[{}] Firewall change ({}): {}",
+            self.session_data.auth_id,
+            if permitted { "as root" } else { "refused" },
+            command
+        );
+        if let Err(err) = self
+            .db_tx
+            .send(DbMessage::RecordFirewallChange {
+                auth_id: self.session_data.auth_id.clone(),
+                timestamp: Utc::now(),
+                tool: tool.to_string(),
+                command,
+                permitted,
+            })
+            .await
+        {
+            log::error!("Failed to send firewall change to db task: {}", err);
+        }
+    }
+
+    async fn record_process_kill(
+        &self,
+        tool: &str,
+        command: String,
+        signal: String,
+        pids: Vec<u32>,
+        names: Vec<String>,
+        permitted: bool,
+    ) {
+        log::info!(
+            "[{}] Process kill (signal {}, {}): {} -> [{}]",
+            self.session_data.auth_id,
+            signal,
+            if permitted { "permitted" } else { "refused" },
+            command,
+            names.join(", ")
+        );
+        if let Err(err) = self
+            .db_tx
+            .send(DbMessage::RecordProcessKill {
+                auth_id: self.session_data.auth_id.clone(),
+                timestamp: Utc::now(),
+                tool: tool.to_string(),
+                command,
+                signal,
+                pids,
+                names,
+                permitted,
+            })
+            .await
+        {
+            log::error!("Failed to send process kill to db task: {}", err);
+        }
+    }
+
+    /// Tab completion of the line being edited, rings the bell when there is nothing to add
+    async fn complete_line(&mut self, session: &mut Session, channel: ChannelId, double_tab: bool) {
+        let mut commands = self.command_dispatcher.list_commands();
+        commands.extend(FILTER_NAMES.iter().map(|name| name.to_string()));
+        commands.sort();
+        commands.dedup();
+        let home = format!("/home/{}", self.user.as_deref().unwrap_or("user"));
+        let completion = {
+            let fs = self.fs2.read().await;
+            complete(&self.current_cmd, &commands, &fs, &self.cwd, &home)
+        };
+
+        let reply = if !completion.insert.is_empty() {
+            self.current_cmd.push_str(&completion.insert);
+            completion.insert
+        } else if completion.candidates.is_empty() || !double_tab {
+            "\x07".to_string()
+        } else {
+            let prompt = if self.pending_block.is_empty() {
+                self.session_data.prompt.as_str()
+            } else {
+                "> "
+            };
+            format!(
+                "\r\n{}{}{}",
+                format_candidates(&completion.candidates, self.terminal_size.0 as usize),
+                prompt,
+                self.current_cmd
+            )
+        };
+        match self.tarpit_data(session, channel, reply.as_bytes()).await {
+            Ok(_) => {
+                log::trace!("Sent completion to client")
+            }
+            Err(err) => {
+                log::error!("Failed to send completion to client: {}", err)
+            }
+        }
+    }
+
+    async fn process_command(&mut self) -> (String, bool) {
+        let cmd = if self.pending_block.is_empty() {
+            self.current_cmd.clone()
+        } else {
+            self.pending_block.clone()
+        };
+        log::debug!("Processing command: {}", cmd);
+        // `cmd &` runs at once, it only gets a job notice like bash prints
+        let (cmd, background) = match shell::parser::strip_background(&cmd) {
+            Some(stripped) => (stripped.to_string(), true),
+            None => (cmd, false),
+        };
+
+        // A real system's answer, see --command-backend
+        if !background
+            && let Some(backend) = &self.command_backend
+            && let Some(argv) = backend.accepts(&cmd)
+        {
+            match backend.run(&argv, &self.hostname).await {
+                Ok(output) => {
+                    log::info!(
+                        "[{}] Answered by {}: {}",
+                        self.session_data.auth_id,
+                        backend.image(),
+                        cmd
+                    );
+                    return (output, false);
+                }
+                Err(err) => log::warn!(
+                    "[{}] Command backend failed, simulating {}: {}",
+                    self.session_data.auth_id,
+                    cmd,
+                    err
+                ),
+            }
+        }
+
+        // Create command context
+        let mut context = CommandContext::new(
+            self.cwd.clone(),
+            self.user.clone().unwrap_or_else(|| "user".to_string()),
+            self.hostname.clone(),
+            self.fs2.clone(),
+            self.session_data.auth_id.clone(),
+        );
+        context.os = self.persona.os_identity();
+        context.terminal_width = self.terminal_size.0 as usize;
+        context.fetch_policy = self.fetch_policy.clone();
+        context.host = self.host_identity.clone();
+        context.canaries = self.canaries.clone();
+        context.dns = self.dns_resolver.clone();
+        context.logins = self.login_history.clone();
+        context.latency = self.command_latency;
+        context.max_sleep = self.max_sleep;
+        context.binary_files = self.binary_files;
+        let newest_pid = {
+            let mut processes = self.process_table.write().await;
+            processes.attach_session(&context.username);
+            processes.processes().last().map(|p| p.pid)
+        };
+        context.processes = self.process_table.clone();
+        context.firewall = self.firewall.clone();
+        match &self.env_vars {
+            Some(env_vars) => context.env_vars = env_vars.clone(),
+            // The first command line starts with the HOME /etc/passwd has
+            None => {
+                let home = home_dir(&*self.fs2.read().await, &context.username);
+                context.set_env("HOME".to_string(), home);
+            }
+        }
+        // Handed back below, `history -c` and `-d` change it
+        context.history = std::mem::take(&mut self.session_data.commands);
+
+        // Use the new dispatcher for all commands (handles parsing, pipes, &&/||, sequencing)
+        let outcome = self.command_dispatcher.execute(&cmd, &mut context).await;
+        for event in context.take_events() {
+            match event {
+                ShellEvent::CanaryRead { path } => self.record_canary_hit(path, cmd.clone()).await,
+                ShellEvent::Execution {
+                    path,
+                    command,
+                    outcome,
+                    content,
+                } => self.record_execution(path, command, outcome, content).await,
+                ShellEvent::DnsQuery {
+                    name,
+                    record_type,
+                    command,
+                    status,
+                    answers,
+                } => {
+                    self.record_dns_query(name, record_type, command, status, answers)
+                        .await
+                }
+                ShellEvent::Shutdown {
+                    command,
+                    action,
+                    permitted,
+                    immediate,
+                } => {
+                    self.shutting_down |= permitted && immediate;
+                    self.record_shutdown_attempt(command, action, permitted, immediate)
+                        .await
+                }
+                ShellEvent::FirewallChange {
+                    tool,
+                    command,
+                    permitted,
+                } => self.record_firewall_change(tool, command, permitted).await,
+                ShellEvent::ProcessKill {
+                    tool,
+                    command,
+                    signal,
+                    pids,
+                    names,
+                    permitted,
+                } => {
+                    self.record_process_kill(tool, command, signal, pids, names, permitted)
+                        .await
+                }
+                ShellEvent::FileWritten { path, content } => {
+                    self.record_written_file(path, content).await
+                }
+            }
+        }
+
+        // Update cwd from context in case it changed (e.g., from cd command)
+        self.cwd = context.cwd.clone();
+        self.env_vars = Some(context.env_vars.clone());
+        self.session_data.commands = std::mem::take(&mut context.history);
+        // The next prompt shows where cd went
+        self.session_data.prompt = context.get_prompt();
+
+        if !background {
+            return (outcome.output, outcome.exit_requested);
+        }
+
+        // `nohup ./x &` already left its process behind, that's the job
+        let mut processes = self.process_table.write().await;
+        let last_pid = processes.processes().last().map(|p| p.pid);
+        let pid = match last_pid {
+            Some(pid) if last_pid != newest_pid => pid,
+            _ => processes.spawn(&context.username, &cmd),
+        };
+        let job = processes.add_job(pid, &cmd);
+        log::info!(
+            "[{}] Command sent to background as job {} (PID {}): {}",
+            self.session_data.auth_id,
+            job,
+            pid,
+            cmd
+        );
+        (format!("[{}] {}\r\n{}", job, pid, outcome.output), false)
+    }
+
+    /// Handles the transmission of data over the provided session and channel, with an optional "tarpit" mode
+    /// to delay the data flow intentionally.
     ///
     /// # Arguments
     ///
@@ -804,20 +1964,94 @@ impl SshHandler {
                 let wait_time = std::time::Duration::from_millis(rng().random_range(10..700));
                 log::trace!("Tarpit delay: {}", wait_time.as_millis());
                 tokio::time::sleep(wait_time).await;
+                self.record_raw(|writer| writer.output(&[*datum]));
                 session.data(channel, vec![*datum])?;
             }
         } else {
+            self.record_raw(|writer| writer.output(data));
             session.data(channel, data.to_vec())?;
         }
         Ok(())
     }
 
+    /// Feed data of an `scp -t` channel to the sink, store the files it completes
+    /// and record them like SFTP uploads
+    async fn receive_scp(
+        &mut self,
+        channel: ChannelId,
+        data: &[u8],
+        session: &mut Session,
+    ) -> Result<(), russh::Error> {
+        let Some((_, sink)) = self.scp.as_mut() else {
+            return Ok(());
+        };
+        let output = sink.feed(data);
+
+        {
+            let mut fs = self.fs2.write().await;
+            for dir in &output.directories {
+                if let Err(err) = fs.create_directory(dir) {
+                    log::debug!("SCP could not create directory {}: {}", dir, err);
+                }
+            }
+            for file in &output.files {
+                if let Err(err) = scp::store_file(&mut fs, file) {
+                    log::warn!("SCP could not store {}: {}", file.path, err);
+                }
+            }
+        }
+
+        for file in output.files {
+            log::info!(
+                "SCP upload: {} bytes to {} (auth_id: {:?})",
+                file.size,
+                file.path,
+                self.auth_id
+            );
+            match self
+                .db_tx
+                .send(HoneypotSftpSession::upload_record(
+                    self.session_data.auth_id.clone(),
+                    file.path,
+                    file.data,
+                    file.size,
+                    file.file_hash,
+                    &self.upload_store,
+                    self.yara_rules.as_deref(),
+                ))
+                .await
+            {
+                Ok(_) => log::debug!("Successfully queued file upload record"),
+                Err(err) => log::error!("Failed to queue file upload record: {}", err),
+            }
+        }
+
+        if !output.reply.is_empty() {
+            session.data(channel, output.reply)?;
+        }
+        if output.finished {
+            self.scp = None;
+            session.exit_status_request(channel, 1)?;
+            session.close(channel)?;
+        }
+        Ok(())
+    }
+
+    /// Create the user's home with its .bash_history. Called once a login is
+    /// accepted, rejected guesses don't leave homes in the shared filesystem
     async fn ensure_user_home_exists(&mut self) {
         let mut fs2 = self.fs2.write().await;
+        let user = self.user.as_deref().unwrap_or("user");
+        // The session starts in the home /etc/passwd gives the user
+        self.cwd = home_dir(&fs2, user);
         // We don't care if the directory already exists or if it can't be created. This is a honeypot not linux
         match fs2.create_directory(&self.cwd) {
             Ok(_) => {
                 log::debug!("Created user home directory: {}", self.cwd);
+                let history = self.bash_history.for_user(user);
+                if let Err(err) = write_bash_history(&mut fs2, &self.cwd, &history) {
+                    log::warn!("Failed to write {}/.bash_history: {}", self.cwd, err);
+                }
             }
             Err(err) => {
                 log::warn!("Failed to create user home directory: {}", err);
@@ -835,8 +2069,15 @@ impl SshHandler {
         let ip = self.peer.ip().to_string();
         log::trace!("Starting AbuseIPDB lookup for IP: {}", ip);
 
-        match abuse_client.check_ip_with_cache(&ip).await {
-            Ok(response) => {
+        // Shares the limit with the background lookups, but never waits for it:
+        // without a free permit only the caches answer
+        let permits = self.enrichment_permits.as_deref();
+        match abuse_client.check_ip_for_auth(&ip, permits).await {
+            Ok(None) => {
+                log::trace!("No cached AbuseIPDB answer for {} and no permit free", ip);
+                None
+            }
+            Ok(Some(response)) => {
                 let score = response.data.abuse_confidence_score.unwrap_or(0);
                 let country = response.data.country_code.as_deref().unwrap_or("Unknown");
                 let is_tor = response.data.is_tor;
@@ -905,6 +2146,35 @@ impl SshHandler {
         None
     }
 
+    /// Send the legal banner and remember that the next command answers it
+    async fn show_legal_banner(
+        &mut self,
+        session: &mut Session,
+        channel: ChannelId,
+        banner: &str,
+    ) {
+        let text = format!("{}\r\n", banner.replace("\r\n", "\n").replace('\n', "\r\n"));
+        if let Err(err) = self.tarpit_data(session, channel, text.as_bytes()).await {
+            log::error!("Failed to send legal banner to client: {}", err);
+            return;
+        }
+
+        log::info!(
+            "[{}] Showed the legal banner to {}",
+            self.session_data.auth_id,
+            self.peer
+        );
+        self.awaiting_banner_response = true;
+        let shown = DbMessage::RecordLegalBannerShown {
+            auth_id: self.session_data.auth_id.clone(),
+            timestamp: Utc::now(),
+            banner_sha256: hex::encode(Sha256::digest(banner.as_bytes())),
+        };
+        if let Err(err) = self.db_tx.send(shown).await {
+            log::error!("Failed to send legal banner view to db: {}", err);
+        }
+    }
+
     /// Generate a welcome message with randomized system statistics
     fn generate_welcome_message(system_description: &str) -> String {
         let mut rng = rand::rng();
@@ -956,39 +2226,334 @@ impl SshHandler {
             Local::now().format("%a %b %e %H:%M:%S %Y")
         )
     }
-}
-
-// Implementation of Server trait
-pub struct SshServerHandler {
-    local_port: u16,
-    db_tx: mpsc::Sender<DbMessage>,
-    disable_cli_interface: bool,
-    disable_exec: bool,
-    authentication_banner: Option<String>,
-    tarpit: bool,
-    fs2: Arc<RwLock<FileSystem>>,
-    enable_sftp: bool,
-    abuse_ip_client: Option<Arc<AbuseIpClient>>,
-    reject_all_auth: bool,
-    ip_api_client: Option<Arc<ipapi::Client>>,
-    welcome_message: String,
-    hostname: String,
-}
+}
+
+/// Settings re-read from the config file on SIGHUP. New connections pick up the
+/// current values, connections that are already open keep the ones they started with
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReloadableSettings {
+    pub authentication_banner: Option<String>,
+    pub welcome_message: String,
+    pub legal_banner: Option<String>,
+    pub reject_all_auth: bool,
+}
+
+// Implementation of Server trait
+pub struct SshServerHandler {
+    local_port: u16,
+    db_tx: mpsc::Sender<DbMessage>,
+    disable_cli_interface: bool,
+    disable_exec: bool,
+    tarpit: bool,
+    auth_banner_drip: bool,
+    fs2: Arc<RwLock<FileSystem>>,
+    enable_sftp: bool,
+    enable_scp: bool,
+    max_upload_size: u64,
+    upload_store: Arc<UploadStore>,
+    yara_rules: Option<Arc<YaraRules>>,
+    unknown_subsystems: UnknownSubsystemPolicy,
+    exec_responses: Arc<ExecResponses>,
+    abuse_ip_client: Option<Arc<AbuseIpClient>>,
+    auth_success_rate: Option<Arc<AuthSuccessRate>>,
+    ip_api_client: Option<Arc<ipapi::Client>>,
+    enrichment_permits: Option<Arc<Semaphore>>,
+    /// Only read in `new_client`, which is sync, hence not the tokio lock
+    settings: Arc<std::sync::RwLock<ReloadableSettings>>,
+    hostname: String,
+    persona: PersonaKind,
+    /// Weakness this listener pretends to have, clients going after it are recorded
+    vulnerable_profile: Option<VulnerableProfile>,
+    command_not_found: Option<CommandNotFoundHandler>,
+    fetch_policy: Arc<FetchPolicy>,
+    host_identity: Arc<HostIdentity>,
+    canaries: Arc<Canaries>,
+    /// Answers dig, host and nslookup
+    dns_resolver: Arc<dyn DnsResolver>,
+    /// wtmp and btmp shown by last and lastb
+    login_history: Arc<dyn LoginHistory>,
+    command_latency: CommandLatency,
+    shutdown_close_delay: std::time::Duration,
+    /// Bytes of output one command may produce
+    max_command_output: usize,
+    max_sleep: std::time::Duration,
+    disconnect_after_commands: Option<u32>,
+    max_input_line: usize,
+    bash_history: BashHistory,
+    auth_delay: RangeInclusive<u64>,
+    max_auth_tries: u32,
+    binary_files: BinaryFiles,
+    command_backend: Option<Arc<CommandBackend>>,
+    scanner_tagger: Arc<ScannerTagger>,
+    /// None when reverse DNS lookups are disabled
+    reverse_dns: Option<Arc<ReverseDns>>,
+    quiet_scanner_mode: bool,
+    credential_alerts: Option<Arc<CredentialAlerts>>,
+    record_raw_streams: Option<PathBuf>,
+    /// Addresses whose connections are dropped on accept, None without --drop-on-exploit-probe
+    probe_penalty: Option<Arc<ProbePenalty>>,
+}
+
+impl server::Server for SshServerHandler {
+    type Handler = SshHandler;
+
+    // Create a new handler for each connection
+    fn new_client(&mut self, peer_addr: Option<SocketAddr>) -> Self::Handler {
+        self.new_client_with_kex_init(peer_addr, None)
+    }
+
+    fn handle_session_error(&mut self, error: <Self::Handler as Handler>::Error) {
+        match error {
+            Error::Disconnect => {}
+            Error::IO(err) => match err.kind() {
+                ErrorKind::UnexpectedEof => {
+                    let complaints = [
+                        "Session did not properly close. Bad bot.",
+                        "No goodbye? Rude.",
+                        "FIN/ACK without SSH disconnect? Half-assing it, I see.",
+                        "TCP teardown: ✓ SSH goodbye: ✗ Try harder.",
+                        "Your mother would be disappointed in your session handling.",
+                        "Closed the TCP socket but forgot there's an SSH session on top.",
+                        "Your mother would be disappointed in your connection handling.",
+                        "Tearing down TCP before SSH? Someone skipped the protocol docs.",
+                        "SSH session still open, TCP already gone. Pick a lane.",
+                        "Did you even read RFC 4253? Section 11.1 is right there.",
+                        "Abrupt TCP close detected. The SSH layer is crying.",
+                        "Layer 7 called, they want their proper disconnect back.",
+                        "SSH state machine in shambles. TCP doesn't care about your feelings.",
+                        "Graceful shutdown was an option. You chose violence.",
+                        "EOF on socket != SSH_MSG_DISCONNECT. Learn the difference.",
+                        "The SSH RFC authors are personally disappointed in you.",
+                    ];
+                    log::warn!(
+                        "UnexpectedEof: {}",
+                        complaints[rng().random_range(0..complaints.len())]
+                    );
+                }
+                ErrorKind::ConnectionReset => {
+                    log::warn!("Session closed by remote peer. (TCP RST Packet)");
+                }
+                _ => {
+                    log::error!("I/O Session error: {:#?}", err);
+                }
+            },
+            Error::Elapsed(_) => {
+                log::warn!("Session timed out");
+            }
+            Error::InactivityTimeout => {
+                log::warn!("Session timed out due to inactivity");
+            }
+            Error::SshEncoding(err) => match err {
+                SshEncodingError::Length => {
+                    log::warn!("Client send invalid length packet");
+                }
+                _ => {
+                    log::error!("SSH encoding error: {:#?}", err);
+                }
+            },
+            Error::InvalidConfig(err_msg) => {
+                log::error!("Invalid configuration: {}", err_msg);
+            }
+            _ => {
+                log::error!("Session error: {:#?}", error);
+            }
+        }
+    }
+}
+
+/// Id for an auth row, made here rather than by the database so that
+/// authentication never waits for the insert
+fn new_auth_id() -> String {
+    sqlx::types::uuid::Builder::from_random_bytes(rng().random())
+        .into_uuid()
+        .to_string()
+}
+
+/// The text in `bytes` and the incomplete UTF-8 sequence after it, which the
+/// client sends the rest of in its next packet. None when `bytes` isn't UTF-8
+fn decode_utf8(bytes: &[u8]) -> Option<(&str, &[u8])> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Some((text, &[])),
+        Err(err) if err.error_len().is_none() => {
+            let (text, partial) = bytes.split_at(err.valid_up_to());
+            std::str::from_utf8(text).ok().map(|text| (text, partial))
+        }
+        Err(_) => None,
+    }
+}
+
+/// Signal name as in the request, without the SIG prefix
+fn signal_name(signal: &Sig) -> String {
+    match signal {
+        Sig::ABRT => "ABRT",
+        Sig::ALRM => "ALRM",
+        Sig::FPE => "FPE",
+        Sig::HUP => "HUP",
+        Sig::ILL => "ILL",
+        Sig::INT => "INT",
+        Sig::KILL => "KILL",
+        Sig::PIPE => "PIPE",
+        Sig::QUIT => "QUIT",
+        Sig::SEGV => "SEGV",
+        Sig::TERM => "TERM",
+        Sig::USR1 => "USR1",
+        Sig::Custom(name) => return name.clone(),
+    }
+    .to_string()
+}
+
+/// Kind and detail of session errors that honest clients don't cause. Fuzzers
+/// and exploit scanners trip these, usually before authenticating; timeouts,
+/// resets and ordinary disconnects give None
+fn protocol_anomaly(error: &Error) -> Option<(&'static str, String)> {
+    let kind = match error {
+        Error::SshEncoding(SshEncodingError::Length) => "invalid_length",
+        Error::SshEncoding(_) => "malformed_packet",
+        Error::PacketSize(_) => "oversized_packet",
+        Error::KexInit => "bad_kexinit",
+        Error::NoCommonAlgo { .. } => "no_common_algorithm",
+        Error::StrictKeyExchangeViolation { .. } => "strict_kex_violation",
+        Error::Inconsistent => "protocol_violation",
+        _ => return None,
+    };
+    Some((kind, error.to_string()))
+}
+
+impl SshServerHandler {
+    pub fn new(
+        db_tx: mpsc::Sender<DbMessage>,
+        disable_cli_interface: bool,
+        disable_exec: bool,
+        authentication_banner: Option<String>,
+        tarpit: bool,
+        auth_banner_drip: bool,
+        fs2: Arc<RwLock<FileSystem>>,
+        enable_sftp: bool,
+        enable_scp: bool,
+        max_upload_size: u64,
+        upload_store: Arc<UploadStore>,
+        yara_rules: Option<Arc<YaraRules>>,
+        unknown_subsystems: UnknownSubsystemPolicy,
+        exec_responses: Arc<ExecResponses>,
+        abuse_ip_client: Option<Arc<AbuseIpClient>>,
+        reject_all_auth: bool,
+        auth_success_rate: Option<Arc<AuthSuccessRate>>,
+        ip_api_client: Option<Arc<ipapi::Client>>,
+        enrichment_permits: Option<Arc<Semaphore>>,
+        welcome_message: String,
+        legal_banner: Option<String>,
+        hostname: String,
+        persona: PersonaKind,
+        vulnerable_profile: Option<VulnerableProfile>,
+        command_not_found: Option<CommandNotFoundHandler>,
+        fetch_policy: Arc<FetchPolicy>,
+        host_identity: Arc<HostIdentity>,
+        canaries: Arc<Canaries>,
+        dns_resolver: Arc<dyn DnsResolver>,
+        login_history: Arc<dyn LoginHistory>,
+        command_latency: CommandLatency,
+        shutdown_close_delay: std::time::Duration,
+        max_command_output: usize,
+        max_sleep: std::time::Duration,
+        disconnect_after_commands: Option<u32>,
+        max_input_line: usize,
+        bash_history: BashHistory,
+        auth_delay: RangeInclusive<u64>,
+        max_auth_tries: u32,
+        binary_files: BinaryFiles,
+        command_backend: Option<Arc<CommandBackend>>,
+        scanner_tagger: Arc<ScannerTagger>,
+        reverse_dns: Option<Arc<ReverseDns>>,
+        quiet_scanner_mode: bool,
+        credential_alerts: Option<Arc<CredentialAlerts>>,
+        record_raw_streams: Option<PathBuf>,
+        probe_penalty: Option<Arc<ProbePenalty>>,
+        local_port: u16,
+    ) -> SshServerHandler {
+        Self {
+            disable_cli_interface,
+            disable_exec,
+            db_tx,
+            tarpit,
+            auth_banner_drip,
+            fs2,
+            enable_sftp,
+            enable_scp,
+            max_upload_size,
+            upload_store,
+            yara_rules,
+            unknown_subsystems,
+            exec_responses,
+            abuse_ip_client,
+            auth_success_rate,
+            ip_api_client,
+            enrichment_permits,
+            settings: Arc::new(std::sync::RwLock::new(ReloadableSettings {
+                authentication_banner,
+                welcome_message,
+                legal_banner,
+                reject_all_auth,
+            })),
+            hostname,
+            persona,
+            vulnerable_profile,
+            command_not_found,
+            fetch_policy,
+            host_identity,
+            canaries,
+            dns_resolver,
+            login_history,
+            command_latency,
+            shutdown_close_delay,
+            max_command_output,
+            max_sleep,
+            disconnect_after_commands,
+            max_input_line,
+            bash_history,
+            auth_delay,
+            max_auth_tries,
+            binary_files,
+            command_backend,
+            scanner_tagger,
+            reverse_dns,
+            quiet_scanner_mode,
+            credential_alerts,
+            record_raw_streams,
+            probe_penalty,
+            local_port,
+        }
+    }
 
-impl server::Server for SshServerHandler {
-    type Handler = SshHandler;
+    /// Handle to the settings of this listener that can be swapped at runtime
+    pub fn reloadable_settings(&self) -> Arc<std::sync::RwLock<ReloadableSettings>> {
+        self.settings.clone()
+    }
 
-    // Create a new handler for each connection
-    fn new_client(&mut self, peer_addr: Option<SocketAddr>) -> Self::Handler {
+    /// Build the per-connection handler. `kex_init_rx` delivers the client's KEXINIT
+    /// when the stream is wrapped in a [`KexInitSniffer`]
+    fn new_client_with_kex_init(
+        &mut self,
+        peer_addr: Option<SocketAddr>,
+        kex_init_rx: Option<oneshot::Receiver<ClientKexInit>>,
+    ) -> SshHandler {
         // Guaranteed to be safe as peer_addr is simply wrapped in Some() by russh for backwards compatibility
         let peer_addr = peer_addr.unwrap();
+        // Dual-stack listeners see IPv4 clients as ::ffff:a.b.c.d
+        let peer_addr = SocketAddr::new(canonical_ip(peer_addr.ip()), peer_addr.port());
         let ip = peer_addr.ip().to_string();
 
-        // Fire-and-forget IP lookup to populate cache
-        if let Some(abuse_client) = &self.abuse_ip_client {
+        // Fire-and-forget IP lookup to populate cache. Waits for a permit so a
+        // flood of new addresses queues up, the connection is recorded regardless
+        if let Some(abuse_client) = &self.abuse_ip_client
+            && let Some(permits) = &self.enrichment_permits
+        {
             let client_clone = abuse_client.clone();
             let ip_clone = ip.clone();
+            let permits = permits.clone();
             tokio::spawn(async move {
+                let Ok(_permit) = permits.acquire_owned().await else {
+                    return;
+                };
                 match client_clone.check_ip_with_cache(&ip_clone).await {
                     Ok(response) => {
                         log::debug!("Background AbuseIPDB lookup completed for {}", ip_clone);
@@ -1066,9 +2631,15 @@ impl server::Server for SshServerHandler {
             log::info!("New connection from: {:?}", peer_addr);
         }
 
-        if let Some(ip_api_client) = &self.ip_api_client {
+        if let Some(ip_api_client) = &self.ip_api_client
+            && let Some(permits) = &self.enrichment_permits
+        {
             let client = ip_api_client.clone();
+            let permits = permits.clone();
             tokio::spawn(async move {
+                let Ok(_permit) = permits.acquire_owned().await else {
+                    return;
+                };
                 log::trace!("Checking IP API for {}", ip);
                 let ipinfo = match client.check_ip_with_cache(&ip).await {
                     Ok(response) => {
@@ -1098,13 +2669,85 @@ impl server::Server for SshServerHandler {
 
         let db_tx = self.db_tx.clone();
         let local_port = self.local_port;
+        let timestamp = Utc::now();
+        let scanner_tagger = self.scanner_tagger.clone();
+        let reverse_dns = self.reverse_dns.clone();
+        let vulnerable_profile = self.vulnerable_profile;
         tokio::spawn(async move {
+            // Hold the record back until the client's KEXINIT was seen, so the
+            // fingerprint lands in the same row. Resolves early if the client disconnects.
+            // The reverse DNS lookup runs meanwhile and has its own deadline
+            let kex_init = async {
+                match kex_init_rx {
+                    Some(rx) => tokio::time::timeout(KEX_INIT_WAIT, rx)
+                        .await
+                        .ok()
+                        .and_then(Result::ok),
+                    None => None,
+                }
+            };
+            let ptr = async {
+                match &reverse_dns {
+                    Some(reverse_dns) => reverse_dns.lookup(peer_addr.ip()).await,
+                    None => PtrLookup::default(),
+                }
+            };
+            let (kex_init, ptr) = tokio::join!(kex_init, ptr);
+            if !ptr.names.is_empty() {
+                log::info!(
+                    "Reverse DNS of {}: {}{}",
+                    peer_addr.ip(),
+                    ptr.names.join(", "),
+                    if ptr.confirmed.is_some() {
+                        ""
+                    } else {
+                        " (not forward-confirmed)"
+                    }
+                );
+            }
+            let scanner_source = scanner_tagger.tag(peer_addr.ip(), &ptr);
+            if let Some(scanner) = &scanner_source {
+                log::info!("Connection from {} is the {} scanner", peer_addr, scanner);
+            }
+            if let Some(kex_init) = &kex_init {
+                log::debug!(
+                    "Client {} offered {} (HASSH {})",
+                    peer_addr,
+                    kex_init.client_version,
+                    kex_init.hassh()
+                );
+                if let Some(profile) = vulnerable_profile
+                    && let Some(reason) = profile.targeted_by_kex_init(kex_init)
+                {
+                    log::warn!(
+                        "Client {} is going after {}: {}",
+                        peer_addr,
+                        profile.cve(),
+                        reason
+                    );
+                    let _ = db_tx
+                        .send(DbMessage::RecordProtocolAnomaly {
+                            timestamp,
+                            ip: peer_addr.ip().to_string(),
+                            port: peer_addr.port(),
+                            kind: vulnerable::PROBE_KIND.to_string(),
+                            detail: format!("{}: {}", profile.cve(), reason),
+                        })
+                        .await;
+                }
+            }
+
             match db_tx
                 .send(DbMessage::RecordConnect {
                     ip: peer_addr.ip().to_string(),
                     port: peer_addr.port(),
-                    timestamp: Utc::now(),
+                    timestamp,
                     local_port,
+                    client_version: kex_init.as_ref().map(|k| k.client_version.clone()),
+                    hassh: kex_init.as_ref().map(|k| k.hassh()),
+                    hassh_algorithms: kex_init.as_ref().map(|k| k.hassh_algorithms()),
+                    scanner_source,
+                    reverse_dns: (!ptr.names.is_empty()).then_some(ptr.names),
                 })
                 .await
             {
@@ -1117,6 +2760,12 @@ impl server::Server for SshServerHandler {
             };
         });
 
+        let settings = self
+            .settings
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+
         SshHandler {
             peer: peer_addr,
             user: None,
@@ -1126,118 +2775,171 @@ impl server::Server for SshServerHandler {
             current_cmd: String::new(),
             pending_block: String::new(),
             cwd: String::from("/home/user"),
+            env_vars: None,
             hostname: self.hostname.clone(),
             disable_cli_interface: self.disable_cli_interface,
             disable_exec: self.disable_exec,
-            authentication_banner: self.authentication_banner.clone(),
+            authentication_banner: settings.authentication_banner,
             tarpit: self.tarpit,
+            auth_banner_drip: self.auth_banner_drip,
             fs2: self.fs2.clone(),
             /*send_task: None,
             send_task_tx: None,*/
             enable_sftp: self.enable_sftp,
+            enable_scp: self.enable_scp,
+            max_upload_size: self.max_upload_size,
+            upload_store: self.upload_store.clone(),
+            yara_rules: self.yara_rules.clone(),
+            unknown_subsystems: self.unknown_subsystems,
+            drained_channels: Vec::new(),
+            sftp_handoffs: Vec::new(),
+            exec_responses: self.exec_responses.clone(),
             abuse_ip_client: self.abuse_ip_client.clone(),
-            reject_all_auth: self.reject_all_auth,
-            command_dispatcher: Self::create_command_dispatcher(),
-            welcome_message: self.welcome_message.clone(),
+            reject_all_auth: settings.reject_all_auth,
+            auth_success_rate: self.auth_success_rate.clone(),
+            command_dispatcher: {
+                let mut dispatcher =
+                    Self::create_command_dispatcher(self.command_not_found.clone(), self.persona);
+                dispatcher.set_max_output(self.max_command_output);
+                dispatcher
+            },
+            process_table: Arc::new(RwLock::new(ProcessTable::new())),
+            firewall: Arc::new(RwLock::new(Firewall::default())),
+            welcome_message: settings.welcome_message,
+            legal_banner: settings.legal_banner,
+            awaiting_banner_response: false,
+            shutting_down: false,
+            shutdown_close_delay: self.shutdown_close_delay,
+            persona: self.persona,
+            terminal_size: (80, 24),
+            fetch_policy: self.fetch_policy.clone(),
+            host_identity: self.host_identity.clone(),
+            canaries: self.canaries.clone(),
+            dns_resolver: self.dns_resolver.clone(),
+            login_history: self.login_history.clone(),
+            command_latency: self.command_latency,
+            max_sleep: self.max_sleep,
+            disconnect_after_commands: self.disconnect_after_commands,
+            max_input_line: self.max_input_line,
+            dropped_input: 0,
+            bash_history: self.bash_history.clone(),
+            partial_utf8: Vec::new(),
+            auth_delay: self.auth_delay.clone(),
+            max_auth_tries: self.max_auth_tries,
+            auth_failures: 0,
+            session_handle: Arc::new(OnceLock::new()),
+            binary_files: self.binary_files,
+            commands_run: 0,
+            command_backend: self.command_backend.clone(),
             ip_api_client: self.ip_api_client.clone(),
+            enrichment_permits: self.enrichment_permits.clone(),
+            record_raw_streams: self.record_raw_streams.clone(),
+            quiet_scanners: self.quiet_scanner_mode.then(|| self.scanner_tagger.clone()),
+            reverse_dns: self.reverse_dns.clone(),
+            credential_alerts: self.credential_alerts.clone(),
+            auth_records: AuthRecords::default(),
+            raw_stream: None,
+            tab_pressed: false,
+            scp: None,
         }
     }
 
-    fn handle_session_error(&mut self, error: <Self::Handler as Handler>::Error) {
-        match error {
-            Error::Disconnect => {}
-            Error::IO(err) => match err.kind() {
-                ErrorKind::UnexpectedEof => {
-                    let complaints = [
-                        "Session did not properly close. Bad bot.",
-                        "No goodbye? Rude.",
-                        "FIN/ACK without SSH disconnect? Half-assing it, I see.",
-                        "TCP teardown: ✓ SSH goodbye: ✗ Try harder.",
-                        "Your mother would be disappointed in your session handling.",
-                        "Closed the TCP socket but forgot there's an SSH session on top.",
-                        "Your mother would be disappointed in your connection handling.",
-                        "Tearing down TCP before SSH? Someone skipped the protocol docs.",
-                        "SSH session still open, TCP already gone. Pick a lane.",
-                        "Did you even read RFC 4253? Section 11.1 is right there.",
-                        "Abrupt TCP close detected. The SSH layer is crying.",
-                        "Layer 7 called, they want their proper disconnect back.",
-                        "SSH state machine in shambles. TCP doesn't care about your feelings.",
-                        "Graceful shutdown was an option. You chose violence.",
-                        "EOF on socket != SSH_MSG_DISCONNECT. Learn the difference.",
-                        "The SSH RFC authors are personally disappointed in you.",
-                    ];
-                    log::warn!(
-                        "UnexpectedEof: {}",
-                        complaints[rng().random_range(0..complaints.len())]
-                    );
-                }
-                ErrorKind::ConnectionReset => {
-                    log::warn!("Session closed by remote peer. (TCP RST Packet)");
-                }
-                _ => {
-                    log::error!("I/O Session error: {:#?}", err);
-                }
-            },
-            Error::Elapsed(_) => {
-                log::warn!("Session timed out");
-            }
-            Error::InactivityTimeout => {
-                log::warn!("Session timed out due to inactivity");
-            }
-            Error::SshEncoding(err) => match err {
-                SshEncodingError::Length => {
-                    log::warn!("Client send invalid length packet");
+    /// Same accept loop as [`server::Server::run_on_socket`], but every stream is
+    /// wrapped in a [`KexInitSniffer`] so the client's offered algorithms (HASSH)
+    /// are stored with the connection. russh does not expose them to the handler.
+    pub async fn run_on_socket_capturing_kex(
+        &mut self,
+        config: Arc<server::Config>,
+        socket: &TcpListener,
+    ) -> std::io::Result<()> {
+        let (error_tx, mut error_rx) = mpsc::unbounded_channel();
+        loop {
+            tokio::select! {
+                accept_result = socket.accept() => {
+                    let (stream, peer_addr) = match accept_result {
+                        Ok(accepted) => accepted,
+                        Err(err) => {
+                            log::warn!("Failed to accept connection: {}", err);
+                            continue;
+                        }
+                    };
+                    if let Some(penalty) = &self.probe_penalty
+                        && penalty.is_penalized(canonical_ip(peer_addr.ip()))
+                    {
+                        log::debug!(
+                            "Dropped connection from {}, it sent an exploit probe",
+                            peer_addr
+                        );
+                        continue;
+                    }
+                    let (stream, kex_init_rx) = KexInitSniffer::new(stream);
+                    let handler = self.new_client_with_kex_init(Some(peer_addr), Some(kex_init_rx));
+                    let session_handle = handler.session_handle.clone();
+                    let config = config.clone();
+                    let error_tx = error_tx.clone();
+                    tokio::spawn(async move {
+                        let session = match server::run_stream(config, stream, handler).await {
+                            Ok(session) => session,
+                            Err(err) => {
+                                let _ = error_tx.send((peer_addr, err));
+                                return;
+                            }
+                        };
+                        let _ = session_handle.set(session.handle());
+                        if let Err(err) = session.await {
+                            let _ = error_tx.send((peer_addr, err));
+                        }
+                    });
                 }
-                _ => {
-                    log::error!("SSH encoding error: {:#?}", err);
+                Some((peer_addr, error)) = error_rx.recv() => {
+                    if let Some((kind, detail)) = protocol_anomaly(&error) {
+                        if let Some(profile) = self.vulnerable_profile
+                            && profile.targeted_by_anomaly(kind)
+                        {
+                            self.record_protocol_anomaly(
+                                peer_addr,
+                                vulnerable::PROBE_KIND,
+                                format!("{}: {}", profile.cve(), kind),
+                            );
+                        }
+                        self.record_protocol_anomaly(peer_addr, kind, detail);
+                        if let Some(penalty) = &self.probe_penalty
+                            && probe_penalty::is_exploit_probe(kind)
+                        {
+                            let ip = canonical_ip(peer_addr.ip());
+                            log::info!("Dropping connections from {} after a {} probe", ip, kind);
+                            penalty.penalize(ip);
+                        }
+                    }
+                    server::Server::handle_session_error(self, error);
                 }
-            },
-            Error::InvalidConfig(err_msg) => {
-                log::error!("Invalid configuration: {}", err_msg);
-            }
-            _ => {
-                log::error!("Session error: {:#?}", error);
             }
         }
     }
-}
 
-impl SshServerHandler {
-    pub fn new(
-        db_tx: mpsc::Sender<DbMessage>,
-        disable_cli_interface: bool,
-        disable_exec: bool,
-        authentication_banner: Option<String>,
-        tarpit: bool,
-        fs2: Arc<RwLock<FileSystem>>,
-        enable_sftp: bool,
-        abuse_ip_client: Option<Arc<AbuseIpClient>>,
-        reject_all_auth: bool,
-        ip_api_client: Option<Arc<ipapi::Client>>,
-        welcome_message: String,
-        hostname: String,
-        local_port: u16,
-    ) -> SshServerHandler {
-        Self {
-            disable_cli_interface,
-            disable_exec,
-            db_tx,
-            authentication_banner,
-            tarpit,
-            fs2,
-            enable_sftp,
-            abuse_ip_client,
-            reject_all_auth,
-            ip_api_client,
-            welcome_message,
-            hostname,
-            local_port,
+    /// Keep a protocol anomaly of `peer_addr` for the anomaly report. Never
+    /// waits on the database, the accept loop must keep going
+    fn record_protocol_anomaly(&self, peer_addr: SocketAddr, kind: &'static str, detail: String) {
+        let ip = canonical_ip(peer_addr.ip());
+        log::warn!("Protocol anomaly from {}: {} ({})", ip, kind, detail);
+        if let Err(e) = self.db_tx.try_send(DbMessage::RecordProtocolAnomaly {
+            timestamp: Utc::now(),
+            ip: ip.to_string(),
+            port: peer_addr.port(),
+            kind: kind.to_string(),
+            detail,
+        }) {
+            log::error!("Failed to queue protocol anomaly: {}", e);
         }
     }
 
     /// Create and initialize the command dispatcher with available commands
-    fn create_command_dispatcher() -> CommandDispatcher {
+    pub(crate) fn create_command_dispatcher(
+        command_not_found: Option<CommandNotFoundHandler>,
+        persona: PersonaKind,
+    ) -> CommandDispatcher {
         let mut dispatcher = CommandDispatcher::new();
+        dispatcher.set_command_not_found_handler(command_not_found);
 
         // Register regular commands
         dispatcher
@@ -1249,21 +2951,132 @@ impl SshServerHandler {
         dispatcher
             .registry_mut()
             .register_command(Arc::new(DateCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(DdCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(DfCommand));
+        for command in [
+            HexdumpCommand::Xxd,
+            HexdumpCommand::Hexdump,
+            HexdumpCommand::Od,
+        ] {
+            dispatcher
+                .registry_mut()
+                .register_command(Arc::new(command));
+        }
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(SedCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(AwkCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(DuCommand));
         dispatcher
             .registry_mut()
             .register_command(Arc::new(FreeCommand));
         dispatcher
             .registry_mut()
             .register_command(Arc::new(PsCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(KillCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(PkillCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(PgrepCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(PidofCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(NohupCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(SetsidCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(WatchCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(SleepCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(TimeoutCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(JobsCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(FgCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(BgCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(DisownCommand));
         dispatcher
             .registry_mut()
             .register_command(Arc::new(UnameCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(ArchCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(GetconfCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(GetentCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(HelpCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(CompgenCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(LsbReleaseCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(MountCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(HostnamectlCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(IfconfigCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(DigCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(HostCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(NslookupCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(LastCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(LastbCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(SshKeygenCommand));
         dispatcher
             .registry_mut()
             .register_command(Arc::new(LsCommand));
         dispatcher
             .registry_mut()
             .register_command(Arc::new(PwdCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(ClearCommand));
         dispatcher
             .registry_mut()
             .register_command(Arc::new(WhoamiCommand));
@@ -1282,6 +3095,22 @@ impl SshServerHandler {
         dispatcher
             .registry_mut()
             .register_command(Arc::new(ExitCommand));
+        for command in [
+            PowerCommand::Reboot,
+            PowerCommand::Poweroff,
+            PowerCommand::Halt,
+            PowerCommand::Shutdown,
+        ] {
+            dispatcher
+                .registry_mut()
+                .register_command(Arc::new(command));
+        }
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(IptablesCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(UfwCommand));
         dispatcher
             .registry_mut()
             .register_command(Arc::new(TestCommand));
@@ -1294,6 +3123,12 @@ impl SshServerHandler {
         dispatcher
             .registry_mut()
             .register_command(Arc::new(ColonCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(EnvCommand));
+        dispatcher
+            .registry_mut()
+            .register_command(Arc::new(HistoryCommand));
         dispatcher
             .registry_mut()
             .register_command(Arc::new(ExportCommand));
@@ -1306,6 +3141,14 @@ impl SshServerHandler {
             .registry_mut()
             .register_stateful_command(Arc::new(CdCommand));
 
+        // Embedded devices only have what their busybox build was configured with
+        if persona == PersonaKind::Dropbear {
+            dispatcher
+                .registry_mut()
+                .register_command(Arc::new(BusyboxCommand));
+            dispatcher.registry_mut().retain_commands(BUSYBOX_APPLETS);
+        }
+
         dispatcher
     }
 }
@@ -1315,13 +3158,29 @@ async fn handle_shell_session(
     mut channel: Channel<Msg>,
     session_data: SessionData,
     db_tx: mpsc::Sender<DbMessage>,
+    mut sftp_rx: oneshot::Receiver<HoneypotSftpSession>,
 ) {
     // We don't need to do anything specific here since
-    // commands are handled in the data/shell_request/exec_request methods
+    // commands are handled in the data/shell_request/exec_request methods,
+    // except for serving SFTP once the subsystem is accepted
 
     log::trace!("Waiting for channel to close before saving metadata");
     // Just wait for the channel to close
-    while let Some(msg) = channel.wait().await {
+    loop {
+        // Polling the receiver again after it completed would panic
+        let sftp_pending = !sftp_rx.is_terminated();
+        let msg = tokio::select! {
+            // The client's first SFTP packet may already be waiting behind it
+            biased;
+            Ok(handler) = &mut sftp_rx, if sftp_pending => {
+                serve_sftp(&mut channel, handler).await;
+                continue;
+            }
+            msg = channel.wait() => msg,
+        };
+        let Some(msg) = msg else {
+            break;
+        };
         log::trace!("Received channel message: {:?}", msg);
         match msg {
             ChannelMsg::Close => {
@@ -1377,3 +3236,123 @@ async fn handle_shell_session(
         }
     }
 }
+
+/// Relay the SFTP subsystem on `channel` to russh-sftp serving `handler` until the
+/// client sends EOF or closes the channel. The server stops once the pipe is dropped,
+/// and `handler` records uploads that were never closed when it goes
+async fn serve_sftp(channel: &mut Channel<Msg>, handler: HoneypotSftpSession) {
+    let (mut pipe, server_end) = tokio::io::duplex(SFTP_RELAY_BUFFER);
+    // Spawns the server and returns
+    russh_sftp::server::run(server_end, handler).await;
+    let mut buffer = vec![0; SFTP_RELAY_BUFFER];
+    loop {
+        tokio::select! {
+            msg = channel.wait() => match msg {
+                Some(ChannelMsg::Data { data }) => {
+                    if pipe.write_all(&data).await.is_err() {
+                        break;
+                    }
+                }
+                Some(ChannelMsg::Eof | ChannelMsg::Close) | None => break,
+                Some(msg) => log::trace!("Received SFTP channel message: {:?}", msg),
+            },
+            read = pipe.read(&mut buffer) => match read {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if let Err(e) = channel.data(&buffer[..n]).await {
+                        log::debug!("Failed to send SFTP response: {}", e);
+                        break;
+                    }
+                }
+            },
+        }
+    }
+    log::debug!("SFTP subsystem on channel {} ended", channel.id());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ssh_honeypot::db::{MemorySink, run_db_handler};
+
+    fn auth(auth_id: &str) -> DbMessage {
+        DbMessage::RecordAuth {
+            auth_id: auth_id.to_string(),
+            timestamp: Utc::now(),
+            ip: "203.0.113.7".to_string(),
+            username: "root".to_string(),
+            auth_type: "password".to_string(),
+            password: Some("123456".to_string()),
+            public_key: None,
+            public_key_algorithm: None,
+            public_key_bits: None,
+            public_key_blob: None,
+            successful: true,
+            abuseipdb_data: None,
+            ipapi_data: None,
+        }
+    }
+
+    fn command(auth_id: &str, command: &str) -> DbMessage {
+        DbMessage::RecordCommand {
+            auth_id: auth_id.to_string(),
+            timestamp: Utc::now(),
+            command: command.to_string(),
+            parsed_command: None,
+            input_bytes: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn auth_records_sent_in_the_background_come_first() {
+        let peer: SocketAddr = "203.0.113.7:40022".parse().unwrap();
+        let (db_tx, db_rx) = mpsc::channel(1);
+        // Another connection filled the channel, the auth records can't wait for it
+        db_tx.send(command("other", "uname -a")).await.unwrap();
+        let mut records = AuthRecords::default();
+        records.send(&db_tx, auth("a1"), peer).await;
+        records.send(&db_tx, auth("a2"), peer).await;
+        assert!(records.backlog.is_some());
+
+        let sink = MemorySink::new();
+        let handler = tokio::spawn(run_db_handler(db_rx, sink.clone()));
+        records.flush().await;
+        db_tx.send(command("a2", "id")).await.unwrap();
+        db_tx.send(DbMessage::Shutdown).await.unwrap();
+        handler.await.unwrap();
+
+        let order: Vec<String> = sink
+            .take()
+            .into_iter()
+            .map(|message| match message {
+                DbMessage::RecordAuth { auth_id, .. } => format!("auth {}", auth_id),
+                DbMessage::RecordCommand {
+                    auth_id, command, ..
+                } => format!("{} {}", auth_id, command),
+                _ => "other message".to_string(),
+            })
+            .collect();
+        assert_eq!(order, ["other uname -a", "auth a1", "auth a2", "a2 id"]);
+    }
+
+    #[test]
+    fn utf8_split_across_packets_is_carried() {
+        // é is C3 A9, the client sent its first byte at the end of a packet
+        assert_eq!(decode_utf8(b"ab\xC3"), Some(("ab", &b"\xC3"[..])));
+        assert_eq!(decode_utf8(b"\xC3\xA9!"), Some(("\u{e9}!", &b""[..])));
+    }
+
+    #[test]
+    fn truncated_utf8_at_the_end_waits_for_the_rest() {
+        // € is E2 82 AC
+        assert_eq!(decode_utf8(b"x\xE2\x82"), Some(("x", &b"\xE2\x82"[..])));
+        assert_eq!(decode_utf8(b"\xE2"), Some(("", &b"\xE2"[..])));
+    }
+
+    #[test]
+    fn invalid_utf8_is_none() {
+        assert_eq!(decode_utf8(b"a\xFFb"), None);
+        // A carried start byte the next packet doesn't continue
+        assert_eq!(decode_utf8(b"\xC3a"), None);
+    }
+}
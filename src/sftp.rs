@@ -4,12 +4,14 @@ use russh_sftp::protocol::{
     Attrs, Data, File, FileAttributes, Handle, Name, OpenFlags, Status, StatusCode, Version,
 };
 use russh_sftp::server::Handler;
-use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::{RwLock, mpsc};
 
+use crate::upload::{UploadStore, UploadStream};
+use crate::yara::YaraRules;
 use ssh_honeypot::db::DbMessage;
+use shell::commands::Canaries;
 use shell::filesystem::fs2::{FileContent, FileSystem};
 
 /*
@@ -31,8 +33,16 @@ pub struct HoneypotSftpSession {
     db_tx: mpsc::Sender<DbMessage>,
     fs: Arc<RwLock<FileSystem>>,
     auth_id: String,
+    canaries: Arc<Canaries>,
+    /// Bytes of a file kept in the filesystem and the database, see `upload`
+    max_upload_size: u64,
+    upload_store: Arc<UploadStore>,
+    /// Rules uploads are scanned with, None without --yara-rules
+    yara_rules: Option<Arc<YaraRules>>,
     /// Active SFTP handles (handle-string → path + type).
     handles: Arc<RwLock<HashMap<String, HandleEntry>>>,
+    /// Files being uploaded, by handle. Recorded when the handle is closed
+    uploads: Arc<RwLock<HashMap<String, UploadStream>>>,
 }
 
 impl HoneypotSftpSession {
@@ -40,12 +50,21 @@ impl HoneypotSftpSession {
         db_tx: mpsc::Sender<DbMessage>,
         fs: Arc<RwLock<FileSystem>>,
         auth_id: String,
+        canaries: Arc<Canaries>,
+        max_upload_size: u64,
+        upload_store: Arc<UploadStore>,
+        yara_rules: Option<Arc<YaraRules>>,
     ) -> Self {
         Self {
             db_tx,
             fs,
             auth_id,
+            canaries,
+            max_upload_size,
+            upload_store,
+            yara_rules,
             handles: Arc::new(RwLock::new(HashMap::new())),
+            uploads: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
@@ -141,6 +160,120 @@ impl HoneypotSftpSession {
 
         (claimed_mime, detected_mime, format_mismatch, entropy)
     }
+
+    /// Database record for a file of `file_size` bytes that was uploaded to
+    /// `filepath`, with the magic/entropy analysis of `data`, the part of it that
+    /// was kept and the YARA `rules` it matches. `data` is handed to `store` on
+    /// the way. SCP uploads are stored the same way
+    pub(crate) fn upload_record(
+        auth_id: String,
+        filepath: String,
+        data: Vec<u8>,
+        file_size: u64,
+        file_hash: String,
+        store: &UploadStore,
+        rules: Option<&YaraRules>,
+    ) -> DbMessage {
+        let filename = filepath
+            .rsplit('/')
+            .next()
+            .unwrap_or(&filepath)
+            .to_string();
+
+        let truncated = file_size > data.len() as u64;
+        if truncated {
+            log::warn!(
+                "Upload to {} is {} bytes, only the first {} were kept",
+                filepath,
+                file_size,
+                data.len()
+            );
+        }
+
+        // Analyze file with magic detection and entropy
+        let (claimed_mime, detected_mime, format_mismatch, file_entropy) =
+            Self::analyze_file(&data, &filepath);
+        let yara_matches = rules.map(|rules| rules.scan(&data)).unwrap_or_default();
+        if let Some(rules) = rules
+            && !yara_matches.is_empty()
+        {
+            log::warn!(
+                "Upload {} ({}) matches YARA rules: {}",
+                filepath,
+                file_hash,
+                yara_matches.join(", ")
+            );
+            rules.alert(&auth_id, &filepath, &file_hash, &yara_matches);
+        }
+        let (stored_path, binary_data) = store.keep(&file_hash, truncated, data);
+
+        DbMessage::RecordFileUpload {
+            auth_id,
+            timestamp: Utc::now(),
+            filename,
+            filepath,
+            file_size,
+            file_hash,
+            claimed_mime_type: claimed_mime,
+            detected_mime_type: detected_mime,
+            format_mismatch,
+            file_entropy,
+            truncated,
+            yara_matches,
+            stored_path,
+            binary_data,
+        }
+    }
+
+    /// Record of the finished upload to `path`, with what the filesystem kept of it
+    fn finished_upload(
+        fs: &FileSystem,
+        auth_id: String,
+        path: String,
+        upload: UploadStream,
+        store: &UploadStore,
+        rules: Option<&YaraRules>,
+    ) -> DbMessage {
+        let data = match fs.get_file(&path).map(|entry| entry.file_content) {
+            Ok(Some(FileContent::RegularFile(bytes))) => {
+                bytes[..bytes.len().min(upload.size() as usize)].to_vec()
+            }
+            _ => Vec::new(),
+        };
+        let file_size = upload.size();
+        let file_hash = upload.finish(&data);
+        Self::upload_record(auth_id, path, data, file_size, file_hash, store, rules)
+    }
+}
+
+/// Clients that drop the connection mid-upload never close their handles, what
+/// they sent so far is recorded here
+impl Drop for HoneypotSftpSession {
+    fn drop(&mut self) {
+        let (Ok(mut uploads), Ok(handles), Ok(fs)) = (
+            self.uploads.try_write(),
+            self.handles.try_read(),
+            self.fs.try_read(),
+        ) else {
+            return;
+        };
+        for (handle, upload) in uploads.drain() {
+            let Some(entry) = handles.get(&handle) else {
+                continue;
+            };
+            let record = Self::finished_upload(
+                &fs,
+                self.auth_id.clone(),
+                entry.path.clone(),
+                upload,
+                &self.upload_store,
+                self.yara_rules.as_deref(),
+            );
+            if let Err(e) = self.db_tx.try_send(record) {
+                log::error!("Failed to queue unfinished file upload record: {}", e);
+            }
+        }
+    }
 }
 
 #[async_trait]
@@ -172,6 +305,11 @@ impl Handler for HoneypotSftpSession {
         let path = path;
         let fs = self.fs.clone();
         let handles = self.handles.clone();
+        let canaries = self.canaries.clone();
+        let db_tx = self.db_tx.clone();
+        let auth_id = self.auth_id.clone();
+        let uploads = self.uploads.clone();
+        let max_upload_size = self.max_upload_size;
 
         async move {
             log::debug!(
@@ -196,6 +334,24 @@ impl Handler for HoneypotSftpSession {
                 // Ensure parent directories exist in filesystem
                 let mut fs_guard = fs.write().await;
                 let _ = fs_guard.create_file(&path);
+                uploads
+                    .write()
+                    .await
+                    .insert(handle.clone(), UploadStream::new(max_upload_size));
+            } else if flags.contains(OpenFlags::READ) {
+                let canary = canaries.lookup(&*fs.read().await, &path);
+                if let Some(canary) = canary {
+                    log::warn!("[{}] Canary file read over SFTP: {}", auth_id, canary);
+                    let hit = DbMessage::RecordCanaryHit {
+                        auth_id,
+                        timestamp: Utc::now(),
+                        path: canary,
+                        command: "sftp open".to_string(),
+                    };
+                    if let Err(e) = db_tx.send(hit).await {
+                        log::error!("Failed to send canary hit to db task: {}", e);
+                    }
+                }
             }
 
             // Register the handle so read/write/close can resolve the path
@@ -218,10 +374,37 @@ impl Handler for HoneypotSftpSession {
     ) -> impl Future<Output = Result<Status, Self::Error>> + Send {
         let handle = handle;
         let handles = self.handles.clone();
+        let uploads = self.uploads.clone();
+        let fs = self.fs.clone();
+        let db_tx = self.db_tx.clone();
+        let auth_id = self.auth_id.clone();
+        let upload_store = self.upload_store.clone();
+        let yara_rules = self.yara_rules.clone();
 
         async move {
             log::debug!("SFTP close request: id={}, handle={}", id, handle);
-            handles.write().await.remove(&handle);
+            let entry = handles.write().await.remove(&handle);
+            let upload = uploads.write().await.remove(&handle);
+
+            // Record in database with enhanced analysis, files opened for writing
+            // but never written to aren't uploads
+            if let (Some(entry), Some(upload)) = (entry, upload)
+                && upload.size() > 0
+            {
+                let record = HoneypotSftpSession::finished_upload(
+                    &*fs.read().await,
+                    auth_id,
+                    entry.path,
+                    upload,
+                    &upload_store,
+                    yara_rules.as_deref(),
+                );
+                match db_tx.send(record).await {
+                    Ok(_) => log::debug!("Successfully queued file upload record"),
+                    Err(e) => log::error!("Failed to queue file upload record: {}", e),
+                }
+            }
+
             Ok(Status {
                 id,
                 status_code: StatusCode::Ok,
@@ -300,8 +483,8 @@ impl Handler for HoneypotSftpSession {
         let handle = handle;
         let fs = self.fs.clone();
         let handles = self.handles.clone();
-        let db_tx = self.db_tx.clone();
-        let auth_id = self.auth_id.clone();
+        let uploads = self.uploads.clone();
+        let max_upload_size = self.max_upload_size;
 
         async move {
             log::info!(
@@ -330,19 +513,34 @@ impl Handler for HoneypotSftpSession {
                 }
             };
 
-            let filename = filepath
-                .rsplit('/')
-                .next()
-                .unwrap_or(&filepath)
-                .to_string();
-
-            // Calculate SHA256 hash
-            let hasher = Sha256::digest(&data);
-            let file_hash = hex::encode(hasher.as_slice());
+            // Out of room on the fake disk, the client gets ENOSPC and nothing is kept
+            let full = {
+                let fs_guard = fs.read().await;
+                let old_len = fs_guard
+                    .get_file(&filepath)
+                    .map_or(0, |entry| entry.inode.i_size_lo as u64);
+                let new_len = old_len.max(offset.saturating_add(data.len() as u64));
+                fs_guard.ensure_space(old_len, new_len).err()
+            };
+            if let Some(err) = full {
+                log::info!("SFTP write to {} refused: {}", filepath, err);
+                return Ok(Status {
+                    id,
+                    status_code: StatusCode::Failure,
+                    error_message: err.to_string(),
+                    language_tag: "".to_string(),
+                });
+            }
 
-            // Analyze file with magic detection and entropy
-            let (claimed_mime, detected_mime, format_mismatch, file_entropy) =
-                HoneypotSftpSession::analyze_file(&data, &filepath);
+            // Only the part below --max-upload-size is stored, the rest is just hashed
+            let (kept, size) = {
+                let mut uploads = uploads.write().await;
+                let upload = uploads
+                    .entry(handle.clone())
+                    .or_insert_with(|| UploadStream::new(max_upload_size));
+                (upload.write(offset, &data), upload.size())
+            };
+            let data = &data[..kept];
 
             // Store / update in filesystem
             {
@@ -356,42 +554,23 @@ impl Handler for HoneypotSftpSession {
                 if let Ok(entry) = fs_guard.get_file_mut(&filepath) {
                     if let Some(FileContent::RegularFile(file_data)) = &mut entry.content {
                         let file_data = Arc::make_mut(file_data);
-                        let required_size = (offset as usize) + data.len();
-                        if file_data.len() < required_size {
-                            file_data.resize(required_size, 0);
+                        if !data.is_empty() {
+                            let required_size = (offset as usize) + data.len();
+                            if file_data.len() < required_size {
+                                file_data.resize(required_size, 0);
+                            }
+                            let start = offset as usize;
+                            let end = start + data.len();
+                            file_data[start..end].copy_from_slice(data);
                         }
-                        let start = offset as usize;
-                        let end = start + data.len();
-                        file_data[start..end].copy_from_slice(&data);
 
-                        entry.inode.i_size_lo = file_data.len() as u32;
+                        // A truncated file still claims its full size
+                        entry.inode.i_size_lo =
+                            (file_data.len() as u64).max(size).min(u32::MAX as u64) as u32;
                     }
                 }
             }
 
-            // Record in database with enhanced analysis
-            let file_size = data.len() as u64;
-
-            match db_tx
-                .send(DbMessage::RecordFileUpload {
-                    auth_id,
-                    timestamp: Utc::now(),
-                    filename,
-                    filepath,
-                    file_size,
-                    file_hash,
-                    claimed_mime_type: claimed_mime,
-                    detected_mime_type: detected_mime,
-                    format_mismatch,
-                    file_entropy,
-                    binary_data: data,
-                })
-                .await
-            {
-                Ok(_) => log::debug!("Successfully queued file upload record"),
-                Err(e) => log::error!("Failed to queue file upload record: {}", e),
-            }
-
             Ok(Status {
                 id,
                 status_code: StatusCode::Ok,
@@ -966,6 +1145,7 @@ impl Handler for HoneypotSftpSession {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use sha2::{Digest, Sha256};
     use shell::filesystem::fs2::FileSystem;
 
     // ═══════════════════════════════════════════════════════════════
@@ -977,7 +1157,15 @@ mod tests {
     ) -> (HoneypotSftpSession, mpsc::Receiver<DbMessage>) {
         let (db_tx, db_rx) = mpsc::channel(32);
         let fs = Arc::new(RwLock::new(fs));
-        let session = HoneypotSftpSession::new(db_tx, fs, "test-auth-id".to_string());
+        let session = HoneypotSftpSession::new(
+            db_tx,
+            fs,
+            "test-auth-id".to_string(),
+            Arc::new(Canaries::default()),
+            1024,
+            Arc::new(UploadStore::new(None, true)),
+            None,
+        );
         (session, db_rx)
     }
 
@@ -1144,6 +1332,33 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_open_canary_for_reading_records_hit() {
+        let canaries = Canaries::new(["/root/.aws/credentials".to_string()]);
+        let mut fs = FileSystem::default();
+        canaries.plant(&mut fs).unwrap();
+        let (mut session, mut rx) = create_test_session(fs);
+        session.canaries = Arc::new(canaries);
+
+        session
+            .open(
+                1,
+                "/root/.aws/credentials".to_string(),
+                OpenFlags::READ,
+                FileAttributes::default(),
+            )
+            .await
+            .unwrap();
+
+        match rx.try_recv().expect("canary hit should be recorded") {
+            DbMessage::RecordCanaryHit { path, command, .. } => {
+                assert_eq!(path, "/root/.aws/credentials");
+                assert_eq!(command, "sftp open");
+            }
+            other => panic!("expected RecordCanaryHit, got {:?}", other),
+        }
+    }
+
     #[tokio::test]
     async fn test_open_registers_handle_with_path() {
         let (mut session, _rx) = create_session_with_tmp();
@@ -1238,6 +1453,37 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_write_to_full_disk_fails() {
+        let (mut session, _rx) = create_session_with_tmp();
+        session.fs.write().await.set_disk_size(Some(64 * 1024));
+
+        let handle = session
+            .open(
+                1,
+                "/tmp/fill.bin".to_string(),
+                OpenFlags::WRITE | OpenFlags::CREATE,
+                FileAttributes::default(),
+            )
+            .await
+            .unwrap();
+        let status = session
+            .write(2, handle.handle.clone(), 0, vec![0; 56 * 1024])
+            .await
+            .unwrap();
+        assert_eq!(status.status_code, StatusCode::Ok);
+
+        let status = session
+            .write(3, handle.handle, 56 * 1024, b"x".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(status.status_code, StatusCode::Failure);
+        assert_eq!(status.error_message, "No space left on device");
+        let fs_guard = session.fs.read().await;
+        let file = fs_guard.get_file("/tmp/fill.bin").unwrap();
+        assert_eq!(file.inode.i_size_lo, 56 * 1024);
+    }
+
     #[tokio::test]
     async fn test_write_appends_at_offset() {
         let (mut session, _rx) = create_session_with_tmp();
@@ -1276,7 +1522,7 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_write_sends_db_record_file_upload() {
+    async fn test_close_sends_db_record_file_upload() {
         let (mut session, mut db_rx) = create_session_with_tmp();
 
         let handle = session
@@ -1291,13 +1537,14 @@ mod tests {
 
         let data = b"hello db".to_vec();
         session
-            .write(2, handle.handle, 0, data.clone())
+            .write(2, handle.handle.clone(), 0, data.clone())
             .await
             .unwrap();
+        session.close(3, handle.handle).await.unwrap();
 
         let msg = db_rx
             .try_recv()
-            .expect("write should queue a DbMessage::RecordFileUpload");
+            .expect("close should queue a DbMessage::RecordFileUpload");
         match msg {
             DbMessage::RecordFileUpload {
                 filename,
@@ -1329,9 +1576,10 @@ mod tests {
 
         let data = b"hash me".to_vec();
         session
-            .write(2, handle.handle, 0, data.clone())
+            .write(2, handle.handle.clone(), 0, data.clone())
             .await
             .unwrap();
+        session.close(3, handle.handle).await.unwrap();
 
         let expected = hex::encode(Sha256::digest(&data));
 
@@ -1359,18 +1607,63 @@ mod tests {
 
         let data: Vec<u8> = (0..=255).collect();
         session
-            .write(2, handle.handle, 0, data.clone())
+            .write(2, handle.handle.clone(), 0, data.clone())
             .await
             .unwrap();
+        session.close(3, handle.handle).await.unwrap();
 
         match db_rx.try_recv().unwrap() {
             DbMessage::RecordFileUpload { binary_data, .. } => {
-                assert_eq!(binary_data, data);
+                assert_eq!(binary_data, Some(data));
             }
             other => panic!("expected RecordFileUpload, got {:?}", other),
         }
     }
 
+    #[tokio::test]
+    async fn test_upload_over_the_limit_is_truncated_but_fully_hashed() {
+        let (mut session, mut db_rx) = create_session_with_tmp();
+
+        let handle = session
+            .open(
+                1,
+                "/tmp/big".to_string(),
+                OpenFlags::WRITE | OpenFlags::CREATE,
+                FileAttributes::default(),
+            )
+            .await
+            .unwrap();
+
+        let data: Vec<u8> = (0..2000).map(|i| (i % 251) as u8).collect();
+        for (i, chunk) in data.chunks(700).enumerate() {
+            session
+                .write(2, handle.handle.clone(), i as u64 * 700, chunk.to_vec())
+                .await
+                .unwrap();
+        }
+        assert!(db_rx.try_recv().is_err(), "nothing is recorded before close");
+        session.close(3, handle.handle).await.unwrap();
+
+        match db_rx.try_recv().unwrap() {
+            DbMessage::RecordFileUpload {
+                file_size,
+                file_hash,
+                truncated,
+                binary_data,
+                ..
+            } => {
+                assert_eq!(file_size, 2000);
+                assert!(truncated);
+                assert_eq!(binary_data.as_deref(), Some(&data[..1024]));
+                assert_eq!(file_hash, hex::encode(Sha256::digest(&data)));
+            }
+            other => panic!("expected RecordFileUpload, got {:?}", other),
+        }
+        let fs = session.fs.read().await;
+        let entry = fs.get_file("/tmp/big").unwrap();
+        assert_eq!(entry.inode.i_size_lo, 2000);
+    }
+
     // ═══════════════════════════════════════════════════════════════
     //  read  (desired-state: returns actual VFS file content)
     // ═══════════════════════════════════════════════════════════════
@@ -2457,4 +2750,57 @@ mod tests {
         assert_eq!(result.files.len(), 1);
         assert_eq!(result.files[0].filename, "/home/original.txt");
     }
+
+    // ═══════════════════════════════════════════════════════════════
+    //  Integration: an SFTP client uploading over the protocol
+    // ═══════════════════════════════════════════════════════════════
+
+    #[tokio::test]
+    async fn test_client_upload_is_stored_and_recorded() {
+        use tokio::io::AsyncWriteExt;
+
+        let (session, mut db_rx) = create_session_with_tmp();
+        let fs = session.fs.clone();
+        // Stands in for the SSH channel the server relays
+        let (client_end, server_end) = tokio::io::duplex(64 * 1024);
+        russh_sftp::server::run(server_end, session).await;
+        let client = russh_sftp::client::SftpSession::new(client_end)
+            .await
+            .unwrap();
+
+        let mut file = client.create("/tmp/payload.sh").await.unwrap();
+        file.write_all(b"#!/bin/sh\nid\n").await.unwrap();
+        file.shutdown().await.unwrap();
+
+        let record = tokio::time::timeout(std::time::Duration::from_secs(5), db_rx.recv())
+            .await
+            .expect("the upload should be recorded")
+            .unwrap();
+        match record {
+            DbMessage::RecordFileUpload {
+                filepath,
+                file_size,
+                binary_data,
+                ..
+            } => {
+                assert_eq!(filepath, "/tmp/payload.sh");
+                assert_eq!(file_size, 13);
+                assert_eq!(binary_data.as_deref(), Some(&b"#!/bin/sh\nid\n"[..]));
+            }
+            other => panic!("expected RecordFileUpload, got {:?}", other),
+        }
+        let stored = fs.read().await.get_file("/tmp/payload.sh").unwrap();
+        match &stored.file_content {
+            Some(FileContent::RegularFile(data)) => {
+                assert_eq!(data.as_slice(), b"#!/bin/sh\nid\n")
+            }
+            _ => panic!("expected a regular file"),
+        }
+
+        assert_eq!(
+            client.read("/tmp/payload.sh").await.unwrap(),
+            b"#!/bin/sh\nid\n"
+        );
+        client.close().await.unwrap();
+    }
 }
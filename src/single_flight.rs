@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex as StdMutex};
+use tokio::sync::OnceCell;
+
+/// Coalesces concurrent lookups of the same key: the first caller runs its lookup,
+/// everyone arriving while it runs waits for that result instead of starting their own.
+/// Used so a burst of connections from one IP costs a single API call
+pub(crate) struct SingleFlight<T> {
+    /// Lookups currently running. The cell holds the shared result, None if the lookup failed
+    in_flight: StdMutex<HashMap<String, Arc<OnceCell<Option<T>>>>>,
+}
+
+impl<T: Clone> SingleFlight<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            in_flight: StdMutex::new(HashMap::new()),
+        }
+    }
+
+    /// Run `lookup` for `key`, or wait for the one already running. The caller that
+    /// ran the lookup gets its error as `Err(Some(_))`, callers that waited for a
+    /// failed lookup get `Err(None)`. If the running lookup is cancelled, one of the
+    /// waiters runs its own `lookup` instead
+    pub(crate) async fn run<E, F>(&self, key: &str, lookup: F) -> Result<T, Option<E>>
+    where
+        F: Future<Output = Result<T, E>>,
+    {
+        let cell = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            in_flight
+                .entry(key.to_string())
+                .or_insert_with(|| Arc::new(OnceCell::new()))
+                .clone()
+        };
+
+        let mut own = None;
+        let shared = {
+            let own = &mut own;
+            cell.get_or_init(|| async move {
+                let result = lookup.await;
+                let shared = result.as_ref().ok().cloned();
+                *own = Some(result);
+                shared
+            })
+            .await
+            .clone()
+        };
+
+        // The next lookup for this key starts fresh, results live in the caches
+        {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            if in_flight
+                .get(key)
+                .is_some_and(|current| Arc::ptr_eq(current, &cell))
+            {
+                in_flight.remove(key);
+            }
+        }
+
+        match own {
+            Some(result) => result.map_err(Some),
+            None => shared.ok_or(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn concurrent_lookups_share_one_call() {
+        let flight = Arc::new(SingleFlight::<u32>::new());
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let lookups = (0..8).map(|_| {
+            let flight = flight.clone();
+            let calls = calls.clone();
+            tokio::spawn(async move {
+                flight
+                    .run("203.0.113.5", async {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        Ok::<_, String>(42)
+                    })
+                    .await
+            })
+        });
+        for lookup in lookups.collect::<Vec<_>>() {
+            assert_eq!(lookup.await.unwrap(), Ok(42));
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Once done, the next lookup runs again
+        let again = flight
+            .run("203.0.113.5", async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Ok::<_, String>(7)
+            })
+            .await;
+        assert_eq!(again, Ok(7));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn failures_reach_waiters() {
+        let flight = Arc::new(SingleFlight::<u32>::new());
+        let leader = {
+            let flight = flight.clone();
+            tokio::spawn(async move {
+                flight
+                    .run("198.51.100.7", async {
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        Err("rate limited".to_string())
+                    })
+                    .await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let waiter = flight
+            .run("198.51.100.7", async { Ok::<_, String>(1) })
+            .await;
+
+        assert_eq!(waiter, Err(None));
+        assert_eq!(leader.await.unwrap(), Err(Some("rate limited".to_string())));
+    }
+}
@@ -0,0 +1,191 @@
+//! Bookkeeping for files arriving over SFTP or SCP.
+//!
+//! Uploads are hostile by design, so only the first `--max-upload-size` bytes of a
+//! file are kept (in the virtual filesystem and in the database). Everything past
+//! that is still hashed and counted on the way through, the record then carries the
+//! real size and is flagged as truncated.
+//!
+//! What was kept goes to the database, to a content-addressed directory
+//! (`<dir>/<sha256>`) for sandboxes and YARA, or both.
+
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+
+/// Default for `--max-upload-size`, 64 MiB
+pub const DEFAULT_MAX_UPLOAD_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Running size and SHA256 of one file being uploaded
+#[derive(Clone, Debug)]
+pub struct UploadStream {
+    limit: u64,
+    size: u64,
+    hasher: Sha256,
+    /// Every write continued where the previous one ended, so `hasher` saw the
+    /// whole file in order
+    in_order: bool,
+}
+
+impl UploadStream {
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            size: 0,
+            hasher: Sha256::new(),
+            in_order: true,
+        }
+    }
+
+    /// Account for `chunk` written at `offset`. Returns how many of its leading
+    /// bytes lie below the limit and should be kept
+    pub fn write(&mut self, offset: u64, chunk: &[u8]) -> usize {
+        if self.in_order && offset == self.size {
+            self.hasher.update(chunk);
+        } else {
+            self.in_order = false;
+        }
+        self.size = self.size.max(offset + chunk.len() as u64);
+        self.limit.saturating_sub(offset).min(chunk.len() as u64) as usize
+    }
+
+    /// Size of the file as the client sent it
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn truncated(&self) -> bool {
+        self.size > self.limit
+    }
+
+    /// Hex SHA256 of the whole upload. `kept` is what was kept of the file, it is
+    /// hashed instead when the client wrote out of order (SFTP allows that). Of a
+    /// truncated file that's only the hash of its beginning
+    pub fn finish(self, kept: &[u8]) -> String {
+        if self.in_order {
+            return hex::encode(self.hasher.finalize());
+        }
+        if self.truncated() {
+            log::warn!(
+                "Upload of {} bytes was written out of order and truncated, its hash only covers the first {} bytes",
+                self.size,
+                kept.len()
+            );
+        }
+        hex::encode(Sha256::digest(kept))
+    }
+}
+
+/// Where the kept bytes of uploaded files end up
+#[derive(Clone, Debug)]
+pub struct UploadStore {
+    /// Files are written to `<dir>/<sha256>`, None to not write them to disk
+    dir: Option<PathBuf>,
+    /// Also store the bytes in uploaded_files.binary_data
+    in_db: bool,
+}
+
+impl UploadStore {
+    pub fn new(dir: Option<PathBuf>, in_db: bool) -> Self {
+        Self { dir, in_db }
+    }
+
+    pub fn dir(&self) -> Option<&std::path::Path> {
+        self.dir.as_deref()
+    }
+
+    pub fn in_db(&self) -> bool {
+        self.in_db
+    }
+
+    /// Write `data` to the store directory as `file_hash`, a file that is already
+    /// there is the same file. The name of a truncated file gets a `.partial`
+    /// suffix since its content doesn't match the hash. Returns the path written
+    /// to and the bytes to put into the database. When writing fails the bytes go
+    /// to the database regardless, so nothing is lost
+    pub fn keep(
+        &self,
+        file_hash: &str,
+        truncated: bool,
+        data: Vec<u8>,
+    ) -> (Option<String>, Option<Vec<u8>>) {
+        let Some(dir) = &self.dir else {
+            return (None, Some(data));
+        };
+        let name = if truncated {
+            format!("{}.partial", file_hash)
+        } else {
+            file_hash.to_string()
+        };
+        let path = dir.join(&name);
+        if !path.exists() {
+            // Written under a temporary name first so the store never holds half a file
+            let partial = dir.join(format!(".{}.tmp", name));
+            if let Err(err) =
+                std::fs::write(&partial, &data).and_then(|_| std::fs::rename(&partial, &path))
+            {
+                log::error!("Failed to store upload in {}: {}", path.display(), err);
+                let _ = std::fs::remove_file(&partial);
+                return (None, Some(data));
+            }
+            log::debug!("Stored upload as {}", path.display());
+        }
+        let stored_path = path.display().to_string();
+        (Some(stored_path), self.in_db.then_some(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_past_the_limit() {
+        let mut upload = UploadStream::new(4);
+        assert_eq!(upload.write(0, b"abc"), 3);
+        assert_eq!(upload.write(3, b"defg"), 1);
+        assert_eq!(upload.write(7, b"h"), 0);
+        assert_eq!(upload.size(), 8);
+        assert!(upload.truncated());
+        assert_eq!(
+            upload.finish(b"abcd"),
+            hex::encode(Sha256::digest(b"abcdefgh"))
+        );
+    }
+
+    #[test]
+    fn out_of_order_writes_hash_what_was_kept() {
+        let mut upload = UploadStream::new(1024);
+        upload.write(4, b"efgh");
+        upload.write(0, b"abcd");
+        assert_eq!(upload.size(), 8);
+        assert!(!upload.truncated());
+        assert_eq!(
+            upload.finish(b"abcdefgh"),
+            hex::encode(Sha256::digest(b"abcdefgh"))
+        );
+    }
+
+    #[test]
+    fn stores_by_hash() {
+        let dir = std::env::temp_dir().join(format!("upload-store-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let store = UploadStore::new(Some(dir.clone()), false);
+
+        let (path, blob) = store.keep("abc123", false, b"payload".to_vec());
+        assert_eq!(path, Some(dir.join("abc123").display().to_string()));
+        assert_eq!(blob, None);
+        assert_eq!(std::fs::read(dir.join("abc123")).unwrap(), b"payload");
+        let (path, _) = store.keep("abc123", true, b"pay".to_vec());
+        assert_eq!(path, Some(dir.join("abc123.partial").display().to_string()));
+
+        let missing = UploadStore::new(Some(dir.join("missing")), false);
+        assert_eq!(
+            missing.keep("abc123", false, b"payload".to_vec()),
+            (None, Some(b"payload".to_vec()))
+        );
+        assert_eq!(
+            UploadStore::new(None, true).keep("abc123", false, b"x".to_vec()),
+            (None, Some(b"x".to_vec()))
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,161 @@
+//! Research profiles that make a listener look vulnerable to a well-known SSH
+//! issue.
+//!
+//! A profile changes what a scanner can see before authenticating, the server
+//! id, the offered key exchange algorithms and the banner, to match a version
+//! with a public exploit. Nothing actually becomes vulnerable. Clients that go
+//! after the profile's weakness are recorded in `protocol_anomalies` with kind
+//! [`PROBE_KIND`], so the exploit tooling can be fingerprinted.
+
+use crate::hassh::ClientKexInit;
+use russh::kex;
+use serde::{Deserialize, Serialize};
+
+/// `protocol_anomalies.kind` of a client targeting the profile's weakness
+pub const PROBE_KIND: &str = "vulnerable_profile_probe";
+
+#[derive(clap::ValueEnum, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VulnerableProfile {
+    /// OpenSSH 7.2p2, usernames can be enumerated with malformed publickey
+    /// requests (CVE-2018-15473)
+    Userenum,
+    /// libssh 0.7.6 server, a client sending USERAUTH_SUCCESS instead of
+    /// authenticating is let in (CVE-2018-10933)
+    Libssh,
+    /// OpenSSH 6.6.1p1 preferring 1024-bit SHA1 Diffie-Hellman groups (Logjam, CVE-2015-4000)
+    Logjam,
+}
+
+impl VulnerableProfile {
+    pub fn cve(self) -> &'static str {
+        match self {
+            VulnerableProfile::Userenum => "CVE-2018-15473",
+            VulnerableProfile::Libssh => "CVE-2018-10933",
+            VulnerableProfile::Logjam => "CVE-2015-4000",
+        }
+    }
+
+    /// Identification string of a vulnerable version
+    pub fn server_id(self) -> &'static str {
+        match self {
+            VulnerableProfile::Userenum => "SSH-2.0-OpenSSH_7.2p2 Ubuntu-4ubuntu2.8",
+            VulnerableProfile::Libssh => "SSH-2.0-libssh_0.7.6",
+            VulnerableProfile::Logjam => "SSH-2.0-OpenSSH_6.6.1p1 Ubuntu-2ubuntu2.13",
+        }
+    }
+
+    /// Banner of the kind of machine that usually runs the version, used unless
+    /// one is configured
+    pub fn authentication_banner(self) -> Option<&'static str> {
+        match self {
+            VulnerableProfile::Userenum => None,
+            // libssh servers are mostly network appliances
+            VulnerableProfile::Libssh => {
+                Some("Unauthorized access to this device is prohibited!\r\n")
+            }
+            VulnerableProfile::Logjam => Some(
+                "WARNING: Authorized users only. All activity may be monitored and reported.\r\n",
+            ),
+        }
+    }
+
+    /// Key exchange algorithms the version offers, in its order
+    pub fn kex(self) -> &'static [kex::Name] {
+        match self {
+            VulnerableProfile::Userenum => &[
+                kex::CURVE25519_PRE_RFC_8731,
+                kex::DH_GEX_SHA256,
+                kex::DH_G14_SHA1,
+                kex::EXTENSION_SUPPORT_AS_SERVER,
+            ],
+            VulnerableProfile::Libssh => &[
+                kex::CURVE25519_PRE_RFC_8731,
+                kex::DH_G14_SHA1,
+                kex::DH_G1_SHA1,
+            ],
+            VulnerableProfile::Logjam => &[
+                kex::DH_G1_SHA1,
+                kex::DH_GEX_SHA1,
+                kex::DH_G14_SHA1,
+                kex::CURVE25519_PRE_RFC_8731,
+                kex::DH_GEX_SHA256,
+            ],
+        }
+    }
+
+    /// Why the client's KEXINIT gives it away as going after the weakness, None
+    /// if it doesn't
+    pub fn targeted_by_kex_init(self, kex_init: &ClientKexInit) -> Option<String> {
+        match self {
+            // Downgrade tooling asks for the weak group first, real clients
+            // only fall back to it
+            VulnerableProfile::Logjam => {
+                let preferred = kex_init.kex_algorithms.split(',').next()?;
+                matches!(
+                    preferred,
+                    "diffie-hellman-group1-sha1" | "diffie-hellman-group-exchange-sha1"
+                )
+                .then(|| format!("client prefers {}", preferred))
+            }
+            VulnerableProfile::Userenum | VulnerableProfile::Libssh => None,
+        }
+    }
+
+    /// Whether a protocol anomaly (kind as in `protocol_anomalies`) is what the
+    /// public exploits for the weakness cause
+    pub fn targeted_by_anomaly(self, kind: &str) -> bool {
+        match self {
+            // The enumeration sends publickey requests cut short after the username
+            VulnerableProfile::Userenum => matches!(kind, "malformed_packet" | "invalid_length"),
+            // russh refuses the stray USERAUTH_SUCCESS as out of protocol
+            VulnerableProfile::Libssh => kind == "protocol_violation",
+            VulnerableProfile::Logjam => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kex_init(kex_algorithms: &str) -> ClientKexInit {
+        ClientKexInit {
+            client_version: "SSH-2.0-Go".to_string(),
+            kex_algorithms: kex_algorithms.to_string(),
+            server_host_key_algorithms: "ssh-rsa".to_string(),
+            encryption_algorithms: "aes128-ctr".to_string(),
+            mac_algorithms: "hmac-sha1".to_string(),
+            compression_algorithms: "none".to_string(),
+        }
+    }
+
+    #[test]
+    fn logjam_flags_clients_preferring_weak_groups() {
+        let profile = VulnerableProfile::Logjam;
+        assert_eq!(
+            profile.targeted_by_kex_init(&kex_init(
+                "diffie-hellman-group1-sha1,diffie-hellman-group14-sha1"
+            )),
+            Some("client prefers diffie-hellman-group1-sha1".to_string())
+        );
+        assert_eq!(
+            profile.targeted_by_kex_init(&kex_init(
+                "curve25519-sha256,diffie-hellman-group1-sha1"
+            )),
+            None
+        );
+    }
+
+    #[test]
+    fn every_profile_looks_like_ssh() {
+        for profile in [
+            VulnerableProfile::Userenum,
+            VulnerableProfile::Libssh,
+            VulnerableProfile::Logjam,
+        ] {
+            assert!(profile.server_id().starts_with("SSH-2.0-"));
+            assert!(!profile.kex().is_empty());
+        }
+    }
+}
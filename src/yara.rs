@@ -0,0 +1,185 @@
+//! YARA scanning of uploaded files, compiled in with the `yara` cargo feature.
+//!
+//! `--yara-rules` points at a rule file or a directory of `.yar`/`.yara` files. They
+//! are compiled once at startup, every upload is scanned after the magic/entropy
+//! analysis and the names of the matching rules are stored with it. With
+//! `--yara-webhook` every match is also POSTed as JSON to that URL.
+
+use std::path::Path;
+
+/// Compiled rules, shared by all sessions
+#[cfg_attr(not(feature = "yara"), allow(dead_code))]
+pub struct YaraRules {
+    #[cfg(feature = "yara")]
+    rules: yara_x::Rules,
+    /// Number of rule files compiled, for the startup log
+    files: usize,
+    /// Client and URL matches are POSTed to
+    webhook: Option<(reqwest::Client, String)>,
+}
+
+impl std::fmt::Debug for YaraRules {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("YaraRules")
+            .field("files", &self.files)
+            .field("webhook", &self.webhook.as_ref().map(|(_, url)| url))
+            .finish_non_exhaustive()
+    }
+}
+
+impl YaraRules {
+    /// Compile the rule file at `path`, or every `.yar`/`.yara` file in the directory
+    /// at `path`. The error names the file and line of the first rule that doesn't compile
+    #[cfg(feature = "yara")]
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let files = rule_files(path)?;
+        let mut compiler = yara_x::Compiler::new();
+        for file in &files {
+            let source = std::fs::read_to_string(file)
+                .map_err(|err| format!("Cannot read YARA rules {}: {}", file.display(), err))?;
+            let origin = file.display().to_string();
+            compiler
+                .add_source(yara_x::SourceCode::from(source.as_str()).with_origin(&origin))
+                .map_err(|err| format!("Invalid YARA rules in {}:\n{}", origin, err))?;
+        }
+        Ok(Self {
+            rules: compiler.build(),
+            files: files.len(),
+            webhook: None,
+        })
+    }
+
+    #[cfg(not(feature = "yara"))]
+    pub fn load(path: &Path) -> Result<Self, String> {
+        Err(format!(
+            "--yara-rules {} needs YARA support, rebuild with `cargo build --release --features yara`",
+            path.display()
+        ))
+    }
+
+    pub fn files(&self) -> usize {
+        self.files
+    }
+
+    /// POST every match to `url` from now on
+    pub fn with_webhook(mut self, url: String) -> Self {
+        let certs = webpki_root_certs::TLS_SERVER_ROOT_CERTS
+            .iter()
+            .map(|cert| reqwest::Certificate::from_der(cert).unwrap())
+            .collect::<Vec<_>>();
+        let client = reqwest::Client::builder()
+            .min_tls_version(reqwest::tls::Version::TLS_1_2)
+            .tls_certs_only(certs)
+            .timeout(std::time::Duration::from_secs(10))
+            .build()
+            .unwrap();
+        self.webhook = Some((client, url));
+        self
+    }
+
+    /// Send a match to the webhook in the background, if there is one. Failures are
+    /// only logged, the upload is recorded either way
+    pub fn alert(&self, auth_id: &str, filepath: &str, file_hash: &str, matches: &[String]) {
+        let Some((client, url)) = self.webhook.clone() else {
+            return;
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            log::warn!("No runtime to send the YARA alert for {} on", file_hash);
+            return;
+        };
+        let body = serde_json::json!({
+            "auth_id": auth_id,
+            "filepath": filepath,
+            "file_hash": file_hash,
+            "rules": matches,
+        });
+        runtime.spawn(async move {
+            match client.post(&url).json(&body).send().await {
+                Ok(response) if response.status().is_success() => {
+                    log::debug!("Sent YARA alert to {}", url)
+                }
+                Ok(response) => log::warn!(
+                    "YARA webhook {} answered {}",
+                    url,
+                    response.status()
+                ),
+                Err(err) => log::warn!("Failed to send YARA alert to {}: {}", url, err),
+            }
+        });
+    }
+
+    /// Names of the rules matching `data`, empty when nothing matches or the scan fails
+    #[cfg(feature = "yara")]
+    pub fn scan(&self, data: &[u8]) -> Vec<String> {
+        let mut scanner = yara_x::Scanner::new(&self.rules);
+        match scanner.scan(data) {
+            Ok(results) => results
+                .matching_rules()
+                .map(|rule| rule.identifier().to_string())
+                .collect(),
+            Err(err) => {
+                log::error!("YARA scan failed: {}", err);
+                Vec::new()
+            }
+        }
+    }
+
+    #[cfg(not(feature = "yara"))]
+    pub fn scan(&self, _data: &[u8]) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// `path` itself when it's a file, otherwise its `.yar` and `.yara` files in name order
+#[cfg_attr(not(feature = "yara"), allow(dead_code))]
+fn rule_files(path: &Path) -> Result<Vec<std::path::PathBuf>, String> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let entries = std::fs::read_dir(path)
+        .map_err(|err| format!("Cannot read YARA rules directory {}: {}", path.display(), err))?;
+    let mut files: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|file| {
+            file.is_file()
+                && file
+                    .extension()
+                    .is_some_and(|extension| extension == "yar" || extension == "yara")
+        })
+        .collect();
+    files.sort();
+    if files.is_empty() {
+        return Err(format!(
+            "No .yar or .yara files in YARA rules directory {}",
+            path.display()
+        ));
+    }
+    Ok(files)
+}
+
+#[cfg(all(test, feature = "yara"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compiles_and_matches_rules() {
+        let dir = std::env::temp_dir().join(format!("yara-rules-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("miner.yar"),
+            "rule xmrig { strings: $a = \"stratum+tcp://\" condition: $a }",
+        )
+        .unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a rule").unwrap();
+
+        let rules = YaraRules::load(&dir).unwrap();
+        assert_eq!(rules.files(), 1);
+        assert_eq!(rules.scan(b"-o stratum+tcp://pool:3333"), ["xmrig"]);
+        assert!(rules.scan(b"#!/bin/sh\n").is_empty());
+
+        std::fs::write(dir.join("broken.yara"), "rule broken { condition: $x }").unwrap();
+        let err = YaraRules::load(&dir).unwrap_err();
+        assert!(err.contains("broken.yara"), "{}", err);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}